@@ -0,0 +1,506 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use anyhow::{bail, Error};
+use bytes::Bytes;
+use context::PerfCounterType;
+use futures::{FutureExt as _, TryFutureExt};
+use futures_ext::{BoxFuture, FutureExt};
+use futures_old::IntoFuture;
+use lazy_static::lazy_static;
+use mononoke_types::hash::Context;
+use tempfile::NamedTempFile;
+use tokio::task;
+
+use blobstore::{Blobstore, BlobstoreGetData, CountedBlobstore};
+
+use crate::dummy::DummyLease;
+use crate::in_process_lease::InProcessLease;
+use crate::locking_cache::{CacheBlobstore, CacheOps};
+
+const BLOBS_DIR: &str = "blobs";
+const TMP_DIR: &str = "tmp";
+const MAGIC: &[u8; 4] = b"MDC1";
+// Every entry is accounted as taking at least this much space, so that presence markers
+// (which are empty files) still count towards the size limit.
+const MIN_ENTRY_SIZE: u64 = 4096;
+
+lazy_static! {
+    // The caches open in this process, by directory. Every blobstore configured with the same
+    // directory uses the same cache, so that they share one size limit and one temporary
+    // directory.
+    static ref OPEN_CACHES: Mutex<HashMap<PathBuf, DiskCacheOps>> = Mutex::new(HashMap::new());
+}
+
+/// Where and how big the disk cache is
+#[derive(Clone, Debug)]
+pub struct DiskCacheOptions {
+    /// Directory to keep the cache in. It can be shared by the blobstores of a process, but
+    /// not between processes.
+    pub path: PathBuf,
+    /// Total size of the cache in bytes. The least recently used entries are evicted to stay
+    /// under it.
+    pub max_size: u64,
+    /// Blobs larger than this are not cached, but are still recorded as present.
+    pub max_blob_size: u64,
+}
+
+/// A caching layer over an existing blobstore, backed by files in a local directory. Entries
+/// are named by a hash of their key, and written to a temporary file and renamed into place, so
+/// a crash never leaves a partially written entry behind.
+#[derive(Clone)]
+pub struct DiskCacheOps {
+    blobs: PathBuf,
+    tmp: PathBuf,
+    max_size: u64,
+    max_blob_size: u64,
+    index: Arc<Mutex<LruIndex>>,
+}
+
+pub fn new_disk_cache_blobstore_no_lease<T>(
+    blobstore: T,
+    options: DiskCacheOptions,
+) -> Result<CountedBlobstore<CacheBlobstore<DiskCacheOps, DummyLease, T>>, Error>
+where
+    T: Blobstore + Clone,
+{
+    let cache_ops = DiskCacheOps::shared(options)?;
+    Ok(CountedBlobstore::new(
+        "diskcache".to_string(),
+        CacheBlobstore::new(cache_ops, DummyLease {}, blobstore),
+    ))
+}
+
+pub fn new_disk_cache_blobstore<T>(
+    blobstore: T,
+    options: DiskCacheOptions,
+) -> Result<CountedBlobstore<CacheBlobstore<DiskCacheOps, InProcessLease, T>>, Error>
+where
+    T: Blobstore + Clone,
+{
+    let cache_ops = DiskCacheOps::shared(options)?;
+    Ok(CountedBlobstore::new(
+        "diskcache".to_string(),
+        CacheBlobstore::new(cache_ops, InProcessLease::new(), blobstore),
+    ))
+}
+
+impl DiskCacheOps {
+    /// The cache in `options.path`, opening it if no other blobstore of this process uses it
+    /// yet. Keys are not prefixed by the cache, so the blobstores sharing it must not use the
+    /// same keys for different blobs, which holds for repo prefixed keys.
+    pub fn shared(options: DiskCacheOptions) -> Result<Self, Error> {
+        fs::create_dir_all(&options.path)?;
+        let path = fs::canonicalize(&options.path)?;
+        let mut caches = OPEN_CACHES.lock().expect("lock poisoned");
+        if let Some(cache) = caches.get(&path) {
+            if cache.max_size != options.max_size || cache.max_blob_size != options.max_blob_size {
+                bail!(
+                    "Disk cache {} is already open with different size limits",
+                    path.display()
+                );
+            }
+            return Ok(cache.clone());
+        }
+        let cache = Self::open(DiskCacheOptions {
+            path: path.clone(),
+            ..options
+        })?;
+        caches.insert(path, cache.clone());
+        Ok(cache)
+    }
+
+    /// Open the cache, creating its directory if needed. Entries left by a previous run are
+    /// kept, ordered by when they were written, and evicted if they no longer fit. Interrupted
+    /// writes are cleaned up, so nothing else may be using the directory: use `shared` unless
+    /// the cache is known to be the only one there.
+    pub fn open(options: DiskCacheOptions) -> Result<Self, Error> {
+        let blobs = options.path.join(BLOBS_DIR);
+        let tmp = options.path.join(TMP_DIR);
+        fs::create_dir_all(&blobs)?;
+        // Anything in here is a write that was interrupted.
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp)?;
+        }
+        fs::create_dir_all(&tmp)?;
+
+        let mut existing = Vec::new();
+        for shard in fs::read_dir(&blobs)? {
+            let shard = shard?;
+            if !shard.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(shard.path())? {
+                let entry = entry?;
+                let meta = entry.metadata()?;
+                let name = match entry.file_name().into_string() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                existing.push((mtime, name, meta.len()));
+            }
+        }
+        existing.sort();
+
+        let mut index = LruIndex::new(options.max_size);
+        let mut evicted = Vec::new();
+        for (_, name, size) in existing {
+            evicted.extend(index.insert(name, size));
+        }
+        remove_entries(&blobs, evicted);
+
+        Ok(Self {
+            blobs,
+            tmp,
+            max_size: options.max_size,
+            max_blob_size: options.max_blob_size,
+            index: Arc::new(Mutex::new(index)),
+        })
+    }
+
+    fn get_blocking(&self, key: &str) -> Result<Option<BlobstoreGetData>, Error> {
+        let name = entry_name(key);
+        let path = entry_path(&self.blobs, &name);
+        let mut data = Vec::new();
+        match File::open(&path) {
+            Ok(mut file) => file.read_to_end(&mut data)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                self.index.lock().expect("lock poisoned").remove(&name);
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if data.is_empty() {
+            // Presence marker
+            self.index.lock().expect("lock poisoned").touch(&name);
+            return Ok(None);
+        }
+
+        match decode_entry(key, Bytes::from(data)) {
+            Some(value) => {
+                self.index.lock().expect("lock poisoned").touch(&name);
+                Ok(Some(value))
+            }
+            None => {
+                // Corrupt, or a hash collision: either way, this entry is no use.
+                self.index.lock().expect("lock poisoned").remove(&name);
+                let _ = fs::remove_file(&path);
+                Ok(None)
+            }
+        }
+    }
+
+    fn put_blocking(&self, key: &str, value: BlobstoreGetData) -> Result<(), Error> {
+        let name = entry_name(key);
+        let path = entry_path(&self.blobs, &name);
+        let encoded = value
+            .encode()
+            .map_err(|()| Error::msg("failed to encode blob"))?;
+        let entry_size = (MAGIC.len() + 4 + key.len() + encoded.len()) as u64;
+
+        let mut tempfile = NamedTempFile::new_in(&self.tmp)?;
+        let size = if entry_size > self.max_blob_size {
+            // Too big to cache, just record that it exists
+            0
+        } else {
+            let file = tempfile.as_file_mut();
+            file.write_all(MAGIC)?;
+            file.write_all(&(key.len() as u32).to_be_bytes())?;
+            file.write_all(key.as_bytes())?;
+            file.write_all(encoded.as_ref())?;
+            entry_size
+        };
+        tempfile.as_file().sync_all()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        tempfile.persist(&path)?;
+
+        let evicted = self.index.lock().expect("lock poisoned").insert(name, size);
+        remove_entries(&self.blobs, evicted);
+        Ok(())
+    }
+}
+
+impl CacheOps for DiskCacheOps {
+    const HIT_COUNTER: Option<PerfCounterType> = Some(PerfCounterType::DiskCacheHits);
+    const MISS_COUNTER: Option<PerfCounterType> = Some(PerfCounterType::DiskCacheMisses);
+
+    fn get(&self, key: &str) -> BoxFuture<Option<BlobstoreGetData>, ()> {
+        let this = self.clone();
+        let key = key.to_string();
+        task::spawn_blocking(move || this.get_blocking(&key))
+            .map(|result| result.map_err(|_| ())?.map_err(|_| ()))
+            .boxed()
+            .compat()
+            .boxify()
+    }
+
+    fn put(&self, key: &str, value: BlobstoreGetData) -> BoxFuture<(), ()> {
+        let this = self.clone();
+        let key = key.to_string();
+        task::spawn_blocking(move || this.put_blocking(&key, value))
+            .map(|result| result.map_err(|_| ())?.map_err(|_| ()))
+            .boxed()
+            .compat()
+            .boxify()
+    }
+
+    /// Ask the cache if it knows whether the backing store has a value for this key. Returns
+    /// `true` if there is definitely a value (i.e. cache entry in Present or Known state), `false`
+    /// otherwise (Empty or Leased states).
+    fn check_present(&self, key: &str) -> BoxFuture<bool, ()> {
+        let present = self
+            .index
+            .lock()
+            .expect("lock poisoned")
+            .contains(&entry_name(key));
+        Ok(present).into_future().boxify()
+    }
+}
+
+impl fmt::Debug for DiskCacheOps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let index = self.index.lock().expect("lock poisoned");
+        f.debug_struct("DiskCacheOps")
+            .field("blobs", &self.blobs)
+            .field("entries", &index.entries.len())
+            .field("size", &index.size)
+            .finish()
+    }
+}
+
+fn entry_name(key: &str) -> String {
+    let mut context = Context::new(b"diskcache");
+    context.update(key.as_bytes());
+    context.finish().to_hex().to_string()
+}
+
+fn entry_path(blobs: &Path, name: &str) -> PathBuf {
+    blobs.join(&name[..2]).join(name)
+}
+
+fn decode_entry(key: &str, data: Bytes) -> Option<BlobstoreGetData> {
+    let header_len = MAGIC.len() + 4;
+    if data.len() < header_len || &data[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    let key_len = u32::from_be_bytes(data[MAGIC.len()..header_len].try_into().ok()?) as usize;
+    let key_end = header_len.checked_add(key_len)?;
+    if data.len() < key_end || &data[header_len..key_end] != key.as_bytes() {
+        return None;
+    }
+    BlobstoreGetData::decode(data.slice(key_end..)).ok()
+}
+
+fn remove_entries(blobs: &Path, names: Vec<String>) {
+    for name in names {
+        // The entry might have been overwritten or removed already.
+        let _ = fs::remove_file(entry_path(blobs, &name));
+    }
+}
+
+struct IndexEntry {
+    size: u64,
+    tick: u64,
+}
+
+/// Tracks the size and the order of last use of the entries on disk
+struct LruIndex {
+    max_size: u64,
+    size: u64,
+    next_tick: u64,
+    entries: HashMap<String, IndexEntry>,
+    by_tick: BTreeMap<u64, String>,
+}
+
+impl LruIndex {
+    fn new(max_size: u64) -> Self {
+        Self {
+            max_size,
+            size: 0,
+            next_tick: 0,
+            entries: HashMap::new(),
+            by_tick: BTreeMap::new(),
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    fn touch(&mut self, name: &str) {
+        let tick = self.tick();
+        if let Some(entry) = self.entries.get_mut(name) {
+            self.by_tick.remove(&entry.tick);
+            entry.tick = tick;
+            self.by_tick.insert(tick, name.to_string());
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Some(entry) = self.entries.remove(name) {
+            self.by_tick.remove(&entry.tick);
+            self.size -= entry.size.max(MIN_ENTRY_SIZE);
+        }
+    }
+
+    /// Record an entry as the most recently used, and return the entries that have to be
+    /// evicted to make room for it.
+    fn insert(&mut self, name: String, size: u64) -> Vec<String> {
+        self.remove(&name);
+        let tick = self.tick();
+        self.size += size.max(MIN_ENTRY_SIZE);
+        self.by_tick.insert(tick, name.clone());
+        self.entries.insert(name.clone(), IndexEntry { size, tick });
+
+        let mut evicted = Vec::new();
+        while self.size > self.max_size {
+            let oldest = match self.by_tick.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            let oldest = self.by_tick[&oldest].clone();
+            self.remove(&oldest);
+            if oldest == name {
+                // Doesn't fit at all. The caller removes the file it just wrote.
+                evicted.push(oldest);
+                break;
+            }
+            evicted.push(oldest);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mononoke_types::BlobstoreBytes;
+    use tempfile::TempDir;
+
+    fn options(dir: &TempDir, max_size: u64) -> DiskCacheOptions {
+        DiskCacheOptions {
+            path: dir.path().to_path_buf(),
+            max_size,
+            max_blob_size: 2 * MIN_ENTRY_SIZE,
+        }
+    }
+
+    fn value(contents: &str) -> BlobstoreGetData {
+        BlobstoreBytes::from_bytes(contents.to_string()).into()
+    }
+
+    #[test]
+    fn put_get() -> Result<(), Error> {
+        let mut rt = tokio_compat::runtime::Runtime::new()?;
+        let dir = TempDir::new()?;
+        let cache = DiskCacheOps::open(options(&dir, 10 * MIN_ENTRY_SIZE))?;
+
+        assert_eq!(rt.block_on(cache.get("k1")), Ok(None));
+        assert_eq!(rt.block_on(cache.check_present("k1")), Ok(false));
+
+        assert_eq!(rt.block_on(cache.put("k1", value("v1"))), Ok(()));
+        assert_eq!(rt.block_on(cache.get("k1")), Ok(Some(value("v1"))));
+        assert_eq!(rt.block_on(cache.check_present("k1")), Ok(true));
+
+        // Too big to cache, but known to be present
+        let big = "x".repeat(3 * MIN_ENTRY_SIZE as usize);
+        assert_eq!(rt.block_on(cache.put("big", value(&big))), Ok(()));
+        assert_eq!(rt.block_on(cache.get("big")), Ok(None));
+        assert_eq!(rt.block_on(cache.check_present("big")), Ok(true));
+
+        // Entries survive reopening the cache
+        let cache = DiskCacheOps::open(options(&dir, 10 * MIN_ENTRY_SIZE))?;
+        assert_eq!(rt.block_on(cache.get("k1")), Ok(Some(value("v1"))));
+        assert_eq!(rt.block_on(cache.check_present("big")), Ok(true));
+
+        Ok(())
+    }
+
+    #[test]
+    fn eviction() -> Result<(), Error> {
+        let mut rt = tokio_compat::runtime::Runtime::new()?;
+        let dir = TempDir::new()?;
+        let cache = DiskCacheOps::open(options(&dir, 2 * MIN_ENTRY_SIZE))?;
+
+        assert_eq!(rt.block_on(cache.put("k1", value("v1"))), Ok(()));
+        assert_eq!(rt.block_on(cache.put("k2", value("v2"))), Ok(()));
+        // Using k1 makes k2 the least recently used
+        assert_eq!(rt.block_on(cache.get("k1")), Ok(Some(value("v1"))));
+        assert_eq!(rt.block_on(cache.put("k3", value("v3"))), Ok(()));
+
+        assert_eq!(rt.block_on(cache.get("k1")), Ok(Some(value("v1"))));
+        assert_eq!(rt.block_on(cache.get("k2")), Ok(None));
+        assert_eq!(rt.block_on(cache.check_present("k2")), Ok(false));
+        assert_eq!(rt.block_on(cache.get("k3")), Ok(Some(value("v3"))));
+
+        // Reopening with a smaller limit evicts down to it
+        let cache = DiskCacheOps::open(options(&dir, MIN_ENTRY_SIZE))?;
+        let present: Vec<_> = vec!["k1", "k3"]
+            .into_iter()
+            .filter(|key| rt.block_on(cache.check_present(key)) == Ok(true))
+            .collect();
+        assert_eq!(present.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared() -> Result<(), Error> {
+        let mut rt = tokio_compat::runtime::Runtime::new()?;
+        let dir = TempDir::new()?;
+        let cache1 = DiskCacheOps::shared(options(&dir, 2 * MIN_ENTRY_SIZE))?;
+        let cache2 = DiskCacheOps::shared(options(&dir, 2 * MIN_ENTRY_SIZE))?;
+
+        // Both see the entries of the other, and count towards the same limit
+        assert_eq!(rt.block_on(cache1.put("k1", value("v1"))), Ok(()));
+        assert_eq!(rt.block_on(cache2.get("k1")), Ok(Some(value("v1"))));
+        assert_eq!(rt.block_on(cache2.put("k2", value("v2"))), Ok(()));
+        assert_eq!(rt.block_on(cache2.put("k3", value("v3"))), Ok(()));
+        assert_eq!(rt.block_on(cache1.check_present("k1")), Ok(false));
+        assert_eq!(rt.block_on(cache1.get("k3")), Ok(Some(value("v3"))));
+
+        // Opening it again must agree on the limits
+        assert!(DiskCacheOps::shared(options(&dir, MIN_ENTRY_SIZE)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_entry() -> Result<(), Error> {
+        let mut rt = tokio_compat::runtime::Runtime::new()?;
+        let dir = TempDir::new()?;
+        let cache = DiskCacheOps::open(options(&dir, 10 * MIN_ENTRY_SIZE))?;
+
+        assert_eq!(rt.block_on(cache.put("k1", value("v1"))), Ok(()));
+        let path = entry_path(&cache.blobs, &entry_name("k1"));
+        fs::write(&path, b"MDC1garbage")?;
+
+        assert_eq!(rt.block_on(cache.get("k1")), Ok(None));
+        assert!(!path.exists());
+        assert_eq!(rt.block_on(cache.check_present("k1")), Ok(false));
+
+        Ok(())
+    }
+}
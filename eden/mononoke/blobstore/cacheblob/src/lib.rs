@@ -12,6 +12,11 @@ pub use crate::cachelib_cache::{new_cachelib_blobstore, new_cachelib_blobstore_n
 
 pub mod dummy;
 
+mod disk_cache;
+pub use crate::disk_cache::{
+    new_disk_cache_blobstore, new_disk_cache_blobstore_no_lease, DiskCacheOps, DiskCacheOptions,
+};
+
 mod in_process_lease;
 pub use in_process_lease::InProcessLease;
 
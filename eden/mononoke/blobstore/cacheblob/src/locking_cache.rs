@@ -6,7 +6,10 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData, CountedBlobstore};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
+    CountedBlobstore,
+};
 use cloned::cloned;
 use context::{CoreContext, PerfCounterType};
 use futures::{compat::Future01CompatExt, FutureExt, TryFutureExt};
@@ -317,6 +320,23 @@ where
     }
 }
 
+/// Enumeration always goes to the backing store, as the cache only knows about the keys that
+/// have been recently used.
+impl<C, L, T> BlobstoreKeySource for CacheBlobstore<C, L, T>
+where
+    C: CacheOps + Clone,
+    L: LeaseOps + Clone,
+    T: BlobstoreKeySource + Clone,
+{
+    fn enumerate(
+        &self,
+        ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        self.blobstore.enumerate(ctx, param)
+    }
+}
+
 impl<C, L, T> CacheBlobstoreExt for CacheBlobstore<C, L, T>
 where
     C: CacheOps + Clone,
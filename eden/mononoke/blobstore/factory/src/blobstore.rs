@@ -5,9 +5,10 @@
  * GNU General Public License version 2.
  */
 
-use anyhow::{Context, Error};
-use blobstore::{Blobstore, DisabledBlob, ErrorKind};
+use anyhow::{format_err, Context, Error};
+use blobstore::{Blobstore, BlobstoreKeySource, DisabledBlob, ErrorKind};
 use blobstore_sync_queue::SqlBlobstoreSyncQueue;
+use cacheblob::{new_disk_cache_blobstore, DiskCacheOptions, MemcacheOps};
 use chaosblob::{ChaosBlobstore, ChaosOptions};
 use cloned::cloned;
 use fbinit::FacebookInit;
@@ -19,16 +20,17 @@ use futures_old::{
     Future,
 };
 use metaconfig_types::{
-    BlobConfig, BlobstoreId, DatabaseConfig, MultiplexId, ScrubAction,
+    BlobConfig, BlobstoreId, DatabaseConfig, MultiplexId, MultiplexPolicy, PackConfig, ScrubAction,
     ShardableRemoteDatabaseConfig,
 };
 use multiplexedblob::{LoggingScrubHandler, MultiplexedBlobstore, ScrubBlobstore, ScrubHandler};
+use packblob::{PackBlob, PackOptions};
 use readonlyblob::ReadOnlyBlobstore;
 use scuba::ScubaSampleBuilder;
 use slog::Logger;
 use sql_construct::SqlConstructFromDatabaseConfig;
 use sql_ext::facebook::MysqlOptions;
-use sqlblob::Sqlblob;
+use sqlblob::{CountedSqlblob, Sqlblob};
 use std::num::NonZeroU64;
 use std::sync::Arc;
 use throttledblob::{ThrottleOptions, ThrottledBlob};
@@ -101,55 +103,16 @@ pub fn make_blobstore(
             .into_future()
             .boxify(),
 
-        Mysql { remote } => match remote {
-            ShardableRemoteDatabaseConfig::Unsharded(config) => {
-                if let Some(myrouter_port) = mysql_options.myrouter_port {
-                    Sqlblob::with_myrouter_unsharded(
-                        fb,
-                        config.db_address,
-                        myrouter_port,
-                        mysql_options.read_connection_type(),
-                        readonly_storage.0,
-                    )
-                } else {
-                    Sqlblob::with_raw_xdb_unsharded(
-                        fb,
-                        config.db_address,
-                        mysql_options.read_connection_type(),
-                        readonly_storage.0,
-                    )
-                }
-            }
-            ShardableRemoteDatabaseConfig::Sharded(config) => {
-                if let Some(myrouter_port) = mysql_options.myrouter_port {
-                    Sqlblob::with_myrouter(
-                        fb,
-                        config.shard_map.clone(),
-                        myrouter_port,
-                        mysql_options.read_connection_type(),
-                        config.shard_num,
-                        readonly_storage.0,
-                    )
-                } else {
-                    Sqlblob::with_raw_xdb_shardmap(
-                        fb,
-                        config.shard_map.clone(),
-                        mysql_options.read_connection_type(),
-                        config.shard_num,
-                        readonly_storage.0,
-                    )
-                }
-            }
-        }
-        .map(|store| Arc::new(store) as Arc<dyn Blobstore>)
-        .into_future()
-        .boxify(),
+        Mysql { remote } => make_sqlblob_mysql(fb, remote, mysql_options, readonly_storage)
+            .map(|store| Arc::new(store) as Arc<dyn Blobstore>)
+            .boxify(),
         Multiplexed {
             multiplex_id,
             scuba_table,
             scuba_sample_rate,
             blobstores,
             queue_db,
+            policy,
         } => {
             has_components = true;
             make_blobstore_multiplexed(
@@ -159,6 +122,7 @@ pub fn make_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                policy,
                 mysql_options,
                 readonly_storage,
                 None,
@@ -182,6 +146,7 @@ pub fn make_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                MultiplexPolicy::default(),
                 mysql_options,
                 readonly_storage,
                 Some((
@@ -231,6 +196,50 @@ pub fn make_blobstore(
                 unimplemented!("This is implemented only for fbcode_build")
             }
         }
+        Pack {
+            blobconfig,
+            pack_config,
+        } => {
+            has_components = true;
+            make_blobstore(
+                fb,
+                *blobconfig,
+                mysql_options,
+                readonly_storage,
+                blobstore_options.clone(),
+                logger,
+            )
+            .map(move |inner| {
+                Arc::new(PackBlob::new(inner, pack_options(&pack_config))) as Arc<dyn Blobstore>
+            })
+            .boxify()
+        }
+        DiskCache {
+            blobconfig,
+            path,
+            max_size,
+            max_blob_size,
+        } => {
+            has_components = true;
+            make_blobstore(
+                fb,
+                *blobconfig,
+                mysql_options,
+                readonly_storage,
+                blobstore_options.clone(),
+                logger,
+            )
+            .and_then(move |inner| {
+                let disk_cache_options = DiskCacheOptions {
+                    path,
+                    max_size,
+                    max_blob_size,
+                };
+                new_disk_cache_blobstore(inner, disk_cache_options)
+                    .map(|store| Arc::new(store) as Arc<dyn Blobstore>)
+            })
+            .boxify()
+        }
     };
 
     let store = if readonly_storage.0 {
@@ -260,9 +269,12 @@ pub fn make_blobstore(
     // For stores with components only set chaos on their components
     let store = if !has_components && blobstore_options.chaos_options.has_chaos() {
         store
-            .map(move |inner| {
-                Arc::new(ChaosBlobstore::new(inner, blobstore_options.chaos_options))
-                    as Arc<dyn Blobstore>
+            .map({
+                cloned!(blobstore_options);
+                move |inner| {
+                    Arc::new(ChaosBlobstore::new(inner, blobstore_options.chaos_options))
+                        as Arc<dyn Blobstore>
+                }
             })
             .boxify()
     } else {
@@ -283,6 +295,7 @@ pub fn make_blobstore_multiplexed(
     scuba_table: Option<String>,
     scuba_sample_rate: NonZeroU64,
     inner_config: Vec<(BlobstoreId, BlobConfig)>,
+    policy: MultiplexPolicy,
     mysql_options: MysqlOptions,
     readonly_storage: ReadOnlyStorage,
     scrub_args: Option<(Arc<dyn ScrubHandler>, ScrubAction)>,
@@ -363,6 +376,7 @@ pub fn make_blobstore_multiplexed(
                             multiplex_id,
                             components,
                             Arc::new(queue),
+                            policy,
                             scuba_table.map_or(ScubaSampleBuilder::with_discard(), |table| {
                                 ScubaSampleBuilder::new(fb, table)
                             }),
@@ -374,3 +388,209 @@ pub fn make_blobstore_multiplexed(
         })
         .boxify()
 }
+
+/// The options to build a PackBlob with for a packed blobstore config.
+pub fn pack_options(pack_config: &PackConfig) -> PackOptions {
+    let mut options = PackOptions::new();
+    for dictionary in &pack_config.dictionaries {
+        options = match &dictionary.path_prefix {
+            Some(path_prefix) => options.with_path_dictionary(
+                dictionary.key_prefix.as_str(),
+                path_prefix.clone(),
+                dictionary.dictionary.as_str(),
+            ),
+            None => options.with_dictionary(
+                dictionary.key_prefix.as_str(),
+                dictionary.dictionary.as_str(),
+            ),
+        };
+    }
+    if let Some(max_delta_depth) = pack_config.max_delta_depth {
+        options = options.with_max_delta_depth(max_delta_depth);
+    }
+    options
+}
+
+fn make_sqlblob_mysql(
+    fb: FacebookInit,
+    remote: ShardableRemoteDatabaseConfig,
+    mysql_options: MysqlOptions,
+    readonly_storage: ReadOnlyStorage,
+) -> BoxFuture<CountedSqlblob<MemcacheOps>, Error> {
+    match remote {
+        ShardableRemoteDatabaseConfig::Unsharded(config) => {
+            if let Some(myrouter_port) = mysql_options.myrouter_port {
+                Sqlblob::with_myrouter_unsharded(
+                    fb,
+                    config.db_address,
+                    myrouter_port,
+                    mysql_options.read_connection_type(),
+                    readonly_storage.0,
+                )
+            } else {
+                Sqlblob::with_raw_xdb_unsharded(
+                    fb,
+                    config.db_address,
+                    mysql_options.read_connection_type(),
+                    readonly_storage.0,
+                )
+            }
+        }
+        ShardableRemoteDatabaseConfig::Sharded(config) => {
+            if let Some(myrouter_port) = mysql_options.myrouter_port {
+                Sqlblob::with_myrouter(
+                    fb,
+                    config.shard_map.clone(),
+                    myrouter_port,
+                    mysql_options.read_connection_type(),
+                    config.shard_num,
+                    readonly_storage.0,
+                )
+            } else {
+                Sqlblob::with_raw_xdb_shardmap(
+                    fb,
+                    config.shard_map.clone(),
+                    mysql_options.read_connection_type(),
+                    config.shard_num,
+                    readonly_storage.0,
+                )
+            }
+        }
+    }
+}
+
+/// Construct a blobstore that can enumerate its keys, for tools that need to list what the
+/// storage actually holds. Only the storage and any multiplex over it are built: throttling,
+/// chaos, caching and packing don't change which keys are present, so they are left out.
+/// Scrub configs are enumerated as plain multiplexes.
+pub fn make_blobstore_enumerable(
+    fb: FacebookInit,
+    blobconfig: BlobConfig,
+    mysql_options: MysqlOptions,
+    readonly_storage: ReadOnlyStorage,
+) -> BoxFuture<Arc<dyn BlobstoreKeySource>, Error> {
+    use BlobConfig::*;
+    let store = match blobconfig {
+        Files { path } => Fileblob::create(path.join("blobs"))
+            .context(ErrorKind::StateOpen)
+            .map(|store| Arc::new(store) as Arc<dyn BlobstoreKeySource>)
+            .map_err(Error::from)
+            .into_future()
+            .boxify(),
+
+        Sqlite { path } => Sqlblob::with_sqlite_path(path.join("blobs"), readonly_storage.0)
+            .context(ErrorKind::StateOpen)
+            .map_err(Error::from)
+            .map(|store| Arc::new(store) as Arc<dyn BlobstoreKeySource>)
+            .into_future()
+            .boxify(),
+
+        Mysql { remote } => make_sqlblob_mysql(fb, remote, mysql_options, readonly_storage)
+            .map(|store| Arc::new(store) as Arc<dyn BlobstoreKeySource>)
+            .boxify(),
+
+        Multiplexed {
+            multiplex_id,
+            scuba_table,
+            scuba_sample_rate,
+            blobstores,
+            queue_db,
+            policy,
+        } => make_blobstore_multiplexed_enumerable(
+            fb,
+            multiplex_id,
+            queue_db,
+            scuba_table,
+            scuba_sample_rate,
+            blobstores,
+            policy,
+            mysql_options,
+            readonly_storage,
+        ),
+
+        Scrub {
+            multiplex_id,
+            scuba_table,
+            scuba_sample_rate,
+            blobstores,
+            queue_db,
+            ..
+        } => make_blobstore_multiplexed_enumerable(
+            fb,
+            multiplex_id,
+            queue_db,
+            scuba_table,
+            scuba_sample_rate,
+            blobstores,
+            MultiplexPolicy::default(),
+            mysql_options,
+            readonly_storage,
+        ),
+
+        Pack { blobconfig, .. } | DiskCache { blobconfig, .. } => {
+            make_blobstore_enumerable(fb, *blobconfig, mysql_options, readonly_storage)
+        }
+
+        blobconfig => future::err(format_err!(
+            "Blobstore {:?} does not support enumeration",
+            blobconfig
+        ))
+        .boxify(),
+    };
+
+    if readonly_storage.0 {
+        store
+            .map(|inner| Arc::new(ReadOnlyBlobstore::new(inner)) as Arc<dyn BlobstoreKeySource>)
+            .boxify()
+    } else {
+        store
+    }
+}
+
+fn make_blobstore_multiplexed_enumerable(
+    fb: FacebookInit,
+    multiplex_id: MultiplexId,
+    queue_db: DatabaseConfig,
+    scuba_table: Option<String>,
+    scuba_sample_rate: NonZeroU64,
+    inner_config: Vec<(BlobstoreId, BlobConfig)>,
+    policy: MultiplexPolicy,
+    mysql_options: MysqlOptions,
+    readonly_storage: ReadOnlyStorage,
+) -> BoxFuture<Arc<dyn BlobstoreKeySource>, Error> {
+    let components: Vec<_> = inner_config
+        .into_iter()
+        .map(|(blobstoreid, config)| {
+            make_blobstore_enumerable(fb, config, mysql_options, readonly_storage)
+                .map(move |store| (blobstoreid, store))
+        })
+        .collect();
+
+    let queue = async move {
+        SqlBlobstoreSyncQueue::with_database_config(
+            fb,
+            &queue_db,
+            mysql_options,
+            readonly_storage.0,
+        )
+        .await
+    }
+    .boxed()
+    .compat();
+
+    queue
+        .join(future::join_all(components))
+        .map(move |(queue, components)| {
+            Arc::new(MultiplexedBlobstore::new_enumerable(
+                multiplex_id,
+                components,
+                Arc::new(queue),
+                policy,
+                scuba_table.map_or(ScubaSampleBuilder::with_discard(), |table| {
+                    ScubaSampleBuilder::new(fb, table)
+                }),
+                scuba_sample_rate,
+            )) as Arc<dyn BlobstoreKeySource>
+        })
+        .boxify()
+}
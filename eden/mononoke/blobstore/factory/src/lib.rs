@@ -10,10 +10,14 @@ mod blobstore;
 mod facebook;
 mod sql;
 
+pub use cacheblob::DiskCacheOptions;
 pub use chaosblob::ChaosOptions;
 pub use throttledblob::ThrottleOptions;
 
-pub use crate::blobstore::{make_blobstore, make_blobstore_multiplexed, BlobstoreOptions};
+pub use crate::blobstore::{
+    make_blobstore, make_blobstore_enumerable, make_blobstore_multiplexed, pack_options,
+    BlobstoreOptions,
+};
 pub use crate::sql::{make_metadata_sql_factory, MetadataSqlFactory};
 
 #[derive(Copy, Clone, PartialEq)]
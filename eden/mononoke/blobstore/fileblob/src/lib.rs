@@ -7,24 +7,32 @@
 
 #![deny(warnings)]
 
+use std::cmp::min;
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::fs::{create_dir_all, File};
+use std::fs::{create_dir_all, read_dir, remove_file, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
 use anyhow::{bail, Error, Result};
 use futures::future::{poll_fn, Future};
 use futures::Async;
 use futures_ext::{BoxFuture, FutureExt};
-use percent_encoding::{percent_encode, AsciiSet, CONTROLS};
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, CONTROLS};
 
-use blobstore::{Blobstore, BlobstoreGetData, BlobstoreMetadata};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeyRange,
+    BlobstoreKeySource, BlobstoreMetadata, BlobstoreUnlinkOps,
+};
 use context::CoreContext;
 use mononoke_types::BlobstoreBytes;
 use tempfile::NamedTempFile;
 
 const PREFIX: &str = "blob";
+// Maximum number of keys returned by one call to enumerate
+const ENUMERATE_PAGE_SIZE: usize = 10000;
 /// https://url.spec.whatwg.org/#fragment-percent-encode-set
 const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
 /// https://url.spec.whatwg.org/#path-percent-encode-set
@@ -33,6 +41,10 @@ const PATH: &AsciiSet = &FRAGMENT.add(b'#').add(b'?').add(b'{').add(b'}');
 #[derive(Debug, Clone)]
 pub struct Fileblob {
     base: PathBuf,
+    page_size: usize,
+    // Sorted keys of the enumerations in progress, by range. The directory is listed once when
+    // an enumeration starts, and each page is then a slice of that listing.
+    snapshots: Arc<Mutex<HashMap<BlobstoreKeyRange, Arc<Vec<String>>>>>,
 }
 
 impl Fileblob {
@@ -45,9 +57,16 @@ impl Fileblob {
 
         Ok(Self {
             base: base.to_owned(),
+            page_size: ENUMERATE_PAGE_SIZE,
+            snapshots: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
+    /// Return at most `page_size` keys from each call to `enumerate`.
+    pub fn with_enumerate_page_size(self, page_size: usize) -> Self {
+        Self { page_size, ..self }
+    }
+
     pub fn create<P: AsRef<Path>>(base: P) -> Result<Self> {
         let base = base.as_ref();
         create_dir_all(base)?;
@@ -58,6 +77,73 @@ impl Fileblob {
         let key = percent_encode(key.as_bytes(), PATH);
         self.base.join(format!("{}-{}", PREFIX, key))
     }
+
+    fn list_keys(&self, range: &BlobstoreKeyRange) -> Result<Vec<String>> {
+        let file_prefix = format!("{}-", PREFIX);
+        let mut keys = vec![];
+        for entry in read_dir(&self.base)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let encoded = match name.to_str() {
+                Some(name) if name.starts_with(&file_prefix) => &name[file_prefix.len()..],
+                _ => continue,
+            };
+            let key = percent_decode_str(encoded).decode_utf8()?;
+            if range.contains(&key) {
+                keys.push(key.into_owned());
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// The sorted keys to page through for `param`. Starting an enumeration lists the directory,
+    /// continuing one reuses that listing. Continuations this process has no listing for, e.g.
+    /// after a restart, list the directory again.
+    fn snapshot(&self, param: &BlobstoreKeyParam) -> Result<Arc<Vec<String>>> {
+        let range = param.range();
+        if let BlobstoreKeyParam::Continuation { .. } = param {
+            let snapshots = self.snapshots.lock().expect("lock poisoned");
+            if let Some(keys) = snapshots.get(range) {
+                return Ok(keys.clone());
+            }
+        }
+
+        let keys = Arc::new(self.list_keys(range)?);
+        self.snapshots
+            .lock()
+            .expect("lock poisoned")
+            .insert(range.clone(), keys.clone());
+        Ok(keys)
+    }
+
+    fn enumerate_page(&self, param: &BlobstoreKeyParam) -> Result<BlobstoreEnumerationData> {
+        let keys = self.snapshot(param)?;
+        let start = match param {
+            BlobstoreKeyParam::Start(_) => 0,
+            BlobstoreKeyParam::Continuation { last_key, .. } => {
+                match keys.binary_search(last_key) {
+                    Ok(index) => index + 1,
+                    Err(index) => index,
+                }
+            }
+        };
+        // Take one key past the page so from_sorted_keys knows whether there are more.
+        let end = min(keys.len(), start + self.page_size + 1);
+        let page = BlobstoreEnumerationData::from_sorted_keys(
+            param,
+            keys[start..end].to_vec(),
+            self.page_size,
+        );
+
+        if page.next_token.is_none() {
+            self.snapshots
+                .lock()
+                .expect("lock poisoned")
+                .remove(param.range());
+        }
+        Ok(page)
+    }
 }
 
 fn ctime(file: &File) -> Option<i64> {
@@ -118,3 +204,33 @@ impl Blobstore for Fileblob {
         .boxify()
     }
 }
+
+impl BlobstoreKeySource for Fileblob {
+    fn enumerate(
+        &self,
+        _ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        let this = self.clone();
+        let param = param.clone();
+
+        poll_fn(move || Ok(Async::Ready(this.enumerate_page(&param)?))).boxify()
+    }
+}
+
+impl BlobstoreUnlinkOps for Fileblob {
+    fn unlink(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        let p = self.path(&key);
+
+        poll_fn(move || {
+            match remove_file(&p) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+                Ok(()) => (),
+            };
+            Ok(Async::Ready(()))
+        })
+        .from_err()
+        .boxify()
+    }
+}
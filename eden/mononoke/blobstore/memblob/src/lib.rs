@@ -13,10 +13,14 @@ use anyhow::Error;
 use futures::future::{lazy, IntoFuture};
 use futures_ext::{BoxFuture, FutureExt};
 
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
+};
 use context::CoreContext;
 use mononoke_types::BlobstoreBytes;
 
+const ENUMERATE_PAGE_SIZE: usize = 1000;
+
 /// In-memory "blob store"
 ///
 /// Pure in-memory implementation for testing.
@@ -98,6 +102,43 @@ impl Blobstore for LazyMemblob {
     }
 }
 
+fn enumerate_hash(
+    hash: &Mutex<HashMap<String, BlobstoreBytes>>,
+    param: &BlobstoreKeyParam,
+) -> BlobstoreEnumerationData {
+    let inner = hash.lock().expect("lock poison");
+    let mut keys: Vec<String> = inner
+        .keys()
+        .filter(|key| param.wants(key))
+        .cloned()
+        .collect();
+    keys.sort();
+    BlobstoreEnumerationData::from_sorted_keys(param, keys, ENUMERATE_PAGE_SIZE)
+}
+
+impl BlobstoreKeySource for EagerMemblob {
+    fn enumerate(
+        &self,
+        _ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        Ok(enumerate_hash(&self.hash, param)).into_future().boxify()
+    }
+}
+
+impl BlobstoreKeySource for LazyMemblob {
+    fn enumerate(
+        &self,
+        _ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        let hash = self.hash.clone();
+        let param = param.clone();
+
+        lazy(move || Ok(enumerate_hash(&hash, &param)).into_future()).boxify()
+    }
+}
+
 impl fmt::Debug for EagerMemblob {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("EagerMemblob")
@@ -6,7 +6,9 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
+};
 use blobstore_sync_queue::OperationKey;
 use cloned::cloned;
 use context::{CoreContext, PerfCounterType};
@@ -14,11 +16,12 @@ use futures_ext::{BoxFuture, FutureExt};
 use futures_old::future::{self, Future, Loop};
 use futures_stats::Timed;
 use itertools::{Either, Itertools};
-use metaconfig_types::{BlobstoreId, MultiplexId};
+use metaconfig_types::{BlobstoreId, MultiplexId, MultiplexPolicy};
 use mononoke_types::BlobstoreBytes;
 use scuba::ScubaSampleBuilder;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::mem;
 use std::num::NonZeroU64;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
@@ -38,12 +41,25 @@ type BlobstoresWithEntry = HashSet<BlobstoreId>;
 type BlobstoresReturnedNone = HashSet<BlobstoreId>;
 type BlobstoresReturnedError = HashMap<BlobstoreId, Error>;
 
+type GetRequest = BoxFuture<(BlobstoreId, Option<BlobstoreGetData>), (BlobstoreId, Error)>;
+type PutRequest = BoxFuture<(PutStage, BlobstoreId), (PutStage, BlobstoreId, Error)>;
+
 #[derive(Error, Debug, Clone)]
 pub enum ErrorKind {
     #[error("Some blobstores failed, and other returned None: {0:?}")]
     SomeFailedOthersNone(Arc<BlobstoresReturnedError>),
     #[error("All blobstores failed: {0:?}")]
     AllFailed(Arc<BlobstoresReturnedError>),
+    #[error("Not all blobstores in multiplex {0} support key enumeration")]
+    NotEnumerable(MultiplexId),
+    #[error("Put succeeded on {written} blobstores, {quorum} required: {errors:?}")]
+    NotEnoughWrites {
+        written: usize,
+        quorum: usize,
+        errors: Arc<BlobstoresReturnedError>,
+    },
+    #[error("Put could not be written to the sync queue: {0:?}")]
+    SyncQueueFailed(Arc<BlobstoresReturnedError>),
     // Errors below this point are from ScrubBlobstore only. If they include an
     // Option<BlobstoreBytes>, this implies that this error is recoverable
     #[error(
@@ -71,7 +87,9 @@ pub trait MultiplexedBlobstorePutHandler: Send + Sync {
 pub struct MultiplexedBlobstoreBase {
     multiplex_id: MultiplexId,
     blobstores: Arc<[(BlobstoreId, Arc<dyn Blobstore>)]>,
+    key_sources: Option<Arc<[(BlobstoreId, Arc<dyn BlobstoreKeySource>)]>>,
     handler: Arc<dyn MultiplexedBlobstorePutHandler>,
+    policy: MultiplexPolicy,
     scuba: ScubaSampleBuilder,
     scuba_sample_rate: NonZeroU64,
 }
@@ -81,6 +99,7 @@ impl MultiplexedBlobstoreBase {
         multiplex_id: MultiplexId,
         blobstores: Vec<(BlobstoreId, Arc<dyn Blobstore>)>,
         handler: Arc<dyn MultiplexedBlobstorePutHandler>,
+        policy: MultiplexPolicy,
         mut scuba: ScubaSampleBuilder,
         scuba_sample_rate: NonZeroU64,
     ) -> Self {
@@ -89,12 +108,45 @@ impl MultiplexedBlobstoreBase {
         Self {
             multiplex_id,
             blobstores: blobstores.into(),
+            key_sources: None,
             handler,
+            policy,
             scuba,
             scuba_sample_rate,
         }
     }
 
+    /// As `new`, but with components that can enumerate their keys, so that the multiplex
+    /// can enumerate the union of them.
+    pub fn new_enumerable(
+        multiplex_id: MultiplexId,
+        blobstores: Vec<(BlobstoreId, Arc<dyn BlobstoreKeySource>)>,
+        handler: Arc<dyn MultiplexedBlobstorePutHandler>,
+        policy: MultiplexPolicy,
+        scuba: ScubaSampleBuilder,
+        scuba_sample_rate: NonZeroU64,
+    ) -> Self {
+        let plain = blobstores
+            .iter()
+            .map(|(blobstore_id, blobstore)| {
+                (
+                    *blobstore_id,
+                    Arc::new(blobstore.clone()) as Arc<dyn Blobstore>,
+                )
+            })
+            .collect();
+        let mut base = Self::new(
+            multiplex_id,
+            plain,
+            handler,
+            policy,
+            scuba,
+            scuba_sample_rate,
+        );
+        base.key_sources = Some(blobstores.into());
+        base
+    }
+
     pub fn scrub_get(
         &self,
         ctx: CoreContext,
@@ -223,18 +275,57 @@ impl Blobstore for MultiplexedBlobstoreBase {
 
         let is_logged = scuba.sampling().is_logged();
 
-        let requests = multiplexed_get(&ctx, self.blobstores.as_ref(), &key, "get", scuba);
-        let state = (
-            requests,                             // pending requests
-            HashMap::<BlobstoreId, Error>::new(), // previous errors
-        );
+        // Query the preferred blobstores first, and only fall back to the others if none of
+        // them has the blob.
+        let (preferred, others): (Vec<_>, Vec<_>) = if self.policy.read_preference.is_empty() {
+            (self.blobstores.iter().cloned().collect(), Vec::new())
+        } else {
+            let read_preference = &self.policy.read_preference;
+            self.blobstores
+                .iter()
+                .cloned()
+                .partition(|(blobstore_id, _)| read_preference.contains(blobstore_id))
+        };
+
+        let requests = multiplexed_get(&ctx, &preferred, &key, "get", scuba.clone());
         let blobstores_count = self.blobstores.len();
-        future::loop_fn(state, move |(requests, mut errors)| {
-            future::select_all(requests).then({
-                move |result| {
-                    let requests = match result {
-                        Ok(((_, Some(mut value)), _, requests)) => {
-                            if is_logged {
+        get_first(GetProgress::new(requests))
+            .and_then({
+                cloned!(ctx, key, scuba);
+                move |mut progress| {
+                    if progress.found.is_some() || others.is_empty() {
+                        future::ok(progress).left_future()
+                    } else {
+                        progress.requests = multiplexed_get(&ctx, &others, &key, "get", scuba);
+                        get_first(progress).right_future()
+                    }
+                }
+            })
+            .and_then({
+                cloned!(ctx, self.blobstores);
+                let read_repair = self.policy.read_repair;
+                let mut scuba = self.scuba.clone();
+                move |progress| {
+                    let GetProgress {
+                        found,
+                        requests,
+                        missing,
+                        errors,
+                    } = progress;
+                    match found {
+                        Some((blobstore_id, value)) => {
+                            if read_repair {
+                                scuba.add("read_repair", true);
+                                spawn(repair_get(
+                                    ctx,
+                                    scuba,
+                                    blobstores,
+                                    key,
+                                    (blobstore_id, value.clone().into_bytes()),
+                                    requests,
+                                    missing,
+                                ));
+                            } else if is_logged {
                                 // Allow the other requests to complete so that we can record some
                                 // metrics for the blobstore.
                                 let requests_fut = future::join_all(
@@ -243,81 +334,76 @@ impl Blobstore for MultiplexedBlobstoreBase {
                                 .map(|_| ());
                                 spawn(requests_fut);
                             }
+                            let mut value = value;
                             value.remove_ctime();
-                            return future::ok(Loop::Break(Some(value)));
-                        }
-                        Ok(((_, None), _, requests)) => requests,
-                        Err(((blobstore_id, error), _, requests)) => {
-                            errors.insert(blobstore_id, error);
-                            requests
+                            Ok(Some(value))
                         }
-                    };
-                    if requests.is_empty() {
-                        if errors.is_empty() {
-                            future::ok(Loop::Break(None))
-                        } else {
+                        None if errors.is_empty() => Ok(None),
+                        None => {
                             let error = if errors.len() == blobstores_count {
                                 ErrorKind::AllFailed(errors.into())
                             } else {
                                 ErrorKind::SomeFailedOthersNone(errors.into())
                             };
-                            future::err(error.into())
+                            Err(error.into())
                         }
-                    } else {
-                        future::ok(Loop::Continue((requests, errors)))
                     }
                 }
             })
-        })
-        .timed(move |stats, _| {
-            ctx.perf_counters().set_max_counter(
-                PerfCounterType::BlobGetsMaxLatency,
-                stats.completion_time.as_millis_unchecked() as i64,
-            );
-            Ok(())
-        })
-        .boxify()
+            .timed(move |stats, _| {
+                ctx.perf_counters().set_max_counter(
+                    PerfCounterType::BlobGetsMaxLatency,
+                    stats.completion_time.as_millis_unchecked() as i64,
+                );
+                Ok(())
+            })
+            .boxify()
     }
 
     fn put(&self, ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
         ctx.perf_counters()
             .increment_counter(PerfCounterType::BlobPuts);
         let write_order = Arc::new(AtomicUsize::new(0));
-        let operation_key = OperationKey::gen();
-        let puts = self
+        let requests = self
             .blobstores
             .iter()
             .map({
                 |(blobstore_id, blobstore)| {
+                    let blobstore_id = *blobstore_id;
                     inner_put(
                         ctx.clone(),
                         self.scuba.clone(),
                         write_order.clone(),
-                        *blobstore_id,
+                        blobstore_id,
                         blobstore.clone(),
                         key.clone(),
                         value.clone(),
                     )
+                    .map(|blobstore_id| (PutStage::Blobstore, blobstore_id))
+                    .map_err(move |error| (PutStage::Blobstore, blobstore_id, error))
+                    .boxify()
                 }
             })
             .collect();
 
-        multiplexed_put(
-            ctx.clone(),
-            self.handler.clone(),
+        let put = Arc::new(PutContext {
+            ctx: ctx.clone(),
+            handler: self.handler.clone(),
             key,
-            self.multiplex_id,
-            operation_key,
-            puts,
-        )
-        .timed(move |stats, _| {
-            ctx.perf_counters().set_max_counter(
-                PerfCounterType::BlobPutsMaxLatency,
-                stats.completion_time.as_millis_unchecked() as i64,
-            );
-            Ok(())
-        })
-        .boxify()
+            multiplex_id: self.multiplex_id,
+            operation_key: OperationKey::gen(),
+        });
+        let progress = PutProgress::new(self.policy.write_quorum.get(), self.blobstores.len());
+
+        multiplexed_put(put, progress, requests)
+            .timed(move |stats, _| {
+                ctx.perf_counters().set_max_counter(
+                    PerfCounterType::BlobPutsMaxLatency,
+                    stats.completion_time.as_millis_unchecked() as i64,
+                );
+                Ok(())
+            })
+            .boxify()
     }
 
     fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
@@ -376,6 +462,40 @@ impl Blobstore for MultiplexedBlobstoreBase {
     }
 }
 
+impl BlobstoreKeySource for MultiplexedBlobstoreBase {
+    /// Enumerates every component and returns the union of their keys, so keys that have
+    /// not yet been healed onto all blobstores are still listed. Any component failing fails
+    /// the whole page, as a partial listing is not useful for audits.
+    fn enumerate(
+        &self,
+        ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        let key_sources = match self.key_sources {
+            Some(ref key_sources) => key_sources.clone(),
+            None => {
+                return future::err(ErrorKind::NotEnumerable(self.multiplex_id).into()).boxify()
+            }
+        };
+
+        let requests: Vec<_> = key_sources
+            .iter()
+            .map(|&(blobstore_id, ref blobstore)| {
+                blobstore
+                    .enumerate(ctx.clone(), param)
+                    .map_err(move |error| {
+                        error.context(format!("While enumerating blobstore {}", blobstore_id))
+                    })
+            })
+            .collect();
+
+        let param = param.clone();
+        future::join_all(requests)
+            .map(move |pages| BlobstoreEnumerationData::merge(&param, pages))
+            .boxify()
+    }
+}
+
 impl fmt::Debug for MultiplexedBlobstoreBase {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -395,7 +515,7 @@ fn multiplexed_get(
     key: &String,
     operation: &'static str,
     scuba: ScubaSampleBuilder,
-) -> Vec<BoxFuture<(BlobstoreId, Option<BlobstoreGetData>), (BlobstoreId, Error)>> {
+) -> Vec<GetRequest> {
     blobstores
         .iter()
         .map(|&(blobstore_id, ref blobstore)| {
@@ -447,82 +567,282 @@ fn multiplexed_get(
         .collect()
 }
 
-fn multiplexed_put<F: Future<Item = BlobstoreId, Error = Error> + Send + 'static>(
+/// Where a get has got to: the first value found, and what the blobstores that have
+/// answered so far returned.
+struct GetProgress {
+    found: Option<(BlobstoreId, BlobstoreGetData)>,
+    requests: Vec<GetRequest>,
+    missing: HashSet<BlobstoreId>,
+    errors: HashMap<BlobstoreId, Error>,
+}
+
+impl GetProgress {
+    fn new(requests: Vec<GetRequest>) -> Self {
+        Self {
+            found: None,
+            requests,
+            missing: HashSet::new(),
+            errors: HashMap::new(),
+        }
+    }
+}
+
+/// Wait for the pending requests until one of them finds the blob, or they have all
+/// answered.
+fn get_first(progress: GetProgress) -> BoxFuture<GetProgress, Error> {
+    if progress.requests.is_empty() {
+        return future::ok(progress).boxify();
+    }
+    future::loop_fn(progress, |mut progress| {
+        let requests = mem::replace(&mut progress.requests, Vec::new());
+        future::select_all(requests).then(move |result| {
+            progress.requests = match result {
+                Ok(((blobstore_id, Some(value)), _, requests)) => {
+                    progress.found = Some((blobstore_id, value));
+                    progress.requests = requests;
+                    return future::ok(Loop::Break(progress));
+                }
+                Ok(((blobstore_id, None), _, requests)) => {
+                    progress.missing.insert(blobstore_id);
+                    requests
+                }
+                Err(((blobstore_id, error), _, requests)) => {
+                    progress.errors.insert(blobstore_id, error);
+                    requests
+                }
+            };
+            if progress.requests.is_empty() {
+                future::ok(Loop::Break(progress))
+            } else {
+                future::ok(Loop::Continue(progress))
+            }
+        })
+    })
+    .boxify()
+}
+
+/// Once a get has returned, wait for the rest of the blobstores it queried to answer, and
+/// write the value most of them agree on to the ones that are missing it or have a
+/// different value. If there is no single most common value, nothing is repaired.
+fn repair_get(
     ctx: CoreContext,
-    handler: Arc<dyn MultiplexedBlobstorePutHandler>,
+    scuba: ScubaSampleBuilder,
+    blobstores: Arc<[(BlobstoreId, Arc<dyn Blobstore>)]>,
     key: String,
-    multiplex_id: MultiplexId,
-    operation_key: OperationKey,
-    puts: Vec<F>,
-) -> impl Future<Item = (), Error = Error> {
-    future::select_ok(puts).and_then(move |(blobstore_id, other_puts)| {
-        finish_put(
-            ctx,
-            handler,
-            key,
-            blobstore_id,
-            multiplex_id,
-            operation_key,
-            other_puts,
-        )
-    })
+    found: (BlobstoreId, BlobstoreBytes),
+    requests: Vec<GetRequest>,
+    missing: HashSet<BlobstoreId>,
+) -> impl Future<Item = (), Error = ()> {
+    let requests = requests
+        .into_iter()
+        .map(|request| request.then(|result| Ok::<_, Error>(result.ok())));
+    future::join_all(requests)
+        .and_then(move |answers| {
+            let mut values: Vec<_> = missing.into_iter().map(|id| (id, None)).collect();
+            values.push((found.0, Some(found.1)));
+            values.extend(
+                answers
+                    .into_iter()
+                    .flatten()
+                    .map(|(id, value)| (id, value.map(BlobstoreGetData::into_bytes))),
+            );
+
+            let value = match most_common_value(&values) {
+                Some(value) => value,
+                None => return future::ok(()).left_future(),
+            };
+
+            let write_order = Arc::new(AtomicUsize::new(0));
+            let repairs = values
+                .into_iter()
+                .filter(|(_, answer)| answer.as_ref() != Some(&value))
+                .filter_map(|(blobstore_id, _)| {
+                    blobstores
+                        .iter()
+                        .find(|(id, _)| *id == blobstore_id)
+                        .map(|(_, blobstore)| {
+                            inner_put(
+                                ctx.clone(),
+                                scuba.clone(),
+                                write_order.clone(),
+                                blobstore_id,
+                                blobstore.clone(),
+                                key.clone(),
+                                value.clone(),
+                            )
+                            .then(|_| Ok(()))
+                        })
+                })
+                .collect::<Vec<_>>();
+            future::join_all(repairs).map(|_| ()).right_future()
+        })
+        .map_err(|_| ())
+}
+
+/// The value returned by the most blobstores, if there is a single one
+fn most_common_value(values: &[(BlobstoreId, Option<BlobstoreBytes>)]) -> Option<BlobstoreBytes> {
+    let mut votes: Vec<(&BlobstoreBytes, usize)> = Vec::new();
+    for value in values.iter().filter_map(|(_, value)| value.as_ref()) {
+        match votes.iter_mut().find(|(candidate, _)| *candidate == value) {
+            Some((_, count)) => *count += 1,
+            None => votes.push((value, 1)),
+        }
+    }
+    let max = votes.iter().map(|(_, count)| *count).max()?;
+    let mut winners = votes.into_iter().filter(|(_, count)| *count == max);
+    match (winners.next(), winners.next()) {
+        (Some((value, _)), None) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum PutStage {
+    Blobstore,
+    Queue,
 }
 
-fn finish_put<F: Future<Item = BlobstoreId, Error = Error> + Send + 'static>(
+/// Everything needed to record a successful blobstore put in the sync queue
+struct PutContext {
     ctx: CoreContext,
     handler: Arc<dyn MultiplexedBlobstorePutHandler>,
     key: String,
-    blobstore_id: BlobstoreId,
     multiplex_id: MultiplexId,
     operation_key: OperationKey,
-    other_puts: Vec<F>,
-) -> BoxFuture<(), Error> {
-    // Ocne we finished a put in one blobstore, we want to return once this blob is in a position
-    // to be replicated properly to the multiplexed stores. This can happen in two cases:
-    // - We wrote it to the SQL queue that will replicate it to other blobstores.
-    // - We wrote it to all the blobstores.
-    // As soon as either of those things happen, we can report the put as successful.
-    use futures_old::future::Either;
-
-    let queue_write = handler.on_put(
-        ctx.clone(),
-        blobstore_id,
-        multiplex_id,
-        operation_key.clone(),
-        key.clone(),
-    );
-
-    let rest_put = if other_puts.len() > 0 {
-        multiplexed_put(ctx, handler, key, multiplex_id, operation_key, other_puts).left_future()
-    } else {
-        // We have no remaining puts to perform, which means we've successfully written to all
-        // blobstores.
-        future::ok(()).right_future()
-    };
-
-    queue_write
-        .select2(rest_put)
-        .then(|res| match res {
-            Ok(Either::A((_, rest_put))) => {
-                // Blobstore queue write succeeded. Spawn the rest of the puts to give them a
-                // chance to complete, but we're done.
-                spawn(rest_put.discard());
-                future::ok(()).boxify()
+}
+
+impl PutContext {
+    fn queue(&self, blobstore_id: BlobstoreId) -> PutRequest {
+        self.handler
+            .on_put(
+                self.ctx.clone(),
+                blobstore_id,
+                self.multiplex_id,
+                self.operation_key.clone(),
+                self.key.clone(),
+            )
+            .map(move |()| (PutStage::Queue, blobstore_id))
+            .map_err(move |error| (PutStage::Queue, blobstore_id, error))
+            .boxify()
+    }
+}
+
+struct PutProgress {
+    write_quorum: usize,
+    total: usize,
+    written: usize,
+    queued: bool,
+    put_errors: HashMap<BlobstoreId, Error>,
+    queue_errors: HashMap<BlobstoreId, Error>,
+}
+
+impl PutProgress {
+    fn new(write_quorum: usize, total: usize) -> Self {
+        Self {
+            write_quorum,
+            total,
+            written: 0,
+            queued: false,
+            put_errors: HashMap::new(),
+            queue_errors: HashMap::new(),
+        }
+    }
+
+    /// Whether the put has succeeded or failed, or `None` if that depends on the requests
+    /// that are still pending.
+    ///
+    /// A put succeeds once it has been written to the write quorum, and is in a position to
+    /// be replicated to the other blobstores: either it has been written to the sync queue,
+    /// or it has been written to all of the blobstores.
+    fn outcome(&mut self, requests_pending: bool) -> Option<Result<(), ErrorKind>> {
+        let pending_puts = self.total - self.written - self.put_errors.len();
+        if self.written >= self.write_quorum && (self.queued || self.written == self.total) {
+            Some(Ok(()))
+        } else if self.written + pending_puts < self.write_quorum {
+            Some(Err(ErrorKind::NotEnoughWrites {
+                written: self.written,
+                quorum: self.write_quorum,
+                errors: Arc::new(mem::replace(&mut self.put_errors, HashMap::new())),
+            }))
+        } else if !requests_pending {
+            Some(Err(ErrorKind::SyncQueueFailed(Arc::new(mem::replace(
+                &mut self.queue_errors,
+                HashMap::new(),
+            )))))
+        } else {
+            None
+        }
+    }
+}
+
+/// Wait for one of the pending requests to complete and record its result. A successful
+/// blobstore put is followed by a write to the sync queue.
+fn put_step(
+    put: Arc<PutContext>,
+    mut progress: PutProgress,
+    requests: Vec<PutRequest>,
+) -> impl Future<Item = (PutProgress, Vec<PutRequest>), Error = Error> {
+    future::select_all(requests).then(move |result| {
+        let requests = match result {
+            Ok(((PutStage::Blobstore, blobstore_id), _, mut requests)) => {
+                progress.written += 1;
+                requests.push(put.queue(blobstore_id));
+                requests
+            }
+            Ok(((PutStage::Queue, _), _, requests)) => {
+                progress.queued = true;
+                requests
             }
-            Ok(Either::B((_, queue_write))) => {
-                // Remaininig puts succeeded (note that this might mean one of them and its
-                // corresponding SQL write succeeded). Spawn the queue write, but we're done.
-                spawn(queue_write.discard());
-                future::ok(()).boxify()
+            Err(((PutStage::Blobstore, blobstore_id, error), _, requests)) => {
+                progress.put_errors.insert(blobstore_id, error);
+                requests
             }
-            Err(Either::A((_, rest_put))) => {
-                // Blobstore queue write failed. We might still succeed if the other puts succeed.
-                rest_put.boxify()
+            Err(((PutStage::Queue, blobstore_id, error), _, requests)) => {
+                progress.queue_errors.insert(blobstore_id, error);
+                requests
+            }
+        };
+        future::ok((progress, requests))
+    })
+}
+
+fn multiplexed_put(
+    put: Arc<PutContext>,
+    progress: PutProgress,
+    requests: Vec<PutRequest>,
+) -> impl Future<Item = (), Error = Error> {
+    future::loop_fn((progress, requests), move |(progress, requests)| {
+        put_step(put.clone(), progress, requests).map({
+            cloned!(put);
+            move |(mut progress, requests)| match progress.outcome(!requests.is_empty()) {
+                Some(result) => {
+                    if !requests.is_empty() {
+                        // We're done, but give the remaining puts a chance to complete, and
+                        // to be recorded in the sync queue.
+                        spawn(finish_put(put, progress, requests));
+                    }
+                    Loop::Break(result)
+                }
+                None => Loop::Continue((progress, requests)),
             }
-            Err(Either::B((_, queue_write))) => {
-                // Remaining puts failed. We might sitll succeed if the queue write succeeds.
-                queue_write
+        })
+    })
+    .and_then(|result| result.map_err(Error::from))
+}
+
+fn finish_put(
+    put: Arc<PutContext>,
+    progress: PutProgress,
+    requests: Vec<PutRequest>,
+) -> impl Future<Item = (), Error = ()> {
+    future::loop_fn((progress, requests), move |(progress, requests)| {
+        put_step(put.clone(), progress, requests).map(|(progress, requests)| {
+            if requests.is_empty() {
+                Loop::Break(())
+            } else {
+                Loop::Continue((progress, requests))
             }
         })
-        .boxify()
+    })
+    .map_err(|_| ())
 }
@@ -7,13 +7,15 @@
 
 use crate::base::{ErrorKind, MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler};
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
+};
 use blobstore_sync_queue::{BlobstoreSyncQueue, BlobstoreSyncQueueEntry, OperationKey};
 use cloned::cloned;
 use context::CoreContext;
 use futures_ext::{BoxFuture, FutureExt};
 use futures_old::future::{self, Future};
-use metaconfig_types::{BlobstoreId, MultiplexId};
+use metaconfig_types::{BlobstoreId, MultiplexId, MultiplexPolicy};
 use mononoke_types::{BlobstoreBytes, DateTime};
 use scuba::ScubaSampleBuilder;
 use std::fmt;
@@ -31,6 +33,7 @@ impl MultiplexedBlobstore {
         multiplex_id: MultiplexId,
         blobstores: Vec<(BlobstoreId, Arc<dyn Blobstore>)>,
         queue: Arc<dyn BlobstoreSyncQueue>,
+        policy: MultiplexPolicy,
         scuba: ScubaSampleBuilder,
         scuba_sample_rate: NonZeroU64,
     ) -> Self {
@@ -42,6 +45,32 @@ impl MultiplexedBlobstore {
                 multiplex_id,
                 blobstores,
                 put_handler,
+                policy,
+                scuba,
+                scuba_sample_rate,
+            )),
+            queue,
+        }
+    }
+
+    /// As `new`, but the resulting multiplex can enumerate its keys.
+    pub fn new_enumerable(
+        multiplex_id: MultiplexId,
+        blobstores: Vec<(BlobstoreId, Arc<dyn BlobstoreKeySource>)>,
+        queue: Arc<dyn BlobstoreSyncQueue>,
+        policy: MultiplexPolicy,
+        scuba: ScubaSampleBuilder,
+        scuba_sample_rate: NonZeroU64,
+    ) -> Self {
+        let put_handler = Arc::new(QueueBlobstorePutHandler {
+            queue: queue.clone(),
+        });
+        Self {
+            blobstore: Arc::new(MultiplexedBlobstoreBase::new_enumerable(
+                multiplex_id,
+                blobstores,
+                put_handler,
+                policy,
                 scuba,
                 scuba_sample_rate,
             )),
@@ -151,3 +180,13 @@ impl Blobstore for MultiplexedBlobstore {
             .boxify()
     }
 }
+
+impl BlobstoreKeySource for MultiplexedBlobstore {
+    fn enumerate(
+        &self,
+        ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        self.blobstore.enumerate(ctx, param)
+    }
+}
@@ -15,7 +15,7 @@ use cloned::cloned;
 use context::CoreContext;
 use futures_ext::{BoxFuture, FutureExt};
 use futures_old::future::{self, Future};
-use metaconfig_types::{BlobstoreId, MultiplexId, ScrubAction};
+use metaconfig_types::{BlobstoreId, MultiplexId, MultiplexPolicy, ScrubAction};
 use mononoke_types::BlobstoreBytes;
 use scuba::ScubaSampleBuilder;
 use slog::{info, warn};
@@ -95,6 +95,7 @@ impl ScrubBlobstore {
             multiplex_id,
             blobstores.clone(),
             queue.clone(),
+            MultiplexPolicy::default(),
             scuba.clone(),
             scuba_sample_rate,
         );
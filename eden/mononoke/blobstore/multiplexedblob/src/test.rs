@@ -8,12 +8,16 @@
 use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::base::{MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler};
 use crate::queue::MultiplexedBlobstore;
 use crate::scrub::{LoggingScrubHandler, ScrubBlobstore, ScrubHandler};
-use anyhow::{bail, Error};
-use blobstore::{Blobstore, BlobstoreGetData};
+use anyhow::{bail, format_err, Error};
+use blobstore::{
+    enumerate_keys, Blobstore, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeyRange,
+    BlobstoreKeySource,
+};
 use blobstore_sync_queue::{
     BlobstoreSyncQueue, BlobstoreSyncQueueEntry, OperationKey, SqlBlobstoreSyncQueue,
 };
@@ -23,20 +27,22 @@ use context::CoreContext;
 use fbinit::FacebookInit;
 use futures::{
     compat::Future01CompatExt,
-    future::FutureExt as _,
+    future::{FutureExt as _, TryFutureExt},
     task::{Context, Poll},
 };
 use futures_ext::{BoxFuture, FutureExt};
 use futures_old::future::{Future, IntoFuture};
+use futures_old::stream::Stream;
 use futures_old::sync::oneshot;
 use lock_ext::LockExt;
 use memblob::LazyMemblob;
-use metaconfig_types::{BlobstoreId, MultiplexId, ScrubAction};
+use metaconfig_types::{BlobstoreId, MultiplexId, MultiplexPolicy, ScrubAction};
 use mononoke_types::{BlobstoreBytes, DateTime};
 use nonzero_ext::nonzero;
 use readonlyblob::ReadOnlyBlobstore;
 use scuba::ScubaSampleBuilder;
 use sql_construct::SqlConstruct;
+use tokio::timer::{Delay, Timeout};
 
 pub struct Tickable<T> {
     pub storage: Arc<Mutex<HashMap<String, T>>>,
@@ -214,6 +220,7 @@ fn base(fb: FacebookInit) {
                 (BlobstoreId::new(1), bs1.clone()),
             ],
             log.clone(),
+            MultiplexPolicy::default(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
         );
@@ -368,6 +375,7 @@ fn multiplexed(fb: FacebookInit) {
             MultiplexId::new(1),
             vec![(bid0, bs0.clone()), (bid1, bs1.clone())],
             queue.clone(),
+            MultiplexPolicy::default(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
         );
@@ -464,6 +472,7 @@ async fn multiplexed_operation_keys(fb: FacebookInit) -> Result<(), Error> {
             (bid2, bs2.clone()),
         ],
         queue.clone(),
+        MultiplexPolicy::default(),
         ScubaSampleBuilder::with_discard(),
         nonzero!(1u64),
     );
@@ -496,6 +505,72 @@ async fn multiplexed_operation_keys(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn multiplexed_enumerate(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let queue = Arc::new(SqlBlobstoreSyncQueue::with_sqlite_in_memory().unwrap());
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(LazyMemblob::new());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(LazyMemblob::new());
+    let bs = MultiplexedBlobstore::new_enumerable(
+        MultiplexId::new(1),
+        vec![
+            (bid0, bs0.clone() as Arc<dyn BlobstoreKeySource>),
+            (bid1, bs1.clone() as Arc<dyn BlobstoreKeySource>),
+        ],
+        queue.clone(),
+        MultiplexPolicy::default(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+
+    // Keys only present on one side (e.g. not yet healed) are still listed, once.
+    bs.put(ctx.clone(), "k1".to_string(), make_value("v1"))
+        .compat()
+        .await?;
+    bs0.put(ctx.clone(), "k2".to_string(), make_value("v2"))
+        .compat()
+        .await?;
+    bs1.put(ctx.clone(), "k3".to_string(), make_value("v3"))
+        .compat()
+        .await?;
+    bs1.put(ctx.clone(), "other".to_string(), make_value("v4"))
+        .compat()
+        .await?;
+
+    let keys = enumerate_keys(
+        ctx.clone(),
+        bs.clone(),
+        BlobstoreKeyParam::Start(BlobstoreKeyRange::with_prefix("k")),
+    )
+    .collect()
+    .compat()
+    .await?;
+    assert_eq!(keys, vec!["k1", "k2", "k3"]);
+
+    // A multiplex built from plain blobstores cannot enumerate.
+    let plain = MultiplexedBlobstore::new(
+        MultiplexId::new(2),
+        vec![(bid0, bs0.clone() as Arc<dyn Blobstore>)],
+        queue,
+        MultiplexPolicy::default(),
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+    assert!(plain
+        .enumerate(
+            ctx,
+            &BlobstoreKeyParam::Start(BlobstoreKeyRange::with_prefix("k"))
+        )
+        .compat()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
 #[fbinit::test]
 fn scrubbed(fb: FacebookInit) {
     async_unit::tokio_unit_test(async move {
@@ -707,6 +782,7 @@ fn queue_waits(fb: FacebookInit) {
                 (BlobstoreId::new(2), bs2.clone()),
             ],
             log.clone(),
+            MultiplexPolicy::default(),
             ScubaSampleBuilder::with_discard(),
             nonzero!(1u64),
         );
@@ -806,3 +882,223 @@ fn queue_waits(fb: FacebookInit) {
         }
     });
 }
+
+#[fbinit::test]
+fn write_quorum(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let waker = futures::task::noop_waker();
+        let mut task_ctx = Context::from_waker(&waker);
+
+        let bs0 = Arc::new(Tickable::new());
+        let bs1 = Arc::new(Tickable::new());
+        let bs2 = Arc::new(Tickable::new());
+        let log = Arc::new(LogHandler::new());
+        let bs = MultiplexedBlobstoreBase::new(
+            MultiplexId::new(1),
+            vec![
+                (BlobstoreId::new(0), bs0.clone()),
+                (BlobstoreId::new(1), bs1.clone()),
+                (BlobstoreId::new(2), bs2.clone()),
+            ],
+            log.clone(),
+            MultiplexPolicy {
+                write_quorum: nonzero!(2usize),
+                ..Default::default()
+            },
+            ScubaSampleBuilder::with_discard(),
+            nonzero!(1u64),
+        );
+        let ctx = CoreContext::test_mock(fb);
+
+        // Put waits for a second blobstore, even once the first is in the queue
+        {
+            let k0 = String::from("k0");
+            let v0 = make_value("v0");
+
+            let mut put_fut = bs
+                .put(ctx.clone(), k0.clone(), v0.clone())
+                .map_err(|_| ())
+                .compat()
+                .boxed();
+            assert_eq!(put_fut.poll_unpin(&mut task_ctx), Poll::Pending);
+            bs0.tick(None);
+            assert_eq!(put_fut.poll_unpin(&mut task_ctx), Poll::Pending);
+            assert!(log
+                .log
+                .with(|log| log == &vec![(BlobstoreId::new(0), k0.clone())]));
+            bs1.tick(None);
+            assert_eq!(put_fut.poll_unpin(&mut task_ctx), Poll::Ready(Ok(())));
+            assert_eq!(bs1.storage.with(|s| s.get(&k0).cloned()), Some(v0.clone()));
+            bs2.tick(None);
+
+            log.clear();
+        }
+
+        // Put fails once the quorum can no longer be reached
+        {
+            let k1 = String::from("k1");
+            let v1 = make_value("v1");
+
+            let mut put_fut = bs
+                .put(ctx.clone(), k1.clone(), v1.clone())
+                .map_err(|_| ())
+                .compat()
+                .boxed();
+            assert_eq!(put_fut.poll_unpin(&mut task_ctx), Poll::Pending);
+            bs0.tick(None);
+            bs1.tick(Some("bs1 failed"));
+            assert_eq!(put_fut.poll_unpin(&mut task_ctx), Poll::Pending);
+            bs2.tick(Some("bs2 failed"));
+            assert_eq!(put_fut.poll_unpin(&mut task_ctx), Poll::Ready(Err(())));
+        }
+    });
+}
+
+#[fbinit::test]
+fn read_preference(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let waker = futures::task::noop_waker();
+        let mut task_ctx = Context::from_waker(&waker);
+
+        let bs0 = Arc::new(Tickable::new());
+        let bs1 = Arc::new(Tickable::new());
+        let log = Arc::new(LogHandler::new());
+        let bs = MultiplexedBlobstoreBase::new(
+            MultiplexId::new(1),
+            vec![
+                (BlobstoreId::new(0), bs0.clone()),
+                (BlobstoreId::new(1), bs1.clone()),
+            ],
+            log.clone(),
+            MultiplexPolicy {
+                read_preference: vec![BlobstoreId::new(1)],
+                ..Default::default()
+            },
+            ScubaSampleBuilder::with_discard(),
+            nonzero!(1u64),
+        );
+        let ctx = CoreContext::test_mock(fb);
+
+        // Found in the preferred blobstore: the other one is not queried
+        {
+            let k0 = String::from("k0");
+            let v0 = make_value("v0");
+            bs1.storage.with(|s| s.insert(k0.clone(), v0.clone()));
+
+            let mut get_fut = bs.get(ctx.clone(), k0).map_err(|_| ()).compat().boxed();
+            assert_eq!(get_fut.poll_unpin(&mut task_ctx), Poll::Pending);
+            assert!(bs0.queue.with(|q| q.is_empty()));
+            bs1.tick(None);
+            assert_eq!(get_fut.await.unwrap(), Some(v0.into()));
+        }
+
+        // Missing from the preferred blobstore: fall back to the other one
+        {
+            let k1 = String::from("k1");
+            let v1 = make_value("v1");
+            bs0.storage.with(|s| s.insert(k1.clone(), v1.clone()));
+
+            let mut get_fut = bs.get(ctx.clone(), k1).map_err(|_| ()).compat().boxed();
+            assert_eq!(get_fut.poll_unpin(&mut task_ctx), Poll::Pending);
+            assert!(bs0.queue.with(|q| q.is_empty()));
+            bs1.tick(None);
+            assert_eq!(get_fut.poll_unpin(&mut task_ctx), Poll::Pending);
+            bs0.tick(None);
+            assert_eq!(get_fut.await.unwrap(), Some(v1.into()));
+        }
+    });
+}
+
+// Read repair puts to the blobstores in the background, so poll until the repaired value
+// shows up, and give up if it doesn't in a few seconds.
+async fn wait_for_value(
+    ctx: &CoreContext,
+    blobstore: &Arc<LazyMemblob>,
+    key: &str,
+    expected: &BlobstoreBytes,
+) -> Result<(), Error> {
+    let poll = {
+        cloned!(ctx, blobstore, expected);
+        let key = key.to_string();
+        async move {
+            loop {
+                let value = blobstore
+                    .get(ctx.clone(), key.clone())
+                    .compat()
+                    .await?
+                    .map(BlobstoreGetData::into_bytes);
+                if value.as_ref() == Some(&expected) {
+                    return Ok(());
+                }
+                Delay::new(Instant::now() + Duration::from_millis(10))
+                    .compat()
+                    .await?;
+            }
+        }
+    };
+    Timeout::new(poll.boxed().compat(), Duration::from_secs(10))
+        .compat()
+        .await
+        .map_err(|e| match e.into_inner() {
+            Some(e) => e,
+            None => format_err!("{} was not repaired in time", key),
+        })
+}
+
+#[fbinit::compat_test]
+async fn read_repair(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let bs0 = Arc::new(LazyMemblob::new());
+    let bs1 = Arc::new(LazyMemblob::new());
+    let bs2 = Arc::new(LazyMemblob::new());
+    let bs = MultiplexedBlobstoreBase::new(
+        MultiplexId::new(1),
+        vec![
+            (BlobstoreId::new(0), bs0.clone() as Arc<dyn Blobstore>),
+            (BlobstoreId::new(1), bs1.clone() as Arc<dyn Blobstore>),
+            (BlobstoreId::new(2), bs2.clone() as Arc<dyn Blobstore>),
+        ],
+        Arc::new(LogHandler::new()),
+        MultiplexPolicy {
+            read_repair: true,
+            ..Default::default()
+        },
+        ScubaSampleBuilder::with_discard(),
+        nonzero!(1u64),
+    );
+
+    // A blobstore missing the blob is repaired
+    {
+        let v0 = make_value("v0");
+        bs0.put(ctx.clone(), "k0".to_string(), v0.clone())
+            .compat()
+            .await?;
+        bs1.put(ctx.clone(), "k0".to_string(), v0.clone())
+            .compat()
+            .await?;
+
+        let value = bs.get(ctx.clone(), "k0".to_string()).compat().await?;
+        assert_eq!(value, Some(v0.clone().into()));
+        wait_for_value(&ctx, &bs2, "k0", &v0).await?;
+    }
+
+    // A blobstore with a different value is repaired to the majority value
+    {
+        let v1 = make_value("v1");
+        for blobstore in &[&bs0, &bs1] {
+            blobstore
+                .put(ctx.clone(), "k1".to_string(), v1.clone())
+                .compat()
+                .await?;
+        }
+        bs2.put(ctx.clone(), "k1".to_string(), make_value("corrupt"))
+            .compat()
+            .await?;
+
+        let value = bs.get(ctx.clone(), "k1".to_string()).compat().await?;
+        assert!(value.is_some());
+        wait_for_value(&ctx, &bs2, "k1", &v1).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::errors::ErrorKind;
+
+use anyhow::Error;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+// Leading bytes of a packed blob. Blobs without this were stored before packing
+// was enabled and are returned as they are.
+const MAGIC: &[u8] = b"\xfePK\x01";
+
+const TAG_RAW: u8 = 0;
+const TAG_ZSTD: u8 = 1;
+const TAG_DELTA: u8 = 2;
+
+// Tag plus unpacked length
+const HEADER_LEN: usize = 1 + 8;
+
+/// The format a blob is stored in. Every variant records the unpacked length, so the
+/// original size can be found without unpacking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Envelope {
+    /// Stored as is, as compressing would not make it smaller.
+    Raw(Bytes),
+    /// Compressed with zstd, using the named dictionary if there is one.
+    Zstd {
+        raw_len: u64,
+        dictionary: Option<String>,
+        compressed: Bytes,
+    },
+    /// A zstd delta against the blob stored at `base_key`, which is `depth - 1` deltas deep.
+    Delta {
+        raw_len: u64,
+        depth: u8,
+        base_key: String,
+        delta: Bytes,
+    },
+}
+
+impl Envelope {
+    pub fn raw_len(&self) -> u64 {
+        match self {
+            Envelope::Raw(data) => data.len() as u64,
+            Envelope::Zstd { raw_len, .. } => *raw_len,
+            Envelope::Delta { raw_len, .. } => *raw_len,
+        }
+    }
+
+    /// How many deltas have to be applied to unpack this blob.
+    pub fn depth(&self) -> u8 {
+        match self {
+            Envelope::Raw(_) | Envelope::Zstd { .. } => 0,
+            Envelope::Delta { depth, .. } => *depth,
+        }
+    }
+
+    pub fn encode(&self) -> Result<Bytes, Error> {
+        let mut buf = BytesMut::with_capacity(MAGIC.len() + HEADER_LEN);
+        buf.put_slice(MAGIC);
+        match self {
+            Envelope::Raw(data) => {
+                buf.reserve(data.len());
+                buf.put_u8(TAG_RAW);
+                buf.put_u64(data.len() as u64);
+                buf.put_slice(data);
+            }
+            Envelope::Zstd {
+                raw_len,
+                dictionary,
+                compressed,
+            } => {
+                let name = dictionary.as_ref().map_or("", |name| name.as_str());
+                if name.len() > u8::max_value() as usize {
+                    return Err(ErrorKind::NameTooLong(name.to_string()).into());
+                }
+                buf.reserve(1 + name.len() + compressed.len());
+                buf.put_u8(TAG_ZSTD);
+                buf.put_u64(*raw_len);
+                buf.put_u8(name.len() as u8);
+                buf.put_slice(name.as_bytes());
+                buf.put_slice(compressed);
+            }
+            Envelope::Delta {
+                raw_len,
+                depth,
+                base_key,
+                delta,
+            } => {
+                if base_key.len() > u16::max_value() as usize {
+                    return Err(ErrorKind::NameTooLong(base_key.clone()).into());
+                }
+                buf.reserve(1 + 2 + base_key.len() + delta.len());
+                buf.put_u8(TAG_DELTA);
+                buf.put_u64(*raw_len);
+                buf.put_u8(*depth);
+                buf.put_u16(base_key.len() as u16);
+                buf.put_slice(base_key.as_bytes());
+                buf.put_slice(delta);
+            }
+        }
+        Ok(buf.freeze())
+    }
+
+    /// Returns None if `bytes` is not a packed blob.
+    pub fn decode(bytes: &Bytes) -> Result<Option<Self>, Error> {
+        if !bytes.starts_with(MAGIC) {
+            return Ok(None);
+        }
+
+        let mut buf = bytes.slice(MAGIC.len()..);
+        if buf.remaining() < HEADER_LEN {
+            return Err(ErrorKind::InvalidEnvelope("truncated header").into());
+        }
+        let tag = buf.get_u8();
+        let raw_len = buf.get_u64();
+
+        let envelope = match tag {
+            TAG_RAW => {
+                if buf.len() as u64 != raw_len {
+                    return Err(ErrorKind::InvalidEnvelope("raw length mismatch").into());
+                }
+                Envelope::Raw(buf)
+            }
+            TAG_ZSTD => {
+                let name_len = get_u8(&mut buf)? as usize;
+                let name = split_string(&mut buf, name_len)?;
+                Envelope::Zstd {
+                    raw_len,
+                    dictionary: if name.is_empty() { None } else { Some(name) },
+                    compressed: buf,
+                }
+            }
+            TAG_DELTA => {
+                let depth = get_u8(&mut buf)?;
+                if depth == 0 {
+                    return Err(ErrorKind::InvalidEnvelope("delta with depth 0").into());
+                }
+                if buf.remaining() < 2 {
+                    return Err(ErrorKind::InvalidEnvelope("truncated base key").into());
+                }
+                let base_key_len = buf.get_u16() as usize;
+                let base_key = split_string(&mut buf, base_key_len)?;
+                Envelope::Delta {
+                    raw_len,
+                    depth,
+                    base_key,
+                    delta: buf,
+                }
+            }
+            _ => return Err(ErrorKind::InvalidEnvelope("unknown tag").into()),
+        };
+        Ok(Some(envelope))
+    }
+}
+
+fn get_u8(buf: &mut Bytes) -> Result<u8, Error> {
+    if buf.remaining() < 1 {
+        return Err(ErrorKind::InvalidEnvelope("truncated").into());
+    }
+    Ok(buf.get_u8())
+}
+
+fn split_string(buf: &mut Bytes, len: usize) -> Result<String, Error> {
+    if buf.remaining() < len {
+        return Err(ErrorKind::InvalidEnvelope("truncated name").into());
+    }
+    let name = buf.split_to(len);
+    String::from_utf8(name.to_vec())
+        .map_err(|_| ErrorKind::InvalidEnvelope("name is not utf-8").into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(envelope: Envelope) {
+        let encoded = envelope.encode().expect("encode should succeed");
+        assert_eq!(
+            Envelope::decode(&encoded).expect("decode should succeed"),
+            Some(envelope)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(Envelope::Raw(Bytes::from("raw data")));
+        round_trip(Envelope::Raw(Bytes::new()));
+        round_trip(Envelope::Zstd {
+            raw_len: 100,
+            dictionary: None,
+            compressed: Bytes::from("zstd"),
+        });
+        round_trip(Envelope::Zstd {
+            raw_len: 100,
+            dictionary: Some("content".to_string()),
+            compressed: Bytes::from("zstd"),
+        });
+        round_trip(Envelope::Delta {
+            raw_len: 100,
+            depth: 3,
+            base_key: "repo0000.content.blake2.abc".to_string(),
+            delta: Bytes::from("delta"),
+        });
+    }
+
+    #[test]
+    fn test_unpacked() {
+        assert_eq!(
+            Envelope::decode(&Bytes::from("plain old blob")).expect("decode should succeed"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let encoded = Envelope::Delta {
+            raw_len: 100,
+            depth: 1,
+            base_key: "base".to_string(),
+            delta: Bytes::new(),
+        }
+        .encode()
+        .expect("encode should succeed");
+        assert!(Envelope::decode(&encoded.slice(..encoded.len() - 2)).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("Invalid packed blob: {0}")]
+    InvalidEnvelope(&'static str),
+    #[error("Name too long to pack: {0}")]
+    NameTooLong(String),
+    #[error("Compression dictionary not found: {0}")]
+    DictionaryNotFound(String),
+    #[error("A different compression dictionary is already uploaded as {0}")]
+    DictionaryExists(String),
+    #[error("Delta base {base_key} of {key} not found")]
+    DeltaBaseNotFound { key: String, base_key: String },
+    #[error("Packed blob {key} is {depth} deltas deep, expected at most {max_depth}")]
+    DeltaTooDeep {
+        key: String,
+        depth: u8,
+        max_depth: u8,
+    },
+}
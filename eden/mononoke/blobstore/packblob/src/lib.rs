@@ -0,0 +1,858 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod envelope;
+mod errors;
+
+pub use crate::envelope::Envelope;
+pub use crate::errors::ErrorKind;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::future::{self, Future};
+use futures_ext::{BoxFuture, FutureExt};
+use slog::warn;
+
+use blobstore::{Blobstore, BlobstoreGetData};
+use context::CoreContext;
+use mononoke_types::{BlobstoreBytes, MPath};
+
+/// Dictionaries are stored unpacked in the underlying blobstore, under this prefix.
+pub const DICTIONARY_KEY_PREFIX: &str = "zstd_dictionary.";
+
+const DEFAULT_MAX_DELTA_DEPTH: u8 = 8;
+// zstd's default compression level
+const ZSTD_LEVEL: i32 = 0;
+
+#[derive(Clone, Debug)]
+struct PackDictionary {
+    key_prefix: String,
+    path_prefix: Option<MPath>,
+    name: String,
+}
+
+/// Which dictionaries to compress new blobs with, and how long delta chains can get.
+#[derive(Clone, Debug)]
+pub struct PackOptions {
+    dictionaries: Vec<PackDictionary>,
+    max_delta_depth: u8,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            dictionaries: Vec::new(),
+            max_delta_depth: DEFAULT_MAX_DELTA_DEPTH,
+        }
+    }
+}
+
+impl PackOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compress blobs whose key, ignoring any repo prefix, starts with `key_prefix` (e.g.
+    /// "content.blake2.") with the dictionary uploaded as `name`.
+    pub fn with_dictionary<P: Into<String>, N: Into<String>>(
+        mut self,
+        key_prefix: P,
+        name: N,
+    ) -> Self {
+        self.dictionaries.push(PackDictionary {
+            key_prefix: key_prefix.into(),
+            path_prefix: None,
+            name: name.into(),
+        });
+        self
+    }
+
+    /// Like `with_dictionary`, but only for blobs of files under `path_prefix` in the repo.
+    /// Those are only known when the blob is stored with `PackBlob::put_for_path`.
+    pub fn with_path_dictionary<P: Into<String>, N: Into<String>>(
+        mut self,
+        key_prefix: P,
+        path_prefix: MPath,
+        name: N,
+    ) -> Self {
+        self.dictionaries.push(PackDictionary {
+            key_prefix: key_prefix.into(),
+            path_prefix: Some(path_prefix),
+            name: name.into(),
+        });
+        self
+    }
+
+    /// Limit how many deltas a get may have to apply. 0 disables deltas.
+    pub fn with_max_delta_depth(mut self, max_delta_depth: u8) -> Self {
+        self.max_delta_depth = max_delta_depth;
+        self
+    }
+
+    // The dictionary for the most specific match: the longest path prefix wins, then the
+    // longest key prefix. Dictionaries for a path never match blobs stored without one.
+    fn dictionary_for(&self, key: &str, path: Option<&MPath>) -> Option<&str> {
+        let key = strip_repo_prefix(key);
+        self.dictionaries
+            .iter()
+            .filter(|dictionary| key.starts_with(dictionary.key_prefix.as_str()))
+            .filter(|dictionary| match (&dictionary.path_prefix, path) {
+                (None, _) => true,
+                (Some(path_prefix), Some(path)) => path_prefix.is_prefix_of(path),
+                (Some(_), None) => false,
+            })
+            .max_by_key(|dictionary| {
+                (
+                    dictionary
+                        .path_prefix
+                        .as_ref()
+                        .map_or(0, MPath::num_components),
+                    dictionary.key_prefix.len(),
+                )
+            })
+            .map(|dictionary| dictionary.name.as_str())
+    }
+}
+
+// Most keys start with the repo, e.g. "repo0001.content.blake2.<hash>"
+fn strip_repo_prefix(key: &str) -> &str {
+    match key.find('.') {
+        Some(dot)
+            if dot > 4
+                && key.starts_with("repo")
+                && key[4..dot].bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            &key[dot + 1..]
+        }
+        _ => key,
+    }
+}
+
+/// The size a stored blob unpacks to, or None if it is not packed.
+pub fn unpacked_size(bytes: &Bytes) -> Option<u64> {
+    Envelope::decode(bytes)
+        .ok()
+        .and_then(|envelope| envelope.map(|envelope| envelope.raw_len()))
+}
+
+/// Train a zstd dictionary of at most `max_size` bytes on samples of the blobs it will be used
+/// for, e.g. the files the walker's corpus subcommand writes out for a type and path.
+pub fn train_dictionary(samples: &[Bytes], max_size: usize) -> Result<Bytes, Error> {
+    let dictionary = zstd::dict::from_samples(samples, max_size)?;
+    Ok(Bytes::from(dictionary))
+}
+
+fn compress(dictionary: Option<(String, Bytes)>, data: Bytes) -> Result<Envelope, Error> {
+    let compressed = match dictionary {
+        Some((_, ref dictionary)) => {
+            zstd::block::Compressor::with_dict(dictionary.to_vec()).compress(&data, ZSTD_LEVEL)?
+        }
+        None => zstd::block::compress(&data, ZSTD_LEVEL)?,
+    };
+    if compressed.len() < data.len() {
+        Ok(Envelope::Zstd {
+            raw_len: data.len() as u64,
+            dictionary: dictionary.map(|(name, _)| name),
+            compressed: Bytes::from(compressed),
+        })
+    } else {
+        Ok(Envelope::Raw(data))
+    }
+}
+
+/// A layer over an existing blobstore that stores blobs zstd compressed, either with a
+/// dictionary trained for that type of blob (or for files under a path), or as a zstd delta
+/// against a similar blob (e.g. a previous version of the same file). Blobs are unpacked on
+/// get, and blobs stored before packing was enabled are returned as they are.
+#[derive(Clone)]
+pub struct PackBlob<T> {
+    blobstore: T,
+    options: Arc<PackOptions>,
+    dictionaries: Arc<Mutex<HashMap<String, Bytes>>>,
+    // Configured dictionaries found missing, so that each is only warned about once
+    missing_dictionaries: Arc<Mutex<HashSet<String>>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for PackBlob<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PackBlob")
+            .field("blobstore", &self.blobstore)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl<T> PackBlob<T> {
+    pub fn into_inner(self) -> T {
+        self.blobstore
+    }
+
+    pub fn as_inner(&self) -> &T {
+        &self.blobstore
+    }
+}
+
+impl<T: Blobstore + Clone> PackBlob<T> {
+    pub fn new(blobstore: T, options: PackOptions) -> Self {
+        Self {
+            blobstore,
+            options: Arc::new(options),
+            dictionaries: Arc::new(Mutex::new(HashMap::new())),
+            missing_dictionaries: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Upload a zstd dictionary, e.g. one from `train_dictionary` or `zstd --train`. Any other
+    /// data is used as raw content for zstd to refer back to. As blobs packed with a dictionary
+    /// need it to unpack, a dictionary can't be replaced by a different one once uploaded.
+    pub fn put_dictionary(
+        &self,
+        ctx: CoreContext,
+        name: String,
+        dictionary: Bytes,
+    ) -> BoxFuture<(), Error> {
+        if name.is_empty() || name.len() > u8::max_value() as usize {
+            return future::err(ErrorKind::NameTooLong(name).into()).boxify();
+        }
+
+        let this = self.clone();
+        self.find_dictionary(ctx.clone(), name.clone())
+            .and_then(move |existing| match existing {
+                Some(existing) if existing == dictionary => future::ok(()).boxify(),
+                Some(_) => future::err(ErrorKind::DictionaryExists(name).into()).boxify(),
+                None => {
+                    let dictionaries = this.dictionaries.clone();
+                    let missing_dictionaries = this.missing_dictionaries.clone();
+                    this.blobstore
+                        .put(
+                            ctx,
+                            [DICTIONARY_KEY_PREFIX, name.as_str()].concat(),
+                            BlobstoreBytes::from_bytes(dictionary.clone()),
+                        )
+                        .map(move |()| {
+                            missing_dictionaries
+                                .lock()
+                                .expect("lock poisoned")
+                                .remove(&name);
+                            dictionaries
+                                .lock()
+                                .expect("lock poisoned")
+                                .insert(name, dictionary);
+                        })
+                        .boxify()
+                }
+            })
+            .boxify()
+    }
+
+    fn find_dictionary(&self, ctx: CoreContext, name: String) -> BoxFuture<Option<Bytes>, Error> {
+        if let Some(dictionary) = self.dictionaries.lock().expect("lock poisoned").get(&name) {
+            return future::ok(Some(dictionary.clone())).boxify();
+        }
+
+        let dictionaries = self.dictionaries.clone();
+        self.blobstore
+            .get(ctx, [DICTIONARY_KEY_PREFIX, name.as_str()].concat())
+            .map(move |dictionary| {
+                dictionary.map(|dictionary| {
+                    let dictionary = dictionary.into_raw_bytes();
+                    dictionaries
+                        .lock()
+                        .expect("lock poisoned")
+                        .insert(name, dictionary.clone());
+                    dictionary
+                })
+            })
+            .boxify()
+    }
+
+    fn get_dictionary(&self, ctx: CoreContext, name: String) -> BoxFuture<Bytes, Error> {
+        self.find_dictionary(ctx, name.clone())
+            .and_then(move |dictionary| {
+                dictionary.ok_or_else(|| ErrorKind::DictionaryNotFound(name).into())
+            })
+            .boxify()
+    }
+
+    /// Store `value` compressed with the dictionary configured for its key and for the path of
+    /// the file it belongs to, which PackOptions::with_path_dictionary dictionaries need.
+    pub fn put_for_path(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        path: &MPath,
+    ) -> BoxFuture<(), Error> {
+        let dictionary = self
+            .options
+            .dictionary_for(&key, Some(path))
+            .map(String::from);
+        self.put_packed(ctx, key, value, dictionary)
+    }
+
+    // Store `value` compressed with the named dictionary. If it hasn't been uploaded yet, the
+    // value is compressed without one, so that configuring a dictionary ahead of uploading it
+    // doesn't break writes.
+    fn put_packed(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        dictionary: Option<String>,
+    ) -> BoxFuture<(), Error> {
+        let dictionary = match dictionary {
+            Some(name) => {
+                let missing_dictionaries = self.missing_dictionaries.clone();
+                self.find_dictionary(ctx.clone(), name.clone())
+                    .map({
+                        let ctx = ctx.clone();
+                        move |dictionary| match dictionary {
+                            Some(dictionary) => Some((name, dictionary)),
+                            None => {
+                                let newly_missing = missing_dictionaries
+                                    .lock()
+                                    .expect("lock poisoned")
+                                    .insert(name.clone());
+                                if newly_missing {
+                                    warn!(
+                                        ctx.logger(),
+                                        "Compression dictionary {} is not uploaded, packing without it",
+                                        name
+                                    );
+                                }
+                                None
+                            }
+                        }
+                    })
+                    .left_future()
+            }
+            None => future::ok(None).right_future(),
+        };
+
+        let blobstore = self.blobstore.clone();
+        dictionary
+            .and_then(move |dictionary| {
+                compress(dictionary, value.into_bytes()).and_then(|envelope| envelope.encode())
+            })
+            .and_then(move |packed| blobstore.put(ctx, key, BlobstoreBytes::from_bytes(packed)))
+            .boxify()
+    }
+
+    /// Store `value` as a zstd delta against the blob at `base_key`, which must already be
+    /// stored. Falls back to a normal put if the delta chain would get too long, or if the
+    /// delta is no smaller than `value`.
+    pub fn put_delta(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        base_key: String,
+    ) -> BoxFuture<(), Error> {
+        let this = self.clone();
+        self.get_unpacked(ctx.clone(), base_key.clone(), u8::max_value())
+            .and_then(move |base| -> BoxFuture<(), Error> {
+                let (base, base_depth) = match base {
+                    Some(base) => base,
+                    None => {
+                        return future::err(ErrorKind::DeltaBaseNotFound { key, base_key }.into())
+                            .boxify()
+                    }
+                };
+                if base_depth >= this.options.max_delta_depth {
+                    return this.put(ctx, key, value);
+                }
+
+                let data = value.into_bytes();
+                let packed = zstdelta::diff(base.as_raw_bytes(), &data)
+                    .map_err(Error::from)
+                    .and_then(|delta| {
+                        if delta.len() < data.len() {
+                            Envelope::Delta {
+                                raw_len: data.len() as u64,
+                                depth: base_depth + 1,
+                                base_key,
+                                delta: Bytes::from(delta),
+                            }
+                            .encode()
+                            .map(Some)
+                        } else {
+                            Ok(None)
+                        }
+                    });
+
+                match packed {
+                    Ok(Some(packed)) => {
+                        this.blobstore
+                            .put(ctx, key, BlobstoreBytes::from_bytes(packed))
+                    }
+                    Ok(None) => this.put(ctx, key, BlobstoreBytes::from_bytes(data)),
+                    Err(e) => future::err(e).boxify(),
+                }
+            })
+            .boxify()
+    }
+
+    // Get and unpack a blob, along with how many deltas deep it is. A blob deeper than
+    // `max_depth` is an error, so that a corrupt delta chain cannot loop forever.
+    fn get_unpacked(
+        &self,
+        ctx: CoreContext,
+        key: String,
+        max_depth: u8,
+    ) -> BoxFuture<Option<(BlobstoreGetData, u8)>, Error> {
+        let this = self.clone();
+        self.blobstore
+            .get(ctx.clone(), key.clone())
+            .and_then(move |data| -> BoxFuture<_, Error> {
+                let data = match data {
+                    Some(data) => data,
+                    None => return future::ok(None).boxify(),
+                };
+                let envelope = match Envelope::decode(data.as_raw_bytes()) {
+                    Ok(Some(envelope)) => envelope,
+                    Ok(None) => return future::ok(Some((data, 0))).boxify(),
+                    Err(e) => {
+                        return future::err(e.context(format!("While unpacking {}", key))).boxify()
+                    }
+                };
+                let depth = envelope.depth();
+                if depth > max_depth {
+                    return future::err(
+                        ErrorKind::DeltaTooDeep {
+                            key,
+                            depth,
+                            max_depth,
+                        }
+                        .into(),
+                    )
+                    .boxify();
+                }
+
+                let meta = data.as_meta().clone();
+                this.unpack(ctx, key, envelope)
+                    .map(move |bytes| {
+                        Some((
+                            BlobstoreGetData::new(meta, BlobstoreBytes::from_bytes(bytes)),
+                            depth,
+                        ))
+                    })
+                    .boxify()
+            })
+            .boxify()
+    }
+
+    fn unpack(&self, ctx: CoreContext, key: String, envelope: Envelope) -> BoxFuture<Bytes, Error> {
+        match envelope {
+            Envelope::Raw(data) => future::ok(data).boxify(),
+            Envelope::Zstd {
+                dictionary: None,
+                compressed,
+                raw_len,
+            } => future::result(zstd::block::decompress(&compressed, raw_len as usize))
+                .map(Bytes::from)
+                .from_err()
+                .boxify(),
+            Envelope::Zstd {
+                dictionary: Some(name),
+                compressed,
+                raw_len,
+            } => self
+                .get_dictionary(ctx, name)
+                .and_then(move |dictionary| -> Result<Bytes, Error> {
+                    let data = zstd::block::Decompressor::with_dict(dictionary.to_vec())
+                        .decompress(&compressed, raw_len as usize)?;
+                    Ok(Bytes::from(data))
+                })
+                .boxify(),
+            Envelope::Delta {
+                depth,
+                base_key,
+                delta,
+                ..
+            } => self
+                .get_unpacked(ctx, base_key.clone(), depth - 1)
+                .and_then(move |base| -> Result<Bytes, Error> {
+                    let (base, _base_depth) =
+                        base.ok_or_else(|| ErrorKind::DeltaBaseNotFound { key, base_key })?;
+                    let data = zstdelta::apply(base.as_raw_bytes(), &delta)?;
+                    Ok(Bytes::from(data))
+                })
+                .boxify(),
+        }
+    }
+}
+
+impl<T: Blobstore + Clone> Blobstore for PackBlob<T> {
+    fn get(&self, ctx: CoreContext, key: String) -> BoxFuture<Option<BlobstoreGetData>, Error> {
+        self.get_unpacked(ctx, key, u8::max_value())
+            .map(|data| data.map(|(data, _depth)| data))
+            .boxify()
+    }
+
+    fn put(&self, ctx: CoreContext, key: String, value: BlobstoreBytes) -> BoxFuture<(), Error> {
+        let dictionary = self.options.dictionary_for(&key, None).map(String::from);
+        self.put_packed(ctx, key, value, dictionary)
+    }
+
+    fn is_present(&self, ctx: CoreContext, key: String) -> BoxFuture<bool, Error> {
+        self.blobstore.is_present(ctx, key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use fbinit::FacebookInit;
+
+    use memblob::EagerMemblob;
+
+    fn stored_envelope(base: &EagerMemblob, ctx: CoreContext, key: &str) -> Envelope {
+        let stored = base
+            .get(ctx, key.to_string())
+            .wait()
+            .expect("get should succeed")
+            .expect("value should be present")
+            .into_raw_bytes();
+        Envelope::decode(&stored)
+            .expect("decode should succeed")
+            .expect("value should be packed")
+    }
+
+    fn file_version(version: usize) -> BlobstoreBytes {
+        let mut data = String::new();
+        for line in 0..1000 {
+            data.push_str(&format!(
+                "line {} of a file at version {}\n",
+                line,
+                line / 100
+            ));
+        }
+        data.push_str(&format!("version {}\n", version));
+        BlobstoreBytes::from_bytes(data)
+    }
+
+    #[test]
+    fn test_strip_repo_prefix() {
+        assert_eq!(
+            strip_repo_prefix("repo0001.content.blake2.abc"),
+            "content.blake2.abc"
+        );
+        assert_eq!(
+            strip_repo_prefix("repo12345.content.blake2.abc"),
+            "content.blake2.abc"
+        );
+        assert_eq!(
+            strip_repo_prefix("repo7.hgfilenode.sha1.abc"),
+            "hgfilenode.sha1.abc"
+        );
+        assert_eq!(strip_repo_prefix("repo.content"), "repo.content");
+        assert_eq!(strip_repo_prefix("repox1.content"), "repox1.content");
+        assert_eq!(
+            strip_repo_prefix("content.blake2.abc"),
+            "content.blake2.abc"
+        );
+        assert_eq!(strip_repo_prefix("repo0001"), "repo0001");
+    }
+
+    #[fbinit::test]
+    fn test_compress(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let packed = PackBlob::new(base.clone(), PackOptions::new());
+
+        // This is EagerMemblob (immediate future completion) so calling wait() is fine.
+        packed
+            .put(ctx.clone(), "compressible".to_string(), file_version(0))
+            .wait()
+            .expect("put should succeed");
+        packed
+            .put(
+                ctx.clone(),
+                "incompressible".to_string(),
+                BlobstoreBytes::from_bytes("x"),
+            )
+            .wait()
+            .expect("put should succeed");
+
+        match stored_envelope(&base, ctx.clone(), "compressible") {
+            Envelope::Zstd {
+                raw_len,
+                dictionary: None,
+                compressed,
+            } => {
+                assert_eq!(raw_len, file_version(0).len() as u64);
+                assert!(compressed.len() < file_version(0).len());
+            }
+            envelope => panic!("unexpected envelope {:?}", envelope),
+        }
+        assert_eq!(
+            stored_envelope(&base, ctx.clone(), "incompressible"),
+            Envelope::Raw(Bytes::from("x"))
+        );
+
+        for (key, value) in &[
+            ("compressible", file_version(0)),
+            ("incompressible", BlobstoreBytes::from_bytes("x")),
+        ] {
+            assert_eq!(
+                packed
+                    .get(ctx.clone(), key.to_string())
+                    .wait()
+                    .expect("get should succeed")
+                    .expect("value should be present")
+                    .into_bytes(),
+                *value
+            );
+        }
+    }
+
+    #[fbinit::test]
+    fn test_dictionary(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let options = PackOptions::new().with_dictionary("content.blake2.", "content");
+        let packed = PackBlob::new(base.clone(), options.clone());
+        let key = "repo0001.content.blake2.abc".to_string();
+
+        // No dictionary uploaded yet, so it is packed without
+        packed
+            .put(ctx.clone(), key.clone(), file_version(1))
+            .wait()
+            .expect("put should succeed");
+        match stored_envelope(&base, ctx.clone(), &key) {
+            Envelope::Zstd { dictionary, .. } => assert_eq!(dictionary, None),
+            envelope => panic!("unexpected envelope {:?}", envelope),
+        }
+
+        packed
+            .put_dictionary(
+                ctx.clone(),
+                "content".to_string(),
+                file_version(0).into_bytes(),
+            )
+            .wait()
+            .expect("put_dictionary should succeed");
+        // Uploading it again is fine, replacing it is not
+        packed
+            .put_dictionary(
+                ctx.clone(),
+                "content".to_string(),
+                file_version(0).into_bytes(),
+            )
+            .wait()
+            .expect("put_dictionary should succeed");
+        assert!(packed
+            .put_dictionary(
+                ctx.clone(),
+                "content".to_string(),
+                file_version(2).into_bytes(),
+            )
+            .wait()
+            .is_err());
+
+        packed
+            .put(ctx.clone(), key.clone(), file_version(1))
+            .wait()
+            .expect("put should succeed");
+
+        match stored_envelope(&base, ctx.clone(), &key) {
+            Envelope::Zstd { dictionary, .. } => {
+                assert_eq!(dictionary, Some("content".to_string()))
+            }
+            envelope => panic!("unexpected envelope {:?}", envelope),
+        }
+
+        // A new instance loads the dictionary from the blobstore
+        let reopened = PackBlob::new(base.clone(), options);
+        assert_eq!(
+            reopened
+                .get(ctx.clone(), key)
+                .wait()
+                .expect("get should succeed")
+                .expect("value should be present")
+                .into_bytes(),
+            file_version(1)
+        );
+    }
+
+    #[fbinit::test]
+    fn test_path_dictionary(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let options = PackOptions::new()
+            .with_dictionary("content.blake2.", "content")
+            .with_path_dictionary("content.blake2.", MPath::new("dir").unwrap(), "dir")
+            .with_path_dictionary("content.blake2.", MPath::new("dir/sub").unwrap(), "sub");
+        let packed = PackBlob::new(base.clone(), options);
+
+        for name in &["content", "dir", "sub"] {
+            packed
+                .put_dictionary(ctx.clone(), name.to_string(), file_version(0).into_bytes())
+                .wait()
+                .expect("put_dictionary should succeed");
+        }
+
+        let puts = vec![
+            ("repo0001.content.blake2.a", None, "content"),
+            ("repo0001.content.blake2.b", Some("other/file"), "content"),
+            ("repo0001.content.blake2.c", Some("dir/file"), "dir"),
+            ("repo0001.content.blake2.d", Some("dir/sub/file"), "sub"),
+            ("repo0001.content.blake2.e", Some("dirx/file"), "content"),
+        ];
+        for (key, path, _) in &puts {
+            let put = match path {
+                Some(path) => packed.put_for_path(
+                    ctx.clone(),
+                    key.to_string(),
+                    file_version(1),
+                    &MPath::new(path).unwrap(),
+                ),
+                None => packed.put(ctx.clone(), key.to_string(), file_version(1)),
+            };
+            put.wait().expect("put should succeed");
+        }
+
+        for (key, _, name) in &puts {
+            match stored_envelope(&base, ctx.clone(), key) {
+                Envelope::Zstd { dictionary, .. } => {
+                    assert_eq!(dictionary, Some(name.to_string()), "{}", key)
+                }
+                envelope => panic!("unexpected envelope {:?}", envelope),
+            }
+            assert_eq!(
+                packed
+                    .get(ctx.clone(), key.to_string())
+                    .wait()
+                    .expect("get should succeed")
+                    .expect("value should be present")
+                    .into_bytes(),
+                file_version(1)
+            );
+        }
+    }
+
+    #[test]
+    fn test_train_dictionary() {
+        let samples: Vec<_> = (0..100)
+            .map(|version| file_version(version).into_bytes())
+            .collect();
+        let dictionary = train_dictionary(&samples, 16 * 1024).expect("training should succeed");
+        assert!(!dictionary.is_empty() && dictionary.len() <= 16 * 1024);
+
+        let data = file_version(100).into_bytes();
+        match compress(Some(("trained".to_string(), dictionary)), data.clone())
+            .expect("compress should succeed")
+        {
+            Envelope::Zstd { compressed, .. } => assert!(compressed.len() < data.len()),
+            envelope => panic!("unexpected envelope {:?}", envelope),
+        }
+    }
+
+    #[fbinit::test]
+    fn test_delta(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let packed = PackBlob::new(base.clone(), PackOptions::new().with_max_delta_depth(1));
+
+        packed
+            .put(ctx.clone(), "v0".to_string(), file_version(0))
+            .wait()
+            .expect("put should succeed");
+        packed
+            .put_delta(
+                ctx.clone(),
+                "v1".to_string(),
+                file_version(1),
+                "v0".to_string(),
+            )
+            .wait()
+            .expect("put_delta should succeed");
+        // Too deep, so stored without a delta
+        packed
+            .put_delta(
+                ctx.clone(),
+                "v2".to_string(),
+                file_version(2),
+                "v1".to_string(),
+            )
+            .wait()
+            .expect("put_delta should succeed");
+
+        match stored_envelope(&base, ctx.clone(), "v1") {
+            Envelope::Delta {
+                depth, base_key, ..
+            } => {
+                assert_eq!(depth, 1);
+                assert_eq!(base_key, "v0".to_string());
+            }
+            envelope => panic!("unexpected envelope {:?}", envelope),
+        }
+        assert_eq!(stored_envelope(&base, ctx.clone(), "v2").depth(), 0);
+
+        for (key, version) in &[("v0", 0), ("v1", 1), ("v2", 2)] {
+            assert_eq!(
+                packed
+                    .get(ctx.clone(), key.to_string())
+                    .wait()
+                    .expect("get should succeed")
+                    .expect("value should be present")
+                    .into_bytes(),
+                file_version(*version)
+            );
+        }
+
+        assert!(packed
+            .put_delta(
+                ctx.clone(),
+                "v3".to_string(),
+                file_version(3),
+                "missing".to_string()
+            )
+            .wait()
+            .is_err());
+    }
+
+    #[fbinit::test]
+    fn test_unpacked_passthrough(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let packed = PackBlob::new(base.clone(), PackOptions::new());
+
+        base.put(ctx.clone(), "legacy".to_string(), file_version(0))
+            .wait()
+            .expect("put should succeed");
+        assert_eq!(
+            packed
+                .get(ctx.clone(), "legacy".to_string())
+                .wait()
+                .expect("get should succeed")
+                .expect("value should be present")
+                .into_bytes(),
+            file_version(0)
+        );
+        assert_eq!(unpacked_size(&file_version(0).into_bytes()), None);
+
+        packed
+            .put(ctx.clone(), "packed".to_string(), file_version(0))
+            .wait()
+            .expect("put should succeed");
+        let stored = base
+            .get(ctx, "packed".to_string())
+            .wait()
+            .expect("get should succeed")
+            .expect("value should be present")
+            .into_raw_bytes();
+        assert_eq!(unpacked_size(&stored), Some(file_version(0).len() as u64));
+    }
+}
@@ -8,11 +8,15 @@
 use anyhow::Error;
 use inlinable_string::InlinableString;
 
-use futures_ext::BoxFuture;
+use futures::Future;
+use futures_ext::{BoxFuture, FutureExt};
 
 use context::CoreContext;
 
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeyRange,
+    BlobstoreKeySource,
+};
 use mononoke_types::BlobstoreBytes;
 
 /// A layer over an existing blobstore that prepends a fixed string to each get and put.
@@ -43,6 +47,25 @@ impl<T: Blobstore + Clone> PrefixBlobstore<T> {
     pub fn prepend(&self, key: String) -> String {
         [&self.prefix, key.as_str()].concat()
     }
+
+    fn prepend_range(&self, range: &BlobstoreKeyRange) -> BlobstoreKeyRange {
+        BlobstoreKeyRange::new(
+            self.prepend(range.begin_key.clone()),
+            self.prepend(range.end_key.clone()),
+        )
+    }
+
+    fn prepend_param(&self, param: &BlobstoreKeyParam) -> BlobstoreKeyParam {
+        match param {
+            BlobstoreKeyParam::Start(range) => BlobstoreKeyParam::Start(self.prepend_range(range)),
+            BlobstoreKeyParam::Continuation { range, last_key } => {
+                BlobstoreKeyParam::Continuation {
+                    range: self.prepend_range(range),
+                    last_key: self.prepend(last_key.clone()),
+                }
+            }
+        }
+    }
 }
 
 impl<T: Blobstore + Clone> Blobstore for PrefixBlobstore<T> {
@@ -62,13 +85,49 @@ impl<T: Blobstore + Clone> Blobstore for PrefixBlobstore<T> {
     }
 }
 
+impl<T: BlobstoreKeySource + Clone> BlobstoreKeySource for PrefixBlobstore<T> {
+    fn enumerate(
+        &self,
+        ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        let prefix = self.prefix.to_string();
+        let prefix_len = prefix.len();
+        let param = param.clone();
+        self.blobstore
+            .enumerate(ctx, &self.prepend_param(&param))
+            .map(move |page| {
+                let keys = page
+                    .keys
+                    .into_iter()
+                    .filter(|key| key.starts_with(&prefix))
+                    .map(|key| key[prefix_len..].to_string())
+                    .collect();
+                let next_token = page.next_token.and_then(|token| match token {
+                    BlobstoreKeyParam::Continuation { last_key, .. }
+                        if last_key.starts_with(&prefix) =>
+                    {
+                        Some(BlobstoreKeyParam::Continuation {
+                            range: param.range().clone(),
+                            last_key: last_key[prefix_len..].to_string(),
+                        })
+                    }
+                    _ => None,
+                });
+                BlobstoreEnumerationData { keys, next_token }
+            })
+            .boxify()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use blobstore::enumerate_keys;
     use bytes::Bytes;
     use fbinit::FacebookInit;
-    use futures::Future;
+    use futures::Stream;
 
     use memblob::EagerMemblob;
 
@@ -119,4 +178,50 @@ mod test {
             .wait()
             .expect("is_present should succeed"));
     }
+
+    #[fbinit::test]
+    fn test_prefix_enumerate(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let base = EagerMemblob::new();
+        let prefixed = PrefixBlobstore::new(base.clone(), "prefix123-");
+
+        for key in &[
+            "other-a",
+            "prefix123-a",
+            "prefix123-b",
+            "prefix123-c",
+            "prefiy",
+        ] {
+            base.put(
+                ctx.clone(),
+                key.to_string(),
+                BlobstoreBytes::from_bytes("x"),
+            )
+            .wait()
+            .expect("put should succeed");
+        }
+
+        let keys = enumerate_keys(
+            ctx.clone(),
+            prefixed.clone(),
+            BlobstoreKeyParam::Start(BlobstoreKeyRange::new("a", "b")),
+        )
+        .collect()
+        .wait()
+        .expect("enumerate should succeed");
+        assert_eq!(keys, vec!["a".to_string(), "b".to_string()]);
+
+        let keys = enumerate_keys(
+            ctx.clone(),
+            prefixed,
+            BlobstoreKeyParam::Continuation {
+                range: BlobstoreKeyRange::with_prefix(""),
+                last_key: "a".to_string(),
+            },
+        )
+        .collect()
+        .wait()
+        .expect("enumerate should succeed");
+        assert_eq!(keys, vec!["b".to_string(), "c".to_string()]);
+    }
 }
@@ -6,7 +6,9 @@
  */
 
 use anyhow::Error;
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
+};
 use context::CoreContext;
 use futures::future;
 use futures_ext::{BoxFuture, FutureExt};
@@ -43,6 +45,17 @@ impl<T: Blobstore + Clone> Blobstore for ReadOnlyBlobstore<T> {
     }
 }
 
+impl<T: BlobstoreKeySource + Clone> BlobstoreKeySource for ReadOnlyBlobstore<T> {
+    #[inline]
+    fn enumerate(
+        &self,
+        ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        self.blobstore.enumerate(ctx, param)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
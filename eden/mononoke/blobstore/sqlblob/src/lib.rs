@@ -13,7 +13,10 @@ mod store;
 use crate::cache::{ChunkCacheTranslator, DataCacheTranslator, SqlblobCacheOps};
 use crate::store::{ChunkSqlStore, DataSqlStore};
 use anyhow::{format_err, Error, Result};
-use blobstore::{Blobstore, BlobstoreGetData, CountedBlobstore};
+use blobstore::{
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
+    BlobstoreUnlinkOps, CountedBlobstore,
+};
 use cacheblob::{dummy::DummyCache, CacheOps, MemcacheOps};
 use cloned::cloned;
 use context::CoreContext;
@@ -41,6 +44,8 @@ const MAX_KEY_SIZE: usize = 200;
 // In order to store blobs that can be stored in Memcache as well use the same max size as memcache
 // does, but leave some extra bytes for metadata
 const CHUNK_SIZE: usize = MEMCACHE_VALUE_MAX_SIZE - 1000;
+// Maximum number of keys returned by one call to enumerate
+const ENUMERATE_PAGE_SIZE: usize = 10000;
 const SQLITE_SHARD_NUM: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(2) };
 
 const COUNTED_ID: &str = "sqlblob";
@@ -422,9 +427,39 @@ impl<C: CacheOps> Blobstore for Sqlblob<C> {
     }
 }
 
+impl<C: CacheOps> BlobstoreKeySource for Sqlblob<C> {
+    fn enumerate(
+        &self,
+        _ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        cloned!(param);
+        // Ask for one more than a page so we know if there is another page to come
+        self.data_store
+            .enumerate(&param, (ENUMERATE_PAGE_SIZE + 1) as u64)
+            .map(move |keys| {
+                BlobstoreEnumerationData::from_sorted_keys(&param, keys, ENUMERATE_PAGE_SIZE)
+            })
+            .boxify()
+    }
+}
+
+impl<C: CacheOps> BlobstoreUnlinkOps for Sqlblob<C> {
+    fn unlink(&self, _ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        cloned!(self.chunk_store);
+        // Remove the data entry first so a concurrent get can't see it without its chunks.
+        // The caches are left alone, so a cached get may still succeed until expiry.
+        self.data_store
+            .unlink(&key)
+            .and_then(move |()| chunk_store.unlink(&key))
+            .boxify()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use blobstore::BlobstoreKeyRange;
     use bytes::Bytes;
     use fbinit::FacebookInit;
     use rand::{distributions::Alphanumeric, thread_rng, Rng, RngCore};
@@ -458,4 +493,49 @@ mod tests {
 
         tokio::run(fut);
     }
+
+    #[fbinit::test]
+    fn enumerate_and_unlink(fb: FacebookInit) {
+        let ctx = CoreContext::test_mock(fb);
+        let bs = Arc::new(Sqlblob::with_sqlite_in_memory().unwrap());
+
+        let puts: Vec<_> = vec!["a.1", "b.1", "b.2", "c.1"]
+            .into_iter()
+            .map(|key| {
+                bs.put(
+                    ctx.clone(),
+                    key.to_string(),
+                    BlobstoreBytes::from_bytes(Bytes::copy_from_slice(key.as_bytes())),
+                )
+            })
+            .collect();
+        let range = BlobstoreKeyParam::Start(BlobstoreKeyRange::new("b", "b\x7f"));
+
+        let fut = join_all(puts)
+            .and_then({
+                cloned!(ctx, bs, range);
+                move |_| bs.enumerate(ctx, &range)
+            })
+            .map(|data| {
+                assert_eq!(data.keys, vec!["b.1".to_string(), "b.2".to_string()]);
+                assert_eq!(data.next_token, None);
+            })
+            .and_then({
+                cloned!(ctx, bs);
+                move |()| bs.unlink(ctx, "b.1".to_string())
+            })
+            .and_then({
+                cloned!(ctx, bs);
+                move |()| bs.enumerate(ctx, &range)
+            })
+            .map(|data| assert_eq!(data.keys, vec!["b.2".to_string()]))
+            .and_then({
+                cloned!(ctx, bs);
+                move |()| bs.is_present(ctx, "b.1".to_string())
+            })
+            .map(|is_present| assert!(!is_present, "Blob should be unlinked"))
+            .map_err(|err| panic!("{:#?}", err));
+
+        tokio::run(fut);
+    }
 }
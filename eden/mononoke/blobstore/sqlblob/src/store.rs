@@ -11,12 +11,13 @@ use std::sync::Arc;
 
 use anyhow::{bail, format_err, Error};
 use fbthrift::compact_protocol;
+use futures::future::join_all;
 use futures::prelude::*;
 use futures_ext::FutureExt;
 use sql::{queries, Connection};
 use twox_hash::XxHash32;
 
-use blobstore::BlobstoreGetData;
+use blobstore::{BlobstoreGetData, BlobstoreKeyParam};
 use mononoke_types::BlobstoreBytes;
 use sqlblob_thrift::InChunk;
 
@@ -103,6 +104,26 @@ queries! {
          WHERE id = {id}"
     }
 
+    read SelectKeysInRange(begin: String, end: String, after: String, limit: u64) -> (String) {
+        "SELECT id
+         FROM data
+         WHERE id >= {begin}
+           AND id <= {end}
+           AND id > {after}
+         ORDER BY id
+         LIMIT {limit}"
+    }
+
+    write DeleteData(id: String) {
+        none,
+        "DELETE FROM data WHERE id = {id}"
+    }
+
+    write DeleteChunks(id: String) {
+        none,
+        "DELETE FROM chunk WHERE id = {id}"
+    }
+
     read SelectChunk(id: String, chunk_id: u32) -> (Vec<u8>) {
         "SELECT value
          FROM chunk
@@ -206,6 +227,41 @@ impl DataSqlStore {
         )
     }
 
+    /// Returns up to `limit` sorted keys from each shard, merged. Caller should truncate.
+    pub(crate) fn enumerate(
+        &self,
+        param: &BlobstoreKeyParam,
+        limit: u64,
+    ) -> impl Future<Item = Vec<String>, Error = Error> {
+        let range = param.range();
+        let (begin, end) = (range.begin_key.clone(), range.end_key.clone());
+        let after = match param {
+            BlobstoreKeyParam::Start(_) => String::new(),
+            BlobstoreKeyParam::Continuation { last_key, .. } => last_key.clone(),
+        };
+
+        let shard_futs: Vec<_> = self
+            .read_connection
+            .iter()
+            .map(|connection| SelectKeysInRange::query(connection, &begin, &end, &after, &limit))
+            .collect();
+
+        join_all(shard_futs).map(|shard_rows| {
+            let mut keys: Vec<String> =
+                shard_rows.into_iter().flatten().map(|(key,)| key).collect();
+            keys.sort();
+            keys.dedup();
+            keys
+        })
+    }
+
+    pub(crate) fn unlink(&self, key: &str) -> impl Future<Item = (), Error = Error> {
+        let key = key.to_owned();
+        let shard_id = self.shard(&key);
+
+        DeleteData::query(&self.write_connection[shard_id - 1], &key).map(|_| ())
+    }
+
     fn shard(&self, key: &str) -> usize {
         let mut hasher = XxHash32::with_seed(0);
         hasher.write(key.as_bytes());
@@ -279,6 +335,18 @@ impl ChunkSqlStore {
         .map(|_| ())
     }
 
+    /// Chunks for one key are spread over all shards, so remove from each of them.
+    pub(crate) fn unlink(&self, key: &str) -> impl Future<Item = (), Error = Error> {
+        let key = key.to_owned();
+        let shard_futs: Vec<_> = self
+            .write_connection
+            .iter()
+            .map(|connection| DeleteChunks::query(connection, &key))
+            .collect();
+
+        join_all(shard_futs).map(|_| ())
+    }
+
     fn shard(&self, key: &str, chunk_id: u32) -> usize {
         let mut hasher = XxHash32::with_seed(0);
         hasher.write(key.as_bytes());
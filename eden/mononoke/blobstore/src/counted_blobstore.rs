@@ -15,7 +15,10 @@ use stats::prelude::*;
 
 use context::CoreContext;
 
-use crate::{Blobstore, BlobstoreBytes, BlobstoreGetData};
+use crate::{
+    Blobstore, BlobstoreBytes, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam,
+    BlobstoreKeySource, BlobstoreUnlinkOps,
+};
 
 define_stats_struct! {
     CountedBlobstoreStats("mononoke.blobstore.{}", prefix: String),
@@ -122,6 +125,22 @@ impl<T: Blobstore> Blobstore for CountedBlobstore<T> {
     }
 }
 
+impl<T: BlobstoreKeySource> BlobstoreKeySource for CountedBlobstore<T> {
+    fn enumerate(
+        &self,
+        ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error> {
+        self.blobstore.enumerate(ctx, param)
+    }
+}
+
+impl<T: BlobstoreUnlinkOps> BlobstoreUnlinkOps for CountedBlobstore<T> {
+    fn unlink(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error> {
+        self.blobstore.unlink(ctx, key)
+    }
+}
+
 impl<T: Blobstore> Deref for CountedBlobstore<T> {
     type Target = T;
 
@@ -14,7 +14,8 @@ use std::fmt;
 use abomonation_derive::Abomonation;
 use anyhow::Error;
 use futures::future::{self, Future};
-use futures_ext::{BoxFuture, FutureExt};
+use futures::stream::{self, Stream};
+use futures_ext::{BoxFuture, BoxStream, FutureExt, StreamExt};
 use thiserror::Error;
 
 use context::CoreContext;
@@ -277,6 +278,175 @@ pub trait Blobstore: fmt::Debug + Send + Sync + 'static {
     }
 }
 
+/// A range of blobstore keys, inclusive of both `begin_key` and `end_key`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlobstoreKeyRange {
+    pub begin_key: String,
+    pub end_key: String,
+}
+
+impl BlobstoreKeyRange {
+    pub fn new<B: Into<String>, E: Into<String>>(begin_key: B, end_key: E) -> Self {
+        Self {
+            begin_key: begin_key.into(),
+            end_key: end_key.into(),
+        }
+    }
+
+    /// The range covering every key that starts with `prefix`.
+    pub fn with_prefix<P: Into<String>>(prefix: P) -> Self {
+        let begin_key = prefix.into();
+        let end_key = format!("{}\u{10ffff}", begin_key);
+        Self { begin_key, end_key }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.begin_key.as_str() <= key && key <= self.end_key.as_str()
+    }
+}
+
+/// Where to enumerate from. Either the start of a range, or carrying on from the
+/// last key returned by a previous call to `BlobstoreKeySource::enumerate`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlobstoreKeyParam {
+    Start(BlobstoreKeyRange),
+    Continuation {
+        range: BlobstoreKeyRange,
+        last_key: String,
+    },
+}
+
+impl BlobstoreKeyParam {
+    pub fn range(&self) -> &BlobstoreKeyRange {
+        match self {
+            BlobstoreKeyParam::Start(range) => range,
+            BlobstoreKeyParam::Continuation { range, .. } => range,
+        }
+    }
+
+    /// True if `key` is in the range and has not been returned by an earlier page.
+    pub fn wants(&self, key: &str) -> bool {
+        match self {
+            BlobstoreKeyParam::Start(range) => range.contains(key),
+            BlobstoreKeyParam::Continuation { range, last_key } => {
+                range.contains(key) && key > last_key.as_str()
+            }
+        }
+    }
+}
+
+/// One page of an enumeration. Keys are sorted. If `next_token` is Some there may be
+/// more keys to come, pass it back to `enumerate` to get them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobstoreEnumerationData {
+    pub keys: Vec<String>,
+    pub next_token: Option<BlobstoreKeyParam>,
+}
+
+impl BlobstoreEnumerationData {
+    /// Build a page from a sorted list of wanted keys, returning at most `limit` of them.
+    pub fn from_sorted_keys(
+        param: &BlobstoreKeyParam,
+        mut keys: Vec<String>,
+        limit: usize,
+    ) -> Self {
+        let next_token = if keys.len() > limit {
+            keys.truncate(limit);
+            keys.last().map(|last_key| BlobstoreKeyParam::Continuation {
+                range: param.range().clone(),
+                last_key: last_key.clone(),
+            })
+        } else {
+            None
+        };
+        Self { keys, next_token }
+    }
+
+    /// Combine pages fetched with the same `param` from several stores holding overlapping
+    /// key sets. Keys past the smallest point any store has reached are held back for the
+    /// next page, so resuming from the returned token does not skip anything.
+    pub fn merge(param: &BlobstoreKeyParam, pages: Vec<Self>) -> Self {
+        let bound = pages
+            .iter()
+            .filter_map(|page| match page.next_token {
+                Some(_) => page.keys.last().cloned(),
+                None => None,
+            })
+            .min();
+
+        let mut keys: Vec<String> = pages
+            .into_iter()
+            .flat_map(|page| page.keys.into_iter())
+            .filter(|key| bound.as_ref().map_or(true, |bound| key <= bound))
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        let next_token = bound.map(|last_key| BlobstoreKeyParam::Continuation {
+            range: param.range().clone(),
+            last_key,
+        });
+        Self { keys, next_token }
+    }
+}
+
+/// Optional capability for blobstores that can list the keys they hold. Only the
+/// underlying storage and wrappers that can map keys back (such as prefixing) implement
+/// this, the keys returned are the keys as seen by users of this blobstore.
+#[auto_impl(Arc, Box)]
+pub trait BlobstoreKeySource: Blobstore {
+    fn enumerate(
+        &self,
+        ctx: CoreContext,
+        param: &BlobstoreKeyParam,
+    ) -> BoxFuture<BlobstoreEnumerationData, Error>;
+}
+
+/// Optional capability for blobstores that can remove a key. This breaks the usual
+/// immutability guarantees, so it is only for offline tools such as garbage collection.
+/// Unlinking a key that is not present is not an error.
+#[auto_impl(Arc, Box)]
+pub trait BlobstoreUnlinkOps: Blobstore {
+    fn unlink(&self, ctx: CoreContext, key: String) -> BoxFuture<(), Error>;
+}
+
+/// Enumerate pages of keys from `param` until the range is exhausted. Each page carries
+/// the token needed to resume after it, so callers can checkpoint progress and restart
+/// from the last token they processed.
+pub fn enumerate_pages<B>(
+    ctx: CoreContext,
+    blobstore: B,
+    param: BlobstoreKeyParam,
+) -> BoxStream<BlobstoreEnumerationData, Error>
+where
+    B: BlobstoreKeySource + Clone,
+{
+    stream::unfold(Some(param), move |param| {
+        param.map(|param| {
+            blobstore.enumerate(ctx.clone(), &param).map(|page| {
+                let next = page.next_token.clone();
+                (page, next)
+            })
+        })
+    })
+    .boxify()
+}
+
+/// Enumerate all keys from `param` as a flat stream.
+pub fn enumerate_keys<B>(
+    ctx: CoreContext,
+    blobstore: B,
+    param: BlobstoreKeyParam,
+) -> BoxStream<String, Error>
+where
+    B: BlobstoreKeySource + Clone,
+{
+    enumerate_pages(ctx, blobstore, param)
+        .map(|page| stream::iter_ok(page.keys))
+        .flatten()
+        .boxify()
+}
+
 #[derive(Debug, Error)]
 pub enum LoadableError {
     #[error("Blobstore error")]
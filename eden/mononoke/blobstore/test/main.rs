@@ -19,7 +19,10 @@ use futures::Future;
 use tempdir::TempDir;
 use tokio::{prelude::*, runtime::Runtime};
 
-use blobstore::{Blobstore, BlobstoreGetData};
+use blobstore::{
+    enumerate_keys, Blobstore, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeyRange,
+    BlobstoreKeySource, BlobstoreUnlinkOps,
+};
 use context::CoreContext;
 use fileblob::Fileblob;
 use memblob::EagerMemblob;
@@ -112,6 +115,99 @@ where
     assert_eq!(out.into_raw_bytes(), Bytes::from_static(b"bar"));
 }
 
+fn enumerate<B>(fb: FacebookInit, blobstore: B)
+where
+    B: IntoFuture,
+    B::Item: BlobstoreKeySource + Clone,
+    B::Future: Send + 'static,
+    Error: From<B::Error>,
+{
+    let ctx = CoreContext::test_mock(fb);
+    let blobstore = blobstore.into_future().map_err(|err| err.into());
+
+    let fut = future::lazy(move || {
+        blobstore.and_then(move |blobstore| {
+            let puts: Vec<_> = vec!["enum.b", "enum.a", "enum.c", "enumz", "other"]
+                .into_iter()
+                .map(|key| {
+                    blobstore.put(
+                        ctx.clone(),
+                        key.to_string(),
+                        BlobstoreBytes::from_bytes(key.as_bytes()),
+                    )
+                })
+                .collect();
+            future::join_all(puts).and_then(move |_| {
+                let from_start = enumerate_keys(
+                    ctx.clone(),
+                    blobstore.clone(),
+                    BlobstoreKeyParam::Start(BlobstoreKeyRange::with_prefix("enum.")),
+                )
+                .collect();
+                let resumed = enumerate_keys(
+                    ctx,
+                    blobstore,
+                    BlobstoreKeyParam::Continuation {
+                        range: BlobstoreKeyRange::with_prefix("enum."),
+                        last_key: "enum.a".to_string(),
+                    },
+                )
+                .collect();
+                from_start.join(resumed)
+            })
+        })
+    });
+
+    let mut runtime = Runtime::new().expect("runtime creation failed");
+    let (from_start, resumed) = runtime.block_on(fut).expect("enumerate failed");
+
+    assert_eq!(from_start, vec!["enum.a", "enum.b", "enum.c"]);
+    assert_eq!(resumed, vec!["enum.b", "enum.c"]);
+}
+
+fn enumerate_and_unlink<B>(fb: FacebookInit, blobstore: B)
+where
+    B: IntoFuture,
+    B::Item: BlobstoreKeySource + BlobstoreUnlinkOps,
+    B::Future: Send + 'static,
+    Error: From<B::Error>,
+{
+    let ctx = CoreContext::test_mock(fb);
+    let blobstore = blobstore.into_future().map_err(|err| err.into());
+    let range = BlobstoreKeyParam::Start(BlobstoreKeyRange::new("repo0000.", "repo0000.\x7f"));
+
+    let fut = future::lazy(move || {
+        blobstore.and_then(move |blobstore| {
+            let puts: Vec<_> = vec!["repo0000.a", "repo0000.b c", "repo0001.a"]
+                .into_iter()
+                .map(|key| {
+                    blobstore.put(
+                        ctx.clone(),
+                        key.to_string(),
+                        BlobstoreBytes::from_bytes(key.as_bytes()),
+                    )
+                })
+                .collect();
+            future::join_all(puts)
+                .and_then({
+                    let ctx = ctx.clone();
+                    move |_| {
+                        blobstore
+                            .unlink(ctx, "repo0000.a".to_string())
+                            .map(|_| blobstore)
+                    }
+                })
+                .and_then(move |blobstore| blobstore.enumerate(ctx, &range))
+        })
+    });
+
+    let mut runtime = Runtime::new().expect("runtime creation failed");
+    let out = runtime.block_on(fut).expect("enumerate failed");
+
+    assert_eq!(out.keys, vec!["repo0000.b c".to_string()]);
+    assert_eq!(out.next_token, None);
+}
+
 macro_rules! blobstore_test_impl {
     ($mod_name: ident => {
         state: $state: expr,
@@ -140,6 +236,12 @@ macro_rules! blobstore_test_impl {
                 let state = $state;
                 boxable(fb, $new_cb(state.clone()));
             }
+
+            #[fbinit::test]
+            fn test_enumerate(fb: FacebookInit) {
+                let state = $state;
+                enumerate(fb, $new_cb(state.clone()));
+            }
         }
     };
 }
@@ -161,3 +263,54 @@ blobstore_test_impl! {
         has_ctime: true,
     }
 }
+
+#[fbinit::test]
+fn fileblob_enumerate_and_unlink(fb: FacebookInit) {
+    let dir = TempDir::new("fileblob_test").unwrap();
+    enumerate_and_unlink(fb, Fileblob::open(&dir));
+}
+
+#[fbinit::test]
+fn fileblob_enumerate_pages(fb: FacebookInit) {
+    let ctx = CoreContext::test_mock(fb);
+    let dir = TempDir::new("fileblob_test").unwrap();
+    let blobstore = Fileblob::open(&dir).unwrap().with_enumerate_page_size(2);
+    let range = BlobstoreKeyRange::with_prefix("enum.");
+    let mut runtime = Runtime::new().expect("runtime creation failed");
+
+    let put = |runtime: &mut Runtime, key: &str| {
+        runtime
+            .block_on(blobstore.put(
+                ctx.clone(),
+                key.to_string(),
+                BlobstoreBytes::from_bytes(key.as_bytes()),
+            ))
+            .expect("put failed")
+    };
+    for key in &["enum.a", "enum.b", "enum.c", "enum.d", "enum.e"] {
+        put(&mut runtime, key);
+    }
+
+    let first = runtime
+        .block_on(blobstore.enumerate(ctx.clone(), &BlobstoreKeyParam::Start(range.clone())))
+        .expect("enumerate failed");
+    assert_eq!(first.keys, vec!["enum.a", "enum.b"]);
+
+    // Pages after the first come from the listing taken when the enumeration started.
+    put(&mut runtime, "enum.bb");
+    let second = runtime
+        .block_on(blobstore.enumerate(ctx.clone(), first.next_token.as_ref().unwrap()))
+        .expect("enumerate failed");
+    assert_eq!(second.keys, vec!["enum.c", "enum.d"]);
+    let third = runtime
+        .block_on(blobstore.enumerate(ctx.clone(), second.next_token.as_ref().unwrap()))
+        .expect("enumerate failed");
+    assert_eq!(third.keys, vec!["enum.e"]);
+    assert_eq!(third.next_token, None);
+
+    // Once an enumeration is done, resuming from a token lists the directory again.
+    let resumed = runtime
+        .block_on(blobstore.enumerate(ctx, first.next_token.as_ref().unwrap()))
+        .expect("enumerate failed");
+    assert_eq!(resumed.keys, vec!["enum.bb", "enum.c"]);
+}
@@ -43,6 +43,7 @@ use metaconfig_types::{
     BlobConfig, CommonConfig, Redaction, RepoConfig, ScrubAction, StorageConfig,
 };
 use mononoke_types::RepositoryId;
+use permission_checker::init_acl_file;
 use sql_construct::SqlConstructFromMetadataDatabaseConfig;
 use sql_ext::facebook::MysqlOptions;
 use tunables::init_tunables_worker;
@@ -69,6 +70,7 @@ const RUNTIME_THREADS: &str = "runtime-threads";
 const CONFIG_PATH: &str = "mononoke-config-path";
 const TUNABLES_CONFIG: &str = "tunables-config";
 const DISABLE_TUNABLES: &str = "disable-tunables";
+const ACL_FILE: &str = "acl-file";
 
 const DEFAULT_TUNABLES_PATH: &str = "signed-configerator:scm/mononoke/tunables/default";
 
@@ -283,6 +285,7 @@ impl MononokeApp {
         app = add_cachelib_args(app, self.hide_advanced_args);
         app = add_runtime_args(app);
         app = add_tunables_args(app);
+        app = add_acl_args(app);
 
         if self.shutdown_timeout {
             app = add_shutdown_timeout_args(app);
@@ -313,6 +316,17 @@ pub fn add_tunables_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
             .help("Use the default values for all tunables (useful for tests)"),
     )
 }
+
+pub fn add_acl_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name(ACL_FILE)
+            .long(ACL_FILE)
+            .takes_value(true)
+            .value_name("PATH")
+            .help("A TOML or JSON file with the ACLs of repos and tiers, reloaded when it changes"),
+    )
+}
+
 pub fn add_runtime_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
     app.arg(
         Arg::with_name(RUNTIME_THREADS)
@@ -1106,10 +1120,21 @@ pub fn init_mononoke<'a>(
     debug!(logger, "Initialising runtime...");
     let runtime = init_runtime(matches)?;
     init_tunables(fb, matches, logger.clone())?;
+    init_acls(matches, logger.clone())?;
 
     Ok((caching, logger, runtime))
 }
 
+pub fn init_acls<'a>(matches: &ArgMatches<'a>, logger: Logger) -> Result<()> {
+    match matches.value_of(ACL_FILE) {
+        Some(path) => {
+            debug!(logger, "Loading ACLs from {}", path);
+            init_acl_file(logger, path)
+        }
+        None => Ok(()),
+    }
+}
+
 pub fn init_tunables<'a>(fb: FacebookInit, matches: &ArgMatches<'a>, logger: Logger) -> Result<()> {
     if matches.is_present(DISABLE_TUNABLES) {
         debug!(logger, "Tunables are disabled");
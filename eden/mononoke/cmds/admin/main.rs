@@ -27,6 +27,7 @@ use crate::hash_convert::subcommand_hash_convert;
 use crate::hg_changeset::subcommand_hg_changeset;
 use crate::hg_sync::subcommand_process_hg_sync;
 use crate::mutable_counters::subcommand_mutable_counters;
+use crate::packblob::subcommand_packblob;
 use crate::redaction::subcommand_redaction;
 use crate::skiplist_subcommand::subcommand_skiplist;
 
@@ -44,6 +45,7 @@ mod hash_convert;
 mod hg_changeset;
 mod hg_sync;
 mod mutable_counters;
+mod packblob;
 mod phases;
 mod redaction;
 mod skiplist_subcommand;
@@ -67,6 +69,7 @@ fn setup_app<'a, 'b>() -> App<'a, 'b> {
         .subcommand(hash_convert::build_subcommand())
         .subcommand(hg_sync::build_subcommand())
         .subcommand(mutable_counters::build_subcommand())
+        .subcommand(packblob::build_subcommand())
         .subcommand(redaction::build_subcommand())
         .subcommand(filenodes::build_subcommand())
         .subcommand(phases::build_subcommand())
@@ -122,6 +125,9 @@ fn main(fb: FacebookInit) -> ExitCode {
             (mutable_counters::MUTABLE_COUNTERS, Some(sub_m)) => {
                 subcommand_mutable_counters(fb, sub_m, &matches, logger.clone()).await
             }
+            (packblob::PACKBLOB, Some(sub_m)) => {
+                subcommand_packblob(fb, logger, &matches, sub_m).await
+            }
             (redaction::REDACTION, Some(sub_m)) => {
                 subcommand_redaction(fb, logger, &matches, sub_m).await
             }
@@ -0,0 +1,324 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{format_err, Error};
+use blobstore::{Blobstore, Loadable};
+use blobstore_factory::{make_blobstore, pack_options};
+use bytes::Bytes;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use cmdlib::{args, helpers};
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    stream::TryStreamExt,
+};
+use manifest::ManifestOps;
+use metaconfig_types::BlobConfig;
+use mononoke_types::{FileContents, MPath, MononokeId};
+use packblob::PackBlob;
+use slog::{info, Logger};
+
+use crate::error::SubcommandError;
+
+pub const PACKBLOB: &str = "packblob";
+const UPLOAD_DICTIONARY: &str = "upload-dictionary";
+const TRAIN_DICTIONARY: &str = "train-dictionary";
+const REPACK: &str = "repack";
+
+const ARG_NAME: &str = "NAME";
+const ARG_FILE: &str = "FILE";
+const ARG_CORPUS_DIR: &str = "CORPUS_DIR";
+const ARG_MAX_SIZE: &str = "max-size";
+const ARG_CHANGESET: &str = "CHANGESET_ID";
+const ARG_CONCURRENCY: &str = "concurrency";
+
+// zstd's default dictionary size
+const DEFAULT_MAX_DICTIONARY_SIZE: usize = 112_640;
+const DEFAULT_CONCURRENCY: usize = 100;
+
+pub fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+    let name_arg = Arg::with_name(ARG_NAME)
+        .help("name to upload the dictionary as, as used in the pack config")
+        .takes_value(true)
+        .required(true)
+        .index(1);
+
+    SubCommand::with_name(PACKBLOB)
+        .about("manage the compression dictionaries of a packblob store")
+        .subcommand(
+            SubCommand::with_name(UPLOAD_DICTIONARY)
+                .about("upload a zstd dictionary, e.g. one made by zstd --train")
+                .arg(name_arg.clone())
+                .arg(
+                    Arg::with_name(ARG_FILE)
+                        .help("file holding the dictionary")
+                        .takes_value(true)
+                        .required(true)
+                        .index(2),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(TRAIN_DICTIONARY)
+                .about("train a zstd dictionary on sample blobs and upload it")
+                .arg(name_arg)
+                .arg(
+                    Arg::with_name(ARG_CORPUS_DIR)
+                        .help(
+                            "directory of samples, e.g. the part of the walker corpus output \
+                             for the type and path the dictionary is for",
+                        )
+                        .takes_value(true)
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::with_name(ARG_MAX_SIZE)
+                        .long(ARG_MAX_SIZE)
+                        .help("maximum size of the dictionary in bytes")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(REPACK)
+                .about(
+                    "store the file contents of a changeset again, so that they are compressed \
+                     with the dictionaries now configured for their keys and paths",
+                )
+                .arg(
+                    Arg::with_name(ARG_CHANGESET)
+                        .help("hg/bonsai changeset id or bookmark")
+                        .takes_value(true)
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name(ARG_CONCURRENCY)
+                        .long(ARG_CONCURRENCY)
+                        .help("how many blobs to repack at once")
+                        .takes_value(true)
+                        .required(false),
+                ),
+        )
+}
+
+pub async fn subcommand_packblob<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+    sub_m: &'a ArgMatches<'_>,
+) -> Result<(), SubcommandError> {
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    let packblob = open_packblob(fb, logger.clone(), matches).await?;
+
+    match sub_m.subcommand() {
+        (UPLOAD_DICTIONARY, Some(sub_m)) => {
+            let name = sub_m.value_of(ARG_NAME).unwrap().to_string();
+            let file = sub_m.value_of(ARG_FILE).unwrap();
+
+            upload_dictionary_file(ctx, &packblob, name, Path::new(file)).await
+        }
+        (TRAIN_DICTIONARY, Some(sub_m)) => {
+            let name = sub_m.value_of(ARG_NAME).unwrap().to_string();
+            let corpus_dir = sub_m.value_of(ARG_CORPUS_DIR).unwrap();
+            let max_size = args::get_usize(sub_m, ARG_MAX_SIZE, DEFAULT_MAX_DICTIONARY_SIZE);
+
+            train_dictionary(ctx, &packblob, name, Path::new(corpus_dir), max_size).await
+        }
+        (REPACK, Some(sub_m)) => {
+            let rev = sub_m.value_of(ARG_CHANGESET).unwrap().to_string();
+            let concurrency = args::get_usize(sub_m, ARG_CONCURRENCY, DEFAULT_CONCURRENCY);
+
+            repack(ctx, fb, &logger, matches, packblob, rev, concurrency).await
+        }
+        (_, _) => Err(format_err!("unknown packblob subcommand")),
+    }
+    .map_err(SubcommandError::from)
+}
+
+// The repo's main blobstore, which has to be a packblob. Blobs read and written through it
+// use keys with the repo prefix, as the repo itself would.
+async fn open_packblob<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'_>,
+) -> Result<PackBlob<Arc<dyn Blobstore>>, Error> {
+    let (_, config) = args::get_config(fb, matches)?;
+    let (blobconfig, pack_config) = match config.storage_config.blobstore {
+        BlobConfig::Pack {
+            blobconfig,
+            pack_config,
+        } => (blobconfig, pack_config),
+        _ => return Err(format_err!("The repo's blobstore is not a packblob")),
+    };
+
+    let inner = make_blobstore(
+        fb,
+        *blobconfig,
+        args::parse_mysql_options(matches),
+        args::parse_readonly_storage(matches),
+        args::parse_blobstore_options(matches),
+        logger,
+    )
+    .compat()
+    .await?;
+
+    Ok(PackBlob::new(inner, pack_options(&pack_config)))
+}
+
+async fn upload_dictionary(
+    ctx: CoreContext,
+    packblob: &PackBlob<Arc<dyn Blobstore>>,
+    name: String,
+    dictionary: Bytes,
+) -> Result<(), Error> {
+    let size = dictionary.len();
+    packblob
+        .put_dictionary(ctx.clone(), name.clone(), dictionary)
+        .compat()
+        .await?;
+    info!(
+        ctx.logger(),
+        "Uploaded dictionary {} of {} bytes", name, size
+    );
+    Ok(())
+}
+
+async fn upload_dictionary_file(
+    ctx: CoreContext,
+    packblob: &PackBlob<Arc<dyn Blobstore>>,
+    name: String,
+    file: &Path,
+) -> Result<(), Error> {
+    let dictionary = Bytes::from(fs::read(file)?);
+    upload_dictionary(ctx, packblob, name, dictionary).await
+}
+
+async fn train_dictionary(
+    ctx: CoreContext,
+    packblob: &PackBlob<Arc<dyn Blobstore>>,
+    name: String,
+    corpus_dir: &Path,
+    max_size: usize,
+) -> Result<(), Error> {
+    let mut samples = Vec::new();
+    read_samples(corpus_dir, &mut samples)?;
+    if samples.is_empty() {
+        return Err(format_err!("No samples found in {}", corpus_dir.display()));
+    }
+
+    info!(
+        ctx.logger(),
+        "Training dictionary {} on {} samples",
+        name,
+        samples.len()
+    );
+    let dictionary = packblob::train_dictionary(&samples, max_size)?;
+    upload_dictionary(ctx, packblob, name, dictionary).await
+}
+
+fn read_samples(dir: &Path, samples: &mut Vec<Bytes>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_samples(&path, samples)?;
+        } else {
+            samples.push(Bytes::from(fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+// Contents are stored again in full: a blob that was a delta becomes a full blob, and no new
+// deltas are made, so that no delta chain can be broken by rewriting its base.
+async fn repack<'a>(
+    ctx: CoreContext,
+    fb: FacebookInit,
+    logger: &Logger,
+    matches: &'a ArgMatches<'_>,
+    packblob: PackBlob<Arc<dyn Blobstore>>,
+    rev: String,
+    concurrency: usize,
+) -> Result<(), Error> {
+    let repo = args::open_repo(fb, logger, matches).compat().await?;
+    let repo_prefix = repo.get_repoid().prefix();
+
+    let bcs_id = helpers::csid_resolve(ctx.clone(), repo.clone(), rev)
+        .compat()
+        .await?;
+    let hg_cs_id = repo
+        .get_hg_from_bonsai_changeset(ctx.clone(), bcs_id)
+        .compat()
+        .await?;
+    let hg_cs = hg_cs_id
+        .load(ctx.clone(), repo.blobstore())
+        .compat()
+        .await?;
+
+    // Identical contents at several paths are packed for the first path listed.
+    let mut seen = HashSet::new();
+    let mut keys = Vec::new();
+    let mut entries = hg_cs
+        .manifestid()
+        .list_leaf_entries(ctx.clone(), repo.get_blobstore())
+        .compat();
+    while let Some((path, (_, filenode_id))) = entries.try_next().await? {
+        let envelope = filenode_id
+            .load(ctx.clone(), repo.blobstore())
+            .compat()
+            .await?;
+        let content_id = envelope.content_id();
+        if !seen.insert(content_id) {
+            continue;
+        }
+
+        keys.push((content_id.blobstore_key(), path.clone()));
+        let contents = content_id
+            .load(ctx.clone(), repo.blobstore())
+            .compat()
+            .await?;
+        if let FileContents::Chunked(chunked) = contents {
+            for chunk in chunked.iter_chunks() {
+                keys.push((chunk.chunk_id().blobstore_key(), path.clone()));
+            }
+        }
+    }
+
+    info!(logger, "Repacking {} blobs", keys.len());
+    futures::stream::iter(keys.into_iter().map(Ok))
+        .try_for_each_concurrent(concurrency, |(key, path): (String, MPath)| {
+            let key = format!("{}{}", repo_prefix, key);
+            repack_blob(ctx.clone(), &packblob, key, path)
+        })
+        .await?;
+    info!(logger, "Repack complete");
+
+    Ok(())
+}
+
+async fn repack_blob(
+    ctx: CoreContext,
+    packblob: &PackBlob<Arc<dyn Blobstore>>,
+    key: String,
+    path: MPath,
+) -> Result<(), Error> {
+    let value = packblob
+        .get(ctx.clone(), key.clone())
+        .compat()
+        .await?
+        .ok_or_else(|| format_err!("Blob {} is missing", key))?;
+    packblob
+        .put_for_path(ctx, key, value.into_bytes(), &path)
+        .compat()
+        .await
+}
@@ -0,0 +1,97 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Error;
+use blobstore_factory::make_metadata_sql_factory;
+use bookmarks::BookmarkName;
+use clap::Arg;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::compat::Future01CompatExt;
+use segmented_changelog::manager::{SegmentedChangelogManager, SegmentedChangelogSqlConnections};
+
+use cmdlib::{args, helpers::block_execute};
+
+const NAME: &str = "segmented_changelog_tailer";
+const ARG_BOOKMARK: &str = "bookmark";
+const ARG_PERIOD: &str = "period";
+const DEFAULT_BOOKMARK: &str = "master";
+const DEFAULT_PERIOD_SECS: u64 = 60;
+
+#[fbinit::main]
+fn main(fb: FacebookInit) -> Result<(), Error> {
+    let matches = args::MononokeApp::new(NAME)
+        .with_advanced_args_hidden()
+        .build()
+        .version("0.0.0")
+        .about(
+            "Keep the saved segmented changelog of a repo up to date with a bookmark. \
+             Servers reload it as it is saved.",
+        )
+        .arg(
+            Arg::with_name(ARG_BOOKMARK)
+                .long(ARG_BOOKMARK)
+                .takes_value(true)
+                .required(false)
+                .help("bookmark whose ancestors the segmented changelog covers"),
+        )
+        .arg(
+            Arg::with_name(ARG_PERIOD)
+                .long(ARG_PERIOD)
+                .takes_value(true)
+                .required(false)
+                .help("how often to check the bookmark, in seconds"),
+        )
+        .get_matches();
+
+    args::init_cachelib(fb, &matches, None);
+
+    let logger = args::init_logging(fb, &matches);
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+
+    let bookmark = BookmarkName::new(matches.value_of(ARG_BOOKMARK).unwrap_or(DEFAULT_BOOKMARK))?;
+    let period =
+        Duration::from_secs(args::get_u64_opt(&matches, ARG_PERIOD).unwrap_or(DEFAULT_PERIOD_SECS));
+    let (_, config) = args::get_config(fb, &matches)?;
+    let mysql_options = args::parse_mysql_options(&matches);
+    let readonly_storage = args::parse_readonly_storage(&matches);
+
+    let blobrepo = args::open_repo(fb, &logger, &matches);
+    let tailer = async move {
+        let blobrepo = blobrepo.compat().await?;
+        let sql_factory = make_metadata_sql_factory(
+            fb,
+            config.storage_config.metadata,
+            mysql_options,
+            readonly_storage,
+            logger.clone(),
+        )
+        .compat()
+        .await?;
+        let connections = sql_factory
+            .open::<SegmentedChangelogSqlConnections>()
+            .compat()
+            .await?;
+        let manager =
+            SegmentedChangelogManager::new(connections, Arc::new(blobrepo.get_blobstore()));
+        manager.run_tailer(&ctx, &blobrepo, &bookmark, period).await
+    };
+
+    block_execute(
+        tailer,
+        fb,
+        NAME,
+        &logger,
+        &matches,
+        cmdlib::monitoring::AliveService,
+    )
+}
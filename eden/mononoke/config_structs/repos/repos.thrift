@@ -118,6 +118,14 @@ struct RawRepoConfig {
     // Name of this repository in hgsql for globalrevs. Required for syncing
     // globalrevs through the sync job.
     37: optional string hgsql_globalrevs_name,
+
+    // Serve ancestry queries from a saved segmented changelog
+    38: optional RawSegmentedChangelogConfig segmented_changelog_config,
+}
+
+struct RawSegmentedChangelogConfig {
+  // Defaults to false
+  1: optional bool enabled,
 }
 
 struct RawDerivedDataConfig {
@@ -155,6 +163,14 @@ struct RawBlobstoreMultiplexed {
     3: optional i64 scuba_sample_rate,
     4: optional i32 multiplex_id,
     5: optional RawDbConfig queue_db,
+    // Number of components a put must be written to before it succeeds.
+    // Defaults to 1, the healer copies the blob to the other components.
+    6: optional i64 write_quorum,
+    // Components to read from first. The others are only read from if
+    // none of these has the blob. Defaults to reading from all at once.
+    7: optional list<i64> read_preference,
+    // Write a blob back to components a get finds it missing or different on
+    8: optional bool read_repair,
 }
 struct RawBlobstoreManifoldWithTtl {
     1: string manifold_bucket,
@@ -162,6 +178,36 @@ struct RawBlobstoreManifoldWithTtl {
     3: i64 ttl_secs,
 }
 
+struct RawPackDictionary {
+    // Blobs whose key, ignoring the repo prefix, starts with this are
+    // compressed with the dictionary
+    1: string key_prefix,
+    // Name the dictionary was uploaded under
+    2: string dictionary,
+    // Only use the dictionary for blobs of files under this repo path. The
+    // path is only known to tools that pack blobs for a path, such as
+    // `admin packblob repack`.
+    3: optional string path_prefix,
+}
+
+struct RawBlobstorePack {
+    1: RawBlobstoreConfig blobstore,
+    2: optional list<RawPackDictionary> dictionaries,
+    // Maximum length of delta chains, 0 disables deltas
+    3: optional i32 max_delta_depth,
+}
+
+// Cache the blobs of another blobstore in a local directory. Blobstores
+// configured with the same directory in one process share the cache.
+struct RawBlobstoreDiskCache {
+    1: RawBlobstoreConfig blobstore,
+    2: string path,
+    // Total size of the cache in bytes
+    3: i64 max_size,
+    // Blobs larger than this many bytes are not cached
+    4: optional i64 max_blob_size,
+}
+
 // Configuration for a single blobstore. These are intended to be defined in a
 // separate blobstore.toml config file, and then referenced by name from a
 // per-server config. Names are only necessary for blobstores which are going
@@ -177,6 +223,8 @@ union RawBlobstoreConfig {
     6: RawBlobstoreMysql mysql,
     7: RawBlobstoreMultiplexed multiplexed,
     8: RawBlobstoreManifoldWithTtl manifold_with_ttl,
+    9: RawBlobstorePack pack,
+    10: RawBlobstoreDiskCache disk_cache,
 }
 
 struct RawBlobstoreIdConfig {
@@ -292,6 +340,8 @@ struct RawHookConfig {
     5: optional string bypass_pushvar,
     6: optional map<string, string> (rust.type = "HashMap") config_strings,
     7: optional map<string, i32> (rust.type = "HashMap") config_ints,
+    /// Report rejections from this hook as warnings instead of blocking the push
+    8: optional bool advisory,
 }
 
 struct RawLfsParams {
@@ -303,6 +353,8 @@ struct RawLfsParams {
     // If a hostname is in this smc tier then it will get
     // lfs pointers regardless of rollout_percentage
     4: optional string rollout_smc_tier,
+    // Whether the LFS server serves the Git LFS locking API for this repo
+    5: optional bool locking_enabled,
 }
 
 struct RawBundle2ReplayParams {
@@ -0,0 +1,146 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Endpoints for clients that clone the commit graph as segments (segmented
+//! changelog) and resolve commit hashes lazily, when they need them.
+
+use anyhow::format_err;
+use bytes::Bytes;
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use serde::Deserialize;
+
+use dag::protocol::CloneData;
+use gotham_ext::{error::HttpError, response::BytesBody};
+use mononoke_api::{HgChangesetId, Location};
+use types::api::{
+    CommitHashToLocationRequestBatch, CommitHashToLocationResponse, CommitLocation,
+    CommitLocationToHashRequestBatch, CommitLocationToHashResponse,
+};
+
+use crate::utils::{
+    cbor_mime, cbor_response, get_repo, map_mononoke_error, parse_cbor_request, to_hg_changeset_id,
+    to_hgid,
+};
+
+// Most hashes a client can ask for in one location_to_hash request
+const MAX_LOCATION_TO_HASH_COUNT: u64 = 10_000;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct CommitParams {
+    repo: String,
+}
+
+/// Segments of the commit graph and the hashes needed to resolve the others.
+pub async fn clone_data(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = CommitParams::take_from(state);
+    let repo = get_repo(state, &params.repo).await?;
+
+    let clone_data = repo
+        .segmented_changelog_clone_data()
+        .await
+        .map_err(map_mononoke_error)?;
+    let clone_data = CloneData {
+        head_id: clone_data.head_id,
+        flat_segments: clone_data.flat_segments,
+        idmap: clone_data
+            .idmap
+            .into_iter()
+            .map(|(id, hg_cs_id)| (id, to_hgid(hg_cs_id)))
+            .collect(),
+    };
+
+    let bytes: Bytes = serde_cbor::to_vec(&clone_data)
+        .map_err(HttpError::e500)?
+        .into();
+    Ok(BytesBody::new(bytes, cbor_mime()))
+}
+
+/// Translate commit locations into hashes.
+pub async fn location_to_hash(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = CommitParams::take_from(state);
+    let repo = get_repo(state, &params.repo).await?;
+    let batch: CommitLocationToHashRequestBatch = parse_cbor_request(state).await?;
+
+    let mut total_count: u64 = 0;
+    for request in &batch.requests {
+        if request
+            .location
+            .distance
+            .checked_add(request.count)
+            .is_none()
+        {
+            return Err(HttpError::e400(format_err!(
+                "invalid count {} for location at distance {}",
+                request.count,
+                request.location.distance
+            )));
+        }
+        total_count = total_count.saturating_add(request.count);
+    }
+    if total_count > MAX_LOCATION_TO_HASH_COUNT {
+        return Err(HttpError::e400(format_err!(
+            "too many hashes requested: {} (max {})",
+            total_count,
+            MAX_LOCATION_TO_HASH_COUNT
+        )));
+    }
+
+    let mut responses = Vec::with_capacity(batch.requests.len());
+    for request in batch.requests {
+        let location = Location::new(
+            to_hg_changeset_id(request.location.descendant),
+            request.location.distance,
+        );
+        let hgids = repo
+            .location_to_hg_changeset_ids(location, request.count)
+            .await
+            .map_err(map_mononoke_error)?
+            .into_iter()
+            .map(to_hgid)
+            .collect();
+        responses.push(CommitLocationToHashResponse {
+            location: request.location,
+            count: request.count,
+            hgids,
+        });
+    }
+
+    cbor_response(responses)
+}
+
+/// Translate commit hashes into locations relative to the client's master heads.
+pub async fn hash_to_location(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = CommitParams::take_from(state);
+    let repo = get_repo(state, &params.repo).await?;
+    let batch: CommitHashToLocationRequestBatch = parse_cbor_request(state).await?;
+
+    let master_heads: Vec<_> = batch
+        .master_heads
+        .into_iter()
+        .map(to_hg_changeset_id)
+        .collect();
+    let mut responses = Vec::with_capacity(batch.hgids.len());
+    for hgid in batch.hgids {
+        let location = repo
+            .hg_changeset_id_to_location(master_heads.clone(), to_hg_changeset_id(hgid))
+            .await
+            .map_err(map_mononoke_error)?;
+        if let Some(location) = location {
+            responses.push(CommitHashToLocationResponse {
+                hgid,
+                location: to_commit_location(location),
+            });
+        }
+    }
+
+    cbor_response(responses)
+}
+
+fn to_commit_location(location: Location<HgChangesetId>) -> CommitLocation {
+    CommitLocation::new(to_hgid(location.descendant), location.distance)
+}
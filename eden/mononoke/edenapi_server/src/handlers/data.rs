@@ -0,0 +1,136 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Endpoints serving file and tree content, in the form Mercurial's data
+//! store expects it.
+
+use anyhow::format_err;
+use bytes::Bytes;
+use futures::{stream, StreamExt, TryStreamExt};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use serde::Deserialize;
+
+use gotham_ext::{error::HttpError, response::BytesBody};
+use mercurial_types::{HgFileNodeId, HgManifestId, HgNodeHash};
+use mononoke_api::hg::HgRepoContext;
+use types::{
+    api::{DataRequest, DataResponse, TreeRequest},
+    DataEntry, HgId, Key,
+};
+
+use crate::utils::{
+    cbor_response, get_repo, map_mononoke_error, parse_cbor_request, to_mononoke_path,
+    to_repo_path, StreamParams,
+};
+
+/// Maximum number of files or trees fetched from the blobstore at once.
+const MAX_CONCURRENT_FETCHES: usize = 10;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct DataParams {
+    repo: String,
+}
+
+/// Content and parents of the requested filenodes.
+pub async fn files(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = DataParams::take_from(state);
+    let stream = StreamParams::take_from(state).stream();
+    let repo = get_repo(state, &params.repo).await?;
+    let request: DataRequest = parse_cbor_request(state).await?;
+
+    let entries = stream::iter(request.keys)
+        .map(|key| fetch_file(&repo, key))
+        .buffer_unordered(MAX_CONCURRENT_FETCHES)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    data_response(entries, stream)
+}
+
+/// Content and parents of the requested tree manifests.
+pub async fn trees(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = DataParams::take_from(state);
+    let stream = StreamParams::take_from(state).stream();
+    let repo = get_repo(state, &params.repo).await?;
+    let request: DataRequest = parse_cbor_request(state).await?;
+
+    let entries = stream::iter(request.keys)
+        .map(|key| fetch_tree(&repo, key))
+        .buffer_unordered(MAX_CONCURRENT_FETCHES)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    data_response(entries, stream)
+}
+
+/// The trees under `rootdir` in `mfnodes` that are not in `basemfnodes`, as
+/// Mercurial's `gettreepack` wire protocol command returns them.
+pub async fn prefetch_trees(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = DataParams::take_from(state);
+    let stream = StreamParams::take_from(state).stream();
+    let repo = get_repo(state, &params.repo).await?;
+    let request: TreeRequest = parse_cbor_request(state).await?;
+
+    let rootdir = to_mononoke_path(&request.rootdir)?;
+    let to_manifest_id = |hgid: HgId| HgManifestId::new(HgNodeHash::from(hgid));
+    let entries = repo
+        .trees_under_path(
+            rootdir,
+            request.mfnodes.into_iter().map(to_manifest_id),
+            request.basemfnodes.into_iter().map(to_manifest_id),
+            request.depth,
+        )
+        .map_err(map_mononoke_error)
+        .and_then(|(tree, path)| async move {
+            let key = Key::new(to_repo_path(&path)?, tree.node_id().into_nodehash().into());
+            Ok::<_, HttpError>(DataEntry::new(
+                key,
+                tree.content(),
+                tree.hg_parents().into(),
+            ))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    data_response(entries, stream)
+}
+
+async fn fetch_file(repo: &HgRepoContext, key: Key) -> Result<DataEntry, HttpError> {
+    let filenode_id = HgFileNodeId::new(HgNodeHash::from(key.hgid));
+    let file = repo
+        .file(filenode_id)
+        .await
+        .map_err(map_mononoke_error)?
+        .ok_or_else(|| HttpError::e404(format_err!("file not found: {}", key)))?;
+    let content = file.content().await.map_err(map_mononoke_error)?;
+    Ok(DataEntry::new(key, content, file.hg_parents().into()))
+}
+
+async fn fetch_tree(repo: &HgRepoContext, key: Key) -> Result<DataEntry, HttpError> {
+    let manifest_id = HgManifestId::new(HgNodeHash::from(key.hgid));
+    let tree = repo
+        .tree(manifest_id)
+        .await
+        .map_err(map_mononoke_error)?
+        .ok_or_else(|| HttpError::e404(format_err!("tree not found: {}", key)))?;
+    Ok(DataEntry::new(
+        key,
+        tree.content(),
+        tree.hg_parents().into(),
+    ))
+}
+
+/// Streaming clients read the entries one at a time, the others read them
+/// all in one `DataResponse`.
+fn data_response(entries: Vec<DataEntry>, stream: bool) -> Result<BytesBody<Bytes>, HttpError> {
+    if stream {
+        cbor_response(entries)
+    } else {
+        cbor_response(Some(DataResponse::new(entries)))
+    }
+}
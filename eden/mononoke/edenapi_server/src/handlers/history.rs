@@ -0,0 +1,86 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Endpoint serving the history of files, as Mercurial's history store
+//! expects it.
+
+use std::convert::TryFrom;
+
+use anyhow::format_err;
+use bytes::Bytes;
+use futures::{stream, StreamExt, TryStreamExt};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use serde::Deserialize;
+
+use gotham_ext::{error::HttpError, response::BytesBody};
+use mercurial_types::{HgFileNodeId, HgNodeHash};
+use mononoke_api::hg::HgRepoContext;
+use types::{
+    api::{HistoryRequest, HistoryResponse},
+    Key, RepoPathBuf, WireHistoryEntry,
+};
+
+use crate::utils::{
+    cbor_response, get_repo, map_mononoke_error, parse_cbor_request, to_hg_path, StreamParams,
+};
+
+/// Maximum number of file histories fetched at once.
+const MAX_CONCURRENT_FETCHES: usize = 10;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct HistoryParams {
+    repo: String,
+}
+
+/// History of the requested filenodes, up to `depth` ancestors of each.
+pub async fn history(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = HistoryParams::take_from(state);
+    let stream = StreamParams::take_from(state).stream();
+    let repo = get_repo(state, &params.repo).await?;
+    let request: HistoryRequest = parse_cbor_request(state).await?;
+    let depth = request.depth;
+
+    let histories = stream::iter(request.keys)
+        .map(|key| fetch_history(&repo, key, depth))
+        .buffer_unordered(MAX_CONCURRENT_FETCHES)
+        .try_collect::<Vec<_>>()
+        .await?;
+    let entries = histories.into_iter().flatten();
+
+    if stream {
+        cbor_response(entries)
+    } else {
+        cbor_response(Some(HistoryResponse::new(entries)))
+    }
+}
+
+async fn fetch_history(
+    repo: &HgRepoContext,
+    key: Key,
+    depth: Option<u32>,
+) -> Result<Vec<(RepoPathBuf, WireHistoryEntry)>, HttpError> {
+    let filenode_id = HgFileNodeId::new(HgNodeHash::from(key.hgid));
+    let file = repo
+        .file(filenode_id)
+        .await
+        .map_err(map_mononoke_error)?
+        .ok_or_else(|| HttpError::e404(format_err!("file not found: {}", key)))?;
+    let path = to_hg_path(&key.path)?;
+
+    file.history(path, depth)
+        .map_err(map_mononoke_error)
+        .and_then(|entry| {
+            let path = key.path.clone();
+            async move {
+                let entry = WireHistoryEntry::try_from(entry).map_err(HttpError::e500)?;
+                Ok((path, entry))
+            }
+        })
+        .try_collect()
+        .await
+}
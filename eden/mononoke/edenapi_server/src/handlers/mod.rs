@@ -22,8 +22,14 @@ use gotham::{
 use gotham_ext::response::build_response;
 
 use crate::context::ServerContext;
+use crate::utils::StreamParams;
 
+mod commit;
+mod data;
+mod history;
+mod push;
 mod repos;
+mod upload;
 
 pub fn build_router(ctx: ServerContext) -> Router {
     let pipeline = new_pipeline().add(StateMiddleware::new(ctx)).build();
@@ -35,6 +41,66 @@ pub fn build_router(ctx: ServerContext) -> Router {
             .get("/repos")
             .with_query_string_extractor::<repos::ReposParams>()
             .to(repos_handler);
+        route
+            .get("/:repo/commit/clone_data")
+            .with_path_extractor::<commit::CommitParams>()
+            .to(commit_clone_data_handler);
+        route
+            .post("/:repo/commit/location_to_hash")
+            .with_path_extractor::<commit::CommitParams>()
+            .to(commit_location_to_hash_handler);
+        route
+            .post("/:repo/commit/hash_to_location")
+            .with_path_extractor::<commit::CommitParams>()
+            .to(commit_hash_to_location_handler);
+        route
+            .post("/:repo/eden/data")
+            .with_path_extractor::<data::DataParams>()
+            .with_query_string_extractor::<StreamParams>()
+            .to(files_handler);
+        route
+            .post("/:repo/eden/history")
+            .with_path_extractor::<history::HistoryParams>()
+            .with_query_string_extractor::<StreamParams>()
+            .to(history_handler);
+        route
+            .post("/:repo/eden/trees")
+            .with_path_extractor::<data::DataParams>()
+            .with_query_string_extractor::<StreamParams>()
+            .to(trees_handler);
+        route
+            .post("/:repo/eden/trees/prefetch")
+            .with_path_extractor::<data::DataParams>()
+            .with_query_string_extractor::<StreamParams>()
+            .to(prefetch_trees_handler);
+        route
+            .post("/:repo/lookup")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(lookup_handler);
+        route
+            .put("/:repo/upload/file/sha256/:sha256")
+            .with_path_extractor::<upload::UploadFileParams>()
+            .to(upload_file_handler);
+        route
+            .post("/:repo/upload/filenodes")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(upload_filenodes_handler);
+        route
+            .post("/:repo/upload/trees")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(upload_trees_handler);
+        route
+            .post("/:repo/upload/changesets")
+            .with_path_extractor::<upload::UploadParams>()
+            .to(upload_changesets_handler);
+        route
+            .post("/:repo/bookmarks/set")
+            .with_path_extractor::<push::PushParams>()
+            .to(set_bookmark_handler);
+        route
+            .post("/:repo/pushrebase")
+            .with_path_extractor::<push::PushParams>()
+            .to(pushrebase_handler);
     })
 }
 
@@ -53,3 +119,115 @@ pub fn repos_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
     }
     .boxed()
 }
+
+pub fn commit_clone_data_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = commit::clone_data(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn commit_location_to_hash_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = commit::location_to_hash(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn commit_hash_to_location_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = commit::hash_to_location(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn files_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = data::files(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn history_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = history::history(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn trees_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = data::trees(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn prefetch_trees_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = data::prefetch_trees(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn lookup_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = upload::lookup(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn upload_file_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = upload::upload_file(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn upload_filenodes_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = upload::upload_filenodes(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn upload_trees_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = upload::upload_trees(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn upload_changesets_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = upload::upload_changesets(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn set_bookmark_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = push::set_bookmark(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+pub fn pushrebase_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = push::pushrebase(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Endpoints for publishing uploaded changesets by moving a bookmark to
+//! them, either directly or by pushrebasing them onto the bookmark.
+
+use bytes::Bytes;
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use serde::Deserialize;
+
+use gotham_ext::{error::HttpError, response::BytesBody};
+use types::api::{PushrebaseRequest, PushrebaseResponse, SetBookmarkRequest, SetBookmarkResponse};
+
+use crate::utils::{
+    cbor_response, get_repo_write, map_mononoke_error, parse_cbor_request, to_hg_changeset_id,
+    to_hgid,
+};
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct PushParams {
+    repo: String,
+}
+
+/// Create, move or delete a bookmark, provided it still points where the
+/// client expects it to.
+pub async fn set_bookmark(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = PushParams::take_from(state);
+    let repo = get_repo_write(state, &params.repo).await?;
+    let request: SetBookmarkRequest = parse_cbor_request(state).await?;
+
+    let hook_warnings = repo
+        .move_bookmark(
+            &request.bookmark,
+            request.from.map(to_hg_changeset_id),
+            request.to.map(to_hg_changeset_id),
+        )
+        .await
+        .map_err(map_mononoke_error)?;
+
+    cbor_response(vec![SetBookmarkResponse { hook_warnings }])
+}
+
+/// Rebase uploaded changesets onto a bookmark and move it to the result.
+pub async fn pushrebase(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = PushParams::take_from(state);
+    let repo = get_repo_write(state, &params.repo).await?;
+    let request: PushrebaseRequest = parse_cbor_request(state).await?;
+
+    let changesets = request
+        .changesets
+        .into_iter()
+        .map(to_hg_changeset_id)
+        .collect();
+    let outcome = repo
+        .pushrebase(&request.bookmark, changesets)
+        .await
+        .map_err(map_mononoke_error)?;
+
+    let response = PushrebaseResponse {
+        head: to_hgid(outcome.head),
+        rebased: outcome
+            .rebased
+            .into_iter()
+            .map(|(old, new)| (to_hgid(old), to_hgid(new)))
+            .collect(),
+        hook_warnings: outcome.hook_warnings,
+    };
+    cbor_response(vec![response])
+}
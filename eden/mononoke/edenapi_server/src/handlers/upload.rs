@@ -0,0 +1,222 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Endpoints for clients pushing commits: finding out what the server is
+//! missing and uploading it, file contents first, then filenodes, trees and
+//! finally the changesets that refer to them.
+
+use std::str::FromStr;
+
+use anyhow::{format_err, Error};
+use bytes::Bytes;
+use futures::{stream, StreamExt, TryStreamExt};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use http::header::{HeaderMap, CONTENT_LENGTH};
+use hyper::Body;
+use serde::Deserialize;
+
+use gotham_ext::{error::HttpError, response::BytesBody};
+use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId, HgNodeHash, HgParents};
+use mononoke_api::hg::HgRepoContext;
+use mononoke_types::hash::Sha256;
+use types::{
+    api::{
+        AnyId, LookupRequestBatch, LookupResponse, UploadHgChangesetsRequest,
+        UploadHgFilenodeRequest, UploadHgFilenodesRequest, UploadTreesRequest,
+    },
+    DataEntry, HgId, Validity,
+};
+
+use crate::utils::{
+    cbor_response, empty_response, get_repo, get_repo_write, map_mononoke_error,
+    parse_cbor_request, to_hg_changeset_id, to_hg_path, to_mononoke_path, to_mononoke_sha256,
+};
+
+/// Maximum number of items looked up or stored at once.
+const MAX_CONCURRENT_UPLOADS: usize = 10;
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct UploadParams {
+    repo: String,
+}
+
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct UploadFileParams {
+    repo: String,
+    sha256: String,
+}
+
+/// Which of the requested ids the repo already has.
+pub async fn lookup(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = UploadParams::take_from(state);
+    let repo = get_repo(state, &params.repo).await?;
+    let request: LookupRequestBatch = parse_cbor_request(state).await?;
+
+    let responses = stream::iter(request.ids)
+        .map(|id| lookup_id(&repo, id))
+        .buffer_unordered(MAX_CONCURRENT_UPLOADS)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    cbor_response(responses)
+}
+
+/// Store the request body as the content of a file, which must hash to the
+/// SHA-256 in the path.
+pub async fn upload_file(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = UploadFileParams::take_from(state);
+    let sha256 = Sha256::from_str(&params.sha256).map_err(HttpError::e400)?;
+    let size = content_length(state)?;
+    let repo = get_repo_write(state, &params.repo).await?;
+
+    let body = Body::take_from(state).map_err(Error::from);
+    repo.store_file_content(sha256, size, body)
+        .await
+        .map_err(map_mononoke_error)?;
+
+    empty_response()
+}
+
+/// Store Mercurial filenodes for file contents uploaded beforehand.
+pub async fn upload_filenodes(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = UploadParams::take_from(state);
+    let repo = get_repo_write(state, &params.repo).await?;
+    let request: UploadHgFilenodesRequest = parse_cbor_request(state).await?;
+
+    stream::iter(request.filenodes)
+        .map(|filenode| {
+            let repo = &repo;
+            async move {
+                let UploadHgFilenodeRequest {
+                    key,
+                    parents,
+                    content_sha256,
+                    copy_from,
+                } = filenode;
+                let copy_from = match copy_from {
+                    Some(key) => Some((to_hg_path(&key.path)?, to_filenode_id(key.hgid))),
+                    None => None,
+                };
+                repo.store_hg_filenode(
+                    to_filenode_id(key.hgid),
+                    to_hg_path(&key.path)?,
+                    HgParents::from(parents),
+                    to_mononoke_sha256(content_sha256),
+                    copy_from,
+                )
+                .await
+                .map_err(map_mononoke_error)
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_UPLOADS)
+        .try_collect::<()>()
+        .await?;
+
+    empty_response()
+}
+
+/// Store tree manifests.
+pub async fn upload_trees(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = UploadParams::take_from(state);
+    let repo = get_repo_write(state, &params.repo).await?;
+    let request: UploadTreesRequest = parse_cbor_request(state).await?;
+
+    stream::iter(request.entries)
+        .map(|entry| {
+            let repo = &repo;
+            async move {
+                let key = entry.key();
+                let manifest_id = HgManifestId::new(HgNodeHash::from(key.hgid));
+                let contents = verified_data(&entry)?;
+                repo.store_tree(
+                    manifest_id,
+                    to_mononoke_path(&key.path)?,
+                    HgParents::from(entry.parents().clone()),
+                    contents,
+                )
+                .await
+                .map_err(map_mononoke_error)
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_UPLOADS)
+        .try_collect::<()>()
+        .await?;
+
+    empty_response()
+}
+
+/// Store changesets, in the order they were sent so that parents are stored
+/// before their children.
+pub async fn upload_changesets(state: &mut State) -> Result<BytesBody<Bytes>, HttpError> {
+    let params = UploadParams::take_from(state);
+    let repo = get_repo_write(state, &params.repo).await?;
+    let request: UploadHgChangesetsRequest = parse_cbor_request(state).await?;
+
+    let changesets = request
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let hg_cs_id = to_hg_changeset_id(entry.key().hgid);
+            let parents = HgParents::from(entry.parents().clone());
+            let contents = verified_data(&entry)?;
+            Ok((hg_cs_id, parents, contents))
+        })
+        .collect::<Result<_, HttpError>>()?;
+    repo.store_hg_changesets(changesets)
+        .await
+        .map_err(map_mononoke_error)?;
+
+    empty_response()
+}
+
+async fn lookup_id(repo: &HgRepoContext, id: AnyId) -> Result<LookupResponse, HttpError> {
+    let present = match &id {
+        AnyId::FileContentSha256(sha256) => {
+            repo.file_content_exists(to_mononoke_sha256(*sha256)).await
+        }
+        AnyId::HgFilenodeId(hgid) => repo.filenode_exists(to_filenode_id(*hgid)).await,
+        AnyId::HgTreeId(hgid) => {
+            repo.tree_exists(HgManifestId::new(HgNodeHash::from(*hgid)))
+                .await
+        }
+        AnyId::HgChangesetId(hgid) => {
+            repo.changeset_exists(HgChangesetId::new(HgNodeHash::from(*hgid)))
+                .await
+        }
+    }
+    .map_err(map_mononoke_error)?;
+    Ok(LookupResponse { id, present })
+}
+
+/// The size of the request body, which file uploads must declare upfront as
+/// the filestore needs it before it starts storing the content.
+fn content_length(state: &State) -> Result<u64, HttpError> {
+    HeaderMap::borrow_from(state)
+        .get(CONTENT_LENGTH)
+        .ok_or_else(|| HttpError::e400(format_err!("missing Content-Length header")))?
+        .to_str()
+        .map_err(HttpError::e400)?
+        .parse()
+        .map_err(HttpError::e400)
+}
+
+/// The content of an uploaded entry, which must hash to the node in its key.
+fn verified_data(entry: &DataEntry) -> Result<Bytes, HttpError> {
+    match entry.data() {
+        (data, Validity::Valid) => Ok(data),
+        (_, Validity::Redacted) => Err(HttpError::e400(format_err!(
+            "cannot upload redacted content for {}",
+            entry.key()
+        ))),
+        (_, Validity::Invalid(e)) | (_, Validity::InvalidEmptyPath(e)) => Err(HttpError::e400(e)),
+    }
+}
+
+fn to_filenode_id(hgid: HgId) -> HgFileNodeId {
+    HgFileNodeId::new(HgNodeHash::from(hgid))
+}
@@ -45,6 +45,7 @@ use secure_utils::SslConfig;
 mod context;
 mod handlers;
 mod middleware;
+mod utils;
 
 use crate::context::ServerContext;
 use crate::handlers::build_router;
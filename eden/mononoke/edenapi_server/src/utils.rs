@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{format_err, Context};
+use bytes::Bytes;
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use http::header::HeaderMap;
+use hyper::Body;
+use mime::Mime;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use gotham_ext::{body_ext::BodyExt, error::HttpError, response::BytesBody};
+use mercurial_types::{HgChangesetId, HgNodeHash, MPath};
+use mononoke_api::{
+    hg::{HgRepoContext, HgRepoWriteContext},
+    MononokeError, MononokePath,
+};
+use mononoke_types::hash::Sha256 as MononokeSha256;
+use types::{HgId, RepoPathBuf, Sha256};
+
+use crate::context::ServerContext;
+use crate::middleware::RequestContext;
+
+/// Query string accepted by the data and history endpoints. Clients that
+/// pass `stream=true` get a sequence of individual entries rather than a
+/// single response value.
+#[derive(Debug, Deserialize, StateData, StaticResponseExtender)]
+pub struct StreamParams {
+    stream: Option<bool>,
+}
+
+impl StreamParams {
+    pub fn stream(&self) -> bool {
+        self.stream.unwrap_or(false)
+    }
+}
+
+/// Look up the repo named in the request, in Mercurial flavour.
+pub async fn get_repo(state: &State, name: &str) -> Result<HgRepoContext, HttpError> {
+    let ctx = RequestContext::borrow_from(state).ctx.clone();
+    let mononoke = ServerContext::borrow_from(state).mononoke_api();
+    let repo = mononoke
+        .repo(ctx, name)
+        .await
+        .map_err(map_mononoke_error)?
+        .ok_or_else(|| HttpError::e404(format_err!("repo does not exist: {}", name)))?;
+    Ok(repo.hg())
+}
+
+/// Look up the repo named in the request for writing, in Mercurial flavour.
+/// Fails unless the client is allowed to write to the repo.
+pub async fn get_repo_write(state: &State, name: &str) -> Result<HgRepoWriteContext, HttpError> {
+    let ctx = RequestContext::borrow_from(state).ctx.clone();
+    let mononoke = ServerContext::borrow_from(state).mononoke_api();
+    let repo = mononoke
+        .repo(ctx, name)
+        .await
+        .map_err(map_mononoke_error)?
+        .ok_or_else(|| HttpError::e404(format_err!("repo does not exist: {}", name)))?;
+    let repo = repo.write().await.map_err(map_mononoke_error)?;
+    Ok(repo.hg())
+}
+
+/// Read the request body and deserialize it from CBOR.
+pub async fn parse_cbor_request<T: DeserializeOwned>(state: &mut State) -> Result<T, HttpError> {
+    let body = Body::take_from(state);
+    let headers = HeaderMap::try_borrow_from(state);
+
+    let body = body
+        .try_concat_body_opt(headers)
+        .map_err(HttpError::e400)?
+        .await
+        .map_err(HttpError::e400)?;
+
+    serde_cbor::from_slice(&body)
+        .context("invalid CBOR request")
+        .map_err(HttpError::e400)
+}
+
+/// Serialize `entries` as a sequence of CBOR values, which clients read
+/// one entry at a time.
+pub fn cbor_response<T: Serialize>(
+    entries: impl IntoIterator<Item = T>,
+) -> Result<BytesBody<Bytes>, HttpError> {
+    let mut buf = Vec::new();
+    for entry in entries {
+        serde_cbor::to_writer(&mut buf, &entry).map_err(HttpError::e500)?;
+    }
+    Ok(BytesBody::new(Bytes::from(buf), cbor_mime()))
+}
+
+pub fn cbor_mime() -> Mime {
+    "application/cbor".parse().expect("invalid CBOR mime type")
+}
+
+pub fn map_mononoke_error(err: MononokeError) -> HttpError {
+    match err {
+        MononokeError::InvalidRequest(_) => HttpError::e400(err),
+        MononokeError::PermissionDenied { .. } => HttpError::e403(err),
+        MononokeError::NotAvailable(_) => HttpError::e404(err),
+        MononokeError::InternalError(_) => HttpError::e500(err),
+    }
+}
+
+pub fn to_hg_changeset_id(hgid: HgId) -> HgChangesetId {
+    HgChangesetId::new(HgNodeHash::from(hgid))
+}
+
+pub fn to_hgid(hg_cs_id: HgChangesetId) -> HgId {
+    HgId::from(hg_cs_id.into_nodehash())
+}
+
+pub fn to_mononoke_sha256(sha256: Sha256) -> MononokeSha256 {
+    MononokeSha256::from_byte_array(sha256.into_inner())
+}
+
+/// An empty CBOR response, for requests that only report success or failure.
+pub fn empty_response() -> Result<BytesBody<Bytes>, HttpError> {
+    cbor_response(Vec::<()>::new())
+}
+
+/// Convert a client supplied file path to a Mononoke path. The path must
+/// not be empty.
+pub fn to_hg_path(path: &RepoPathBuf) -> Result<MPath, HttpError> {
+    MPath::new(path.as_byte_slice()).map_err(HttpError::e400)
+}
+
+/// Convert a client supplied directory path, which is empty for the root
+/// directory, to a Mononoke path.
+pub fn to_mononoke_path(path: &RepoPathBuf) -> Result<MononokePath, HttpError> {
+    let mpath = MPath::new_opt(path.as_byte_slice()).map_err(HttpError::e400)?;
+    Ok(MononokePath::new(mpath))
+}
+
+pub fn to_repo_path(path: &MononokePath) -> Result<RepoPathBuf, HttpError> {
+    let bytes = path.as_mpath().map(MPath::to_vec).unwrap_or_default();
+    RepoPathBuf::from_utf8(bytes).map_err(HttpError::e500)
+}
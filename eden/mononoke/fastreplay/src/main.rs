@@ -31,6 +31,7 @@ use hooks_content_stores::InMemoryFileContentFetcher;
 use metaconfig_types::{BlobConfig, HookManagerParams};
 use mononoke_types::Timestamp;
 use nonzero_ext::nonzero;
+use permission_checker::PermissionCheckerBuilder;
 use rand::{thread_rng, Rng};
 use repo_client::MononokeRepoBuilder;
 use scopeguard::defer;
@@ -311,7 +312,11 @@ async fn bootstrap_repositories<'a>(
                 false, // Don't record infinitepush writes
             )
             .await?
-            .finalize(noop_hook_manager.clone())
+            // Replayed traffic was already allowed by the ACLs of the server it came from.
+            .finalize(
+                noop_hook_manager.clone(),
+                PermissionCheckerBuilder::always_allow().into(),
+            )
             .await?;
 
             let warmup = if no_cache_warmup {
@@ -11,7 +11,7 @@ use thiserror::Error;
 use crate::{expected_size::ExpectedSize, FetchKey};
 use mononoke_types::{
     hash::{RichGitSha1, Sha1, Sha256},
-    ContentId,
+    ContentChunkId, ContentId,
 };
 
 #[derive(Debug)]
@@ -39,4 +39,14 @@ pub enum ErrorKind {
 
     #[error("Missing content: {0:?}")]
     MissingContent(FetchKey),
+
+    #[error("Storing partial content requires a chunk size")]
+    ChunkingDisabled,
 }
+
+/// Chunks that were stored for content that was then rejected (e.g. because it did not match the
+/// hashes it was uploaded with). Nothing refers to these chunks. This is attached as the source of
+/// the rejection error, so callers can clean them up or report them.
+#[derive(Debug, Error)]
+#[error("{} chunk(s) stored for the rejected content are unreferenced", .0.len())]
+pub struct OrphanedChunks(pub Vec<ContentChunkId>);
@@ -8,17 +8,20 @@
 use anyhow::Error;
 use blobstore::{Blobstore, Storable};
 use context::CoreContext;
-use futures_ext::{try_left_future, FutureExt};
+use futures_ext::FutureExt;
 use futures_old::{Future, IntoFuture};
-use mononoke_types::{BlobstoreValue, ContentAlias, ContentMetadata};
+use mononoke_types::{
+    hash::{RichGitSha1, Sha1, Sha256},
+    BlobstoreValue, ContentAlias, ContentId, ContentMetadata, FileContents,
+};
 
-use crate::errors::{ErrorKind, InvalidHash};
+use crate::errors::{ErrorKind, InvalidHash, OrphanedChunks};
 use crate::fetch_key::{Alias, AliasBlob};
 use crate::prepare::Prepared;
 use crate::StoreRequest;
 
-// Verify that a given $expected hash matches the $effective hash, and otherwise return a left
-// future containing the $error.
+// Verify that a given $expected hash matches the $effective hash, and otherwise return the
+// $error.
 macro_rules! check_request_hash {
     ($expected:expr, $effective:expr, $error:expr) => {
         if let Some(expected) = $expected {
@@ -27,14 +30,39 @@ macro_rules! check_request_hash {
                     expected: *expected,
                     effective: $effective,
                 })
-                .into())
-                .into_future()
-                .left_future();
+                .into());
             }
         }
     };
 }
 
+fn check_request(
+    req: &StoreRequest,
+    total_size: u64,
+    content_id: ContentId,
+    sha1: Sha1,
+    sha256: Sha256,
+    git_sha1: RichGitSha1,
+) -> Result<(), Error> {
+    let StoreRequest {
+        expected_size,
+        canonical: req_content_id,
+        sha1: req_sha1,
+        sha256: req_sha256,
+        git_sha1: req_git_sha1,
+    } = req;
+
+    expected_size.check_equals(total_size)?;
+
+    use ErrorKind::*;
+    check_request_hash!(req_content_id, content_id, InvalidContentId);
+    check_request_hash!(req_sha1, sha1, InvalidSha1);
+    check_request_hash!(req_sha256, sha256, InvalidSha256);
+    check_request_hash!(req_git_sha1, git_sha1, InvalidGitSha1);
+
+    Ok(())
+}
+
 pub fn finalize<B: Blobstore + Clone>(
     blobstore: B,
     ctx: CoreContext,
@@ -50,30 +78,28 @@ pub fn finalize<B: Blobstore + Clone>(
 
     let total_size = contents.size();
 
-    let blob = contents.into_blob();
-    let content_id = *blob.id();
-
-    // If we were provided any hashes in the request, then validate them before we proceed.
+    // If we were provided any hashes in the request, then validate them before we proceed. If
+    // the content was chunked, its chunks are stored already: report them along with the error.
     if let Some(req) = req {
-        let StoreRequest {
-            expected_size,
-            canonical: req_content_id,
-            sha1: req_sha1,
-            sha256: req_sha256,
-            git_sha1: req_git_sha1,
-        } = req;
-
-        let _ = try_left_future!(expected_size.check_equals(total_size));
-
-        {
-            use ErrorKind::*;
-            check_request_hash!(req_content_id, content_id, InvalidContentId);
-            check_request_hash!(req_sha1, sha1, InvalidSha1);
-            check_request_hash!(req_sha256, sha256, InvalidSha256);
-            check_request_hash!(req_git_sha1, git_sha1, InvalidGitSha1);
+        let content_id = contents.content_id();
+        if let Err(e) = check_request(req, total_size, content_id, sha1, sha256, git_sha1) {
+            let e = match &contents {
+                FileContents::Bytes(..) => e,
+                FileContents::Chunked(chunked) => {
+                    let chunks = chunked.iter_chunks().map(|c| c.chunk_id()).collect();
+                    match e.downcast::<ErrorKind>() {
+                        Ok(kind) => Error::from(OrphanedChunks(chunks)).context(kind),
+                        Err(e) => e,
+                    }
+                }
+            };
+            return Err(e).into_future().left_future();
         }
     }
 
+    let blob = contents.into_blob();
+    let content_id = *blob.id();
+
     let put_contents = blob.store(ctx.clone(), &blobstore);
 
     let alias = ContentAlias::from_content_id(content_id);
@@ -14,12 +14,14 @@ use std::convert::TryInto;
 
 use anyhow::Error;
 use cloned::cloned;
-use futures_ext::FutureExt;
+use futures_ext::{FutureExt, StreamExt};
 use futures_old::{stream, Future, IntoFuture, Stream};
 
 use blobstore::{Blobstore, Loadable, LoadableError};
 use context::CoreContext;
-use mononoke_types::{hash, ContentId, ContentMetadata, FileContents, MononokeId};
+use mononoke_types::{
+    hash, ContentChunkPointer, ContentId, ContentMetadata, FileContents, MononokeId,
+};
 
 mod alias;
 mod chunk;
@@ -36,6 +38,7 @@ mod rechunk;
 mod spawn;
 mod streamhash;
 
+pub use errors::{ErrorKind, InvalidHash, OrphanedChunks};
 pub use fetch_key::{Alias, AliasBlob, FetchKey};
 pub use rechunk::{force_rechunk, rechunk};
 
@@ -195,6 +198,30 @@ pub fn get_metadata_readonly<B: Blobstore + Clone>(
         })
 }
 
+/// Recompute the metadata for the underlying content from its bytes. This will return None if
+/// the content does not exist. Nothing is stored, and the ContentId and aliases returned are
+/// those of the bytes actually fetched, so they differ from the stored ones if the content is
+/// corrupt.
+pub fn recompute_metadata<B: Blobstore + Clone>(
+    blobstore: &B,
+    ctx: CoreContext,
+    key: &FetchKey,
+) -> impl Future<Item = Option<ContentMetadata>, Error = Error> {
+    key.load(ctx.clone(), blobstore)
+        .map(Some)
+        .or_else(|err| match err {
+            LoadableError::Error(err) => Err(err),
+            LoadableError::Missing(_) => Ok(None),
+        })
+        .and_then({
+            cloned!(blobstore, ctx);
+            move |maybe_id| match maybe_id {
+                Some(id) => metadata::recompute_metadata(blobstore, ctx, id).left_future(),
+                None => Ok(None).into_future().right_future(),
+            }
+        })
+}
+
 /// Return true if the given key exists. A successful return means the key definitely
 /// either exists or doesn't; an error means the existence could not be determined.
 pub fn exists<B: Blobstore + Clone>(
@@ -408,6 +435,51 @@ pub fn store<B: Blobstore + Clone>(
     })
 }
 
+/// Store part of a file as chunks, without making the file exist yet. This is intended for files
+/// that are uploaded in several parts, possibly over several requests: the pointers returned for
+/// each part (in order) can be recorded as they come, and passed to `store_from_chunks` once all
+/// the data has been received. Data is split into chunks of the configured chunk size, so this
+/// requires chunking to be enabled.
+pub fn store_partial<B: Blobstore + Clone>(
+    blobstore: B,
+    config: FilestoreConfig,
+    ctx: CoreContext,
+    data: impl Stream<Item = Bytes, Error = Error>,
+) -> impl Stream<Item = ContentChunkPointer, Error = Error> {
+    let chunk_size = match config.chunk_size {
+        Some(chunk_size) => chunk_size,
+        None => return stream::once(Err(errors::ErrorKind::ChunkingDisabled.into())).left_stream(),
+    };
+
+    chunk::ChunkStream::new(data, chunk_size as usize)
+        .map(move |bytes| prepare::upload_chunk(ctx.clone(), blobstore.clone(), bytes))
+        .buffered(config.concurrency)
+        .right_stream()
+}
+
+/// Store a file from chunks previously stored using `store_partial`. Like `store`, this is
+/// atomic: the file only exists once this succeeds. The chunks are read back in order to verify
+/// the hashes in the request.
+pub fn store_from_chunks<B: Blobstore + Clone>(
+    blobstore: B,
+    config: FilestoreConfig,
+    ctx: CoreContext,
+    req: &StoreRequest,
+    chunks: Vec<ContentChunkPointer>,
+) -> impl Future<Item = ContentMetadata, Error = Error> {
+    prepare::prepare_from_chunks(
+        ctx.clone(),
+        blobstore.clone(),
+        req.expected_size,
+        chunks,
+        config.concurrency,
+    )
+    .and_then({
+        cloned!(req);
+        move |prepared| finalize::finalize(blobstore, ctx, Some(&req), prepared)
+    })
+}
+
 /// Store a set of bytes, and immediately return their Contentid and size. This function is
 /// inefficient for large files, since it will hash the file twice if it's larger than the chunk
 /// size. This function is intended as a transition function while we convert writers to streams
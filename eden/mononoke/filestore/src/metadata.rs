@@ -10,13 +10,17 @@ use blobstore::{Blobstore, Loadable, LoadableError, Storable};
 use cloned::cloned;
 use context::CoreContext;
 use futures_ext::FutureExt;
-use futures_old::{Future, IntoFuture};
+use futures_old::{Future, IntoFuture, Stream};
 use mononoke_types::{BlobstoreValue, ContentId, ContentMetadata, ContentMetadataId};
 use thiserror::Error;
 
 use crate::alias::alias_stream;
 use crate::expected_size::ExpectedSize;
 use crate::fetch;
+use crate::incremental_hash::{
+    ContentIdIncrementalHasher, GitSha1IncrementalHasher, Hasher, Sha1IncrementalHasher,
+    Sha256IncrementalHasher,
+};
 
 #[derive(Debug, Error)]
 pub enum RebuildBackmappingError {
@@ -131,3 +135,66 @@ fn rebuild_metadata<B: Blobstore + Clone>(
             }
         })
 }
+
+/// Recomputes the metadata of a ContentId from the content's bytes, without storing it. Unlike
+/// `rebuild_metadata`, the ContentId and size in the result are those of the bytes actually
+/// fetched, so comparing them (and the aliases) with what is stored detects corrupt content.
+/// Returns None if the content does not exist.
+pub fn recompute_metadata<B: Blobstore + Clone>(
+    blobstore: B,
+    ctx: CoreContext,
+    content_id: ContentId,
+) -> impl Future<Item = Option<ContentMetadata>, Error = Error> {
+    content_id
+        .load(ctx.clone(), &blobstore)
+        .map(Some)
+        .or_else(|err| match err {
+            LoadableError::Error(err) => Err(err),
+            LoadableError::Missing(_) => Ok(None),
+        })
+        .and_then(move |maybe_file_contents| match maybe_file_contents {
+            Some(file_contents) => {
+                // The git sha1 header needs the size up front, so that one comes from the
+                // stored contents. A wrong size shows up as a git sha1 mismatch.
+                let expected_size = ExpectedSize::new(file_contents.size());
+                let hashers = (
+                    ContentIdIncrementalHasher::new(),
+                    Sha1IncrementalHasher::new(),
+                    Sha256IncrementalHasher::new(),
+                    GitSha1IncrementalHasher::new(expected_size),
+                    0,
+                );
+
+                fetch::stream_file_bytes(blobstore, ctx, file_contents, fetch::Range::All)
+                    .fold(
+                        hashers,
+                        |(mut content_id, mut sha1, mut sha256, mut git_sha1, total_size),
+                         bytes| {
+                            content_id.update(&bytes);
+                            sha1.update(&bytes);
+                            sha256.update(&bytes);
+                            git_sha1.update(&bytes);
+                            let res: Result<_, Error> = Ok((
+                                content_id,
+                                sha1,
+                                sha256,
+                                git_sha1,
+                                total_size + bytes.len() as u64,
+                            ));
+                            res
+                        },
+                    )
+                    .map(|(content_id, sha1, sha256, git_sha1, total_size)| {
+                        Some(ContentMetadata {
+                            total_size,
+                            content_id: content_id.finish(),
+                            sha1: sha1.finish(),
+                            sha256: sha256.finish(),
+                            git_sha1: git_sha1.finish(),
+                        })
+                    })
+                    .left_future()
+            }
+            None => Ok(None).into_future().right_future(),
+        })
+}
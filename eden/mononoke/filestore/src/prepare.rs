@@ -6,22 +6,24 @@
  */
 
 use anyhow::{Error, Result};
-use blobstore::Blobstore;
+use blobstore::{Blobstore, Loadable, LoadableError};
 use bytes::Bytes;
 use cloned::cloned;
 use context::CoreContext;
 use futures_ext::FutureExt;
 use futures_old::{
     future::{lazy, IntoFuture},
-    Future, Stream,
+    stream, Future, Stream,
 };
 use mononoke_types::{
-    content_chunk::new_blob_and_pointer, hash, ChunkedFileContents, FileContents, MononokeId,
+    content_chunk::new_blob_and_pointer, hash, ChunkedFileContents, ContentChunk,
+    ContentChunkPointer, FileContents, MononokeId,
 };
 
 use crate::alias::add_aliases_to_multiplexer;
 use crate::chunk::{BufferedStream, ChunkedStream};
 use crate::expected_size::ExpectedSize;
+use crate::fetch::ErrorKind::ChunkNotFound;
 use crate::incremental_hash::{
     hash_bytes, ContentIdIncrementalHasher, GitSha1IncrementalHasher, Sha1IncrementalHasher,
     Sha256IncrementalHasher,
@@ -62,6 +64,27 @@ where
     chunk.map(prepare_bytes)
 }
 
+/// Upload a single chunk of a file, returning the pointer to reference it with from the file's
+/// contents.
+pub fn upload_chunk<B: Blobstore + Clone>(
+    ctx: CoreContext,
+    blobstore: B,
+    bytes: Bytes,
+) -> impl Future<Item = ContentChunkPointer, Error = Error> {
+    // NOTE: This is lazy to allow the hash computation for this chunk's ID to happen on a
+    // separate core.
+    let fut = lazy(move || {
+        let (blob, pointer) = new_blob_and_pointer(bytes);
+
+        // TODO: Convert this along with other store calls to impl Storable for MononokeId.
+        blobstore
+            .put(ctx, blob.id().blobstore_key(), blob.into())
+            .map(move |_| pointer)
+    });
+
+    spawn::spawn_and_start(fut).map_err(|e| e.into())
+}
+
 /// Prepare a stream of bytes for upload. This will return a Prepared struct that can be used to
 /// finalize the upload. The hashes we compute may depend on the size hint.
 pub fn prepare_chunked<B: Blobstore + Clone, S>(
@@ -92,23 +115,7 @@ where
         let contents = multiplexer.add(move |stream| {
             stream
                 .map_err(|e| -> Error { e })  // Coerce the Error value for our stream.
-                .map(move |bytes| {
-                    // NOTE: This is lazy to allow the hash computation for this chunk's ID to
-                    // happen on a separate core.
-                    let fut = lazy({
-                        cloned!(blobstore, ctx);
-                        move || {
-                            let (blob, pointer) = new_blob_and_pointer(bytes);
-
-                            // TODO: Convert this along with other store calls to impl Storable for
-                            // MononokeId.
-                            blobstore.put(ctx, blob.id().blobstore_key(), blob.into())
-                                .map(move |_| pointer)
-                        }
-                    });
-
-                    spawn::spawn_and_start(fut).map_err(|e| e.into())
-                })
+                .map(move |bytes| upload_chunk(ctx.clone(), blobstore.clone(), bytes))
                 .buffered(concurrency)
                 .fold(vec![], |mut chunks, chunk| {
                     chunks.push(chunk);
@@ -166,3 +173,65 @@ where
         })
     })
 }
+
+/// Prepare a file whose chunks were uploaded beforehand (using `upload_chunk`) for finalization.
+/// The chunks are read back from the blobstore in order to compute the file's hashes.
+pub fn prepare_from_chunks<B: Blobstore + Clone>(
+    ctx: CoreContext,
+    blobstore: B,
+    expected_size: ExpectedSize,
+    chunks: Vec<ContentChunkPointer>,
+    concurrency: usize,
+) -> impl Future<Item = Prepared, Error = Error> {
+    if chunks.is_empty() {
+        // Store empty files inline, like store() does.
+        return Ok(prepare_bytes(Bytes::new())).into_future().left_future();
+    }
+
+    let data = stream::iter_ok(chunks.clone())
+        .map({
+            cloned!(blobstore, ctx);
+            move |chunk| {
+                let chunk_id = chunk.chunk_id();
+                chunk_id
+                    .load(ctx.clone(), &blobstore)
+                    .or_else(move |err| match err {
+                        LoadableError::Error(err) => Err(err),
+                        LoadableError::Missing(_) => Err(ChunkNotFound(chunk_id).into()),
+                    })
+                    .map(ContentChunk::into_bytes)
+            }
+        })
+        .buffered(concurrency);
+
+    lazy(move || {
+        let mut multiplexer = Multiplexer::<Bytes>::new();
+
+        let content_id =
+            multiplexer.add(|stream| hash_stream(ContentIdIncrementalHasher::new(), stream));
+        let aliases = add_aliases_to_multiplexer(&mut multiplexer, expected_size);
+
+        multiplexer
+            .drain(data)
+            .map_err(|e| e.into())
+            .and_then(move |_| {
+                (
+                    content_id.map_err(|e| e.into()),
+                    aliases.map_err(|e| e.into()),
+                )
+                    .into_future()
+            })
+            .and_then(move |(content_id, aliases)| {
+                let contents = FileContents::Chunked(ChunkedFileContents::new(content_id, chunks));
+                let (sha1, sha256, git_sha1) = aliases.redeem(contents.size())?;
+
+                Ok(Prepared {
+                    sha1,
+                    sha256,
+                    git_sha1,
+                    contents,
+                })
+            })
+    })
+    .right_future()
+}
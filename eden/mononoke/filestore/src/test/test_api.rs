@@ -23,7 +23,10 @@ use futures_old::{
 };
 use futures_util::compat::Future01CompatExt;
 use lazy_static::lazy_static;
-use mononoke_types::{hash, typed_hash::MononokeId, ContentId, ContentMetadata, ContentMetadataId};
+use mononoke_types::{
+    hash, typed_hash::MononokeId, BlobstoreValue, ContentId, ContentMetadata, ContentMetadataId,
+    FileContents,
+};
 use mononoke_types_mocks::contentid::ONES_CTID;
 
 const HELLO_WORLD: &'static [u8] = b"hello, world";
@@ -362,6 +365,63 @@ async fn filestore_chunk_not_found(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn filestore_recompute_metadata(fb: FacebookInit) -> Result<()> {
+    let req = request(HELLO_WORLD);
+    let content_id = canonical(HELLO_WORLD);
+
+    let blob = memblob::LazyMemblob::new();
+    let config = FilestoreConfig {
+        chunk_size: Some(5),
+        concurrency: 5,
+    };
+    let ctx = CoreContext::test_mock(fb);
+
+    filestore::store(
+        blob.clone(),
+        config,
+        ctx.clone(),
+        &req,
+        stream::once(Ok(Bytes::from(HELLO_WORLD))),
+    )
+    .boxify()
+    .compat()
+    .await?;
+
+    let expected = ContentMetadata {
+        total_size: HELLO_WORLD_LENGTH,
+        content_id,
+        sha1: *HELLO_WORLD_SHA1,
+        git_sha1: *HELLO_WORLD_GIT_SHA1,
+        sha256: *HELLO_WORLD_SHA256,
+    };
+    let res = filestore::recompute_metadata(&blob, ctx.clone(), &FetchKey::Canonical(content_id))
+        .compat()
+        .await;
+    println!("res = {:#?}", res);
+    assert_eq!(res?, Some(expected));
+
+    // Overwrite the content with different bytes, recomputing reports the hashes of those
+    let corrupt = FileContents::new_bytes(&b"hello, wurld"[..]);
+    blob.put(
+        ctx.clone(),
+        content_id.blobstore_key(),
+        corrupt.into_blob().into(),
+    )
+    .compat()
+    .await?;
+    let res = filestore::recompute_metadata(&blob, ctx, &FetchKey::Canonical(content_id))
+        .compat()
+        .await?
+        .expect("content should exist");
+    assert_eq!(res.total_size, HELLO_WORLD_LENGTH);
+    assert_ne!(res.content_id, content_id);
+    assert_ne!(res.sha1, *HELLO_WORLD_SHA1);
+    assert_ne!(res.sha256, *HELLO_WORLD_SHA256);
+    assert_ne!(res.git_sha1, *HELLO_WORLD_GIT_SHA1);
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn filestore_put_invalid_size(fb: FacebookInit) -> Result<()> {
     let blob = memblob::LazyMemblob::new();
@@ -578,6 +638,51 @@ async fn filestore_put_sha256(fb: FacebookInit) -> Result<()> {
     Ok(())
 }
 
+#[fbinit::compat_test]
+async fn filestore_put_invalid_reports_orphaned_chunks(fb: FacebookInit) -> Result<()> {
+    let blob = memblob::LazyMemblob::new();
+    let config = FilestoreConfig {
+        chunk_size: Some(5),
+        concurrency: 5,
+    };
+    let ctx = CoreContext::test_mock(fb);
+
+    let req = StoreRequest::with_sha256(
+        HELLO_WORLD_LENGTH,
+        hash::Sha256::from_byte_array([0x00; 32]),
+    );
+    let res = filestore::store(
+        blob.clone(),
+        config,
+        ctx.clone(),
+        &req,
+        stream::once(Ok(Bytes::from(HELLO_WORLD))),
+    )
+    .boxify()
+    .compat()
+    .await;
+    println!("res = {:#?}", res);
+
+    let err = res.unwrap_err();
+    assert_matches!(
+        err.downcast_ref::<errors::ErrorKind>(),
+        Some(errors::ErrorKind::InvalidSha256(..))
+    );
+
+    // The chunks were stored before the hash was checked, and are reported with the error.
+    let orphaned = err.downcast::<filestore::OrphanedChunks>().unwrap();
+    assert_eq!(orphaned.0.len(), 3);
+    for chunk_id in orphaned.0 {
+        let chunk = blob
+            .get(ctx.clone(), chunk_id.blobstore_key())
+            .compat()
+            .await?;
+        assert!(chunk.is_some());
+    }
+
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn filestore_get_range(fb: FacebookInit) -> Result<()> {
     let req = request(HELLO_WORLD);
@@ -1319,3 +1424,88 @@ async fn assert_fetches_as<B: Blobstore + Clone>(
     assert_eq!(res?, Some(expected));
     Ok(())
 }
+
+#[fbinit::compat_test]
+async fn filestore_store_partial(fb: FacebookInit) -> Result<()> {
+    let blob = memblob::LazyMemblob::new();
+    let config = FilestoreConfig {
+        chunk_size: Some(5),
+        concurrency: 5,
+    };
+    let ctx = CoreContext::test_mock(fb);
+
+    // Upload the data in two parts that are not aligned with the chunk size.
+    let (first, second) = HELLO_WORLD.split_at(7);
+    let mut chunks = vec![];
+    for part in vec![first, second] {
+        let part_chunks = filestore::store_partial(
+            blob.clone(),
+            config,
+            ctx.clone(),
+            stream::once(Ok(Bytes::from(part))),
+        )
+        .collect()
+        .compat()
+        .await?;
+        chunks.extend(part_chunks);
+    }
+    assert_eq!(chunks.len(), 3);
+
+    // Nothing exists until the chunks are finalized.
+    let key = FetchKey::Aliased(Alias::Sha256(*HELLO_WORLD_SHA256));
+    let res = filestore::fetch_concat_opt(&blob, ctx.clone(), &key)
+        .compat()
+        .await?;
+    assert_eq!(res, None);
+
+    // Bad hashes should fail.
+    let req = StoreRequest::with_sha256(
+        HELLO_WORLD_LENGTH,
+        hash::Sha256::from_byte_array([0x00; 32]),
+    );
+    let res = filestore::store_from_chunks(blob.clone(), config, ctx.clone(), &req, chunks.clone())
+        .compat()
+        .await;
+    println!("res = {:#?}", res);
+    assert_matches!(
+        res.unwrap_err().downcast::<errors::ErrorKind>(),
+        Ok(errors::ErrorKind::InvalidSha256(..))
+    );
+
+    let req = StoreRequest::with_sha256(HELLO_WORLD_LENGTH, *HELLO_WORLD_SHA256);
+    let metadata = filestore::store_from_chunks(blob.clone(), config, ctx.clone(), &req, chunks)
+        .compat()
+        .await?;
+    assert_eq!(metadata.content_id, canonical(HELLO_WORLD));
+    assert_eq!(metadata.sha256, *HELLO_WORLD_SHA256);
+
+    assert_fetches_as(
+        ctx,
+        &blob,
+        metadata.content_id,
+        vec!["hello", ", ", "world"],
+    )
+    .await
+}
+
+#[fbinit::compat_test]
+async fn filestore_store_partial_requires_chunking(fb: FacebookInit) -> Result<()> {
+    let blob = memblob::LazyMemblob::new();
+    let ctx = CoreContext::test_mock(fb);
+
+    let res = filestore::store_partial(
+        blob,
+        DEFAULT_CONFIG,
+        ctx,
+        stream::once(Ok(Bytes::from(HELLO_WORLD))),
+    )
+    .collect()
+    .compat()
+    .await;
+    assert_matches!(
+        res.unwrap_err().downcast::<errors::ErrorKind>(),
+        Ok(errors::ErrorKind::ChunkingDisabled)
+    );
+
+    Ok(())
+}
@@ -13,7 +13,10 @@ use gotham::{
     helpers::http::response::create_response,
     state::{request_id, State},
 };
-use hyper::{Body, Response, StatusCode};
+use hyper::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Body, Response, StatusCode,
+};
 use itertools::Itertools;
 use serde_derive::{Deserialize, Serialize};
 
@@ -22,6 +25,8 @@ use serde_derive::{Deserialize, Serialize};
 pub struct HttpError {
     pub error: Error,
     pub status_code: StatusCode,
+    /// Headers to send along with the error, e.g. the Content-Range of a 416.
+    pub headers: HeaderMap,
 }
 
 impl HttpError {
@@ -29,6 +34,7 @@ impl HttpError {
         Self {
             error: err.into(),
             status_code: StatusCode::BAD_REQUEST,
+            headers: HeaderMap::new(),
         }
     }
 
@@ -36,6 +42,7 @@ impl HttpError {
         Self {
             error: err.into(),
             status_code: StatusCode::FORBIDDEN,
+            headers: HeaderMap::new(),
         }
     }
 
@@ -43,6 +50,15 @@ impl HttpError {
         Self {
             error: err.into(),
             status_code: StatusCode::NOT_FOUND,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    pub fn e409<E: Into<Error>>(err: E) -> Self {
+        Self {
+            error: err.into(),
+            status_code: StatusCode::CONFLICT,
+            headers: HeaderMap::new(),
         }
     }
 
@@ -50,6 +66,31 @@ impl HttpError {
         Self {
             error: err.into(),
             status_code: StatusCode::GONE,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    pub fn e415<E: Into<Error>>(err: E) -> Self {
+        Self {
+            error: err.into(),
+            status_code: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    pub fn e416<E: Into<Error>>(err: E) -> Self {
+        Self {
+            error: err.into(),
+            status_code: StatusCode::RANGE_NOT_SATISFIABLE,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    pub fn e422<E: Into<Error>>(err: E) -> Self {
+        Self {
+            error: err.into(),
+            status_code: StatusCode::UNPROCESSABLE_ENTITY,
+            headers: HeaderMap::new(),
         }
     }
 
@@ -57,6 +98,7 @@ impl HttpError {
         Self {
             error: err.into(),
             status_code: StatusCode::TOO_MANY_REQUESTS,
+            headers: HeaderMap::new(),
         }
     }
 
@@ -64,9 +106,15 @@ impl HttpError {
         Self {
             error: err.into(),
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            headers: HeaderMap::new(),
         }
     }
 
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
     /// Turn this error into a type corresponding to the return type
     /// of a Gotham handler, so that it may be directly returned from
     /// a handler function.
@@ -88,7 +136,9 @@ impl HttpError {
         // Convert to JSON; should not fail but return a handler error if so.
         match serde_json::to_string(&res) {
             Ok(res) => {
-                let res = create_response(&state, self.status_code, mime::APPLICATION_JSON, res);
+                let mut res =
+                    create_response(&state, self.status_code, mime::APPLICATION_JSON, res);
+                res.headers_mut().extend(self.headers);
                 Ok((state, res))
             }
             Err(error) => Err((state, error.into_handler_error())),
@@ -191,6 +191,7 @@ async fn run_hook_tailer<'a>(
     );
     info!(logger, "Changesets accepted: {}", summary.accepted);
     info!(logger, "Changesets rejected: {}", summary.rejected);
+    info!(logger, "Changesets with warnings: {}", summary.warned);
 
     if summary.rejected > 0 {
         return Err(format_err!("Hook rejections: {}", summary.rejected));
@@ -203,6 +204,7 @@ async fn run_hook_tailer<'a>(
 struct HookExecutionSummary {
     accepted: u64,
     rejected: u64,
+    warned: u64,
     completion_time: Duration,
     poll_time: Duration,
 }
@@ -210,11 +212,15 @@ struct HookExecutionSummary {
 impl HookExecutionSummary {
     pub fn add_instance(&mut self, instance: &HookExecutionInstance, logger: &Logger) {
         let mut is_rejected = false;
+        let mut is_warned = false;
 
         for outcome in instance.outcomes.iter() {
             if outcome.is_rejection() {
                 is_rejected = true;
                 info!(logger, "{}", outcome);
+            } else if outcome.is_warning() {
+                is_warned = true;
+                info!(logger, "{}", outcome);
             } else {
                 debug!(logger, "{}", outcome);
             }
@@ -225,6 +231,9 @@ impl HookExecutionSummary {
         } else {
             self.accepted += 1;
         }
+        if is_warned {
+            self.warned += 1;
+        }
 
         self.completion_time += instance.stats.completion_time;
         self.poll_time += instance.stats.poll_time;
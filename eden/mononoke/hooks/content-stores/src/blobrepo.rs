@@ -5,12 +5,17 @@
  * GNU General Public License version 2.
  */
 
+use anyhow::Error;
 use async_trait::async_trait;
 use blobrepo::BlobRepo;
+use blobstore::Loadable;
+use bookmarks::BookmarkName;
 use bytes::Bytes;
 use context::CoreContext;
 use futures::compat::Future01CompatExt;
-use mononoke_types::ContentId;
+use manifest::{Entry, Manifest, ManifestOps};
+use mercurial_types::HgManifestId;
+use mononoke_types::{ChangesetId, ContentId, MPath, MPathElement};
 
 use crate::{ErrorKind, FileContentFetcher};
 
@@ -45,10 +50,101 @@ impl FileContentFetcher for BlobRepoFileContentFetcher {
             .ok_or(ErrorKind::ContentIdNotFound(id))
             .map(Option::Some)
     }
+
+    async fn peek_file<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        id: ContentId,
+        size: usize,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        let store = self.repo.get_blobstore();
+        filestore::peek(&store, ctx.clone(), &id.into(), size)
+            .compat()
+            .await?
+            .ok_or(ErrorKind::ContentIdNotFound(id))
+            .map(Option::Some)
+    }
+
+    async fn list_directory<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        cs_id: ChangesetId,
+        path: Option<MPath>,
+    ) -> Result<Option<Vec<MPathElement>>, ErrorKind> {
+        let store = self.repo.get_blobstore();
+        let entry = self
+            .manifest(ctx, cs_id)
+            .await?
+            .find_entry(ctx.clone(), store.clone(), path)
+            .compat()
+            .await?;
+
+        match entry {
+            Some(Entry::Tree(manifest_id)) => {
+                let manifest = manifest_id
+                    .load(ctx.clone(), &store)
+                    .compat()
+                    .await
+                    .map_err(Error::from)?;
+                Ok(Some(manifest.list().map(|(name, _)| name).collect()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn find_file_in_bookmark<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        bookmark: &BookmarkName,
+        path: MPath,
+    ) -> Result<Option<ContentId>, ErrorKind> {
+        let cs_id = match self
+            .repo
+            .get_bonsai_bookmark(ctx.clone(), bookmark)
+            .compat()
+            .await?
+        {
+            Some(cs_id) => cs_id,
+            None => return Ok(None),
+        };
+
+        let store = self.repo.get_blobstore();
+        let entry = self
+            .manifest(ctx, cs_id)
+            .await?
+            .find_entry(ctx.clone(), store.clone(), Some(path))
+            .compat()
+            .await?;
+
+        match entry {
+            Some(Entry::Leaf((_file_type, filenode_id))) => {
+                let envelope = filenode_id
+                    .load(ctx.clone(), &store)
+                    .compat()
+                    .await
+                    .map_err(Error::from)?;
+                Ok(Some(envelope.content_id()))
+            }
+            _ => Ok(None),
+        }
+    }
 }
 
 impl BlobRepoFileContentFetcher {
     pub fn new(repo: BlobRepo) -> BlobRepoFileContentFetcher {
         BlobRepoFileContentFetcher { repo }
     }
+
+    async fn manifest(&self, ctx: &CoreContext, cs_id: ChangesetId) -> Result<HgManifestId, Error> {
+        let hg_cs_id = self
+            .repo
+            .get_hg_from_bonsai_changeset(ctx.clone(), cs_id)
+            .compat()
+            .await?;
+        let hg_cs = hg_cs_id
+            .load(ctx.clone(), &self.repo.get_blobstore())
+            .compat()
+            .await?;
+        Ok(hg_cs.manifestid())
+    }
 }
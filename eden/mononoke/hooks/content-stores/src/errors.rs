@@ -7,12 +7,14 @@
 
 use thiserror::Error;
 
-use mononoke_types::ContentId;
+use mononoke_types::{ChangesetId, ContentId};
 
 #[derive(Debug, Error)]
 pub enum ErrorKind {
     #[error("Content with id '{0}' not found")]
     ContentIdNotFound(ContentId),
+    #[error("Changeset with id '{0}' not found")]
+    ChangesetNotFound(ChangesetId),
     #[error(transparent)]
     BackingStore(#[from] anyhow::Error),
     #[error("Content too large to fit in memory")]
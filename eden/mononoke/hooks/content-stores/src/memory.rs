@@ -8,9 +8,10 @@
 use crate::{ErrorKind, FileContentFetcher};
 
 use async_trait::async_trait;
+use bookmarks::BookmarkName;
 use bytes::Bytes;
 use context::CoreContext;
-use mononoke_types::ContentId;
+use mononoke_types::{ChangesetId, ContentId, MPath, MPathElement};
 use std::collections::HashMap;
 
 #[derive(Clone)]
@@ -72,6 +73,36 @@ impl FileContentFetcher for InMemoryFileContentFetcher {
                 InMemoryFileText::Elided(_) => None,
             })
     }
+
+    async fn peek_file<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        id: ContentId,
+        size: usize,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        let text = self.get_file_text(ctx, id).await?;
+        Ok(text.map(|text| text.slice(..size.min(text.len()))))
+    }
+
+    /// There are no changesets in memory.
+    async fn list_directory<'a, 'b: 'a>(
+        &'a self,
+        _ctx: &'b CoreContext,
+        cs_id: ChangesetId,
+        _path: Option<MPath>,
+    ) -> Result<Option<Vec<MPathElement>>, ErrorKind> {
+        Err(ErrorKind::ChangesetNotFound(cs_id))
+    }
+
+    /// There are no bookmarks in memory.
+    async fn find_file_in_bookmark<'a, 'b: 'a>(
+        &'a self,
+        _ctx: &'b CoreContext,
+        _bookmark: &BookmarkName,
+        _path: MPath,
+    ) -> Result<Option<ContentId>, ErrorKind> {
+        Ok(None)
+    }
 }
 
 impl InMemoryFileContentFetcher {
@@ -8,9 +8,10 @@
 use crate::ErrorKind;
 
 use async_trait::async_trait;
+use bookmarks::BookmarkName;
 use bytes::Bytes;
 use context::CoreContext;
-use mononoke_types::ContentId;
+use mononoke_types::{ChangesetId, ContentId, MPath, MPathElement};
 
 #[async_trait]
 pub trait FileContentFetcher: Send + Sync {
@@ -25,4 +26,31 @@ pub trait FileContentFetcher: Send + Sync {
         ctx: &'b CoreContext,
         id: ContentId,
     ) -> Result<Option<Bytes>, ErrorKind>;
+
+    /// The first `size` bytes of a file, or all of it if it is shorter. Unlike `get_file_text`,
+    /// binary files are returned.
+    async fn peek_file<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        id: ContentId,
+        size: usize,
+    ) -> Result<Option<Bytes>, ErrorKind>;
+
+    /// The names of the entries of the directory at `path` (the root directory if `None`) in a
+    /// changeset, or `None` if there is no directory there.
+    async fn list_directory<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        cs_id: ChangesetId,
+        path: Option<MPath>,
+    ) -> Result<Option<Vec<MPathElement>>, ErrorKind>;
+
+    /// The content of the file at `path` in the changeset a bookmark points to, or `None` if the
+    /// bookmark or the file doesn't exist.
+    async fn find_file_in_bookmark<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        bookmark: &BookmarkName,
+        path: MPath,
+    ) -> Result<Option<ContentId>, ErrorKind>;
 }
@@ -8,9 +8,10 @@
 use crate::{ErrorKind, FileContentFetcher};
 
 use async_trait::async_trait;
+use bookmarks::BookmarkName;
 use bytes::Bytes;
 use context::CoreContext;
-use mononoke_types::ContentId;
+use mononoke_types::{ChangesetId, ContentId, MPath, MPathElement};
 use std::sync::Arc;
 
 const NULL: u8 = 0;
@@ -62,6 +63,33 @@ impl<T: FileContentFetcher + 'static> FileContentFetcher for TextOnlyFileContent
             }
         }))
     }
+
+    async fn peek_file<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        id: ContentId,
+        size: usize,
+    ) -> Result<Option<Bytes>, ErrorKind> {
+        self.inner.peek_file(ctx, id, size).await
+    }
+
+    async fn list_directory<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        cs_id: ChangesetId,
+        path: Option<MPath>,
+    ) -> Result<Option<Vec<MPathElement>>, ErrorKind> {
+        self.inner.list_directory(ctx, cs_id, path).await
+    }
+
+    async fn find_file_in_bookmark<'a, 'b: 'a>(
+        &'a self,
+        ctx: &'b CoreContext,
+        bookmark: &BookmarkName,
+        path: MPath,
+    ) -> Result<Option<ContentId>, ErrorKind> {
+        self.inner.find_file_in_bookmark(ctx, bookmark, path).await
+    }
 }
 
 fn looks_like_binary(file_bytes: &[u8]) -> bool {
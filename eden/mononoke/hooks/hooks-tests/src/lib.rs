@@ -12,6 +12,7 @@ use async_trait::async_trait;
 use blobrepo::BlobRepo;
 use blobstore::Loadable;
 use bookmarks::{BookmarkName, BookmarkUpdateReason};
+use bytes::Bytes;
 use context::CoreContext;
 use fbinit::FacebookInit;
 use futures::{
@@ -20,20 +21,37 @@ use futures::{
     stream::{futures_unordered, TryStreamExt},
 };
 use hooks::{
-    hook_loader::load_hooks, ChangesetHook, ErrorKind, FileHook, HookExecution, HookManager,
-    HookRejectionInfo,
+    builtin_hooks::{
+        make_changeset_hook, make_file_hook, BLOCK_BINARY_FILES, COMMIT_MESSAGE_PATTERN,
+        CONFLICT_MARKERS, DENY_PATHS, LIMIT_COMMIT_MESSAGE_LENGTH, LIMIT_FILESIZE,
+        NO_CASE_CONFLICTS, NO_EXECUTABLE_FILES, NO_MERGE_COMMITS, NO_SYMLINKS, REQUIRE_REVIEWERS,
+    },
+    hook_loader::load_hooks,
+    lfs_locks::{BlockLfsLockedFilesHook, BLOCK_LFS_LOCKED_FILES},
+    wasm_hooks::WasmHook,
+    ChangesetHook, ErrorKind, FileHook, HookExecution, HookManager, HookOutcome, HookRejectionInfo,
 };
 use hooks_content_stores::{
     BlobRepoFileContentFetcher, FileContentFetcher, InMemoryFileContentFetcher,
 };
+use lfs_locks::SqlLfsLocks;
 use maplit::{btreemap, hashmap, hashset};
-use metaconfig_types::{BookmarkParams, HookConfig, HookParams, RepoConfig};
+use metaconfig_types::{BookmarkParams, HookBypass, HookConfig, HookParams, RepoConfig};
 use mononoke_types::{BonsaiChangeset, BonsaiChangesetMut, DateTime, FileChange, FileType, MPath};
+use mononoke_types_mocks::changesetid::{ONES_CSID, TWOS_CSID};
 use mononoke_types_mocks::contentid::{ONES_CTID, THREES_CTID, TWOS_CTID};
+use mononoke_types_mocks::repo::REPO_ZERO;
+use permission_checker::{
+    ArcMembershipChecker, MembershipCheckerBuilder, MononokeIdentity, MononokeIdentitySet,
+    PermissionCheckerBuilder,
+};
 use regex::Regex;
 use scuba_ext::ScubaSampleBuilder;
+use sql_construct::SqlConstruct;
 use std::collections::hash_map::Entry;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
 use tests_utils::{create_commit, store_files};
 
 #[derive(Clone, Debug)]
@@ -499,6 +517,63 @@ fn test_changeset_hook_lengths(fb: FacebookInit) {
     });
 }
 
+async fn lfs_locks_with_lock(path: &str) -> SqlLfsLocks {
+    let locks = SqlLfsLocks::with_sqlite_in_memory().unwrap();
+    let owner = MononokeIdentity::from_str("USER:someone_else").unwrap();
+    locks
+        .create_lock(REPO_ZERO, path.to_string(), &owner)
+        .await
+        .unwrap();
+    locks
+}
+
+#[fbinit::test]
+fn test_lfs_locked_files_hook_accepted(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let locks = lfs_locks_with_lock("dir1/unrelated").await;
+        let hooks: HashMap<String, Box<dyn ChangesetHook>> = hashmap! {
+            "hook1".to_string() => Box::new(BlockLfsLockedFilesHook::new(REPO_ZERO, locks)) as Box<dyn ChangesetHook>
+        };
+        let bookmarks = hashmap! {
+            "bm1".to_string() => vec!["hook1".to_string()]
+        };
+        let regexes = hashmap! {};
+        let expected = hashmap! {
+            "hook1".to_string() => HookExecution::Accepted
+        };
+        run_changeset_hooks(ctx, "bm1", hooks, bookmarks, regexes, expected).await;
+    });
+}
+
+#[fbinit::test]
+fn test_lfs_locked_files_hook_rejected(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let locks = lfs_locks_with_lock("dir1/subdir1/subsubdir2/file_1").await;
+        let content_fetcher = InMemoryFileContentFetcher::new();
+
+        let res = BlockLfsLockedFilesHook::new(REPO_ZERO, locks)
+            .run(
+                &ctx,
+                &BookmarkName::new("bm1").unwrap(),
+                &default_changeset(),
+                &content_fetcher,
+            )
+            .await
+            .unwrap();
+
+        match res {
+            HookExecution::Rejected(info) => {
+                assert!(info
+                    .long_description
+                    .contains("dir1/subdir1/subsubdir2/file_1 (locked by USER:someone_else)"));
+            }
+            HookExecution::Accepted => panic!("Push of a locked file was accepted"),
+        }
+    });
+}
+
 #[fbinit::test]
 fn test_file_hook_accepted(fb: FacebookInit) {
     async_unit::tokio_unit_test(async move {
@@ -836,6 +911,7 @@ fn test_cs_hooks_with_blob_store(fb: FacebookInit) {
             regexes.clone(),
             expected,
             ContentFetcherType::Blob(fixtures::many_files_dirs::getrepo(ctx.fb).await),
+            default_changeset(),
         )
         .await;
     });
@@ -926,6 +1002,789 @@ fn test_file_hooks_with_blob_store(fb: FacebookInit) {
     })
 }
 
+#[fbinit::test]
+fn test_builtin_file_hooks(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let limit_filesize_config = HookConfig {
+            ints: hashmap! {"filesize_limit".to_string() => 16},
+            ..Default::default()
+        };
+        let deny_paths_config = HookConfig {
+            strings: hashmap! {
+                "deny_path_regex".to_string() => "subsubdir2/".to_string(),
+                "allow_path_regex".to_string() => "file_2$".to_string(),
+            },
+            ..Default::default()
+        };
+        let hooks: HashMap<String, Box<dyn FileHook>> = hashmap! {
+            LIMIT_FILESIZE.to_string() => make_file_hook(LIMIT_FILESIZE, &limit_filesize_config).unwrap().unwrap(),
+            DENY_PATHS.to_string() => make_file_hook(DENY_PATHS, &deny_paths_config).unwrap().unwrap(),
+            NO_SYMLINKS.to_string() => make_file_hook(NO_SYMLINKS, &Default::default()).unwrap().unwrap(),
+            NO_EXECUTABLE_FILES.to_string() => make_file_hook(NO_EXECUTABLE_FILES, &Default::default()).unwrap().unwrap(),
+        };
+        let bookmarks = hashmap! {
+            "bm1".to_string() => hooks.keys().cloned().collect()
+        };
+        let regexes = hashmap! {};
+        let expected = hashmap! {
+            LIMIT_FILESIZE.to_string() => hashmap! {
+                "dir1/subdir1/subsubdir1/file_1".to_string() => HookExecution::Accepted,
+                "dir1/subdir1/subsubdir2/file_1".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "File too large",
+                    "File size limit is 16 bytes. You tried to push file dir1/subdir1/subsubdir2/file_1 that is over the limit (17 bytes).".to_string(),
+                )),
+                "dir1/subdir1/subsubdir2/file_2".to_string() => HookExecution::Accepted,
+            },
+            DENY_PATHS.to_string() => hashmap! {
+                "dir1/subdir1/subsubdir1/file_1".to_string() => HookExecution::Accepted,
+                "dir1/subdir1/subsubdir2/file_1".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Denied path",
+                    "Changes to dir1/subdir1/subsubdir2/file_1 are not allowed: paths matching subsubdir2/ are denied.".to_string(),
+                )),
+                "dir1/subdir1/subsubdir2/file_2".to_string() => HookExecution::Accepted,
+            },
+            NO_SYMLINKS.to_string() => hashmap! {
+                "dir1/subdir1/subsubdir1/file_1".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Symlinks are not allowed",
+                    "dir1/subdir1/subsubdir1/file_1 is a symlink, and symlinks are not allowed.".to_string(),
+                )),
+                "dir1/subdir1/subsubdir2/file_1".to_string() => HookExecution::Accepted,
+                "dir1/subdir1/subsubdir2/file_2".to_string() => HookExecution::Accepted,
+            },
+            NO_EXECUTABLE_FILES.to_string() => hashmap! {
+                "dir1/subdir1/subsubdir1/file_1".to_string() => HookExecution::Accepted,
+                "dir1/subdir1/subsubdir2/file_1".to_string() => HookExecution::Accepted,
+                "dir1/subdir1/subsubdir2/file_2".to_string() => HookExecution::Accepted,
+            },
+        };
+        run_file_hooks(
+            ctx,
+            "bm1",
+            hooks,
+            bookmarks,
+            regexes,
+            expected,
+            ContentFetcherType::InMemory,
+        )
+        .await;
+    });
+}
+
+#[fbinit::test]
+fn test_builtin_content_hooks_with_blob_store(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let (repo, bcs_id) = {
+            let repo = blobrepo_factory::new_memblob_empty(None).unwrap();
+            let bcs_id = create_commit(
+                ctx.clone(),
+                repo.clone(),
+                vec![],
+                store_files(
+                    ctx.clone(),
+                    btreemap! {
+                        "conflicted" => Some("a\n<<<<<<< local\nb\n=======\nc\n>>>>>>> other\n"),
+                        "binary" => Some("a\0b"),
+                        "lfs/binary" => Some("a\0b"),
+                        "text" => Some("a\nb\n"),
+                    },
+                    repo.clone(),
+                )
+                .await,
+            )
+            .await;
+            (repo, bcs_id)
+        };
+
+        let binary_files_config = HookConfig {
+            strings: hashmap! {"allow_path_regex".to_string() => "^lfs/".to_string()},
+            ..Default::default()
+        };
+        let hooks: HashMap<String, Box<dyn FileHook>> = hashmap! {
+            CONFLICT_MARKERS.to_string() => make_file_hook(CONFLICT_MARKERS, &Default::default()).unwrap().unwrap(),
+            BLOCK_BINARY_FILES.to_string() => make_file_hook(BLOCK_BINARY_FILES, &binary_files_config).unwrap().unwrap(),
+        };
+        let bookmarks = hashmap! {
+            "master".to_string() => hooks.keys().cloned().collect()
+        };
+        let regexes = hashmap! {};
+        let expected = hashmap! {
+            CONFLICT_MARKERS.to_string() => hashmap! {
+                "conflicted".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Conflict markers were found in file",
+                    "Conflict markers were found in conflicted at line 2. Resolve the conflict before pushing.".to_string(),
+                )),
+                "binary".to_string() => HookExecution::Accepted,
+                "lfs/binary".to_string() => HookExecution::Accepted,
+                "text".to_string() => HookExecution::Accepted,
+            },
+            BLOCK_BINARY_FILES.to_string() => hashmap! {
+                "conflicted".to_string() => HookExecution::Accepted,
+                "binary".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Binary file not in LFS",
+                    "binary is a binary file, and binary files must be stored in LFS.".to_string(),
+                )),
+                "lfs/binary".to_string() => HookExecution::Accepted,
+                "text".to_string() => HookExecution::Accepted,
+            },
+        };
+
+        let bcs = bcs_id
+            .load(ctx.clone(), &repo.get_blobstore())
+            .compat()
+            .await
+            .expect("Can't load commit");
+        run_file_hooks_for_cs(
+            ctx,
+            "master",
+            hooks,
+            bookmarks,
+            regexes,
+            expected,
+            ContentFetcherType::Blob(repo),
+            bcs,
+        )
+        .await;
+    })
+}
+
+#[fbinit::test]
+fn test_builtin_changeset_hooks(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let reviewers_membership: ArcMembershipChecker =
+            Arc::from(MembershipCheckerBuilder::never_member());
+        let make_hook = |name: &str, config: HookConfig| {
+            make_changeset_hook(name, &config, reviewers_membership.clone())
+                .unwrap()
+                .unwrap()
+        };
+        let hooks: HashMap<String, Box<dyn ChangesetHook>> = hashmap! {
+            "short_message".to_string() => make_hook(LIMIT_COMMIT_MESSAGE_LENGTH, HookConfig {
+                ints: hashmap! {"length_limit".to_string() => 10},
+                ..Default::default()
+            }),
+            "long_message".to_string() => make_hook(LIMIT_COMMIT_MESSAGE_LENGTH, HookConfig {
+                ints: hashmap! {"length_limit".to_string() => 100},
+                ..Default::default()
+            }),
+            "required_pattern".to_string() => make_hook(COMMIT_MESSAGE_PATTERN, HookConfig {
+                strings: hashmap! {"required_pattern".to_string() => "^This".to_string()},
+                ..Default::default()
+            }),
+            "forbidden_pattern".to_string() => make_hook(COMMIT_MESSAGE_PATTERN, HookConfig {
+                strings: hashmap! {"forbidden_pattern".to_string() => "com+it".to_string()},
+                ..Default::default()
+            }),
+            NO_CASE_CONFLICTS.to_string() => make_hook(NO_CASE_CONFLICTS, Default::default()),
+            NO_MERGE_COMMITS.to_string() => make_hook(NO_MERGE_COMMITS, Default::default()),
+            REQUIRE_REVIEWERS.to_string() => make_hook(REQUIRE_REVIEWERS, Default::default()),
+        };
+        let bookmarks = hashmap! {
+            "bm1".to_string() => hooks.keys().cloned().collect()
+        };
+        let regexes = hashmap! {};
+        let expected = hashmap! {
+            "short_message".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Commit message too long",
+                format!(
+                    "Commit message length for {} (24) exceeds length limit (>= 10)",
+                    default_changeset().get_changeset_id()
+                ),
+            )),
+            "long_message".to_string() => HookExecution::Accepted,
+            "required_pattern".to_string() => HookExecution::Accepted,
+            "forbidden_pattern".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Commit message matches a forbidden pattern",
+                "Commit messages must not match com+it, but found 'commit'.".to_string(),
+            )),
+            NO_CASE_CONFLICTS.to_string() => HookExecution::Accepted,
+            NO_MERGE_COMMITS.to_string() => HookExecution::Accepted,
+            REQUIRE_REVIEWERS.to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Not enough reviewers",
+                "Commits need at least 1 reviewer(s) on a 'Reviewed By:' line, but 0 found.".to_string(),
+            )),
+        };
+        run_changeset_hooks(ctx, "bm1", hooks, bookmarks, regexes, expected).await;
+    });
+}
+
+#[fbinit::test]
+fn test_builtin_case_conflicts_with_blob_store(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let (repo, bcs_id) = {
+            let repo = blobrepo_factory::new_memblob_empty(None).unwrap();
+            let parent = create_commit(
+                ctx.clone(),
+                repo.clone(),
+                vec![],
+                store_files(
+                    ctx.clone(),
+                    btreemap! {
+                        "Dir/file" => Some("a"),
+                        "README" => Some("b"),
+                        "Removed" => Some("c"),
+                    },
+                    repo.clone(),
+                )
+                .await,
+            )
+            .await;
+            let bcs_id = create_commit(
+                ctx.clone(),
+                repo.clone(),
+                vec![parent],
+                store_files(
+                    ctx.clone(),
+                    btreemap! {
+                        "dir/other" => Some("d"),
+                        "readme" => Some("e"),
+                        "Removed" => None,
+                        "removed" => Some("f"),
+                        "Foo" => Some("g"),
+                        "foo" => Some("h"),
+                    },
+                    repo.clone(),
+                )
+                .await,
+            )
+            .await;
+            (repo, bcs_id)
+        };
+
+        let hooks: HashMap<String, Box<dyn ChangesetHook>> = hashmap! {
+            NO_CASE_CONFLICTS.to_string() => make_changeset_hook(
+                NO_CASE_CONFLICTS,
+                &Default::default(),
+                Arc::from(MembershipCheckerBuilder::never_member()),
+            )
+            .unwrap()
+            .unwrap(),
+        };
+        let bookmarks = hashmap! {
+            "master".to_string() => hooks.keys().cloned().collect()
+        };
+        let regexes = hashmap! {};
+        let expected = hashmap! {
+            NO_CASE_CONFLICTS.to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Case conflict found",
+                "These paths only differ by case, which is not supported on case-insensitive filesystems: Foo and foo, dir and Dir, readme and README.".to_string(),
+            )),
+        };
+
+        let bcs = bcs_id
+            .load(ctx.clone(), &repo.get_blobstore())
+            .compat()
+            .await
+            .expect("Can't load commit");
+        run_changeset_hooks_with_mgr(
+            ctx,
+            "master",
+            hooks,
+            bookmarks,
+            regexes,
+            expected,
+            ContentFetcherType::Blob(repo),
+            bcs,
+        )
+        .await;
+    })
+}
+
+#[fbinit::test]
+fn test_builtin_merge_and_reviewers_hooks(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let mut reviewers = MononokeIdentitySet::new();
+        reviewers.insert(MononokeIdentity::new("USER", "jsgf").unwrap());
+        reviewers.insert(MononokeIdentity::new("USER", "alice").unwrap());
+        let reviewers_membership: ArcMembershipChecker =
+            Arc::from(MembershipCheckerBuilder::whitelist_checker(reviewers));
+        let make_hook = |name: &str, config: HookConfig| {
+            make_changeset_hook(name, &config, reviewers_membership.clone())
+                .unwrap()
+                .unwrap()
+        };
+        let hooks: HashMap<String, Box<dyn ChangesetHook>> = hashmap! {
+            NO_MERGE_COMMITS.to_string() => make_hook(NO_MERGE_COMMITS, Default::default()),
+            "one_reviewer".to_string() => make_hook(REQUIRE_REVIEWERS, HookConfig {
+                ints: hashmap! {"verify_reviewers".to_string() => 1},
+                ..Default::default()
+            }),
+            "two_reviewers".to_string() => make_hook(REQUIRE_REVIEWERS, HookConfig {
+                ints: hashmap! {
+                    "min_reviewers".to_string() => 2,
+                    "verify_reviewers".to_string() => 1,
+                },
+                ..Default::default()
+            }),
+        };
+        let bookmarks = hashmap! {
+            "bm1".to_string() => hooks.keys().cloned().collect()
+        };
+        let regexes = hashmap! {};
+        // The author doesn't count as a reviewer, and "bob" is not in the reviewers group.
+        let expected = hashmap! {
+            NO_MERGE_COMMITS.to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Merge commits are not allowed",
+                "Merge commits are not allowed on bm1. Rebase your changes instead.".to_string(),
+            )),
+            "one_reviewer".to_string() => HookExecution::Accepted,
+            "two_reviewers".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Not enough reviewers",
+                "Commits need at least 2 reviewer(s) on a 'Reviewed By:' line, but 1 found.".to_string(),
+            )),
+        };
+
+        let mut cs = default_changeset().into_mut();
+        cs.parents = vec![ONES_CSID, TWOS_CSID];
+        cs.message = "Merge things\n\nReviewed By: jsgf, alice, bob\n".to_string();
+        run_changeset_hooks_with_mgr(
+            ctx,
+            "bm1",
+            hooks,
+            bookmarks,
+            regexes,
+            expected,
+            ContentFetcherType::InMemory,
+            cs.freeze().expect("Created changeset"),
+        )
+        .await;
+    });
+}
+
+#[fbinit::test]
+fn test_builtin_hooks_invalid_config(_fb: FacebookInit) {
+    let no_limit = HookConfig::default();
+    match make_file_hook(LIMIT_FILESIZE, &no_limit)
+        .err()
+        .expect("limit_filesize requires a filesize_limit")
+        .downcast::<ErrorKind>()
+    {
+        Ok(ErrorKind::InvalidHookConfig(hook_name, key, _)) => {
+            assert_eq!(hook_name, LIMIT_FILESIZE.to_string());
+            assert_eq!(key, "filesize_limit".to_string());
+        }
+        _ => assert!(false, "Unexpected err type"),
+    };
+
+    let bad_regex = HookConfig {
+        strings: hashmap! {"deny_path_regex".to_string() => "(".to_string()},
+        ..Default::default()
+    };
+    match make_file_hook(DENY_PATHS, &bad_regex)
+        .err()
+        .expect("deny_paths requires a valid regex")
+        .downcast::<ErrorKind>()
+    {
+        Ok(ErrorKind::InvalidHookConfig(hook_name, key, _)) => {
+            assert_eq!(hook_name, DENY_PATHS.to_string());
+            assert_eq!(key, "deny_path_regex".to_string());
+        }
+        _ => assert!(false, "Unexpected err type"),
+    };
+
+    assert!(make_file_hook("no_such_hook", &Default::default())
+        .unwrap()
+        .is_none());
+}
+
+#[fbinit::test]
+fn test_advisory_hook_warns(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let bookmarks = hashmap! {
+            "bm1".to_string() => vec!["hook1".to_string(), "hook2".to_string()]
+        };
+        let mut hook_manager =
+            setup_hook_manager(fb, bookmarks, hashmap! {}, ContentFetcherType::InMemory).await;
+        hook_manager.register_changeset_hook(
+            "hook1",
+            always_rejecting_changeset_hook(),
+            HookConfig {
+                advisory: true,
+                ..Default::default()
+            },
+        );
+        hook_manager.register_file_hook(
+            "hook2",
+            length_matching_file_hook(4),
+            HookConfig {
+                advisory: true,
+                ..Default::default()
+            },
+        );
+
+        let outcomes = hook_manager
+            .run_hooks_for_bookmark(
+                &ctx,
+                vec![default_changeset()].iter(),
+                &BookmarkName::new("bm1").unwrap(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(outcomes.iter().all(HookOutcome::is_accept));
+        let warnings: HashSet<_> = outcomes
+            .iter()
+            .filter(|outcome| outcome.is_warning())
+            .map(|outcome| {
+                (
+                    outcome.get_hook_name().to_string(),
+                    outcome.get_file_path().map(|path| path.to_string()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            hashset! {
+                ("hook1".to_string(), None),
+                ("hook2".to_string(), Some("dir1/subdir1/subsubdir1/file_1".to_string())),
+                ("hook2".to_string(), Some("dir1/subdir1/subsubdir2/file_1".to_string())),
+            }
+        );
+    });
+}
+
+#[fbinit::test]
+fn test_bypassed_hooks_do_not_run(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let bookmarks = hashmap! {
+            "bm1".to_string() => vec!["hook1".to_string(), "hook2".to_string()]
+        };
+        let mut hook_manager =
+            setup_hook_manager(fb, bookmarks, hashmap! {}, ContentFetcherType::InMemory).await;
+        hook_manager.register_changeset_hook(
+            "hook1",
+            always_rejecting_changeset_hook(),
+            HookConfig {
+                bypass: Some(HookBypass::CommitMessage("commit message".to_string())),
+                ..Default::default()
+            },
+        );
+        hook_manager.register_changeset_hook(
+            "hook2",
+            always_rejecting_changeset_hook(),
+            HookConfig {
+                bypass: Some(HookBypass::Pushvar {
+                    name: "BYPASS_HOOK2".to_string(),
+                    value: "true".to_string(),
+                }),
+                ..Default::default()
+            },
+        );
+
+        let pushvars = hashmap! {"BYPASS_HOOK2".to_string() => Bytes::from("true")};
+        let outcomes = hook_manager
+            .run_hooks_for_bookmark(
+                &ctx,
+                vec![default_changeset()].iter(),
+                &BookmarkName::new("bm1").unwrap(),
+                Some(&pushvars),
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcomes, vec![]);
+    });
+}
+
+#[fbinit::test]
+fn test_bypass_needs_permission(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let bookmarks = hashmap! {
+            "bm1".to_string() => vec!["hook1".to_string()]
+        };
+        let mut hook_manager =
+            setup_hook_manager(fb, bookmarks, hashmap! {}, ContentFetcherType::InMemory).await;
+        hook_manager.register_changeset_hook(
+            "hook1",
+            always_rejecting_changeset_hook(),
+            HookConfig {
+                bypass: Some(HookBypass::CommitMessage("commit message".to_string())),
+                ..Default::default()
+            },
+        );
+        hook_manager.set_permission_checker(PermissionCheckerBuilder::always_reject().into());
+
+        let outcomes = hook_manager
+            .run_hooks_for_bookmark(
+                &ctx,
+                vec![default_changeset()].iter(),
+                &BookmarkName::new("bm1").unwrap(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(outcomes[0].is_rejection());
+    });
+}
+
+const WASM_ACCEPT: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "run") (param i32 i32) (result i32) (i32.const 0)))
+"#;
+
+const WASM_REJECT: &str = r#"
+(module
+  (import "mononoke" "reject" (func $reject (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "No thanks")
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "run") (param i32 i32) (result i32)
+    (call $reject (i32.const 0) (i32.const 9))
+    (i32.const 1)))
+"#;
+
+// Accepts the default changeset if the content of the symlink isn't available, and the content
+// of the last file is "eels".
+const WASM_READ_FILES: &str = r#"
+(module
+  (import "mononoke" "file_size" (func $file_size (param i32) (result i64)))
+  (import "mononoke" "read_file" (func $read_file (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "run") (param i32 i32) (result i32)
+    (if (i64.ne (call $file_size (i32.const 0)) (i64.const -1))
+      (then (return (i32.const 1))))
+    (if (i64.ne (call $file_size (i32.const 2)) (i64.const 4))
+      (then (return (i32.const 1))))
+    (if (i32.ne (call $read_file (i32.const 2) (i32.const 0)) (i32.const 4))
+      (then (return (i32.const 1))))
+    (i32.ne (i32.load8_u (i32.const 0)) (i32.const 101))))
+"#;
+
+const WASM_LOOP: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "run") (param i32 i32) (result i32)
+    (loop $forever (br $forever))
+    (i32.const 0)))
+"#;
+
+fn wasm_hook(module: &str) -> Box<dyn ChangesetHook> {
+    wasm_hook_with_config(HookConfig {
+        strings: hashmap! {"wasm_module".to_string() => module.to_string()},
+        ..Default::default()
+    })
+}
+
+fn wasm_hook_with_config(config: HookConfig) -> Box<dyn ChangesetHook> {
+    Box::new(
+        WasmHook::from_config("wasm_hook", &config)
+            .unwrap()
+            .expect("the config has a module"),
+    )
+}
+
+#[fbinit::test]
+fn test_wasm_hooks(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let hooks: HashMap<String, Box<dyn ChangesetHook>> = hashmap! {
+            "accept".to_string() => wasm_hook(WASM_ACCEPT),
+            "reject".to_string() => wasm_hook(WASM_REJECT),
+            "read_files".to_string() => wasm_hook(WASM_READ_FILES),
+        };
+        let bookmarks = hashmap! {
+            "bm1".to_string() => hooks.keys().cloned().collect()
+        };
+        let regexes = hashmap! {};
+        let expected = hashmap! {
+            "accept".to_string() => HookExecution::Accepted,
+            "reject".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Rejected by WebAssembly hook",
+                "No thanks".to_string(),
+            )),
+            "read_files".to_string() => HookExecution::Accepted,
+        };
+        run_changeset_hooks(ctx, "bm1", hooks, bookmarks, regexes, expected).await;
+    });
+}
+
+#[fbinit::test]
+fn test_wasm_hook_max_total_file_size(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let read_files = |max_total_file_size| {
+            wasm_hook_with_config(HookConfig {
+                strings: hashmap! {"wasm_module".to_string() => WASM_READ_FILES.to_string()},
+                ints: hashmap! {"max_total_file_size".to_string() => max_total_file_size},
+                ..Default::default()
+            })
+        };
+        // The regular files of the default changeset are 17 and 2 bytes large.
+        let hooks: HashMap<String, Box<dyn ChangesetHook>> = hashmap! {
+            "all_files".to_string() => read_files(19),
+            "first_file".to_string() => read_files(18),
+        };
+        let bookmarks = hashmap! {
+            "bm1".to_string() => hooks.keys().cloned().collect()
+        };
+        let regexes = hashmap! {};
+        let expected = hashmap! {
+            "all_files".to_string() => HookExecution::Accepted,
+            "first_file".to_string() => HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Rejected by WebAssembly hook",
+                None,
+            )),
+        };
+        run_changeset_hooks(ctx, "bm1", hooks, bookmarks, regexes, expected).await;
+    });
+}
+
+#[fbinit::test]
+fn test_wasm_hook_from_repo(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = blobrepo_factory::new_memblob_empty(None).unwrap();
+        let set_module = |module: &'static str| {
+            let ctx = ctx.clone();
+            let repo = repo.clone();
+            async move {
+                let bcs_id = create_commit(
+                    ctx.clone(),
+                    repo.clone(),
+                    vec![],
+                    store_files(
+                        ctx.clone(),
+                        btreemap! {"hooks/check.wat" => Some(module)},
+                        repo.clone(),
+                    )
+                    .await,
+                )
+                .await;
+                let mut txn = repo.update_bookmark_transaction(ctx.clone());
+                txn.force_set(
+                    &BookmarkName::new("master").unwrap(),
+                    bcs_id,
+                    BookmarkUpdateReason::TestMove {
+                        bundle_replay_data: None,
+                    },
+                )
+                .unwrap();
+                txn.commit().compat().await.unwrap();
+            }
+        };
+        let hook = WasmHook::from_config(
+            "wasm_hook",
+            &HookConfig {
+                strings: hashmap! {
+                    "wasm_module_repo_path".to_string() => "hooks/check.wat".to_string(),
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .expect("the config has a module");
+        let content_fetcher = BlobRepoFileContentFetcher::new(repo.clone());
+        let run_hook = |bookmark: &'static str| {
+            let (hook, ctx, content_fetcher) = (&hook, &ctx, &content_fetcher);
+            async move {
+                let bookmark = BookmarkName::new(bookmark).unwrap();
+                hook.run(ctx, &bookmark, &default_changeset(), content_fetcher)
+                    .await
+            }
+        };
+
+        run_hook("master")
+            .await
+            .expect_err("there is no module in the repo yet");
+
+        set_module(WASM_REJECT).await;
+        assert_eq!(
+            run_hook("master").await.unwrap(),
+            HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Rejected by WebAssembly hook",
+                "No thanks".to_string(),
+            ))
+        );
+
+        // The module is compiled again once it changes.
+        set_module(WASM_ACCEPT).await;
+        assert_eq!(run_hook("master").await.unwrap(), HookExecution::Accepted);
+
+        run_hook("other")
+            .await
+            .expect_err("there is no module in other bookmarks");
+    });
+}
+
+#[fbinit::test]
+fn test_wasm_hook_out_of_fuel(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let ctx = CoreContext::test_mock(fb);
+        let config = HookConfig {
+            strings: hashmap! {"wasm_module".to_string() => WASM_LOOP.to_string()},
+            ints: hashmap! {"fuel".to_string() => 10000},
+            ..Default::default()
+        };
+        let hook = WasmHook::from_config("wasm_hook", &config)
+            .unwrap()
+            .expect("the config has a module");
+
+        hook.run(
+            &ctx,
+            &BookmarkName::new("bm1").unwrap(),
+            &default_changeset(),
+            &InMemoryFileContentFetcher::new(),
+        )
+        .await
+        .expect_err("the hook should run out of fuel");
+    });
+}
+
+#[fbinit::test]
+fn test_load_wasm_hook(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let mut config = RepoConfig::default();
+        config.bookmarks = vec![BookmarkParams {
+            bookmark: BookmarkName::new("bm1").unwrap().into(),
+            hooks: vec!["wasm_hook".into()],
+            only_fast_forward: false,
+            allowed_users: None,
+            rewrite_dates: None,
+        }];
+        config.hooks = vec![HookParams {
+            name: "wasm_hook".into(),
+            config: HookConfig {
+                strings: hashmap! {"wasm_module".to_string() => WASM_ACCEPT.to_string()},
+                ..Default::default()
+            },
+        }];
+
+        let mut hm = hook_manager_many_files_dirs_blobrepo(fb).await;
+        load_hooks(fb, &mut hm, config, &hashset![]).expect("the wasm hook should load");
+
+        let mut config = RepoConfig::default();
+        config.hooks = vec![HookParams {
+            name: "wasm_hook".into(),
+            config: HookConfig {
+                strings: hashmap! {"wasm_module".to_string() => "(module".to_string()},
+                ..Default::default()
+            },
+        }];
+        match load_hooks(fb, &mut hm, config, &hashset![])
+            .unwrap_err()
+            .downcast::<ErrorKind>()
+        {
+            Ok(ErrorKind::InvalidHookConfig(hook_name, key, _)) => {
+                assert_eq!(hook_name, "wasm_hook".to_string());
+                assert_eq!(key, "wasm_module".to_string());
+            }
+            _ => assert!(false, "Unexpected err type"),
+        };
+    });
+}
+
 async fn run_changeset_hooks(
     ctx: CoreContext,
     bookmark_name: &str,
@@ -942,6 +1801,7 @@ async fn run_changeset_hooks(
         regexes,
         expected,
         ContentFetcherType::InMemory,
+        default_changeset(),
     )
     .await
 }
@@ -954,6 +1814,7 @@ async fn run_changeset_hooks_with_mgr(
     regexes: HashMap<String, Vec<String>>,
     expected: HashMap<String, HookExecution>,
     content_fetcher_type: ContentFetcherType,
+    cs: BonsaiChangeset,
 ) {
     let mut hook_manager =
         setup_hook_manager(ctx.fb, bookmarks, regexes, content_fetcher_type).await;
@@ -963,7 +1824,7 @@ async fn run_changeset_hooks_with_mgr(
     let res = hook_manager
         .run_hooks_for_bookmark(
             &ctx,
-            vec![default_changeset()].iter(),
+            vec![cs].iter(),
             &BookmarkName::new(bookmark_name).unwrap(),
             None,
         )
@@ -1268,3 +2129,44 @@ fn test_load_disabled_hooks_hook_does_not_exist(fb: FacebookInit) {
         };
     });
 }
+
+#[fbinit::test]
+fn test_load_lfs_locked_files_hook_requires_locking(fb: FacebookInit) {
+    async_unit::tokio_unit_test(async move {
+        let mut config = RepoConfig::default();
+
+        config.bookmarks = vec![];
+        config.hooks = vec![HookParams {
+            name: BLOCK_LFS_LOCKED_FILES.into(),
+            config: Default::default(),
+        }];
+
+        let mut hm = hook_manager_many_files_dirs_blobrepo(fb).await;
+
+        match load_hooks(fb, &mut hm, config.clone(), &hashset![])
+            .unwrap_err()
+            .downcast::<ErrorKind>()
+        {
+            Ok(ErrorKind::LfsLockingDisabled(hook_name)) => {
+                assert_eq!(hook_name, BLOCK_LFS_LOCKED_FILES.to_string());
+            }
+            _ => assert!(false, "Unexpected err type"),
+        };
+
+        // Without a lock store, the hook is skipped, along with its uses.
+        config.lfs.locking_enabled = true;
+        config.bookmarks = vec![BookmarkParams {
+            bookmark: BookmarkName::new("bm1").unwrap().into(),
+            hooks: vec![BLOCK_LFS_LOCKED_FILES.into()],
+            only_fast_forward: false,
+            allowed_users: None,
+            rewrite_dates: None,
+        }];
+        load_hooks(fb, &mut hm, config.clone(), &hashset![])
+            .expect("the hook should be skipped without a lock store");
+
+        hm.set_lfs_locks(REPO_ZERO, SqlLfsLocks::with_sqlite_in_memory().unwrap());
+        load_hooks(fb, &mut hm, config, &hashset![])
+            .expect("the hook should load once locking is enabled");
+    });
+}
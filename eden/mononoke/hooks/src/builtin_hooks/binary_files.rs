@@ -0,0 +1,85 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::{FileChange, FileType, MPath};
+use regex::Regex;
+
+use super::{config_int, config_regex, is_binary, ALLOW_PATH_REGEX, BINARY_DETECTION_LENGTH};
+use crate::{FileHook, HookExecution, HookRejectionInfo};
+
+pub const BLOCK_BINARY_FILES: &str = "block_binary_files";
+
+/// Rejects binary files that would not be stored in LFS.
+///
+/// If the `lfs_threshold` int is set, files at least this large (in bytes) are stored in LFS, and
+/// are allowed whatever their content. Set it to the LFS threshold of the repository. Paths
+/// matching the `allow_path_regex` string are exempt. Only the start of files is fetched to tell
+/// whether they are binary, so large files are cheap to check.
+pub struct BlockBinaryFilesHook {
+    lfs_threshold: Option<u64>,
+    allow_path_regex: Option<Regex>,
+}
+
+impl BlockBinaryFilesHook {
+    pub fn new(config: &HookConfig) -> Result<Self> {
+        Ok(Self {
+            lfs_threshold: config_int(config, BLOCK_BINARY_FILES, "lfs_threshold")?,
+            allow_path_regex: config_regex(config, BLOCK_BINARY_FILES, ALLOW_PATH_REGEX)?,
+        })
+    }
+}
+
+#[async_trait]
+impl FileHook for BlockBinaryFilesHook {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        ctx: &'ctx CoreContext,
+        content_fetcher: &'fetcher dyn FileContentFetcher,
+        change: Option<&'change FileChange>,
+        path: &'path MPath,
+    ) -> Result<HookExecution, Error> {
+        let change = match change {
+            Some(change) if change.file_type() != FileType::Symlink => change,
+            _ => return Ok(HookExecution::Accepted),
+        };
+
+        if let Some(lfs_threshold) = self.lfs_threshold {
+            if change.size() >= lfs_threshold {
+                return Ok(HookExecution::Accepted);
+            }
+        }
+
+        let path = path.to_string();
+        if let Some(regex) = &self.allow_path_regex {
+            if regex.is_match(&path) {
+                return Ok(HookExecution::Accepted);
+            }
+        }
+
+        let content = content_fetcher
+            .peek_file(ctx, change.content_id(), BINARY_DETECTION_LENGTH)
+            .await?;
+
+        match content {
+            Some(content) if is_binary(&content) => {
+                Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Binary file not in LFS",
+                    format!(
+                        "{} is a binary file, and binary files must be stored in LFS.",
+                        path
+                    ),
+                )))
+            }
+            _ => Ok(HookExecution::Accepted),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use itertools::Itertools;
+use mononoke_types::{BonsaiChangeset, ChangesetId, MPath, MPathElement};
+
+use crate::{ChangesetHook, HookExecution, HookRejectionInfo};
+
+pub const NO_CASE_CONFLICTS: &str = "no_case_conflicts";
+
+/// Rejects changesets adding paths that only differ by case, which can't be checked out on
+/// case-insensitive filesystems. Directories are taken into account: adding `a/B` and `A/c`
+/// conflicts.
+///
+/// Paths added by the changeset are compared to each other, and to the paths in its parents. A
+/// file removed by the changeset doesn't conflict, but a directory does until all its files are
+/// removed.
+#[derive(Default)]
+pub struct NoCaseConflictsHook;
+
+impl NoCaseConflictsHook {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Group paths and their parent directories that only differ by case.
+fn find_case_conflicts<'a>(paths: impl Iterator<Item = &'a str>) -> Vec<BTreeSet<&'a str>> {
+    let mut by_lowercase: BTreeMap<String, BTreeSet<&'a str>> = BTreeMap::new();
+    for path in paths {
+        let prefixes = path
+            .match_indices('/')
+            .map(|(index, _)| &path[..index])
+            .chain(std::iter::once(path));
+        for prefix in prefixes {
+            by_lowercase
+                .entry(prefix.to_lowercase())
+                .or_default()
+                .insert(prefix);
+        }
+    }
+
+    by_lowercase
+        .into_iter()
+        .map(|(_, paths)| paths)
+        .filter(|paths| paths.len() > 1)
+        .collect()
+}
+
+fn lowercase(name: &MPathElement) -> String {
+    String::from_utf8_lossy(name.as_ref()).to_lowercase()
+}
+
+/// Find paths added by a changeset, or their parent directories, that only differ by case from a
+/// path in `parent`. Each directory is listed once.
+async fn find_parent_case_conflicts(
+    ctx: &CoreContext,
+    content_fetcher: &dyn FileContentFetcher,
+    parent: ChangesetId,
+    added: &[MPath],
+    removed: &HashSet<MPath>,
+) -> Result<BTreeSet<String>, Error> {
+    let mut names_by_dir: BTreeMap<Option<MPath>, BTreeSet<&MPathElement>> = BTreeMap::new();
+    for path in added {
+        let mut dir = None;
+        for name in path {
+            names_by_dir.entry(dir.clone()).or_default().insert(name);
+            dir = Some(MPath::join_opt_element(dir.as_ref(), name));
+        }
+    }
+
+    let mut conflicts = BTreeSet::new();
+    for (dir, names) in names_by_dir {
+        let entries = match content_fetcher
+            .list_directory(ctx, parent, dir.clone())
+            .await?
+        {
+            Some(entries) => entries,
+            None => continue,
+        };
+        for name in names {
+            let name_lowercase = lowercase(name);
+            for entry in &entries {
+                if entry == name || lowercase(entry) != name_lowercase {
+                    continue;
+                }
+                let existing = MPath::join_opt_element(dir.as_ref(), entry);
+                if removed.contains(&existing) {
+                    continue;
+                }
+                conflicts.insert(format!(
+                    "{} and {}",
+                    MPath::join_opt_element(dir.as_ref(), name),
+                    existing
+                ));
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+#[async_trait]
+impl ChangesetHook for NoCaseConflictsHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        let mut added = Vec::new();
+        let mut removed = HashSet::new();
+        for (path, change) in changeset.file_changes() {
+            match change {
+                Some(_) => added.push(path.clone()),
+                None => {
+                    removed.insert(path.clone());
+                }
+            }
+        }
+
+        let paths: Vec<String> = added.iter().map(MPath::to_string).collect();
+        let mut conflicts: BTreeSet<String> = find_case_conflicts(paths.iter().map(String::as_str))
+            .iter()
+            .map(|paths| paths.iter().join(" and "))
+            .collect();
+        for parent in changeset.parents() {
+            conflicts.extend(
+                find_parent_case_conflicts(ctx, content_fetcher, parent, &added, &removed).await?,
+            );
+        }
+        if conflicts.is_empty() {
+            return Ok(HookExecution::Accepted);
+        }
+
+        let conflicts = conflicts.iter().join(", ");
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "Case conflict found",
+            format!(
+                "These paths only differ by case, which is not supported on case-insensitive filesystems: {}.",
+                conflicts
+            ),
+        )))
+    }
+}
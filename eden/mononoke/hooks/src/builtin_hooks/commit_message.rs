@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::BonsaiChangeset;
+use regex::Regex;
+
+use super::{config_regex, invalid_config, required_config_int};
+use crate::{ChangesetHook, HookExecution, HookRejectionInfo};
+
+pub const LIMIT_COMMIT_MESSAGE_LENGTH: &str = "limit_commit_message_length";
+pub const COMMIT_MESSAGE_PATTERN: &str = "commit_message_pattern";
+
+/// Rejects commit messages longer than the `length_limit` int, in bytes.
+pub struct LimitCommitMessageLengthHook {
+    length_limit: u64,
+}
+
+impl LimitCommitMessageLengthHook {
+    pub fn new(config: &HookConfig) -> Result<Self> {
+        Ok(Self {
+            length_limit: required_config_int(config, LIMIT_COMMIT_MESSAGE_LENGTH, "length_limit")?,
+        })
+    }
+}
+
+#[async_trait]
+impl ChangesetHook for LimitCommitMessageLengthHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        let length = changeset.message().len() as u64;
+        if length <= self.length_limit {
+            return Ok(HookExecution::Accepted);
+        }
+
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "Commit message too long",
+            format!(
+                "Commit message length for {} ({}) exceeds length limit (>= {})",
+                changeset.get_changeset_id(),
+                length,
+                self.length_limit
+            ),
+        )))
+    }
+}
+
+/// Rejects commit messages that don't match the `required_pattern` string, or that match the
+/// `forbidden_pattern` string. At least one of them must be set.
+pub struct CommitMessagePatternHook {
+    required_pattern: Option<Regex>,
+    forbidden_pattern: Option<Regex>,
+}
+
+impl CommitMessagePatternHook {
+    pub fn new(config: &HookConfig) -> Result<Self> {
+        let required_pattern = config_regex(config, COMMIT_MESSAGE_PATTERN, "required_pattern")?;
+        let forbidden_pattern = config_regex(config, COMMIT_MESSAGE_PATTERN, "forbidden_pattern")?;
+        if required_pattern.is_none() && forbidden_pattern.is_none() {
+            return Err(invalid_config(
+                COMMIT_MESSAGE_PATTERN,
+                "required_pattern",
+                "either it or forbidden_pattern is required",
+            ));
+        }
+
+        Ok(Self {
+            required_pattern,
+            forbidden_pattern,
+        })
+    }
+}
+
+#[async_trait]
+impl ChangesetHook for CommitMessagePatternHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        let message = changeset.message();
+
+        if let Some(regex) = &self.required_pattern {
+            if !regex.is_match(message) {
+                return Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Commit message does not match the required pattern",
+                    format!("Commit messages must match {}.", regex),
+                )));
+            }
+        }
+
+        if let Some(regex) = &self.forbidden_pattern {
+            if let Some(found) = regex.find(message) {
+                return Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Commit message matches a forbidden pattern",
+                    format!(
+                        "Commit messages must not match {}, but found '{}'.",
+                        regex,
+                        found.as_str()
+                    ),
+                )));
+            }
+        }
+
+        Ok(HookExecution::Accepted)
+    }
+}
@@ -0,0 +1,95 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::{FileChange, FileType, MPath};
+use regex::Regex;
+
+use super::{config_int, config_regex, is_binary, ALLOW_PATH_REGEX};
+use crate::{FileHook, HookExecution, HookRejectionInfo};
+
+pub const CONFLICT_MARKERS: &str = "conflict_markers";
+
+/// Files larger than this are not checked, unless configured otherwise.
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Rejects text files containing merge conflict markers. Binary files, files larger than the
+/// `max_file_size` int (in bytes, 10MiB by default) and paths matching the `allow_path_regex`
+/// string are not checked.
+pub struct ConflictMarkersHook {
+    max_file_size: u64,
+    allow_path_regex: Option<Regex>,
+}
+
+impl ConflictMarkersHook {
+    pub fn new(config: &HookConfig) -> Result<Self> {
+        Ok(Self {
+            max_file_size: config_int(config, CONFLICT_MARKERS, "max_file_size")?
+                .unwrap_or(DEFAULT_MAX_FILE_SIZE),
+            allow_path_regex: config_regex(config, CONFLICT_MARKERS, ALLOW_PATH_REGEX)?,
+        })
+    }
+}
+
+/// Find the first line (numbered from 1) that starts with a conflict marker.
+fn find_conflict_marker(content: &[u8]) -> Option<usize> {
+    content
+        .split(|&byte| byte == b'\n')
+        .position(|line| line.starts_with(b"<<<<<<< ") || line.starts_with(b">>>>>>> "))
+        .map(|index| index + 1)
+}
+
+#[async_trait]
+impl FileHook for ConflictMarkersHook {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        ctx: &'ctx CoreContext,
+        content_fetcher: &'fetcher dyn FileContentFetcher,
+        change: Option<&'change FileChange>,
+        path: &'path MPath,
+    ) -> Result<HookExecution, Error> {
+        let change = match change {
+            Some(change)
+                if change.file_type() != FileType::Symlink
+                    && change.size() <= self.max_file_size =>
+            {
+                change
+            }
+            _ => return Ok(HookExecution::Accepted),
+        };
+
+        let path = path.to_string();
+        if let Some(regex) = &self.allow_path_regex {
+            if regex.is_match(&path) {
+                return Ok(HookExecution::Accepted);
+            }
+        }
+
+        let content = match content_fetcher
+            .get_file_text(ctx, change.content_id())
+            .await?
+        {
+            Some(content) if !is_binary(&content) => content,
+            _ => return Ok(HookExecution::Accepted),
+        };
+
+        match find_conflict_marker(&content) {
+            Some(line) => Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+                "Conflict markers were found in file",
+                format!(
+                    "Conflict markers were found in {} at line {}. Resolve the conflict before pushing.",
+                    path, line
+                ),
+            ))),
+            None => Ok(HookExecution::Accepted),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::{FileChange, FileType, MPath};
+use regex::Regex;
+
+use super::{config_regex, ALLOW_PATH_REGEX};
+use crate::{FileHook, HookExecution, HookRejectionInfo};
+
+pub const NO_EXECUTABLE_FILES: &str = "no_executable_files";
+pub const NO_SYMLINKS: &str = "no_symlinks";
+
+/// Rejects files of one type: executable files for `no_executable_files`, or symlinks for
+/// `no_symlinks`. Paths matching the `allow_path_regex` string are exempt.
+pub struct BlockFileTypeHook {
+    file_type: FileType,
+    allow_path_regex: Option<Regex>,
+}
+
+impl BlockFileTypeHook {
+    pub fn no_executable_files(config: &HookConfig) -> Result<Self> {
+        Ok(Self {
+            file_type: FileType::Executable,
+            allow_path_regex: config_regex(config, NO_EXECUTABLE_FILES, ALLOW_PATH_REGEX)?,
+        })
+    }
+
+    pub fn no_symlinks(config: &HookConfig) -> Result<Self> {
+        Ok(Self {
+            file_type: FileType::Symlink,
+            allow_path_regex: config_regex(config, NO_SYMLINKS, ALLOW_PATH_REGEX)?,
+        })
+    }
+}
+
+#[async_trait]
+impl FileHook for BlockFileTypeHook {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+        change: Option<&'change FileChange>,
+        path: &'path MPath,
+    ) -> Result<HookExecution, Error> {
+        match change {
+            Some(change) if change.file_type() == self.file_type => {}
+            _ => return Ok(HookExecution::Accepted),
+        }
+
+        let path = path.to_string();
+        if let Some(regex) = &self.allow_path_regex {
+            if regex.is_match(&path) {
+                return Ok(HookExecution::Accepted);
+            }
+        }
+
+        let rejection = match self.file_type {
+            FileType::Symlink => HookRejectionInfo::new_long(
+                "Symlinks are not allowed",
+                format!("{} is a symlink, and symlinks are not allowed.", path),
+            ),
+            _ => HookRejectionInfo::new_long(
+                "Executable files are not allowed",
+                format!(
+                    "{} is executable, and executable files are not allowed. Remove its executable bit.",
+                    path
+                ),
+            ),
+        };
+        Ok(HookExecution::Rejected(rejection))
+    }
+}
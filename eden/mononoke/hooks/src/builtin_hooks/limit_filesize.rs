@@ -0,0 +1,70 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::{FileChange, MPath};
+use regex::Regex;
+
+use super::{config_regex, required_config_int, ALLOW_PATH_REGEX};
+use crate::{FileHook, HookExecution, HookRejectionInfo};
+
+pub const LIMIT_FILESIZE: &str = "limit_filesize";
+
+/// Rejects files larger than the `filesize_limit` int, in bytes. Paths matching the
+/// `allow_path_regex` string are exempt.
+pub struct LimitFilesizeHook {
+    filesize_limit: u64,
+    allow_path_regex: Option<Regex>,
+}
+
+impl LimitFilesizeHook {
+    pub fn new(config: &HookConfig) -> Result<Self> {
+        Ok(Self {
+            filesize_limit: required_config_int(config, LIMIT_FILESIZE, "filesize_limit")?,
+            allow_path_regex: config_regex(config, LIMIT_FILESIZE, ALLOW_PATH_REGEX)?,
+        })
+    }
+}
+
+#[async_trait]
+impl FileHook for LimitFilesizeHook {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+        change: Option<&'change FileChange>,
+        path: &'path MPath,
+    ) -> Result<HookExecution, Error> {
+        let size = match change {
+            Some(change) => change.size(),
+            None => return Ok(HookExecution::Accepted),
+        };
+
+        if size <= self.filesize_limit {
+            return Ok(HookExecution::Accepted);
+        }
+
+        let path = path.to_string();
+        if let Some(regex) = &self.allow_path_regex {
+            if regex.is_match(&path) {
+                return Ok(HookExecution::Accepted);
+            }
+        }
+
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "File too large",
+            format!(
+                "File size limit is {} bytes. You tried to push file {} that is over the limit ({} bytes).",
+                self.filesize_limit, path, size
+            ),
+        )))
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! A standard set of hooks, configured through the strings and ints of their `HookConfig`. See
+//! each hook for the config it accepts.
+
+mod binary_files;
+mod case_conflicts;
+mod commit_message;
+mod conflict_markers;
+mod file_types;
+mod limit_filesize;
+mod no_merge_commits;
+mod paths;
+mod reviewers;
+
+use anyhow::{Error, Result};
+use metaconfig_types::HookConfig;
+use permission_checker::ArcMembershipChecker;
+use regex::Regex;
+
+use crate::errors::ErrorKind;
+use crate::{ChangesetHook, FileHook};
+
+pub use binary_files::{BlockBinaryFilesHook, BLOCK_BINARY_FILES};
+pub use case_conflicts::{NoCaseConflictsHook, NO_CASE_CONFLICTS};
+pub use commit_message::{
+    CommitMessagePatternHook, LimitCommitMessageLengthHook, COMMIT_MESSAGE_PATTERN,
+    LIMIT_COMMIT_MESSAGE_LENGTH,
+};
+pub use conflict_markers::{ConflictMarkersHook, CONFLICT_MARKERS};
+pub use file_types::{BlockFileTypeHook, NO_EXECUTABLE_FILES, NO_SYMLINKS};
+pub use limit_filesize::{LimitFilesizeHook, LIMIT_FILESIZE};
+pub use no_merge_commits::{NoMergeCommitsHook, NO_MERGE_COMMITS};
+pub use paths::{DenyPathsHook, DENY_PATHS};
+pub use reviewers::{RequireReviewersHook, REQUIRE_REVIEWERS};
+
+/// Create the built-in changeset hook with this name, if there is one.
+pub fn make_changeset_hook(
+    name: &str,
+    config: &HookConfig,
+    reviewers_membership: ArcMembershipChecker,
+) -> Result<Option<Box<dyn ChangesetHook>>> {
+    let hook: Box<dyn ChangesetHook> = match name {
+        COMMIT_MESSAGE_PATTERN => Box::new(CommitMessagePatternHook::new(config)?),
+        LIMIT_COMMIT_MESSAGE_LENGTH => Box::new(LimitCommitMessageLengthHook::new(config)?),
+        NO_CASE_CONFLICTS => Box::new(NoCaseConflictsHook::new()),
+        NO_MERGE_COMMITS => Box::new(NoMergeCommitsHook::new()),
+        REQUIRE_REVIEWERS => Box::new(RequireReviewersHook::new(config, reviewers_membership)?),
+        _ => return Ok(None),
+    };
+    Ok(Some(hook))
+}
+
+/// Create the built-in file hook with this name, if there is one.
+pub fn make_file_hook(name: &str, config: &HookConfig) -> Result<Option<Box<dyn FileHook>>> {
+    let hook: Box<dyn FileHook> = match name {
+        BLOCK_BINARY_FILES => Box::new(BlockBinaryFilesHook::new(config)?),
+        CONFLICT_MARKERS => Box::new(ConflictMarkersHook::new(config)?),
+        DENY_PATHS => Box::new(DenyPathsHook::new(config)?),
+        LIMIT_FILESIZE => Box::new(LimitFilesizeHook::new(config)?),
+        NO_EXECUTABLE_FILES => Box::new(BlockFileTypeHook::no_executable_files(config)?),
+        NO_SYMLINKS => Box::new(BlockFileTypeHook::no_symlinks(config)?),
+        _ => return Ok(None),
+    };
+    Ok(Some(hook))
+}
+
+pub(crate) fn invalid_config(hook: &str, key: &str, reason: impl ToString) -> Error {
+    ErrorKind::InvalidHookConfig(hook.to_string(), key.to_string(), reason.to_string()).into()
+}
+
+/// A non-negative int from the config.
+pub(crate) fn config_int(config: &HookConfig, hook: &str, key: &str) -> Result<Option<u64>> {
+    match config.ints.get(key) {
+        Some(&value) if value < 0 => Err(invalid_config(hook, key, "must not be negative")),
+        Some(&value) => Ok(Some(value as u64)),
+        None => Ok(None),
+    }
+}
+
+fn required_config_int(config: &HookConfig, hook: &str, key: &str) -> Result<u64> {
+    config_int(config, hook, key)?.ok_or_else(|| invalid_config(hook, key, "is required"))
+}
+
+pub(crate) fn config_regex(config: &HookConfig, hook: &str, key: &str) -> Result<Option<Regex>> {
+    config
+        .strings
+        .get(key)
+        .map(|pattern| Regex::new(pattern).map_err(|e| invalid_config(hook, key, e)))
+        .transpose()
+}
+
+fn required_config_regex(config: &HookConfig, hook: &str, key: &str) -> Result<Regex> {
+    config_regex(config, hook, key)?.ok_or_else(|| invalid_config(hook, key, "is required"))
+}
+
+/// Paths matching the `allow_path_regex` config are exempt from file hooks that support it.
+const ALLOW_PATH_REGEX: &str = "allow_path_regex";
+
+/// How far into a file to look for NUL bytes to decide whether it is binary, like Git and
+/// Mercurial do.
+const BINARY_DETECTION_LENGTH: usize = 8000;
+
+fn is_binary(content: &[u8]) -> bool {
+    content
+        .iter()
+        .take(BINARY_DETECTION_LENGTH)
+        .any(|&byte| byte == 0)
+}
@@ -0,0 +1,50 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Error;
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use mononoke_types::BonsaiChangeset;
+
+use crate::{ChangesetHook, HookExecution, HookRejectionInfo};
+
+pub const NO_MERGE_COMMITS: &str = "no_merge_commits";
+
+/// Rejects merge commits. Use it on the bookmarks that should have a linear history.
+#[derive(Default)]
+pub struct NoMergeCommitsHook;
+
+impl NoMergeCommitsHook {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl ChangesetHook for NoMergeCommitsHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        if !changeset.is_merge() {
+            return Ok(HookExecution::Accepted);
+        }
+
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "Merge commits are not allowed",
+            format!(
+                "Merge commits are not allowed on {}. Rebase your changes instead.",
+                bookmark
+            ),
+        )))
+    }
+}
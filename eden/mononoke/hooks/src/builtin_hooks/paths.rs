@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::{FileChange, MPath};
+use regex::Regex;
+
+use super::{config_regex, required_config_regex, ALLOW_PATH_REGEX};
+use crate::{FileHook, HookExecution, HookRejectionInfo};
+
+pub const DENY_PATHS: &str = "deny_paths";
+
+/// Rejects adding or modifying files whose path matches the `deny_path_regex` string, unless it
+/// also matches the `allow_path_regex` string. Deleting such files is allowed.
+pub struct DenyPathsHook {
+    deny_path_regex: Regex,
+    allow_path_regex: Option<Regex>,
+}
+
+impl DenyPathsHook {
+    pub fn new(config: &HookConfig) -> Result<Self> {
+        Ok(Self {
+            deny_path_regex: required_config_regex(config, DENY_PATHS, "deny_path_regex")?,
+            allow_path_regex: config_regex(config, DENY_PATHS, ALLOW_PATH_REGEX)?,
+        })
+    }
+}
+
+#[async_trait]
+impl FileHook for DenyPathsHook {
+    async fn run<'this: 'change, 'ctx: 'this, 'change, 'fetcher: 'change, 'path: 'change>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+        change: Option<&'change FileChange>,
+        path: &'path MPath,
+    ) -> Result<HookExecution, Error> {
+        if change.is_none() {
+            return Ok(HookExecution::Accepted);
+        }
+
+        let path = path.to_string();
+        if !self.deny_path_regex.is_match(&path) {
+            return Ok(HookExecution::Accepted);
+        }
+        if let Some(regex) = &self.allow_path_regex {
+            if regex.is_match(&path) {
+                return Ok(HookExecution::Accepted);
+            }
+        }
+
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "Denied path",
+            format!(
+                "Changes to {} are not allowed: paths matching {} are denied.",
+                path, self.deny_path_regex
+            ),
+        )))
+    }
+}
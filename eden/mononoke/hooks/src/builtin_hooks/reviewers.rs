@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::BonsaiChangeset;
+use permission_checker::{ArcMembershipChecker, MononokeIdentity, MononokeIdentitySet};
+
+use super::config_int;
+use crate::{ChangesetHook, HookExecution, HookRejectionInfo};
+
+pub const REQUIRE_REVIEWERS: &str = "require_reviewers";
+
+const DEFAULT_TRAILER: &str = "Reviewed By";
+
+/// Rejects commits without enough reviewers listed in the commit message, on lines like
+/// `Reviewed By: alice, bob`.
+///
+/// Configured with the `trailer` string (`Reviewed By` by default), the `min_reviewers` int (1 by
+/// default), and the `verify_reviewers` int: when it is not 0, each reviewer must be a member of
+/// the reviewers group of the repo, and the author doesn't count as a reviewer. Reviewers are user
+/// names: the author `Jane Doe <jdoe@example.com>` is the user `jdoe`.
+pub struct RequireReviewersHook {
+    trailer: String,
+    min_reviewers: u64,
+    verify_reviewers: bool,
+    reviewers_membership: ArcMembershipChecker,
+}
+
+impl RequireReviewersHook {
+    pub fn new(config: &HookConfig, reviewers_membership: ArcMembershipChecker) -> Result<Self> {
+        Ok(Self {
+            trailer: config
+                .strings
+                .get("trailer")
+                .cloned()
+                .unwrap_or_else(|| DEFAULT_TRAILER.to_string()),
+            min_reviewers: config_int(config, REQUIRE_REVIEWERS, "min_reviewers")?.unwrap_or(1),
+            verify_reviewers: config_int(config, REQUIRE_REVIEWERS, "verify_reviewers")?
+                .map_or(false, |verify| verify != 0),
+            reviewers_membership,
+        })
+    }
+
+    fn reviewers<'a>(&self, message: &'a str) -> Vec<&'a str> {
+        let mut reviewers = Vec::new();
+        for line in message.lines() {
+            let line = line.trim();
+            if let Some(names) = line
+                .strip_prefix(self.trailer.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+            {
+                for name in names.split(',').map(str::trim) {
+                    if !name.is_empty() && !reviewers.contains(&name) {
+                        reviewers.push(name);
+                    }
+                }
+            }
+        }
+        reviewers
+    }
+
+    async fn is_valid_reviewer(&self, name: &str) -> Result<bool> {
+        let mut identities = MononokeIdentitySet::new();
+        identities.insert(MononokeIdentity::new("USER", name)?);
+        self.reviewers_membership.is_member(&identities).await
+    }
+}
+
+/// The user name of a commit author like `Jane Doe <jdoe@example.com>`: the local part of their
+/// email address, or the whole author if there is no email address.
+fn author_user(author: &str) -> &str {
+    let author = match (author.rfind('<'), author.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &author[start + 1..end],
+        _ => author.trim(),
+    };
+    author.split('@').next().unwrap_or(author)
+}
+
+#[async_trait]
+impl ChangesetHook for RequireReviewersHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        let mut reviewers = self.reviewers(changeset.message());
+
+        if self.verify_reviewers {
+            let author = author_user(changeset.author());
+            let mut valid = Vec::new();
+            for name in reviewers {
+                if name != author && self.is_valid_reviewer(name).await? {
+                    valid.push(name);
+                }
+            }
+            reviewers = valid;
+        }
+
+        if reviewers.len() as u64 >= self.min_reviewers {
+            return Ok(HookExecution::Accepted);
+        }
+
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "Not enough reviewers",
+            format!(
+                "Commits need at least {} reviewer(s) on a '{}:' line, but {} found.",
+                self.min_reviewers,
+                self.trailer,
+                reviewers.len()
+            ),
+        )))
+    }
+}
@@ -36,6 +36,12 @@ pub enum ErrorKind {
     #[error("invalid rust hook: {0}")]
     InvalidRustHook(String),
 
+    #[error("Invalid config '{1}' for hook '{0}': {2}")]
+    InvalidHookConfig(String, String, String),
+
+    #[error("Hook '{0}' requires LFS locking to be enabled for the repository")]
+    LfsLockingDisabled(String),
+
     #[error("Disabled hook(s) do(es) not exist: {0:?}")]
     NoSuchHookToDisable(HashSet<String>),
 }
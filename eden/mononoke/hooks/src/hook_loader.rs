@@ -10,6 +10,8 @@
 #![deny(warnings)]
 
 use crate::errors::*;
+use crate::lfs_locks::{BlockLfsLockedFilesHook, BLOCK_LFS_LOCKED_FILES};
+use crate::wasm_hooks::WasmHook;
 use crate::{ChangesetHook, FileHook, HookManager};
 use anyhow::Error;
 use fbinit::FacebookInit;
@@ -26,6 +28,11 @@ enum LoadedRustHook {
     FileHook(Box<dyn FileHook>),
 }
 
+/// Load the hooks configured for a repository into the hook manager.
+///
+/// The hook blocking LFS locked files needs the lock store, which callers provide with
+/// `HookManager::set_lfs_locks` if they enforce locks. Callers that don't (such as tools replaying
+/// hooks) skip that hook, as if it was disabled.
 pub fn load_hooks(
     fb: FacebookInit,
     hook_manager: &mut HookManager,
@@ -34,6 +41,9 @@ pub fn load_hooks(
 ) -> Result<(), Error> {
     let mut hooks_not_disabled = disabled_hooks.clone();
 
+    let locking_enabled = config.lfs.locking_enabled;
+    let mut skipped_hooks = HashSet::new();
+
     let mut hook_set = HashSet::new();
     for hook in config.hooks {
         use LoadedRustHook::*;
@@ -44,7 +54,22 @@ pub fn load_hooks(
         }
 
         let rust_hook = {
-            if let Some(hook) = hook_name_to_changeset_hook(
+            if hook.name == BLOCK_LFS_LOCKED_FILES {
+                if !locking_enabled {
+                    return Err(ErrorKind::LfsLockingDisabled(hook.name.clone()).into());
+                }
+                match hook_manager.get_lfs_locks() {
+                    Some((repo_id, locks)) => {
+                        ChangesetHook(Box::new(BlockLfsLockedFilesHook::new(repo_id, locks)))
+                    }
+                    None => {
+                        skipped_hooks.insert(hook.name.clone());
+                        continue;
+                    }
+                }
+            } else if let Some(hook) = WasmHook::from_config(&hook.name, &hook.config)? {
+                ChangesetHook(Box::new(hook))
+            } else if let Some(hook) = hook_name_to_changeset_hook(
                 fb,
                 &hook.name,
                 &hook.config,
@@ -79,7 +104,7 @@ pub fn load_hooks(
         let hooks: Vec<_> = bookmark_hook
             .hooks
             .into_iter()
-            .filter(|h| !disabled_hooks.contains(h))
+            .filter(|h| !disabled_hooks.contains(h) && !skipped_hooks.contains(h))
             .collect();
         let bm_hook_set: HashSet<String> = hooks.clone().into_iter().collect();
         let diff: HashSet<_> = bm_hook_set.difference(&hook_set).collect();
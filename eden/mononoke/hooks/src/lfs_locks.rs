@@ -0,0 +1,82 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Enforcement of Git LFS file locks at push time.
+
+use anyhow::Error;
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use hooks_content_stores::FileContentFetcher;
+use itertools::Itertools;
+use lfs_locks::SqlLfsLocks;
+use mononoke_types::{BonsaiChangeset, RepositoryId};
+
+use crate::{ChangesetHook, HookExecution, HookRejectionInfo};
+
+pub const BLOCK_LFS_LOCKED_FILES: &str = "block_lfs_locked_files";
+
+/// How many paths to look up locks for in one query.
+const PATHS_PER_QUERY: usize = 1000;
+
+/// Rejects changesets that modify or delete files someone else holds an LFS lock on.
+pub struct BlockLfsLockedFilesHook {
+    repo_id: RepositoryId,
+    locks: SqlLfsLocks,
+}
+
+impl BlockLfsLockedFilesHook {
+    pub fn new(repo_id: RepositoryId, locks: SqlLfsLocks) -> Self {
+        Self { repo_id, locks }
+    }
+}
+
+#[async_trait]
+impl ChangesetHook for BlockLfsLockedFilesHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        let paths: Vec<String> = changeset
+            .file_changes()
+            .map(|(path, _)| path.to_string())
+            .collect();
+
+        for chunk in paths.chunks(PATHS_PER_QUERY) {
+            let locks = self.locks.get_locks_for_paths(self.repo_id, chunk).await?;
+
+            // Pushes from clients we can't identify can't own any locks.
+            let locked_by_others: Vec<_> = locks
+                .into_iter()
+                .filter(|lock| {
+                    ctx.identities()
+                        .map_or(true, |identities| !lock.is_owned_by(identities))
+                })
+                .collect();
+
+            if !locked_by_others.is_empty() {
+                let locked = locked_by_others
+                    .iter()
+                    .map(|lock| format!("{} (locked by {})", lock.path, lock.owner))
+                    .join(", ");
+
+                return Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+                    "Changeset modifies files locked by someone else",
+                    format!(
+                        "These files are locked by someone else: {}. Ask the owner to unlock them before pushing changes to them.",
+                        locked
+                    ),
+                )));
+            }
+        }
+
+        Ok(HookExecution::Accepted)
+    }
+}
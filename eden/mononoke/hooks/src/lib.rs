@@ -14,12 +14,15 @@
 
 #![deny(warnings)]
 
+pub mod builtin_hooks;
 pub mod errors;
 #[cfg(fbcode_build)]
 mod facebook;
 pub mod hook_loader;
+pub mod lfs_locks;
 #[cfg(not(fbcode_build))]
 mod rust_hooks;
+pub mod wasm_hooks;
 
 use anyhow::{Error, Result};
 use async_trait::async_trait;
@@ -34,13 +37,16 @@ use futures::{
 };
 use futures_stats::TimedFutureExt;
 use hooks_content_stores::FileContentFetcher;
+use lfs_locks::SqlLfsLocks;
 use metaconfig_types::{BookmarkOrRegex, HookBypass, HookConfig, HookManagerParams};
-use mononoke_types::{BonsaiChangeset, ChangesetId, FileChange, MPath};
-use permission_checker::{ArcMembershipChecker, MembershipCheckerBuilder};
+use mononoke_types::{BonsaiChangeset, ChangesetId, FileChange, MPath, RepositoryId};
+use permission_checker::{
+    ArcMembershipChecker, ArcPermissionChecker, MembershipCheckerBuilder, PermissionChecker,
+};
 use regex::Regex;
 use scuba::builder::ServerData;
-use scuba_ext::ScubaSampleBuilder;
-use slog::debug;
+use scuba_ext::{ScubaSampleBuilder, ScubaSampleBuilderExt};
+use slog::{debug, info};
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
@@ -55,6 +61,8 @@ pub struct HookManager {
     regex_hooks: Vec<(Regex, Vec<String>)>,
     content_fetcher: Box<dyn FileContentFetcher>,
     reviewers_membership: ArcMembershipChecker,
+    lfs_locks: Option<(RepositoryId, SqlLfsLocks)>,
+    permission_checker: Option<ArcPermissionChecker>,
     scuba: ScubaSampleBuilder,
 }
 
@@ -87,6 +95,8 @@ impl HookManager {
             regex_hooks: Vec::new(),
             content_fetcher,
             reviewers_membership: reviewers_membership.into(),
+            lfs_locks: None,
+            permission_checker: None,
             scuba,
         })
     }
@@ -126,6 +136,38 @@ impl HookManager {
         self.reviewers_membership.clone()
     }
 
+    /// Make the LFS locks for this repository available to hooks that enforce them. Call this
+    /// before loading hooks.
+    pub fn set_lfs_locks(&mut self, repo_id: RepositoryId, locks: SqlLfsLocks) {
+        self.lfs_locks = Some((repo_id, locks));
+    }
+
+    pub(crate) fn get_lfs_locks(&self) -> Option<(RepositoryId, SqlLfsLocks)> {
+        self.lfs_locks.clone()
+    }
+
+    /// Only let users permitted to `bypass_hooks` by this ACL bypass hooks. Without an ACL,
+    /// anyone can bypass hooks.
+    pub fn set_permission_checker(&mut self, permission_checker: ArcPermissionChecker) {
+        self.permission_checker = Some(permission_checker);
+    }
+
+    async fn is_bypass_permitted(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkName,
+    ) -> Result<bool> {
+        match &self.permission_checker {
+            Some(permission_checker) => {
+                let identities = ctx.identities().cloned().unwrap_or_default();
+                permission_checker
+                    .check_set_for_bookmark(&identities, bookmark.as_str(), &["bypass_hooks"])
+                    .await
+            }
+            None => Ok(true),
+        }
+    }
+
     fn hooks_for_bookmark<'a>(
         &'a self,
         bookmark: &BookmarkName,
@@ -169,17 +211,24 @@ impl HookManager {
             scuba.add("user", user);
         }
 
+        let bypass_permitted = self.is_bypass_permitted(ctx, bookmark).await?;
+
         for (cs, hook_name) in changesets.cartesian_product(hooks) {
             let hook = self
                 .hooks
                 .get(hook_name)
                 .ok_or_else(|| ErrorKind::NoSuchHook(hook_name.to_string()))?;
-            if is_hook_bypassed(
-                hook.get_config().bypass.as_ref(),
-                cs.message(),
-                maybe_pushvars,
-            ) {
-                continue;
+            if let Some(bypass) = hook.get_config().bypass.as_ref() {
+                if is_hook_bypassed(bypass, cs.message(), maybe_pushvars) {
+                    if bypass_permitted {
+                        log_bypass(ctx, scuba.clone(), hook_name, cs, bypass);
+                        continue;
+                    }
+                    info!(
+                        ctx.logger(),
+                        "Not permitted to bypass hook {} on {}", hook_name, bookmark
+                    );
+                }
             }
 
             let mut scuba = scuba.clone();
@@ -196,11 +245,11 @@ impl HookManager {
 }
 
 fn is_hook_bypassed(
-    bypass: Option<&HookBypass>,
+    bypass: &HookBypass,
     cs_msg: &str,
     maybe_pushvars: Option<&HashMap<String, Bytes>>,
 ) -> bool {
-    bypass.map_or(false, move |bypass| match bypass {
+    match bypass {
         HookBypass::CommitMessage(bypass_string) => cs_msg.contains(bypass_string),
         HookBypass::Pushvar { name, value } => {
             if let Some(pushvars) = maybe_pushvars {
@@ -215,7 +264,38 @@ fn is_hook_bypassed(
             }
             false
         }
-    })
+    }
+}
+
+/// Record who bypassed a hook, and how, so that the use of bypasses can be audited.
+fn log_bypass(
+    ctx: &CoreContext,
+    mut scuba: ScubaSampleBuilder,
+    hook_name: &str,
+    cs: &BonsaiChangeset,
+    bypass: &HookBypass,
+) {
+    let cs_id = cs.get_changeset_id();
+    let (bypass_type, bypass_value) = match bypass {
+        HookBypass::CommitMessage(bypass_string) => ("commit_message", bypass_string.clone()),
+        HookBypass::Pushvar { name, value } => ("pushvar", format!("{}={}", name, value)),
+    };
+
+    info!(
+        ctx.logger(),
+        "Hook {} bypassed for {} using {} {}", hook_name, cs_id, bypass_type, bypass_value
+    );
+
+    scuba
+        .add("hook", hook_name.to_string())
+        .add("changeset_id", cs_id.to_string())
+        .add("author", cs.author().to_string())
+        .add("bypass_type", bypass_type)
+        .add("bypass_value", bypass_value);
+    if let Some(unix_name) = ctx.user_unix_name() {
+        scuba.add("unix_username", unix_name.as_str());
+    }
+    scuba.log_with_msg("Hook bypassed", None);
 }
 
 enum Hook {
@@ -238,6 +318,7 @@ impl<'a> HookInstance<'a> {
         mut scuba: ScubaSampleBuilder,
         cs: &BonsaiChangeset,
         cs_id: ChangesetId,
+        advisory: bool,
     ) -> Result<HookOutcome, Error> {
         let (stats, result) = match self {
             Self::Changeset(hook) => {
@@ -248,7 +329,7 @@ impl<'a> HookInstance<'a> {
                                 cs_id,
                                 hook_name: hook_name.to_string(),
                             },
-                            exec,
+                            exec.with_advisory(advisory),
                         )
                     })
                     .timed()
@@ -263,7 +344,7 @@ impl<'a> HookInstance<'a> {
                                 path: path.clone(),
                                 hook_name: hook_name.to_string(),
                             },
-                            exec,
+                            exec.with_advisory(advisory),
                         )
                     })
                     .timed()
@@ -275,8 +356,15 @@ impl<'a> HookInstance<'a> {
             scuba.add("stderr", e.to_string());
         }
 
+        let is_warning = match result.as_ref() {
+            Ok(outcome) => outcome.is_warning(),
+            Err(_) => false,
+        };
+
         let elapsed = stats.completion_time.as_millis() as i64;
         scuba
+            .add("advisory", advisory)
+            .add("warnings", is_warning as i32)
             .add("elapsed", elapsed)
             .add("total_time", elapsed)
             .add("errorcode", result.is_err() as i32)
@@ -315,6 +403,7 @@ impl Hook {
         let mut futures = Vec::new();
 
         let cs_id = cs.get_changeset_id();
+        let advisory = self.get_config().advisory;
 
         match self {
             Self::Changeset(hook, _) => futures.push(HookInstance::Changeset(&**hook).run(
@@ -325,6 +414,7 @@ impl Hook {
                 scuba,
                 cs,
                 cs_id,
+                advisory,
             )),
             Self::File(hook, _) => futures.extend(cs.file_changes().map(move |(path, change)| {
                 HookInstance::File(&**hook, path, change).run(
@@ -335,6 +425,7 @@ impl Hook {
                     scuba.clone(),
                     cs,
                     cs_id,
+                    advisory,
                 )
             })),
         };
@@ -388,11 +479,19 @@ impl fmt::Display for HookOutcome {
 impl HookOutcome {
     pub fn is_rejection(&self) -> bool {
         match self.get_execution() {
-            HookExecution::Accepted => false,
+            HookExecution::Accepted | HookExecution::Warning(_) => false,
             HookExecution::Rejected(_) => true,
         }
     }
 
+    pub fn is_warning(&self) -> bool {
+        match self.get_execution() {
+            HookExecution::Accepted | HookExecution::Rejected(_) => false,
+            HookExecution::Warning(_) => true,
+        }
+    }
+
+    /// Warnings don't block the push, so they count as accepted.
     pub fn is_accept(&self) -> bool {
         !self.is_rejection()
     }
@@ -426,21 +525,30 @@ impl HookOutcome {
     }
 
     pub fn into_rejection(self) -> Option<(String, ChangesetId, HookRejectionInfo)> {
+        match self.into_parts() {
+            (hook_name, cs_id, HookExecution::Rejected(reason)) => Some((hook_name, cs_id, reason)),
+            _ => None,
+        }
+    }
+
+    pub fn into_warning(self) -> Option<(String, ChangesetId, HookRejectionInfo)> {
+        match self.into_parts() {
+            (hook_name, cs_id, HookExecution::Warning(reason)) => Some((hook_name, cs_id, reason)),
+            _ => None,
+        }
+    }
+
+    fn into_parts(self) -> (String, ChangesetId, HookExecution) {
         match self {
-            HookOutcome::ChangesetHook(_, HookExecution::Accepted)
-            | HookOutcome::FileHook(_, HookExecution::Accepted) => None,
-            HookOutcome::ChangesetHook(
-                ChangesetHookExecutionID { cs_id, hook_name },
-                HookExecution::Rejected(reason),
-            )
+            HookOutcome::ChangesetHook(ChangesetHookExecutionID { cs_id, hook_name }, exec)
             | HookOutcome::FileHook(
                 FileHookExecutionID {
                     cs_id,
                     hook_name,
                     path: _,
                 },
-                HookExecution::Rejected(reason),
-            ) => Some((hook_name, cs_id, reason)),
+                exec,
+            ) => (hook_name, cs_id, exec),
         }
     }
 }
@@ -449,6 +557,18 @@ impl HookOutcome {
 pub enum HookExecution {
     Accepted,
     Rejected(HookRejectionInfo),
+    /// The hook found a problem, but it should be reported to the user without blocking the
+    /// push. Hooks configured as advisory have all their rejections turned into warnings.
+    Warning(HookRejectionInfo),
+}
+
+impl HookExecution {
+    fn with_advisory(self, advisory: bool) -> Self {
+        match self {
+            HookExecution::Rejected(reason) if advisory => HookExecution::Warning(reason),
+            exec => exec,
+        }
+    }
 }
 
 impl From<HookOutcome> for HookExecution {
@@ -465,6 +585,7 @@ impl fmt::Display for HookExecution {
         match self {
             HookExecution::Accepted => write!(f, "Accepted"),
             HookExecution::Rejected(reason) => write!(f, "Rejected: {}", reason.long_description),
+            HookExecution::Warning(reason) => write!(f, "Warning: {}", reason.long_description),
         }
     }
 }
@@ -5,27 +5,28 @@
  * GNU General Public License version 2.
  */
 
-//! For Facebook hooks check the src/facebook/ folder
+//! For Facebook hooks check the src/facebook/ folder. Open source builds get the hooks from
+//! the builtin_hooks module.
 
 use anyhow::Result;
 use fbinit::FacebookInit;
 use metaconfig_types::HookConfig;
 use permission_checker::ArcMembershipChecker;
 
-use crate::{ChangesetHook, FileHook};
+use crate::{builtin_hooks, ChangesetHook, FileHook};
 
 pub fn hook_name_to_changeset_hook(
     _fb: FacebookInit,
-    _name: &str,
-    _config: &HookConfig,
-    _reviewers_membership: ArcMembershipChecker,
+    name: &str,
+    config: &HookConfig,
+    reviewers_membership: ArcMembershipChecker,
 ) -> Result<Option<Box<dyn ChangesetHook>>> {
-    Ok(None)
+    builtin_hooks::make_changeset_hook(name, config, reviewers_membership)
 }
 
 pub fn hook_name_to_file_hook(
-    _name: &str,
-    _config: &HookConfig,
+    name: &str,
+    config: &HookConfig,
 ) -> Result<Option<Box<dyn FileHook>>> {
-    Ok(None)
+    builtin_hooks::make_file_hook(name, config)
 }
@@ -0,0 +1,431 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Changeset hooks implemented as WebAssembly modules, so that repo owners can ship hooks
+//! without redeploying the server.
+//!
+//! The module is given in the hook config, either inline in the `wasm_module` string (as
+//! WebAssembly text or binary), as a file in the `wasm_module_path` string, or as a file in the
+//! repo in the `wasm_module_repo_path` string. A module in the repo is read from the bookmark
+//! being pushed to, or from the `wasm_module_bookmark` string when it is set, and is compiled
+//! again whenever it changes. It runs in a sandbox: it can only see what the host API below gives
+//! it, it is stopped once it has used up its fuel (the `fuel` int), and its memory is limited (the
+//! `max_memory_bytes` int).
+//!
+//! The module must export:
+//!  - `memory`: its linear memory.
+//!  - `alloc(len: i32) -> i32`: allocate `len` bytes and return a pointer to them.
+//!  - `run(ptr: i32, len: i32) -> i32`: run the hook on the changeset described by the JSON
+//!    document at `ptr`. Return 0 to accept the changeset, and anything else to reject it.
+//!
+//! The JSON document has the `bookmark`, `changeset_id`, `author`, `message`, `parents` and
+//! `files` of the changeset. Each file has a `path`, and unless the file is `deleted`, its
+//! `type` and `size`.
+//!
+//! The module can import from the `mononoke` module:
+//!  - `file_size(index: i32) -> i64`: the size of the content of the file at `index` in `files`,
+//!    or -1 if its content isn't available.
+//!  - `read_file(index: i32, ptr: i32) -> i32`: copy the content of the file at `index` in
+//!    `files` to `ptr`, and return its size, or -1 if its content isn't available.
+//!  - `reject(ptr: i32, len: i32)`: explain to the user why the changeset is rejected.
+//!
+//! File contents are fetched before the module runs: the contents of symlinks, of files larger
+//! than the `max_file_size` int, and of files not matching the `file_path_regex` string (when it
+//! is set) aren't available. Neither are the contents of the files past the
+//! `max_total_file_size` int, counting the sizes of the files in order.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::sync::Mutex;
+
+use anyhow::{bail, format_err, Context, Error, Result};
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use bytes::Bytes;
+use context::CoreContext;
+use futures::future::try_join_all;
+use hooks_content_stores::FileContentFetcher;
+use metaconfig_types::HookConfig;
+use mononoke_types::{BonsaiChangeset, ContentId, FileType, MPath};
+use regex::Regex;
+use serde::Serialize;
+use tokio::task;
+use wasmtime::{
+    Caller, Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, Trap,
+};
+
+use crate::builtin_hooks::{config_int, config_regex, invalid_config};
+use crate::{ChangesetHook, HookExecution, HookRejectionInfo};
+
+const WASM_MODULE: &str = "wasm_module";
+const WASM_MODULE_PATH: &str = "wasm_module_path";
+const WASM_MODULE_REPO_PATH: &str = "wasm_module_repo_path";
+const WASM_MODULE_BOOKMARK: &str = "wasm_module_bookmark";
+
+const DEFAULT_FUEL: u64 = 100_000_000;
+const DEFAULT_MAX_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+const DEFAULT_MAX_TOTAL_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Modules in the repo larger than this are not loaded.
+const MAX_REPO_MODULE_SIZE: u64 = 16 * 1024 * 1024;
+
+enum ModuleSource {
+    /// Compiled when the hook is created.
+    Config(Module),
+    /// Compiled when the hook runs, and again only when the file changes.
+    Repo {
+        path: MPath,
+        bookmark: Option<BookmarkName>,
+        compiled: Mutex<Option<(ContentId, Module)>>,
+    },
+}
+
+/// A changeset hook running a WebAssembly module.
+pub struct WasmHook {
+    name: String,
+    engine: Engine,
+    source: ModuleSource,
+    fuel: u64,
+    max_memory_bytes: usize,
+    max_file_size: u64,
+    max_total_file_size: u64,
+    file_path_regex: Option<Regex>,
+}
+
+impl WasmHook {
+    /// Create the hook if its config has a WebAssembly module.
+    pub fn from_config(name: &str, config: &HookConfig) -> Result<Option<Self>> {
+        let keys = [WASM_MODULE, WASM_MODULE_PATH, WASM_MODULE_REPO_PATH];
+        let mut given = keys
+            .iter()
+            .filter_map(|key| Some((*key, config.strings.get(*key)?)));
+        let (key, value) = match (given.next(), given.next()) {
+            (None, _) => return Ok(None),
+            (Some((key, _)), Some((other_key, _))) => {
+                return Err(invalid_config(
+                    name,
+                    key,
+                    format!("can't be used with {}", other_key),
+                ));
+            }
+            (Some(given), None) => given,
+        };
+
+        let mut wasm_config = Config::new();
+        wasm_config.consume_fuel(true);
+        let engine = Engine::new(&wasm_config)?;
+
+        let source = match key {
+            WASM_MODULE_REPO_PATH => ModuleSource::Repo {
+                path: MPath::new(value).map_err(|e| invalid_config(name, key, e))?,
+                bookmark: config
+                    .strings
+                    .get(WASM_MODULE_BOOKMARK)
+                    .map(|bookmark| {
+                        BookmarkName::new(bookmark)
+                            .map_err(|e| invalid_config(name, WASM_MODULE_BOOKMARK, e))
+                    })
+                    .transpose()?,
+                compiled: Mutex::new(None),
+            },
+            _ => {
+                let wasm = if key == WASM_MODULE_PATH {
+                    fs::read(value)
+                        .map_err(|e| invalid_config(name, key, format!("{}: {}", value, e)))?
+                } else {
+                    value.as_bytes().to_vec()
+                };
+                let module =
+                    Module::new(&engine, &wasm).map_err(|e| invalid_config(name, key, e))?;
+                ModuleSource::Config(module)
+            }
+        };
+
+        let max_memory_bytes =
+            config_int(config, name, "max_memory_bytes")?.unwrap_or(DEFAULT_MAX_MEMORY_BYTES);
+
+        Ok(Some(Self {
+            name: name.to_string(),
+            engine,
+            source,
+            fuel: config_int(config, name, "fuel")?.unwrap_or(DEFAULT_FUEL),
+            max_memory_bytes: max_memory_bytes as usize,
+            max_file_size: config_int(config, name, "max_file_size")?
+                .unwrap_or(DEFAULT_MAX_FILE_SIZE),
+            max_total_file_size: config_int(config, name, "max_total_file_size")?
+                .unwrap_or(DEFAULT_MAX_TOTAL_FILE_SIZE),
+            file_path_regex: config_regex(config, name, "file_path_regex")?,
+        }))
+    }
+
+    /// The module to run for a push to `bookmark`.
+    async fn module(
+        &self,
+        ctx: &CoreContext,
+        bookmark: &BookmarkName,
+        content_fetcher: &dyn FileContentFetcher,
+    ) -> Result<Module> {
+        let (path, bookmark, compiled) = match &self.source {
+            ModuleSource::Config(module) => return Ok(module.clone()),
+            ModuleSource::Repo {
+                path,
+                bookmark: module_bookmark,
+                compiled,
+            } => (path, module_bookmark.as_ref().unwrap_or(bookmark), compiled),
+        };
+
+        let content_id = content_fetcher
+            .find_file_in_bookmark(ctx, bookmark, path.clone())
+            .await?
+            .ok_or_else(|| format_err!("The module {} is not in {}", path, bookmark))?;
+        if let Some((compiled_id, module)) = &*compiled.lock().expect("lock poisoned") {
+            if *compiled_id == content_id {
+                return Ok(module.clone());
+            }
+        }
+
+        let size = content_fetcher.get_file_size(ctx, content_id).await?;
+        if size > MAX_REPO_MODULE_SIZE {
+            bail!(
+                "The module {} is too large ({} bytes, the limit is {})",
+                path,
+                size,
+                MAX_REPO_MODULE_SIZE
+            );
+        }
+        let wasm = content_fetcher
+            .peek_file(ctx, content_id, size as usize)
+            .await?
+            .ok_or_else(|| format_err!("The content of the module {} is not available", path))?;
+        let engine = self.engine.clone();
+        let module = task::spawn_blocking(move || Module::new(&engine, &wasm))
+            .await?
+            .with_context(|| format!("while compiling {} from {}", path, bookmark))?;
+
+        *compiled.lock().expect("lock poisoned") = Some((content_id, module.clone()));
+        Ok(module)
+    }
+
+    fn is_content_available(&self, path: &str, file_type: FileType, size: u64) -> bool {
+        file_type != FileType::Symlink
+            && size <= self.max_file_size
+            && self
+                .file_path_regex
+                .as_ref()
+                .map_or(true, |regex| regex.is_match(path))
+    }
+}
+
+/// Run a module, returning its result and the rejection it explained, if any. This blocks until
+/// the module returns or runs out of fuel.
+fn run_module(
+    engine: &Engine,
+    module: &Module,
+    fuel: u64,
+    max_memory_bytes: usize,
+    input: &[u8],
+    contents: Vec<Option<Bytes>>,
+) -> Result<(i32, Option<String>)> {
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(max_memory_bytes)
+        .instances(1)
+        .build();
+    let mut store = Store::new(
+        engine,
+        HostState {
+            contents,
+            rejection: None,
+            limits,
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    store.add_fuel(fuel)?;
+
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(
+        "mononoke",
+        "file_size",
+        |caller: Caller<'_, HostState>, index: i32| -> i64 {
+            match caller.data().content(index) {
+                Some(content) => content.len() as i64,
+                None => -1,
+            }
+        },
+    )?;
+    linker.func_wrap(
+        "mononoke",
+        "read_file",
+        |mut caller: Caller<'_, HostState>, index: i32, ptr: i32| -> Result<i32, Trap> {
+            let memory = exported_memory(&mut caller)?;
+            let (memory, state) = memory.data_and_store_mut(&mut caller);
+            let content = match state.content(index) {
+                Some(content) => content,
+                None => return Ok(-1),
+            };
+            guest_slice_mut(memory, ptr, content.len())?.copy_from_slice(content);
+            Ok(content.len() as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "mononoke",
+        "reject",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<(), Trap> {
+            let memory = exported_memory(&mut caller)?;
+            let (memory, state) = memory.data_and_store_mut(&mut caller);
+            let len = usize::try_from(len).map_err(|_| Trap::new("negative length"))?;
+            let message = guest_slice_mut(memory, ptr, len)?;
+            state.rejection = Some(String::from_utf8_lossy(message).into_owned());
+            Ok(())
+        },
+    )?;
+
+    let instance = linker.instantiate(&mut store, module)?;
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .context("The module doesn't export its memory")?;
+    let alloc = instance.get_typed_func::<i32, i32, _>(&mut store, "alloc")?;
+    let run = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "run")?;
+
+    let len = i32::try_from(input.len())?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory.write(&mut store, ptr as usize, input)?;
+    let result = run.call(&mut store, (ptr, len))?;
+
+    Ok((result, store.into_data().rejection))
+}
+
+struct HostState {
+    contents: Vec<Option<Bytes>>,
+    rejection: Option<String>,
+    limits: StoreLimits,
+}
+
+impl HostState {
+    fn content(&self, index: i32) -> Option<&Bytes> {
+        let index = usize::try_from(index).ok()?;
+        self.contents.get(index)?.as_ref()
+    }
+}
+
+fn exported_memory(caller: &mut Caller<'_, HostState>) -> Result<Memory, Trap> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| Trap::new("The module doesn't export its memory"))
+}
+
+fn guest_slice_mut(memory: &mut [u8], ptr: i32, len: usize) -> Result<&mut [u8], Trap> {
+    let start = usize::try_from(ptr).map_err(|_| Trap::new("negative pointer"))?;
+    memory
+        .get_mut(start..start.saturating_add(len))
+        .ok_or_else(|| Trap::new("out of bounds memory access"))
+}
+
+#[derive(Serialize)]
+struct ChangesetInput<'a> {
+    bookmark: String,
+    changeset_id: String,
+    author: &'a str,
+    message: &'a str,
+    parents: Vec<String>,
+    files: Vec<FileInput>,
+}
+
+#[derive(Serialize)]
+struct FileInput {
+    path: String,
+    deleted: bool,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    file_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+}
+
+#[async_trait]
+impl ChangesetHook for WasmHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        ctx: &'ctx CoreContext,
+        bookmark: &BookmarkName,
+        changeset: &'cs BonsaiChangeset,
+        content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        let module = self.module(ctx, bookmark, content_fetcher).await?;
+
+        let mut files = Vec::new();
+        let mut to_fetch = Vec::new();
+        let mut total_file_size = 0;
+        for (path, change) in changeset.file_changes() {
+            let path = path.to_string();
+            match change {
+                Some(change) => {
+                    let file_type = change.file_type();
+                    if self.is_content_available(&path, file_type, change.size())
+                        && total_file_size + change.size() <= self.max_total_file_size
+                    {
+                        total_file_size += change.size();
+                        to_fetch.push(Some(change.content_id()));
+                    } else {
+                        to_fetch.push(None);
+                    }
+                    files.push(FileInput {
+                        path,
+                        deleted: false,
+                        file_type: Some(match file_type {
+                            FileType::Regular => "regular",
+                            FileType::Executable => "executable",
+                            FileType::Symlink => "symlink",
+                        }),
+                        size: Some(change.size()),
+                    });
+                }
+                None => {
+                    to_fetch.push(None);
+                    files.push(FileInput {
+                        path,
+                        deleted: true,
+                        file_type: None,
+                        size: None,
+                    });
+                }
+            }
+        }
+        let contents = try_join_all(to_fetch.into_iter().map(|content_id| async move {
+            match content_id {
+                Some(content_id) => content_fetcher.get_file_text(ctx, content_id).await,
+                None => Ok(None),
+            }
+        }))
+        .await?;
+
+        let input = serde_json::to_vec(&ChangesetInput {
+            bookmark: bookmark.to_string(),
+            changeset_id: changeset.get_changeset_id().to_string(),
+            author: changeset.author(),
+            message: changeset.message(),
+            parents: changeset.parents().map(|p| p.to_string()).collect(),
+            files,
+        })?;
+
+        let engine = self.engine.clone();
+        let (fuel, max_memory_bytes) = (self.fuel, self.max_memory_bytes);
+        let (result, rejection) = task::spawn_blocking(move || {
+            run_module(&engine, &module, fuel, max_memory_bytes, &input, contents)
+        })
+        .await?
+        .with_context(|| format!("while running WebAssembly hook {}", self.name))?;
+
+        if result == 0 {
+            return Ok(HookExecution::Accepted);
+        }
+        Ok(HookExecution::Rejected(HookRejectionInfo::new_long(
+            "Rejected by WebAssembly hook",
+            rejection,
+        )))
+    }
+}
@@ -0,0 +1,230 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Storage for Git LFS file locks.
+//!
+//! A lock gives one identity exclusive rights to push changes to a path in a repository, until it
+//! is released. Locks are advisory for the LFS protocol itself, and are enforced at push time by
+//! the `block_lfs_locked_files` hook.
+
+#![deny(warnings)]
+
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+use futures::compat::Future01CompatExt;
+use mononoke_types::{RepositoryId, Timestamp};
+use permission_checker::{MononokeIdentity, MononokeIdentitySet};
+use sql::queries;
+use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
+use sql_ext::SqlConnections;
+
+/// The identity type preferred as the owner of a lock, if the client presents several.
+const USER_IDENTITY_TYPE: &str = "USER";
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LfsLock {
+    pub id: u64,
+    pub path: String,
+    pub owner: MononokeIdentity,
+    pub locked_at: Timestamp,
+}
+
+impl LfsLock {
+    /// Whether a client presenting these identities owns this lock.
+    pub fn is_owned_by(&self, identities: &MononokeIdentitySet) -> bool {
+        identities.contains(&self.owner)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LockCreation {
+    /// The path was not locked, and is now locked by the requested owner.
+    Created(LfsLock),
+    /// The path was already locked. This is the existing lock.
+    AlreadyLocked(LfsLock),
+}
+
+/// Pick the identity that should own locks taken by a client presenting these identities: its
+/// user identity if it has one, or any identity otherwise.
+pub fn lock_owner(identities: &MononokeIdentitySet) -> Option<&MononokeIdentity> {
+    identities
+        .iter()
+        .find(|id| id.id_type() == USER_IDENTITY_TYPE)
+        .or_else(|| identities.iter().next())
+}
+
+type LockRow = (u64, String, String, Timestamp);
+
+fn lock_from_row((id, path, owner, locked_at): LockRow) -> Result<LfsLock, Error> {
+    Ok(LfsLock {
+        id,
+        path,
+        owner: MononokeIdentity::from_str(&owner)?,
+        locked_at,
+    })
+}
+
+fn locks_from_rows(rows: Vec<LockRow>) -> Result<Vec<LfsLock>, Error> {
+    rows.into_iter().map(lock_from_row).collect()
+}
+
+queries! {
+    write InsertLock(values: (
+        repo_id: RepositoryId,
+        path: String,
+        owner: String,
+        locked_at: Timestamp,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO lfs_locks (repo_id, path, owner, locked_at) VALUES {values}"
+    }
+
+    write DeleteLock(repo_id: RepositoryId, id: u64) {
+        none,
+        "DELETE FROM lfs_locks WHERE repo_id = {repo_id} AND id = {id}"
+    }
+
+    read SelectLockById(repo_id: RepositoryId, id: u64) -> (u64, String, String, Timestamp) {
+        "SELECT id, path, owner, locked_at
+         FROM lfs_locks
+         WHERE repo_id = {repo_id} AND id = {id}"
+    }
+
+    read SelectLocksByPath(repo_id: RepositoryId, >list paths: String) -> (u64, String, String, Timestamp) {
+        "SELECT id, path, owner, locked_at
+         FROM lfs_locks
+         WHERE repo_id = {repo_id} AND path IN {paths}
+         ORDER BY id"
+    }
+
+    read SelectLocks(repo_id: RepositoryId, after: u64, limit: usize) -> (u64, String, String, Timestamp) {
+        "SELECT id, path, owner, locked_at
+         FROM lfs_locks
+         WHERE repo_id = {repo_id} AND id > {after}
+         ORDER BY id
+         LIMIT {limit}"
+    }
+}
+
+#[derive(Clone)]
+pub struct SqlLfsLocks {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlLfsLocks {
+    const LABEL: &'static str = "lfs_locks";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-lfs-locks.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlLfsLocks {}
+
+impl SqlLfsLocks {
+    /// Lock a path for this owner, unless it is already locked (by anyone, including this owner).
+    pub async fn create_lock(
+        &self,
+        repo_id: RepositoryId,
+        path: String,
+        owner: &MononokeIdentity,
+    ) -> Result<LockCreation, Error> {
+        let locked_at = Timestamp::now();
+        let owner_str = owner.to_string();
+
+        let res = InsertLock::query(
+            &self.connections.write_connection,
+            &[(&repo_id, &path, &owner_str, &locked_at)],
+        )
+        .compat()
+        .await?;
+
+        match res.last_insert_id() {
+            Some(id) if res.affected_rows() == 1 => Ok(LockCreation::Created(LfsLock {
+                id,
+                path,
+                owner: owner.clone(),
+                locked_at,
+            })),
+            _ => {
+                // The path is locked already. Read from master: this lock may have been created
+                // moments ago.
+                let rows = SelectLocksByPath::query(
+                    &self.connections.read_master_connection,
+                    &repo_id,
+                    &[path],
+                )
+                .compat()
+                .await?;
+
+                match locks_from_rows(rows)?.into_iter().next() {
+                    Some(lock) => Ok(LockCreation::AlreadyLocked(lock)),
+                    // The lock was released in the meantime. Let the client retry.
+                    None => Err(Error::msg("Lock was released while it was being created")),
+                }
+            }
+        }
+    }
+
+    pub async fn get_lock(&self, repo_id: RepositoryId, id: u64) -> Result<Option<LfsLock>, Error> {
+        let rows = SelectLockById::query(&self.connections.read_master_connection, &repo_id, &id)
+            .compat()
+            .await?;
+
+        rows.into_iter().next().map(lock_from_row).transpose()
+    }
+
+    /// Find the locks held on any of these paths.
+    pub async fn get_locks_for_paths(
+        &self,
+        repo_id: RepositoryId,
+        paths: &[String],
+    ) -> Result<Vec<LfsLock>, Error> {
+        if paths.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let rows =
+            SelectLocksByPath::query(&self.connections.read_master_connection, &repo_id, paths)
+                .compat()
+                .await?;
+
+        locks_from_rows(rows)
+    }
+
+    /// List up to `limit` locks in this repository, starting after the lock with id `after` (use
+    /// 0 to start from the beginning). Locks are listed in creation order.
+    pub async fn list_locks(
+        &self,
+        repo_id: RepositoryId,
+        after: u64,
+        limit: usize,
+    ) -> Result<Vec<LfsLock>, Error> {
+        let rows = SelectLocks::query(
+            &self.connections.read_master_connection,
+            &repo_id,
+            &after,
+            &limit,
+        )
+        .compat()
+        .await?;
+
+        locks_from_rows(rows)
+    }
+
+    /// Release a lock. Returns whether the lock existed.
+    pub async fn delete_lock(&self, repo_id: RepositoryId, id: u64) -> Result<bool, Error> {
+        let res = DeleteLock::query(&self.connections.write_connection, &repo_id, &id)
+            .compat()
+            .await?;
+
+        Ok(res.affected_rows() > 0)
+    }
+}
@@ -0,0 +1,119 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use std::str::FromStr;
+
+use anyhow::Error;
+use fbinit::FacebookInit;
+use lfs_locks::{lock_owner, LockCreation, SqlLfsLocks};
+use maplit::btreeset;
+use mononoke_types_mocks::repo::{REPO_ONE, REPO_ZERO};
+use permission_checker::MononokeIdentity;
+use sql_construct::SqlConstruct;
+
+fn identity(s: &str) -> MononokeIdentity {
+    MononokeIdentity::from_str(s).unwrap()
+}
+
+#[fbinit::compat_test]
+async fn test_create_lock(_fb: FacebookInit) -> Result<(), Error> {
+    let locks = SqlLfsLocks::with_sqlite_in_memory()?;
+    let alice = identity("USER:alice");
+    let bob = identity("USER:bob");
+
+    let lock = match locks
+        .create_lock(REPO_ZERO, "a/b".to_string(), &alice)
+        .await?
+    {
+        LockCreation::Created(lock) => lock,
+        LockCreation::AlreadyLocked(lock) => panic!("unexpected lock: {:?}", lock),
+    };
+    assert_eq!(lock.path, "a/b");
+    assert_eq!(lock.owner, alice);
+
+    assert_eq!(
+        locks
+            .create_lock(REPO_ZERO, "a/b".to_string(), &bob)
+            .await?,
+        LockCreation::AlreadyLocked(lock.clone())
+    );
+    assert_eq!(
+        locks
+            .create_lock(REPO_ZERO, "a/b".to_string(), &alice)
+            .await?,
+        LockCreation::AlreadyLocked(lock.clone())
+    );
+
+    // Locks are per-repository.
+    assert!(matches!(
+        locks.create_lock(REPO_ONE, "a/b".to_string(), &bob).await?,
+        LockCreation::Created(_)
+    ));
+
+    assert_eq!(
+        locks.get_lock(REPO_ZERO, lock.id).await?,
+        Some(lock.clone())
+    );
+    assert!(lock.is_owned_by(&btreeset! { alice }));
+    assert!(!lock.is_owned_by(&btreeset! { bob }));
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_list_and_delete_locks(_fb: FacebookInit) -> Result<(), Error> {
+    let locks = SqlLfsLocks::with_sqlite_in_memory()?;
+    let alice = identity("USER:alice");
+
+    for path in &["a", "b", "c"] {
+        locks
+            .create_lock(REPO_ZERO, path.to_string(), &alice)
+            .await?;
+    }
+
+    let first = locks.list_locks(REPO_ZERO, 0, 2).await?;
+    assert_eq!(
+        first.iter().map(|l| l.path.as_str()).collect::<Vec<_>>(),
+        vec!["a", "b"]
+    );
+
+    let rest = locks.list_locks(REPO_ZERO, first[1].id, 2).await?;
+    assert_eq!(
+        rest.iter().map(|l| l.path.as_str()).collect::<Vec<_>>(),
+        vec!["c"]
+    );
+
+    let found = locks
+        .get_locks_for_paths(REPO_ZERO, &["c".to_string(), "d".to_string()])
+        .await?;
+    assert_eq!(found, rest);
+
+    assert!(locks.delete_lock(REPO_ZERO, rest[0].id).await?);
+    assert!(!locks.delete_lock(REPO_ZERO, rest[0].id).await?);
+    assert_eq!(locks.get_lock(REPO_ZERO, rest[0].id).await?, None);
+    assert!(locks
+        .get_locks_for_paths(REPO_ZERO, &["c".to_string()])
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_lock_owner() {
+    let user = identity("USER:alice");
+    let service = identity("SERVICE_IDENTITY:robot");
+
+    assert_eq!(
+        lock_owner(&btreeset! { service.clone(), user.clone() }),
+        Some(&user)
+    );
+    assert_eq!(lock_owner(&btreeset! { service.clone() }), Some(&service));
+    assert_eq!(lock_owner(&btreeset! {}), None);
+}
@@ -7,10 +7,15 @@
 
 #![deny(warnings)]
 
+mod locks;
 mod protocol;
 mod str_serialized;
 
+pub use locks::{
+    CreateLockRequest, ListLocksResponse, Lock, LockConflict, LockOwner, LockResponse,
+    UnlockRequest, VerifyLocksRequest, VerifyLocksResponse,
+};
 pub use protocol::{
-    git_lfs_mime, ObjectAction, ObjectError, ObjectStatus, Operation, RequestBatch, RequestObject,
-    ResponseBatch, ResponseError, ResponseObject, Sha256, Transfer,
+    git_lfs_mime, ObjectAction, ObjectError, ObjectStatus, Operation, Ref, RequestBatch,
+    RequestObject, ResponseBatch, ResponseError, ResponseObject, Sha256, Transfer,
 };
@@ -0,0 +1,147 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::Ref;
+
+// This module provides types conforming to the Git-LFS locking API specification:
+// https://github.com/git-lfs/git-lfs/blob/master/docs/api/locking.md
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct LockOwner {
+    pub name: String,
+}
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct Lock {
+    pub id: String,
+    pub path: String,
+    /// When the lock was created, in RFC 3339 format.
+    pub locked_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<LockOwner>,
+}
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct CreateLockRequest {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#ref: Option<Ref>,
+}
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct LockResponse {
+    pub lock: Lock,
+}
+
+/// Returned with a 409 when the path to lock is already locked.
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct LockConflict {
+    pub lock: Lock,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct ListLocksResponse {
+    pub locks: Vec<Lock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct VerifyLocksRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#ref: Option<Ref>,
+}
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct VerifyLocksResponse {
+    /// Locks held by the requesting client.
+    pub ours: Vec<Lock>,
+    /// Locks held by anyone else.
+    pub theirs: Vec<Lock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Clone, Serialize, Debug, Deserialize, Eq, PartialEq)]
+pub struct UnlockRequest {
+    /// Release the lock even if it is owned by someone else.
+    #[serde(default)]
+    pub force: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#ref: Option<Ref>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::{self, json};
+
+    #[test]
+    pub fn test_deserialize_create_lock() {
+        let j = json!({
+            "path": "foo/bar.zip",
+            "ref": {
+                "name": "refs/heads/my-feature"
+            }
+        });
+
+        let res = serde_json::from_str::<CreateLockRequest>(&j.to_string()).unwrap();
+        assert_eq!(res.path, "foo/bar.zip");
+        assert_eq!(
+            res.r#ref,
+            Some(Ref {
+                name: "refs/heads/my-feature".to_string()
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_deserialize_unlock() {
+        let res = serde_json::from_str::<UnlockRequest>("{}").unwrap();
+        assert_eq!(res.force, false);
+
+        let res = serde_json::from_str::<UnlockRequest>(r#"{"force": true}"#).unwrap();
+        assert_eq!(res.force, true);
+    }
+
+    #[test]
+    pub fn test_serialize_list() {
+        let res = ListLocksResponse {
+            locks: vec![Lock {
+                id: "1".to_string(),
+                path: "foo".to_string(),
+                locked_at: "2016-05-17T15:49:06+00:00".to_string(),
+                owner: Some(LockOwner {
+                    name: "alice".to_string(),
+                }),
+            }],
+            next_cursor: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&res).unwrap(),
+            json!({
+                "locks": [{
+                    "id": "1",
+                    "path": "foo",
+                    "locked_at": "2016-05-17T15:49:06+00:00",
+                    "owner": {
+                        "name": "alice"
+                    }
+                }]
+            })
+        );
+    }
+}
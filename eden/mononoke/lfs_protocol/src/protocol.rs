@@ -52,14 +52,23 @@ impl Arbitrary for Operation {
 pub enum Transfer {
     #[serde(rename = "basic")]
     Basic,
+    /// Resumable uploads, following the tus.io protocol: the client asks for the current offset
+    /// of an upload with a HEAD request, and sends the rest of the data with PATCH requests
+    /// starting at that offset. Downloads are the same as for the basic transfer.
+    #[serde(rename = "tus")]
+    Tus,
     #[serde(other)]
     Unknown,
 }
 
 impl Arbitrary for Transfer {
-    fn arbitrary<G: Gen>(_g: &mut G) -> Self {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
         // We don't generate invalid Transfer instances for testing.
-        Transfer::Basic
+        if bool::arbitrary(g) {
+            Transfer::Basic
+        } else {
+            Transfer::Tus
+        }
     }
 }
 
@@ -342,11 +351,10 @@ mod test {
             serde_json::from_str::<ResponseObject>(&j.to_string()),
             Ok(ResponseObject {
                 object: RequestObject { oid: _, size: 123 },
-                status:
-                    ObjectStatus::Ok {
-                        authenticated: false,
-                        actions: _,
-                    },
+                status: ObjectStatus::Ok {
+                    authenticated: false,
+                    actions: _,
+                },
             })
         )
     }
@@ -366,14 +374,12 @@ mod test {
             serde_json::from_str::<ResponseObject>(&j.to_string()),
             Ok(ResponseObject {
                 object: RequestObject { oid: _, size: 123 },
-                status:
-                    ObjectStatus::Err {
-                        error:
-                            ObjectError {
-                                code: 404,
-                                message: _,
-                            },
+                status: ObjectStatus::Err {
+                    error: ObjectError {
+                        code: 404,
+                        message: _,
                     },
+                },
             })
         )
     }
@@ -400,6 +406,21 @@ mod test {
         assert_eq!(res.expires_at, Some("2016-11-10T15:29:07Z".to_string()));
     }
 
+    #[test]
+    pub fn test_deserialize_transfers() {
+        let j = json!({
+            "operation": "upload",
+            "transfers": ["tus", "basic", "multipart"],
+            "objects": [],
+        });
+
+        let res = serde_json::from_str::<RequestBatch>(&j.to_string()).unwrap();
+        assert_eq!(
+            res.transfers,
+            vec![Transfer::Tus, Transfer::Basic, Transfer::Unknown]
+        );
+    }
+
     quickcheck! {
         fn request_batch_roundtrip(batch: RequestBatch) -> bool {
             let json = serde_json::to_string(&batch).unwrap();
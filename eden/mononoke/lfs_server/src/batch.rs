@@ -37,6 +37,7 @@ use mononoke_types::{hash::Sha256, typed_hash::ContentId, MononokeId};
 use crate::errors::ErrorKind;
 use crate::lfs_server_context::{RepositoryRequestContext, UriBuilder};
 use crate::middleware::{LfsMethod, RequestContext, ScubaKey, ScubaMiddlewareState};
+use crate::quota::{upload_budget, UploadBudget};
 
 define_stats! {
     prefix ="mononoke.lfs.batch";
@@ -132,7 +133,7 @@ async fn upstream_objects(
                 })
                 .collect()
         }
-        Transfer::Unknown => HashMap::new(),
+        Transfer::Tus | Transfer::Unknown => HashMap::new(),
     };
 
     Ok(UpstreamObjects::UpstreamPresence(objects))
@@ -223,9 +224,20 @@ async fn internal_objects(
     Ok(ret.context(ErrorKind::GenerateDownloadUrisError)?)
 }
 
+/// Pick the transfer adapter to upload objects with. Resumable uploads store the data as it
+/// comes in as Filestore chunks, so they are only offered when the repo's Filestore chunks files.
+fn upload_transfer(transfers: &[Transfer], chunking_enabled: bool) -> Transfer {
+    if chunking_enabled && transfers.contains(&Transfer::Tus) {
+        Transfer::Tus
+    } else {
+        Transfer::Basic
+    }
+}
+
 fn batch_upload_response_objects(
     uri_builder: &UriBuilder,
-    max_upload_size: Option<u64>,
+    transfer: &Transfer,
+    budget: &mut UploadBudget,
     objects: &[RequestObject],
     upstream: &UpstreamObjects,
     internal: &HashMap<RequestObject, ObjectAction>,
@@ -233,12 +245,8 @@ fn batch_upload_response_objects(
     let objects: Result<Vec<ResponseObject>, Error> = objects
         .iter()
         .map(|object| {
-            let status = match (
-                upstream.should_upload(object),
-                internal.get(object),
-                max_upload_size,
-            ) {
-                (false, Some(_), _) => {
+            let status = match (upstream.should_upload(object), internal.get(object)) {
+                (false, Some(_)) => {
                     // Object doesn't need to be uploaded anywhere: move on.
                     STATS::upload_no_redirect.add_value(1);
 
@@ -247,31 +255,35 @@ fn batch_upload_response_objects(
                         actions: hashmap! {},
                     }
                 }
-                (_, _, Some(max_upload_size)) if object.size > max_upload_size => {
-                    // Object is too large and upload is required: reject it (note: this doesn't
-                    // enforce that uploads cannot be done: the upload endpoint has its own
-                    // validation too).
-                    STATS::upload_rejected.add_value(1);
-
-                    ObjectStatus::Err {
-                        error: ObjectError {
-                            code: StatusCode::BAD_REQUEST.as_u16(),
-                            message: ErrorKind::UploadTooLarge(object.size, max_upload_size)
-                                .to_string(),
-                        },
+                _ => match budget.reserve(object.size) {
+                    Err(rejection) => {
+                        // Object is too large or over quota and upload is required: reject it
+                        // (note: this doesn't enforce that uploads cannot be done: the upload
+                        // endpoint has its own validation too).
+                        STATS::upload_rejected.add_value(1);
+
+                        ObjectStatus::Err {
+                            error: ObjectError {
+                                code: rejection.status_code.as_u16(),
+                                message: rejection.error.to_string(),
+                            },
+                        }
                     }
-                }
-                _ => {
-                    // Object is missing in at least one location. Require uploading it.
-                    STATS::upload_redirect.add_value(1);
-                    let uri = uri_builder.upload_uri(&object)?;
-                    let action = ObjectAction::new(uri);
+                    Ok(()) => {
+                        // Object is missing in at least one location. Require uploading it.
+                        STATS::upload_redirect.add_value(1);
+                        let uri = match transfer {
+                            Transfer::Tus => uri_builder.resumable_upload_uri(&object)?,
+                            _ => uri_builder.upload_uri(&object)?,
+                        };
+                        let action = ObjectAction::new(uri);
 
-                    ObjectStatus::Ok {
-                        authenticated: false,
-                        actions: hashmap! { Operation::Upload => action },
+                        ObjectStatus::Ok {
+                            authenticated: false,
+                            actions: hashmap! { Operation::Upload => action },
+                        }
                     }
-                }
+                },
             };
 
             Ok(ResponseObject {
@@ -290,23 +302,27 @@ async fn batch_upload(
     ctx: &RepositoryRequestContext,
     batch: RequestBatch,
 ) -> Result<ResponseBatch, Error> {
-    let (upstream, internal) = try_join!(
+    let (upstream, internal, mut budget) = try_join!(
         upstream_objects(ctx, &batch.objects),
         internal_objects(ctx, &batch.objects),
+        upload_budget(ctx).map(Ok::<_, Error>),
     )?;
 
+    let transfer = upload_transfer(
+        &batch.transfers,
+        ctx.repo.filestore_config().chunk_size.is_some(),
+    );
+
     let objects = batch_upload_response_objects(
         &ctx.uri_builder,
-        ctx.max_upload_size(),
+        &transfer,
+        &mut budget,
         &batch.objects,
         &upstream,
         &internal,
     )?;
 
-    Ok(ResponseBatch {
-        transfer: Transfer::Basic,
-        objects,
-    })
+    Ok(ResponseBatch { transfer, objects })
 }
 
 /// This method peforms the routing logic for a given object being requested, given what's
@@ -728,7 +744,8 @@ mod test {
 
         let res = batch_upload_response_objects(
             &uri_builder,
-            Some(1000),
+            &Transfer::Basic,
+            &mut UploadBudget::new(Some(1000), None),
             &req,
             &UpstreamObjects::UpstreamPresence(upstream),
             &internal,
@@ -764,7 +781,7 @@ mod test {
                     object: o4,
                     status: ObjectStatus::Err {
                         error: ObjectError {
-                            code: 400,
+                            code: 422,
                             message: "Object size (1111) exceeds max allowed size (1000)"
                                 .to_string(),
                         }
@@ -777,6 +794,121 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_upload_quota() -> Result<(), Error> {
+        let o1 = obj(ONES_HASH, 60)?;
+        let o2 = obj(TWOS_HASH, 60)?;
+        let o3 = obj(THREES_HASH, 30)?;
+
+        let internal = hashmap! {
+            o1 => ObjectAction::new("http://bar.com/1".parse()?),
+        };
+
+        let server = ServerUris::new("http://foo.com", None)?;
+        let uri_builder = UriBuilder {
+            repository: "repo123".to_string(),
+            server: Arc::new(server),
+        };
+
+        let res = batch_upload_response_objects(
+            &uri_builder,
+            &Transfer::Basic,
+            &mut UploadBudget::new(None, Some(100)),
+            &[o1, o2, o2, o3],
+            &UpstreamObjects::NoUpstream,
+            &internal,
+        )?;
+
+        assert_eq!(
+            vec![
+                ResponseObject {
+                    object: o1,
+                    status: ObjectStatus::Ok {
+                        authenticated: false,
+                        // This is present already, so it doesn't count against the quota.
+                        actions: hashmap! {}
+                    }
+                },
+                ResponseObject {
+                    object: o2,
+                    status: ObjectStatus::Ok {
+                        authenticated: false,
+                        actions: hashmap! { Operation::Upload => ObjectAction::new(upload_uri(&o2)?) }
+                    }
+                },
+                ResponseObject {
+                    object: o2,
+                    status: ObjectStatus::Err {
+                        error: ObjectError {
+                            code: 429,
+                            message:
+                                "Object size (60) exceeds the remaining daily upload quota (40)"
+                                    .to_string(),
+                        }
+                    }
+                },
+                ResponseObject {
+                    object: o3,
+                    status: ObjectStatus::Ok {
+                        authenticated: false,
+                        actions: hashmap! { Operation::Upload => ObjectAction::new(upload_uri(&o3)?) }
+                    }
+                },
+            ],
+            res
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upload_transfer() {
+        let both = vec![Transfer::Basic, Transfer::Tus];
+        assert_eq!(upload_transfer(&both, true), Transfer::Tus);
+        assert_eq!(upload_transfer(&both, false), Transfer::Basic);
+        assert_eq!(upload_transfer(&[Transfer::Basic], true), Transfer::Basic);
+        assert_eq!(upload_transfer(&[Transfer::Unknown], true), Transfer::Basic);
+    }
+
+    #[test]
+    fn test_upload_resumable() -> Result<(), Error> {
+        let o1 = obj(ONES_HASH, 123)?;
+
+        let server = ServerUris::new("http://foo.com", None)?;
+        let uri_builder = UriBuilder {
+            repository: "repo123".to_string(),
+            server: Arc::new(server),
+        };
+
+        let res = batch_upload_response_objects(
+            &uri_builder,
+            &Transfer::Tus,
+            &mut UploadBudget::default(),
+            &[o1],
+            &UpstreamObjects::NoUpstream,
+            &hashmap! {},
+        )?;
+
+        let uri = format!(
+            "http://foo.com/repo123/upload_resumable/{}/{}",
+            o1.oid, o1.size
+        )
+        .parse()?;
+
+        assert_eq!(
+            vec![ResponseObject {
+                object: o1,
+                status: ObjectStatus::Ok {
+                    authenticated: false,
+                    actions: hashmap! { Operation::Upload => ObjectAction::new(uri) }
+                }
+            }],
+            res
+        );
+
+        Ok(())
+    }
+
     #[fbinit::compat_test]
     async fn test_resolve_missing(fb: FacebookInit) -> Result<(), Error> {
         let ctx = RepositoryRequestContext::test_builder(fb)?.build()?;
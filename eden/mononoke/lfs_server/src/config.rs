@@ -23,6 +23,22 @@ pub struct RawLimit {
     pub client_identities: Vec<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawUploadQuota {
+    /// The repository this quota applies to. Quotas without a repository apply to each repository
+    /// separately.
+    #[serde(default)]
+    pub repository: Option<String>,
+    /// The clients this quota applies to. Each of them gets its own budget. Quotas without client
+    /// identities apply to all uploads to the repository combined.
+    #[serde(default)]
+    pub client_identities: Vec<String>,
+    #[serde(default)]
+    pub max_bytes_per_day: Option<u64>,
+    #[serde(default)]
+    pub max_object_size: Option<u64>,
+}
+
 /// Struct representing actual config data.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RawServerConfig {
@@ -32,6 +48,8 @@ pub struct RawServerConfig {
     pub throttle_limits: Vec<RawLimit>,
     pub acl_check: bool,
     pub enforce_acl_check: bool,
+    #[serde(default)]
+    pub upload_quotas: Vec<RawUploadQuota>,
 }
 
 #[derive(Debug, Clone)]
@@ -57,10 +75,34 @@ impl TryFrom<&RawLimit> for Limit {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct UploadQuota {
+    raw_upload_quota: RawUploadQuota,
+    client_identities: Vec<MononokeIdentity>,
+}
+
+impl TryFrom<&RawUploadQuota> for UploadQuota {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &RawUploadQuota) -> Result<Self, Self::Error> {
+        let client_identities = value
+            .client_identities
+            .iter()
+            .map(|x| FromStr::from_str(&x))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            raw_upload_quota: value.clone(),
+            client_identities,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     raw_server_config: RawServerConfig,
     throttle_limits: Vec<Limit>,
+    upload_quotas: Vec<UploadQuota>,
 }
 
 impl<'de> Deserialize<'de> for ServerConfig {
@@ -80,9 +122,17 @@ impl<'de> Deserialize<'de> for ServerConfig {
             Ok(v) => v,
         };
 
+        let upload_quotas = raw_server_config
+            .upload_quotas
+            .iter()
+            .map(UploadQuota::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| D::Error::custom(e.to_string()))?;
+
         Ok(Self {
             raw_server_config,
             throttle_limits,
+            upload_quotas,
         })
     }
 }
@@ -105,6 +155,7 @@ impl Default for RawServerConfig {
             throttle_limits: vec![],
             acl_check: false,
             enforce_acl_check: false,
+            upload_quotas: vec![],
         }
     }
 }
@@ -113,6 +164,7 @@ impl Default for ServerConfig {
         Self {
             raw_server_config: RawServerConfig::default(),
             throttle_limits: vec![],
+            upload_quotas: vec![],
         }
     }
 }
@@ -136,6 +188,9 @@ impl ServerConfig {
     pub fn enforce_acl_check(&self) -> bool {
         self.raw_server_config.enforce_acl_check
     }
+    pub fn upload_quotas(&self) -> &[UploadQuota] {
+        &self.upload_quotas
+    }
 }
 
 impl Limit {
@@ -155,3 +210,18 @@ impl Limit {
         self.client_identities.clone()
     }
 }
+
+impl UploadQuota {
+    pub fn repository(&self) -> Option<&str> {
+        self.raw_upload_quota.repository.as_deref()
+    }
+    pub fn client_identities(&self) -> &[MononokeIdentity] {
+        &self.client_identities
+    }
+    pub fn max_bytes_per_day(&self) -> Option<u64> {
+        self.raw_upload_quota.max_bytes_per_day
+    }
+    pub fn max_object_size(&self) -> Option<u64> {
+        self.raw_upload_quota.max_object_size
+    }
+}
@@ -5,15 +5,20 @@
  * GNU General Public License version 2.
  */
 
+use std::cmp::min;
+use std::fmt::Display;
 use std::str::FromStr;
 
-use anyhow::Context;
+use anyhow::{Context, Error};
+use bytes::Bytes;
 use futures::{
     compat::{Future01CompatExt, Stream01CompatExt},
-    stream::{StreamExt, TryStreamExt},
+    stream::{Stream, StreamExt, TryStreamExt},
 };
-use gotham::state::State;
+use gotham::state::{FromState, State};
 use gotham_derive::{StateData, StaticResponseExtender};
+use http::header::{HeaderMap, HeaderValue, ACCEPT_RANGES, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use hyper::StatusCode;
 use serde::Deserialize;
 
 use filestore::{self, Alias, FetchKey};
@@ -34,6 +39,7 @@ define_stats! {
         Sum;
         Duration::from_secs(5), Duration::from_secs(15), Duration::from_secs(60)
     ),
+    range_requests: timeseries(Rate, Sum),
 }
 
 #[derive(Deserialize, StateData, StaticResponseExtender)]
@@ -48,28 +54,114 @@ pub struct DownloadParamsSha256 {
     oid: String,
 }
 
-async fn fetch_by_key(
-    ctx: RepositoryRequestContext,
-    key: FetchKey,
-) -> Result<impl TryIntoResponse, HttpError> {
-    // Query a stream out of the Filestore
-    let fetched = filestore::fetch_with_size(ctx.repo.blobstore(), ctx.ctx.clone(), &key)
-        .compat()
-        .await
-        .map_err(|e| {
-            if has_redaction_root_cause(&e) {
-                HttpError::e410(e)
-            } else {
-                HttpError::e500(e.context(ErrorKind::FilestoreReadFailure))
-            }
-        })?;
+/// A single byte range requested in a `Range` header. Requests for several ranges are served the
+/// whole object instead, which HTTP allows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ByteRange {
+    /// `bytes=start-end` or `bytes=start-` (`end` is inclusive).
+    FromStart { start: u64, end: Option<u64> },
+    /// `bytes=-len`, for the last `len` bytes.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    fn parse(header: &str) -> Option<Self> {
+        let spec = header.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+
+        let (start, end) = spec.split_at(spec.find('-')?);
+        let (start, end) = (start.trim(), end[1..].trim());
+
+        if start.is_empty() {
+            return end.parse().ok().map(ByteRange::Suffix);
+        }
+
+        let start = start.parse().ok()?;
+        let end = if end.is_empty() {
+            None
+        } else {
+            Some(end.parse().ok()?)
+        };
+
+        match end {
+            Some(end) if end < start => None,
+            _ => Some(ByteRange::FromStart { start, end }),
+        }
+    }
+}
+
+/// The range to serve, if any. Ranges conditional on an `If-Range` that does not match the object
+/// (or that is a date, since we don't track modification times) are ignored.
+fn requested_range(headers: Option<&HeaderMap>, etag: &HeaderValue) -> Option<ByteRange> {
+    let headers = headers?;
+
+    if let Some(if_range) = headers.get(IF_RANGE) {
+        if if_range != etag {
+            return None;
+        }
+    }
+
+    ByteRange::parse(headers.get(RANGE)?.to_str().ok()?)
+}
+
+fn map_fetch_error(e: Error) -> HttpError {
+    if has_redaction_root_cause(&e) {
+        HttpError::e410(e)
+    } else {
+        HttpError::e500(e.context(ErrorKind::FilestoreReadFailure))
+    }
+}
+
+/// Query a stream of the bytes in [start, end) of an object out of the Filestore, along with the
+/// size of the whole object. Only the chunks that overlap the range are loaded.
+async fn fetch_range(
+    ctx: &RepositoryRequestContext,
+    key: &FetchKey,
+    start: u64,
+    end: u64,
+) -> Result<(impl Stream<Item = Result<Bytes, Error>>, u64), HttpError> {
+    let fetched = filestore::fetch_range_with_size(
+        ctx.repo.blobstore(),
+        ctx.ctx.clone(),
+        key,
+        start,
+        end.saturating_sub(start),
+    )
+    .compat()
+    .await
+    .map_err(map_fetch_error)?;
 
     // Return a 404 if the stream doesn't exist.
     let (stream, size) = fetched
-        .ok_or_else(|| ErrorKind::ObjectDoesNotExist(key))
+        .ok_or_else(|| ErrorKind::ObjectDoesNotExist(key.clone()))
         .map_err(HttpError::e404)?;
 
-    let stream = stream.compat();
+    Ok((stream.compat(), size))
+}
+
+async fn fetch_by_key(
+    ctx: RepositoryRequestContext,
+    key: FetchKey,
+    etag: HeaderValue,
+    range: Option<ByteRange>,
+) -> Result<StreamBody<impl Stream<Item = Result<Bytes, Error>> + Send + 'static>, HttpError> {
+    // Resolve the range into [start, end) offsets. Suffix ranges need the size of the object, which
+    // we get by fetching an empty range first.
+    let span = match range {
+        Some(ByteRange::FromStart { start, end }) => {
+            Some((start, end.map_or(u64::MAX, |end| end.saturating_add(1))))
+        }
+        Some(ByteRange::Suffix(len)) => {
+            let (_, size) = fetch_range(&ctx, &key, 0, 0).await?;
+            Some((size.saturating_sub(len), size))
+        }
+        None => None,
+    };
+
+    let (start, end) = span.unwrap_or((0, u64::MAX));
+    let (stream, size) = fetch_range(&ctx, &key, start, end).await?;
 
     let stream = if ctx.config.track_bytes_sent() {
         stream
@@ -79,11 +171,36 @@ async fn fetch_by_key(
         stream.right_stream()
     };
 
-    Ok(StreamBody::new(
-        stream,
-        size,
-        mime::APPLICATION_OCTET_STREAM,
-    ))
+    let body = match span {
+        Some((start, end)) => {
+            if start >= size {
+                let content_range =
+                    HeaderValue::from_str(&format!("bytes */{}", size)).map_err(HttpError::e500)?;
+                let err = HttpError::e416(ErrorKind::RangeNotSatisfiable(size));
+                return Err(err.with_header(CONTENT_RANGE, content_range));
+            }
+            let end = min(end, size);
+            let content_range = format!("bytes {}-{}/{}", start, end - 1, size);
+
+            STATS::range_requests.add_value(1);
+            StreamBody::new(stream, end - start, mime::APPLICATION_OCTET_STREAM)
+                .with_status(StatusCode::PARTIAL_CONTENT)
+                .with_header(
+                    CONTENT_RANGE,
+                    HeaderValue::from_str(&content_range).map_err(HttpError::e500)?,
+                )
+        }
+        None => StreamBody::new(stream, size, mime::APPLICATION_OCTET_STREAM),
+    };
+
+    Ok(body
+        .with_header(ACCEPT_RANGES, HeaderValue::from_static("bytes"))
+        .with_header(ETAG, etag))
+}
+
+/// Objects are immutable, so the identifier they are downloaded by makes a strong ETag.
+fn etag(id: impl Display) -> Result<HeaderValue, HttpError> {
+    HeaderValue::from_str(&format!("\"{}\"", id)).map_err(HttpError::e500)
 }
 
 pub async fn download(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
@@ -97,11 +214,13 @@ pub async fn download(state: &mut State) -> Result<impl TryIntoResponse, HttpErr
         .map_err(HttpError::e400)?;
 
     let key = FetchKey::Canonical(content_id);
+    let etag = etag(content_id)?;
+    let range = requested_range(HeaderMap::try_borrow_from(state), &etag);
 
     let ctx = RepositoryRequestContext::instantiate(state, repository.clone(), LfsMethod::Download)
         .await?;
 
-    fetch_by_key(ctx, key).await
+    fetch_by_key(ctx, key, etag, range).await
 }
 
 pub async fn download_sha256(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
@@ -112,26 +231,54 @@ pub async fn download_sha256(state: &mut State) -> Result<impl TryIntoResponse,
         .map_err(HttpError::e400)?;
 
     let key = FetchKey::Aliased(Alias::Sha256(oid));
+    let etag = etag(oid)?;
+    let range = requested_range(HeaderMap::try_borrow_from(state), &etag);
 
     let ctx =
         RepositoryRequestContext::instantiate(state, repository.clone(), LfsMethod::DownloadSha256)
             .await?;
 
-    fetch_by_key(ctx, key).await
+    fetch_by_key(ctx, key, etag, range).await
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    use anyhow::Error;
     use blobrepo_factory::TestRepoBuilder;
     use fbinit::FacebookInit;
+    use filestore::StoreRequest;
+    use futures_old::stream as stream_old;
     use http::StatusCode;
     use maplit::hashmap;
     use mononoke_types::typed_hash::MononokeId;
     use mononoke_types_mocks::contentid::ONES_CTID;
 
+    const DATA: &str = "0123456789";
+
+    async fn store_data(ctx: &RepositoryRequestContext) -> Result<(FetchKey, HeaderValue), Error> {
+        let meta = filestore::store(
+            ctx.repo.blobstore().clone(),
+            ctx.repo.filestore_config(),
+            ctx.ctx.clone(),
+            &StoreRequest::new(DATA.len() as u64),
+            stream_old::once(Ok(Bytes::from(DATA))),
+        )
+        .compat()
+        .await?;
+
+        let etag = HeaderValue::from_str(&format!("\"{}\"", meta.content_id))?;
+        Ok((FetchKey::Canonical(meta.content_id), etag))
+    }
+
+    async fn body_of<S>(body: StreamBody<S>) -> Result<Bytes, Error>
+    where
+        S: Stream<Item = Result<Bytes, Error>>,
+    {
+        let chunks: Vec<Bytes> = body.into_stream().try_collect().await?;
+        Ok(chunks.concat().into())
+    }
+
     #[fbinit::compat_test]
     async fn test_redacted_fetch(fb: FacebookInit) -> Result<(), Error> {
         let content_id = ONES_CTID;
@@ -149,9 +296,159 @@ mod test {
 
         let key = FetchKey::Canonical(content_id);
 
-        let err = fetch_by_key(ctx, key).await.map(|_| ()).unwrap_err();
+        let etag = HeaderValue::from_str(&format!("\"{}\"", content_id))?;
+
+        let err = fetch_by_key(ctx, key, etag, None)
+            .await
+            .map(|_| ())
+            .unwrap_err();
         assert_eq!(err.status_code, StatusCode::GONE);
         assert!(err.error.to_string().contains(reason));
         Ok(())
     }
+
+    #[fbinit::compat_test]
+    async fn test_range_fetch(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = RepositoryRequestContext::test_builder(fb)?.build()?;
+        let (key, etag) = store_data(&ctx).await?;
+
+        let range = ByteRange::FromStart {
+            start: 2,
+            end: Some(5),
+        };
+        let body = fetch_by_key(ctx.clone(), key.clone(), etag.clone(), Some(range))
+            .await
+            .map_err(|e| e.error)?;
+        assert_eq!(body.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body.content_length(), 4);
+        assert_eq!(
+            body.headers().get(CONTENT_RANGE),
+            Some(&HeaderValue::from_static("bytes 2-5/10"))
+        );
+        assert_eq!(body_of(body).await?, Bytes::from("2345"));
+
+        let body = fetch_by_key(
+            ctx.clone(),
+            key.clone(),
+            etag.clone(),
+            ByteRange::parse("bytes=-3"),
+        )
+        .await
+        .map_err(|e| e.error)?;
+        assert_eq!(body.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            body.headers().get(CONTENT_RANGE),
+            Some(&HeaderValue::from_static("bytes 7-9/10"))
+        );
+        assert_eq!(body_of(body).await?, Bytes::from("789"));
+
+        // Ranges that go past the end of the object are truncated to it.
+        let body = fetch_by_key(ctx, key, etag, ByteRange::parse("bytes=8-100"))
+            .await
+            .map_err(|e| e.error)?;
+        assert_eq!(
+            body.headers().get(CONTENT_RANGE),
+            Some(&HeaderValue::from_static("bytes 8-9/10"))
+        );
+        assert_eq!(body_of(body).await?, Bytes::from("89"));
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_range_fetch_if_range_mismatch(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = RepositoryRequestContext::test_builder(fb)?.build()?;
+        let (key, etag) = store_data(&ctx).await?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RANGE, HeaderValue::from_static("bytes=2-5"));
+        headers.insert(IF_RANGE, HeaderValue::from_static("\"stale\""));
+
+        // The object is not the one the client has part of, so it gets all of it.
+        let range = requested_range(Some(&headers), &etag);
+        let body = fetch_by_key(ctx, key, etag, range)
+            .await
+            .map_err(|e| e.error)?;
+        assert_eq!(body.status(), StatusCode::OK);
+        assert_eq!(body.content_length(), DATA.len() as u64);
+        assert_eq!(body.headers().get(CONTENT_RANGE), None);
+        assert_eq!(body_of(body).await?, Bytes::from(DATA));
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_range_not_satisfiable(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = RepositoryRequestContext::test_builder(fb)?.build()?;
+        let (key, etag) = store_data(&ctx).await?;
+
+        let range = ByteRange::FromStart {
+            start: 10,
+            end: None,
+        };
+        let err = fetch_by_key(ctx, key, etag, Some(range))
+            .await
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            err.headers.get(CONTENT_RANGE),
+            Some(&HeaderValue::from_static("bytes */10"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            ByteRange::parse("bytes=10-20"),
+            Some(ByteRange::FromStart {
+                start: 10,
+                end: Some(20)
+            })
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=10-"),
+            Some(ByteRange::FromStart {
+                start: 10,
+                end: None
+            })
+        );
+        assert_eq!(ByteRange::parse("bytes=-5"), Some(ByteRange::Suffix(5)));
+        assert_eq!(ByteRange::parse("bytes=20-10"), None);
+        assert_eq!(ByteRange::parse("bytes=0-1,5-6"), None);
+        assert_eq!(ByteRange::parse("bytes=-"), None);
+        assert_eq!(ByteRange::parse("lines=1-2"), None);
+        assert_eq!(ByteRange::parse("bytes=a-b"), None);
+    }
+
+    #[test]
+    fn test_requested_range() -> Result<(), Error> {
+        let etag = HeaderValue::from_static("\"abc\"");
+        let range = ByteRange::FromStart {
+            start: 0,
+            end: Some(9),
+        };
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(requested_range(Some(&headers), &etag), None);
+
+        headers.insert(RANGE, HeaderValue::from_static("bytes=0-9"));
+        assert_eq!(requested_range(Some(&headers), &etag), Some(range));
+
+        headers.insert(IF_RANGE, etag.clone());
+        assert_eq!(requested_range(Some(&headers), &etag), Some(range));
+
+        headers.insert(IF_RANGE, HeaderValue::from_static("\"def\""));
+        assert_eq!(requested_range(Some(&headers), &etag), None);
+
+        headers.insert(
+            IF_RANGE,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(requested_range(Some(&headers), &etag), None);
+
+        Ok(())
+    }
 }
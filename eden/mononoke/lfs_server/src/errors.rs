@@ -67,8 +67,48 @@ pub enum ErrorKind {
     Throttled(String, i64, i64),
     #[error("Object size ({0}) exceeds max allowed size ({1})")]
     UploadTooLarge(u64, u64),
+    #[error("Object size ({0}) exceeds the remaining daily upload quota ({1})")]
+    UploadQuotaExceeded(u64, u64),
+    #[error("Uploaded data does not match the object it was uploaded as: {0}")]
+    UploadVerificationFailed(String),
     #[error("Object is not internally available, and upstream is not available: {0:?}")]
     ObjectNotInternallyAvailableAndUpstreamUnavailable(RequestObject),
+    #[error("Requested range is outside of the object (size: {0})")]
+    RangeNotSatisfiable(u64),
+    #[error("Upload-Offset header is missing or invalid")]
+    InvalidUploadOffset,
+    #[error("Upload offset ({0}) does not match the current offset of the upload ({1})")]
+    UploadOffsetMismatch(u64, u64),
+    #[error("Resumable uploads must have Content-Type: {0}")]
+    InvalidResumableContentType(&'static str),
+    #[error("Upload exceeds its declared size ({0})")]
+    UploadExceedsSize(u64),
+    #[error("Could not load upload session")]
+    UploadSessionLoadFailure,
+    #[error("Could not save upload session")]
+    UploadSessionSaveFailure,
+    #[error("File locking is not enabled for this repository")]
+    LockingDisabled,
+    #[error("File locking requires a client identity")]
+    LockingRequiresIdentity,
+    #[error("Could not parse lock request")]
+    InvalidLockRequest,
+    #[error("Invalid lock path: {0}")]
+    InvalidLockPath(String),
+    #[error("Invalid lock id: {0}")]
+    InvalidLockId(String),
+    #[error("Invalid lock listing cursor: {0}")]
+    InvalidLockCursor(String),
+    #[error("Invalid lock listing limit: {0}")]
+    InvalidLockLimit(String),
+    #[error("Lock does not exist: {0}")]
+    LockDoesNotExist(u64),
+    #[error("Lock {0} is owned by {1}, use force to unlock it")]
+    LockNotOwned(u64, String),
+    #[error("Lock {0} is owned by {1}, and forcing unlocks requires the {2} permission")]
+    LockForceNotPermitted(u64, String, &'static str),
+    #[error("Could not access lock storage")]
+    LockStoreFailure,
 }
 
 #[derive(Debug, Error)]
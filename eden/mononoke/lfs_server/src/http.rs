@@ -18,7 +18,7 @@ use gotham_ext::{
     signal_stream::SignalStream,
 };
 use hyper::{
-    header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
     Body, Response, StatusCode,
 };
 use mime::Mime;
@@ -41,6 +41,8 @@ pub struct StreamBody<S> {
     stream: S,
     content_length: u64,
     mime: Mime,
+    status: StatusCode,
+    headers: HeaderMap,
 }
 
 impl<S> StreamBody<S> {
@@ -49,8 +51,39 @@ impl<S> StreamBody<S> {
             stream,
             content_length,
             mime,
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
         }
     }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+}
+
+#[cfg(test)]
+impl<S> StreamBody<S> {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn content_length(&self) -> u64 {
+        self.content_length
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn into_stream(self) -> S {
+        self.stream
+    }
 }
 
 impl<S> TryIntoResponse for StreamBody<S>
@@ -62,6 +95,8 @@ where
             stream,
             content_length,
             mime,
+            status,
+            headers,
         } = self;
 
         state.put(ResponseContentLength(content_length));
@@ -83,11 +118,14 @@ where
             receiver.right_stream()
         };
 
-        Response::builder()
+        let mut res = Response::builder()
             .header(CONTENT_TYPE, mime_header)
             .header(CONTENT_LENGTH, content_length)
-            .status(StatusCode::OK)
-            .body(Body::wrap_stream(stream))
-            .map_err(Error::from)
+            .status(status)
+            .body(Body::wrap_stream(stream))?;
+
+        res.headers_mut().extend(headers);
+
+        Ok(res)
     }
 }
@@ -31,7 +31,9 @@ use blobrepo::BlobRepo;
 use context::CoreContext;
 use hyper::{client::HttpConnector, Client};
 use hyper_openssl::HttpsConnector;
+use lfs_locks::SqlLfsLocks;
 use lfs_protocol::{RequestBatch, RequestObject, ResponseBatch};
+use lfs_upload_sessions::SqlLfsUploadSessions;
 use mononoke_types::hash::Sha256;
 use mononoke_types::ContentId;
 
@@ -45,7 +47,15 @@ pub type HttpsHyperClient = Client<HttpsConnector<HttpConnector>>;
 const ACL_CHECK_ACTION: &str = "read";
 
 struct LfsServerContextInner {
-    repositories: HashMap<String, (BlobRepo, ArcPermissionChecker)>,
+    repositories: HashMap<
+        String,
+        (
+            BlobRepo,
+            ArcPermissionChecker,
+            Option<SqlLfsLocks>,
+            SqlLfsUploadSessions,
+        ),
+    >,
     client: Arc<HttpsHyperClient>,
     server: Arc<ServerUris>,
     always_wait_for_upstream: bool,
@@ -61,7 +71,15 @@ pub struct LfsServerContext {
 
 impl LfsServerContext {
     pub fn new(
-        repositories: HashMap<String, (BlobRepo, ArcPermissionChecker)>,
+        repositories: HashMap<
+            String,
+            (
+                BlobRepo,
+                ArcPermissionChecker,
+                Option<SqlLfsLocks>,
+                SqlLfsUploadSessions,
+            ),
+        >,
         server: ServerUris,
         always_wait_for_upstream: bool,
         max_upload_size: Option<u64>,
@@ -94,13 +112,25 @@ impl LfsServerContext {
         repository: String,
         identities: Option<&MononokeIdentitySet>,
     ) -> Result<RepositoryRequestContext, LfsServerContextErrorKind> {
-        let (repo, aclchecker, client, server, always_wait_for_upstream, max_upload_size, config) = {
+        let (
+            repo,
+            aclchecker,
+            locks,
+            upload_sessions,
+            client,
+            server,
+            always_wait_for_upstream,
+            max_upload_size,
+            config,
+        ) = {
             let inner = self.inner.lock().expect("poisoned lock");
 
             match inner.repositories.get(&repository) {
-                Some((repo, aclchecker)) => (
+                Some((repo, aclchecker, locks, upload_sessions)) => (
                     repo.clone(),
                     aclchecker.clone(),
+                    locks.clone(),
+                    upload_sessions.clone(),
                     inner.client.clone(),
                     inner.server.clone(),
                     inner.always_wait_for_upstream,
@@ -116,12 +146,16 @@ impl LfsServerContext {
         };
 
         if config.acl_check() {
-            acl_check(aclchecker, identities, config.enforce_acl_check()).await?;
+            acl_check(aclchecker.clone(), identities, config.enforce_acl_check()).await?;
         }
 
         Ok(RepositoryRequestContext {
             ctx,
             repo,
+            locks,
+            upload_sessions,
+            aclchecker,
+            identities: identities.cloned(),
             uri_builder: UriBuilder { repository, server },
             client: HttpClient::Enabled(client),
             config,
@@ -183,6 +217,14 @@ enum HttpClient {
 pub struct RepositoryRequestContext {
     pub ctx: CoreContext,
     pub repo: BlobRepo,
+    /// The LFS locks for this repository, if locking is enabled for it.
+    pub locks: Option<SqlLfsLocks>,
+    /// The progress of resumable uploads to this repository.
+    pub upload_sessions: SqlLfsUploadSessions,
+    /// The permission checker for this repository, for actions beyond accessing it.
+    pub aclchecker: ArcPermissionChecker,
+    /// The identities the client authenticated with, if any.
+    pub identities: Option<MononokeIdentitySet>,
     pub uri_builder: UriBuilder,
     pub config: Arc<ServerConfig>,
     always_wait_for_upstream: bool,
@@ -316,6 +358,17 @@ impl UriBuilder {
             .map_err(Error::from)
     }
 
+    pub fn resumable_upload_uri(&self, object: &RequestObject) -> Result<Uri, Error> {
+        self.server
+            .self_uri
+            .build(format_args!(
+                "{}/upload_resumable/{}/{}",
+                &self.repository, object.oid, object.size
+            ))
+            .context(ErrorKind::UriBuilderFailed("resumable_upload_uri"))
+            .map_err(Error::from)
+    }
+
     pub fn download_uri(&self, content_id: &ContentId) -> Result<Uri, Error> {
         self.server
             .self_uri
@@ -423,6 +476,8 @@ mod test {
     use fbinit::FacebookInit;
     use lfs_protocol::Sha256 as LfsSha256;
     use mononoke_types::{hash::Sha256, ContentId};
+    use permission_checker::PermissionCheckerBuilder;
+    use sql_construct::SqlConstruct;
     use std::str::FromStr;
 
     const ONES_HASH: &str = "1111111111111111111111111111111111111111111111111111111111111111";
@@ -440,6 +495,10 @@ mod test {
     pub struct TestContextBuilder {
         fb: FacebookInit,
         repo: BlobRepo,
+        locks: Option<SqlLfsLocks>,
+        upload_sessions: SqlLfsUploadSessions,
+        aclchecker: ArcPermissionChecker,
+        identities: Option<MononokeIdentitySet>,
         uri_builder: UriBuilder,
     }
 
@@ -449,16 +508,39 @@ mod test {
             self
         }
 
+        pub fn locks(mut self, locks: SqlLfsLocks) -> Self {
+            self.locks = Some(locks);
+            self
+        }
+
+        pub fn aclchecker(mut self, aclchecker: ArcPermissionChecker) -> Self {
+            self.aclchecker = aclchecker;
+            self
+        }
+
+        pub fn identities(mut self, identities: MononokeIdentitySet) -> Self {
+            self.identities = Some(identities);
+            self
+        }
+
         pub fn build(self) -> Result<RepositoryRequestContext, Error> {
             let Self {
                 fb,
                 repo,
+                locks,
+                upload_sessions,
+                aclchecker,
+                identities,
                 uri_builder,
             } = self;
 
             Ok(RepositoryRequestContext {
                 ctx: CoreContext::test_mock(fb),
                 repo,
+                locks,
+                upload_sessions,
+                aclchecker,
+                identities,
                 config: Arc::new(ServerConfig::default()),
                 uri_builder,
                 always_wait_for_upstream: false,
@@ -475,6 +557,10 @@ mod test {
             Ok(TestContextBuilder {
                 fb,
                 repo: TestRepoBuilder::new().build()?,
+                locks: None,
+                upload_sessions: SqlLfsUploadSessions::with_sqlite_in_memory()?,
+                aclchecker: Arc::from(PermissionCheckerBuilder::always_allow()),
+                identities: None,
                 uri_builder,
             })
         }
@@ -0,0 +1,545 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::cmp::min;
+
+use anyhow::{Context, Error};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use gotham_ext::{
+    body_ext::BodyExt,
+    error::HttpError,
+    response::{BytesBody, TryIntoResponse},
+};
+use http::header::HeaderMap;
+use hyper::{Body, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use lfs_locks::{lock_owner, LfsLock, LockCreation, SqlLfsLocks};
+use lfs_protocol::{
+    git_lfs_mime, CreateLockRequest, ListLocksResponse, Lock, LockConflict, LockOwner,
+    LockResponse, UnlockRequest, VerifyLocksRequest, VerifyLocksResponse,
+};
+use mononoke_types::{DateTime, MPath};
+use permission_checker::MononokeIdentitySet;
+
+use crate::errors::ErrorKind;
+use crate::lfs_server_context::RepositoryRequestContext;
+use crate::middleware::LfsMethod;
+
+/// How many locks to list when the client doesn't ask for a specific number.
+const DEFAULT_LIMIT: usize = 100;
+/// The most locks we'll list in one response.
+const MAX_LIMIT: usize = 1000;
+/// The action clients need to be allowed in the repository's ACL to release other users' locks.
+const LOCK_ADMIN_ACTION: &str = "lfs_lock_admin";
+
+#[derive(Deserialize, StateData, StaticResponseExtender)]
+pub struct LocksParams {
+    repository: String,
+}
+
+#[derive(Deserialize, StateData, StaticResponseExtender)]
+pub struct UnlockParams {
+    repository: String,
+    id: String,
+}
+
+// NOTE: Like path parameters, we don't deserialize these beyond a String form, in order to report
+// errors in our controller, not in routing.
+#[derive(Default, Deserialize, StateData, StaticResponseExtender)]
+pub struct ListLocksQuery {
+    path: Option<String>,
+    id: Option<String>,
+    cursor: Option<String>,
+    limit: Option<String>,
+}
+
+fn lock_store(ctx: &RepositoryRequestContext) -> Result<&SqlLfsLocks, HttpError> {
+    ctx.locks
+        .as_ref()
+        .ok_or_else(|| HttpError::e404(ErrorKind::LockingDisabled))
+}
+
+fn client_identities(ctx: &RepositoryRequestContext) -> Result<&MononokeIdentitySet, HttpError> {
+    ctx.identities
+        .as_ref()
+        .filter(|identities| !identities.is_empty())
+        .ok_or_else(|| HttpError::e403(ErrorKind::LockingRequiresIdentity))
+}
+
+/// Lock paths are relative to the root of the repository. Normalize them the way we normalize
+/// paths in commits, so that the hook enforcing locks finds them.
+fn normalize_path(path: &str) -> Result<String, HttpError> {
+    MPath::new(path)
+        .map(|path| path.to_string())
+        .context(ErrorKind::InvalidLockPath(path.to_string()))
+        .map_err(HttpError::e400)
+}
+
+fn parse_id(id: &str) -> Result<u64, HttpError> {
+    id.parse()
+        .context(ErrorKind::InvalidLockId(id.to_string()))
+        .map_err(HttpError::e400)
+}
+
+fn parse_cursor(cursor: Option<&str>) -> Result<u64, HttpError> {
+    cursor.map_or(Ok(0), |cursor| {
+        cursor
+            .parse()
+            .context(ErrorKind::InvalidLockCursor(cursor.to_string()))
+            .map_err(HttpError::e400)
+    })
+}
+
+fn parse_limit(limit: &str) -> Result<usize, HttpError> {
+    limit
+        .parse()
+        .context(ErrorKind::InvalidLockLimit(limit.to_string()))
+        .map_err(HttpError::e400)
+}
+
+fn check_limit(limit: Option<usize>) -> Result<usize, HttpError> {
+    match limit {
+        Some(0) => Err(HttpError::e400(ErrorKind::InvalidLockLimit(
+            "0".to_string(),
+        ))),
+        Some(limit) => Ok(min(limit, MAX_LIMIT)),
+        None => Ok(DEFAULT_LIMIT),
+    }
+}
+
+/// If we returned a full page of locks, there might be more: return a cursor to fetch them.
+fn next_cursor(locks: &[LfsLock], limit: usize) -> Option<String> {
+    if locks.len() < limit {
+        return None;
+    }
+    locks.last().map(|lock| lock.id.to_string())
+}
+
+fn to_lfs_lock(lock: LfsLock) -> Result<Lock, Error> {
+    let locked_at = DateTime::from_timestamp(lock.locked_at.timestamp_seconds(), 0)?;
+
+    Ok(Lock {
+        id: lock.id.to_string(),
+        path: lock.path,
+        locked_at: locked_at.as_chrono().to_rfc3339(),
+        owner: Some(LockOwner {
+            name: lock.owner.id_data().to_string(),
+        }),
+    })
+}
+
+fn to_lfs_locks(locks: Vec<LfsLock>) -> Result<Vec<Lock>, HttpError> {
+    locks
+        .into_iter()
+        .map(to_lfs_lock)
+        .collect::<Result<_, _>>()
+        .map_err(HttpError::e500)
+}
+
+async fn read_request<T: DeserializeOwned>(state: &mut State) -> Result<T, HttpError> {
+    let body = Body::take_from(state);
+    let headers = HeaderMap::try_borrow_from(state);
+
+    let body = body
+        .try_concat_body_opt(headers)
+        .map_err(HttpError::e400)?
+        .await
+        .context(ErrorKind::ClientCancelled)
+        .map_err(HttpError::e400)?;
+
+    serde_json::from_slice::<T>(&body)
+        .context(ErrorKind::InvalidLockRequest)
+        .map_err(HttpError::e400)
+}
+
+fn json_response<T: Serialize>(
+    status: StatusCode,
+    res: &T,
+) -> Result<BytesBody<String>, HttpError> {
+    let body = serde_json::to_string(res).map_err(HttpError::e500)?;
+    Ok(BytesBody::new(body, git_lfs_mime()).with_status(status))
+}
+
+async fn create(ctx: &RepositoryRequestContext, path: &str) -> Result<LockCreation, HttpError> {
+    let locks = lock_store(ctx)?;
+    let owner = lock_owner(client_identities(ctx)?)
+        .ok_or_else(|| HttpError::e403(ErrorKind::LockingRequiresIdentity))?;
+    let path = normalize_path(path)?;
+
+    locks
+        .create_lock(ctx.repo.get_repoid(), path, owner)
+        .await
+        .context(ErrorKind::LockStoreFailure)
+        .map_err(HttpError::e500)
+}
+
+async fn list(
+    ctx: &RepositoryRequestContext,
+    query: &ListLocksQuery,
+) -> Result<ListLocksResponse, HttpError> {
+    let locks = lock_store(ctx)?;
+    let repo_id = ctx.repo.get_repoid();
+
+    let id = query.id.as_deref().map(parse_id).transpose()?;
+    let path = query.path.as_deref().map(normalize_path).transpose()?;
+    let after = parse_cursor(query.cursor.as_deref())?;
+    let limit = check_limit(query.limit.as_deref().map(parse_limit).transpose()?)?;
+
+    // A lookup by id or path matches at most one lock, so there is nothing to paginate.
+    let (found, next_cursor) = match (id, path) {
+        (Some(id), path) => {
+            let lock = locks
+                .get_lock(repo_id, id)
+                .await
+                .context(ErrorKind::LockStoreFailure)
+                .map_err(HttpError::e500)?;
+            let lock = lock.filter(|lock| path.map_or(true, |path| lock.path == path));
+            (lock.into_iter().collect(), None)
+        }
+        (None, Some(path)) => {
+            let found = locks
+                .get_locks_for_paths(repo_id, &[path])
+                .await
+                .context(ErrorKind::LockStoreFailure)
+                .map_err(HttpError::e500)?;
+            (found, None)
+        }
+        (None, None) => {
+            let found = locks
+                .list_locks(repo_id, after, limit)
+                .await
+                .context(ErrorKind::LockStoreFailure)
+                .map_err(HttpError::e500)?;
+            let cursor = next_cursor(&found, limit);
+            (found, cursor)
+        }
+    };
+
+    Ok(ListLocksResponse {
+        locks: to_lfs_locks(found)?,
+        next_cursor,
+    })
+}
+
+async fn verify(
+    ctx: &RepositoryRequestContext,
+    request: &VerifyLocksRequest,
+) -> Result<VerifyLocksResponse, HttpError> {
+    let locks = lock_store(ctx)?;
+    let identities = client_identities(ctx)?;
+
+    let after = parse_cursor(request.cursor.as_deref())?;
+    let limit = check_limit(request.limit.map(|limit| limit as usize))?;
+
+    let found = locks
+        .list_locks(ctx.repo.get_repoid(), after, limit)
+        .await
+        .context(ErrorKind::LockStoreFailure)
+        .map_err(HttpError::e500)?;
+
+    let next_cursor = next_cursor(&found, limit);
+    let (ours, theirs): (Vec<_>, Vec<_>) = found
+        .into_iter()
+        .partition(|lock| lock.is_owned_by(identities));
+
+    Ok(VerifyLocksResponse {
+        ours: to_lfs_locks(ours)?,
+        theirs: to_lfs_locks(theirs)?,
+        next_cursor,
+    })
+}
+
+async fn unlock(
+    ctx: &RepositoryRequestContext,
+    id: &str,
+    request: &UnlockRequest,
+) -> Result<LockResponse, HttpError> {
+    let locks = lock_store(ctx)?;
+    let identities = client_identities(ctx)?;
+    let repo_id = ctx.repo.get_repoid();
+    let id = parse_id(id)?;
+
+    let lock = locks
+        .get_lock(repo_id, id)
+        .await
+        .context(ErrorKind::LockStoreFailure)
+        .map_err(HttpError::e500)?
+        .ok_or_else(|| HttpError::e404(ErrorKind::LockDoesNotExist(id)))?;
+
+    if !lock.is_owned_by(identities) {
+        if !request.force {
+            return Err(HttpError::e403(ErrorKind::LockNotOwned(
+                id,
+                lock.owner.to_string(),
+            )));
+        }
+
+        let is_admin = ctx
+            .aclchecker
+            .check_set(identities, &[LOCK_ADMIN_ACTION])
+            .await
+            .map_err(HttpError::e500)?;
+        if !is_admin {
+            return Err(HttpError::e403(ErrorKind::LockForceNotPermitted(
+                id,
+                lock.owner.to_string(),
+                LOCK_ADMIN_ACTION,
+            )));
+        }
+    }
+
+    let deleted = locks
+        .delete_lock(repo_id, id)
+        .await
+        .context(ErrorKind::LockStoreFailure)
+        .map_err(HttpError::e500)?;
+
+    // Someone else released this lock while we were checking it.
+    if !deleted {
+        return Err(HttpError::e404(ErrorKind::LockDoesNotExist(id)));
+    }
+
+    Ok(LockResponse {
+        lock: to_lfs_lock(lock).map_err(HttpError::e500)?,
+    })
+}
+
+pub async fn create_lock(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let LocksParams { repository } = state.take();
+
+    let ctx = RepositoryRequestContext::instantiate(state, repository, LfsMethod::Locks).await?;
+    let request: CreateLockRequest = read_request(state).await?;
+
+    match create(&ctx, &request.path).await? {
+        LockCreation::Created(lock) => json_response(
+            StatusCode::CREATED,
+            &LockResponse {
+                lock: to_lfs_lock(lock).map_err(HttpError::e500)?,
+            },
+        ),
+        LockCreation::AlreadyLocked(lock) => json_response(
+            StatusCode::CONFLICT,
+            &LockConflict {
+                lock: to_lfs_lock(lock).map_err(HttpError::e500)?,
+                message: "already created lock".to_string(),
+            },
+        ),
+    }
+}
+
+pub async fn list_locks(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let LocksParams { repository } = state.take();
+    let query: ListLocksQuery = state.take();
+
+    let ctx = RepositoryRequestContext::instantiate(state, repository, LfsMethod::Locks).await?;
+
+    json_response(StatusCode::OK, &list(&ctx, &query).await?)
+}
+
+pub async fn verify_locks(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let LocksParams { repository } = state.take();
+
+    let ctx = RepositoryRequestContext::instantiate(state, repository, LfsMethod::Locks).await?;
+    let request: VerifyLocksRequest = read_request(state).await?;
+
+    json_response(StatusCode::OK, &verify(&ctx, &request).await?)
+}
+
+pub async fn unlock_lock(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let UnlockParams { repository, id } = state.take();
+
+    let ctx = RepositoryRequestContext::instantiate(state, repository, LfsMethod::Locks).await?;
+    let request: UnlockRequest = read_request(state).await?;
+
+    json_response(StatusCode::OK, &unlock(&ctx, &id, &request).await?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use fbinit::FacebookInit;
+    use maplit::btreeset;
+    use permission_checker::{ArcPermissionChecker, MononokeIdentity, PermissionCheckerBuilder};
+    use sql_construct::SqlConstruct;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    fn identities(id: &str) -> Result<MononokeIdentitySet, Error> {
+        Ok(btreeset! { MononokeIdentity::from_str(id)? })
+    }
+
+    fn test_ctx(
+        fb: FacebookInit,
+        locks: &SqlLfsLocks,
+        id: &str,
+    ) -> Result<RepositoryRequestContext, Error> {
+        RepositoryRequestContext::test_builder(fb)?
+            .locks(locks.clone())
+            .identities(identities(id)?)
+            .build()
+    }
+
+    fn lock_id(creation: LockCreation) -> u64 {
+        match creation {
+            LockCreation::Created(lock) => lock.id,
+            LockCreation::AlreadyLocked(lock) => panic!("unexpected existing lock: {:?}", lock),
+        }
+    }
+
+    #[fbinit::compat_test]
+    async fn test_locking_disabled(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = RepositoryRequestContext::test_builder(fb)?
+            .identities(identities("USER:alice")?)
+            .build()?;
+
+        let err = create(&ctx, "foo").await.unwrap_err();
+        assert_eq!(err.status_code, StatusCode::NOT_FOUND);
+
+        let err = list(&ctx, &ListLocksQuery::default()).await.unwrap_err();
+        assert_eq!(err.status_code, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_locking_requires_identity(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = RepositoryRequestContext::test_builder(fb)?
+            .locks(SqlLfsLocks::with_sqlite_in_memory()?)
+            .build()?;
+
+        let err = create(&ctx, "foo").await.unwrap_err();
+        assert_eq!(err.status_code, StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_create_and_unlock(fb: FacebookInit) -> Result<(), Error> {
+        let locks = SqlLfsLocks::with_sqlite_in_memory()?;
+        let alice = test_ctx(fb, &locks, "USER:alice")?;
+        let bob = test_ctx(fb, &locks, "USER:bob")?;
+
+        let id = lock_id(create(&alice, "foo/bar.zip").await?);
+        assert!(matches!(
+            create(&bob, "foo/bar.zip").await?,
+            LockCreation::AlreadyLocked(_)
+        ));
+
+        let err = create(&alice, "").await.unwrap_err();
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+
+        // Bob doesn't own this lock, so he needs to force it.
+        let no_force = UnlockRequest {
+            force: false,
+            r#ref: None,
+        };
+        let err = unlock(&bob, &id.to_string(), &no_force).await.unwrap_err();
+        assert_eq!(err.status_code, StatusCode::FORBIDDEN);
+
+        let res = unlock(&alice, &id.to_string(), &no_force).await?;
+        assert_eq!(res.lock.path, "foo/bar.zip");
+        assert_eq!(res.lock.owner.map(|o| o.name), Some("alice".to_string()));
+
+        let err = unlock(&alice, &id.to_string(), &no_force)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_force_unlock(fb: FacebookInit) -> Result<(), Error> {
+        let locks = SqlLfsLocks::with_sqlite_in_memory()?;
+        let aclchecker: ArcPermissionChecker = Arc::from(
+            PermissionCheckerBuilder::whitelist_checker(identities("USER:admin")?),
+        );
+        let alice = test_ctx(fb, &locks, "USER:alice")?;
+        let bob = RepositoryRequestContext::test_builder(fb)?
+            .locks(locks.clone())
+            .aclchecker(aclchecker.clone())
+            .identities(identities("USER:bob")?)
+            .build()?;
+        let admin = RepositoryRequestContext::test_builder(fb)?
+            .locks(locks.clone())
+            .aclchecker(aclchecker)
+            .identities(identities("USER:admin")?)
+            .build()?;
+
+        let id = lock_id(create(&alice, "foo/bar.zip").await?);
+        let force = UnlockRequest {
+            force: true,
+            r#ref: None,
+        };
+
+        // Only lock admins can release other users' locks.
+        let err = unlock(&bob, &id.to_string(), &force).await.unwrap_err();
+        assert_eq!(err.status_code, StatusCode::FORBIDDEN);
+
+        let res = unlock(&admin, &id.to_string(), &force).await?;
+        assert_eq!(res.lock.path, "foo/bar.zip");
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_list_and_verify(fb: FacebookInit) -> Result<(), Error> {
+        let locks = SqlLfsLocks::with_sqlite_in_memory()?;
+        let alice = test_ctx(fb, &locks, "USER:alice")?;
+        let bob = test_ctx(fb, &locks, "USER:bob")?;
+
+        create(&alice, "a").await?;
+        let b = lock_id(create(&bob, "b").await?);
+        create(&alice, "c").await?;
+
+        let query = ListLocksQuery {
+            limit: Some("2".to_string()),
+            ..Default::default()
+        };
+        let res = list(&bob, &query).await?;
+        let paths: Vec<_> = res.locks.iter().map(|l| l.path.as_str()).collect();
+        assert_eq!(paths, vec!["a", "b"]);
+        assert_eq!(res.next_cursor, Some(b.to_string()));
+
+        let query = ListLocksQuery {
+            cursor: res.next_cursor,
+            limit: Some("2".to_string()),
+            ..Default::default()
+        };
+        let res = list(&bob, &query).await?;
+        let paths: Vec<_> = res.locks.iter().map(|l| l.path.as_str()).collect();
+        assert_eq!(paths, vec!["c"]);
+        assert_eq!(res.next_cursor, None);
+
+        let query = ListLocksQuery {
+            path: Some("b".to_string()),
+            ..Default::default()
+        };
+        let res = list(&alice, &query).await?;
+        assert_eq!(res.locks.len(), 1);
+        assert_eq!(res.locks[0].id, b.to_string());
+
+        let res = verify(
+            &alice,
+            &VerifyLocksRequest {
+                cursor: None,
+                limit: None,
+                r#ref: None,
+            },
+        )
+        .await?;
+        let ours: Vec<_> = res.ours.iter().map(|l| l.path.as_str()).collect();
+        let theirs: Vec<_> = res.theirs.iter().map(|l| l.path.as_str()).collect();
+        assert_eq!(ours, vec!["a", "c"]);
+        assert_eq!(theirs, vec!["b"]);
+
+        Ok(())
+    }
+}
@@ -30,14 +30,16 @@ use std::str::FromStr;
 use std::sync::{atomic::AtomicBool, atomic::Ordering, Arc};
 use tokio::net::TcpListener;
 
-use blobrepo::BlobRepo;
 use blobrepo_factory::BlobrepoBuilder;
 use cmdlib::{
     args::{self, get_config_handle},
     helpers::serve_forever,
     monitoring::{start_fb303_server, AliveService},
 };
+use lfs_locks::SqlLfsLocks;
+use lfs_upload_sessions::SqlLfsUploadSessions;
 use metaconfig_parser::RepoConfigs;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
 
 use crate::lfs_server_context::{LfsServerContext, ServerUris};
 use crate::middleware::{
@@ -52,7 +54,10 @@ mod config;
 mod download;
 mod errors;
 mod lfs_server_context;
+mod locks;
 mod middleware;
+mod quota;
+mod resumable;
 mod service;
 mod upload;
 #[macro_use]
@@ -278,29 +283,49 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
                     if let Some(test_checker) = test_acl_checker {
                         Ok(test_checker.clone())
                     } else {
-                        Ok(ArcPermissionChecker::from(
-                            match (disable_acl_checker, hipster_acl) {
-                                (true, _) | (false, None) => {
-                                    PermissionCheckerBuilder::always_allow()
-                                }
-                                (_, Some(acl)) => {
-                                    info!(
-                                        logger,
-                                        "{}: Actions will be checked against {} ACL", name, acl
-                                    );
-                                    PermissionCheckerBuilder::acl_for_repo(fb, &acl).await?
-                                }
-                            },
-                        ))
+                        Ok(ArcPermissionChecker::from(if disable_acl_checker {
+                            PermissionCheckerBuilder::always_allow()
+                        } else {
+                            if let Some(acl) = &hipster_acl {
+                                info!(
+                                    logger,
+                                    "{}: Actions will be checked against {} ACL", name, acl
+                                );
+                            }
+                            PermissionCheckerBuilder::for_repo(fb, &name, hipster_acl.as_deref())
+                                .await?
+                        }))
+                    }
+                };
+
+                let locking_enabled = config.lfs.locking_enabled;
+                let db_config = config.storage_config.metadata.clone();
+                let locks = async {
+                    if locking_enabled {
+                        let locks = SqlLfsLocks::with_metadata_database_config(
+                            fb,
+                            &db_config,
+                            mysql_options,
+                            readonly_storage.0,
+                        )
+                        .await?;
+                        Ok(Some(locks))
+                    } else {
+                        Ok(None)
                     }
                 };
 
-                let (repo, aclchecker) = try_join!(builder.build(), aclchecker)?;
+                let upload_sessions = SqlLfsUploadSessions::with_metadata_database_config(
+                    fb,
+                    &db_config,
+                    mysql_options,
+                    readonly_storage.0,
+                );
+
+                let (repo, aclchecker, locks, upload_sessions) =
+                    try_join!(builder.build(), aclchecker, locks, upload_sessions)?;
 
-                Result::<(String, (BlobRepo, ArcPermissionChecker)), Error>::Ok((
-                    name,
-                    (repo, aclchecker),
-                ))
+                Result::<_, Error>::Ok((name, (repo, aclchecker, locks, upload_sessions)))
             }
         });
 
@@ -20,9 +20,11 @@ define_stats! {
     failure_4xx: dynamic_timeseries("{}.failure_4xx", (repo_and_method: String); Rate, Sum),
     failure_5xx: dynamic_timeseries("{}.failure_5xx", (repo_and_method: String); Rate, Sum),
     upload_duration: dynamic_histogram("{}.upload_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    upload_resumable_duration: dynamic_histogram("{}.upload_resumable_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     download_duration: dynamic_histogram("{}.download_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     download_sha256_duration: dynamic_histogram("{}.download_sha256_ms", (repo: String); 100, 0, 5000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     batch_duration: dynamic_histogram("{}.batch_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
+    locks_duration: dynamic_histogram("{}.locks_ms", (repo: String); 10, 0, 500, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
     response_bytes_sent: dynamic_histogram("{}.response_bytes_sent", (repo_and_method: String); 1_500_000, 0, 150_000_000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
 }
 
@@ -41,6 +43,8 @@ fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
             LfsMethod::Upload => {
                 STATS::upload_duration.add_value(duration.as_millis_unchecked() as i64, (repo,))
             }
+            LfsMethod::UploadResumable => STATS::upload_resumable_duration
+                .add_value(duration.as_millis_unchecked() as i64, (repo,)),
             LfsMethod::Download => {
                 STATS::download_duration.add_value(duration.as_millis_unchecked() as i64, (repo,))
             }
@@ -49,6 +53,9 @@ fn log_stats(state: &mut State, status: StatusCode) -> Option<()> {
             LfsMethod::Batch => {
                 STATS::batch_duration.add_value(duration.as_millis_unchecked() as i64, (repo,))
             }
+            LfsMethod::Locks => {
+                STATS::locks_duration.add_value(duration.as_millis_unchecked() as i64, (repo,))
+            }
         }
 
         STATS::requests.add_value(1, (repo_and_method.clone(),));
@@ -32,18 +32,22 @@ type PostRequestCallback =
 #[derive(Copy, Clone)]
 pub enum LfsMethod {
     Upload,
+    UploadResumable,
     Download,
     DownloadSha256,
     Batch,
+    Locks,
 }
 
 impl fmt::Display for LfsMethod {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let name = match self {
             Self::Upload => "upload",
+            Self::UploadResumable => "upload_resumable",
             Self::Download => "download",
             Self::DownloadSha256 => "download_sha256",
             Self::Batch => "batch",
+            Self::Locks => "locks",
         };
         write!(f, "{}", name)
     }
@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Upload quotas. Quotas limit the size of objects that can be uploaded and how many bytes can be
+//! uploaded per day, for a repository as a whole or for individual clients. Usage is tracked in
+//! time window counters. In fbcode builds those are global, so usage is shared by all the tasks
+//! serving a repository. Elsewhere they only count what was uploaded to this process, so each
+//! task enforces the daily quotas separately.
+
+use std::cmp::min;
+
+use futures::future::join_all;
+use gotham_ext::error::HttpError;
+use load_limiter::Metric;
+use permission_checker::MononokeIdentitySet;
+use slog::warn;
+use time_window_counter::{BoxGlobalTimeWindowCounter, GlobalTimeWindowCounterBuilder};
+
+use crate::config::UploadQuota;
+use crate::errors::ErrorKind;
+use crate::lfs_server_context::RepositoryRequestContext;
+
+const UPLOAD_QUOTA_CATEGORY: &str = "mononoke_lfs_upload_quota";
+const TIME_WINDOW_MIN: u32 = 60;
+const ONE_DAY: u32 = 24 * 60 * 60;
+
+fn min_limit(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(min(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Find the quotas that apply to uploads to this repository by a client presenting these
+/// identities. Each is returned with the key of the counter tracking usage against it: quotas
+/// scoped to client identities are tracked separately for each identity they match.
+fn applicable_quotas<'a>(
+    quotas: &'a [UploadQuota],
+    repository: &str,
+    identities: Option<&MononokeIdentitySet>,
+) -> Vec<(&'a UploadQuota, String)> {
+    let mut applicable = vec![];
+
+    for quota in quotas {
+        match quota.repository() {
+            Some(quota_repository) if quota_repository != repository => continue,
+            _ => {}
+        }
+
+        if quota.client_identities().is_empty() {
+            applicable.push((quota, repository.to_string()));
+            continue;
+        }
+
+        let identities = match identities {
+            Some(identities) => identities,
+            None => continue,
+        };
+
+        for identity in quota.client_identities() {
+            if identities.contains(identity) {
+                applicable.push((quota, format!("{}.{}", repository, identity)));
+            }
+        }
+    }
+
+    applicable
+}
+
+fn usage_counter(ctx: &RepositoryRequestContext, key: String) -> BoxGlobalTimeWindowCounter {
+    GlobalTimeWindowCounterBuilder::build(
+        ctx.ctx.fb,
+        UPLOAD_QUOTA_CATEGORY,
+        key,
+        TIME_WINDOW_MIN,
+        ONE_DAY,
+    )
+}
+
+/// What a client may still upload: the largest object it may upload, and how many more bytes it
+/// may upload today.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct UploadBudget {
+    max_object_size: Option<u64>,
+    remaining_bytes: Option<u64>,
+}
+
+impl UploadBudget {
+    pub fn new(max_object_size: Option<u64>, remaining_bytes: Option<u64>) -> Self {
+        Self {
+            max_object_size,
+            remaining_bytes,
+        }
+    }
+
+    /// Reserve room for an object of this size, or explain why it cannot be uploaded.
+    pub fn reserve(&mut self, size: u64) -> Result<(), HttpError> {
+        if let Some(max_object_size) = self.max_object_size {
+            if size > max_object_size {
+                return Err(HttpError::e422(ErrorKind::UploadTooLarge(
+                    size,
+                    max_object_size,
+                )));
+            }
+        }
+
+        if let Some(remaining_bytes) = self.remaining_bytes {
+            if size > remaining_bytes {
+                return Err(HttpError::e429(ErrorKind::UploadQuotaExceeded(
+                    size,
+                    remaining_bytes,
+                )));
+            }
+            self.remaining_bytes = Some(remaining_bytes - size);
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute what this client may upload to this repository. If usage can't be loaded, the quotas
+/// it is for are not enforced: we'd rather let uploads through than fail them all.
+pub async fn upload_budget(ctx: &RepositoryRequestContext) -> UploadBudget {
+    let quotas = applicable_quotas(
+        ctx.config.upload_quotas(),
+        &ctx.uri_builder.repository,
+        ctx.identities.as_ref(),
+    );
+
+    let max_object_size = quotas.iter().fold(ctx.max_upload_size(), |acc, (q, _)| {
+        min_limit(acc, q.max_object_size())
+    });
+
+    let remaining = join_all(quotas.into_iter().filter_map(|(quota, key)| {
+        let max_bytes_per_day = quota.max_bytes_per_day()?;
+        let counter = usage_counter(ctx, key.clone());
+
+        Some(async move {
+            match counter.get(ONE_DAY).await {
+                Ok(used) => Some(max_bytes_per_day.saturating_sub(used as u64)),
+                Err(e) => {
+                    warn!(
+                        ctx.logger(),
+                        "Could not load upload quota usage for {}: {:?}", key, e
+                    );
+                    None
+                }
+            }
+        })
+    }))
+    .await;
+
+    let remaining_bytes = remaining.into_iter().fold(None, min_limit);
+
+    UploadBudget::new(max_object_size, remaining_bytes)
+}
+
+/// Account for an object that was uploaded against the quotas that apply to it.
+pub fn record_upload(ctx: &RepositoryRequestContext, size: u64) {
+    ctx.ctx
+        .session()
+        .bump_load(Metric::IngressBlobstoreBytes, size as f64);
+
+    let quotas = applicable_quotas(
+        ctx.config.upload_quotas(),
+        &ctx.uri_builder.repository,
+        ctx.identities.as_ref(),
+    );
+
+    let mut keys: Vec<_> = quotas
+        .into_iter()
+        .filter(|(quota, _)| quota.max_bytes_per_day().is_some())
+        .map(|(_, key)| key)
+        .collect();
+
+    // Quotas with the same scope share a counter: only count the upload once.
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        usage_counter(ctx, key).bump(size as f64);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    use anyhow::Error;
+    use hyper::StatusCode;
+    use maplit::btreeset;
+    use permission_checker::MononokeIdentity;
+
+    use crate::config::RawUploadQuota;
+
+    fn quota(repository: Option<&str>, client_identities: &[&str]) -> Result<UploadQuota, Error> {
+        UploadQuota::try_from(&RawUploadQuota {
+            repository: repository.map(|r| r.to_string()),
+            client_identities: client_identities.iter().map(|i| i.to_string()).collect(),
+            max_bytes_per_day: Some(100),
+            max_object_size: None,
+        })
+    }
+
+    #[test]
+    fn test_applicable_quotas() -> Result<(), Error> {
+        let quotas = vec![
+            quota(None, &[])?,
+            quota(Some("repo1"), &[])?,
+            quota(Some("repo2"), &[])?,
+            quota(None, &["USER:alice", "USER:bob"])?,
+        ];
+
+        let keys = |repository, identities| {
+            applicable_quotas(&quotas, repository, identities)
+                .into_iter()
+                .map(|(_, key)| key)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys("repo1", None), vec!["repo1", "repo1"]);
+        assert_eq!(keys("repo3", None), vec!["repo3"]);
+
+        let alice = btreeset! { MononokeIdentity::from_str("USER:alice")? };
+        assert_eq!(
+            keys("repo2", Some(&alice)),
+            vec!["repo2", "repo2", "repo2.USER:alice"]
+        );
+
+        let carol = btreeset! { MononokeIdentity::from_str("USER:carol")? };
+        assert_eq!(keys("repo3", Some(&carol)), vec!["repo3"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut budget = UploadBudget::new(Some(10), Some(15));
+
+        let too_large = budget.reserve(11).unwrap_err();
+        assert_eq!(too_large.status_code, StatusCode::UNPROCESSABLE_ENTITY);
+
+        assert!(budget.reserve(10).is_ok());
+
+        let exceeded = budget.reserve(6).unwrap_err();
+        assert_eq!(exceeded.status_code, StatusCode::TOO_MANY_REQUESTS);
+
+        assert!(budget.reserve(5).is_ok());
+        assert_eq!(budget, UploadBudget::new(Some(10), Some(0)));
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let mut budget = UploadBudget::default();
+        assert!(budget.reserve(u64::max_value()).is_ok());
+        assert_eq!(budget, UploadBudget::default());
+    }
+}
@@ -0,0 +1,374 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Resumable uploads, for clients that negotiated the `tus` transfer adapter in their batch
+//! request. Those follow the core tus.io protocol: the client finds out how much of an object we
+//! already have with a HEAD request, then sends the rest with PATCH requests that start at that
+//! offset. Data is stored as Filestore chunks as it is received, so that an interrupted request
+//! only loses the chunk it was in the middle of.
+//!
+//! Progress is recorded in an upload session in SQL, keyed by the object being uploaded. Since
+//! objects are content-addressed, two clients uploading the same object write the same data, so
+//! sharing sessions between them is fine: each chunk is only recorded if no other upload recorded
+//! one at its offset first, and the one that loses is told its offset is stale. Sessions are
+//! deleted once the object is stored.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Error};
+use bytes::Bytes;
+use futures::{
+    channel::mpsc,
+    compat::{Future01CompatExt, Stream01CompatExt},
+    future, StreamExt, TryStreamExt,
+};
+use gotham::state::{FromState, State};
+use gotham_derive::{StateData, StaticResponseExtender};
+use gotham_ext::{
+    error::HttpError,
+    response::{ResponseContentLength, TryIntoResponse},
+};
+use http::header::{HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{Body, Response, StatusCode};
+use serde::Deserialize;
+use stats::prelude::*;
+
+use filestore::{Alias, FetchKey, StoreRequest};
+use lfs_upload_sessions::UploadSession;
+use mononoke_types::{hash::Sha256, ContentChunkPointer};
+
+use crate::errors::ErrorKind;
+use crate::lfs_server_context::RepositoryRequestContext;
+use crate::middleware::{LfsMethod, ScubaKey, ScubaMiddlewareState};
+use crate::quota::{record_upload, upload_budget};
+use crate::upload::{store_error, upstream_upload};
+
+define_stats! {
+    prefix ="mononoke.lfs.upload_resumable";
+    resumed_uploads: timeseries(Rate, Sum),
+    completed_uploads: timeseries(Rate, Sum),
+    invalid_uploads: timeseries(Rate, Sum),
+}
+
+const TUS_RESUMABLE: &str = "Tus-Resumable";
+const TUS_VERSION: &str = "1.0.0";
+const UPLOAD_OFFSET: &str = "Upload-Offset";
+const UPLOAD_LENGTH: &str = "Upload-Length";
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+// Small buffer for streaming uploaded objects to upstream.
+const BUFFER_SIZE: usize = 5;
+
+#[derive(Deserialize, StateData, StaticResponseExtender)]
+pub struct ResumableUploadParams {
+    repository: String,
+    oid: String,
+    size: String,
+}
+
+async fn load_session(
+    ctx: &RepositoryRequestContext,
+    oid: &Sha256,
+    size: u64,
+) -> Result<UploadSession, Error> {
+    ctx.upload_sessions
+        .get_session(ctx.repo.get_repoid(), oid, size)
+        .await
+        .context(ErrorKind::UploadSessionLoadFailure)
+        .map_err(Error::from)
+}
+
+/// Record a chunk received at `offset`. Returns false if another upload of the same object
+/// recorded a chunk there first.
+async fn save_chunk(
+    ctx: &RepositoryRequestContext,
+    oid: &Sha256,
+    size: u64,
+    offset: u64,
+    chunk: &ContentChunkPointer,
+) -> Result<bool, Error> {
+    ctx.upload_sessions
+        .add_chunk(ctx.repo.get_repoid(), oid, size, offset, chunk)
+        .await
+        .context(ErrorKind::UploadSessionSaveFailure)
+        .map_err(Error::from)
+}
+
+async fn delete_session(
+    ctx: &RepositoryRequestContext,
+    oid: &Sha256,
+    size: u64,
+) -> Result<(), Error> {
+    ctx.upload_sessions
+        .delete_session(ctx.repo.get_repoid(), oid, size)
+        .await
+        .context(ErrorKind::UploadSessionSaveFailure)
+        .map_err(Error::from)
+}
+
+/// Response to tus requests, which carry their information in headers.
+struct TusResponse {
+    status: StatusCode,
+    offset: u64,
+    length: Option<u64>,
+}
+
+impl TryIntoResponse for TusResponse {
+    fn try_into_response(self, state: &mut State) -> Result<Response<Body>, Error> {
+        state.put(ResponseContentLength(0));
+
+        let mut res = Response::builder()
+            .status(self.status)
+            .header(TUS_RESUMABLE, TUS_VERSION)
+            .header(UPLOAD_OFFSET, self.offset)
+            .header(CACHE_CONTROL, "no-store")
+            .header(CONTENT_LENGTH, 0);
+
+        if let Some(length) = self.length {
+            res = res.header(UPLOAD_LENGTH, length);
+        }
+
+        res.body(Body::empty()).map_err(Error::from)
+    }
+}
+
+async fn instantiate(
+    state: &mut State,
+) -> Result<(RepositoryRequestContext, Sha256, u64), HttpError> {
+    let ResumableUploadParams {
+        repository,
+        oid,
+        size,
+    } = state.take();
+
+    let ctx = RepositoryRequestContext::instantiate(state, repository, LfsMethod::UploadResumable)
+        .await?;
+
+    let oid = Sha256::from_str(&oid).map_err(HttpError::e400)?;
+    let size = size.parse().map_err(Error::from).map_err(HttpError::e400)?;
+
+    Ok((ctx, oid, size))
+}
+
+async fn object_exists(ctx: &RepositoryRequestContext, oid: Sha256) -> Result<bool, Error> {
+    filestore::exists(
+        ctx.repo.blobstore(),
+        ctx.ctx.clone(),
+        &FetchKey::Aliased(Alias::Sha256(oid)),
+    )
+    .compat()
+    .await
+    .context(ErrorKind::FilestoreReadFailure)
+    .map_err(Error::from)
+}
+
+/// Tell the client where to resume uploading from. Objects that already exist are reported as
+/// fully uploaded.
+pub async fn upload_offset(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let (ctx, oid, size) = instantiate(state).await?;
+
+    let offset = if object_exists(&ctx, oid).await.map_err(HttpError::e500)? {
+        size
+    } else {
+        load_session(&ctx, &oid, size)
+            .await
+            .map_err(HttpError::e500)?
+            .offset()
+    };
+
+    Ok(TusResponse {
+        status: StatusCode::OK,
+        offset,
+        length: Some(size),
+    })
+}
+
+/// Receive more of an object, starting at the current offset of its upload. Once all of it has
+/// been received, it is stored in the Filestore (checking its SHA-256) and forwarded to upstream.
+pub async fn upload_resume(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
+    let (ctx, oid, size) = instantiate(state).await?;
+
+    let headers = HeaderMap::borrow_from(state);
+
+    if headers.get(CONTENT_TYPE) != Some(&HeaderValue::from_static(OFFSET_OCTET_STREAM)) {
+        return Err(HttpError::e415(ErrorKind::InvalidResumableContentType(
+            OFFSET_OCTET_STREAM,
+        )));
+    }
+
+    let offset: u64 = headers
+        .get(UPLOAD_OFFSET)
+        .and_then(|offset| offset.to_str().ok())
+        .and_then(|offset| offset.parse().ok())
+        .ok_or_else(|| HttpError::e400(ErrorKind::InvalidUploadOffset))?;
+
+    let session = load_session(&ctx, &oid, size)
+        .await
+        .map_err(HttpError::e500)?;
+
+    if offset != session.offset() {
+        return Err(HttpError::e409(ErrorKind::UploadOffsetMismatch(
+            offset,
+            session.offset(),
+        )));
+    }
+
+    // The quota is checked once, when the session starts. Resuming it must not count the object
+    // against the quota again, nor fail because of what other uploads used since.
+    if offset == 0 {
+        upload_budget(&ctx).await.reserve(size)?;
+    } else {
+        STATS::resumed_uploads.add_value(1);
+    }
+
+    let mut received: u64 = 0;
+
+    let data = Body::take_from(state)
+        .map_err(|_| Error::from(ErrorKind::ClientCancelled))
+        .and_then(|chunk| {
+            received += chunk.len() as u64;
+            let res = if offset + received > size {
+                Err(ErrorKind::UploadExceedsSize(size).into())
+            } else {
+                Ok(chunk)
+            };
+            future::ready(res)
+        });
+
+    let mut chunks = filestore::store_partial(
+        ctx.repo.get_blobstore(),
+        ctx.repo.filestore_config(),
+        ctx.ctx.clone(),
+        data.compat(),
+    )
+    .compat();
+
+    // Record each chunk as soon as it is stored, so the client can resume after the ones we have
+    // even if the request fails.
+    let mut current = offset;
+    let mut res = Ok(());
+    while let Some(chunk) = chunks.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                res = Err(store_partial_error(e));
+                break;
+            }
+        };
+
+        match save_chunk(&ctx, &oid, size, current, &chunk).await {
+            Ok(true) => current += chunk.size(),
+            Ok(false) => {
+                // Another upload of this object got there first: the client has to resume from
+                // wherever that one got to.
+                res = match load_session(&ctx, &oid, size).await {
+                    Ok(session) => Err(HttpError::e409(ErrorKind::UploadOffsetMismatch(
+                        current,
+                        session.offset(),
+                    ))),
+                    Err(e) => Err(HttpError::e500(e)),
+                };
+                break;
+            }
+            Err(e) => {
+                res = Err(HttpError::e500(e));
+                break;
+            }
+        }
+    }
+    drop(chunks);
+
+    ScubaMiddlewareState::try_borrow_add(state, ScubaKey::RequestBytesReceived, received);
+
+    res?;
+
+    if current == size {
+        let session = load_session(&ctx, &oid, size)
+            .await
+            .map_err(HttpError::e500)?;
+        finish_upload(&ctx, oid, size, session).await?;
+    }
+
+    Ok(TusResponse {
+        status: StatusCode::NO_CONTENT,
+        offset: current,
+        length: None,
+    })
+}
+
+/// Errors from the client's data (it went away, or sent too much) are its fault. Anything else
+/// means we failed to store what it sent.
+fn store_partial_error(e: Error) -> HttpError {
+    if e.downcast_ref::<ErrorKind>().is_some() {
+        HttpError::e400(e)
+    } else {
+        HttpError::e500(e.context(ErrorKind::FilestoreWriteFailure))
+    }
+}
+
+async fn finish_upload(
+    ctx: &RepositoryRequestContext,
+    oid: Sha256,
+    size: u64,
+    session: UploadSession,
+) -> Result<(), HttpError> {
+    let chunks = session.into_chunks();
+
+    let res = filestore::store_from_chunks(
+        ctx.repo.get_blobstore(),
+        ctx.repo.filestore_config(),
+        ctx.ctx.clone(),
+        &StoreRequest::with_sha256(size, oid),
+        chunks,
+    )
+    .compat()
+    .await;
+
+    if let Err(e) = res {
+        if e.downcast_ref::<filestore::ErrorKind>().is_none() {
+            return Err(HttpError::e500(e.context(ErrorKind::FilestoreWriteFailure)));
+        }
+
+        // The data we received does not match the object: start over.
+        STATS::invalid_uploads.add_value(1);
+        delete_session(ctx, &oid, size)
+            .await
+            .map_err(HttpError::e500)?;
+        return Err(store_error(ctx, e).await);
+    }
+
+    STATS::completed_uploads.add_value(1);
+    delete_session(ctx, &oid, size)
+        .await
+        .map_err(HttpError::e500)?;
+    record_upload(ctx, size);
+
+    // The client only uploaded the object to us, so pass it on to upstream if needed.
+    let fetched = filestore::fetch(
+        ctx.repo.blobstore(),
+        ctx.ctx.clone(),
+        &FetchKey::Aliased(Alias::Sha256(oid)),
+    )
+    .compat()
+    .await
+    .context(ErrorKind::FilestoreReadFailure)
+    .map_err(HttpError::e500)?
+    .ok_or_else(|| {
+        HttpError::e500(ErrorKind::ObjectDoesNotExist(FetchKey::Aliased(
+            Alias::Sha256(oid),
+        )))
+    })?;
+
+    // Hyper needs the body it sends upstream to be Sync, which the Filestore stream is not, so
+    // forward it through a channel.
+    let (sender, receiver) = mpsc::channel(BUFFER_SIZE);
+    tokio::spawn(fetched.compat().map(Ok).forward(sender));
+
+    upstream_upload(ctx, oid, size, receiver)
+        .await
+        .map_err(HttpError::e500)
+}
@@ -25,12 +25,14 @@ use std::pin::Pin;
 use crate::batch;
 use crate::download;
 use crate::lfs_server_context::LfsServerContext;
+use crate::locks;
+use crate::resumable;
 use crate::upload;
 
 use super::middleware::ThrottleMiddleware;
 use super::util::build_response;
 
-// These 3 methods are wrappers to go from async fn's to the implementations Gotham expects,
+// These methods are wrappers to go from async fn's to the implementations Gotham expects,
 // as well as creating HTTP responses using build_response().
 fn batch_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
     async move {
@@ -64,6 +66,54 @@ fn upload_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
     .boxed()
 }
 
+fn upload_offset_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = resumable::upload_offset(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+fn upload_resume_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = resumable::upload_resume(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+fn create_lock_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = locks::create_lock(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+fn list_locks_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = locks::list_locks(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+fn verify_locks_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = locks::verify_locks(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
+fn unlock_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+    async move {
+        let res = locks::unlock_lock(&mut state).await;
+        build_response(res, state)
+    }
+    .boxed()
+}
+
 fn health_handler(state: State) -> (State, &'static str) {
     let lfs_ctx = LfsServerContext::borrow_from(&state);
     let res = if lfs_ctx.will_exit() {
@@ -114,6 +164,37 @@ pub fn build_router(fb: FacebookInit, lfs_ctx: LfsServerContext) -> Router {
             .with_path_extractor::<upload::UploadParams>()
             .to(upload_handler);
 
+        route
+            .head("/:repository/upload_resumable/:oid/:size")
+            .with_path_extractor::<resumable::ResumableUploadParams>()
+            .to(upload_offset_handler);
+
+        route
+            .patch("/:repository/upload_resumable/:oid/:size")
+            .with_path_extractor::<resumable::ResumableUploadParams>()
+            .to(upload_resume_handler);
+
+        route
+            .post("/:repository/locks")
+            .with_path_extractor::<locks::LocksParams>()
+            .to(create_lock_handler);
+
+        route
+            .get("/:repository/locks")
+            .with_path_extractor::<locks::LocksParams>()
+            .with_query_string_extractor::<locks::ListLocksQuery>()
+            .to(list_locks_handler);
+
+        route
+            .post("/:repository/locks/verify")
+            .with_path_extractor::<locks::LocksParams>()
+            .to(verify_locks_handler);
+
+        route
+            .post("/:repository/locks/:id/unlock")
+            .with_path_extractor::<locks::UnlockParams>()
+            .to(unlock_handler);
+
         route.get("/health_check").to(health_handler);
         route.get("/config").to(config_handler);
     })
@@ -44,7 +44,11 @@ pub fn http_error_to_handler_error(
     error: HttpError,
     mut state: State,
 ) -> Result<(State, Response<Body>), (State, HandlerError)> {
-    let HttpError { error, status_code } = error;
+    let HttpError {
+        error,
+        status_code,
+        headers,
+    } = error;
 
     let error_message = iter::once(error.to_string())
         .chain(error.chain().skip(1).map(|c| c.to_string()))
@@ -63,7 +67,8 @@ pub fn http_error_to_handler_error(
     // Bail if we can't convert the response to json.
     match serde_json::to_string(&res) {
         Ok(res) => {
-            let res = create_response(&state, status_code, git_lfs_mime(), res);
+            let mut res = create_response(&state, status_code, git_lfs_mime(), res);
+            res.headers_mut().extend(headers);
             Ok((state, res))
         }
         Err(error) => Err((state, error.into_handler_error())),
@@ -21,7 +21,7 @@ use hyper::{Body, Request};
 use serde::Deserialize;
 use stats::prelude::*;
 
-use filestore::StoreRequest;
+use filestore::{OrphanedChunks, StoreRequest};
 use gotham_ext::{
     error::HttpError,
     response::{EmptyBody, TryIntoResponse},
@@ -30,11 +30,14 @@ use lfs_protocol::{
     ObjectAction, ObjectStatus, Operation, RequestBatch, RequestObject, ResponseBatch,
     Sha256 as LfsSha256, Transfer,
 };
-use mononoke_types::hash::Sha256;
+use mononoke_types::{hash::Sha256, MononokeId};
+use scuba_ext::ScubaSampleBuilderExt;
+use slog::warn;
 
 use crate::errors::ErrorKind;
 use crate::lfs_server_context::RepositoryRequestContext;
 use crate::middleware::{LfsMethod, ScubaKey, ScubaMiddlewareState};
+use crate::quota::{record_upload, upload_budget};
 
 define_stats! {
     prefix ="mononoke.lfs.upload";
@@ -42,6 +45,8 @@ define_stats! {
     upstream_success: timeseries(Rate, Sum),
     internal_uploads: timeseries(Rate, Sum),
     internal_success: timeseries(Rate, Sum),
+    verification_failures: timeseries(Rate, Sum),
+    orphaned_chunks: timeseries(Rate, Sum),
     size_bytes: histogram(1_500_000, 0, 150_000_000, Average, Sum, Count; P 5; P 25; P 50; P 75; P 95; P 97; P 99),
 }
 
@@ -64,7 +69,7 @@ where
     Ok(data.for_each(|_| ready(())).await)
 }
 
-async fn upstream_upload<S>(
+pub(crate) async fn upstream_upload<S>(
     ctx: &RepositoryRequestContext,
     oid: Sha256,
     size: u64,
@@ -110,7 +115,7 @@ where
                 } => Ok(actions),
                 _ => Err(ErrorKind::UpstreamInvalidObject(o).into()),
             }),
-        Transfer::Unknown => Err(ErrorKind::UpstreamInvalidTransfer.into()),
+        Transfer::Tus | Transfer::Unknown => Err(ErrorKind::UpstreamInvalidTransfer.into()),
     };
 
     if let Some(action) = actions?.remove(&Operation::Upload) {
@@ -138,6 +143,45 @@ where
     discard_stream(data).await
 }
 
+/// Turn an error storing uploaded data into a response. If the data did not match the object it
+/// was uploaded as, tell the client why. Any chunks the Filestore stored for it before finding out
+/// might be unreferenced: record them, so that the walker's gc removes them once it has checked
+/// nothing else refers to them.
+pub(crate) async fn store_error(ctx: &RepositoryRequestContext, e: Error) -> HttpError {
+    let verification_error = match e.downcast_ref::<filestore::ErrorKind>() {
+        Some(verification_error) => verification_error.to_string(),
+        None => return HttpError::e500(e),
+    };
+
+    STATS::verification_failures.add_value(1);
+
+    if let Some(OrphanedChunks(chunks)) = e.downcast_ref::<OrphanedChunks>() {
+        STATS::orphaned_chunks.add_value(chunks.len() as i64);
+
+        let keys: Vec<String> = chunks.iter().map(|c| c.blobstore_key()).collect();
+        warn!(
+            ctx.logger(),
+            "Upload of invalid data orphaned {} chunks: {:?}",
+            keys.len(),
+            keys
+        );
+
+        let mut scuba = ctx.ctx.scuba().clone();
+        scuba.add("orphaned_chunks", keys);
+        scuba.log_with_msg("Orphaned chunks", Some(verification_error.clone()));
+
+        if let Err(e) = ctx
+            .upload_sessions
+            .add_orphaned_chunks(ctx.repo.get_repoid(), chunks)
+            .await
+        {
+            warn!(ctx.logger(), "Could not record orphaned chunks: {:?}", e);
+        }
+    }
+
+    HttpError::e422(ErrorKind::UploadVerificationFailed(verification_error))
+}
+
 pub async fn upload(state: &mut State) -> Result<impl TryIntoResponse, HttpError> {
     let UploadParams {
         repository,
@@ -152,14 +196,7 @@ pub async fn upload(state: &mut State) -> Result<impl TryIntoResponse, HttpError
     let size = size.parse().map_err(Error::from).map_err(HttpError::e400)?;
     STATS::size_bytes.add_value(size as i64);
 
-    if let Some(max_upload_size) = ctx.max_upload_size() {
-        if size > max_upload_size {
-            Err(HttpError::e400(ErrorKind::UploadTooLarge(
-                size,
-                max_upload_size,
-            )))?;
-        }
-    }
+    upload_budget(&ctx).await.reserve(size)?;
 
     ScubaMiddlewareState::try_borrow_add(state, ScubaKey::RequestContentLength, size);
 
@@ -193,6 +230,7 @@ pub async fn upload(state: &mut State) -> Result<impl TryIntoResponse, HttpError
 
         if !res.is_err() {
             STATS::internal_success.add_value(1);
+            record_upload(&ctx, size);
         }
 
         res
@@ -226,9 +264,12 @@ pub async fn upload(state: &mut State) -> Result<impl TryIntoResponse, HttpError
         Ok(())
     };
 
-    let res = try_join!(internal_upload, upstream_upload, consume_stream).map_err(HttpError::e500);
+    let res = match try_join!(internal_upload, upstream_upload, consume_stream) {
+        Ok(_) => Ok(EmptyBody::new()),
+        Err(e) => Err(store_error(&ctx, e).await),
+    };
 
     ScubaMiddlewareState::try_borrow_add(state, ScubaKey::RequestBytesReceived, received);
 
-    res.map(|_| EmptyBody::new())
+    res
 }
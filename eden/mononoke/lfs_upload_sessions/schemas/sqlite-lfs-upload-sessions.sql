@@ -0,0 +1,22 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE `lfs_upload_chunks` (
+  `repo_id` INT UNSIGNED NOT NULL,
+  `oid` VARCHAR(64) NOT NULL,
+  `size` BIGINT UNSIGNED NOT NULL,
+  `chunk_offset` BIGINT UNSIGNED NOT NULL,
+  `chunk_id` VARCHAR(64) NOT NULL,
+  `chunk_size` BIGINT UNSIGNED NOT NULL,
+  PRIMARY KEY (`repo_id`, `oid`, `size`, `chunk_offset`)
+);
+
+CREATE TABLE `lfs_orphaned_chunks` (
+  `repo_id` INT UNSIGNED NOT NULL,
+  `chunk_id` VARCHAR(64) NOT NULL,
+  PRIMARY KEY (`repo_id`, `chunk_id`)
+);
@@ -0,0 +1,255 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Storage for the state of Git LFS uploads that outlives the requests making them.
+//!
+//! An upload session records the Filestore chunks received so far for an object, each at the
+//! offset it starts at. Recording a chunk only succeeds if no chunk was recorded at its offset yet,
+//! so that when two clients upload the same object concurrently, only one of them makes progress
+//! and the other one finds out it has to resume from a different offset.
+//!
+//! Uploads that turn out not to match the object they were uploaded as leave behind the chunks
+//! stored for them. Those are recorded as orphaned chunks, for garbage collection to remove once
+//! it has checked that nothing else refers to them (chunks are content-addressed, so another
+//! object can share them).
+
+#![deny(warnings)]
+
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+use futures::compat::Future01CompatExt;
+use mononoke_types::{hash::Sha256, ContentChunkId, ContentChunkPointer, RepositoryId};
+use sql::queries;
+use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
+use sql_ext::SqlConnections;
+
+/// The chunks of an object received so far, in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UploadSession {
+    chunks: Vec<ContentChunkPointer>,
+}
+
+impl UploadSession {
+    /// How much of the object has been received.
+    pub fn offset(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.size()).sum()
+    }
+
+    pub fn into_chunks(self) -> Vec<ContentChunkPointer> {
+        self.chunks
+    }
+}
+
+queries! {
+    write InsertChunk(values: (
+        repo_id: RepositoryId,
+        oid: String,
+        size: u64,
+        chunk_offset: u64,
+        chunk_id: String,
+        chunk_size: u64,
+    )) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO lfs_upload_chunks (repo_id, oid, size, chunk_offset, chunk_id, chunk_size) VALUES {values}"
+    }
+
+    write DeleteChunks(repo_id: RepositoryId, oid: String, size: u64) {
+        none,
+        "DELETE FROM lfs_upload_chunks WHERE repo_id = {repo_id} AND oid = {oid} AND size = {size}"
+    }
+
+    write InsertOrphanedChunks(values: (repo_id: RepositoryId, chunk_id: String)) {
+        insert_or_ignore,
+        "{insert_or_ignore} INTO lfs_orphaned_chunks (repo_id, chunk_id) VALUES {values}"
+    }
+
+    write DeleteOrphanedChunks(repo_id: RepositoryId, >list chunk_ids: String) {
+        none,
+        "DELETE FROM lfs_orphaned_chunks WHERE repo_id = {repo_id} AND chunk_id IN {chunk_ids}"
+    }
+
+    read SelectOrphanedChunks(repo_id: RepositoryId, after: String, limit: usize) -> (String) {
+        "SELECT chunk_id
+         FROM lfs_orphaned_chunks
+         WHERE repo_id = {repo_id} AND chunk_id > {after}
+         ORDER BY chunk_id
+         LIMIT {limit}"
+    }
+
+    read SelectChunks(repo_id: RepositoryId, oid: String, size: u64) -> (u64, String, u64) {
+        "SELECT chunk_offset, chunk_id, chunk_size
+         FROM lfs_upload_chunks
+         WHERE repo_id = {repo_id} AND oid = {oid} AND size = {size}
+         ORDER BY chunk_offset"
+    }
+}
+
+#[derive(Clone)]
+pub struct SqlLfsUploadSessions {
+    connections: SqlConnections,
+}
+
+impl SqlConstruct for SqlLfsUploadSessions {
+    const LABEL: &'static str = "lfs_upload_sessions";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-lfs-upload-sessions.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self { connections }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlLfsUploadSessions {}
+
+impl SqlLfsUploadSessions {
+    /// Find the chunks received so far for an object of this size. Sessions that don't exist are
+    /// empty.
+    pub async fn get_session(
+        &self,
+        repo_id: RepositoryId,
+        oid: &Sha256,
+        size: u64,
+    ) -> Result<UploadSession, Error> {
+        // Read from master: the client may be resuming right after its previous request.
+        let rows = SelectChunks::query(
+            &self.connections.read_master_connection,
+            &repo_id,
+            &oid.to_hex().to_string(),
+            &size,
+        )
+        .compat()
+        .await?;
+
+        // Only the chunks that follow each other from the start of the object are usable. Others
+        // can only be left over from a session that was deleted while it was being added to.
+        // Since those hold data for the same object, it is fine to pick them up where they fit:
+        // the data is verified once all of it has been received.
+        let mut chunks = Vec::with_capacity(rows.len());
+        let mut offset = 0;
+        for (chunk_offset, chunk_id, chunk_size) in rows {
+            if chunk_offset < offset {
+                continue;
+            }
+            if chunk_offset > offset {
+                break;
+            }
+            let chunk_id = ContentChunkId::from_str(&chunk_id)?;
+            chunks.push(ContentChunkPointer::new(chunk_id, chunk_size));
+            offset += chunk_size;
+        }
+
+        Ok(UploadSession { chunks })
+    }
+
+    /// Record that `chunk` was received for an object of this size, starting at `offset`. Returns
+    /// false if a chunk was already recorded at this offset, in which case the session was
+    /// changed by another upload of the same object.
+    pub async fn add_chunk(
+        &self,
+        repo_id: RepositoryId,
+        oid: &Sha256,
+        size: u64,
+        offset: u64,
+        chunk: &ContentChunkPointer,
+    ) -> Result<bool, Error> {
+        let res = InsertChunk::query(
+            &self.connections.write_connection,
+            &[(
+                &repo_id,
+                &oid.to_hex().to_string(),
+                &size,
+                &offset,
+                &chunk.chunk_id().to_string(),
+                &chunk.size(),
+            )],
+        )
+        .compat()
+        .await?;
+
+        Ok(res.affected_rows() == 1)
+    }
+
+    /// Forget the chunks received for an object of this size, once it has been stored or if they
+    /// turned out not to match it.
+    pub async fn delete_session(
+        &self,
+        repo_id: RepositoryId,
+        oid: &Sha256,
+        size: u64,
+    ) -> Result<(), Error> {
+        DeleteChunks::query(
+            &self.connections.write_connection,
+            &repo_id,
+            &oid.to_hex().to_string(),
+            &size,
+        )
+        .compat()
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record chunks that were stored for an upload that was then rejected.
+    pub async fn add_orphaned_chunks(
+        &self,
+        repo_id: RepositoryId,
+        chunks: &[ContentChunkId],
+    ) -> Result<(), Error> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        let chunks: Vec<_> = chunks.iter().map(|chunk| chunk.to_string()).collect();
+        let values: Vec<_> = chunks.iter().map(|chunk| (&repo_id, chunk)).collect();
+        InsertOrphanedChunks::query(&self.connections.write_connection, &values[..])
+            .compat()
+            .await?;
+
+        Ok(())
+    }
+
+    /// List orphaned chunks in order, starting after `after` if given.
+    pub async fn get_orphaned_chunks(
+        &self,
+        repo_id: RepositoryId,
+        after: Option<&ContentChunkId>,
+        limit: usize,
+    ) -> Result<Vec<ContentChunkId>, Error> {
+        let after = after.map_or_else(String::new, |chunk| chunk.to_string());
+        let rows = SelectOrphanedChunks::query(
+            &self.connections.read_master_connection,
+            &repo_id,
+            &after,
+            &limit,
+        )
+        .compat()
+        .await?;
+
+        rows.into_iter()
+            .map(|(chunk_id,)| ContentChunkId::from_str(&chunk_id))
+            .collect()
+    }
+
+    /// Forget orphaned chunks once they have been removed, or found to be in use.
+    pub async fn remove_orphaned_chunks(
+        &self,
+        repo_id: RepositoryId,
+        chunks: &[ContentChunkId],
+    ) -> Result<(), Error> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        let chunks: Vec<_> = chunks.iter().map(|chunk| chunk.to_string()).collect();
+        DeleteOrphanedChunks::query(&self.connections.write_connection, &repo_id, &chunks[..])
+            .compat()
+            .await?;
+
+        Ok(())
+    }
+}
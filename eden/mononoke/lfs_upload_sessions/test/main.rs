@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use std::str::FromStr;
+
+use anyhow::Error;
+use fbinit::FacebookInit;
+use lfs_upload_sessions::{SqlLfsUploadSessions, UploadSession};
+use mononoke_types::{hash::Sha256, ContentChunkId, ContentChunkPointer};
+use mononoke_types_mocks::contentid::{ONES_CTID, TWOS_CTID};
+use mononoke_types_mocks::repo::{REPO_ONE, REPO_ZERO};
+use sql_construct::SqlConstruct;
+
+const OID: Sha256 = Sha256::from_byte_array([0x11; 32]);
+
+fn chunk(ctid: &impl ToString, size: u64) -> ContentChunkPointer {
+    let chunk_id = ContentChunkId::from_str(&ctid.to_string()).unwrap();
+    ContentChunkPointer::new(chunk_id, size)
+}
+
+#[fbinit::compat_test]
+async fn test_add_chunks(_fb: FacebookInit) -> Result<(), Error> {
+    let sessions = SqlLfsUploadSessions::with_sqlite_in_memory()?;
+    let first = chunk(&ONES_CTID, 10);
+    let second = chunk(&TWOS_CTID, 5);
+
+    assert_eq!(
+        sessions.get_session(REPO_ZERO, &OID, 15).await?,
+        UploadSession::default()
+    );
+
+    assert!(sessions.add_chunk(REPO_ZERO, &OID, 15, 0, &first).await?);
+    assert!(sessions.add_chunk(REPO_ZERO, &OID, 15, 10, &second).await?);
+
+    let session = sessions.get_session(REPO_ZERO, &OID, 15).await?;
+    assert_eq!(session.offset(), 15);
+    assert_eq!(session.into_chunks(), vec![first.clone(), second]);
+
+    // Sessions are per repository and object size.
+    assert_eq!(sessions.get_session(REPO_ONE, &OID, 15).await?.offset(), 0);
+    assert_eq!(sessions.get_session(REPO_ZERO, &OID, 16).await?.offset(), 0);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_concurrent_upload(_fb: FacebookInit) -> Result<(), Error> {
+    let sessions = SqlLfsUploadSessions::with_sqlite_in_memory()?;
+    let ours = chunk(&ONES_CTID, 10);
+    let theirs = chunk(&TWOS_CTID, 10);
+
+    assert!(sessions.add_chunk(REPO_ZERO, &OID, 20, 0, &ours).await?);
+    // Another upload that started from the same offset doesn't get to record its chunk.
+    assert!(!sessions.add_chunk(REPO_ZERO, &OID, 20, 0, &theirs).await?);
+
+    let session = sessions.get_session(REPO_ZERO, &OID, 20).await?;
+    assert_eq!(session.into_chunks(), vec![ours]);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_delete_session(_fb: FacebookInit) -> Result<(), Error> {
+    let sessions = SqlLfsUploadSessions::with_sqlite_in_memory()?;
+
+    assert!(
+        sessions
+            .add_chunk(REPO_ZERO, &OID, 20, 0, &chunk(&ONES_CTID, 10))
+            .await?
+    );
+    sessions.delete_session(REPO_ZERO, &OID, 20).await?;
+    assert_eq!(sessions.get_session(REPO_ZERO, &OID, 20).await?.offset(), 0);
+
+    // A chunk left over from an upload that was in progress when the session was deleted is not
+    // part of the new session.
+    assert!(
+        sessions
+            .add_chunk(REPO_ZERO, &OID, 20, 10, &chunk(&TWOS_CTID, 10))
+            .await?
+    );
+    assert_eq!(sessions.get_session(REPO_ZERO, &OID, 20).await?.offset(), 0);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn test_orphaned_chunks(_fb: FacebookInit) -> Result<(), Error> {
+    let sessions = SqlLfsUploadSessions::with_sqlite_in_memory()?;
+    let ones = ContentChunkId::from_str(&ONES_CTID.to_string())?;
+    let twos = ContentChunkId::from_str(&TWOS_CTID.to_string())?;
+
+    sessions
+        .add_orphaned_chunks(REPO_ZERO, &[ones, twos])
+        .await?;
+    // Chunks can be orphaned more than once.
+    sessions.add_orphaned_chunks(REPO_ZERO, &[ones]).await?;
+
+    assert_eq!(
+        sessions.get_orphaned_chunks(REPO_ZERO, None, 10).await?,
+        vec![ones, twos]
+    );
+    assert_eq!(
+        sessions.get_orphaned_chunks(REPO_ZERO, None, 1).await?,
+        vec![ones]
+    );
+    assert_eq!(
+        sessions
+            .get_orphaned_chunks(REPO_ZERO, Some(&ones), 10)
+            .await?,
+        vec![twos]
+    );
+    assert_eq!(
+        sessions.get_orphaned_chunks(REPO_ONE, None, 10).await?,
+        vec![]
+    );
+
+    sessions.remove_orphaned_chunks(REPO_ZERO, &[ones]).await?;
+    assert_eq!(
+        sessions.get_orphaned_chunks(REPO_ZERO, None, 10).await?,
+        vec![twos]
+    );
+
+    Ok(())
+}
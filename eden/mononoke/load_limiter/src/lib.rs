@@ -14,6 +14,10 @@ mod oss;
 
 use anyhow::Result;
 use async_trait::async_trait;
+#[cfg(fbcode_build)]
+use fbinit::FacebookInit;
+#[cfg(fbcode_build)]
+use limits::types::MononokeThrottleLimit;
 use limits::types::RateLimits;
 pub use session_id::SessionId;
 use std::{fmt, sync::Arc, time::Duration};
@@ -45,3 +49,18 @@ pub trait LoadLimiter: fmt::Debug {
 }
 
 pub struct LoadLimiterBuilder {}
+
+#[cfg(fbcode_build)]
+impl LoadLimiterBuilder {
+    /// A load limiter for a client of the category. The load is counted for all the clients of
+    /// the category together, across servers, so `client` is not used.
+    pub fn build_for_client(
+        fb: FacebookInit,
+        throttle_limits: MononokeThrottleLimit,
+        rate_limits: RateLimits,
+        category: String,
+        _client: Option<&str>,
+    ) -> BoxLoadLimiter {
+        Self::build(fb, throttle_limits, rate_limits, category)
+    }
+}
@@ -5,41 +5,154 @@
  * GNU General Public License version 2.
  */
 
+use std::fmt;
+use std::time::Duration;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use fbinit::FacebookInit;
 use limits::types::{MononokeThrottleLimit, RateLimits};
-use std::time::Duration;
+use stats::prelude::*;
+use time_window_counter::{BoxGlobalTimeWindowCounter, GlobalTimeWindowCounterBuilder};
 
 use crate::{BoxLoadLimiter, LoadCost, LoadLimiter, LoadLimiterBuilder, Metric};
 
+// Load is counted over windows of up to this many seconds.
+const MIN_TIME_WINDOW: u32 = 1;
+const MAX_TIME_WINDOW: u32 = 60;
+
+define_stats! {
+    prefix = "mononoke.load_limiter";
+    load: dynamic_timeseries("{}.{}.load", (category: String, metric: &'static str); Rate, Sum),
+    throttled: dynamic_timeseries("{}.{}.throttled", (category: String, metric: &'static str); Rate, Sum),
+}
+
 impl LoadLimiterBuilder {
+    /// A load limiter for all the clients of the category.
     pub fn build(
-        _fb: FacebookInit,
-        _throttle_limits: MononokeThrottleLimit,
+        fb: FacebookInit,
+        throttle_limits: MononokeThrottleLimit,
         rate_limits: RateLimits,
         category: String,
     ) -> BoxLoadLimiter {
-        Box::new(NoopLimiter {
+        Self::build_for_client(fb, throttle_limits, rate_limits, category, None)
+    }
+
+    /// A load limiter for a client of the category. The load of each client is counted
+    /// separately, so that a client going over the limits doesn't get the others throttled.
+    ///
+    /// The load is counted in this process: each server enforces the limits on its own.
+    pub fn build_for_client(
+        fb: FacebookInit,
+        throttle_limits: MononokeThrottleLimit,
+        rate_limits: RateLimits,
+        category: String,
+        client: Option<&str>,
+    ) -> BoxLoadLimiter {
+        let counter = |metric: &Metric| {
+            let key = match client {
+                Some(client) => format!("{}.{}", metric_name(metric), client),
+                None => metric_name(metric).to_string(),
+            };
+            GlobalTimeWindowCounterBuilder::build(
+                fb,
+                &category,
+                key,
+                MIN_TIME_WINDOW,
+                MAX_TIME_WINDOW,
+            )
+        };
+
+        Box::new(LocalLoadLimiter {
+            egress_bytes: counter(&Metric::EgressBytes),
+            ingress_blobstore_bytes: counter(&Metric::IngressBlobstoreBytes),
+            egress_total_manifests: counter(&Metric::EgressTotalManifests),
+            egress_getfiles_files: counter(&Metric::EgressGetfilesFiles),
+            egress_getpack_files: counter(&Metric::EgressGetpackFiles),
+            egress_commits: counter(&Metric::EgressCommits),
             category,
+            throttle_limits,
             rate_limits,
         })
     }
 }
 
-#[derive(Debug)]
-struct NoopLimiter {
+fn metric_name(metric: &Metric) -> &'static str {
+    match metric {
+        Metric::EgressBytes => "egress_bytes",
+        Metric::IngressBlobstoreBytes => "ingress_blobstore_bytes",
+        Metric::EgressTotalManifests => "egress_total_manifests",
+        Metric::EgressGetfilesFiles => "egress_getfiles_files",
+        Metric::EgressGetpackFiles => "egress_getpack_files",
+        Metric::EgressCommits => "egress_commits",
+    }
+}
+
+/// Throttles when the load of a metric over the window is higher than its limit, which is a
+/// load per second.
+struct LocalLoadLimiter {
     category: String,
+    throttle_limits: MononokeThrottleLimit,
     rate_limits: RateLimits,
+    egress_bytes: BoxGlobalTimeWindowCounter,
+    ingress_blobstore_bytes: BoxGlobalTimeWindowCounter,
+    egress_total_manifests: BoxGlobalTimeWindowCounter,
+    egress_getfiles_files: BoxGlobalTimeWindowCounter,
+    egress_getpack_files: BoxGlobalTimeWindowCounter,
+    egress_commits: BoxGlobalTimeWindowCounter,
+}
+
+impl LocalLoadLimiter {
+    fn counter(&self, metric: &Metric) -> &BoxGlobalTimeWindowCounter {
+        match metric {
+            Metric::EgressBytes => &self.egress_bytes,
+            Metric::IngressBlobstoreBytes => &self.ingress_blobstore_bytes,
+            Metric::EgressTotalManifests => &self.egress_total_manifests,
+            Metric::EgressGetfilesFiles => &self.egress_getfiles_files,
+            Metric::EgressGetpackFiles => &self.egress_getpack_files,
+            Metric::EgressCommits => &self.egress_commits,
+        }
+    }
+
+    fn limit(&self, metric: &Metric) -> f64 {
+        let limits = &self.throttle_limits;
+        match metric {
+            Metric::EgressBytes => limits.egress_bytes,
+            Metric::IngressBlobstoreBytes => limits.ingress_blobstore_bytes,
+            Metric::EgressTotalManifests => limits.total_manifests,
+            Metric::EgressGetfilesFiles => limits.getfiles_files,
+            Metric::EgressGetpackFiles => limits.getpack_files,
+            Metric::EgressCommits => limits.commits,
+        }
+    }
+}
+
+impl fmt::Debug for LocalLoadLimiter {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("LocalLoadLimiter")
+            .field("category", &self.category)
+            .field("throttle_limits", &self.throttle_limits)
+            .field("rate_limits", &self.rate_limits)
+            .finish()
+    }
 }
 
 #[async_trait]
-impl LoadLimiter for NoopLimiter {
-    async fn should_throttle(&self, _metric: Metric, _window: Duration) -> Result<bool> {
-        Ok(false)
+impl LoadLimiter for LocalLoadLimiter {
+    async fn should_throttle(&self, metric: Metric, window: Duration) -> Result<bool> {
+        let window = window.as_secs().max(1).min(MAX_TIME_WINDOW as u64) as u32;
+        let load = self.counter(&metric).get(window).await?;
+        let throttle = load > self.limit(&metric) * window as f64;
+        if throttle {
+            STATS::throttled.add_value(1, (self.category.clone(), metric_name(&metric)));
+        }
+        Ok(throttle)
     }
 
-    fn bump_load(&self, _metric: Metric, _load: LoadCost) {}
+    fn bump_load(&self, metric: Metric, load: LoadCost) {
+        STATS::load.add_value(load as i64, (self.category.clone(), metric_name(&metric)));
+        self.counter(&metric).bump(load);
+    }
 
     fn category(&self) -> &str {
         &self.category
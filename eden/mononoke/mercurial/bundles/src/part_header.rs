@@ -63,12 +63,13 @@ pub enum PartHeaderType {
     /// Used in communicating phases between Mononoke and clients
     /// Pushkey / Listkeys are not used to communicate phases
     PhaseHeads,
+    /// Text for the client to show to the user, f.e. warnings from hooks
+    Output,
     // RemoteChangegroup,       // We don't wish to support this functionality
     // CheckBookmarks,          // TODO Do we want to support this?
     // CheckHeads,              // TODO Do we want to support this?
     // CheckUpdatedHeads,       // TODO Do we want to support this?
     // CheckPhases,             // TODO Do we want to support this?
-    // Output,                  // TODO Do we want to support this?
     // ErrorAbort,              // TODO Do we want to support this?
     // ErrorPushkey,            // TODO Do we want to support this?
     // ErrorUnsupportedContent, // TODO Do we want to support this?
@@ -101,6 +102,7 @@ impl PartHeaderType {
             "pushvars" => Ok(Pushvars),
             "phase-heads" => Ok(PhaseHeads),
             "obsmarkers" => Ok(Obsmarkers),
+            "output" => Ok(Output),
             bad => bail!("unknown header type {}", bad),
         }
     }
@@ -124,6 +126,7 @@ impl PartHeaderType {
             ReplyPushkey => "reply:pushkey",
             PhaseHeads => "phase-heads",
             Obsmarkers => "obsmarkers",
+            Output => "output",
         }
     }
 }
@@ -257,6 +257,14 @@ pub fn replycaps_part(caps: Bytes) -> Result<PartEncodeBuilder> {
     Ok(builder)
 }
 
+/// A part with text to show to the user. Clients print it prefixed with "remote: ".
+pub fn output_part(text: Bytes) -> Result<PartEncodeBuilder> {
+    let mut builder = PartEncodeBuilder::advisory(PartHeaderType::Output)?;
+    builder.set_data_fixed(Chunk::new(text)?);
+
+    Ok(builder)
+}
+
 pub fn common_heads_part(heads: Vec<HgChangesetId>) -> Result<PartEncodeBuilder> {
     let mut w = Vec::new();
     for h in heads {
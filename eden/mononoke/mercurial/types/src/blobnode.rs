@@ -75,6 +75,16 @@ impl From<HgParents> for HgTypesParents {
     }
 }
 
+impl From<HgTypesParents> for HgParents {
+    fn from(parents: HgTypesParents) -> Self {
+        match parents {
+            HgTypesParents::None => HgParents::None,
+            HgTypesParents::One(p1) => HgParents::One(p1.into()),
+            HgTypesParents::Two(p1, p2) => HgParents::Two(p1.into(), p2.into()),
+        }
+    }
+}
+
 impl<'a> IntoIterator for &'a HgParents {
     type IntoIter = ParentIter;
     type Item = HgNodeHash;
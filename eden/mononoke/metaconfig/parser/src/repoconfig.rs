@@ -31,9 +31,9 @@ use metaconfig_types::{
     CommitSyncDirection, CommonConfig, DefaultSmallToLargeCommitSyncPathAction, DerivedDataConfig,
     HgsqlGlobalrevsName, HgsqlName, HookBypass, HookConfig, HookManagerParams, HookParams,
     InfinitepushNamespace, InfinitepushParams, LfsParams, PushParams, PushrebaseFlags,
-    PushrebaseParams, Redaction, RepoConfig, RepoReadOnly, SmallRepoCommitSyncConfig,
-    SourceControlServiceParams, StorageConfig, UnodeVersion, WhitelistEntry,
-    WireprotoLoggingConfig,
+    PushrebaseParams, Redaction, RepoConfig, RepoReadOnly, SegmentedChangelogConfig,
+    SmallRepoCommitSyncConfig, SourceControlServiceParams, StorageConfig, UnodeVersion,
+    WhitelistEntry, WireprotoLoggingConfig,
 };
 use mononoke_types::{MPath, RepositoryId};
 use regex::Regex;
@@ -389,6 +389,7 @@ impl RepoConfigs {
                 bypass: RepoConfigs::get_bypass(raw_hook_config.clone())?,
                 strings: raw_hook_config.config_strings.unwrap_or_default(),
                 ints: raw_hook_config.config_ints.unwrap_or_default(),
+                advisory: raw_hook_config.advisory.unwrap_or(false),
             };
 
             let hook_params = HookParams {
@@ -650,6 +651,7 @@ impl RepoConfigs {
                     .generate_lfs_blob_in_hg_sync_job
                     .unwrap_or(false),
                 rollout_smc_tier: lfs_params.rollout_smc_tier,
+                locking_enabled: lfs_params.locking_enabled.unwrap_or(false),
             },
             None => LfsParams::default(),
         };
@@ -787,6 +789,13 @@ impl RepoConfigs {
             .transpose()?
             .unwrap_or(DerivedDataConfig::default());
 
+        let segmented_changelog_config = this
+            .segmented_changelog_config
+            .map(|raw| SegmentedChangelogConfig {
+                enabled: raw.enabled.unwrap_or(false),
+            })
+            .unwrap_or_default();
+
         let hgsql_name = HgsqlName(this.hgsql_name.unwrap_or_else(|| reponame.to_string()));
 
         let hgsql_globalrevs_name = HgsqlGlobalrevsName(
@@ -829,6 +838,7 @@ impl RepoConfigs {
             derived_data_config,
             hgsql_name,
             hgsql_globalrevs_name,
+            segmented_changelog_config,
         })
     }
 
@@ -979,8 +989,9 @@ mod test {
     use maplit::{btreemap, btreeset, hashmap};
     use metaconfig_types::{
         BlobConfig, BlobstoreId, DatabaseConfig, FilestoreParams, LocalDatabaseConfig,
-        MetadataDatabaseConfig, MultiplexId, RemoteDatabaseConfig, RemoteMetadataDatabaseConfig,
-        ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig, SourceControlServiceMonitoring,
+        MetadataDatabaseConfig, MultiplexId, MultiplexPolicy, RemoteDatabaseConfig,
+        RemoteMetadataDatabaseConfig, ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig,
+        SourceControlServiceMonitoring,
     };
     use nonzero_ext::nonzero;
     use pretty_assertions::assert_eq;
@@ -1384,6 +1395,9 @@ mod test {
             [derived_data_config.raw_unode_version]
             unode_version_v2 = {}
 
+            [segmented_changelog_config]
+            enabled = true
+
             [storage.main.metadata.remote]
             primary = { db_address = "db_address" }
             filenodes = { sharded = { shard_map = "db_address_shards", shard_num = 123 } }
@@ -1397,6 +1411,9 @@ mod test {
                 { blobstore_id = 1, blobstore = { blob_files = { path = "/tmp/foo" } } },
             ]
             queue_db = { remote = { db_address = "queue_db_address" } }
+            write_quorum = 2
+            read_preference = [1]
+            read_repair = true
 
             [[bookmarks]]
             name="master"
@@ -1421,6 +1438,7 @@ mod test {
             name="rust:rusthook"
             hook_type="PerChangeset"
             config_ints={ int1 = 44 }
+            advisory=true
 
             [push]
             pure_push_allowed = false
@@ -1437,6 +1455,7 @@ mod test {
             rollout_percentage = 56
             generate_lfs_blob_in_hg_sync_job = true
             rollout_smc_tier = "smc_tier"
+            locking_enabled = true
 
             [bundle2_replay_params]
             preserve_raw_bundle2 = true
@@ -1512,6 +1531,11 @@ mod test {
             queue_db: DatabaseConfig::Remote(RemoteDatabaseConfig {
                 db_address: "queue_db_address".into(),
             }),
+            policy: MultiplexPolicy {
+                write_quorum: nonzero!(2usize),
+                read_preference: vec![BlobstoreId::new(1)],
+                read_repair: true,
+            },
         };
         let main_storage_config = StorageConfig {
             blobstore: multiplex,
@@ -1574,6 +1598,7 @@ mod test {
                             bypass: Some(HookBypass::CommitMessage("@allow_hook1".into())),
                             strings: hashmap! {},
                             ints: hashmap! {},
+                            advisory: false,
                         },
                     },
                     HookParams {
@@ -1584,6 +1609,7 @@ mod test {
                             ints: hashmap! {
                                 "int1".into() => 44,
                             },
+                            advisory: true,
                         },
                     },
                 ],
@@ -1609,6 +1635,7 @@ mod test {
                     rollout_percentage: 56,
                     generate_lfs_blob_in_hg_sync_job: true,
                     rollout_smc_tier: Some("smc_tier".to_string()),
+                    locking_enabled: true,
                 },
                 wireproto_logging: WireprotoLoggingConfig {
                     scribe_category: Some("category".to_string()),
@@ -1655,6 +1682,7 @@ mod test {
                 },
                 hgsql_name: HgsqlName("fbsource".to_string()),
                 hgsql_globalrevs_name: HgsqlGlobalrevsName("fbsource".to_string()),
+                segmented_changelog_config: SegmentedChangelogConfig { enabled: true },
             },
         );
 
@@ -1702,6 +1730,7 @@ mod test {
                 derived_data_config: DerivedDataConfig::default(),
                 hgsql_name: HgsqlName("www-foobar".to_string()),
                 hgsql_globalrevs_name: HgsqlGlobalrevsName("www-barfoo".to_string()),
+                segmented_changelog_config: SegmentedChangelogConfig::default(),
             },
         );
         assert_eq!(
@@ -1944,6 +1973,7 @@ mod test {
                                 db_address: "queue_db_address".into(),
                             }
                         ),
+                        policy: MultiplexPolicy::default(),
                     },
                     metadata: MetadataDatabaseConfig::Remote(RemoteMetadataDatabaseConfig {
                         primary: RemoteDatabaseConfig {
@@ -141,6 +141,8 @@ pub struct RepoConfig {
     /// Name of this repository in hgsql ... for globalrevs. This could, in some cases, not be the
     /// same as HgsqlName.
     pub hgsql_globalrevs_name: HgsqlGlobalrevsName,
+    /// Segmented changelog config for this repo
+    pub segmented_changelog_config: SegmentedChangelogConfig,
 }
 
 /// Config for derived data
@@ -154,6 +156,13 @@ pub struct DerivedDataConfig {
     pub unode_version: UnodeVersion,
 }
 
+/// Config for the segmented changelog
+#[derive(Eq, Clone, Default, Debug, PartialEq)]
+pub struct SegmentedChangelogConfig {
+    /// Whether ancestry queries are answered by the saved segmented changelog
+    pub enabled: bool,
+}
+
 /// What type of unode derived data to generate
 #[derive(Eq, Clone, Copy, Debug, PartialEq)]
 pub enum UnodeVersion {
@@ -409,6 +418,9 @@ pub struct HookConfig {
     pub strings: HashMap<String, String>,
     /// Map of config to it's value. Values here are integers
     pub ints: HashMap<String, i32>,
+    /// Whether rejections from the hook are only reported as warnings, without blocking the
+    /// push. Used to measure the impact of a new hook before enforcing it.
+    pub advisory: bool,
 }
 
 /// Configuration for a hook
@@ -503,6 +515,8 @@ pub struct LfsParams {
     pub generate_lfs_blob_in_hg_sync_job: bool,
     /// Hosts in this smc tier will receive lfs pointers regardless of rollout_percentage
     pub rollout_smc_tier: Option<String>,
+    /// Whether the LFS server allows locking files in this repo
+    pub locking_enabled: bool,
 }
 
 /// Id used to discriminate diffirent underlying blobstore instances
@@ -550,6 +564,30 @@ impl From<BlobstoreId> for ScubaValue {
     }
 }
 
+/// How a multiplexed blobstore writes to and reads from its components
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MultiplexPolicy {
+    /// Number of components a put must be written to before it succeeds. The sync queue
+    /// and healer copy the blob to the other components.
+    pub write_quorum: NonZeroUsize,
+    /// Components to read from first. The others are only read from if none of these has
+    /// the blob. If empty, all components are read from at once.
+    pub read_preference: Vec<BlobstoreId>,
+    /// Whether a get that finds the blob missing or different on some of the components it
+    /// read from writes it back to them, instead of leaving that to the healer.
+    pub read_repair: bool,
+}
+
+impl Default for MultiplexPolicy {
+    fn default() -> Self {
+        Self {
+            write_quorum: nonzero!(1_usize),
+            read_preference: Vec::new(),
+            read_repair: false,
+        }
+    }
+}
+
 /// Id used to identify storage configuration for a multiplexed blobstore.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct MultiplexId(i32);
@@ -636,6 +674,30 @@ impl FromStr for ScrubAction {
     }
 }
 
+// Blobs bigger than this are not kept in a disk cache, unless configured otherwise
+const DEFAULT_DISK_CACHE_MAX_BLOB_SIZE: u64 = 4 * 1024 * 1024;
+
+/// A zstd dictionary a packed blobstore compresses some blobs with
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PackDictionaryConfig {
+    /// Blobs whose key, ignoring the repo prefix, starts with this use the dictionary
+    pub key_prefix: String,
+    /// If set, only blobs of files under this path use the dictionary
+    pub path_prefix: Option<MPath>,
+    /// The name the dictionary was uploaded under
+    pub dictionary: String,
+}
+
+/// How a packed blobstore compresses new blobs
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct PackConfig {
+    /// The dictionaries to compress with. The one with the longest matching path prefix, then
+    /// the longest matching key prefix, wins.
+    pub dictionaries: Vec<PackDictionaryConfig>,
+    /// Maximum length of delta chains, 0 disables deltas. None uses the packblob default.
+    pub max_delta_depth: Option<u8>,
+}
+
 /// Configuration for a blobstore
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum BlobConfig {
@@ -678,6 +740,8 @@ pub enum BlobConfig {
         scuba_sample_rate: NonZeroU64,
         /// DB config to use for the sync queue
         queue_db: DatabaseConfig,
+        /// Write quorum, read preference and read repair
+        policy: MultiplexPolicy,
     },
     /// Multiplex across multiple blobstores scrubbing for errors
     Scrub {
@@ -703,6 +767,25 @@ pub enum BlobConfig {
         /// TTL for each object we put in Manifold
         ttl: Duration,
     },
+    /// Store blobs zstd compressed with dictionaries, or as deltas, in another blobstore
+    Pack {
+        /// The blobstore the packed blobs are stored in
+        blobconfig: Box<BlobConfig>,
+        /// How new blobs are packed
+        pack_config: PackConfig,
+    },
+    /// Cache the blobs of another blobstore in a local directory
+    DiskCache {
+        /// The blobstore to cache
+        blobconfig: Box<BlobConfig>,
+        /// Directory to keep the cache in, shared by all the blobstores of the process
+        /// configured with it
+        path: PathBuf,
+        /// Total size of the cache in bytes
+        max_size: u64,
+        /// Blobs larger than this many bytes are not cached
+        max_blob_size: u64,
+    },
 }
 
 impl BlobConfig {
@@ -718,6 +801,7 @@ impl BlobConfig {
                 .iter()
                 .map(|(_, config)| config)
                 .all(BlobConfig::is_local),
+            Pack { blobconfig, .. } | DiskCache { blobconfig, .. } => blobconfig.is_local(),
         }
     }
 
@@ -725,7 +809,20 @@ impl BlobConfig {
     /// This maximises error rates, and asks blobstores to silently fix errors when they are able
     /// to do so - ideal for repository checkers.
     pub fn set_scrubbed(&mut self, scrub_action: ScrubAction) {
-        use BlobConfig::{Multiplexed, Scrub};
+        use BlobConfig::{DiskCache, Multiplexed, Pack, Scrub};
+
+        if let Pack { blobconfig, .. } = self {
+            blobconfig.set_scrubbed(scrub_action);
+            return;
+        }
+
+        // A scrub has to read the blobstores themselves, not the cache
+        if let DiskCache { blobconfig, .. } = self {
+            let mut inner = mem::replace(&mut **blobconfig, BlobConfig::Disabled);
+            inner.set_scrubbed(scrub_action);
+            *self = inner;
+            return;
+        }
 
         if let Multiplexed {
             multiplex_id,
@@ -733,6 +830,7 @@ impl BlobConfig {
             scuba_sample_rate,
             blobstores,
             queue_db,
+            ..
         } = self
         {
             let scuba_table = mem::replace(scuba_table, None);
@@ -752,6 +850,40 @@ impl BlobConfig {
     }
 }
 
+impl MultiplexPolicy {
+    fn from_raw(
+        write_quorum: Option<i64>,
+        read_preference: Option<Vec<i64>>,
+        read_repair: Option<bool>,
+        blobstores: &[(BlobstoreId, BlobConfig)],
+    ) -> Result<Self> {
+        let mut policy = MultiplexPolicy::default();
+        if let Some(write_quorum) = write_quorum {
+            policy.write_quorum = NonZeroUsize::new(write_quorum.try_into()?)
+                .ok_or_else(|| anyhow!("write_quorum must be an integer larger than zero"))?;
+        }
+        if policy.write_quorum.get() > blobstores.len() {
+            return Err(anyhow!(
+                "write_quorum {} is larger than the number of components {}",
+                policy.write_quorum,
+                blobstores.len()
+            ));
+        }
+        for id in read_preference.unwrap_or_default() {
+            let id = BlobstoreId(id.try_into()?);
+            if !blobstores
+                .iter()
+                .any(|(blobstore_id, _)| *blobstore_id == id)
+            {
+                return Err(anyhow!("read_preference {} is not a component", id));
+            }
+            policy.read_preference.push(id);
+        }
+        policy.read_repair = read_repair.unwrap_or(false);
+        Ok(policy)
+    }
+}
+
 impl Default for BlobConfig {
     fn default() -> Self {
         BlobConfig::Disabled
@@ -791,22 +923,8 @@ impl TryFrom<RawBlobstoreConfig> for BlobConfig {
                     }
                 }
             }
-            RawBlobstoreConfig::multiplexed(def) => BlobConfig::Multiplexed {
-                multiplex_id: def
-                    .multiplex_id
-                    .map(|id| MultiplexId::new(id))
-                    .ok_or_else(|| anyhow!("missing multiplex_id from configuration"))?,
-                scuba_table: def.scuba_table,
-                scuba_sample_rate: def
-                    .scuba_sample_rate
-                    .map(|rate| {
-                        NonZeroU64::new(rate.try_into()?).ok_or(anyhow!(
-                            "scuba_sample_rate must be an integer larger than zero"
-                        ))
-                    })
-                    .transpose()?
-                    .unwrap_or(nonzero!(100_u64)),
-                blobstores: def
+            RawBlobstoreConfig::multiplexed(def) => {
+                let blobstores = def
                     .components
                     .into_iter()
                     .map(|comp| {
@@ -815,12 +933,36 @@ impl TryFrom<RawBlobstoreConfig> for BlobConfig {
                             BlobConfig::try_from(comp.blobstore)?,
                         ))
                     })
-                    .collect::<Result<Vec<_>>>()?,
-                queue_db: def
-                    .queue_db
-                    .ok_or_else(|| anyhow!("missing queue_db from configuration"))?
-                    .try_into()?,
-            },
+                    .collect::<Result<Vec<_>>>()?;
+                let policy = MultiplexPolicy::from_raw(
+                    def.write_quorum,
+                    def.read_preference,
+                    def.read_repair,
+                    &blobstores,
+                )?;
+                BlobConfig::Multiplexed {
+                    multiplex_id: def
+                        .multiplex_id
+                        .map(|id| MultiplexId::new(id))
+                        .ok_or_else(|| anyhow!("missing multiplex_id from configuration"))?,
+                    scuba_table: def.scuba_table,
+                    scuba_sample_rate: def
+                        .scuba_sample_rate
+                        .map(|rate| {
+                            NonZeroU64::new(rate.try_into()?).ok_or(anyhow!(
+                                "scuba_sample_rate must be an integer larger than zero"
+                            ))
+                        })
+                        .transpose()?
+                        .unwrap_or(nonzero!(100_u64)),
+                    blobstores,
+                    queue_db: def
+                        .queue_db
+                        .ok_or_else(|| anyhow!("missing queue_db from configuration"))?
+                        .try_into()?,
+                    policy,
+                }
+            }
             RawBlobstoreConfig::manifold_with_ttl(def) => {
                 let ttl = Duration::from_secs(def.ttl_secs.try_into()?);
                 BlobConfig::ManifoldWithTtl {
@@ -829,6 +971,36 @@ impl TryFrom<RawBlobstoreConfig> for BlobConfig {
                     ttl,
                 }
             }
+            RawBlobstoreConfig::pack(def) => BlobConfig::Pack {
+                blobconfig: Box::new(BlobConfig::try_from(def.blobstore)?),
+                pack_config: PackConfig {
+                    dictionaries: def
+                        .dictionaries
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|dict| -> Result<_> {
+                            Ok(PackDictionaryConfig {
+                                key_prefix: dict.key_prefix,
+                                path_prefix: dict.path_prefix.map(MPath::new).transpose()?,
+                                dictionary: dict.dictionary,
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                    max_delta_depth: def
+                        .max_delta_depth
+                        .map(|depth| depth.try_into())
+                        .transpose()?,
+                },
+            },
+            RawBlobstoreConfig::disk_cache(def) => BlobConfig::DiskCache {
+                blobconfig: Box::new(BlobConfig::try_from(def.blobstore)?),
+                path: PathBuf::from(def.path),
+                max_size: def.max_size.try_into()?,
+                max_blob_size: match def.max_blob_size {
+                    Some(max_blob_size) => max_blob_size.try_into()?,
+                    None => DEFAULT_DISK_CACHE_MAX_BLOB_SIZE,
+                },
+            },
             RawBlobstoreConfig::UnknownField(_) => {
                 return Err(anyhow!("unsupported blobstore configuration"));
             }
@@ -22,12 +22,14 @@ use fsnodes::RootFsnodeId;
 use futures::compat::{Future01CompatExt, Stream01CompatExt};
 use futures::future::{self, try_join, FutureExt, Shared};
 use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use futures_ext::StreamExt as OldStreamExt;
 use manifest::{Diff as ManifestDiff, Entry as ManifestEntry, ManifestOps, PathOrPrefix};
 use maplit::hashset;
 use mercurial_types::Globalrev;
 pub use mononoke_types::Generation;
 use mononoke_types::{BonsaiChangeset, FileChange, MPath, MPathElement};
 use reachabilityindex::ReachabilityIndex;
+use revset::{greatest_common_ancestor, AncestorsNodeStream, RangeNodeStream};
 use unodes::RootUnodeManifestId;
 
 use crate::changeset_path::ChangesetPathContext;
@@ -308,6 +310,11 @@ impl ChangesetContext {
 
     /// Returns `true` if this commit is an ancestor of `other_commit`.
     pub async fn is_ancestor_of(&self, other_commit: ChangesetId) -> Result<bool, MononokeError> {
+        if let Some(dag) = self.repo().segmented_changelog() {
+            if let Some(is_ancestor_of) = dag.is_ancestor(self.id, other_commit).await? {
+                return Ok(is_ancestor_of);
+            }
+        }
         let is_ancestor_of = self
             .repo()
             .skiplist_index()
@@ -322,6 +329,77 @@ impl ChangesetContext {
         Ok(is_ancestor_of)
     }
 
+    /// Returns the greatest common ancestor of this commit and `other_commit`, or `None` if
+    /// they have no common history. If there is more than one, one of them is returned.
+    pub async fn common_base_with(
+        &self,
+        other_commit: ChangesetId,
+    ) -> Result<Option<ChangesetContext>, MononokeError> {
+        let dag_common_bases = match self.repo().segmented_changelog() {
+            Some(dag) => {
+                dag.greatest_common_ancestors(&[self.id, other_commit])
+                    .await?
+            }
+            None => None,
+        };
+        let common_base = match dag_common_bases {
+            Some(common_bases) => common_bases.into_iter().next(),
+            None => {
+                greatest_common_ancestor(
+                    self.ctx().clone(),
+                    self.repo().blob_repo().get_changeset_fetcher(),
+                    vec![self.id, other_commit],
+                )
+                .compat()
+                .try_next()
+                .await?
+            }
+        };
+        Ok(common_base.map(|id| ChangesetContext::new(self.repo.clone(), id)))
+    }
+
+    /// Returns the ancestors of this commit in topological order, starting with this commit.
+    /// If `descendants_of` is given, only commits that descend from it are included, which
+    /// gives the commits between the two.
+    pub async fn linear_history(
+        &self,
+        descendants_of: Option<ChangesetId>,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<ChangesetContext>, MononokeError> {
+        let dag_history = match self.repo().segmented_changelog() {
+            Some(dag) => {
+                dag.linear_history(self.id, descendants_of, skip, limit)
+                    .await?
+            }
+            None => None,
+        };
+        let history = match dag_history {
+            Some(history) => history,
+            None => {
+                let ctx = self.ctx().clone();
+                let changeset_fetcher = self.repo().blob_repo().get_changeset_fetcher();
+                let nodes = match descendants_of {
+                    None => AncestorsNodeStream::new(ctx, &changeset_fetcher, self.id).boxify(),
+                    Some(descendants_of) => {
+                        RangeNodeStream::new(ctx, changeset_fetcher, descendants_of, self.id)
+                            .boxify()
+                    }
+                };
+                nodes
+                    .compat()
+                    .skip(skip)
+                    .take(limit)
+                    .try_collect::<Vec<_>>()
+                    .await?
+            }
+        };
+        Ok(history
+            .into_iter()
+            .map(|id| ChangesetContext::new(self.repo.clone(), id))
+            .collect())
+    }
+
     /// Returns differences between this changeset and some other changeset.
     ///
     /// `self` is considered the "new" changeset (so files missing there are "Removed")
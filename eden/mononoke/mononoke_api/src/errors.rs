@@ -7,6 +7,7 @@
 
 use blobstore::LoadableError;
 use derived_data::DeriveError;
+use pushrebase::PushrebaseError;
 use std::backtrace::Backtrace;
 use std::convert::Infallible;
 use std::error::Error as StdError;
@@ -83,3 +84,12 @@ impl From<DeriveError> for MononokeError {
         }
     }
 }
+
+impl From<PushrebaseError> for MononokeError {
+    fn from(e: PushrebaseError) -> Self {
+        match e {
+            PushrebaseError::Error(e) => MononokeError::from(e),
+            e => MononokeError::InvalidRequest(e.to_string()),
+        }
+    }
+}
@@ -9,8 +9,10 @@ pub use mercurial_types::{HgFileNodeId, HgManifestId, HgParents};
 
 pub mod file;
 pub mod repo;
+pub mod repo_write;
 pub mod tree;
 
 pub use file::HgFileContext;
 pub use repo::HgRepoContext;
+pub use repo_write::{HgPushrebaseOutcome, HgRepoWriteContext};
 pub use tree::HgTreeContext;
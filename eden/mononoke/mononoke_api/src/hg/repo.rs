@@ -5,13 +5,21 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
+
+use anyhow::format_err;
 use blobrepo::BlobRepo;
 use context::CoreContext;
-use futures::{compat::Stream01CompatExt, TryStream, TryStreamExt};
+use dag::protocol::CloneData;
+use futures::{
+    compat::{Future01CompatExt, Stream01CompatExt},
+    TryStream, TryStreamExt,
+};
 use hgproto::GettreepackArgs;
-use mercurial_types::{HgFileNodeId, HgManifestId};
-use mononoke_types::MPath;
+use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
+use mononoke_types::{hash::Sha256, ChangesetId, MPath};
 use repo_client::gettreepack_entries;
+use segmented_changelog::dag::Location;
 
 use crate::errors::MononokeError;
 use crate::path::MononokePath;
@@ -60,6 +68,116 @@ impl HgRepoContext {
         HgTreeContext::new_check_exists(self.clone(), manifest_id).await
     }
 
+    /// Whether file content with this SHA-256 is stored in the repo.
+    pub async fn file_content_exists(&self, sha256: Sha256) -> Result<bool, MononokeError> {
+        Ok(self.repo().file_by_content_sha256(sha256).await?.is_some())
+    }
+
+    /// Whether the repo has the filenode `filenode_id`.
+    pub async fn filenode_exists(&self, filenode_id: HgFileNodeId) -> Result<bool, MononokeError> {
+        Ok(self.file(filenode_id).await?.is_some())
+    }
+
+    /// Whether the repo has the tree manifest `manifest_id`.
+    pub async fn tree_exists(&self, manifest_id: HgManifestId) -> Result<bool, MononokeError> {
+        Ok(self.tree(manifest_id).await?.is_some())
+    }
+
+    /// Whether the repo has the Mercurial changeset `hg_cs_id`.
+    pub async fn changeset_exists(&self, hg_cs_id: HgChangesetId) -> Result<bool, MononokeError> {
+        Ok(self
+            .blob_repo()
+            .changeset_exists(self.ctx().clone(), hg_cs_id)
+            .compat()
+            .await?)
+    }
+
+    /// Like `RepoContext::location_to_changeset_ids`, with Mercurial changeset ids.
+    pub async fn location_to_hg_changeset_ids(
+        &self,
+        location: Location<HgChangesetId>,
+        count: u64,
+    ) -> Result<Vec<HgChangesetId>, MononokeError> {
+        let descendant = self.bonsai_id(location.descendant).await?;
+        let cs_ids = self
+            .repo()
+            .location_to_changeset_ids(location.map_descendant(|_| descendant), count)
+            .await?;
+        let mut hg_ids = self.hg_ids(cs_ids.clone()).await?;
+        cs_ids
+            .into_iter()
+            .map(|cs_id| hg_ids.remove(&cs_id).ok_or_else(|| missing_hg_id(cs_id)))
+            .collect()
+    }
+
+    /// Like `RepoContext::changeset_id_to_location`, with Mercurial changeset ids.
+    pub async fn hg_changeset_id_to_location(
+        &self,
+        master_heads: Vec<HgChangesetId>,
+        hg_cs_id: HgChangesetId,
+    ) -> Result<Option<Location<HgChangesetId>>, MononokeError> {
+        let mut heads = Vec::with_capacity(master_heads.len());
+        for head in master_heads {
+            heads.push(self.bonsai_id(head).await?);
+        }
+        let cs_id = self.bonsai_id(hg_cs_id).await?;
+        let location = match self.repo().changeset_id_to_location(heads, cs_id).await? {
+            None => return Ok(None),
+            Some(location) => location,
+        };
+        let mut hg_ids = self.hg_ids(vec![location.descendant]).await?;
+        let descendant = hg_ids
+            .remove(&location.descendant)
+            .ok_or_else(|| missing_hg_id(location.descendant))?;
+        Ok(Some(location.map_descendant(|_| descendant)))
+    }
+
+    /// Like `RepoContext::segmented_changelog_clone_data`, with Mercurial changeset ids.
+    pub async fn segmented_changelog_clone_data(
+        &self,
+    ) -> Result<CloneData<HgChangesetId>, MononokeError> {
+        let clone_data = self.repo().segmented_changelog_clone_data().await?;
+        let mut hg_ids = self
+            .hg_ids(clone_data.idmap.values().cloned().collect())
+            .await?;
+        let idmap = clone_data
+            .idmap
+            .into_iter()
+            .map(|(id, cs_id)| {
+                let hg_cs_id = hg_ids.remove(&cs_id).ok_or_else(|| missing_hg_id(cs_id))?;
+                Ok((id, hg_cs_id))
+            })
+            .collect::<Result<_, MononokeError>>()?;
+        Ok(CloneData {
+            head_id: clone_data.head_id,
+            flat_segments: clone_data.flat_segments,
+            idmap,
+        })
+    }
+
+    async fn bonsai_id(&self, hg_cs_id: HgChangesetId) -> Result<ChangesetId, MononokeError> {
+        self.blob_repo()
+            .get_bonsai_from_hg(self.ctx().clone(), hg_cs_id)
+            .compat()
+            .await?
+            .ok_or_else(|| MononokeError::InvalidRequest(format!("unknown changeset {}", hg_cs_id)))
+    }
+
+    async fn hg_ids(
+        &self,
+        cs_ids: Vec<ChangesetId>,
+    ) -> Result<HashMap<ChangesetId, HgChangesetId>, MononokeError> {
+        let mapping = self
+            .blob_repo()
+            .get_hg_bonsai_mapping(self.ctx().clone(), cs_ids)
+            .compat()
+            .await?
+            .into_iter()
+            .map(|(hg_cs_id, cs_id)| (cs_id, hg_cs_id))
+            .collect();
+        Ok(mapping)
+    }
+
     /// Request all of the tree nodes in the repo under a given path.
     ///
     /// The caller must specify a list of desired versions of the subtree for
@@ -109,6 +227,10 @@ impl HgRepoContext {
     }
 }
 
+fn missing_hg_id(cs_id: ChangesetId) -> MononokeError {
+    format_err!("failed to find Mercurial id of changeset {}", cs_id).into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
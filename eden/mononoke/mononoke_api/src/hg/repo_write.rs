@@ -0,0 +1,539 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::Error;
+use blobrepo::{BlobRepo, ChangesetHandle, CreateChangeset};
+use blobstore::Loadable;
+use bookmarks::{BookmarkName, BookmarkUpdateReason};
+use bytes::Bytes;
+use context::CoreContext;
+use filestore::{Alias, FetchKey, StoreRequest};
+use futures::{
+    compat::Future01CompatExt,
+    future::{try_join_all, TryFutureExt},
+    TryStream, TryStreamExt,
+};
+use futures_ext::{FutureExt as OldFutureExt, StreamExt as OldStreamExt};
+use futures_old::{future as old_future, stream as old_stream, Stream as OldStream};
+use itertools::Itertools;
+use mercurial_types::{
+    blobs::{
+        ChangesetMetadata, ContentBlobMeta, HgBlobEntry, RevlogChangeset, UploadHgFileContents,
+        UploadHgFileEntry, UploadHgNodeHash, UploadHgTreeEntry,
+    },
+    HgBlobNode, HgChangesetId, HgFileNodeId, HgManifestId, HgNodeHash, HgParents, NULL_HASH,
+};
+use mononoke_types::{hash::Sha256, BonsaiChangeset, ChangesetId, FileType, MPath, RepoPath};
+use pushrebase::{do_pushrebase_bonsai, OntoBookmarkParams};
+use reachabilityindex::LeastCommonAncestorsHint;
+use revset::DifferenceOfUnionsOfAncestorsNodeStream;
+use slog::warn;
+use unbundle::get_pushrebase_hooks;
+
+use crate::errors::MononokeError;
+use crate::path::MononokePath;
+use crate::repo_write::RepoWriteContext;
+
+/// The most changesets a single bookmark move may make public. Each of them
+/// is checked by the bookmark's hooks.
+const MAX_LANDED_CHANGESETS: usize = 1000;
+
+/// Result of rebasing a stack of changesets onto a bookmark.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HgPushrebaseOutcome {
+    /// Where the bookmark points after the pushrebase.
+    pub head: HgChangesetId,
+    /// Pairs of (pushed changeset, its rebased counterpart).
+    pub rebased: Vec<(HgChangesetId, HgChangesetId)>,
+    /// Warnings from advisory hooks, which did not block the push.
+    pub hook_warnings: Vec<String>,
+}
+
+/// A context object for writing to a repo in Mercurial's formats, as a
+/// Mercurial client pushing commits would.
+///
+/// Mercurial ids are always checked against the data they name. Bookmark
+/// moves are subject to the same checks as pushes over the wire protocol:
+/// the bookmark's hooks are run on the changesets it lands, and bookmarks
+/// configured as fast-forward only can't be moved backwards or deleted.
+pub struct HgRepoWriteContext {
+    repo: RepoWriteContext,
+}
+
+impl HgRepoWriteContext {
+    pub(crate) fn new(repo: RepoWriteContext) -> Self {
+        Self { repo }
+    }
+
+    /// The `CoreContext` for this query.
+    pub(crate) fn ctx(&self) -> &CoreContext {
+        self.repo.ctx()
+    }
+
+    /// The underlying Mononoke `BlobRepo` backing this repo.
+    pub(crate) fn blob_repo(&self) -> &BlobRepo {
+        self.repo.blob_repo()
+    }
+
+    /// Store the content of a file that is `size` bytes long and hashes to
+    /// `sha256`. The content is streamed into the filestore, which rejects it
+    /// if either the size or the hash does not match.
+    pub async fn store_file_content<S>(
+        &self,
+        sha256: Sha256,
+        size: u64,
+        content: S,
+    ) -> Result<(), MononokeError>
+    where
+        S: TryStream<Ok = Bytes, Error = Error> + Send + Unpin + 'static,
+    {
+        filestore::store(
+            self.blob_repo().get_blobstore(),
+            self.blob_repo().filestore_config(),
+            self.ctx().clone(),
+            &StoreRequest::with_sha256(size, sha256),
+            content.compat(),
+        )
+        .compat()
+        .await?;
+        Ok(())
+    }
+
+    /// Store a Mercurial filenode for file content that was stored earlier
+    /// with `store_file_content`. The filenode id is checked against the
+    /// content, parents and copy information.
+    pub async fn store_hg_filenode(
+        &self,
+        filenode_id: HgFileNodeId,
+        path: MPath,
+        parents: HgParents,
+        content_sha256: Sha256,
+        copy_from: Option<(MPath, HgFileNodeId)>,
+    ) -> Result<(), MononokeError> {
+        let ctx = self.ctx().clone();
+        let blobstore = self.blob_repo().get_blobstore();
+
+        let metadata = filestore::get_metadata(
+            &blobstore,
+            ctx.clone(),
+            &FetchKey::Aliased(Alias::Sha256(content_sha256)),
+        )
+        .compat()
+        .await?
+        .ok_or_else(|| {
+            MononokeError::InvalidRequest(format!(
+                "content with sha256 {} has not been uploaded",
+                content_sha256
+            ))
+        })?;
+
+        let (p1, p2) = parents.get_nodes();
+        let upload = UploadHgFileEntry {
+            upload_node_id: UploadHgNodeHash::Checked(filenode_id.into_nodehash()),
+            contents: UploadHgFileContents::ContentUploaded(ContentBlobMeta {
+                id: metadata.content_id,
+                size: metadata.total_size,
+                copy_from,
+            }),
+            // The file type is recorded in the parent tree, not the filenode.
+            file_type: FileType::Regular,
+            p1: p1.map(HgFileNodeId::new),
+            p2: p2.map(HgFileNodeId::new),
+            path,
+        };
+        let (_content_info, upload) = upload.upload(ctx, blobstore.boxed())?;
+        upload.compat().await?;
+        Ok(())
+    }
+
+    /// Store a Mercurial tree manifest for the directory at `path`. The
+    /// manifest id is checked against the contents and parents.
+    pub async fn store_tree(
+        &self,
+        manifest_id: HgManifestId,
+        path: MononokePath,
+        parents: HgParents,
+        contents: Bytes,
+    ) -> Result<(), MononokeError> {
+        let path = match path.into_mpath() {
+            Some(mpath) => RepoPath::DirectoryPath(mpath),
+            None => RepoPath::RootPath,
+        };
+        let (p1, p2) = parents.get_nodes();
+        let upload = UploadHgTreeEntry {
+            upload_node_id: UploadHgNodeHash::Checked(manifest_id.into_nodehash()),
+            contents,
+            p1,
+            p2,
+            path,
+        };
+        let (_node, upload) =
+            upload.upload(self.ctx().clone(), self.blob_repo().get_blobstore().boxed())?;
+        upload.compat().await?;
+        Ok(())
+    }
+
+    /// Store Mercurial changesets given in revlog format, together with the
+    /// bonsai changesets they convert to.
+    ///
+    /// The trees and filenodes of the changesets must have been stored
+    /// already. Each changeset's parents must either be in the repo or come
+    /// before it in `changesets`.
+    pub async fn store_hg_changesets(
+        &self,
+        changesets: Vec<(HgChangesetId, HgParents, Bytes)>,
+    ) -> Result<Vec<(HgChangesetId, ChangesetId)>, MononokeError> {
+        let ctx = self.ctx();
+        let blob_repo = self.blob_repo();
+
+        let mut handles: HashMap<HgChangesetId, ChangesetHandle> = HashMap::new();
+        let mut uploads = Vec::with_capacity(changesets.len());
+        for (hg_cs_id, parents, contents) in changesets {
+            let (p1, p2) = parents.get_nodes();
+            let revlog_cs = RevlogChangeset::new(HgBlobNode::new(contents, p1, p2))
+                .map_err(|e| invalid_changeset(hg_cs_id, e))?;
+            let cs_metadata = ChangesetMetadata {
+                user: String::from_utf8(revlog_cs.user().into())
+                    .map_err(|e| invalid_changeset(hg_cs_id, e))?,
+                time: revlog_cs.time().clone(),
+                extra: revlog_cs.extra().clone(),
+                comments: String::from_utf8(revlog_cs.comments().into())
+                    .map_err(|e| invalid_changeset(hg_cs_id, e))?,
+            };
+
+            // The manifests were stored beforehand, so there are no new
+            // entries to process: `CreateChangeset` checks that everything
+            // the changeset needs is in the blobstore.
+            let root_manifest = if revlog_cs.manifestid().into_nodehash() == NULL_HASH {
+                None
+            } else {
+                let entry =
+                    HgBlobEntry::new_root(blob_repo.blobstore().boxed(), revlog_cs.manifestid());
+                Some((entry, RepoPath::RootPath))
+            };
+
+            let create_changeset = CreateChangeset {
+                expected_nodeid: Some(hg_cs_id.into_nodehash()),
+                expected_files: Some(Vec::from(revlog_cs.files())),
+                p1: parent_handle(ctx, blob_repo, &handles, revlog_cs.p1()),
+                p2: parent_handle(ctx, blob_repo, &handles, revlog_cs.p2()),
+                root_manifest: old_future::ok(root_manifest).boxify(),
+                sub_entries: old_stream::empty().boxify(),
+                cs_metadata,
+                must_check_case_conflicts: true,
+            };
+            let handle = create_changeset.create(ctx.clone(), blob_repo, ctx.scuba().clone());
+            handles.insert(hg_cs_id, handle.clone());
+            uploads.push(
+                handle
+                    .get_completed_changeset()
+                    .map_err(Error::from)
+                    .compat()
+                    .map_ok(move |(bonsai, _hg_cs)| (hg_cs_id, bonsai.get_changeset_id())),
+            );
+        }
+
+        Ok(try_join_all(uploads).await?)
+    }
+
+    /// Move `bookmark` from `old_target` to `new_target`, where `None` means
+    /// the bookmark does not exist. Fails if the bookmark has moved since the
+    /// client last saw it, if the move is not allowed for the bookmark, or if
+    /// a hook rejects one of the changesets the move makes public. Returns the
+    /// warnings of advisory hooks.
+    pub async fn move_bookmark(
+        &self,
+        bookmark: &str,
+        old_target: Option<HgChangesetId>,
+        new_target: Option<HgChangesetId>,
+    ) -> Result<Vec<String>, MononokeError> {
+        self.repo.check_bookmark_write_permission(bookmark).await?;
+        let bookmark = bookmark_name(bookmark)?;
+        let old_target = self.maybe_bonsai_id(old_target).await?;
+        let new_target = self.maybe_bonsai_id(new_target).await?;
+
+        self.check_fast_forward(&bookmark, old_target, new_target)
+            .await?;
+        let hook_warnings = match new_target {
+            Some(new) => {
+                let landed = self.landed_changesets(old_target, new).await?;
+                self.run_hooks(&bookmark, landed.iter()).await?
+            }
+            None => Vec::new(),
+        };
+
+        let reason = BookmarkUpdateReason::Push {
+            bundle_replay_data: None,
+        };
+
+        let mut txn = self
+            .blob_repo()
+            .update_bookmark_transaction(self.ctx().clone());
+        match (old_target, new_target) {
+            (Some(old), Some(new)) => txn.update(&bookmark, new, old, reason)?,
+            (None, Some(new)) => txn.create(&bookmark, new, reason)?,
+            (Some(old), None) => txn.delete(&bookmark, old, reason)?,
+            (None, None) => return Ok(hook_warnings),
+        }
+
+        if txn.commit().compat().await? {
+            Ok(hook_warnings)
+        } else {
+            Err(MononokeError::InvalidRequest(format!(
+                "bookmark {} does not point to the expected changeset",
+                bookmark
+            )))
+        }
+    }
+
+    /// Rebase `changesets`, a stack that was stored with
+    /// `store_hg_changesets`, onto `bookmark` and move the bookmark to the
+    /// rebased head, as pushrebase does for pushes over the wire protocol.
+    /// The bookmark's hooks are run on the stack before it is rebased.
+    pub async fn pushrebase(
+        &self,
+        bookmark: &str,
+        changesets: Vec<HgChangesetId>,
+    ) -> Result<HgPushrebaseOutcome, MononokeError> {
+        self.repo.check_bookmark_write_permission(bookmark).await?;
+        let ctx = self.ctx();
+        let blob_repo = self.blob_repo();
+        let bookmark = bookmark_name(bookmark)?;
+
+        let mut bonsai_ids = HashMap::new();
+        let mut pushed = HashSet::new();
+        for hg_cs_id in changesets {
+            let cs_id = self.bonsai_id(hg_cs_id).await?;
+            let bonsai = cs_id
+                .load(ctx.clone(), blob_repo.blobstore())
+                .compat()
+                .await?;
+            bonsai_ids.insert(cs_id, hg_cs_id);
+            pushed.insert(bonsai);
+        }
+
+        let hook_warnings = self.run_hooks(&bookmark, pushed.iter()).await?;
+
+        let onto_bookmark = OntoBookmarkParams::new(bookmark);
+        let params = self.repo.pushrebase_params();
+        let hooks = get_pushrebase_hooks(blob_repo, params);
+        let outcome = do_pushrebase_bonsai(
+            ctx,
+            blob_repo,
+            &params.flags,
+            &onto_bookmark,
+            &pushed,
+            &None,
+            &hooks,
+        )
+        .await?;
+
+        let head = self.hg_id(outcome.head).await?;
+        let mut rebased = Vec::with_capacity(outcome.rebased_changesets.len());
+        for pair in outcome.rebased_changesets {
+            let old = bonsai_ids.get(&pair.id_old).cloned().ok_or_else(|| {
+                MononokeError::from(anyhow::format_err!(
+                    "pushrebase rebased unexpected changeset {}",
+                    pair.id_old
+                ))
+            })?;
+            rebased.push((old, self.hg_id(pair.id_new).await?));
+        }
+
+        Ok(HgPushrebaseOutcome {
+            head,
+            rebased,
+            hook_warnings,
+        })
+    }
+
+    /// Check that a bookmark that may only be moved forwards is not moved
+    /// backwards, sideways or deleted.
+    async fn check_fast_forward(
+        &self,
+        bookmark: &BookmarkName,
+        old_target: Option<ChangesetId>,
+        new_target: Option<ChangesetId>,
+    ) -> Result<(), MononokeError> {
+        if !self.repo.bookmark_attrs().is_fast_forward_only(bookmark) {
+            return Ok(());
+        }
+        match (old_target, new_target) {
+            (Some(old), Some(new)) if old != new => {
+                let is_ancestor = self
+                    .repo
+                    .skiplist_index()
+                    .is_ancestor(
+                        self.ctx().clone(),
+                        self.blob_repo().get_changeset_fetcher(),
+                        old,
+                        new,
+                    )
+                    .compat()
+                    .await?;
+                if !is_ancestor {
+                    return Err(MononokeError::InvalidRequest(format!(
+                        "bookmark {} can only be moved forwards, and {} is not a descendant of {}",
+                        bookmark, new, old
+                    )));
+                }
+                Ok(())
+            }
+            (Some(_old), None) => Err(MononokeError::InvalidRequest(format!(
+                "bookmark {} can't be deleted",
+                bookmark
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The changesets that become public when a bookmark moves from
+    /// `old_target` to `new_target`: those that are ancestors of the new
+    /// target, but not of the old target or of any publishing bookmark.
+    async fn landed_changesets(
+        &self,
+        old_target: Option<ChangesetId>,
+        new_target: ChangesetId,
+    ) -> Result<Vec<BonsaiChangeset>, MononokeError> {
+        let ctx = self.ctx();
+        let blob_repo = self.blob_repo();
+
+        let mut excludes: Vec<ChangesetId> = blob_repo
+            .get_bonsai_publishing_bookmarks_maybe_stale(ctx.clone())
+            .map(|(_bookmark, cs_id)| cs_id)
+            .collect()
+            .compat()
+            .await?;
+        excludes.extend(old_target);
+
+        let lca_hint: Arc<dyn LeastCommonAncestorsHint> = self.repo.skiplist_index().clone();
+        let cs_ids = DifferenceOfUnionsOfAncestorsNodeStream::new_with_excludes(
+            ctx.clone(),
+            &blob_repo.get_changeset_fetcher(),
+            lca_hint,
+            vec![new_target],
+            excludes,
+        )
+        .take(MAX_LANDED_CHANGESETS as u64 + 1)
+        .collect()
+        .compat()
+        .await?;
+        if cs_ids.len() > MAX_LANDED_CHANGESETS {
+            return Err(MononokeError::InvalidRequest(format!(
+                "moving the bookmark to {} would make more than {} changesets public",
+                new_target, MAX_LANDED_CHANGESETS
+            )));
+        }
+
+        let changesets = cs_ids.into_iter().map(|cs_id| {
+            cs_id
+                .load(ctx.clone(), blob_repo.blobstore())
+                .compat()
+                .map_err(MononokeError::from)
+        });
+        try_join_all(changesets).await
+    }
+
+    /// Run the hooks for `bookmark` on `changesets`, failing if any of them
+    /// rejects a changeset. Returns the warnings of advisory hooks, which are
+    /// logged as well.
+    async fn run_hooks<'a>(
+        &self,
+        bookmark: &BookmarkName,
+        changesets: impl Iterator<Item = &'a BonsaiChangeset> + Clone + Itertools,
+    ) -> Result<Vec<String>, MononokeError> {
+        let outcomes = self
+            .repo
+            .hook_manager()?
+            .run_hooks_for_bookmark(self.ctx(), changesets, bookmark, None)
+            .await?;
+
+        let mut rejections = Vec::new();
+        let mut warnings = Vec::new();
+        for outcome in outcomes {
+            let (messages, failure) = if outcome.is_warning() {
+                (&mut warnings, outcome.into_warning())
+            } else {
+                (&mut rejections, outcome.into_rejection())
+            };
+            if let Some((hook_name, cs_id, info)) = failure {
+                let hg_cs_id = self.hg_id(cs_id).await?;
+                messages.push(format!(
+                    "{} for {}: {}",
+                    hook_name, hg_cs_id, info.long_description
+                ));
+            }
+        }
+        for warning in &warnings {
+            warn!(self.ctx().logger(), "hook warning: {}", warning);
+        }
+        if rejections.is_empty() {
+            Ok(warnings)
+        } else {
+            Err(MononokeError::InvalidRequest(format!(
+                "hooks failed:\n{}",
+                rejections.join("\n")
+            )))
+        }
+    }
+
+    async fn bonsai_id(&self, hg_cs_id: HgChangesetId) -> Result<ChangesetId, MononokeError> {
+        self.blob_repo()
+            .get_bonsai_from_hg(self.ctx().clone(), hg_cs_id)
+            .compat()
+            .await?
+            .ok_or_else(|| MononokeError::InvalidRequest(format!("unknown changeset {}", hg_cs_id)))
+    }
+
+    async fn maybe_bonsai_id(
+        &self,
+        hg_cs_id: Option<HgChangesetId>,
+    ) -> Result<Option<ChangesetId>, MononokeError> {
+        match hg_cs_id {
+            Some(hg_cs_id) => Ok(Some(self.bonsai_id(hg_cs_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The Mercurial id of a changeset, deriving it if needed, as is the case
+    /// for changesets pushrebase just created.
+    async fn hg_id(&self, cs_id: ChangesetId) -> Result<HgChangesetId, MononokeError> {
+        Ok(self
+            .blob_repo()
+            .get_hg_from_bonsai_changeset(self.ctx().clone(), cs_id)
+            .compat()
+            .await?)
+    }
+}
+
+/// The handle for a parent changeset, either one uploaded earlier in the same
+/// request or one already in the repo.
+fn parent_handle(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    handles: &HashMap<HgChangesetId, ChangesetHandle>,
+    parent: Option<HgNodeHash>,
+) -> Option<ChangesetHandle> {
+    parent.map(|p| {
+        let hg_cs_id = HgChangesetId::new(p);
+        match handles.get(&hg_cs_id) {
+            Some(handle) => handle.clone(),
+            None => ChangesetHandle::ready_cs_handle(ctx.clone(), repo.clone(), hg_cs_id),
+        }
+    })
+}
+
+fn bookmark_name(bookmark: &str) -> Result<BookmarkName, MononokeError> {
+    BookmarkName::new(bookmark).map_err(|e| MononokeError::InvalidRequest(e.to_string()))
+}
+
+fn invalid_changeset(hg_cs_id: HgChangesetId, e: impl std::fmt::Display) -> MononokeError {
+    MononokeError::InvalidRequest(format!("invalid changeset {}: {}", hg_cs_id, e))
+}
@@ -65,6 +65,8 @@ pub use crate::tree::{TreeContext, TreeEntry, TreeId, TreeSummary};
 
 // Re-export types that are useful for clients.
 pub use context::{CoreContext, LoggingContainer, SessionContainer};
+pub use dag::protocol::CloneData;
+pub use segmented_changelog::dag::Location;
 
 /// An instance of Mononoke, which may manage multiple repositories.
 pub struct Mononoke {
@@ -140,6 +142,29 @@ impl Mononoke {
         Ok(Self { repos })
     }
 
+    #[cfg(test)]
+    async fn new_test_segmented_changelog(
+        ctx: CoreContext,
+        repos: impl IntoIterator<Item = (String, BlobRepo, segmented_changelog::dag::Dag)>,
+    ) -> Result<Self, Error> {
+        use futures_util::stream::{FuturesOrdered, TryStreamExt};
+        let repos = repos
+            .into_iter()
+            .map(move |(name, repo, dag)| {
+                cloned!(ctx);
+                async move {
+                    Repo::new_test_segmented_changelog(ctx.clone(), repo, dag)
+                        .await
+                        .map(move |repo| (name, Arc::new(repo)))
+                }
+            })
+            .collect::<FuturesOrdered<_>>()
+            .try_collect()
+            .await?;
+
+        Ok(Self { repos })
+    }
+
     #[cfg(test)]
     async fn new_test_xrepo(
         ctx: CoreContext,
@@ -177,6 +202,36 @@ impl Mononoke {
         Ok(Self { repos })
     }
 
+    #[cfg(test)]
+    async fn new_test_hooks(
+        ctx: CoreContext,
+        repos: impl IntoIterator<
+            Item = (
+                String,
+                BlobRepo,
+                hooks::HookManager,
+                Vec<metaconfig_types::BookmarkParams>,
+            ),
+        >,
+    ) -> Result<Self, Error> {
+        use futures_util::stream::{FuturesOrdered, TryStreamExt};
+        let repos = repos
+            .into_iter()
+            .map(move |(name, repo, hook_manager, bookmarks)| {
+                cloned!(ctx);
+                async move {
+                    Repo::new_test_hooks(ctx.clone(), repo, hook_manager, bookmarks)
+                        .await
+                        .map(move |repo| (name, Arc::new(repo)))
+                }
+            })
+            .collect::<FuturesOrdered<_>>()
+            .try_collect()
+            .await?;
+
+        Ok(Self { repos })
+    }
+
     /// Temporary function to create directly from parts.
     pub fn new_from_parts(
         repos: impl IntoIterator<
@@ -9,7 +9,7 @@ use std::fmt;
 use std::{
     borrow::Cow,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{format_err, Error};
@@ -21,30 +21,45 @@ use bookmarks::{BookmarkName, BookmarkPrefix};
 use changeset_info::ChangesetInfo;
 use context::CoreContext;
 use cross_repo_sync::{CommitSyncRepos, CommitSyncer};
+use dag::protocol::CloneData;
 use derived_data::BonsaiDerived;
 use fbinit::FacebookInit;
 use filestore::{Alias, FetchKey};
 use futures::compat::{Future01CompatExt, Stream01CompatExt};
-use futures::future::{try_join, try_join_all};
+use futures::future::{try_join, try_join3, try_join_all};
 use futures::StreamExt as NewStreamExt;
 use futures_ext::StreamExt;
 use futures_old::stream::{self, Stream};
+use hooks::{hook_loader::load_hooks, HookManager};
+use hooks_content_stores::blobrepo_text_only_fetcher;
+#[cfg(test)]
+use hooks_content_stores::InMemoryFileContentFetcher;
 use itertools::Itertools;
+use lfs_locks::SqlLfsLocks;
 use mercurial_types::Globalrev;
 use metaconfig_types::{
-    CommitSyncConfig, CommonConfig, RepoConfig, SourceControlServiceMonitoring,
-    SourceControlServiceParams,
+    BookmarkAttrs, CommitSyncConfig, CommonConfig, PushrebaseParams, RepoConfig,
+    SourceControlServiceMonitoring, SourceControlServiceParams,
 };
+#[cfg(test)]
+use metaconfig_types::{BookmarkParams, HookManagerParams};
 use mononoke_types::{
     hash::{GitSha1, Sha1, Sha256},
     Generation,
 };
 use permission_checker::{ArcPermissionChecker, MononokeIdentitySet, PermissionCheckerBuilder};
 use revset::AncestorsNodeStream;
+use scuba_ext::ScubaSampleBuilder;
+use segmented_changelog::{
+    dag::{Dag, Location},
+    manager::{SegmentedChangelogManager, SegmentedChangelogSqlConnections},
+    periodic_reload::PeriodicReloadDag,
+};
 use skiplist::{fetch_skiplist_index, SkiplistIndex};
 use slog::{debug, error, Logger};
 #[cfg(test)]
 use sql_construct::SqlConstruct;
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
 use sql_ext::facebook::MysqlOptions;
 use stats_facebook::service_data::{get_service_data_singleton, ServiceData};
 use std::collections::HashSet;
@@ -66,11 +81,15 @@ const COMMON_COUNTER_PREFIX: &'static str = "mononoke.api";
 const STALENESS_INFIX: &'static str = "staleness.secs";
 const MISSING_FROM_CACHE_INFIX: &'static str = "missing_from_cache";
 const MISSING_FROM_REPO_INFIX: &'static str = "missing_from_repo";
+// How often to look for a newer segmented changelog saved by the tailer
+const SEGMENTED_CHANGELOG_RELOAD_PERIOD: Duration = Duration::from_secs(60);
 
 pub(crate) struct Repo {
     pub(crate) name: String,
     pub(crate) blob_repo: BlobRepo,
     pub(crate) skiplist_index: Arc<SkiplistIndex>,
+    // Answers ancestry queries for the commits it covers, if enabled for the repo
+    pub(crate) segmented_changelog: Option<PeriodicReloadDag>,
     pub(crate) warm_bookmarks_cache: Arc<WarmBookmarksCache>,
     // This doesn't really belong here, but until we have production mappings, we can't do a better job
     pub(crate) synced_commit_mapping: Arc<dyn SyncedCommitMapping>,
@@ -79,6 +98,11 @@ pub(crate) struct Repo {
     pub(crate) monitoring_config: Option<SourceControlServiceMonitoring>,
     pub(crate) perm_checker: ArcPermissionChecker,
     pub(crate) commit_sync_config: Option<CommitSyncConfig>,
+    pub(crate) pushrebase_params: PushrebaseParams,
+    pub(crate) bookmark_attrs: BookmarkAttrs,
+    // Runs the hooks for bookmarks moved through this API. Repos that can't be
+    // written to don't have one.
+    pub(crate) hook_manager: Option<Arc<HookManager>>,
 }
 
 #[derive(Clone)]
@@ -118,6 +142,42 @@ pub async fn open_synced_commit_mapping(
     ))
 }
 
+/// Load the segmented changelog last saved for the repo, and reload it whenever the tailer
+/// saves a newer one. It covers the commits that existed when it was saved; queries about
+/// newer commits fall back to other indexes.
+pub async fn open_segmented_changelog(
+    ctx: &CoreContext,
+    config: &RepoConfig,
+    mysql_options: MysqlOptions,
+    readonly_storage: ReadOnlyStorage,
+    blob_repo: &BlobRepo,
+) -> Result<Option<PeriodicReloadDag>, Error> {
+    if !config.segmented_changelog_config.enabled {
+        return Ok(None);
+    }
+    let sql_factory = make_metadata_sql_factory(
+        ctx.fb,
+        config.storage_config.metadata.clone(),
+        mysql_options,
+        readonly_storage,
+        ctx.logger().clone(),
+    )
+    .compat()
+    .await?;
+    let connections = sql_factory
+        .open::<SegmentedChangelogSqlConnections>()
+        .compat()
+        .await?;
+    let manager = SegmentedChangelogManager::new(connections, Arc::new(blob_repo.get_blobstore()));
+    let dag = PeriodicReloadDag::start(
+        ctx.clone(),
+        Arc::new(manager),
+        SEGMENTED_CHANGELOG_RELOAD_PERIOD,
+    )
+    .await?;
+    Ok(Some(dag))
+}
+
 impl Repo {
     pub(crate) async fn new(
         fb: FacebookInit,
@@ -158,12 +218,8 @@ impl Repo {
 
         let ctx = CoreContext::new_with_logger(fb, logger.clone());
 
-        let perm_checker = async {
-            match &config.hipster_acl {
-                Some(acl) => PermissionCheckerBuilder::acl_for_repo(fb, acl).await,
-                None => Ok(PermissionCheckerBuilder::always_allow()),
-            }
-        };
+        let perm_checker =
+            PermissionCheckerBuilder::for_repo(fb, &name, config.hipster_acl.as_deref());
 
         let skiplist_index = fetch_skiplist_index(
             ctx.clone(),
@@ -178,18 +234,52 @@ impl Repo {
                 .await?,
         );
 
-        let (perm_checker, skiplist_index) = try_join(perm_checker, skiplist_index).await?;
+        let segmented_changelog =
+            open_segmented_changelog(&ctx, &config, mysql_options, readonly_storage, &blob_repo);
+
+        let (perm_checker, skiplist_index, segmented_changelog) =
+            try_join3(perm_checker, skiplist_index, segmented_changelog).await?;
+        let perm_checker = ArcPermissionChecker::from(perm_checker);
+
+        let hook_manager = if service_config.permit_writes {
+            let mut hook_manager = HookManager::new(
+                fb,
+                blobrepo_text_only_fetcher(blob_repo.clone(), config.hook_max_file_size),
+                config.hook_manager_params.clone().unwrap_or_default(),
+                ScubaSampleBuilder::with_discard(),
+            )
+            .await?;
+            if config.lfs.locking_enabled {
+                let locks = SqlLfsLocks::with_metadata_database_config(
+                    fb,
+                    &config.storage_config.metadata,
+                    mysql_options,
+                    readonly_storage.0,
+                )
+                .await?;
+                hook_manager.set_lfs_locks(blob_repo.get_repoid(), locks);
+            }
+            hook_manager.set_permission_checker(perm_checker.clone());
+            load_hooks(fb, &mut hook_manager, config.clone(), &HashSet::new())?;
+            Some(Arc::new(hook_manager))
+        } else {
+            None
+        };
 
         Ok(Self {
             name,
             blob_repo,
             skiplist_index,
+            segmented_changelog,
             warm_bookmarks_cache,
             synced_commit_mapping,
             service_config,
             monitoring_config,
-            perm_checker: ArcPermissionChecker::from(perm_checker),
+            perm_checker,
             commit_sync_config: config.commit_sync_config,
+            pushrebase_params: config.pushrebase,
+            bookmark_attrs: BookmarkAttrs::new(config.bookmarks),
+            hook_manager,
         })
     }
 
@@ -207,6 +297,7 @@ impl Repo {
             name,
             blob_repo,
             skiplist_index,
+            segmented_changelog: None,
             warm_bookmarks_cache,
             synced_commit_mapping,
             service_config: SourceControlServiceParams {
@@ -215,6 +306,9 @@ impl Repo {
             monitoring_config,
             perm_checker: ArcPermissionChecker::from(PermissionCheckerBuilder::always_allow()),
             commit_sync_config,
+            pushrebase_params: PushrebaseParams::default(),
+            bookmark_attrs: BookmarkAttrs::new(Vec::new()),
+            hook_manager: None,
         }
     }
 
@@ -226,6 +320,28 @@ impl Repo {
             blob_repo,
             None,
             Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
+            None,
+            None,
+            Vec::new(),
+        )
+        .await
+    }
+
+    #[cfg(test)]
+    /// Construct a Repo from a test BlobRepo and a segmented changelog built for it
+    pub(crate) async fn new_test_segmented_changelog(
+        ctx: CoreContext,
+        blob_repo: BlobRepo,
+        segmented_changelog: Dag,
+    ) -> Result<Self, Error> {
+        Self::new_test_common(
+            ctx,
+            blob_repo,
+            None,
+            Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
+            Some(PeriodicReloadDag::fixed(segmented_changelog)),
+            None,
+            Vec::new(),
         )
         .await
     }
@@ -243,6 +359,44 @@ impl Repo {
             blob_repo,
             Some(commit_sync_config),
             synced_commit_mapping,
+            None,
+            None,
+            Vec::new(),
+        )
+        .await
+    }
+
+    #[cfg(test)]
+    /// Construct a Repo from a test BlobRepo, with hooks and bookmark
+    /// attributes for testing writes
+    pub(crate) async fn new_test_hooks(
+        ctx: CoreContext,
+        blob_repo: BlobRepo,
+        hook_manager: HookManager,
+        bookmarks: Vec<BookmarkParams>,
+    ) -> Result<Self, Error> {
+        Self::new_test_common(
+            ctx,
+            blob_repo,
+            None,
+            Arc::new(SqlSyncedCommitMapping::with_sqlite_in_memory()?),
+            None,
+            Some(hook_manager),
+            bookmarks,
+        )
+        .await
+    }
+
+    #[cfg(test)]
+    /// A hook manager without any hooks, for tests
+    pub(crate) async fn new_test_hook_manager(fb: FacebookInit) -> Result<HookManager, Error> {
+        HookManager::new(
+            fb,
+            Box::new(InMemoryFileContentFetcher::new()),
+            HookManagerParams {
+                disable_acl_checker: true,
+            },
+            ScubaSampleBuilder::with_discard(),
         )
         .await
     }
@@ -254,16 +408,24 @@ impl Repo {
         blob_repo: BlobRepo,
         commit_sync_config: Option<CommitSyncConfig>,
         synced_commit_mapping: Arc<dyn SyncedCommitMapping>,
+        segmented_changelog: Option<PeriodicReloadDag>,
+        hook_manager: Option<HookManager>,
+        bookmarks: Vec<BookmarkParams>,
     ) -> Result<Self, Error> {
         let warm_bookmarks_cache = Arc::new(
             WarmBookmarksCache::new(ctx.clone(), blob_repo.clone())
                 .compat()
                 .await?,
         );
+        let hook_manager = match hook_manager {
+            Some(hook_manager) => hook_manager,
+            None => Self::new_test_hook_manager(ctx.fb).await?,
+        };
         Ok(Self {
             name: String::from("test"),
             blob_repo,
             skiplist_index: Arc::new(SkiplistIndex::new()),
+            segmented_changelog,
             warm_bookmarks_cache,
             synced_commit_mapping,
             service_config: SourceControlServiceParams {
@@ -272,6 +434,9 @@ impl Repo {
             monitoring_config: None,
             perm_checker: ArcPermissionChecker::from(PermissionCheckerBuilder::always_allow()),
             commit_sync_config,
+            pushrebase_params: PushrebaseParams::default(),
+            bookmark_attrs: BookmarkAttrs::new(bookmarks),
+            hook_manager: Some(Arc::new(hook_manager)),
         })
     }
 
@@ -493,6 +658,16 @@ impl Repo {
         &self,
         ctx: &CoreContext,
         mode: PermMode,
+    ) -> Result<(), MononokeError> {
+        self.check_permissions_for_bookmark(ctx, mode, None).await
+    }
+
+    /// Check permissions on a bookmark, or on the whole repo if `bookmark` is `None`.
+    async fn check_permissions_for_bookmark(
+        &self,
+        ctx: &CoreContext,
+        mode: PermMode,
+        bookmark: Option<&str>,
     ) -> Result<(), MononokeError> {
         let mode = match mode {
             PermMode::Read => "read",
@@ -503,10 +678,23 @@ impl Repo {
         let identities =
             identities.map_or_else(|| Cow::Owned(MononokeIdentitySet::new()), Cow::Borrowed);
 
-        if !self.perm_checker.check_set(&*identities, &[mode]).await? {
+        let permitted = match bookmark {
+            Some(bookmark) => {
+                self.perm_checker
+                    .check_set_for_bookmark(&*identities, bookmark, &[mode])
+                    .await?
+            }
+            None => self.perm_checker.check_set(&*identities, &[mode]).await?,
+        };
+
+        if !permitted {
+            let reponame = match bookmark {
+                Some(bookmark) => format!("{} (bookmark {})", self.name, bookmark),
+                None => self.name.clone(),
+            };
             debug!(
                 ctx.logger(),
-                "Permission denied: {} access to {}", mode, self.name
+                "Permission denied: {} access to {}", mode, reponame
             );
             let identities = if identities.is_empty() {
                 "<none>".to_string()
@@ -516,7 +704,7 @@ impl Repo {
             return Err(MononokeError::PermissionDenied {
                 mode,
                 identities,
-                reponame: self.name.clone(),
+                reponame,
             });
         }
         Ok(())
@@ -558,10 +746,18 @@ impl RepoContext {
     }
 
     /// The skiplist index for the referenced repository.
-    pub(crate) fn skiplist_index(&self) -> &SkiplistIndex {
+    pub(crate) fn skiplist_index(&self) -> &Arc<SkiplistIndex> {
         &self.repo.skiplist_index
     }
 
+    /// The segmented changelog for the referenced repository, if it has one.
+    pub(crate) fn segmented_changelog(&self) -> Option<Arc<Dag>> {
+        self.repo
+            .segmented_changelog
+            .as_ref()
+            .map(PeriodicReloadDag::dag)
+    }
+
     /// The commit sync mapping for the referenced repository
     pub(crate) fn synced_commit_mapping(&self) -> &Arc<dyn SyncedCommitMapping> {
         &self.repo.synced_commit_mapping
@@ -572,6 +768,24 @@ impl RepoContext {
         &self.repo.warm_bookmarks_cache
     }
 
+    /// How pushes to the referenced repository are rebased.
+    pub(crate) fn pushrebase_params(&self) -> &PushrebaseParams {
+        &self.repo.pushrebase_params
+    }
+
+    /// The attributes of the referenced repository's bookmarks.
+    pub(crate) fn bookmark_attrs(&self) -> &BookmarkAttrs {
+        &self.repo.bookmark_attrs
+    }
+
+    /// The hook manager that checks bookmark moves in the referenced
+    /// repository.
+    pub(crate) fn hook_manager(&self) -> Result<&HookManager, MononokeError> {
+        self.repo.hook_manager.as_deref().ok_or_else(|| {
+            MononokeError::NotAvailable(format!("hooks are not loaded for repo {}", self.name()))
+        })
+    }
+
     pub(crate) fn derive_changeset_info_enabled(&self) -> bool {
         self.blob_repo()
             .get_derived_data_config()
@@ -938,6 +1152,52 @@ impl RepoContext {
         Ok(maybe_cs_id.map(|cs_id| ChangesetContext::new(other.clone(), cs_id)))
     }
 
+    /// The segmented changelog, or an error if the repo does not have one.
+    fn require_segmented_changelog(&self) -> Result<Arc<Dag>, MononokeError> {
+        self.segmented_changelog().ok_or_else(|| {
+            MononokeError::NotAvailable(format!(
+                "segmented changelog is not enabled for repo {}",
+                self.name()
+            ))
+        })
+    }
+
+    /// The changeset at `location` and its first ancestors, `count` changesets in total.
+    pub async fn location_to_changeset_ids(
+        &self,
+        location: Location<ChangesetId>,
+        count: u64,
+    ) -> Result<Vec<ChangesetId>, MononokeError> {
+        let cs_ids = self
+            .require_segmented_changelog()?
+            .location_to_many_changeset_ids(location, count)
+            .await?;
+        Ok(cs_ids)
+    }
+
+    /// Express `cs_id` relative to `master_heads`, or to a commit that all clients of the
+    /// segmented changelog know. None if `cs_id` is not an ancestor of `master_heads`.
+    pub async fn changeset_id_to_location(
+        &self,
+        master_heads: Vec<ChangesetId>,
+        cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>, MononokeError> {
+        let location = self
+            .require_segmented_changelog()?
+            .changeset_id_to_location(&master_heads, cs_id)
+            .await?;
+        Ok(location)
+    }
+
+    /// The segments of the commit graph, used by clients to clone the graph without all the
+    /// commit hashes.
+    pub async fn segmented_changelog_clone_data(
+        &self,
+    ) -> Result<CloneData<ChangesetId>, MononokeError> {
+        let clone_data = self.require_segmented_changelog()?.clone_data().await?;
+        Ok(clone_data)
+    }
+
     /// Get a write context to make changes to this repository.
     pub async fn write(self) -> Result<RepoWriteContext, MononokeError> {
         if !self.repo.service_config.permit_writes {
@@ -954,6 +1214,16 @@ impl RepoContext {
         Ok(RepoWriteContext::new(self))
     }
 
+    /// Check the user is permitted to move this bookmark.
+    pub(crate) async fn check_bookmark_write_permission(
+        &self,
+        bookmark: &str,
+    ) -> Result<(), MononokeError> {
+        self.repo
+            .check_permissions_for_bookmark(&self.ctx, PermMode::Write, Some(bookmark))
+            .await
+    }
+
     /// Get an HgRepoContext to access this repo's data in Mercurial-specific formats.
     pub fn hg(self) -> HgRepoContext {
         HgRepoContext::new(self)
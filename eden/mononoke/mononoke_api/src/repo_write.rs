@@ -27,6 +27,7 @@ use mononoke_types::{
 use crate::changeset::ChangesetContext;
 use crate::errors::MononokeError;
 use crate::file::{FileId, FileType};
+use crate::hg::HgRepoWriteContext;
 use crate::path::MononokePath;
 use crate::repo::RepoContext;
 use crate::specifiers::ChangesetSpecifier;
@@ -278,6 +279,11 @@ impl RepoWriteContext {
         Self { repo }
     }
 
+    /// Get an HgRepoWriteContext to write to this repo in Mercurial-specific formats.
+    pub fn hg(self) -> HgRepoWriteContext {
+        HgRepoWriteContext::new(self)
+    }
+
     /// Create a new changeset in the repository.
     ///
     /// The new changeset is created with the given metadata by unioning the
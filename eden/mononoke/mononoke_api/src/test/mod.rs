@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+mod test_hg_repo_write;
 mod test_history;
 mod test_repo;
 mod test_repo_write;
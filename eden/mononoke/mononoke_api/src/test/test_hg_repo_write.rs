@@ -0,0 +1,233 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use anyhow::Error;
+use assert_matches::assert_matches;
+use async_trait::async_trait;
+use bookmarks::BookmarkName;
+use bytes::Bytes;
+use chrono::{FixedOffset, TimeZone};
+use fbinit::FacebookInit;
+use fixtures::linear;
+use futures::compat::Future01CompatExt;
+use hooks::{ChangesetHook, HookExecution, HookManager, HookRejectionInfo};
+use hooks_content_stores::FileContentFetcher;
+use mercurial_types::HgChangesetId;
+use metaconfig_types::{BookmarkParams, HookConfig};
+use mononoke_types::BonsaiChangeset;
+
+use crate::repo::Repo;
+use crate::{
+    CoreContext, CreateChange, FileType, Mononoke, MononokeError, MononokePath, RepoContext,
+};
+
+/// Head of `master` in the linear fixture, and two of its ancestors.
+const MASTER: &str = "79a13814c5ce7330173ec04d279bf95ab3f652fb";
+const MASTER_PARENT: &str = "a5ffa77602a066db7d5cfb9fb5823a0895717c5a";
+const MASTER_GRANDPARENT: &str = "3c15267ebf11807f3d772eb891272b911ec68759";
+
+struct RejectAllHook;
+
+#[async_trait]
+impl ChangesetHook for RejectAllHook {
+    async fn run<'this: 'cs, 'ctx: 'this, 'cs, 'fetcher: 'cs>(
+        &'this self,
+        _ctx: &'ctx CoreContext,
+        _bookmark: &BookmarkName,
+        _changeset: &'cs BonsaiChangeset,
+        _content_fetcher: &'fetcher dyn FileContentFetcher,
+    ) -> Result<HookExecution, Error> {
+        Ok(HookExecution::Rejected(HookRejectionInfo::new(
+            "all changesets are rejected",
+        )))
+    }
+}
+
+async fn test_repo(
+    fb: FacebookInit,
+    hook_manager: HookManager,
+    bookmarks: Vec<BookmarkParams>,
+) -> Result<RepoContext, Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mononoke = Mononoke::new_test_hooks(
+        ctx.clone(),
+        vec![(
+            "test".to_string(),
+            linear::getrepo(fb).await,
+            hook_manager,
+            bookmarks,
+        )],
+    )
+    .await?;
+    Ok(mononoke.repo(ctx, "test").await?.expect("repo exists"))
+}
+
+/// Create a changeset on top of `master` that no bookmark points to, and
+/// return its Mercurial id.
+async fn create_draft(repo: &RepoContext) -> Result<HgChangesetId, Error> {
+    let parent = repo
+        .blob_repo()
+        .get_bonsai_from_hg(repo.ctx().clone(), HgChangesetId::from_str(MASTER)?)
+        .compat()
+        .await?
+        .expect("master exists");
+    let mut changes = BTreeMap::new();
+    changes.insert(
+        MononokePath::try_from("DRAFT")?,
+        CreateChange::NewContent(Bytes::from("draft\n"), FileType::Regular, None),
+    );
+    let cs = repo
+        .clone()
+        .write()
+        .await?
+        .create_changeset(
+            vec![parent],
+            String::from("Test Author <test@example.com>"),
+            FixedOffset::east(0).ymd(2000, 2, 1).and_hms(12, 0, 0),
+            None,
+            None,
+            String::from("Draft commit"),
+            BTreeMap::new(),
+            changes,
+        )
+        .await?;
+    Ok(repo
+        .blob_repo()
+        .get_hg_from_bonsai_changeset(repo.ctx().clone(), cs.id())
+        .compat()
+        .await?)
+}
+
+#[fbinit::compat_test]
+async fn move_fast_forward_only_bookmark(fb: FacebookInit) -> Result<(), Error> {
+    let bookmarks = vec![BookmarkParams {
+        bookmark: BookmarkName::new("master")?.into(),
+        hooks: vec![],
+        only_fast_forward: true,
+        rewrite_dates: None,
+        allowed_users: None,
+    }];
+    let repo = test_repo(fb, Repo::new_test_hook_manager(fb).await?, bookmarks).await?;
+    let master = HgChangesetId::from_str(MASTER)?;
+    let parent = HgChangesetId::from_str(MASTER_PARENT)?;
+    let grandparent = HgChangesetId::from_str(MASTER_GRANDPARENT)?;
+
+    // Moving backwards and deleting are forbidden.
+    let hg_repo = repo.clone().write().await?.hg();
+    assert_matches!(
+        hg_repo
+            .move_bookmark("master", Some(master), Some(parent))
+            .await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        hg_repo.move_bookmark("master", Some(master), None).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    // Other bookmarks can move in any direction.
+    hg_repo.move_bookmark("other", None, Some(parent)).await?;
+    hg_repo
+        .move_bookmark("other", Some(parent), Some(grandparent))
+        .await?;
+    hg_repo
+        .move_bookmark("other", Some(grandparent), None)
+        .await?;
+
+    // Moving forwards is allowed.
+    let draft = create_draft(&repo).await?;
+    hg_repo
+        .move_bookmark("master", Some(master), Some(draft))
+        .await?;
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn move_bookmark_runs_hooks(fb: FacebookInit) -> Result<(), Error> {
+    let mut hook_manager = Repo::new_test_hook_manager(fb).await?;
+    hook_manager.register_changeset_hook(
+        "reject_all",
+        Box::new(RejectAllHook),
+        HookConfig::default(),
+    );
+    hook_manager.set_hooks_for_bookmark(
+        BookmarkName::new("master")?.into(),
+        vec!["reject_all".to_string()],
+    );
+    let repo = test_repo(fb, hook_manager, vec![]).await?;
+    let master = HgChangesetId::from_str(MASTER)?;
+    let parent = HgChangesetId::from_str(MASTER_PARENT)?;
+    let draft = create_draft(&repo).await?;
+    let hg_repo = repo.clone().write().await?.hg();
+
+    // The draft is rejected when it lands on master, both by moving the
+    // bookmark and by pushrebasing onto it.
+    assert_matches!(
+        hg_repo
+            .move_bookmark("master", Some(master), Some(draft))
+            .await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+    assert_matches!(
+        hg_repo.pushrebase("master", vec![draft]).await,
+        Err(MononokeError::InvalidRequest(_))
+    );
+
+    // Moving master to a changeset that is already public runs no hooks.
+    hg_repo
+        .move_bookmark("master", Some(master), Some(parent))
+        .await?;
+
+    // Bookmarks without hooks accept the draft.
+    hg_repo.move_bookmark("other", None, Some(draft)).await?;
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn advisory_hooks_return_warnings(fb: FacebookInit) -> Result<(), Error> {
+    let mut hook_manager = Repo::new_test_hook_manager(fb).await?;
+    hook_manager.register_changeset_hook(
+        "reject_all",
+        Box::new(RejectAllHook),
+        HookConfig {
+            advisory: true,
+            ..HookConfig::default()
+        },
+    );
+    hook_manager.set_hooks_for_bookmark(
+        BookmarkName::new("master")?.into(),
+        vec!["reject_all".to_string()],
+    );
+    let repo = test_repo(fb, hook_manager, vec![]).await?;
+    let master = HgChangesetId::from_str(MASTER)?;
+    let draft = create_draft(&repo).await?;
+    let hg_repo = repo.clone().write().await?.hg();
+
+    // An advisory hook lets the draft land, but its rejection comes back as
+    // a warning, both by moving the bookmark and by pushrebasing onto it.
+    let warnings = hg_repo
+        .move_bookmark("master", Some(master), Some(draft))
+        .await?;
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with(&format!("reject_all for {}: ", draft)));
+
+    let warnings = hg_repo
+        .move_bookmark("master", Some(draft), Some(master))
+        .await?;
+    assert!(warnings.is_empty());
+
+    let outcome = hg_repo.pushrebase("master", vec![draft]).await?;
+    assert_eq!(outcome.hook_warnings.len(), 1);
+
+    Ok(())
+}
@@ -32,6 +32,7 @@ use mononoke_types::{
     hash::{GitSha1, RichGitSha1, Sha1, Sha256},
     MPath,
 };
+use segmented_changelog::dag::Dag;
 use slog::info;
 use synced_commit_mapping::SyncedCommitMapping;
 use tests_utils::{bookmark, resolve_cs_id, CreateCommitContext};
@@ -213,6 +214,130 @@ async fn commit_is_ancestor_of(fb: FacebookInit) -> Result<(), Error> {
     Ok(())
 }
 
+const BRANCH_UNEVEN_BRANCH_1_TOP: &str = "5d43888a3c972fe68c224f93d41b30e9f888df7c";
+const BRANCH_UNEVEN_BRANCH_1_BOTTOM: &str = "d7542c9db7f4c77dab4b315edd328edf1514952f";
+const BRANCH_UNEVEN_BRANCH_2: &str = "1d8a907f7b4bf50c6a09c16361e2205047ecc5e5";
+const BRANCH_UNEVEN_BASE: &str = "15c40d0abc36d47fb51c8eaec51ac7aad31f669c";
+
+// Mononoke instances serving branch_uneven without and with a segmented changelog, which
+// should give the same answers to ancestry queries.
+async fn branch_uneven_mononokes(fb: FacebookInit) -> Result<Vec<Mononoke>, Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let plain = Mononoke::new_test(
+        ctx.clone(),
+        vec![("test".to_string(), branch_uneven::getrepo(fb).await)],
+    )
+    .await?;
+
+    let blob_repo = branch_uneven::getrepo(fb).await;
+    let mut dag = Dag::new_in_process()?;
+    for hg_hash in &[BRANCH_UNEVEN_BRANCH_1_TOP, BRANCH_UNEVEN_BRANCH_2] {
+        let cs_id = resolve_cs_id(&ctx, &blob_repo, hg_hash).await?;
+        dag.build_up(&ctx, &blob_repo, cs_id).await?;
+    }
+    let segmented =
+        Mononoke::new_test_segmented_changelog(ctx, vec![("test".to_string(), blob_repo, dag)])
+            .await?;
+
+    Ok(vec![plain, segmented])
+}
+
+async fn resolve_hg(
+    repo: &crate::RepoContext,
+    hg_hash: &str,
+) -> Result<crate::ChangesetContext, Error> {
+    let changeset = repo
+        .changeset(ChangesetSpecifier::Hg(HgChangesetId::from_str(hg_hash)?))
+        .await?
+        .ok_or_else(|| anyhow!("changeset {} not found", hg_hash))?;
+    Ok(changeset)
+}
+
+#[fbinit::compat_test]
+async fn commit_is_ancestor_of_segmented_changelog(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    for mononoke in branch_uneven_mononokes(fb).await? {
+        let repo = mononoke
+            .repo(ctx.clone(), "test")
+            .await?
+            .expect("repo exists");
+        let top = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_1_TOP).await?;
+        let bottom = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_1_BOTTOM).await?;
+        let branch_2 = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_2).await?;
+        assert!(bottom.is_ancestor_of(top.id()).await?);
+        assert!(top.is_ancestor_of(top.id()).await?);
+        assert!(!top.is_ancestor_of(bottom.id()).await?);
+        assert!(!branch_2.is_ancestor_of(top.id()).await?);
+    }
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn commit_common_base_with(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    for mononoke in branch_uneven_mononokes(fb).await? {
+        let repo = mononoke
+            .repo(ctx.clone(), "test")
+            .await?
+            .expect("repo exists");
+        let top = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_1_TOP).await?;
+        let bottom = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_1_BOTTOM).await?;
+        let branch_2 = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_2).await?;
+        let base = resolve_hg(&repo, BRANCH_UNEVEN_BASE).await?;
+
+        let common_base = top.common_base_with(branch_2.id()).await?;
+        assert_eq!(common_base.map(|cs| cs.id()), Some(base.id()));
+        let common_base = top.common_base_with(bottom.id()).await?;
+        assert_eq!(common_base.map(|cs| cs.id()), Some(bottom.id()));
+        let common_base = top.common_base_with(top.id()).await?;
+        assert_eq!(common_base.map(|cs| cs.id()), Some(top.id()));
+    }
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn commit_linear_history(fb: FacebookInit) -> Result<(), Error> {
+    let ctx = CoreContext::test_mock(fb);
+    let mut histories = Vec::new();
+    for mononoke in branch_uneven_mononokes(fb).await? {
+        let repo = mononoke
+            .repo(ctx.clone(), "test")
+            .await?
+            .expect("repo exists");
+        let top = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_1_TOP).await?;
+        let bottom = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_1_BOTTOM).await?;
+        let branch_2 = resolve_hg(&repo, BRANCH_UNEVEN_BRANCH_2).await?;
+
+        let history: Vec<_> = top
+            .linear_history(Some(bottom.id()), 0, 100)
+            .await?
+            .into_iter()
+            .map(|cs| cs.id())
+            .collect();
+        assert_eq!(history.first(), Some(&top.id()));
+        assert_eq!(history.last(), Some(&bottom.id()));
+
+        let skipped: Vec<_> = top
+            .linear_history(Some(bottom.id()), 1, 2)
+            .await?
+            .into_iter()
+            .map(|cs| cs.id())
+            .collect();
+        assert_eq!(skipped, history[1..3].to_vec());
+
+        let all: Vec<_> = top.linear_history(None, 0, 100).await?;
+        assert_eq!(all.len(), history.len() + 1);
+
+        assert!(top
+            .linear_history(Some(branch_2.id()), 0, 100)
+            .await?
+            .is_empty());
+        histories.push(history);
+    }
+    assert_eq!(histories[0], histories[1]);
+    Ok(())
+}
+
 #[fbinit::compat_test]
 async fn commit_find_files(fb: FacebookInit) -> Result<(), Error> {
     let ctx = CoreContext::test_mock(fb);
@@ -0,0 +1,523 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! ACLs read from a TOML or JSON file, and reloaded when the file changes.
+//!
+//! Identities are written as `TYPE:data`, f.e. `USER:alice`, `GROUP:engineers` or
+//! `X509_SUBJECT_NAME:CN=alice`. Members of the groups defined in the file are granted what is
+//! granted to the group. The `reviewers` group is used to check who can review changes.
+//!
+//! Repos are listed under the `hipster_acl` of their config, or under their name if they have
+//! none. Repos that aren't listed are denied everything.
+//!
+//! ```toml
+//! [groups]
+//! engineers = ["USER:alice", "USER:bob"]
+//! reviewers = ["USER:alice"]
+//!
+//! [repos.myrepo.actions]
+//! read = ["GROUP:engineers"]
+//! write = ["GROUP:engineers"]
+//! bypass_hooks = ["USER:alice"]
+//!
+//! # Bookmarks matching the pattern only allow the actions they list to these identities.
+//! [[repos.myrepo.bookmarks]]
+//! pattern = "release/.*"
+//! actions = { write = ["USER:alice"] }
+//!
+//! [tiers.mononoke.actions]
+//! tupperware = ["X509_SUBJECT_NAME:CN=proxy"]
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use slog::{info, warn, Logger};
+
+use crate::checker::PermissionChecker;
+use crate::identity::{MononokeIdentity, MononokeIdentitySet};
+use crate::membership::MembershipChecker;
+
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The group whose members are allowed to review changes.
+pub const REVIEWERS_GROUP: &str = "reviewers";
+
+lazy_static! {
+    static ref ACL_FILE: RwLock<Option<Arc<AclFile>>> = RwLock::new(None);
+}
+
+/// Use the ACLs from this file for the checkers created from now on, and reload them whenever
+/// the file changes.
+pub fn init_acl_file(logger: Logger, path: impl Into<PathBuf>) -> Result<()> {
+    let acl_file = Arc::new(AclFile::load(path)?);
+
+    thread::Builder::new()
+        .name("mononoke-acl-file".into())
+        .spawn({
+            let acl_file = acl_file.clone();
+            move || acl_file.reload_worker(logger)
+        })
+        .context("Can't spawn ACL file reloader")?;
+
+    *ACL_FILE.write().expect("poisoned lock") = Some(acl_file);
+    Ok(())
+}
+
+/// The ACL file set up with `init_acl_file`, if any.
+pub fn acl_file() -> Option<Arc<AclFile>> {
+    ACL_FILE.read().expect("poisoned lock").clone()
+}
+
+#[derive(Deserialize, Default)]
+struct RawAcls {
+    #[serde(default)]
+    groups: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    repos: HashMap<String, RawRepoAcl>,
+    #[serde(default)]
+    tiers: HashMap<String, RawActions>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawRepoAcl {
+    #[serde(default)]
+    actions: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    bookmarks: Vec<RawBookmarkAcl>,
+}
+
+#[derive(Deserialize)]
+struct RawBookmarkAcl {
+    pattern: String,
+    #[serde(default)]
+    actions: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawActions {
+    #[serde(default)]
+    actions: HashMap<String, Vec<String>>,
+}
+
+/// The identities allowed to perform each action.
+#[derive(Debug, Default)]
+struct ActionAcl(HashMap<String, MononokeIdentitySet>);
+
+impl ActionAcl {
+    fn new(
+        raw: HashMap<String, Vec<String>>,
+        groups: &HashMap<String, MononokeIdentitySet>,
+    ) -> Result<Self> {
+        let mut acl = HashMap::new();
+        for (action, identities) in raw {
+            let identities = parse_identities(&identities)?;
+            let mut allowed = identities.clone();
+            for identity in identities.iter() {
+                if identity.id_type() == "GROUP" {
+                    if let Some(members) = groups.get(identity.id_data()) {
+                        allowed.extend(members.iter().cloned());
+                    }
+                }
+            }
+            acl.insert(action, allowed);
+        }
+        Ok(Self(acl))
+    }
+
+    /// Whether the accessors are allowed the action, or None if the ACL doesn't mention it.
+    fn check(&self, accessors: &MononokeIdentitySet, action: &str) -> Option<bool> {
+        self.0
+            .get(action)
+            .map(|allowed| !allowed.is_disjoint(accessors))
+    }
+}
+
+#[derive(Debug)]
+struct BookmarkAcl {
+    pattern: Regex,
+    actions: ActionAcl,
+}
+
+#[derive(Debug, Default)]
+struct RepoAcl {
+    actions: ActionAcl,
+    bookmarks: Vec<BookmarkAcl>,
+}
+
+/// The parsed content of an ACL file.
+#[derive(Debug, Default)]
+pub struct Acls {
+    groups: HashMap<String, MononokeIdentitySet>,
+    repos: HashMap<String, RepoAcl>,
+    tiers: HashMap<String, ActionAcl>,
+}
+
+fn parse_identities(identities: &[String]) -> Result<MononokeIdentitySet> {
+    identities
+        .iter()
+        .map(|identity| MononokeIdentity::from_str(identity))
+        .collect()
+}
+
+impl Acls {
+    fn from_raw(raw: RawAcls) -> Result<Self> {
+        let groups = raw
+            .groups
+            .into_iter()
+            .map(|(name, members)| Ok((name, parse_identities(&members)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let mut repos = HashMap::new();
+        for (name, repo) in raw.repos {
+            let bookmarks = repo
+                .bookmarks
+                .into_iter()
+                .map(|bookmark| {
+                    // Patterns must match whole bookmark names.
+                    let pattern = Regex::new(&format!("^(?:{})$", bookmark.pattern))
+                        .with_context(|| format!("Invalid bookmark pattern in repo {}", name))?;
+                    Ok(BookmarkAcl {
+                        pattern,
+                        actions: ActionAcl::new(bookmark.actions, &groups)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let actions = ActionAcl::new(repo.actions, &groups)?;
+            repos.insert(name, RepoAcl { actions, bookmarks });
+        }
+
+        let tiers = raw
+            .tiers
+            .into_iter()
+            .map(|(name, tier)| Ok((name, ActionAcl::new(tier.actions, &groups)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        Ok(Self {
+            groups,
+            repos,
+            tiers,
+        })
+    }
+
+    /// Parse ACLs, as JSON if the path ends with `.json`, or as TOML otherwise.
+    pub fn parse(path: &Path, content: &str) -> Result<Self> {
+        let raw: RawAcls = if path.extension().map_or(false, |ext| ext == "json") {
+            serde_json::from_str(content)?
+        } else {
+            toml::from_str(content)?
+        };
+        Self::from_raw(raw)
+    }
+
+    /// Whether the accessors are allowed all the actions on the repo. Nothing is allowed on
+    /// repos that aren't in the file.
+    pub fn check_repo(
+        &self,
+        repo: &str,
+        accessors: &MononokeIdentitySet,
+        actions: &[&str],
+    ) -> bool {
+        match self.repos.get(repo) {
+            Some(repo) => actions
+                .iter()
+                .all(|action| repo.actions.check(accessors, action).unwrap_or(false)),
+            None => false,
+        }
+    }
+
+    /// Whether the accessors are allowed all the actions on the bookmark of the repo. Bookmarks
+    /// matching a pattern which lists an action only allow that action to the identities listed
+    /// for that pattern. Other actions are checked against the repo.
+    pub fn check_bookmark(
+        &self,
+        repo: &str,
+        bookmark: &str,
+        accessors: &MononokeIdentitySet,
+        actions: &[&str],
+    ) -> bool {
+        let repo = match self.repos.get(repo) {
+            Some(repo) => repo,
+            None => return false,
+        };
+
+        actions.iter().all(|action| {
+            let mut bookmark_checks = repo
+                .bookmarks
+                .iter()
+                .filter(|bookmark_acl| bookmark_acl.pattern.is_match(bookmark))
+                .filter_map(|bookmark_acl| bookmark_acl.actions.check(accessors, action))
+                .peekable();
+            if bookmark_checks.peek().is_some() {
+                bookmark_checks.any(|allowed| allowed)
+            } else {
+                repo.actions.check(accessors, action).unwrap_or(false)
+            }
+        })
+    }
+
+    /// Whether the accessors are allowed all the actions on the tier. Nothing is allowed on
+    /// tiers that aren't in the file.
+    pub fn check_tier(
+        &self,
+        tier: &str,
+        accessors: &MononokeIdentitySet,
+        actions: &[&str],
+    ) -> bool {
+        match self.tiers.get(tier) {
+            Some(tier) => actions
+                .iter()
+                .all(|action| tier.check(accessors, action).unwrap_or(false)),
+            None => false,
+        }
+    }
+
+    /// Whether one of the identities is a member of the group.
+    pub fn is_member(&self, group: &str, identities: &MononokeIdentitySet) -> bool {
+        self.groups
+            .get(group)
+            .map_or(false, |members| !members.is_disjoint(identities))
+    }
+}
+
+/// An ACL file, and the ACLs it had when it was last loaded.
+pub struct AclFile {
+    path: PathBuf,
+    state: RwLock<(Option<SystemTime>, Arc<Acls>)>,
+}
+
+impl AclFile {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let (modified, acls) = Self::read(&path)?;
+        Ok(Self {
+            path,
+            state: RwLock::new((modified, Arc::new(acls))),
+        })
+    }
+
+    fn read(path: &Path) -> Result<(Option<SystemTime>, Acls)> {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Can't read ACL file {}", path.display()))?;
+        let acls = Acls::parse(path, &content)
+            .with_context(|| format!("Invalid ACL file {}", path.display()))?;
+        Ok((modified, acls))
+    }
+
+    /// The current ACLs.
+    pub fn acls(&self) -> Arc<Acls> {
+        self.state.read().expect("poisoned lock").1.clone()
+    }
+
+    /// Reload the ACLs if the file was modified since they were last loaded. Returns whether
+    /// they were reloaded. If the new file is invalid, the previous ACLs are kept.
+    pub fn reload_if_modified(&self) -> Result<bool> {
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_some() && modified == self.state.read().expect("poisoned lock").0 {
+            return Ok(false);
+        }
+
+        let (modified, acls) = Self::read(&self.path)?;
+        *self.state.write().expect("poisoned lock") = (modified, Arc::new(acls));
+        Ok(true)
+    }
+
+    fn reload_worker(&self, logger: Logger) {
+        loop {
+            thread::sleep(RELOAD_INTERVAL);
+            match self.reload_if_modified() {
+                Ok(true) => info!(logger, "Reloaded ACL file {}", self.path.display()),
+                Ok(false) => {}
+                Err(e) => warn!(logger, "Failed to reload ACL file: {:#}", e),
+            }
+        }
+    }
+}
+
+/// What a file ACL checker checks permissions on.
+pub(crate) enum AclScope {
+    Repo(String),
+    Tier(String),
+}
+
+pub(crate) struct FileAclChecker {
+    acl_file: Arc<AclFile>,
+    scope: AclScope,
+}
+
+impl FileAclChecker {
+    pub(crate) fn new(acl_file: Arc<AclFile>, scope: AclScope) -> Self {
+        Self { acl_file, scope }
+    }
+}
+
+#[async_trait]
+impl PermissionChecker for FileAclChecker {
+    async fn check_set(&self, accessors: &MononokeIdentitySet, actions: &[&str]) -> Result<bool> {
+        let acls = self.acl_file.acls();
+        Ok(match &self.scope {
+            AclScope::Repo(repo) => acls.check_repo(repo, accessors, actions),
+            AclScope::Tier(tier) => acls.check_tier(tier, accessors, actions),
+        })
+    }
+
+    async fn check_set_for_bookmark(
+        &self,
+        accessors: &MononokeIdentitySet,
+        bookmark: &str,
+        actions: &[&str],
+    ) -> Result<bool> {
+        let acls = self.acl_file.acls();
+        Ok(match &self.scope {
+            AclScope::Repo(repo) => acls.check_bookmark(repo, bookmark, accessors, actions),
+            AclScope::Tier(tier) => acls.check_tier(tier, accessors, actions),
+        })
+    }
+}
+
+pub(crate) struct FileGroupChecker {
+    acl_file: Arc<AclFile>,
+    group: String,
+}
+
+impl FileGroupChecker {
+    pub(crate) fn new(acl_file: Arc<AclFile>, group: impl Into<String>) -> Self {
+        Self {
+            acl_file,
+            group: group.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl MembershipChecker for FileGroupChecker {
+    async fn is_member(&self, identities: &MononokeIdentitySet) -> Result<bool> {
+        Ok(self.acl_file.acls().is_member(&self.group, identities))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maplit::btreeset;
+
+    const ACLS: &str = r#"
+        [groups]
+        engineers = ["USER:alice", "USER:bob"]
+        reviewers = ["USER:alice"]
+
+        [repos.repo.actions]
+        read = ["GROUP:engineers", "USER:carol"]
+        write = ["GROUP:engineers"]
+
+        [[repos.repo.bookmarks]]
+        pattern = "release/.*"
+        actions = { write = ["USER:alice"] }
+
+        [tiers.mononoke.actions]
+        tupperware = ["X509_SUBJECT_NAME:CN=proxy"]
+    "#;
+
+    fn identities(identities: &[&str]) -> MononokeIdentitySet {
+        identities
+            .iter()
+            .map(|identity| MononokeIdentity::from_str(identity).unwrap())
+            .collect()
+    }
+
+    fn acls() -> Acls {
+        Acls::parse(Path::new("acls.toml"), ACLS).unwrap()
+    }
+
+    #[test]
+    fn test_check_repo() {
+        let acls = acls();
+        let alice = identities(&["USER:alice"]);
+        let carol = identities(&["USER:carol"]);
+        let dave = identities(&["USER:dave"]);
+
+        assert!(acls.check_repo("repo", &alice, &["read", "write"]));
+        assert!(acls.check_repo("repo", &carol, &["read"]));
+        assert!(!acls.check_repo("repo", &carol, &["read", "write"]));
+        assert!(!acls.check_repo("repo", &dave, &["read"]));
+        assert!(!acls.check_repo("repo", &alice, &["bypass_hooks"]));
+        assert!(!acls.check_repo("other_repo", &alice, &["read"]));
+        assert!(acls.check_repo("repo", &identities(&["GROUP:engineers"]), &["read"]));
+    }
+
+    #[test]
+    fn test_check_bookmark() {
+        let acls = acls();
+        let alice = identities(&["USER:alice"]);
+        let bob = identities(&["USER:bob"]);
+
+        assert!(acls.check_bookmark("repo", "master", &bob, &["write"]));
+        assert!(acls.check_bookmark("repo", "release/1.0", &alice, &["write"]));
+        assert!(!acls.check_bookmark("repo", "release/1.0", &bob, &["write"]));
+        assert!(acls.check_bookmark("repo", "release/1.0", &bob, &["read"]));
+        // Patterns match whole bookmark names.
+        assert!(acls.check_bookmark("repo", "old/release/1.0", &bob, &["write"]));
+    }
+
+    #[test]
+    fn test_check_tier_and_groups() {
+        let acls = acls();
+        let proxy = identities(&["X509_SUBJECT_NAME:CN=proxy"]);
+
+        assert!(acls.check_tier("mononoke", &proxy, &["tupperware"]));
+        assert!(!acls.check_tier("mononoke", &identities(&["USER:alice"]), &["tupperware"]));
+        assert!(!acls.check_tier("other", &proxy, &["tupperware"]));
+
+        assert!(acls.is_member(REVIEWERS_GROUP, &identities(&["USER:alice"])));
+        assert!(!acls.is_member(REVIEWERS_GROUP, &identities(&["USER:bob"])));
+        assert_eq!(
+            acls.groups.get("engineers"),
+            Some(&btreeset! {
+                MononokeIdentity::new("USER", "alice").unwrap(),
+                MononokeIdentity::new("USER", "bob").unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let acls = Acls::parse(
+            Path::new("acls.json"),
+            r#"{"repos": {"repo": {"actions": {"read": ["USER:alice"]}}}}"#,
+        )
+        .unwrap();
+        assert!(acls.check_repo("repo", &identities(&["USER:alice"]), &["read"]));
+    }
+
+    #[test]
+    fn test_invalid_identity() {
+        let error = Acls::parse(
+            Path::new("acls.toml"),
+            r#"
+            [repos.repo.actions]
+            read = ["alice"]
+            "#,
+        );
+        assert!(error.is_err());
+    }
+}
@@ -7,6 +7,7 @@
 
 use anyhow::Result;
 use async_trait::async_trait;
+use fbinit::FacebookInit;
 use std::panic::RefUnwindSafe;
 use std::sync::Arc;
 
@@ -16,8 +17,19 @@ pub type ArcPermissionChecker = Arc<dyn PermissionChecker + Send + Sync + RefUnw
 pub type BoxPermissionChecker = Box<dyn PermissionChecker + Send + Sync + RefUnwindSafe + 'static>;
 
 #[async_trait]
-pub trait PermissionChecker {
+pub trait PermissionChecker: Send + Sync {
     async fn check_set(&self, accessors: &MononokeIdentitySet, actions: &[&str]) -> Result<bool>;
+
+    /// Check the actions on a bookmark. Checkers without per-bookmark permissions check the
+    /// actions on the whole repo.
+    async fn check_set_for_bookmark(
+        &self,
+        accessors: &MononokeIdentitySet,
+        _bookmark: &str,
+        actions: &[&str],
+    ) -> Result<bool> {
+        self.check_set(accessors, actions).await
+    }
 }
 
 pub struct PermissionCheckerBuilder {}
@@ -33,6 +45,20 @@ impl PermissionCheckerBuilder {
     pub fn whitelist_checker(whitelist: MononokeIdentitySet) -> BoxPermissionChecker {
         Box::new(WhitelistChecker { whitelist })
     }
+
+    /// The checker for a repo with the `hipster_acl` of its config. In OSS builds, a repo without
+    /// one is checked by its name, so that the ACL file denies it unless it lists it.
+    pub async fn for_repo(
+        fb: FacebookInit,
+        repo_name: &str,
+        hipster_acl: Option<&str>,
+    ) -> Result<BoxPermissionChecker> {
+        match hipster_acl {
+            Some(acl) => Self::acl_for_repo(fb, acl).await,
+            None if cfg!(fbcode_build) => Ok(Self::always_allow()),
+            None => Self::acl_for_repo(fb, repo_name).await,
+        }
+    }
 }
 
 struct AlwaysAllow {}
@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+pub mod acl_file;
 mod checker;
 #[cfg(fbcode_build)]
 mod facebook;
@@ -13,6 +14,7 @@ mod membership;
 #[cfg(not(fbcode_build))]
 mod oss;
 
+pub use acl_file::init_acl_file;
 pub use checker::{
     ArcPermissionChecker, BoxPermissionChecker, PermissionChecker, PermissionCheckerBuilder,
 };
@@ -7,7 +7,10 @@
 
 use anyhow::Result;
 use fbinit::FacebookInit;
+use maplit::btreeset;
+use openssl::x509::X509Ref;
 
+use crate::acl_file::{acl_file, AclScope, FileAclChecker, FileGroupChecker, REVIEWERS_GROUP};
 use crate::checker::{BoxPermissionChecker, PermissionCheckerBuilder};
 use crate::identity::{MononokeIdentity, MononokeIdentitySet};
 use crate::membership::{BoxMembershipChecker, MembershipCheckerBuilder};
@@ -16,20 +19,90 @@ impl MononokeIdentity {
     pub fn reviewer_identities(_username: &str) -> MononokeIdentitySet {
         MononokeIdentitySet::new()
     }
+
+    /// The identity of a client certificate is its subject name, f.e.
+    /// `X509_SUBJECT_NAME:CN=alice,O=Example`.
+    pub fn try_from_x509(cert: &X509Ref) -> Result<MononokeIdentitySet> {
+        let subject = cert
+            .subject_name()
+            .entries()
+            .map(|entry| {
+                Ok(format!(
+                    "{}={}",
+                    entry.object().nid().short_name()?,
+                    entry.data().as_utf8()?
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join(",");
+        Ok(btreeset! { Self::new("X509_SUBJECT_NAME", subject)? })
+    }
 }
 
 impl PermissionCheckerBuilder {
-    pub async fn acl_for_repo(_fb: FacebookInit, _name: &str) -> Result<BoxPermissionChecker> {
-        Ok(Self::always_allow())
+    pub async fn acl_for_repo(_fb: FacebookInit, name: &str) -> Result<BoxPermissionChecker> {
+        Ok(match acl_file() {
+            Some(acl_file) => Box::new(FileAclChecker::new(
+                acl_file,
+                AclScope::Repo(name.to_string()),
+            )),
+            None => Self::always_allow(),
+        })
     }
 
-    pub async fn acl_for_tier(_fb: FacebookInit, _name: &str) -> Result<BoxPermissionChecker> {
-        Ok(Self::always_allow())
+    pub async fn acl_for_tier(_fb: FacebookInit, name: &str) -> Result<BoxPermissionChecker> {
+        Ok(match acl_file() {
+            Some(acl_file) => Box::new(FileAclChecker::new(
+                acl_file,
+                AclScope::Tier(name.to_string()),
+            )),
+            None => Self::always_allow(),
+        })
     }
 }
 
 impl MembershipCheckerBuilder {
     pub async fn for_reviewers_group(_fb: FacebookInit) -> Result<BoxMembershipChecker> {
-        Ok(Self::always_member())
+        Ok(match acl_file() {
+            Some(acl_file) => Box::new(FileGroupChecker::new(acl_file, REVIEWERS_GROUP)),
+            None => Self::always_member(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    use slog::{o, Discard, Logger};
+
+    use crate::acl_file::init_acl_file;
+    use crate::checker::PermissionChecker;
+
+    #[fbinit::test]
+    async fn test_acl_for_repo_by_name(fb: FacebookInit) -> Result<()> {
+        let path = std::env::temp_dir().join(format!("mononoke-acls-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+                [repos.repo.actions]
+                read = ["USER:alice"]
+            "#,
+        )?;
+        init_acl_file(Logger::root(Discard, o!()), path.clone())?;
+        let alice = btreeset! { MononokeIdentity::new("USER", "alice")? };
+
+        // Repos without an ACL are looked up by name, and denied if they aren't listed.
+        let checker = PermissionCheckerBuilder::for_repo(fb, "repo", None).await?;
+        assert!(checker.check_set(&alice, &["read"]).await?);
+        let checker = PermissionCheckerBuilder::for_repo(fb, "other_repo", None).await?;
+        assert!(!checker.check_set(&alice, &["read"]).await?);
+        let checker = PermissionCheckerBuilder::for_repo(fb, "other_repo", Some("repo")).await?;
+        assert!(checker.check_set(&alice, &["read"]).await?);
+
+        fs::remove_file(&path)?;
+        Ok(())
     }
 }
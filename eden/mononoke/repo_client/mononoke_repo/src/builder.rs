@@ -13,6 +13,7 @@ use futures::{compat::Future01CompatExt, future, FutureExt};
 use hooks::HookManager;
 use metaconfig_types::RepoConfig;
 use mutable_counters::SqlMutableCounters;
+use permission_checker::ArcPermissionChecker;
 use reachabilityindex::LeastCommonAncestorsHint;
 use repo_read_write_status::{RepoReadWriteFetcher, SqlRepoReadWriteStatus};
 use reverse_filler_queue::{ReverseFillerQueue, SqlReverseFillerQueue};
@@ -67,7 +68,11 @@ impl MononokeRepoBuilder {
         })
     }
 
-    pub async fn finalize(self, hook_manager: Arc<HookManager>) -> Result<MononokeRepo, Error> {
+    pub async fn finalize(
+        self,
+        hook_manager: Arc<HookManager>,
+        permission_checker: ArcPermissionChecker,
+    ) -> Result<MononokeRepo, Error> {
         let Self {
             ctx,
             repo,
@@ -185,6 +190,7 @@ impl MononokeRepoBuilder {
             &pushrebase,
             bookmarks,
             hook_manager,
+            permission_checker,
             streaming_clone,
             lfs,
             read_write_fetcher,
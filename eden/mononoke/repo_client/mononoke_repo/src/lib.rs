@@ -19,6 +19,7 @@ use metaconfig_types::{
 };
 use mononoke_types::RepositoryId;
 use mutable_counters::MutableCounters;
+use permission_checker::ArcPermissionChecker;
 use rand::Rng;
 use reachabilityindex::LeastCommonAncestorsHint;
 use repo_blobstore::RepoBlobstore;
@@ -53,6 +54,7 @@ pub struct MononokeRepo {
     blobrepo: BlobRepo,
     pushrebase_params: PushrebaseParams,
     hook_manager: Arc<HookManager>,
+    permission_checker: ArcPermissionChecker,
     streaming_clone: Option<SqlStreamingCloneConfig>,
     lfs_params: LfsParams,
     readonly_fetcher: RepoReadWriteFetcher,
@@ -77,6 +79,7 @@ impl MononokeRepo {
         pushrebase_params: &PushrebaseParams,
         bookmark_params: Vec<BookmarkParams>,
         hook_manager: Arc<HookManager>,
+        permission_checker: ArcPermissionChecker,
         streaming_clone: Option<SqlStreamingCloneConfig>,
         lfs_params: LfsParams,
         readonly_fetcher: RepoReadWriteFetcher,
@@ -108,6 +111,7 @@ impl MononokeRepo {
             blobrepo,
             pushrebase_params: pushrebase_params.clone(),
             hook_manager,
+            permission_checker,
             streaming_clone,
             lfs_params,
             readonly_fetcher,
@@ -138,6 +142,11 @@ impl MononokeRepo {
         self.hook_manager.clone()
     }
 
+    /// The ACL of the repo, checked when clients connect and push.
+    pub fn permission_checker(&self) -> ArcPermissionChecker {
+        self.permission_checker.clone()
+    }
+
     pub fn streaming_clone(&self) -> &Option<SqlStreamingCloneConfig> {
         &self.streaming_clone
     }
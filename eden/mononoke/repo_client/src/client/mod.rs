@@ -7,7 +7,7 @@
 
 use crate::errors::ErrorKind;
 
-use unbundle::{run_hooks, run_post_resolve_action, PushRedirector};
+use unbundle::{check_bookmark_permissions, run_hooks, run_post_resolve_action, PushRedirector};
 
 use anyhow::{format_err, Error, Result};
 use blobrepo::BlobRepo;
@@ -1388,6 +1388,7 @@ impl HgCommands for RepoClient {
         );
 
         let hook_manager = self.repo.hook_manager();
+        let permission_checker = self.repo.permission_checker();
 
         // Kill the saved set of bookmarks here - the unbundle may change them, and the next
         // command in sequence will need to fetch a new set
@@ -1433,16 +1434,28 @@ impl HgCommands for RepoClient {
                 }
                 .boxed()
                 .compat()
+                .and_then({
+                    cloned!(ctx);
+                    move |action| {
+                        async move {
+                            check_bookmark_permissions(&ctx, &*permission_checker, &action)
+                                .await
+                                .map(|()| action)
+                        }
+                        .boxed()
+                        .compat()
+                    }
+                })
                 .and_then({
                     cloned!(ctx, blobrepo);
                     move |action| {
                         run_hooks(ctx, blobrepo, hook_manager, &action)
-                            .map(move |_| action)
+                            .map(move |hook_warnings| (action, hook_warnings))
                     }
                 }).and_then({
                     cloned!(ctx, client, blobrepo, pushrebase_params, lca_hint);
-                    move |action| {
-                        match try_boxfuture!(client.maybe_get_push_redirector_for_action(&action)) {
+                    move |(action, hook_warnings)| {
+                        let response = match try_boxfuture!(client.maybe_get_push_redirector_for_action(&action)) {
                             Some(push_redirector) => {
                                 let ctx = ctx.with_mutated_scuba(|mut sample| {
                                     sample.add("target_repo_name", push_redirector.repo.reponame().as_ref());
@@ -1473,7 +1486,8 @@ impl HgCommands for RepoClient {
                                 .compat()
                                 .boxify()
                             }
-                        }
+                        };
+                        response.map(move |response| (response, hook_warnings)).boxify()
                     }
                 }).and_then({
                     // There's a bookmarks race condition where the client requests bookmarks after we return commits to it,
@@ -1501,7 +1515,7 @@ impl HgCommands for RepoClient {
                     }
                 }).and_then({
                     cloned!(ctx, reponame);
-                    move |response| {
+                    move |(response, hook_warnings)| {
                         response.generate_bytes(
                             ctx,
                             blobrepo,
@@ -1509,6 +1523,7 @@ impl HgCommands for RepoClient {
                             pushrebase_params,
                             lca_hint,
                             lfs_params,
+                            hook_warnings,
                         )
                         .from_err()
                     }
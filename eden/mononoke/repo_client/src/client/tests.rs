@@ -19,6 +19,7 @@ use mercurial_types::HgFileNodeId;
 use metaconfig_types::{HookManagerParams, InfinitepushParams, LfsParams, PushrebaseParams};
 use mononoke_repo::MononokeRepo;
 use mutable_counters::SqlMutableCounters;
+use permission_checker::PermissionCheckerBuilder;
 use repo_read_write_status::RepoReadWriteFetcher;
 use scuba_ext::ScubaSampleBuilder;
 use skiplist::SkiplistIndex;
@@ -484,6 +485,7 @@ async fn run_and_check_if_lfs(
             },
             ScubaSampleBuilder::with_discard(),
         )),
+        PermissionCheckerBuilder::always_allow().into(),
         None,
         lfs_params,
         RepoReadWriteFetcher::new(
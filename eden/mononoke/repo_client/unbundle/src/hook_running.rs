@@ -7,8 +7,11 @@
 
 #![deny(warnings)]
 
-use crate::{resolver::HookFailure, BundleResolverError, PostResolveAction, PostResolvePushRebase};
-use anyhow::Context;
+use crate::{
+    resolver::{HookFailure, HookWarning},
+    BundleResolverError, PostResolveAction, PostResolvePushRebase,
+};
+use anyhow::{Context, Error};
 use blobrepo::BlobRepo;
 use bookmarks::BookmarkName;
 use bytes::Bytes;
@@ -17,25 +20,28 @@ use futures::{compat::Future01CompatExt, stream::TryStreamExt, FutureExt, TryFut
 use futures_ext::{BoxFuture, FutureExt as _};
 use futures_old::future::ok;
 use futures_stats::TimedFutureExt;
-use hooks::{HookManager, HookOutcome};
-use mononoke_types::BonsaiChangeset;
+use hooks::{HookManager, HookRejectionInfo};
+use mercurial_types::HgChangesetId;
+use mononoke_types::{BonsaiChangeset, ChangesetId};
 use scuba_ext::ScubaSampleBuilderExt;
 use std::{collections::HashMap, sync::Arc};
 
+/// Run the hooks for the action. Rejections from hooks fail the push, while warnings from
+/// advisory hooks are returned to be shown to the user.
 pub fn run_hooks(
     ctx: CoreContext,
     repo: BlobRepo,
     hook_manager: Arc<HookManager>,
     action: &PostResolveAction,
-) -> BoxFuture<(), BundleResolverError> {
+) -> BoxFuture<Vec<HookWarning>, BundleResolverError> {
     match action {
         // TODO: Need to run hooks on Push, not just PushRebase
-        PostResolveAction::Push(_) => ok(()).boxify(),
-        PostResolveAction::InfinitePush(_) => ok(()).boxify(),
+        PostResolveAction::Push(_) => ok(vec![]).boxify(),
+        PostResolveAction::InfinitePush(_) => ok(vec![]).boxify(),
         PostResolveAction::PushRebase(action) => {
             run_pushrebase_hooks(ctx, repo, action, hook_manager)
         }
-        PostResolveAction::BookmarkOnlyPushRebase(_) => ok(()).boxify(),
+        PostResolveAction::BookmarkOnlyPushRebase(_) => ok(vec![]).boxify(),
     }
 }
 
@@ -44,7 +50,7 @@ fn run_pushrebase_hooks(
     repo: BlobRepo,
     action: &PostResolvePushRebase,
     hook_manager: Arc<HookManager>,
-) -> BoxFuture<(), BundleResolverError> {
+) -> BoxFuture<Vec<HookWarning>, BundleResolverError> {
     // The changesets that will be pushed
     let changesets = action.uploaded_bonsais.clone();
     let maybe_pushvars = action.maybe_pushvars.clone();
@@ -60,8 +66,7 @@ fn run_pushrebase_hooks(
             bookmark,
             maybe_pushvars,
         )
-        .await?;
-        Ok(())
+        .await
     }
     .boxed()
     .compat()
@@ -75,29 +80,63 @@ async fn run_hooks_on_changesets(
     changesets: impl Iterator<Item = &BonsaiChangeset> + Clone + itertools::Itertools,
     bookmark: BookmarkName,
     maybe_pushvars: Option<HashMap<String, Bytes>>,
-) -> Result<(), BundleResolverError> {
+) -> Result<Vec<HookWarning>, BundleResolverError> {
     let (stats, hook_outcomes) = hook_manager
         .run_hooks_for_bookmark(&ctx, changesets, &bookmark, maybe_pushvars.as_ref())
         .timed()
         .await;
     let hook_outcomes = hook_outcomes.context("While running hooks")?;
 
-    let rejections = hook_outcomes
-        .into_iter()
-        .filter_map(HookOutcome::into_rejection)
-        .collect::<Vec<_>>();
+    let mut rejections = vec![];
+    let mut warnings = vec![];
+    for outcome in hook_outcomes {
+        if outcome.is_rejection() {
+            rejections.extend(outcome.into_rejection());
+        } else if outcome.is_warning() {
+            warnings.extend(outcome.into_warning());
+        }
+    }
 
     ctx.scuba()
         .clone()
         .add_future_stats(&stats)
         .add("hook_rejections", rejections.len())
+        .add("hook_warnings", warnings.len())
         .log_with_msg("Executed hooks", None);
 
     if rejections.is_empty() {
-        return Ok(());
+        let warnings = to_hg_changesets(ctx, repo, warnings)
+            .await?
+            .into_iter()
+            .map(|(hook_name, cs_id, info)| HookWarning {
+                hook_name,
+                cs_id,
+                info,
+            })
+            .collect();
+        return Ok(warnings);
     }
 
-    let rejections = rejections
+    let rejections = to_hg_changesets(ctx, repo, rejections)
+        .await?
+        .into_iter()
+        .map(|(hook_name, cs_id, info)| HookFailure {
+            hook_name,
+            cs_id,
+            info,
+        })
+        .collect();
+
+    Err(BundleResolverError::HookError(rejections))
+}
+
+/// Replace the changeset ids of hook outcomes with the hg changeset ids the user knows about.
+async fn to_hg_changesets(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    outcomes: Vec<(String, ChangesetId, HookRejectionInfo)>,
+) -> Result<Vec<(String, HgChangesetId, HookRejectionInfo)>, Error> {
+    outcomes
         .into_iter()
         .map(|(hook_name, cs_id, info)| async move {
             let cs_id = repo
@@ -105,15 +144,9 @@ async fn run_hooks_on_changesets(
                 .compat()
                 .await?;
 
-            Result::<_, anyhow::Error>::Ok(HookFailure {
-                hook_name,
-                cs_id,
-                info,
-            })
+            Result::<_, Error>::Ok((hook_name, cs_id, info))
         })
         .collect::<futures::stream::FuturesUnordered<_>>()
         .try_collect()
-        .await?;
-
-    Err(BundleResolverError::HookError(rejections))
+        .await
 }
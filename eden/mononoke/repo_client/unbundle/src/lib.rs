@@ -11,6 +11,7 @@
 mod changegroup;
 mod errors;
 mod hook_running;
+mod permissions;
 mod processing;
 mod push_redirector;
 mod rate_limits;
@@ -21,10 +22,11 @@ mod upload_blobs;
 mod upload_changesets;
 
 pub use hook_running::run_hooks;
+pub use permissions::check_bookmark_permissions;
 pub use processing::{get_pushrebase_hooks, run_post_resolve_action};
 pub use push_redirector::{PushRedirector, CONFIGERATOR_PUSHREDIRECT_ENABLE};
 pub use resolver::{
-    resolve, BundleResolverError, Changesets, CommonHeads, InfiniteBookmarkPush,
+    resolve, BundleResolverError, Changesets, CommonHeads, HookWarning, InfiniteBookmarkPush,
     NonFastForwardPolicy, PlainBookmarkPush, PostResolveAction, PostResolveBookmarkOnlyPushRebase,
     PostResolveInfinitePush, PostResolvePush, PostResolvePushRebase, PushrebaseBookmarkSpec,
     UploadedBonsais,
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::{
+    BundleResolverError, PostResolveAction, PostResolveBookmarkOnlyPushRebase, PostResolvePush,
+    PostResolvePushRebase,
+};
+use anyhow::format_err;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use itertools::Itertools;
+use permission_checker::PermissionChecker;
+
+/// Check that the user is permitted to write to the bookmarks moved by the action, or to the
+/// repo if the action doesn't move any. Infinitepush only moves scratch bookmarks, which aren't
+/// checked.
+pub async fn check_bookmark_permissions(
+    ctx: &CoreContext,
+    permission_checker: &dyn PermissionChecker,
+    action: &PostResolveAction,
+) -> Result<(), BundleResolverError> {
+    let bookmarks: Vec<&BookmarkName> = match action {
+        PostResolveAction::Push(PostResolvePush {
+            bookmark_pushes, ..
+        }) => bookmark_pushes.iter().map(|push| &push.name).collect(),
+        PostResolveAction::PushRebase(PostResolvePushRebase { bookmark_spec, .. }) => {
+            vec![bookmark_spec.get_bookmark_name()]
+        }
+        PostResolveAction::BookmarkOnlyPushRebase(PostResolveBookmarkOnlyPushRebase {
+            bookmark_push,
+            ..
+        }) => vec![&bookmark_push.name],
+        PostResolveAction::InfinitePush(..) => return Ok(()),
+    };
+
+    let identities = ctx.identities().cloned().unwrap_or_default();
+
+    let mut denied = vec![];
+    if bookmarks.is_empty()
+        && !permission_checker
+            .check_set(&identities, &["write"])
+            .await?
+    {
+        denied.push("the repo".to_string());
+    }
+    for bookmark in bookmarks {
+        if !permission_checker
+            .check_set_for_bookmark(&identities, bookmark.as_str(), &["write"])
+            .await?
+        {
+            denied.push(format!("bookmark {}", bookmark));
+        }
+    }
+
+    if denied.is_empty() {
+        return Ok(());
+    }
+
+    let identities = if identities.is_empty() {
+        "<none>".to_string()
+    } else {
+        identities.iter().join(",")
+    };
+    Err(format_err!(
+        "Permission denied: write access to {} not permitted for {}",
+        denied.join(", "),
+        identities
+    )
+    .into())
+}
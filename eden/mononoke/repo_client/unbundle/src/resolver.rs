@@ -49,6 +49,7 @@ use pushrebase::HgReplayData;
 use scuba_ext::ScubaSampleBuilderExt;
 use slog::{debug, trace};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::sync::{Arc, Mutex};
 use topo_sort::sort_topological;
 use wirepack::{TreemanifestBundle2Parser, TreemanifestEntry};
@@ -114,6 +115,29 @@ impl HookFailure {
     }
 }
 
+/// A rejection from an advisory hook, which is shown to the user without failing the push
+pub struct HookWarning {
+    pub(crate) hook_name: String,
+    pub(crate) cs_id: HgChangesetId,
+    pub(crate) info: HookRejectionInfo,
+}
+
+impl HookWarning {
+    pub fn get_hook_name(&self) -> &str {
+        &self.hook_name
+    }
+}
+
+impl fmt::Display for HookWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "hook {} warned for {}: {}",
+            self.hook_name, self.cs_id, self.info.long_description
+        )
+    }
+}
+
 pub enum BundleResolverError {
     HookError(Vec<HookFailure>),
     PushrebaseConflicts(Vec<pushrebase::PushrebaseConflict>),
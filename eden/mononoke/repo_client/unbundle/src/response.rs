@@ -5,7 +5,7 @@
  * GNU General Public License version 2.
  */
 
-use crate::CommonHeads;
+use crate::{CommonHeads, HookWarning};
 use anyhow::Error;
 use blobrepo::BlobRepo;
 use bookmarks::BookmarkName;
@@ -125,6 +125,7 @@ impl UnbundleResponse {
         pushrebase_params: PushrebaseParams,
         lca_hint: Arc<dyn LeastCommonAncestorsHint>,
         lfs_params: SessionLfsParams,
+        hook_warnings: Vec<HookWarning>,
     ) -> BoxFuture<Bytes, Error> {
         let UnbundlePushRebaseResponse {
             commonheads,
@@ -157,6 +158,16 @@ impl UnbundleResponse {
             false => None,
         };
 
+        let output_part = if hook_warnings.is_empty() {
+            None
+        } else {
+            let output: Vec<_> = hook_warnings
+                .iter()
+                .map(|warning| format!("{}\n", warning))
+                .collect();
+            Some(try_boxfuture!(parts::output_part(output.concat().into())))
+        };
+
         let mut scuba_logger = ctx.scuba().clone();
         maybe_onto_head
             .join(pushrebased_hg_rev)
@@ -189,6 +200,7 @@ impl UnbundleResponse {
             .and_then(move |mut cg_part_builder| {
                 cg_part_builder.extend(bookmark_reply_part.into_iter());
                 cg_part_builder.extend(obsmarkers_part.into_iter());
+                cg_part_builder.extend(output_part.into_iter());
                 let compression = None;
                 create_bundle_stream(cg_part_builder, compression)
                     .collect()
@@ -242,7 +254,8 @@ impl UnbundleResponse {
             .boxify()
     }
 
-    /// Produce bundle2 response parts for the completed `unbundle` processing
+    /// Produce bundle2 response parts for the completed `unbundle` processing. Warnings from
+    /// hooks are sent to be shown to the user.
     pub fn generate_bytes(
         self,
         ctx: CoreContext,
@@ -251,6 +264,7 @@ impl UnbundleResponse {
         pushrebase_params: PushrebaseParams,
         lca_hint: Arc<dyn LeastCommonAncestorsHint>,
         lfs_params: SessionLfsParams,
+        hook_warnings: Vec<HookWarning>,
     ) -> BoxFuture<Bytes, Error> {
         match self {
             UnbundleResponse::Push(data) => Self::generate_push_response_bytes(ctx, data),
@@ -265,6 +279,7 @@ impl UnbundleResponse {
                 pushrebase_params,
                 lca_hint,
                 lfs_params,
+                hook_warnings,
             ),
             UnbundleResponse::BookmarkOnlyPushRebase(data) => {
                 Self::generate_bookmark_only_pushrebase_response_bytes(ctx, data)
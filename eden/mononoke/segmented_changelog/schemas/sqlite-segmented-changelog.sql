@@ -14,3 +14,14 @@ CREATE TABLE segmented_changelog_idmap (
   PRIMARY KEY (vertex),
   UNIQUE (cs_id)
 );
+
+/*
+ * Each row points at a serialized IdDag in the blobstore that covers all
+ * vertexes up to and including head_vertex. The row with the greatest
+ * head_vertex is the one to load.
+ */
+CREATE TABLE segmented_changelog_iddag_version (
+  head_vertex BIGINT NOT NULL,
+  iddag_version VARBINARY(32) NOT NULL,
+  PRIMARY KEY (head_vertex)
+);
@@ -5,13 +5,18 @@
  * GNU General Public License version 2.
  */
 
-use anyhow::{format_err, Result};
+use std::collections::BTreeMap;
+
+use anyhow::{bail, format_err, Context, Result};
 use futures::{
     future,
     stream::{self, StreamExt, TryStreamExt},
 };
 
-use dag::{self, Id as Vertex, InProcessIdDag, Level};
+use dag::{
+    self, protocol::CloneData, FirstAncestorConstraint, Group, Id as Vertex, InProcessIdDag, Level,
+    SpanSet,
+};
 
 use blobrepo::BlobRepo;
 use context::CoreContext;
@@ -20,6 +25,30 @@ use sql_construct::SqlConstruct;
 
 use crate::{idmap::IdMap, parents::Parents};
 
+// Number of vertexes translated to changesets in one IdMap query
+const IDMAP_CHUNK_SIZE: usize = 1000;
+
+/// A commit identified by a descendant and the number of first parent steps from it,
+/// `descendant~distance` in revset syntax.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Location<Name> {
+    pub descendant: Name,
+    pub distance: u64,
+}
+
+impl<Name> Location<Name> {
+    pub fn new(descendant: Name, distance: u64) -> Self {
+        Self {
+            descendant,
+            distance,
+        }
+    }
+
+    pub fn map_descendant<T, F: FnOnce(Name) -> T>(self, f: F) -> Location<T> {
+        Location::new(f(self.descendant), self.distance)
+    }
+}
+
 // Note. The equivalent graph in the scm/lib/dag crate is `NameDag`.
 pub struct Dag {
     idmap: IdMap,
@@ -27,22 +56,47 @@ pub struct Dag {
 }
 
 impl Dag {
+    pub(crate) fn new(idmap: IdMap, iddag: InProcessIdDag) -> Self {
+        Dag { idmap, iddag }
+    }
+
     pub fn new_in_process() -> Result<Self> {
         let idmap = IdMap::with_sqlite_in_memory()?;
         let iddag = InProcessIdDag::new_in_process();
         Ok(Dag { idmap, iddag })
     }
 
-    // Dummy method. A production setup would have the changeset built by a separate job.
+    pub(crate) fn iddag(&self) -> &InProcessIdDag {
+        &self.iddag
+    }
+
+    /// The highest vertex covered by the IdDag, None if it is empty.
+    pub fn head_vertex(&self) -> Result<Option<Vertex>> {
+        let next_vertex = self.iddag.next_free_id(0 as Level, Group::MASTER)?;
+        if next_vertex > Group::MASTER.min_id() {
+            Ok(Some(next_vertex - 1))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Assigns vertexes to the new ancestors of `head` and extends the IdDag to cover them.
+    // Segments built by earlier calls are kept, so only the new part of the graph is walked.
     pub async fn build_up(
         &mut self,
         ctx: &CoreContext,
         blob_repo: &BlobRepo,
         head: ChangesetId,
     ) -> Result<()> {
-        let high_vertex = self.idmap.build_up(ctx, blob_repo, head).await?;
+        self.idmap.build_up(ctx, blob_repo, head).await?;
+        // The IdMap may hold vertexes beyond `head`, assigned by a build up that did not get to
+        // update the IdDag. Cover those too, so that the IdDag has no gaps.
+        let high_vertex = match self.idmap.get_last_entry().await? {
+            None => return Ok(()),
+            Some((vertex, _)) => vertex,
+        };
         let low_vertex = self.iddag.next_free_id(0 as Level, high_vertex.group())?;
-        if low_vertex >= high_vertex {
+        if low_vertex > high_vertex {
             return Ok(());
         }
         let idmap = &self.idmap;
@@ -89,6 +143,190 @@ impl Dag {
         let dist_ancestor = self.idmap.get_changeset_id(dist_ancestor_vertex).await?;
         Ok(dist_ancestor)
     }
+
+    /// The commit at `location` followed by its first ancestors, `count` commits in total.
+    pub async fn location_to_many_changeset_ids(
+        &self,
+        location: Location<ChangesetId>,
+        count: u64,
+    ) -> Result<Vec<ChangesetId>> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let known_vertex = self.idmap.get_vertex(location.descendant).await?;
+        // Check that the last commit requested exists before walking to it, so that a count
+        // beyond the root fails up front.
+        let last_distance = location
+            .distance
+            .checked_add(count - 1)
+            .ok_or_else(|| format_err!("invalid count {} for {:?}", count, location))?;
+        self.iddag
+            .first_ancestor_nth(known_vertex, last_distance)
+            .with_context(|| {
+                format!(
+                    "{:?} with count {} goes past the root of the segmented changelog",
+                    location, count
+                )
+            })?;
+        let mut vertex = self
+            .iddag
+            .first_ancestor_nth(known_vertex, location.distance)?;
+        let mut vertexes = Vec::with_capacity(count as usize);
+        vertexes.push(vertex);
+        for _ in 1..count {
+            vertex = self.iddag.first_ancestor_nth(vertex, 1)?;
+            vertexes.push(vertex);
+        }
+        self.changeset_ids(&vertexes).await
+    }
+
+    /// Express `cs_id` as a first ancestor of a commit the client is guaranteed to know the
+    /// hash of: one of `master_heads`, or a parent of a merge. None if `cs_id` is not an
+    /// ancestor of `master_heads` or the IdDag does not cover it.
+    pub async fn changeset_id_to_location(
+        &self,
+        master_heads: &[ChangesetId],
+        cs_id: ChangesetId,
+    ) -> Result<Option<Location<ChangesetId>>> {
+        let mut head_vertexes = Vec::with_capacity(master_heads.len());
+        for head in master_heads {
+            match self.find_covered_vertex(*head).await? {
+                Some(vertex) => head_vertexes.push(vertex),
+                None => bail!(
+                    "master head {} is not covered by the segmented changelog",
+                    head
+                ),
+            }
+        }
+        let vertex = match self.find_covered_vertex(cs_id).await? {
+            None => return Ok(None),
+            Some(vertex) => vertex,
+        };
+        let constraint = FirstAncestorConstraint::KnownUniversally {
+            heads: SpanSet::from_spans(head_vertexes),
+        };
+        match self.iddag.to_first_ancestor_nth(vertex, constraint)? {
+            None => Ok(None),
+            Some((descendant, distance)) => {
+                let descendant = self.idmap.get_changeset_id(descendant).await?;
+                Ok(Some(Location::new(descendant, distance)))
+            }
+        }
+    }
+
+    /// The data a client needs to clone the commit graph without fetching every hash: the
+    /// flat segments and the changesets of the vertexes it has to know the hash of.
+    pub async fn clone_data(&self) -> Result<CloneData<ChangesetId>> {
+        let head_id = match self.head_vertex()? {
+            None => bail!("the segmented changelog is empty"),
+            Some(head_vertex) => head_vertex,
+        };
+        let flat_segments = self.iddag.flat_segments(Group::MASTER)?;
+        let universal: Vec<Vertex> = self.iddag.universal()?.into_iter().collect();
+        let cs_ids = self.changeset_ids(&universal).await?;
+        let idmap: BTreeMap<Vertex, ChangesetId> = universal.into_iter().zip(cs_ids).collect();
+        Ok(CloneData {
+            head_id,
+            flat_segments,
+            idmap,
+        })
+    }
+
+    /// Whether `ancestor` is an ancestor of `descendant`. A commit is its own ancestor.
+    /// None if the IdDag does not cover `descendant`.
+    pub async fn is_ancestor(
+        &self,
+        ancestor: ChangesetId,
+        descendant: ChangesetId,
+    ) -> Result<Option<bool>> {
+        let (ancestor_vertex, descendant_vertex) = future::try_join(
+            self.find_covered_vertex(ancestor),
+            self.find_covered_vertex(descendant),
+        )
+        .await?;
+        match (ancestor_vertex, descendant_vertex) {
+            (_, None) => Ok(None),
+            // All ancestors of a covered commit are covered
+            (None, Some(_)) => Ok(Some(false)),
+            (Some(ancestor_vertex), Some(descendant_vertex)) => Ok(Some(
+                self.iddag.is_ancestor(ancestor_vertex, descendant_vertex)?,
+            )),
+        }
+    }
+
+    /// The heads of the common ancestors of `cs_ids`. There is more than one after criss-cross
+    /// merges, and none if `cs_ids` have no common history.
+    /// None if the IdDag does not cover all of `cs_ids`.
+    pub async fn greatest_common_ancestors(
+        &self,
+        cs_ids: &[ChangesetId],
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        let vertexes =
+            future::try_join_all(cs_ids.iter().map(|cs_id| self.find_covered_vertex(*cs_id)))
+                .await?;
+        let vertexes: Option<Vec<Vertex>> = vertexes.into_iter().collect();
+        match vertexes {
+            None => Ok(None),
+            Some(vertexes) => {
+                let gca = self.iddag.gca_all(SpanSet::from_spans(vertexes))?;
+                let gca: Vec<Vertex> = gca.iter().collect();
+                Ok(Some(self.changeset_ids(&gca).await?))
+            }
+        }
+    }
+
+    /// Ancestors of `head` in topological order, `head` first, skipping `skip` commits and
+    /// returning at most `limit`. With `descendants_of`, only commits that descend from it are
+    /// included, giving the `descendants_of::head` range.
+    /// None if the IdDag does not cover `head`.
+    pub async fn linear_history(
+        &self,
+        head: ChangesetId,
+        descendants_of: Option<ChangesetId>,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Option<Vec<ChangesetId>>> {
+        let head_vertex = match self.find_covered_vertex(head).await? {
+            None => return Ok(None),
+            Some(head_vertex) => head_vertex,
+        };
+        let set = match descendants_of {
+            None => self.iddag.ancestors(head_vertex)?,
+            Some(root) => match self.find_covered_vertex(root).await? {
+                // Not an ancestor of `head`, so the range is empty
+                None => SpanSet::empty(),
+                Some(root_vertex) => self.iddag.range(root_vertex, head_vertex)?,
+            },
+        };
+        // SpanSet iterates from the highest vertex, which is a topological order
+        let vertexes: Vec<Vertex> = set.iter().skip(skip).take(limit).collect();
+        Ok(Some(self.changeset_ids(&vertexes).await?))
+    }
+
+    // The vertex of `cs_id`, if the IdDag covers it. Commits that were added to the repository
+    // after the IdDag was last built up are not covered.
+    async fn find_covered_vertex(&self, cs_id: ChangesetId) -> Result<Option<Vertex>> {
+        let head_vertex = match self.head_vertex()? {
+            None => return Ok(None),
+            Some(head_vertex) => head_vertex,
+        };
+        let vertex = self.idmap.find_vertex(cs_id).await?;
+        Ok(vertex.filter(|vertex| *vertex <= head_vertex))
+    }
+
+    async fn changeset_ids(&self, vertexes: &[Vertex]) -> Result<Vec<ChangesetId>> {
+        let mut cs_ids = Vec::with_capacity(vertexes.len());
+        for chunk in vertexes.chunks(IDMAP_CHUNK_SIZE) {
+            let mut found = self.idmap.find_many_changeset_ids(chunk).await?;
+            for vertex in chunk {
+                let cs_id = found.remove(vertex).ok_or_else(|| {
+                    format_err!("Failed to find segmented changelog id {} in IdMap", vertex)
+                })?;
+                cs_ids.push(cs_id);
+            }
+        }
+        Ok(cs_ids)
+    }
 }
 
 #[cfg(test)]
@@ -174,4 +412,91 @@ mod tests {
 
         Ok(())
     }
+
+    #[fbinit::compat_test]
+    async fn test_ancestry_queries(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let mut dag = Dag::new_in_process()?;
+
+        let head = resolve_cs_id(&ctx, &repo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+        let middle = resolve_cs_id(&ctx, &repo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
+        let low = resolve_cs_id(&ctx, &repo, "d0a361e9022d226ae52f689667bd7d212a19cfe0").await?;
+
+        assert_eq!(dag.is_ancestor(low, middle).await?, None);
+
+        dag.build_up(&ctx, &repo, middle).await?;
+        assert_eq!(dag.is_ancestor(low, middle).await?, Some(true));
+        assert_eq!(dag.is_ancestor(middle, middle).await?, Some(true));
+        assert_eq!(dag.is_ancestor(middle, low).await?, Some(false));
+        assert_eq!(dag.is_ancestor(head, middle).await?, Some(false));
+        assert_eq!(dag.is_ancestor(middle, head).await?, None);
+        assert_eq!(
+            dag.greatest_common_ancestors(&[low, middle]).await?,
+            Some(vec![low])
+        );
+        assert_eq!(dag.greatest_common_ancestors(&[low, head]).await?, None);
+
+        dag.build_up(&ctx, &repo, head).await?;
+        assert_eq!(dag.is_ancestor(middle, head).await?, Some(true));
+        let history = dag.linear_history(head, Some(middle), 0, 10).await?;
+        assert_eq!(history.as_ref().map(|h| h.len()), Some(5));
+        assert_eq!(history.as_ref().and_then(|h| h.first()), Some(&head));
+        assert_eq!(history.as_ref().and_then(|h| h.last()), Some(&middle));
+        let parent = dag.location_to_changeset_id(head, 1).await?;
+        assert_eq!(
+            dag.linear_history(head, Some(middle), 1, 1).await?,
+            Some(vec![parent])
+        );
+        assert_eq!(
+            dag.linear_history(head, None, 4, 1).await?,
+            Some(vec![middle])
+        );
+        assert_eq!(
+            dag.linear_history(middle, Some(head), 0, 10).await?,
+            Some(vec![])
+        );
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_clone_data_and_locations(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let mut dag = Dag::new_in_process()?;
+
+        let head = resolve_cs_id(&ctx, &repo, "79a13814c5ce7330173ec04d279bf95ab3f652fb").await?;
+        let middle = resolve_cs_id(&ctx, &repo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
+
+        assert!(dag.clone_data().await.is_err());
+        dag.build_up(&ctx, &repo, head).await?;
+
+        let clone_data = dag.clone_data().await?;
+        let head_vertex = dag.head_vertex()?.unwrap();
+        assert_eq!(clone_data.head_id, head_vertex);
+        assert_eq!(clone_data.flat_segments.len(), 1);
+        assert_eq!(clone_data.idmap.get(&head_vertex), Some(&head));
+
+        let location = dag.changeset_id_to_location(&[head], middle).await?;
+        assert_eq!(location, Some(Location::new(head, 4)));
+        let expected = dag.linear_history(head, None, 4, 2).await?.unwrap();
+        assert_eq!(
+            dag.location_to_many_changeset_ids(Location::new(head, 4), 2)
+                .await?,
+            expected
+        );
+        assert_eq!(
+            dag.location_to_many_changeset_ids(Location::new(head, 4), 0)
+                .await?,
+            vec![]
+        );
+        assert!(dag
+            .location_to_many_changeset_ids(Location::new(head, 4), u64::max_value())
+            .await
+            .is_err());
+        assert_eq!(dag.changeset_id_to_location(&[middle], head).await?, None);
+
+        Ok(())
+    }
 }
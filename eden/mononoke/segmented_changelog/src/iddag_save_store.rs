@@ -0,0 +1,98 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+
+use anyhow::{format_err, Context, Result};
+use futures::compat::Future01CompatExt;
+
+use dag::InProcessIdDag;
+
+use blobstore::Blobstore;
+use context::CoreContext;
+use mononoke_types::{hash, BlobstoreBytes};
+
+/// Identifies a serialized IdDag. Derived from the serialized bytes, so saving the same IdDag
+/// twice gives the same version.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct IdDagVersion(pub hash::Blake2);
+
+impl IdDagVersion {
+    pub fn from_serialized_bytes(bytes: &[u8]) -> Self {
+        let mut context = hash::Context::new(b"segmented_changelog_iddag");
+        context.update(bytes);
+        IdDagVersion(context.finish())
+    }
+}
+
+/// Stores serialized IdDags in the blobstore.
+#[derive(Clone)]
+pub struct IdDagSaveStore {
+    blobstore: Arc<dyn Blobstore>,
+}
+
+impl IdDagSaveStore {
+    pub fn new(blobstore: Arc<dyn Blobstore>) -> Self {
+        Self { blobstore }
+    }
+
+    pub async fn find(
+        &self,
+        ctx: &CoreContext,
+        iddag_version: IdDagVersion,
+    ) -> Result<Option<InProcessIdDag>> {
+        let bytes_opt = self
+            .blobstore
+            .get(ctx.clone(), self.key(iddag_version))
+            .compat()
+            .await?;
+        bytes_opt
+            .map(|bytes| {
+                mincode::deserialize(bytes.as_raw_bytes()).with_context(|| {
+                    format!(
+                        "failed to deserialize segmented changelog iddag {:?}",
+                        iddag_version
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    pub async fn load(
+        &self,
+        ctx: &CoreContext,
+        iddag_version: IdDagVersion,
+    ) -> Result<InProcessIdDag> {
+        self.find(ctx, iddag_version).await?.ok_or_else(|| {
+            format_err!(
+                "failed to find segmented changelog iddag {:?} in blobstore",
+                iddag_version
+            )
+        })
+    }
+
+    pub async fn save(&self, ctx: &CoreContext, iddag: &InProcessIdDag) -> Result<IdDagVersion> {
+        let buffer = mincode::serialize(iddag)?;
+        let iddag_version = IdDagVersion::from_serialized_bytes(&buffer);
+        self.blobstore
+            .put(
+                ctx.clone(),
+                self.key(iddag_version),
+                BlobstoreBytes::from_bytes(buffer),
+            )
+            .compat()
+            .await?;
+        Ok(iddag_version)
+    }
+
+    fn key(&self, iddag_version: IdDagVersion) -> String {
+        format!(
+            "segmented_changelog.iddag.blake2.{}",
+            iddag_version.0.to_hex()
+        )
+    }
+}
@@ -5,7 +5,9 @@
  * GNU General Public License version 2.
  */
 
-use anyhow::{format_err, Result};
+use std::collections::HashMap;
+
+use anyhow::{format_err, Error, Result};
 use futures::compat::Future01CompatExt;
 use maplit::hashset;
 use sql::queries;
@@ -24,11 +26,10 @@ use crate::parents::Parents;
 pub struct IdMap(SqlConnections);
 
 queries! {
-    // TODO(sfilip): upsert is a hack around a limited build_up implementation, we want insert_or_ignore.
     write InsertIdMapEntry(values: (vertex: u64, cs_id: ChangesetId)) {
-        none,
+        insert_or_ignore,
         "
-        INSERT OR REPLACE INTO segmented_changelog_idmap (vertex, cs_id)
+        {insert_or_ignore} INTO segmented_changelog_idmap (vertex, cs_id)
         VALUES {values}
         "
     }
@@ -41,6 +42,14 @@ queries! {
         "
     }
 
+    read SelectManyChangesetIds(>list vertex: u64) -> (u64, ChangesetId) {
+        "
+        SELECT idmap.vertex as vertex, idmap.cs_id as cs_id
+        FROM segmented_changelog_idmap AS idmap
+        WHERE idmap.vertex IN {vertex}
+        "
+    }
+
     read SelectVertex(cs_id: ChangesetId) -> (u64) {
         "
         SELECT idmap.vertex as vertex
@@ -49,6 +58,14 @@ queries! {
         "
     }
 
+    read SelectLastEntry() -> (u64, ChangesetId) {
+        "
+        SELECT idmap.vertex as vertex, idmap.cs_id as cs_id
+        FROM segmented_changelog_idmap AS idmap
+        ORDER BY idmap.vertex DESC
+        LIMIT 1
+        "
+    }
 }
 
 impl SqlConstruct for IdMap {
@@ -65,42 +82,55 @@ impl SqlConstructFromMetadataDatabaseConfig for IdMap {}
 
 impl IdMap {
     pub async fn insert(&self, vertex: Vertex, cs_id: ChangesetId) -> Result<()> {
-        // TODO(sfilip): add tests
         let result = InsertIdMapEntry::query(&self.0.write_connection, &[(&vertex.0, &cs_id)])
             .compat()
             .await?;
-        if result.affected_rows() != 1 {
-            let stored = SelectChangesetId::query(&self.0.read_master_connection, &vertex.0)
-                .compat()
-                .await?;
-            match stored.as_slice() {
-                &[] => {
+        if result.affected_rows() == 1 {
+            return Ok(());
+        }
+        // The insert was ignored because the vertex or the changeset is already assigned, e.g.
+        // by a concurrent build up. That is only fine if it was assigned the same way.
+        let stored = SelectChangesetId::query(&self.0.read_master_connection, &vertex.0)
+            .compat()
+            .await?;
+        match stored.as_slice() {
+            &[] => {}
+            &[(store_cs_id,)] => {
+                if store_cs_id != cs_id {
                     return Err(format_err!(
-                        "Failed to insert entry ({} -> {}) in Idmap",
+                        "Duplicate segmented changelog idmap entry {} \
+                            has different assignments: {} vs {}",
                         vertex,
-                        cs_id
-                    ))
+                        cs_id,
+                        store_cs_id
+                    ));
                 }
-                &[(store_cs_id,)] => {
-                    if store_cs_id != cs_id {
-                        return Err(format_err!(
-                            "Duplicate segmented changelog idmap entry {} \
-                                has different assignments: {} vs {}",
-                            vertex,
-                            cs_id,
-                            store_cs_id
-                        ));
-                    }
-                }
-                _ => {
-                    return Err(format_err!(
-                        "Duplicate segmented changelog idmap entries: {:?}",
-                        stored
-                    ))
-                }
-            };
+                return Ok(());
+            }
+            _ => {
+                return Err(format_err!(
+                    "Duplicate segmented changelog idmap entries: {:?}",
+                    stored
+                ))
+            }
+        };
+        let stored = SelectVertex::query(&self.0.read_master_connection, &cs_id)
+            .compat()
+            .await?;
+        match stored.as_slice() {
+            &[(store_vertex,)] => Err(format_err!(
+                "Duplicate segmented changelog idmap entry {} \
+                    has different assignments: {} vs {}",
+                cs_id,
+                vertex,
+                Vertex(store_vertex)
+            )),
+            _ => Err(format_err!(
+                "Failed to insert entry ({} -> {}) in Idmap",
+                vertex,
+                cs_id
+            )),
         }
-        Ok(())
     }
 
     pub async fn find_changeset_id(&self, vertex: Vertex) -> Result<Option<ChangesetId>> {
@@ -122,6 +152,30 @@ impl IdMap {
             .ok_or_else(|| format_err!("Failed to find segmented changelog id {} in IdMap", vertex))
     }
 
+    pub async fn find_many_changeset_ids(
+        &self,
+        vertexes: &[Vertex],
+    ) -> Result<HashMap<Vertex, ChangesetId>> {
+        let select = |connection, vertexes: Vec<u64>| async move {
+            let rows = SelectManyChangesetIds::query(connection, &vertexes[..])
+                .compat()
+                .await?;
+            Ok::<_, Error>(rows.into_iter().map(|(v, cs_id)| (Vertex(v), cs_id)))
+        };
+        let vertexes: Vec<u64> = vertexes.iter().map(|v| v.0).collect();
+        let mut result: HashMap<_, _> = select(&self.0.read_connection, vertexes.clone())
+            .await?
+            .collect();
+        let missing: Vec<u64> = vertexes
+            .into_iter()
+            .filter(|v| !result.contains_key(&Vertex(*v)))
+            .collect();
+        if !missing.is_empty() {
+            result.extend(select(&self.0.read_master_connection, missing).await?);
+        }
+        Ok(result)
+    }
+
     pub async fn find_vertex(&self, cs_id: ChangesetId) -> Result<Option<Vertex>> {
         let select = |connection| async move {
             let rows = SelectVertex::query(connection, &cs_id).compat().await?;
@@ -139,6 +193,22 @@ impl IdMap {
             .ok_or_else(|| format_err!("Failed to find find changeset id {} in IdMap", cs_id))
     }
 
+    /// The highest assigned vertex. Vertexes are assigned consecutively, so every vertex up to
+    /// this one has a changeset.
+    pub async fn get_last_entry(&self) -> Result<Option<(Vertex, ChangesetId)>> {
+        let rows = SelectLastEntry::query(&self.0.read_master_connection)
+            .compat()
+            .await?;
+        Ok(rows
+            .into_iter()
+            .next()
+            .map(|(vertex, cs_id)| (Vertex(vertex), cs_id)))
+    }
+
+    // Assigns vertexes to the ancestors of `head` that don't have one yet, continuing after the
+    // last assigned vertex. Ancestors of an assigned changeset are assigned too, so the walk
+    // stops there. A concurrent build up that assigned a vertex or a changeset differently makes
+    // the insert fail, so this one errors out instead of leaving a conflicting entry behind.
     pub async fn build_up(
         &self,
         ctx: &CoreContext,
@@ -149,13 +219,19 @@ impl IdMap {
             Visit(ChangesetId),
             Assign(ChangesetId),
         }
-        let mut next_vertex = dag::Group::MASTER.min_id().0;
+        let mut next_vertex = match self.get_last_entry().await? {
+            None => dag::Group::MASTER.min_id().0,
+            Some((vertex, _)) => vertex.0 + 1,
+        };
         let parents = Parents::new(ctx, blob_repo);
         let mut todo_stack = vec![Todo::Visit(head)];
         let mut seen = hashset![head];
         while let Some(todo) = todo_stack.pop() {
             match todo {
                 Todo::Visit(cs_id) => {
+                    if self.find_vertex(cs_id).await?.is_some() {
+                        continue;
+                    }
                     todo_stack.push(Todo::Assign(cs_id));
                     let parents = parents.get(cs_id).await?;
                     for parent in parents.into_iter().rev() {
@@ -239,4 +315,49 @@ mod tests {
         .await?;
         Ok(())
     }
+
+    #[fbinit::compat_test]
+    async fn test_incremental_build_up_idmap(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let idmap = IdMap::with_sqlite_in_memory()?;
+
+        let first = resolve_cs_id(&ctx, &repo, "d0a361e9022d226ae52f689667bd7d212a19cfe0").await?;
+        let first_vertex = idmap.build_up(&ctx, &repo, first).await?;
+        assert_eq!(idmap.get_last_entry().await?, Some((first_vertex, first)));
+
+        // Building up again does not assign new vertexes
+        assert_eq!(idmap.build_up(&ctx, &repo, first).await?, first_vertex);
+        assert_eq!(idmap.get_last_entry().await?, Some((first_vertex, first)));
+
+        // Descendants are assigned after the existing vertexes
+        let second = resolve_cs_id(&ctx, &repo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
+        let second_vertex = idmap.build_up(&ctx, &repo, second).await?;
+        assert!(second_vertex > first_vertex);
+        assert_eq!(idmap.get_vertex(first).await?, first_vertex);
+        assert_eq!(idmap.get_last_entry().await?, Some((second_vertex, second)));
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_insert_conflicts(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let idmap = IdMap::with_sqlite_in_memory()?;
+        let first = resolve_cs_id(&ctx, &repo, "d0a361e9022d226ae52f689667bd7d212a19cfe0").await?;
+        let second = resolve_cs_id(&ctx, &repo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
+
+        idmap.insert(Vertex(1), first).await?;
+        // Inserting the same entry again is fine
+        idmap.insert(Vertex(1), first).await?;
+        // The vertex is already assigned to another changeset
+        assert!(idmap.insert(Vertex(1), second).await.is_err());
+        // The changeset is already assigned to another vertex
+        assert!(idmap.insert(Vertex(2), first).await.is_err());
+
+        assert_eq!(idmap.get_changeset_id(Vertex(1)).await?, first);
+        assert_eq!(idmap.find_changeset_id(Vertex(2)).await?, None);
+        Ok(())
+    }
 }
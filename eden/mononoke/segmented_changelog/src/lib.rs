@@ -12,5 +12,9 @@
 ///!
 ///! Data structures and algorithms for a commit graph used by source control.
 pub mod dag;
+mod iddag_save_store;
 mod idmap;
+pub mod manager;
 mod parents;
+pub mod periodic_reload;
+mod version_store;
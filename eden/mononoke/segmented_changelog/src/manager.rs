@@ -0,0 +1,233 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{format_err, Result};
+use futures::compat::Future01CompatExt;
+use slog::{info, warn};
+use sql_ext::SqlConnections;
+
+use dag::{Id as Vertex, InProcessIdDag};
+
+use blobrepo::BlobRepo;
+use blobstore::Blobstore;
+use bookmarks::BookmarkName;
+use context::CoreContext;
+use mononoke_types::ChangesetId;
+use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
+
+use crate::dag::Dag;
+use crate::iddag_save_store::IdDagSaveStore;
+use crate::idmap::IdMap;
+use crate::version_store::{IdDagVersionStore, SavedIdDag};
+
+/// Connections to the database holding the segmented changelog tables.
+pub struct SegmentedChangelogSqlConnections(pub SqlConnections);
+
+impl SqlConstruct for SegmentedChangelogSqlConnections {
+    const LABEL: &'static str = "segmented_changelog";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-segmented-changelog.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self(connections)
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SegmentedChangelogSqlConnections {}
+
+/// Loads and saves the segmented changelog of a repository. The IdMap and the list of saved
+/// IdDags live in SQL, the serialized IdDags in the blobstore.
+///
+/// The segmented_changelog_tailer binary keeps the saved graph up to date with `run_tailer`.
+/// Servers load it with `load_dag`, and follow the tailer's saves with PeriodicReloadDag.
+pub struct SegmentedChangelogManager {
+    idmap: IdMap,
+    version_store: IdDagVersionStore,
+    iddag_save_store: IdDagSaveStore,
+}
+
+impl SegmentedChangelogManager {
+    pub fn new(
+        connections: SegmentedChangelogSqlConnections,
+        blobstore: Arc<dyn Blobstore>,
+    ) -> Self {
+        let connections = connections.0;
+        Self {
+            idmap: IdMap::from_sql_connections(connections.clone()),
+            version_store: IdDagVersionStore::from_sql_connections(connections),
+            iddag_save_store: IdDagSaveStore::new(blobstore),
+        }
+    }
+
+    /// Load the latest saved Dag. If nothing was saved yet the Dag is empty.
+    pub async fn load_dag(&self, ctx: &CoreContext) -> Result<Dag> {
+        let iddag = match self.version_store.get_latest().await? {
+            None => InProcessIdDag::new_in_process(),
+            Some(saved) => self.iddag_save_store.load(ctx, saved.iddag_version).await?,
+        };
+        Ok(Dag::new(self.idmap.clone(), iddag))
+    }
+
+    /// Load the latest saved Dag if it covers more vertexes than `head_vertex`, the head of a
+    /// Dag loaded earlier.
+    pub async fn load_newer_dag(
+        &self,
+        ctx: &CoreContext,
+        head_vertex: Option<Vertex>,
+    ) -> Result<Option<Dag>> {
+        match self.version_store.get_latest().await? {
+            Some(saved) if Some(saved.head_vertex) > head_vertex => {
+                let iddag = self.iddag_save_store.load(ctx, saved.iddag_version).await?;
+                Ok(Some(Dag::new(self.idmap.clone(), iddag)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Save the IdDag of `dag` so that it becomes the one `load_dag` returns, unless a save
+    /// covering more vertexes already happened.
+    pub async fn save_dag(&self, ctx: &CoreContext, dag: &Dag) -> Result<()> {
+        let head_vertex = match dag.head_vertex()? {
+            None => return Ok(()),
+            Some(head_vertex) => head_vertex,
+        };
+        let iddag_version = self.iddag_save_store.save(ctx, dag.iddag()).await?;
+        self.version_store
+            .insert(SavedIdDag {
+                head_vertex,
+                iddag_version,
+            })
+            .await
+    }
+
+    /// Extend the saved Dag with the ancestors of `head`. An interrupted update is picked up
+    /// by the next one, as both the IdMap and the IdDag are only ever extended.
+    pub async fn update(
+        &self,
+        ctx: &CoreContext,
+        blob_repo: &BlobRepo,
+        head: ChangesetId,
+    ) -> Result<Dag> {
+        let mut dag = self.load_dag(ctx).await?;
+        dag.build_up(ctx, blob_repo, head).await?;
+        self.save_dag(ctx, &dag).await?;
+        Ok(dag)
+    }
+
+    /// Follow `bookmark`, saving the Dag every time it moves. Runs until an error occurs.
+    pub async fn run_tailer(
+        &self,
+        ctx: &CoreContext,
+        blob_repo: &BlobRepo,
+        bookmark: &BookmarkName,
+        period: Duration,
+    ) -> Result<()> {
+        let mut dag = self.load_dag(ctx).await?;
+        let mut last_head = None;
+        loop {
+            let head = blob_repo
+                .get_bonsai_bookmark(ctx.clone(), bookmark)
+                .compat()
+                .await?
+                .ok_or_else(|| format_err!("bookmark {} not found", bookmark))?;
+            if last_head != Some(head) {
+                let old_head_vertex = dag.head_vertex()?;
+                dag.build_up(ctx, blob_repo, head).await?;
+                if dag.head_vertex()? != old_head_vertex {
+                    self.save_dag(ctx, &dag).await?;
+                    info!(
+                        ctx.logger(),
+                        "segmented changelog updated to {} ({:?})",
+                        head,
+                        dag.head_vertex()?
+                    );
+                } else {
+                    warn!(
+                        ctx.logger(),
+                        "bookmark {} moved to {} which is already in the segmented changelog",
+                        bookmark,
+                        head
+                    );
+                }
+                last_head = Some(head);
+            }
+            tokio::time::delay_for(period).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fbinit::FacebookInit;
+
+    use fixtures::linear;
+    use tests_utils::resolve_cs_id;
+
+    fn new_manager(blob_repo: &BlobRepo) -> Result<SegmentedChangelogManager> {
+        Ok(SegmentedChangelogManager::new(
+            SegmentedChangelogSqlConnections::with_sqlite_in_memory()?,
+            Arc::new(blob_repo.get_blobstore()),
+        ))
+    }
+
+    #[fbinit::compat_test]
+    async fn test_save_load_dag(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let manager = new_manager(&repo)?;
+
+        let dag = manager.load_dag(&ctx).await?;
+        assert_eq!(dag.head_vertex()?, None);
+
+        let known_cs =
+            resolve_cs_id(&ctx, &repo, "d0a361e9022d226ae52f689667bd7d212a19cfe0").await?;
+        let head_vertex = manager.update(&ctx, &repo, known_cs).await?.head_vertex()?;
+        assert!(head_vertex.is_some());
+
+        let dag = manager.load_dag(&ctx).await?;
+        assert_eq!(dag.head_vertex()?, head_vertex);
+        let answer = dag.location_to_changeset_id(known_cs, 2).await?;
+        let expected_cs =
+            resolve_cs_id(&ctx, &repo, "3e0e761030db6e479a7fb58b12881883f9f8c63f").await?;
+        assert_eq!(answer, expected_cs);
+
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_incremental_update(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let manager = new_manager(&repo)?;
+
+        let first_cs =
+            resolve_cs_id(&ctx, &repo, "d0a361e9022d226ae52f689667bd7d212a19cfe0").await?;
+        let first_vertex = manager.update(&ctx, &repo, first_cs).await?.head_vertex()?;
+
+        let second_cs =
+            resolve_cs_id(&ctx, &repo, "0ed509bf086fadcb8a8a5384dc3b550729b0fc17").await?;
+        manager.update(&ctx, &repo, second_cs).await?;
+
+        let dag = manager.load_dag(&ctx).await?;
+        assert!(dag.head_vertex()? > first_vertex);
+        let answer = dag.location_to_changeset_id(second_cs, 3).await?;
+        assert_eq!(answer, first_cs);
+
+        // Updating to an ancestor keeps the saved Dag
+        let saved_vertex = dag.head_vertex()?;
+        manager.update(&ctx, &repo, first_cs).await?;
+        let dag = manager.load_dag(&ctx).await?;
+        assert_eq!(dag.head_vertex()?, saved_vertex);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::{
+    channel::oneshot,
+    future::{select, FutureExt},
+};
+use slog::{info, warn};
+
+use context::CoreContext;
+
+use crate::dag::Dag;
+use crate::manager::SegmentedChangelogManager;
+
+/// A Dag that follows the saves of a tailer: every `period` it is replaced by the latest saved
+/// Dag, if that covers more commits. Reloading stops when this is dropped.
+pub struct PeriodicReloadDag {
+    dag: Arc<RwLock<Arc<Dag>>>,
+    terminate: Option<oneshot::Sender<()>>,
+}
+
+impl PeriodicReloadDag {
+    /// Load the latest saved Dag and start reloading it.
+    pub async fn start(
+        ctx: CoreContext,
+        manager: Arc<SegmentedChangelogManager>,
+        period: Duration,
+    ) -> Result<Self> {
+        let dag = Arc::new(RwLock::new(Arc::new(manager.load_dag(&ctx).await?)));
+        let (sender, receiver) = oneshot::channel();
+        spawn_reloader(ctx, manager, dag.clone(), period, receiver);
+        Ok(Self {
+            dag,
+            terminate: Some(sender),
+        })
+    }
+
+    /// A Dag that is never reloaded.
+    pub fn fixed(dag: Dag) -> Self {
+        Self {
+            dag: Arc::new(RwLock::new(Arc::new(dag))),
+            terminate: None,
+        }
+    }
+
+    /// The Dag loaded last. Queries made on it keep using it while a newer one is loaded.
+    pub fn dag(&self) -> Arc<Dag> {
+        self.dag.read().expect("lock poisoned").clone()
+    }
+}
+
+impl Drop for PeriodicReloadDag {
+    fn drop(&mut self) {
+        // Ignore any error - the reloader may have gone away already.
+        if let Some(terminate) = self.terminate.take() {
+            let _ = terminate.send(());
+        }
+    }
+}
+
+fn spawn_reloader(
+    ctx: CoreContext,
+    manager: Arc<SegmentedChangelogManager>,
+    dag: Arc<RwLock<Arc<Dag>>>,
+    period: Duration,
+    terminate: oneshot::Receiver<()>,
+) {
+    // ignore JoinHandle, because we want it to run until `terminate` receives a signal
+    let _ = tokio::spawn(async move {
+        let reload_loop = async {
+            loop {
+                tokio::time::delay_for(period).await;
+                if let Err(err) = reload(&ctx, &manager, &dag).await {
+                    warn!(
+                        ctx.logger(),
+                        "failed to reload segmented changelog: {:?}", err
+                    );
+                }
+            }
+        }
+        .boxed();
+
+        let _ = select(reload_loop, terminate).await;
+    });
+}
+
+async fn reload(
+    ctx: &CoreContext,
+    manager: &SegmentedChangelogManager,
+    dag: &RwLock<Arc<Dag>>,
+) -> Result<()> {
+    let head_vertex = dag.read().expect("lock poisoned").head_vertex()?;
+    if let Some(newer) = manager.load_newer_dag(ctx, head_vertex).await? {
+        info!(
+            ctx.logger(),
+            "reloaded segmented changelog at {:?}",
+            newer.head_vertex()?
+        );
+        *dag.write().expect("lock poisoned") = Arc::new(newer);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fbinit::FacebookInit;
+
+    use fixtures::linear;
+    use tests_utils::resolve_cs_id;
+
+    use crate::manager::SegmentedChangelogSqlConnections;
+    use sql_construct::SqlConstruct;
+
+    #[fbinit::compat_test]
+    async fn test_reload_after_update(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = linear::getrepo(fb).await;
+        let manager = Arc::new(SegmentedChangelogManager::new(
+            SegmentedChangelogSqlConnections::with_sqlite_in_memory()?,
+            Arc::new(repo.get_blobstore()),
+        ));
+
+        let reloading =
+            PeriodicReloadDag::start(ctx.clone(), manager.clone(), Duration::from_millis(10))
+                .await?;
+        assert_eq!(reloading.dag().head_vertex()?, None);
+
+        let cs_id = resolve_cs_id(&ctx, &repo, "d0a361e9022d226ae52f689667bd7d212a19cfe0").await?;
+        let head_vertex = manager.update(&ctx, &repo, cs_id).await?.head_vertex()?;
+        assert!(head_vertex.is_some());
+
+        for _ in 0..500 {
+            if reloading.dag().head_vertex()? == head_vertex {
+                return Ok(());
+            }
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+        panic!("segmented changelog was not reloaded");
+    }
+}
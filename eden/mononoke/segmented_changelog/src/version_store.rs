@@ -0,0 +1,88 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::Result;
+use futures::compat::Future01CompatExt;
+use sql::queries;
+use sql_ext::SqlConnections;
+
+use dag::Id as Vertex;
+
+use mononoke_types::hash::Blake2;
+use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
+
+use crate::iddag_save_store::IdDagVersion;
+
+/// A saved IdDag, and the highest vertex it covers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SavedIdDag {
+    pub head_vertex: Vertex,
+    pub iddag_version: IdDagVersion,
+}
+
+/// Tracks which IdDags were saved. IdDags only ever grow, so the one covering the highest
+/// vertex is the latest.
+#[derive(Clone)]
+pub struct IdDagVersionStore(SqlConnections);
+
+queries! {
+    write InsertIdDagVersion(values: (head_vertex: u64, iddag_version: &[u8])) {
+        insert_or_ignore,
+        "
+        {insert_or_ignore} INTO segmented_changelog_iddag_version (head_vertex, iddag_version)
+        VALUES {values}
+        "
+    }
+
+    read SelectLatestIdDagVersion() -> (u64, Vec<u8>) {
+        "
+        SELECT version.head_vertex as head_vertex, version.iddag_version as iddag_version
+        FROM segmented_changelog_iddag_version AS version
+        ORDER BY version.head_vertex DESC
+        LIMIT 1
+        "
+    }
+}
+
+impl SqlConstruct for IdDagVersionStore {
+    const LABEL: &'static str = "segmented_changelog_iddag_version";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-segmented-changelog.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self(connections)
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for IdDagVersionStore {}
+
+impl IdDagVersionStore {
+    // A concurrent save for the same head vertex is ignored, both describe the same graph.
+    pub async fn insert(&self, saved: SavedIdDag) -> Result<()> {
+        let iddag_version = saved.iddag_version.0;
+        InsertIdDagVersion::query(
+            &self.0.write_connection,
+            &[(&saved.head_vertex.0, &iddag_version.as_ref())],
+        )
+        .compat()
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_latest(&self) -> Result<Option<SavedIdDag>> {
+        let rows = SelectLatestIdDagVersion::query(&self.0.read_master_connection)
+            .compat()
+            .await?;
+        match rows.into_iter().next() {
+            None => Ok(None),
+            Some((head_vertex, iddag_version)) => Ok(Some(SavedIdDag {
+                head_vertex: Vertex(head_vertex),
+                iddag_version: IdDagVersion(Blake2::from_bytes(iddag_version)?),
+            })),
+        }
+    }
+}
@@ -56,6 +56,8 @@ define_perf_counters! {
         BytesSent,
         CachelibHits,
         CachelibMisses,
+        DiskCacheHits,
+        DiskCacheMisses,
         GetbundleFilenodesTotalWeight,
         GetbundleNumCommits,
         GetbundleNumDrafts,
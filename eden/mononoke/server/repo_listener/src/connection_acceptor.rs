@@ -187,8 +187,7 @@ fn accept(
                         }
                         #[cfg(not(fbcode_build))]
                         {
-                            let _ = cert;
-                            Ok(MononokeIdentitySet::new())
+                            MononokeIdentity::try_from_x509(&cert)
                         }
                     }
                     None => Err(ErrorKind::ConnectionNoClientCertificate.into()),
@@ -247,10 +246,13 @@ fn accept(
                         .add("client_ip", addr.to_string())
                         .add("client_identities", join(identities.iter(), ","));
 
+                    let repo_permchecker = handler.repo.permission_checker();
                     (async move {
-                        security_checker
+                        let is_allowed = security_checker
                             .check_if_connections_allowed(&identities)
-                            .await
+                            .await?
+                            && repo_permchecker.check_set(&identities, &["read"]).await?;
+                        Ok::<_, Error>((is_allowed, identities))
                     })
                     .boxed()
                     .compat()
@@ -263,12 +265,13 @@ fn accept(
                             )
                         }
                     })
-                    .and_then(move |is_allowed| {
+                    .and_then(move |(is_allowed, identities)| {
                         if is_allowed {
                             request_handler(
                                 fb,
                                 handler,
                                 stdio,
+                                identities,
                                 load_limiting_config,
                                 pushredirect_config,
                             )
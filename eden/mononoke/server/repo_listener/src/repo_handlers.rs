@@ -27,11 +27,13 @@ use cross_repo_sync::create_commit_syncers;
 use fbinit::FacebookInit;
 use hooks::{hook_loader::load_hooks, HookManager};
 use hooks_content_stores::blobrepo_text_only_fetcher;
+use lfs_locks::SqlLfsLocks;
 use metaconfig_types::{
     CommitSyncConfig, MetadataDatabaseConfig, RepoConfig, WireprotoLoggingConfig,
 };
 use mononoke_types::RepositoryId;
 use mutable_counters::{MutableCounters, SqlMutableCounters};
+use permission_checker::{ArcPermissionChecker, PermissionCheckerBuilder};
 use repo_client::{MononokeRepo, MononokeRepoBuilder, PushRedirector, WireprotoLogging};
 use scuba_ext::{ScubaSampleBuilder, ScubaSampleBuilderExt};
 use sql_construct::SqlConstructFromMetadataDatabaseConfig;
@@ -235,6 +237,8 @@ pub fn repo_handlers(
             let wireproto_logging = config.wireproto_logging.clone();
             let commit_sync_config = config.commit_sync_config.clone();
             let hook_manager_params = config.hook_manager_params.clone();
+            let lfs_locking_enabled = config.lfs.locking_enabled;
+            let hipster_acl = config.hipster_acl.clone();
             let record_infinitepush_writes: bool =
                 config.infinitepush.populate_reverse_filler_queue
                     && config.infinitepush.allow_writes;
@@ -298,10 +302,27 @@ pub fn repo_handlers(
                 )
                 .await?;
 
+                if lfs_locking_enabled {
+                    let locks = SqlLfsLocks::with_metadata_database_config(
+                        fb,
+                        &db_config,
+                        mysql_options,
+                        readonly_storage.0,
+                    )
+                    .await?;
+                    hook_manager.set_lfs_locks(blobrepo.get_repoid(), locks);
+                }
+
+                let permission_checker: ArcPermissionChecker =
+                    PermissionCheckerBuilder::for_repo(fb, &reponame, hipster_acl.as_deref())
+                        .await?
+                        .into();
+                hook_manager.set_permission_checker(permission_checker.clone());
+
                 info!(logger, "Loading hooks");
                 load_hooks(fb, &mut hook_manager, hook_config, &disabled_hooks)?;
 
-                let repo = builder.finalize(Arc::new(hook_manager));
+                let repo = builder.finalize(Arc::new(hook_manager), permission_checker);
 
                 let support_bundle2_listkeys = async {
                     let counters = SqlMutableCounters::with_metadata_database_config(
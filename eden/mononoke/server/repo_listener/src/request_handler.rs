@@ -19,6 +19,7 @@ use futures_stats::Timed;
 use lazy_static::lazy_static;
 use limits::types::{MononokeThrottleLimit, MononokeThrottleLimits, RateLimits};
 use maplit::{hashmap, hashset};
+use permission_checker::MononokeIdentitySet;
 use pushredirect_enable::types::MononokePushRedirectEnable;
 use slog::{self, error, info, o, warn, Drain, Level, Logger};
 use slog_ext::SimpleFormatWithError;
@@ -79,6 +80,7 @@ pub fn request_handler(
         maybe_push_redirector,
     }: RepoHandler,
     stdio: Stdio,
+    identities: MononokeIdentitySet,
     load_limiting_config: Option<(ConfigHandle<MononokeThrottleLimits>, String)>,
     pushredirect_config: Option<ConfigHandle<MononokePushRedirectEnable>>,
 ) -> impl Future<Item = (), Error = ()> {
@@ -164,13 +166,30 @@ pub fn request_handler(
     let load_limiter = load_limiting_config.map(|(config, category)| {
         let (throttle_limits, rate_limits) =
             loadlimiting_configs(config, &client_hostname, &ssh_env_vars);
-        LoadLimiterBuilder::build(fb, throttle_limits, rate_limits, category)
+        // Where the load is counted per client, an abusive client only gets itself throttled.
+        let client = if identities.is_empty() {
+            client_hostname.clone()
+        } else {
+            identities
+                .iter()
+                .map(|identity| identity.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        LoadLimiterBuilder::build_for_client(
+            fb,
+            throttle_limits,
+            rate_limits,
+            category,
+            Some(&client),
+        )
     });
 
     let mut session_builder = SessionContainer::builder(fb)
         .session_id(session_id)
         .trace(trace.clone())
         .user_unix_name(preamble.misc.get("unix_username").cloned())
+        .identities(identities)
         .source_hostname(client_hostname)
         .ssh_env_vars(ssh_env_vars)
         .load_limiter(load_limiter);
@@ -0,0 +1,51 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ default_setup_blobimport "blob_files"
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  |
+  o  B [draft;rev=1;112478962961]
+  |
+  o  A [draft;rev=0;426bada5c675]
+  $
+  blobimporting
+
+Nothing is recorded as orphaned yet
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --lfs-orphaned-chunks -I deep -q --bookmark master_bookmark 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated 0, reachable 0, not walked type 0, unreachable 0 {}, in grace period 0, archived 0 (0 bytes)
+
+Record two orphaned chunks, only one of which is in the blobstore
+  $ cp blobstore/blobs/blob-repo0000.content.blake2.896ad5879a5df0403bfc93fc96507ad9c93b31b11f3d0fa05445da7918241e5d blobstore/blobs/blob-repo0000.chunk.blake2.1111111111111111111111111111111111111111111111111111111111111111
+  $ sqlite3 "$TESTTMP/monsql/sqlite_dbs" "INSERT INTO lfs_orphaned_chunks (repo_id, chunk_id) VALUES (0, '1111111111111111111111111111111111111111111111111111111111111111'), (0, '2222222222222222222222222222222222222222222222222222222222222222')"
+
+Report only, both chunks stay recorded
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --lfs-orphaned-chunks -I deep -q --bookmark master_bookmark 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated 2, reachable 0, not walked type 0, unreachable 2 {"chunk": 2}, in grace period 0, archived 0 (0 bytes)
+  $ sqlite3 "$TESTTMP/monsql/sqlite_dbs" "SELECT chunk_id FROM lfs_orphaned_chunks ORDER BY chunk_id"
+  1111111111111111111111111111111111111111111111111111111111111111
+  2222222222222222222222222222222222222222222222222222222222222222
+
+Sweep with the default grace period. The stored chunk is too new to move and stays recorded, the missing one is forgotten
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --lfs-orphaned-chunks --sweep -I deep -q --bookmark master_bookmark 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated 2, reachable 0, not walked type 0, unreachable 2 {"chunk": 2}, in grace period 1, archived 0 (0 bytes)
+  $ sqlite3 "$TESTTMP/monsql/sqlite_dbs" "SELECT chunk_id FROM lfs_orphaned_chunks ORDER BY chunk_id"
+  1111111111111111111111111111111111111111111111111111111111111111
+
+Sweep with no grace period, the stored chunk is archived and forgotten
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --lfs-orphaned-chunks --sweep --grace-period 0 -I deep -q --bookmark master_bookmark 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated 1, reachable 0, not walked type 0, unreachable 1 {"chunk": 1}, in grace period 0, archived 1 (*) (glob)
+  $ sqlite3 "$TESTTMP/monsql/sqlite_dbs" "SELECT COUNT(*) FROM lfs_orphaned_chunks"
+  0
+  $ ls blobstore/blobs | grep 1111111111111111111111111111111111111111111111111111111111111111
+  blob-archive.repo0000.chunk.blake2.1111111111111111111111111111111111111111111111111111111111111111
+
+Checking orphaned chunks needs the walk to load file contents
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --lfs-orphaned-chunks -I deep -x FileContent -q --bookmark master_bookmark 2>&1 | strip_glog | grep -o "needs the walk to include FileContent nodes"
+  needs the walk to include FileContent nodes
@@ -0,0 +1,26 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ default_setup_blobimport "blob_sqlite"
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  |
+  o  B [draft;rev=1;112478962961]
+  |
+  o  A [draft;rev=0;426bada5c675]
+  $
+  blobimporting
+
+Report works on sqlblob, everything the walk covers is reachable
+  $ mononoke_walker --storage-id=blobstore --readonly-storage --cachelib-only-blobstore gc -I deep -q --bookmark master_bookmark 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated *, reachable *, not walked type *, unreachable 0 {}, in grace period 0, archived 0 (0 bytes) (glob)
+
+Sqlblob does not record ctime, so sweeping refuses to start rather than ignoring the grace period
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --sweep -I deep -q --bookmark master_bookmark 2>&1 | strip_glog | grep -e "ctime" -e "Walking roots"
+  Execution error: --sweep needs a blobstore that records ctime, to apply the grace period
@@ -0,0 +1,56 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ default_setup_blobimport "blob_files"
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  |
+  o  B [draft;rev=1;112478962961]
+  |
+  o  A [draft;rev=0;426bada5c675]
+  $
+  blobimporting
+
+Add a content blob that nothing refers to, and one of a type the walk does not cover
+  $ cd blobstore/blobs
+  $ cp blob-repo0000.content.blake2.896ad5879a5df0403bfc93fc96507ad9c93b31b11f3d0fa05445da7918241e5d blob-repo0000.content.blake2.0000000000000000000000000000000000000000000000000000000000000000
+  $ cp blob-repo0000.content.blake2.896ad5879a5df0403bfc93fc96507ad9c93b31b11f3d0fa05445da7918241e5d blob-repo0000.unwalked.blake2.0000000000000000000000000000000000000000000000000000000000000000
+  $ cd "$TESTTMP"
+
+Report only, nothing is moved
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc -I deep -q --bookmark master_bookmark --scuba-log-file scuba-report.json 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated *, reachable *, not walked type *, unreachable 1 {"content": 1}, in grace period 0, archived 0 (0 bytes) (glob)
+  $ jq -r '.int * .normal | [ .check_fail, .check_type, .node_key ] | @csv' < scuba-report.json
+  1,"gc_unreachable","repo0000.content.blake2.0000000000000000000000000000000000000000000000000000000000000000"
+  $ ls blobstore/blobs | grep 0000000000000000000000000000000000000000000000000000000000000000
+  blob-repo0000.content.blake2.0000000000000000000000000000000000000000000000000000000000000000
+  blob-repo0000.unwalked.blake2.0000000000000000000000000000000000000000000000000000000000000000
+
+Sweep with the default grace period, the blob is too new to move
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --sweep -I deep -q --bookmark master_bookmark 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated *, reachable *, not walked type *, unreachable 1 {"content": 1}, in grace period 1, archived 0 (0 bytes) (glob)
+
+Sweep with no grace period, the unreachable blob is moved to the archive prefix
+  $ mononoke_walker --storage-id=blobstore --cachelib-only-blobstore gc --sweep --grace-period 0 -I deep -q --bookmark master_bookmark --scuba-log-file scuba-sweep.json 2>&1 | strip_glog | grep "Gc complete"
+  Gc complete. Enumerated *, reachable *, not walked type *, unreachable 1 {"content": 1}, in grace period 0, archived 1 (*) (glob)
+  $ jq -r '.int * .normal | [ .check_fail, .check_type, .node_key ] | @csv' < scuba-sweep.json | sort
+  0,"gc_archived","repo0000.content.blake2.0000000000000000000000000000000000000000000000000000000000000000"
+  1,"gc_unreachable","repo0000.content.blake2.0000000000000000000000000000000000000000000000000000000000000000"
+  $ ls blobstore/blobs | grep 0000000000000000000000000000000000000000000000000000000000000000
+  blob-archive.repo0000.content.blake2.0000000000000000000000000000000000000000000000000000000000000000
+  blob-repo0000.unwalked.blake2.0000000000000000000000000000000000000000000000000000000000000000
+
+The repo still walks fine after the sweep
+  $ mononoke_walker --storage-id=blobstore --readonly-storage --cachelib-only-blobstore scrub -I deep -q --bookmark master_bookmark 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types * (glob)
+  Walking node types * (glob)
+  Final count: (40, 40)
+  Bytes/s,* (glob)
+  Walked* (glob)
@@ -0,0 +1,68 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ default_setup_blobimport "blob_files"
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  |
+  o  B [draft;rev=1;112478962961]
+  |
+  o  A [draft;rev=0;426bada5c675]
+  $
+  blobimporting
+
+validate hashes, expecting all valid
+  $ mononoke_walker --storage-id=blobstore --readonly-storage --cachelib-only-blobstore validate -I deep -q --bookmark master_bookmark -c FileContentHashesMatch -c HgFileEnvelopeHashMatches -c HgManifestHashMatches 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types * (glob)
+  Walking node types * (glob)
+  Performing check types [FileContentHashesMatch, HgFileEnvelopeHashMatches, HgManifestHashMatches]
+  Final count: * (glob)
+  Walked* (glob)
+  Nodes,Pass,Fail:*,*,0; EdgesChecked:*; CheckType:Pass,Fail Total:*,0 FileContentHashesMatch:3,0 HgFileEnvelopeHashMatches:3,0 HgManifestHashMatches:3,0 (glob)
+
+Corrupt blobs by replacing one content blob with another
+  $ cd blobstore/blobs
+  $ cp blob-repo0000.content.blake2.896ad5879a5df0403bfc93fc96507ad9c93b31b11f3d0fa05445da7918241e5d blob-repo0000.content.blake2.eb56488e97bb4cf5eb17f05357b80108a4a71f6c3bab52dfcaec07161d105ec9
+  $ cd "$TESTTMP"
+
+validate hashes, expecting the content and the filenode using it to fail
+  $ mononoke_walker --storage-id=blobstore --readonly-storage --cachelib-only-blobstore validate -I deep -q --bookmark master_bookmark -c FileContentHashesMatch -c HgFileEnvelopeHashMatches -c HgManifestHashMatches --scuba-log-file scuba.json 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types * (glob)
+  Walking node types * (glob)
+  Performing check types [FileContentHashesMatch, HgFileEnvelopeHashMatches, HgManifestHashMatches]
+  Validation failed: *hash* (glob)
+  Validation failed: *hash* (glob)
+  Final count: * (glob)
+  Walked* (glob)
+  Nodes,Pass,Fail:*,*,2; EdgesChecked:*; CheckType:Pass,Fail Total:*,2 FileContentHashesMatch:2,1 HgFileEnvelopeHashMatches:2,1 HgManifestHashMatches:3,0 (glob)
+
+Check scuba data, failures are reported with the repo path they were reached by
+  $ wc -l < scuba.json
+  2
+  $ jq -r '.int * .normal | [ .check_fail, .check_type, .node_path, .node_type, .repo, .walk_type ] | @csv' < scuba.json | sort
+  1,"file_content_hashes_match","?","FileContent","repo","validate" (glob)
+  1,"hg_file_envelope_hash_matches","?","HgFileEnvelope","repo","validate" (glob)
+
+Corrupt the stored metadata of another content blob so that it can't be parsed
+  $ echo "not metadata" > blobstore/blobs/blob-repo0000.content_metadata.blake2.896ad5879a5df0403bfc93fc96507ad9c93b31b11f3d0fa05445da7918241e5d
+
+validate hashes, the blob that can't be loaded fails its check and the walk carries on
+  $ mononoke_walker --storage-id=blobstore --readonly-storage --cachelib-only-blobstore validate -I deep -x FileContentMetadata -q --bookmark master_bookmark -c FileContentHashesMatch -c HgFileEnvelopeHashMatches -c HgManifestHashMatches 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types * (glob)
+  Walking node types * (glob)
+  Performing check types [FileContentHashesMatch, HgFileEnvelopeHashMatches, HgManifestHashMatches]
+  Validation failed: * (glob)
+  Validation failed: * (glob)
+  Validation failed: * (glob)
+  Final count: * (glob)
+  Walked* (glob)
+  Nodes,Pass,Fail:*,*,3; EdgesChecked:*; CheckType:Pass,Fail Total:*,3 FileContentHashesMatch:1,2 HgFileEnvelopeHashMatches:2,1 HgManifestHashMatches:3,0 (glob)
@@ -5,31 +5,234 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use fbinit::FacebookInit;
+use lazy_static::lazy_static;
 
 use crate::{BoxGlobalTimeWindowCounter, GlobalTimeWindowCounter, GlobalTimeWindowCounterBuilder};
 
-struct AlwaysZeroCounter {}
+/// How often to forget about the counters which weren't bumped recently.
+const PRUNE_INTERVAL_SECS: u64 = 60;
+
+lazy_static! {
+    static ref START: Instant = Instant::now();
+    /// Counters are only global to this process: counters built with the same category and key
+    /// share their values.
+    static ref COUNTERS: Mutex<Counters> = Mutex::new(Counters {
+        buckets: HashMap::new(),
+        pruned_at: 0,
+    });
+}
+
+fn now_secs() -> u64 {
+    START.elapsed().as_secs()
+}
+
+struct Counters {
+    buckets: HashMap<(String, String), Arc<Buckets>>,
+    pruned_at: u64,
+}
+
+/// The values bumped during each bucket of `bucket_secs` seconds, for the buckets in the largest
+/// time window.
+struct Buckets {
+    bucket_secs: u64,
+    max_time_window: AtomicU32,
+    buckets: Mutex<VecDeque<(u64, f64)>>,
+}
+
+impl Buckets {
+    fn new(bucket_secs: u32, max_time_window: u32) -> Self {
+        Self {
+            bucket_secs: bucket_secs.max(1) as u64,
+            max_time_window: AtomicU32::new(max_time_window),
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn bucket(&self, now: u64) -> u64 {
+        now / self.bucket_secs
+    }
+
+    /// How many buckets cover `time_window` seconds, rounding up.
+    fn bucket_count(&self, time_window: u32) -> u64 {
+        ((time_window as u64 + self.bucket_secs - 1) / self.bucket_secs).max(1)
+    }
+
+    fn max_bucket_count(&self) -> u64 {
+        self.bucket_count(self.max_time_window.load(Ordering::Relaxed))
+    }
+
+    fn bump(&self, now: u64, value: f64) {
+        let bucket = self.bucket(now);
+        let max_bucket_count = self.max_bucket_count();
+        let mut buckets = self.buckets.lock().expect("poisoned lock");
+        match buckets.back_mut() {
+            Some((last, total)) if *last == bucket => *total += value,
+            _ => buckets.push_back((bucket, value)),
+        }
+
+        while buckets
+            .front()
+            .map_or(false, |(first, _)| first + max_bucket_count <= bucket)
+        {
+            buckets.pop_front();
+        }
+    }
+
+    /// The sum of the values bumped during the buckets covering the last `time_window` seconds,
+    /// including the current one.
+    fn get(&self, now: u64, time_window: u32) -> f64 {
+        let bucket = self.bucket(now);
+        let bucket_count = self.bucket_count(time_window);
+        self.buckets
+            .lock()
+            .expect("poisoned lock")
+            .iter()
+            .rev()
+            .take_while(|(first, _)| first + bucket_count > bucket)
+            .map(|(_, value)| value)
+            .sum()
+    }
+
+    /// Whether nothing was bumped in the largest time window.
+    fn is_expired(&self, now: u64) -> bool {
+        let max_bucket_count = self.max_bucket_count();
+        self.buckets
+            .lock()
+            .expect("poisoned lock")
+            .back()
+            .map_or(true, |(last, _)| {
+                last + max_bucket_count <= self.bucket(now)
+            })
+    }
+}
+
+struct LocalTimeWindowCounter {
+    min_time_window: u32,
+    max_time_window: u32,
+    buckets: Arc<Buckets>,
+}
 
 #[async_trait]
-impl GlobalTimeWindowCounter for AlwaysZeroCounter {
-    async fn get(&self, _time_window: u32) -> Result<f64> {
-        Ok(0.0)
+impl GlobalTimeWindowCounter for LocalTimeWindowCounter {
+    async fn get(&self, time_window: u32) -> Result<f64> {
+        let time_window = time_window
+            .max(self.min_time_window)
+            .min(self.max_time_window);
+        Ok(self.buckets.get(now_secs(), time_window))
     }
 
-    fn bump(&self, _value: f64) {}
+    fn bump(&self, value: f64) {
+        self.buckets.bump(now_secs(), value)
+    }
 }
 
 impl GlobalTimeWindowCounterBuilder {
     pub fn build(
         _fb: FacebookInit,
-        _category: impl AsRef<str>,
-        _key: impl AsRef<str>,
-        _min_time_window: u32,
-        _max_time_window: u32,
+        category: impl AsRef<str>,
+        key: impl AsRef<str>,
+        min_time_window: u32,
+        max_time_window: u32,
     ) -> BoxGlobalTimeWindowCounter {
-        Box::new(AlwaysZeroCounter {})
+        let max_time_window = max_time_window.max(min_time_window).max(1);
+        let now = now_secs();
+
+        let mut counters = COUNTERS.lock().expect("poisoned lock");
+        // Forget about the keys which weren't bumped recently, so that counting per user or per
+        // client doesn't grow forever.
+        if counters.pruned_at + PRUNE_INTERVAL_SECS <= now {
+            counters.pruned_at = now;
+            counters
+                .buckets
+                .retain(|_, buckets| Arc::strong_count(buckets) > 1 || !buckets.is_expired(now));
+        }
+
+        // Values are counted in buckets as long as the smallest time window, so that counting
+        // over a large window doesn't take many buckets.
+        let id = (category.as_ref().to_string(), key.as_ref().to_string());
+        let buckets = counters
+            .buckets
+            .entry(id)
+            .or_insert_with(|| Arc::new(Buckets::new(min_time_window, max_time_window)));
+        buckets
+            .max_time_window
+            .fetch_max(max_time_window, Ordering::Relaxed);
+
+        Box::new(LocalTimeWindowCounter {
+            min_time_window,
+            max_time_window,
+            buckets: buckets.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        let buckets = Buckets::new(1, 10);
+        buckets.bump(100, 1.0);
+        buckets.bump(100, 2.0);
+        buckets.bump(105, 4.0);
+
+        assert_eq!(buckets.get(105, 1), 4.0);
+        assert_eq!(buckets.get(105, 6), 7.0);
+        assert_eq!(buckets.get(109, 5), 4.0);
+        assert_eq!(buckets.get(110, 10), 4.0);
+        assert_eq!(buckets.get(115, 10), 0.0);
+        assert!(buckets.is_expired(115));
+
+        // Old buckets are dropped when bumping.
+        buckets.bump(112, 8.0);
+        assert_eq!(buckets.buckets.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_large_buckets() {
+        let buckets = Buckets::new(60, 3600);
+        buckets.bump(0, 1.0);
+        buckets.bump(59, 2.0);
+        buckets.bump(60, 4.0);
+        assert_eq!(buckets.buckets.lock().unwrap().len(), 2);
+
+        // Time windows are rounded up to whole buckets.
+        assert_eq!(buckets.get(60, 1), 4.0);
+        assert_eq!(buckets.get(60, 61), 7.0);
+        assert_eq!(buckets.get(3599, 3600), 7.0);
+        assert_eq!(buckets.get(3600, 3600), 4.0);
+        assert!(!buckets.is_expired(3659));
+        assert!(buckets.is_expired(3660));
+
+        // A day of values takes a bucket per minute.
+        for second in 0..86400 {
+            buckets.bump(3600 + second, 1.0);
+        }
+        assert_eq!(buckets.buckets.lock().unwrap().len(), 60);
+        assert_eq!(buckets.get(3600 + 86399, 3600), 3600.0);
+    }
+
+    #[fbinit::test]
+    async fn test_shared_counters(fb: FacebookInit) -> Result<()> {
+        let counter = GlobalTimeWindowCounterBuilder::build(fb, "test", "shared", 1, 10);
+        let other = GlobalTimeWindowCounterBuilder::build(fb, "test", "shared", 1, 10);
+        let unrelated = GlobalTimeWindowCounterBuilder::build(fb, "test", "unrelated", 1, 10);
+
+        counter.bump(3.0);
+        other.bump(4.0);
+
+        assert_eq!(counter.get(10).await?, 7.0);
+        assert_eq!(other.get(10).await?, 7.0);
+        assert_eq!(unrelated.get(10).await?, 0.0);
+        Ok(())
     }
 }
@@ -8,22 +8,26 @@
 use crate::validate::{CHECK_FAIL, CHECK_TYPE, NODE_KEY, REPO};
 
 use anyhow::{format_err, Error};
-use blobstore::{Blobstore, BlobstoreMetadata};
+use blobstore::{Blobstore, BlobstoreKeySource, BlobstoreMetadata, BlobstoreUnlinkOps};
 use blobstore_factory::{
-    make_blobstore, make_blobstore_multiplexed, BlobstoreOptions, ReadOnlyStorage,
+    make_blobstore, make_blobstore_enumerable, make_blobstore_multiplexed, pack_options,
+    BlobstoreOptions, ReadOnlyStorage,
 };
 use context::CoreContext;
 use fbinit::FacebookInit;
+use fileblob::Fileblob;
 use futures::compat::Future01CompatExt;
 use inlinable_string::InlinableString;
-use metaconfig_types::{BlobConfig, BlobstoreId, ScrubAction};
+use metaconfig_types::{BlobConfig, BlobstoreId, MultiplexPolicy, ScrubAction};
 use multiplexedblob::{LoggingScrubHandler, ScrubHandler};
+use packblob::PackBlob;
 use prefixblob::PrefixBlobstore;
 use samplingblob::{SamplingBlobstore, SamplingHandler};
 use scuba::value::{NullScubaValue, ScubaValue};
 use scuba_ext::ScubaSampleBuilder;
 use slog::Logger;
 use sql_ext::facebook::MysqlOptions;
+use sqlblob::Sqlblob;
 use stats::prelude::*;
 use std::{convert::From, sync::Arc};
 
@@ -138,6 +142,18 @@ fn get_blobconfig(
                         inner_blobstore_id
                     ))
             }
+            // The walk reads each blob once, a cache would only get in the way
+            BlobConfig::DiskCache { blobconfig, .. } => {
+                get_blobconfig(*blobconfig, Some(inner_blobstore_id))
+            }
+            // Blobs in the chosen component are still packed
+            BlobConfig::Pack {
+                blobconfig,
+                pack_config,
+            } => Ok(BlobConfig::Pack {
+                blobconfig: Box::new(get_blobconfig(*blobconfig, Some(inner_blobstore_id))?),
+                pack_config,
+            }),
             _ => Err(format_err!(
                 "inner-blobstore-id supplied but blobstore is not multiplexed"
             )),
@@ -162,6 +178,21 @@ pub async fn open_blobstore(
     logger: Logger,
 ) -> Result<Arc<dyn Blobstore>, Error> {
     let mut blobconfig = get_blobconfig(blob_config, inner_blobstore_id)?;
+    // The walk reads each blob once, a cache would only get in the way
+    while let BlobConfig::DiskCache {
+        blobconfig: inner, ..
+    } = blobconfig
+    {
+        blobconfig = *inner;
+    }
+    // Packing is applied above the sampler, so that sizing sees the bytes actually stored
+    let (mut blobconfig, pack_config) = match blobconfig {
+        BlobConfig::Pack {
+            blobconfig,
+            pack_config,
+        } => (*blobconfig, Some(pack_config)),
+        blobconfig => (blobconfig, None),
+    };
     let scrub_handler = scrub_action.map(|scrub_action| {
         blobconfig.set_scrubbed(scrub_action);
         Arc::new(StatsScrubHandler::new(
@@ -200,6 +231,7 @@ pub async fn open_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                MultiplexPolicy::default(),
                 mysql_options,
                 readonly_storage,
                 Some((scrub_handler, scrub_action)),
@@ -217,6 +249,7 @@ pub async fn open_blobstore(
                 scuba_sample_rate,
                 blobstores,
                 queue_db,
+                policy,
             },
         ) => {
             make_blobstore_multiplexed(
@@ -226,6 +259,7 @@ pub async fn open_blobstore(
                 scuba_table,
                 scuba_sample_rate,
                 blobstores,
+                policy,
                 mysql_options,
                 readonly_storage,
                 None,
@@ -258,6 +292,12 @@ pub async fn open_blobstore(
         None => blobstore,
     };
 
+    let blobstore = match pack_config {
+        Some(pack_config) => Arc::new(PackBlob::new(blobstore, pack_options(&pack_config)))
+            as Arc<dyn blobstore::Blobstore>,
+        None => blobstore,
+    };
+
     if let Some(prefix) = prefix {
         return Ok(Arc::new(PrefixBlobstore::new(
             blobstore,
@@ -267,3 +307,66 @@ pub async fn open_blobstore(
 
     Ok(blobstore)
 }
+
+// Open the storage so that its keys can be enumerated. A multiplex enumerates the union of
+// its components, pass an inner blobstore id to enumerate just one of them.
+pub async fn open_enumerable_blobstore(
+    fb: FacebookInit,
+    blob_config: BlobConfig,
+    inner_blobstore_id: Option<u64>,
+    mysql_options: MysqlOptions,
+    readonly_storage: ReadOnlyStorage,
+) -> Result<Arc<dyn BlobstoreKeySource>, Error> {
+    let blob_config = get_blobconfig(blob_config, inner_blobstore_id)?;
+    make_blobstore_enumerable(fb, blob_config, mysql_options, readonly_storage)
+        .compat()
+        .await
+}
+
+/// A store that gc can both enumerate and remove keys from
+pub trait SweepableBlobstore: BlobstoreKeySource + BlobstoreUnlinkOps {}
+
+impl<T: BlobstoreKeySource + BlobstoreUnlinkOps> SweepableBlobstore for T {}
+
+// Whether gets from the underlying storage return the blob ctime, which gc needs to apply its
+// grace period. Sqlblob does not record when a blob was put.
+pub fn sweepable_blobstore_has_ctime(
+    blob_config: BlobConfig,
+    inner_blobstore_id: Option<u64>,
+) -> Result<bool, Error> {
+    match get_blobconfig(blob_config, inner_blobstore_id)? {
+        BlobConfig::Files { .. } => Ok(true),
+        BlobConfig::Pack { blobconfig, .. } | BlobConfig::DiskCache { blobconfig, .. } => {
+            sweepable_blobstore_has_ctime(*blobconfig, None)
+        }
+        _ => Ok(false),
+    }
+}
+
+// Open the underlying storage without any of the usual wrappers, so that we can enumerate
+// it. Only local store types can do this for now.
+pub fn open_sweepable_blobstore(
+    blob_config: BlobConfig,
+    inner_blobstore_id: Option<u64>,
+    readonly_storage: ReadOnlyStorage,
+) -> Result<Arc<dyn SweepableBlobstore>, Error> {
+    match get_blobconfig(blob_config, inner_blobstore_id)? {
+        BlobConfig::Files { path } => Ok(Arc::new(Fileblob::open(path.join("blobs"))?)),
+        BlobConfig::Sqlite { path } => Ok(Arc::new(Sqlblob::with_sqlite_path(
+            path.join("blobs"),
+            readonly_storage.0,
+        )?)),
+        // Packing doesn't change the keys, so the packed store is swept directly. A cache in
+        // front of the store might keep serving swept blobs until they are evicted.
+        BlobConfig::Pack { blobconfig, .. } | BlobConfig::DiskCache { blobconfig, .. } => {
+            open_sweepable_blobstore(*blobconfig, None, readonly_storage)
+        }
+        BlobConfig::Multiplexed { .. } | BlobConfig::Scrub { .. } => Err(format_err!(
+            "Can't enumerate a multiplexed blobstore, pass --inner-blobstore-id to choose a component"
+        )),
+        blob_config => Err(format_err!(
+            "Blobstore {:?} does not support enumeration",
+            blob_config
+        )),
+    }
+}
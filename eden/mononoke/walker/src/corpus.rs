@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::graph::{FileContentData, Node, NodeData, WrappedPath};
+use crate::progress::{
+    progress_stream, report_state, ProgressReporter, ProgressStateCountByType, ProgressStateMutex,
+};
+use crate::sampling::{NodeSamplingHandler, PathTrackingRoute, SamplingWalkVisitor};
+use crate::scrub::ScrubStats;
+use crate::setup::{
+    parse_node_types, setup_common, CORPUS, DEFAULT_INCLUDE_NODE_TYPES,
+    EXCLUDE_SAMPLE_NODE_TYPE_ARG, INCLUDE_SAMPLE_NODE_TYPE_ARG, OUTPUT_DIR_ARG,
+    PROGRESS_INTERVAL_ARG, PROGRESS_SAMPLE_DURATION_S, PROGRESS_SAMPLE_RATE,
+    PROGRESS_SAMPLE_RATE_ARG, SAMPLE_RATE_ARG,
+};
+use crate::tail::{walk_exact_tail, RepoWalkRun};
+
+use anyhow::{format_err, Error};
+use clap::ArgMatches;
+use cloned::cloned;
+use cmdlib::args;
+use context::CoreContext;
+use fbinit::FacebookInit;
+use futures::{
+    future::{self, FutureExt},
+    stream::{Stream, TryStreamExt},
+};
+use mononoke_types::BlobstoreBytes;
+use percent_encoding::{percent_encode, AsciiSet, CONTROLS};
+use samplingblob::SamplingHandler;
+use slog::{info, Logger};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::fs;
+
+// Directory the blobs for a path are written into. Chosen to be unlikely to
+// clash with a real directory name in the repo.
+const BLOB_DIR: &str = ".mononoke,";
+
+// Bytes escaped in file and directory names: path separators, and `,` so that no
+// escaped repo path element can be BLOB_DIR. Non-ASCII bytes are always escaped.
+const NAME_ESCAPES: &AsciiSet = &CONTROLS.add(b'%').add(b'/').add(b'\\').add(b',');
+
+// Turn a repo path element or blobstore key into a single file name that stays
+// inside the directory it is joined to.
+fn escape_name(name: &[u8]) -> String {
+    match name {
+        b"." | b".." => "%2E".repeat(name.len()),
+        _ => percent_encode(name, NAME_ESCAPES).to_string(),
+    }
+}
+
+#[derive(Debug)]
+struct CorpusSample {
+    data: HashMap<String, BlobstoreBytes>,
+}
+
+impl Default for CorpusSample {
+    fn default() -> Self {
+        Self {
+            data: HashMap::with_capacity(1),
+        }
+    }
+}
+
+impl SamplingHandler for NodeSamplingHandler<CorpusSample> {
+    fn sample_get(&self, ctx: CoreContext, key: String, value: Option<&BlobstoreBytes>) {
+        ctx.sampling_key().map(|sampling_key| {
+            self.inflight()
+                .get_mut(sampling_key)
+                .map(|mut guard| value.map(|value| guard.data.insert(key.clone(), value.clone())))
+        });
+    }
+}
+
+// Where to write the blobs for a node: <output_dir>/<node type>/<repo path>/BLOB_DIR
+fn sample_dir(output_dir: &Path, node: &Node, path: Option<&WrappedPath>) -> PathBuf {
+    let mut dir = output_dir.join(node.get_type().to_str());
+    if let Some(mpath) = path.and_then(|p| p.as_ref()) {
+        for element in mpath {
+            dir.push(escape_name(element.as_ref()));
+        }
+    }
+    dir.push(BLOB_DIR);
+    dir
+}
+
+async fn dump_sample(
+    output_dir: &Path,
+    node: &Node,
+    sample: Option<(Option<WrappedPath>, CorpusSample)>,
+) -> Result<Option<ScrubStats>, Error> {
+    let (path, sample) = match sample {
+        Some(sample) => sample,
+        None => return Ok(None),
+    };
+
+    let mut stats = ScrubStats::default();
+    if sample.data.is_empty() {
+        return Ok(Some(stats));
+    }
+
+    let dir = sample_dir(output_dir, node, path.as_ref());
+    fs::create_dir_all(&dir).await?;
+    for (key, value) in sample.data {
+        let bytes = value.into_bytes();
+        stats.blobstore_keys += 1;
+        stats.blobstore_bytes += bytes.len() as u64;
+        fs::write(dir.join(escape_name(key.as_bytes())), bytes).await?;
+    }
+    Ok(Some(stats))
+}
+
+// Force load of leaf data and write out the blobs of sampled nodes
+fn corpus_stream<InStream, InStats>(
+    scheduled_max: usize,
+    output_dir: Arc<PathBuf>,
+    s: InStream,
+    sampler: Arc<NodeSamplingHandler<CorpusSample>>,
+) -> impl Stream<Item = Result<(Node, Option<NodeData>, Option<ScrubStats>), Error>>
+where
+    InStream:
+        Stream<Item = Result<(Node, Option<NodeData>, Option<InStats>), Error>> + 'static + Send,
+    InStats: 'static + Send,
+{
+    s.map_ok(move |(n, data_opt, _stats_opt)| {
+        cloned!(output_dir, sampler);
+        match data_opt {
+            Some(NodeData::FileContent(FileContentData::ContentStream(file_bytes_stream))) => {
+                async move {
+                    // Consume the stream to make sure we loaded all blobs
+                    let num_bytes = file_bytes_stream
+                        .try_fold(0, |acc, file_bytes| future::ok(acc + file_bytes.size()))
+                        .await?;
+                    let stats =
+                        dump_sample(&output_dir, &n, sampler.complete_node_with_path(&n)).await?;
+                    Ok((
+                        n,
+                        Some(NodeData::FileContent(FileContentData::Consumed(num_bytes))),
+                        stats,
+                    ))
+                }
+                .boxed()
+            }
+            data_opt => async move {
+                let stats =
+                    dump_sample(&output_dir, &n, sampler.complete_node_with_path(&n)).await?;
+                Ok((n, data_opt, stats))
+            }
+            .boxed(),
+        }
+    })
+    .try_buffer_unordered(scheduled_max)
+}
+
+// Subcommand entry point for dumping a corpus of sampled blobs
+pub async fn corpus<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'a>,
+    sub_m: &'a ArgMatches<'a>,
+) -> Result<(), Error> {
+    let corpus_sampler = Arc::new(NodeSamplingHandler::<CorpusSample>::new());
+
+    let (datasources, walk_params) = setup_common(
+        CORPUS,
+        fb,
+        &logger,
+        Some(corpus_sampler.clone()),
+        matches,
+        sub_m,
+    )?;
+
+    let repo_stats_key = args::get_repo_name(fb, &matches)?;
+
+    let output_dir = sub_m
+        .value_of(OUTPUT_DIR_ARG)
+        .map(|dir| Arc::new(PathBuf::from(dir)))
+        .ok_or_else(|| format_err!("--{} is required", OUTPUT_DIR_ARG))?;
+    let sample_rate = args::get_u64_opt(&sub_m, SAMPLE_RATE_ARG).unwrap_or(100);
+    let progress_interval_secs = args::get_u64_opt(&sub_m, PROGRESS_INTERVAL_ARG);
+    let progress_sample_rate = args::get_u64_opt(&sub_m, PROGRESS_SAMPLE_RATE_ARG);
+
+    cloned!(
+        walk_params.include_node_types,
+        walk_params.include_edge_types
+    );
+    let mut sampling_node_types = parse_node_types(
+        sub_m,
+        INCLUDE_SAMPLE_NODE_TYPE_ARG,
+        EXCLUDE_SAMPLE_NODE_TYPE_ARG,
+        DEFAULT_INCLUDE_NODE_TYPES,
+    )?;
+    sampling_node_types.retain(|i| include_node_types.contains(i));
+
+    info!(
+        logger,
+        "Writing corpus for {:?} to {}",
+        sampling_node_types,
+        output_dir.display()
+    );
+
+    let corpus_progress_state =
+        ProgressStateMutex::new(ProgressStateCountByType::<ScrubStats, ScrubStats>::new(
+            fb,
+            logger.clone(),
+            CORPUS,
+            repo_stats_key,
+            sampling_node_types.clone(),
+            progress_sample_rate.unwrap_or(PROGRESS_SAMPLE_RATE),
+            Duration::from_secs(progress_interval_secs.unwrap_or(PROGRESS_SAMPLE_DURATION_S)),
+        ));
+
+    let make_sink = {
+        cloned!(
+            walk_params.progress_state,
+            walk_params.quiet,
+            walk_params.scheduled_max,
+            corpus_sampler
+        );
+        move |run: RepoWalkRun| {
+            cloned!(run.ctx);
+            async move |walk_output| {
+                cloned!(ctx, corpus_progress_state);
+                let walk_progress = progress_stream(quiet, &progress_state.clone(), walk_output);
+
+                let corpus =
+                    corpus_stream(scheduled_max, output_dir, walk_progress, corpus_sampler);
+                let report_corpus = progress_stream(quiet, &corpus_progress_state.clone(), corpus);
+                report_state(ctx, corpus_progress_state, report_corpus)
+                    .map({
+                        cloned!(progress_state);
+                        move |d| {
+                            progress_state.report_progress();
+                            d
+                        }
+                    })
+                    .await
+            }
+        }
+    };
+
+    let walk_state = Arc::new(SamplingWalkVisitor::new(
+        include_node_types,
+        include_edge_types,
+        sampling_node_types,
+        corpus_sampler,
+        sample_rate,
+    ));
+    walk_exact_tail::<_, _, _, _, _, PathTrackingRoute>(
+        fb,
+        logger,
+        datasources,
+        walk_params,
+        walk_state,
+        make_sink,
+        true,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mononoke_types::MPath;
+
+    #[test]
+    fn test_sample_dir_escapes_names() -> Result<(), Error> {
+        let output_dir = Path::new("/corpus");
+        let path = WrappedPath::from(Some(MPath::new("a/../../.mononoke,/b%c")?));
+        assert_eq!(
+            sample_dir(output_dir, &Node::Root, Some(&path)),
+            Path::new("/corpus/Root/a/%2E%2E/%2E%2E/.mononoke%2C/b%25c/.mononoke,")
+        );
+
+        assert_eq!(
+            escape_name(b"repo0000.content.blake2.00"),
+            "repo0000.content.blake2.00"
+        );
+        assert_eq!(escape_name(b".."), "%2E%2E");
+        assert_eq!(escape_name(b"../key/x"), "..%2Fkey%2Fx");
+        Ok(())
+    }
+}
@@ -0,0 +1,631 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+// Soft gc of the blobstore. The walk records every blobstore key it loads, then the
+// underlying store is enumerated and any key of a type the walk covers that was not
+// loaded is unreachable. Unreachable keys are reported, and optionally moved under
+// an archive prefix once they are older than the grace period. Instead of enumerating
+// the store, gc can check just the chunks the LFS server recorded as orphaned.
+
+use crate::blobstore::{
+    open_enumerable_blobstore, open_sweepable_blobstore, sweepable_blobstore_has_ctime,
+    SweepableBlobstore,
+};
+use crate::graph::{FileContentData, Node, NodeData, NodeType};
+use crate::progress::{progress_stream, report_state};
+use crate::setup::{
+    setup_common, RepoStorageParams, GC, GC_ARCHIVE_PREFIX_ARG, GC_GRACE_PERIOD_ARG,
+    GC_LFS_ORPHANED_CHUNKS_ARG, GC_SWEEP_ARG,
+};
+use crate::state::WalkStateCHashMap;
+use crate::tail::{walk_exact_tail, RepoWalkRun};
+use crate::validate::{CHECK_FAIL, CHECK_TYPE, NODE_KEY};
+
+use anyhow::{format_err, Error};
+use blobstore::{Blobstore, BlobstoreKeyParam, BlobstoreKeyRange, BlobstoreKeySource};
+use clap::ArgMatches;
+use cloned::cloned;
+use cmdlib::args;
+use context::CoreContext;
+use dashmap::DashMap;
+use fbinit::FacebookInit;
+use futures::{
+    compat::Future01CompatExt,
+    future::{self, FutureExt, TryFutureExt},
+    stream::{self, Stream, StreamExt, TryStreamExt},
+};
+use inlinable_string::InlinableString;
+use lfs_upload_sessions::SqlLfsUploadSessions;
+use mononoke_types::{BlobstoreBytes, MononokeId, RepositoryId};
+use prefixblob::PrefixBlobstore;
+use samplingblob::SamplingHandler;
+use scuba_ext::ScubaSampleBuilder;
+use slog::{info, warn, Logger};
+use sql_construct::SqlConstructFromMetadataDatabaseConfig;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const DEFAULT_GRACE_PERIOD_SECS: u64 = 7 * 24 * 60 * 60;
+const DEFAULT_ARCHIVE_PREFIX: &str = "archive.";
+const GC_UNREACHABLE: &str = "gc_unreachable";
+const GC_ARCHIVED: &str = "gc_archived";
+const ORPHANED_CHUNKS_PAGE: usize = 1000;
+
+// Blobstore key prefixes (after the repo prefix) of the blobs loaded by each node type.
+// Only keys with one of these prefixes are considered for gc, as keys of other types
+// are not walked and so would all look unreachable.
+fn node_type_key_prefixes(node_type: NodeType) -> &'static [&'static str] {
+    match node_type {
+        NodeType::BonsaiChangeset => &["changeset.blake2."],
+        NodeType::HgChangeset => &["hgchangeset.sha1."],
+        NodeType::HgManifest => &["hgmanifest.sha1."],
+        NodeType::HgFileEnvelope => &["hgfilenode.sha1."],
+        NodeType::FileContent => &["content.blake2.", "chunk.blake2."],
+        NodeType::FileContentMetadata => &["content_metadata.blake2."],
+        NodeType::AliasContentMapping => &["alias."],
+        NodeType::BonsaiFsnodeMapping => &["derived_root_fsnode."],
+        NodeType::Fsnode => &["fsnode.blake2."],
+        _ => &[],
+    }
+}
+
+// Records every key the walk loads from the blobstore. Unlike the other samplers this
+// ignores the SamplingKey, as gc needs to see everything.
+#[derive(Debug, Default)]
+struct GcSamplingHandler {
+    visited: DashMap<String, ()>,
+}
+
+impl GcSamplingHandler {
+    fn is_visited(&self, key: &str) -> bool {
+        self.visited.contains_key(key)
+    }
+}
+
+impl SamplingHandler for GcSamplingHandler {
+    fn sample_get(&self, _ctx: CoreContext, key: String, value: Option<&BlobstoreBytes>) {
+        if value.is_some() {
+            self.visited.insert(key, ());
+        }
+    }
+
+    fn sample_is_present(&self, _ctx: CoreContext, key: String, value: bool) {
+        if value {
+            self.visited.insert(key, ());
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct GcStats {
+    enumerated: u64,
+    reachable: u64,
+    not_walked_type: u64,
+    unreachable: u64,
+    unreachable_by_type: BTreeMap<String, u64>,
+    in_grace_period: u64,
+    archived: u64,
+    archived_bytes: u64,
+}
+
+#[derive(Clone)]
+struct GcParams {
+    grace_period_secs: u64,
+    archive_prefix: String,
+    key_prefixes: Vec<String>,
+    scheduled_max: usize,
+    quiet: bool,
+}
+
+// Where the keys to check for reachability come from
+enum Candidates {
+    Enumerate(Arc<dyn BlobstoreKeySource>),
+    OrphanedChunks(SqlLfsUploadSessions),
+}
+
+enum SweepOutcome {
+    Archived(u64),
+    InGracePeriod,
+    AlreadyGone,
+}
+
+// Force load of file contents so their chunk keys are seen by the sampler
+fn loading_stream<InStream, SS>(
+    scheduled_max: usize,
+    s: InStream,
+) -> impl Stream<Item = Result<(Node, Option<NodeData>, Option<SS>), Error>>
+where
+    InStream: Stream<Item = Result<(Node, Option<NodeData>, Option<SS>), Error>> + 'static + Send,
+    SS: 'static + Send,
+{
+    s.map_ok(move |(n, nd, ss)| match nd {
+        Some(NodeData::FileContent(FileContentData::ContentStream(file_bytes_stream))) => {
+            file_bytes_stream
+                .try_fold(0, |acc, file_bytes| future::ok(acc + file_bytes.size()))
+                .map_ok(move |num_bytes| {
+                    (
+                        n,
+                        Some(NodeData::FileContent(FileContentData::Consumed(num_bytes))),
+                        ss,
+                    )
+                })
+                .left_future()
+        }
+        nd => future::ok((n, nd, ss)).right_future(),
+    })
+    .try_buffer_unordered(scheduled_max)
+}
+
+fn key_type(repo_prefix: &str, key: &str) -> String {
+    let unprefixed = key.get(repo_prefix.len()..).unwrap_or(key);
+    unprefixed
+        .split('.')
+        .next()
+        .unwrap_or(unprefixed)
+        .to_string()
+}
+
+async fn sweep_key(
+    ctx: CoreContext,
+    store: Arc<dyn SweepableBlobstore>,
+    archive: PrefixBlobstore<Arc<dyn SweepableBlobstore>>,
+    key: String,
+    grace_period_secs: u64,
+    now_secs: i64,
+) -> Result<SweepOutcome, Error> {
+    let data = match store.get(ctx.clone(), key.clone()).compat().await? {
+        Some(data) => data,
+        None => return Ok(SweepOutcome::AlreadyGone),
+    };
+    match data.as_meta().as_ctime() {
+        // Without a ctime the blob might have been put moments ago, e.g. by a push that has
+        // not moved a bookmark yet, so it is never safe to sweep.
+        None => {
+            return Err(format_err!(
+                "No ctime for {}, can't apply the grace period",
+                key
+            ));
+        }
+        Some(ctime) if now_secs - *ctime < grace_period_secs as i64 => {
+            return Ok(SweepOutcome::InGracePeriod);
+        }
+        Some(_) => (),
+    }
+    let size = data.as_bytes().len() as u64;
+    // Archive before unlink, so an interrupted sweep never loses data
+    archive
+        .put(ctx.clone(), key.clone(), data.into_bytes())
+        .compat()
+        .await?;
+    store.unlink(ctx, key).compat().await?;
+    Ok(SweepOutcome::Archived(size))
+}
+
+type Sweeper = (
+    Arc<dyn SweepableBlobstore>,
+    PrefixBlobstore<Arc<dyn SweepableBlobstore>>,
+);
+
+fn open_sweeper(store: Arc<dyn SweepableBlobstore>, params: &GcParams) -> Sweeper {
+    let archive = PrefixBlobstore::new(
+        store.clone(),
+        InlinableString::from(params.archive_prefix.as_str()),
+    );
+    (store, archive)
+}
+
+fn report_unreachable(
+    ctx: &CoreContext,
+    repo_prefix: &str,
+    key: &str,
+    params: &GcParams,
+    scuba_builder: &ScubaSampleBuilder,
+    stats: &mut GcStats,
+) {
+    stats.unreachable += 1;
+    *stats
+        .unreachable_by_type
+        .entry(key_type(repo_prefix, key))
+        .or_insert(0) += 1;
+    if !params.quiet {
+        info!(ctx.logger(), "Unreachable {}", key);
+    }
+    scuba_builder
+        .clone()
+        .add(NODE_KEY, key)
+        .add(CHECK_TYPE, GC_UNREACHABLE)
+        .add(CHECK_FAIL, 1)
+        .log();
+}
+
+// Sweep unreachable keys, returning the outcome for each of them
+async fn sweep_keys(
+    ctx: &CoreContext,
+    sweeper: &Sweeper,
+    keys: Vec<String>,
+    params: &GcParams,
+    now_secs: i64,
+    scuba_builder: &ScubaSampleBuilder,
+    stats: &mut GcStats,
+) -> Result<Vec<(String, SweepOutcome)>, Error> {
+    let (store, archive) = sweeper;
+    let outcomes: Vec<(String, SweepOutcome)> = stream::iter(keys)
+        .map(|key| {
+            cloned!(ctx, store, archive);
+            sweep_key(
+                ctx,
+                store,
+                archive,
+                key.clone(),
+                params.grace_period_secs,
+                now_secs,
+            )
+            .map_ok(move |outcome| (key, outcome))
+        })
+        .buffer_unordered(params.scheduled_max)
+        .try_collect()
+        .await?;
+
+    for (key, outcome) in &outcomes {
+        match outcome {
+            SweepOutcome::Archived(size) => {
+                stats.archived += 1;
+                stats.archived_bytes += size;
+                scuba_builder
+                    .clone()
+                    .add(NODE_KEY, key.as_str())
+                    .add(CHECK_TYPE, GC_ARCHIVED)
+                    .add(CHECK_FAIL, 0)
+                    .log();
+            }
+            SweepOutcome::InGracePeriod => stats.in_grace_period += 1,
+            SweepOutcome::AlreadyGone => (),
+        }
+    }
+
+    Ok(outcomes)
+}
+
+async fn enumerate_and_sweep(
+    ctx: CoreContext,
+    enumerator: Arc<dyn BlobstoreKeySource>,
+    sweeper: Option<Arc<dyn SweepableBlobstore>>,
+    sampler: Arc<GcSamplingHandler>,
+    storage: RepoStorageParams,
+    params: GcParams,
+    scuba_builder: ScubaSampleBuilder,
+) -> Result<GcStats, Error> {
+    let sweeper = sweeper.map(|store| open_sweeper(store, &params));
+    let now_secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let repo_prefix = storage.repo_prefix.clone();
+
+    // All keys for this repo, and no others that share the store
+    let range = BlobstoreKeyRange::with_prefix(repo_prefix.clone());
+    let mut stats = GcStats::default();
+    let mut param = Some(BlobstoreKeyParam::Start(range));
+
+    while let Some(current) = param {
+        let page = enumerator.enumerate(ctx.clone(), &current).compat().await?;
+        param = page.next_token;
+
+        let mut unreachable = vec![];
+        for key in page.keys {
+            stats.enumerated += 1;
+            if sampler.is_visited(&key) {
+                stats.reachable += 1;
+                continue;
+            }
+            let unprefixed = &key[repo_prefix.len()..];
+            if !params
+                .key_prefixes
+                .iter()
+                .any(|p| unprefixed.starts_with(p.as_str()))
+            {
+                stats.not_walked_type += 1;
+                continue;
+            }
+            report_unreachable(
+                &ctx,
+                &repo_prefix,
+                &key,
+                &params,
+                &scuba_builder,
+                &mut stats,
+            );
+            unreachable.push(key);
+        }
+
+        if let Some(sweeper) = &sweeper {
+            sweep_keys(
+                &ctx,
+                sweeper,
+                unreachable,
+                &params,
+                now_secs,
+                &scuba_builder,
+                &mut stats,
+            )
+            .await?;
+        }
+    }
+
+    Ok(stats)
+}
+
+// Check the chunks recorded as orphaned by rejected LFS uploads, rather than enumerating
+// the whole store. When sweeping, chunks that are gone afterwards or turned out to be
+// reachable are forgotten, and those still in their grace period are kept for next time.
+async fn check_orphaned_chunks(
+    ctx: CoreContext,
+    repo_id: RepositoryId,
+    orphans: SqlLfsUploadSessions,
+    sweeper: Option<Arc<dyn SweepableBlobstore>>,
+    sampler: Arc<GcSamplingHandler>,
+    storage: RepoStorageParams,
+    params: GcParams,
+    scuba_builder: ScubaSampleBuilder,
+) -> Result<GcStats, Error> {
+    let sweeper = sweeper.map(|store| open_sweeper(store, &params));
+    let now_secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let repo_prefix = storage.repo_prefix.clone();
+
+    let mut stats = GcStats::default();
+    let mut after = None;
+
+    loop {
+        let chunks = orphans
+            .get_orphaned_chunks(repo_id, after.as_ref(), ORPHANED_CHUNKS_PAGE)
+            .await?;
+        after = match chunks.last() {
+            Some(chunk) => Some(*chunk),
+            None => break,
+        };
+
+        let mut done = vec![];
+        let mut unreachable = BTreeMap::new();
+        for chunk in chunks {
+            stats.enumerated += 1;
+            let key = format!("{}{}", repo_prefix, chunk.blobstore_key());
+            if sampler.is_visited(&key) {
+                stats.reachable += 1;
+                done.push(chunk);
+                continue;
+            }
+            report_unreachable(
+                &ctx,
+                &repo_prefix,
+                &key,
+                &params,
+                &scuba_builder,
+                &mut stats,
+            );
+            unreachable.insert(key, chunk);
+        }
+
+        let sweeper = match &sweeper {
+            Some(sweeper) => sweeper,
+            None => continue,
+        };
+
+        let outcomes = sweep_keys(
+            &ctx,
+            sweeper,
+            unreachable.keys().cloned().collect(),
+            &params,
+            now_secs,
+            &scuba_builder,
+            &mut stats,
+        )
+        .await?;
+
+        for (key, outcome) in outcomes {
+            match outcome {
+                SweepOutcome::Archived(_) | SweepOutcome::AlreadyGone => {
+                    done.extend(unreachable.get(&key).copied())
+                }
+                SweepOutcome::InGracePeriod => (),
+            }
+        }
+
+        orphans.remove_orphaned_chunks(repo_id, &done).await?;
+    }
+
+    Ok(stats)
+}
+
+// Subcommand entry point for mark and sweep of unreachable blobs
+pub async fn gc<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a ArgMatches<'a>,
+    sub_m: &'a ArgMatches<'a>,
+) -> Result<(), Error> {
+    let gc_sampler = Arc::new(GcSamplingHandler::default());
+
+    let (datasources, walk_params) =
+        setup_common(GC, fb, &logger, Some(gc_sampler.clone()), matches, sub_m)?;
+
+    if walk_params.tail_secs.is_some() {
+        return Err(format_err!("gc can't be run in tailing mode"));
+    }
+
+    let sweep = sub_m.is_present(GC_SWEEP_ARG);
+    let orphaned_chunks = sub_m.is_present(GC_LFS_ORPHANED_CHUNKS_ARG);
+    let storage = datasources.storage.clone();
+    if sweep && storage.readonly_storage.0 {
+        return Err(format_err!("--{} needs writable storage", GC_SWEEP_ARG));
+    }
+    // Check before walking, rather than failing on the first unreachable key hours later
+    if sweep
+        && !sweepable_blobstore_has_ctime(storage.blob_config.clone(), storage.inner_blobstore_id)?
+    {
+        return Err(format_err!(
+            "--{} needs a blobstore that records ctime, to apply the grace period",
+            GC_SWEEP_ARG
+        ));
+    }
+
+    // Chunks are only reachable through file contents
+    if orphaned_chunks
+        && !walk_params
+            .include_node_types
+            .contains(&NodeType::FileContent)
+    {
+        return Err(format_err!(
+            "--{} needs the walk to include {:?} nodes",
+            GC_LFS_ORPHANED_CHUNKS_ARG,
+            NodeType::FileContent
+        ));
+    }
+
+    let mut key_prefixes: Vec<String> = walk_params
+        .include_node_types
+        .iter()
+        .flat_map(|t| node_type_key_prefixes(*t).iter().map(|p| p.to_string()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    key_prefixes.sort();
+    info!(
+        logger,
+        "Checking reachability of key types {:?}", key_prefixes
+    );
+    if sweep {
+        warn!(
+            logger,
+            "Sweep enabled, unreachable blobs older than the grace period will be moved to the archive prefix"
+        );
+    }
+
+    let params = GcParams {
+        grace_period_secs: args::get_u64_opt(&sub_m, GC_GRACE_PERIOD_ARG)
+            .unwrap_or(DEFAULT_GRACE_PERIOD_SECS),
+        archive_prefix: sub_m
+            .value_of(GC_ARCHIVE_PREFIX_ARG)
+            .unwrap_or(DEFAULT_ARCHIVE_PREFIX)
+            .to_string(),
+        key_prefixes,
+        scheduled_max: walk_params.scheduled_max,
+        quiet: walk_params.quiet,
+    };
+
+    // Enumeration goes through the blobstore factory, so reports work on any store that can
+    // list its keys, including multiplexes. Sweeping needs direct access to one store.
+    let candidates = if orphaned_chunks {
+        Candidates::OrphanedChunks(
+            SqlLfsUploadSessions::with_metadata_database_config(
+                fb,
+                &storage.metadata,
+                storage.mysql_options,
+                storage.readonly_storage.0,
+            )
+            .await?,
+        )
+    } else {
+        Candidates::Enumerate(
+            open_enumerable_blobstore(
+                fb,
+                storage.blob_config.clone(),
+                storage.inner_blobstore_id,
+                storage.mysql_options,
+                storage.readonly_storage,
+            )
+            .await?,
+        )
+    };
+    let sweeper = if sweep {
+        Some(open_sweepable_blobstore(
+            storage.blob_config.clone(),
+            storage.inner_blobstore_id,
+            storage.readonly_storage,
+        )?)
+    } else {
+        None
+    };
+
+    let make_sink = {
+        cloned!(walk_params.progress_state, gc_sampler);
+        move |run: RepoWalkRun| {
+            cloned!(run.ctx, run.scuba_builder);
+            let repo_id = run.repo.get_repoid();
+            async move |walk_output| {
+                let walk_progress = progress_stream(params.quiet, &progress_state, walk_output);
+                let loading = loading_stream(params.scheduled_max, walk_progress);
+                report_state(ctx.clone(), progress_state, loading).await?;
+
+                let stats = match candidates {
+                    Candidates::Enumerate(enumerator) => {
+                        info!(
+                            ctx.logger(),
+                            "Walk loaded {} keys, enumerating blobstore",
+                            gc_sampler.visited.len()
+                        );
+                        enumerate_and_sweep(
+                            ctx.clone(),
+                            enumerator,
+                            sweeper,
+                            gc_sampler,
+                            storage,
+                            params,
+                            scuba_builder,
+                        )
+                        .await?
+                    }
+                    Candidates::OrphanedChunks(orphans) => {
+                        info!(
+                            ctx.logger(),
+                            "Walk loaded {} keys, checking orphaned LFS chunks",
+                            gc_sampler.visited.len()
+                        );
+                        check_orphaned_chunks(
+                            ctx.clone(),
+                            repo_id,
+                            orphans,
+                            sweeper,
+                            gc_sampler,
+                            storage,
+                            params,
+                            scuba_builder,
+                        )
+                        .await?
+                    }
+                };
+                info!(
+                    ctx.logger(),
+                    "Gc complete. Enumerated {}, reachable {}, not walked type {}, unreachable {} {:?}, in grace period {}, archived {} ({} bytes)",
+                    stats.enumerated,
+                    stats.reachable,
+                    stats.not_walked_type,
+                    stats.unreachable,
+                    stats.unreachable_by_type,
+                    stats.in_grace_period,
+                    stats.archived,
+                    stats.archived_bytes,
+                );
+                Ok(())
+            }
+        }
+    };
+
+    let walk_state = Arc::new(WalkStateCHashMap::new(
+        walk_params.include_node_types.clone(),
+        walk_params.include_edge_types.clone(),
+    ));
+    walk_exact_tail::<_, _, _, _, _, ()>(
+        fb,
+        logger,
+        datasources,
+        walk_params,
+        walk_state,
+        make_sink,
+        false,
+    )
+    .await
+}
@@ -15,6 +15,8 @@ use futures::future::{self, FutureExt};
 use cmdlib::{args, helpers::block_execute};
 
 mod blobstore;
+mod corpus;
+mod gc;
 #[macro_use]
 mod graph;
 mod parse_node;
@@ -47,6 +49,8 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         (setup::VALIDATE, Some(sub_m)) => {
             validate::validate(fb, logger.clone(), &matches, sub_m).boxed()
         }
+        (setup::GC, Some(sub_m)) => gc::gc(fb, logger.clone(), &matches, sub_m).boxed(),
+        (setup::CORPUS, Some(sub_m)) => corpus::corpus(fb, logger.clone(), &matches, sub_m).boxed(),
         _ => {
             future::err::<_, Error>(Error::msg("Invalid Arguments, pass --help for usage.")).boxed()
         }
@@ -91,16 +91,16 @@ where
         step: &OutgoingEdge,
     ) -> CoreContext {
         if self.sample_node_types.contains(&step.target.get_type()) {
+            let sample_path = PathTrackingRoute::evolve_path(
+                route.and_then(|r| r.path.as_ref()),
+                step.path.as_ref(),
+                &step.target,
+            );
             let should_sample = match self.sample_rate {
                 0 => false,
                 1 => true,
                 sample_rate => {
-                    let sampling_fingerprint = PathTrackingRoute::evolve_path(
-                        route.and_then(|r| r.path.as_ref()),
-                        step.path.as_ref(),
-                        &step.target,
-                    )
-                    .map_or_else(
+                    let sampling_fingerprint = sample_path.map_or_else(
                         || step.target.sampling_fingerprint(),
                         |r| r.sampling_fingerprint(),
                     );
@@ -110,8 +110,10 @@ where
 
             if should_sample {
                 ctx = ctx.clone_and_sample(SamplingKey::new());
-                ctx.sampling_key()
-                    .map(|k| self.sampler.start_node(*k, step.target.clone()));
+                ctx.sampling_key().map(|k| {
+                    self.sampler
+                        .start_node(*k, step.target.clone(), sample_path.cloned())
+                });
             }
         }
         self.inner.start_step(ctx, route.map(|_| &()), step)
@@ -162,7 +164,7 @@ where
             if should_sample {
                 ctx = ctx.clone_and_sample(SamplingKey::new());
                 if let Some(k) = ctx.sampling_key() {
-                    self.sampler.start_node(*k, step.target.clone())
+                    self.sampler.start_node(*k, step.target.clone(), None)
                 }
             }
         }
@@ -190,8 +192,9 @@ pub struct NodeSamplingHandler<T> {
     // T can keep a one to many mapping, e.g. some nodes like
     // chunked files have multiple blobstore keys
     inflight: DashMap<SamplingKey, T>,
-    // 1:1 relationship, each node has one SamplingKey
-    inflight_reverse: DashMap<Node, SamplingKey>,
+    // 1:1 relationship, each node has one SamplingKey, plus the repo path
+    // it was reached by if the route tracks paths
+    inflight_reverse: DashMap<Node, (SamplingKey, Option<WrappedPath>)>,
 }
 
 impl<T> NodeSamplingHandler<T>
@@ -211,9 +214,9 @@ where
     }
 
     // Called from the visitor start_step
-    pub fn start_node(&self, key: SamplingKey, node: Node) {
+    pub fn start_node(&self, key: SamplingKey, node: Node, path: Option<WrappedPath>) {
         self.inflight.insert(key, T::default());
-        self.inflight_reverse.insert(node, key);
+        self.inflight_reverse.insert(node, (key, path));
     }
 
     pub fn is_sampling(&self, node: &Node) -> bool {
@@ -224,10 +227,14 @@ where
     // Can be called from the vistor visit, or in the stream processing
     // walk output.
     pub fn complete_node(&self, node: &Node) -> Option<T> {
+        self.complete_node_with_path(node).map(|(_path, v)| v)
+    }
+
+    // As complete_node, but also returns the repo path the node was sampled at.
+    pub fn complete_node_with_path(&self, node: &Node) -> Option<(Option<WrappedPath>, T)> {
         let reverse_mapping = self.inflight_reverse.remove(node);
-        reverse_mapping
-            .as_ref()
-            .and_then(|(_k, sample_key)| self.inflight.remove(sample_key))
-            .map(|(_k, v)| v)
+        reverse_mapping.and_then(|(_k, (sample_key, path))| {
+            self.inflight.remove(&sample_key).map(|(_k, v)| (path, v))
+        })
     }
 }
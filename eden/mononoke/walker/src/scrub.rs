@@ -48,9 +48,9 @@ define_stats! {
 }
 
 #[derive(Add, Div, Mul, Sub, Clone, Copy, Default, Debug)]
-struct ScrubStats {
-    blobstore_bytes: u64,
-    blobstore_keys: u64,
+pub struct ScrubStats {
+    pub blobstore_bytes: u64,
+    pub blobstore_keys: u64,
 }
 
 impl ScrubStats {
@@ -18,7 +18,7 @@ use crate::walk::OutgoingEdge;
 use anyhow::{format_err, Error};
 use blobrepo::BlobRepo;
 use blobrepo_factory::open_blobrepo_given_datasources;
-use blobstore_factory::make_metadata_sql_factory;
+use blobstore_factory::{make_metadata_sql_factory, ReadOnlyStorage};
 use bookmarks::BookmarkName;
 use clap::{App, Arg, ArgMatches, SubCommand, Values};
 use cmdlib::args;
@@ -29,15 +29,28 @@ use futures::{
 };
 use futures_ext::FutureExt as _;
 use lazy_static::lazy_static;
-use metaconfig_types::{Redaction, ScrubAction};
+use metaconfig_types::{BlobConfig, MetadataDatabaseConfig, Redaction, ScrubAction};
 use samplingblob::SamplingHandler;
 use scuba_ext::{ScubaSampleBuilder, ScubaSampleBuilderExt};
 use slog::{info, warn, Logger};
+use sql_ext::facebook::MysqlOptions;
 use std::{collections::HashSet, iter::FromIterator, str::FromStr, sync::Arc, time::Duration};
 
 pub struct RepoWalkDatasources {
     pub blobrepo: BoxFuture<'static, Result<BlobRepo, Error>>,
     pub scuba_builder: ScubaSampleBuilder,
+    pub storage: RepoStorageParams,
+}
+
+// What is needed to reopen the repo's underlying blobstore directly, e.g. to enumerate it
+#[derive(Clone)]
+pub struct RepoStorageParams {
+    pub blob_config: BlobConfig,
+    pub inner_blobstore_id: Option<u64>,
+    pub metadata: MetadataDatabaseConfig,
+    pub mysql_options: MysqlOptions,
+    pub readonly_storage: ReadOnlyStorage,
+    pub repo_prefix: String,
 }
 
 #[derive(Clone)]
@@ -61,6 +74,8 @@ pub const PROGRESS_SAMPLE_DURATION_S: u64 = 5;
 pub const SCRUB: &str = "scrub";
 pub const COMPRESSION_BENEFIT: &str = "compression-benefit";
 pub const VALIDATE: &str = "validate";
+pub const GC: &str = "gc";
+pub const CORPUS: &str = "corpus";
 
 // Subcommand args
 const QUIET_ARG: &str = "quiet";
@@ -89,6 +104,11 @@ pub const EXCLUDE_SAMPLE_NODE_TYPE_ARG: &str = "exclude-sample-node-type";
 pub const INCLUDE_SAMPLE_NODE_TYPE_ARG: &str = "include-sample-node-type";
 const SCUBA_TABLE_ARG: &str = "scuba-table";
 const SCUBA_LOG_FILE_ARG: &str = "scuba-log-file";
+pub const GC_SWEEP_ARG: &str = "sweep";
+pub const GC_GRACE_PERIOD_ARG: &str = "grace-period";
+pub const GC_ARCHIVE_PREFIX_ARG: &str = "archive-prefix";
+pub const GC_LFS_ORPHANED_CHUNKS_ARG: &str = "lfs-orphaned-chunks";
+pub const OUTPUT_DIR_ARG: &str = "output-dir";
 
 const SHALLOW_VALUE_ARG: &str = "shallow";
 const DEEP_VALUE_ARG: &str = "deep";
@@ -348,6 +368,76 @@ pub fn setup_toplevel_app<'a, 'b>(app_name: &str) -> App<'a, 'b> {
             .help(&INCLUDE_CHECK_TYPE_HELP),
     );
 
+    let gc = setup_subcommand_args(
+        SubCommand::with_name(GC).about("find blobs no walked node reaches, by comparing the keys loaded by a walk with a blobstore enumeration. Reports only, unless --sweep is passed"),
+    )
+    .arg(
+        Arg::with_name(GC_SWEEP_ARG)
+            .long(GC_SWEEP_ARG)
+            .takes_value(false)
+            .required(false)
+            .help("Move unreachable blobs older than the grace period to the archive prefix. Only safe if the walk roots and edge types cover everything the repo can reach, so run without this first and check the report."),
+    )
+    .arg(
+        Arg::with_name(GC_GRACE_PERIOD_ARG)
+            .long(GC_GRACE_PERIOD_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("Minimum age in seconds, going by blobstore ctime, before an unreachable blob is swept. Sweeping fails on stores that don't record ctime, such as sqlblob. Default 7 days."),
+    )
+    .arg(
+        Arg::with_name(GC_ARCHIVE_PREFIX_ARG)
+            .long(GC_ARCHIVE_PREFIX_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("Prefix to move swept blobs to. Default archive."),
+    )
+    .arg(
+        Arg::with_name(GC_LFS_ORPHANED_CHUNKS_ARG)
+            .long(GC_LFS_ORPHANED_CHUNKS_ARG)
+            .takes_value(false)
+            .required(false)
+            .help("Instead of enumerating the blobstore, only check the chunks the LFS server recorded as orphaned by rejected uploads. Swept chunks, and chunks found to be reachable, are then forgotten."),
+    );
+
+    let corpus = setup_subcommand_args(
+        SubCommand::with_name(CORPUS).about("Dump a sampled corpus of blobstore data, grouped by node type and repo path"),
+    )
+    .arg(
+        Arg::with_name(OUTPUT_DIR_ARG)
+            .long(OUTPUT_DIR_ARG)
+            .takes_value(true)
+            .required(true)
+            .help("Where to write the output corpus. Blobs are written to <node-type>/<repo-path>/.mononoke,/<blobstore-key>"),
+    )
+    .arg(
+        Arg::with_name(SAMPLE_RATE_ARG)
+            .long(SAMPLE_RATE_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("How many files to sample. Pass 1 to try all, 120 to do 1 in 120, etc."),
+    )
+    .arg(
+        Arg::with_name(EXCLUDE_SAMPLE_NODE_TYPE_ARG)
+            .long(EXCLUDE_SAMPLE_NODE_TYPE_ARG)
+            .short("S")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(false)
+            .help("Node types to exclude from the corpus"),
+    )
+    .arg(
+        Arg::with_name(INCLUDE_SAMPLE_NODE_TYPE_ARG)
+            .long(INCLUDE_SAMPLE_NODE_TYPE_ARG)
+            .short("s")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(false)
+            .help("Node types to include in the corpus"),
+    );
+
     app_template.build()
         .version("0.0.0")
         .about("Walks the mononoke commit and/or derived data graphs, with option of performing validations and modifications")
@@ -361,6 +451,8 @@ pub fn setup_toplevel_app<'a, 'b>(app_name: &str) -> App<'a, 'b> {
         .subcommand(compression_benefit)
         .subcommand(scrub_objects)
         .subcommand(validate)
+        .subcommand(gc)
+        .subcommand(corpus)
 }
 
 // Add the args the "start from repo" walk types need
@@ -783,6 +875,15 @@ pub fn setup_common(
         .map(ScrubAction::from_str)
         .transpose()?;
 
+    let storage = RepoStorageParams {
+        blob_config: storage_config.blobstore.clone(),
+        inner_blobstore_id,
+        metadata: storage_config.metadata.clone(),
+        mysql_options,
+        readonly_storage,
+        repo_prefix: config.repoid.prefix(),
+    };
+
     // Open the blobstore explicitly so we can do things like run on one side of a multiplex
     let blobstore = blobstore::open_blobstore(
         fb,
@@ -848,6 +949,7 @@ pub fn setup_common(
         RepoWalkDatasources {
             blobrepo,
             scuba_builder,
+            storage,
         },
         RepoWalkParams {
             enable_derive,
@@ -33,6 +33,7 @@ use futures::{
     stream::{Stream, TryStreamExt},
 };
 use mononoke_types::BlobstoreBytes;
+use packblob::unpacked_size;
 use samplingblob::SamplingHandler;
 use slog::{info, Logger};
 use std::{
@@ -48,6 +49,8 @@ use std::{
 struct SizingStats {
     raw: u64,
     compressed: u64,
+    // What the blobstore actually holds, less than raw if the blob is packed
+    stored: u64,
 }
 
 impl SizingStats {
@@ -58,29 +61,54 @@ impl SizingStats {
             100 * (self.raw - self.compressed) / self.raw
         }
     }
+
+    fn stored_benefit_pct(&self) -> u64 {
+        if self.raw == 0 {
+            0
+        } else {
+            100 * self.raw.saturating_sub(self.stored) / self.raw
+        }
+    }
 }
 
 impl fmt::Display for SizingStats {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         write!(
             fmt,
-            "{},{},{}%",
+            "{},{},{}%,{},{}%",
             self.raw,
             self.compressed,
-            self.compression_benefit_pct()
+            self.compression_benefit_pct(),
+            self.stored,
+            self.stored_benefit_pct(),
         )
     }
 }
 
 fn try_compress(raw_data: &Bytes, compressor_type: CompressorType) -> Result<SizingStats, Error> {
-    let raw = raw_data.len() as u64;
+    let stored = raw_data.len() as u64;
+    // Blobs packed by packblob report the size they unpack to and the size packing
+    // achieved, rather than being compressed again.
+    if let Some(raw) = unpacked_size(raw_data) {
+        return Ok(SizingStats {
+            raw,
+            compressed: min(raw, stored),
+            stored,
+        });
+    }
+
+    let raw = stored;
     let compressed_buf = MeteredWrite::new(Cursor::new(Vec::with_capacity(4 * 1024)));
     let mut compressor = Compressor::new(compressed_buf, compressor_type);
     compressor.write_all(raw_data)?;
     let compressed_buf = compressor.try_finish().map_err(|(_encoder, e)| e)?;
     // Assume we wouldn't compress if its bigger
     let compressed = min(raw, compressed_buf.total_thru());
-    Ok(SizingStats { raw, compressed })
+    Ok(SizingStats {
+        raw,
+        compressed,
+        stored,
+    })
 }
 
 // Force load of leaf data and check compression ratio
@@ -205,7 +233,7 @@ impl ProgressStateCountByType<SizingStats, SizingStats> {
 
         info!(
             self.params.logger,
-            "Raw/s,Compressed/s,Raw,Compressed,%Saving; Delta {:06}/s,{:06}/s,{},{}s; Run {:06}/s,{:06}/s,{},{}s; Type:Raw,Compressed,%Saving {}",
+            "Raw/s,Compressed/s,Raw,Compressed,%Saving,Stored,%StoredSaving; Delta {:06}/s,{:06}/s,{},{}s; Run {:06}/s,{:06}/s,{},{}s; Type:Raw,Compressed,%Saving,Stored,%StoredSaving {}",
             delta_summary_per_s.raw,
             delta_summary_per_s.compressed,
             delta_summary,
@@ -9,6 +9,7 @@ use crate::setup::{RepoWalkDatasources, RepoWalkParams};
 use crate::walk::{walk_exact, WalkVisitor};
 
 use anyhow::Error;
+use blobrepo::BlobRepo;
 use cloned::cloned;
 use context::CoreContext;
 use fbinit::FacebookInit;
@@ -20,6 +21,7 @@ use tokio::time::{Duration, Instant};
 #[derive(Clone)]
 pub struct RepoWalkRun {
     pub ctx: CoreContext,
+    pub repo: BlobRepo,
     pub scuba_builder: ScubaSampleBuilder,
 }
 
@@ -50,6 +52,7 @@ where
         scuba_builder.add("session", ctx.session().session_id().to_string());
         let walk_run = RepoWalkRun {
             ctx: ctx.clone(),
+            repo: repo.clone(),
             scuba_builder: scuba_builder.clone(),
         };
 
@@ -11,8 +11,10 @@
 //  2. Add CheckType::node_type() and CheckType::enum_type() cases for the new variant
 //  3. Add a new validation method
 //  4. Add the method to the match/case in ValidatingVisitor::visit()
+// Checks that need to load data, like the hash checks, are run after the visit by check_hashes()
 
-use crate::graph::{EdgeType, Node, NodeData, NodeType};
+use crate::blobstore::BLOBSTORE_ID;
+use crate::graph::{EdgeType, Node, NodeData, NodeType, WrappedPath};
 use crate::progress::{
     progress_stream, report_state, sort_by_string, ProgressRecorder, ProgressRecorderUnprotected,
     ProgressReporter, ProgressReporterUnprotected, ProgressStateMutex,
@@ -26,15 +28,24 @@ use crate::tail::{walk_exact_tail, RepoWalkRun};
 use crate::walk::{OutgoingEdge, WalkVisitor};
 
 use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
+use blobstore::Loadable;
 use clap::ArgMatches;
 use cloned::cloned;
 use cmdlib::args;
 use context::CoreContext;
 use derive_more::AddAssign;
 use fbinit::FacebookInit;
-use futures::{future::TryFutureExt, stream::TryStreamExt};
+use filestore::{self, Alias, FetchKey};
+use futures::{compat::Future01CompatExt, future::TryFutureExt, stream::TryStreamExt};
+use futures_old::{stream as old_stream, Stream as Stream01};
 use itertools::Itertools;
-use mononoke_types::MPath;
+use mercurial_types::{
+    calculate_hg_node_id, calculate_hg_node_id_stream, fetch_manifest_envelope, HgFileNodeId,
+    HgManifestId, HgNodeHash, HgParents, NULL_HASH,
+};
+use metaconfig_types::BlobstoreId;
+use mononoke_types::{ContentId, MPath, MononokeId};
 use phases::Phase;
 use scuba_ext::ScubaSampleBuilder;
 use slog::{info, warn, Logger};
@@ -63,6 +74,7 @@ pub const NODE_PATH: &'static str = "node_path";
 pub const EDGE_TYPE: &'static str = "edge_type";
 pub const CHECK_TYPE: &'static str = "check_type";
 pub const CHECK_FAIL: &'static str = "check_fail";
+pub const CHECK_DETAIL: &'static str = "check_detail";
 pub const WALK_TYPE: &'static str = "walk_type";
 pub const REPO: &'static str = "repo";
 const SRC_NODE_KEY: &'static str = "src_node_key";
@@ -90,6 +102,9 @@ define_type_enum! {
 enum CheckType {
     BonsaiChangesetPhaseIsPublic,
     HgLinkNodePopulated,
+    FileContentHashesMatch,
+    HgFileEnvelopeHashMatches,
+    HgManifestHashMatches,
 }
 }
 
@@ -98,12 +113,27 @@ impl CheckType {
         match self {
             CheckType::BonsaiChangesetPhaseIsPublic => "bonsai_phase_is_public",
             CheckType::HgLinkNodePopulated => "hg_link_node_populated",
+            CheckType::FileContentHashesMatch => "file_content_hashes_match",
+            CheckType::HgFileEnvelopeHashMatches => "hg_file_envelope_hash_matches",
+            CheckType::HgManifestHashMatches => "hg_manifest_hash_matches",
         }
     }
     pub fn node_type(&self) -> NodeType {
         match self {
             CheckType::BonsaiChangesetPhaseIsPublic => NodeType::BonsaiPhaseMapping,
             CheckType::HgLinkNodePopulated => NodeType::HgFileNode,
+            CheckType::FileContentHashesMatch => NodeType::FileContent,
+            CheckType::HgFileEnvelopeHashMatches => NodeType::HgFileEnvelope,
+            CheckType::HgManifestHashMatches => NodeType::HgManifest,
+        }
+    }
+    // Hash checks reload the data so they can hash it, see check_hashes()
+    fn is_hash_check(&self) -> bool {
+        match self {
+            CheckType::BonsaiChangesetPhaseIsPublic | CheckType::HgLinkNodePopulated => false,
+            CheckType::FileContentHashesMatch
+            | CheckType::HgFileEnvelopeHashMatches
+            | CheckType::HgManifestHashMatches => true,
         }
     }
 }
@@ -118,11 +148,24 @@ impl fmt::Display for CheckType {
 struct CheckOutput {
     check: CheckType,
     status: CheckStatus,
+    detail: Option<String>,
 }
 
 impl CheckOutput {
     fn new(check: CheckType, status: CheckStatus) -> Self {
-        Self { check, status }
+        Self {
+            check,
+            status,
+            detail: None,
+        }
+    }
+
+    fn failed_with(check: CheckType, detail: String) -> Self {
+        Self {
+            check,
+            status: CheckStatus::Fail,
+            detail: Some(detail),
+        }
     }
 }
 
@@ -170,6 +213,146 @@ fn check_linknode_populated(outgoing: &[OutgoingEdge]) -> CheckStatus {
     }
 }
 
+fn check_hg_hash(check: CheckType, id: HgNodeHash, computed: HgNodeHash) -> CheckOutput {
+    if id == computed {
+        CheckOutput::new(check, CheckStatus::Pass)
+    } else {
+        CheckOutput::failed_with(check, format!("computed {}", computed))
+    }
+}
+
+// Recompute blake2, sha1, sha256 and git sha1 from the bytes. The aliases are compared with the
+// stored metadata, which is what the alias mappings were written from.
+async fn check_file_content_hashes(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: ContentId,
+) -> Result<CheckOutput, Error> {
+    let check = CheckType::FileContentHashesMatch;
+    let key = FetchKey::Canonical(id);
+    let computed = filestore::recompute_metadata(repo.blobstore(), ctx.clone(), &key)
+        .compat()
+        .await?;
+    let computed = match computed {
+        Some(computed) => computed,
+        None => return Ok(CheckOutput::failed_with(check, "missing".to_string())),
+    };
+
+    let mut mismatches = vec![];
+    if computed.content_id != id {
+        mismatches.push(format!("computed {}", computed.content_id.blobstore_key()));
+    }
+    let stored = filestore::get_metadata_readonly(repo.blobstore(), ctx.clone(), &key)
+        .compat()
+        .await?
+        .and_then(|metadata| metadata);
+    if let Some(stored) = stored {
+        if stored.total_size != computed.total_size {
+            mismatches.push(format!(
+                "size {} stored as {}",
+                computed.total_size, stored.total_size
+            ));
+        }
+        let aliases = vec![
+            (Alias::Sha1(stored.sha1), Alias::Sha1(computed.sha1)),
+            (Alias::Sha256(stored.sha256), Alias::Sha256(computed.sha256)),
+            (
+                Alias::GitSha1(stored.git_sha1.sha1()),
+                Alias::GitSha1(computed.git_sha1.sha1()),
+            ),
+        ];
+        for (stored_alias, computed_alias) in aliases {
+            if stored_alias != computed_alias {
+                mismatches.push(format!(
+                    "computed {} stored as {}",
+                    computed_alias.blobstore_key(),
+                    stored_alias.blobstore_key()
+                ));
+            }
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(CheckOutput::new(check, CheckStatus::Pass))
+    } else {
+        Ok(CheckOutput::failed_with(check, mismatches.join(", ")))
+    }
+}
+
+// The filenode hash covers the copy metadata and file content, streamed from the filestore
+async fn check_hg_file_envelope_hash(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: HgFileNodeId,
+) -> Result<CheckOutput, Error> {
+    let envelope = id.load(ctx.clone(), repo.blobstore()).compat().await?;
+    let content = filestore::fetch_stream(repo.blobstore(), ctx.clone(), envelope.content_id());
+    let computed = calculate_hg_node_id_stream(
+        old_stream::once(Ok(envelope.metadata().clone())).chain(content),
+        &envelope.hg_parents(),
+    )
+    .compat()
+    .await?;
+    Ok(check_hg_hash(
+        CheckType::HgFileEnvelopeHashMatches,
+        id.into_nodehash(),
+        computed,
+    ))
+}
+
+async fn check_hg_manifest_hash(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    id: HgManifestId,
+) -> Result<CheckOutput, Error> {
+    let check = CheckType::HgManifestHashMatches;
+    // The empty manifest is not stored
+    if id.into_nodehash() == NULL_HASH {
+        return Ok(CheckOutput::new(check, CheckStatus::Pass));
+    }
+    let envelope = fetch_manifest_envelope(ctx.clone(), repo.blobstore(), id)
+        .compat()
+        .await?
+        .into_mut();
+    let computed = calculate_hg_node_id(
+        envelope.contents.as_ref(),
+        &HgParents::new(envelope.p1, envelope.p2),
+    );
+    Ok(check_hg_hash(check, id.into_nodehash(), computed))
+}
+
+async fn check_hashes(
+    ctx: CoreContext,
+    repo: BlobRepo,
+    n: &Node,
+    mut check_data: CheckData,
+) -> Result<CheckData, Error> {
+    for check in std::mem::take(&mut check_data.hash_checks) {
+        let output = match (check, n) {
+            (CheckType::FileContentHashesMatch, Node::FileContent(id)) => {
+                check_file_content_hashes(&ctx, &repo, *id).await
+            }
+            (CheckType::HgFileEnvelopeHashMatches, Node::HgFileEnvelope(id)) => {
+                check_hg_file_envelope_hash(&ctx, &repo, *id).await
+            }
+            (CheckType::HgManifestHashMatches, Node::HgManifest((_, id))) => {
+                check_hg_manifest_hash(&ctx, &repo, *id).await
+            }
+            _ => return Err(format_err!("Check {} does not apply to {:?}", check, n)),
+        };
+        // A blob that can't be loaded or parsed fails the check, the walk carries on to the
+        // other nodes.
+        let output = output.unwrap_or_else(|e| CheckOutput::failed_with(check, format!("{:#}", e)));
+        if output.status == CheckStatus::Pass {
+            check_data.stats.pass += 1;
+        } else {
+            check_data.stats.fail += 1;
+        }
+        check_data.checked.push(output);
+    }
+    Ok(check_data)
+}
+
 #[derive(AddAssign, Clone, Copy, Default, Debug)]
 struct CheckStats {
     pass: u64,
@@ -179,7 +362,10 @@ struct CheckStats {
 
 struct CheckData {
     source_node: Option<Node>,
+    // Repo path of the step to the node, for nodes like FileContent that have none of their own
+    path: Option<WrappedPath>,
     checked: Vec<CheckOutput>,
+    hash_checks: Vec<CheckType>,
     stats: CheckStats,
 }
 
@@ -223,6 +409,10 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), Node> for Validat
                             num_edges += outgoing.len() as u64;
                             check_linknode_populated(&outgoing)
                         }
+                        // Run later, see check_hashes()
+                        CheckType::FileContentHashesMatch
+                        | CheckType::HgFileEnvelopeHashMatches
+                        | CheckType::HgManifestHashMatches => return None,
                     };
                     if status == CheckStatus::Pass {
                         pass += 1;
@@ -235,6 +425,14 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), Node> for Validat
             .into_iter()
             .flatten()
             .collect();
+        let hash_checks: Vec<_> = checks_to_do
+            .map(|set| set.iter().filter(|c| c.is_hash_check()).cloned().collect())
+            .unwrap_or_default();
+        let path = if !hash_checks.is_empty() && resolved.target.stats_path().is_none() {
+            resolved.path.clone()
+        } else {
+            None
+        };
 
         STATS::walker_validate.add_value(
             num_edges as i64,
@@ -252,12 +450,14 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), Node> for Validat
 
         let vout = (
             node.clone(),
-            if checked.is_empty() {
+            if checked.is_empty() && hash_checks.is_empty() {
                 None
             } else {
                 Some(CheckData {
                     source_node: route,
+                    path,
                     checked,
+                    hash_checks,
                     stats: CheckStats {
                         pass,
                         fail,
@@ -445,6 +645,12 @@ impl ProgressRecorderUnprotected<CheckData> for ValidateProgressState {
                     // For failures log immediately
                     let mut scuba = self.scuba_builder.clone();
                     add_node_to_scuba(source_node.as_ref(), n, &mut scuba);
+                    if let Some(path) = &checkdata.path {
+                        scuba.add(NODE_PATH, MPath::display_opt(path.as_ref()).to_string());
+                    }
+                    if let Some(detail) = &c.detail {
+                        scuba.add(CHECK_DETAIL, detail.clone());
+                    }
                     scuba
                         .add(CHECK_TYPE, k.stats_key())
                         .add(
@@ -495,10 +701,18 @@ pub async fn validate<'a>(
     matches: &'a ArgMatches<'a>,
     sub_m: &'a ArgMatches<'a>,
 ) -> Result<(), Error> {
-    let (datasources, walk_params) = setup_common(VALIDATE, fb, &logger, None, matches, sub_m)?;
+    let (mut datasources, walk_params) = setup_common(VALIDATE, fb, &logger, None, matches, sub_m)?;
     let repo_stats_key = args::get_repo_name(fb, &matches)?;
     let mut include_check_types = parse_check_types(sub_m)?;
     include_check_types.retain(|t| walk_params.include_node_types.contains(&t.node_type()));
+    // Hash checks report the repo path of file contents and envelopes, which is on the edge
+    let keep_edge_paths = include_check_types.iter().any(|t| t.is_hash_check());
+    // When reading a single component of a multiplex, corrupt blobs are from that component
+    if let Some(inner_blobstore_id) = datasources.storage.inner_blobstore_id {
+        datasources
+            .scuba_builder
+            .add(BLOBSTORE_ID, BlobstoreId::new(inner_blobstore_id));
+    }
 
     cloned!(
         walk_params.include_node_types,
@@ -527,17 +741,34 @@ pub async fn validate<'a>(
         Duration::from_secs(PROGRESS_SAMPLE_DURATION_S),
     ));
 
-    cloned!(walk_params.progress_state, walk_params.quiet);
+    cloned!(
+        walk_params.progress_state,
+        walk_params.quiet,
+        walk_params.scheduled_max
+    );
     let make_sink = move |run: RepoWalkRun| {
-        cloned!(run.ctx);
+        cloned!(run.ctx, run.repo);
         validate_progress_state.set_sample_builder(run.scuba_builder);
         async move |walk_output| {
             cloned!(ctx, progress_state, validate_progress_state);
             let walk_progress = progress_stream(quiet, &progress_state.clone(), walk_output)
-                .map_ok(|(n, d, s)| {
-                    // swap stats and data round
-                    (n, s, d)
-                });
+                .map_ok({
+                    cloned!(ctx, repo);
+                    move |(n, d, s)| {
+                        cloned!(ctx, repo);
+                        async move {
+                            let d = match d {
+                                Some(d) if !d.hash_checks.is_empty() => {
+                                    Some(check_hashes(ctx, repo, &n, d).await?)
+                                }
+                                d => d,
+                            };
+                            // swap stats and data round
+                            Ok::<_, Error>((n, s, d))
+                        }
+                    }
+                })
+                .try_buffer_unordered(scheduled_max);
 
             let validate_progress =
                 progress_stream(quiet, &validate_progress_state.clone(), walk_progress);
@@ -559,7 +790,7 @@ pub async fn validate<'a>(
         walk_params,
         stateful_visitor,
         make_sink,
-        false,
+        keep_edge_paths,
     )
     .await
 }
@@ -374,6 +374,25 @@ py_class!(class namedag |py| {
         Ok(Names(namedag.descendants(set.0).map_pyerr(py)?))
     }
 
+    /// Evaluate a revset expression.
+    ///
+    /// `resolve` is called with each symbol, and returns the set it refers to.
+    /// Without it, symbols are looked up as names in the DAG.
+    def revset(&self, expr: &str, resolve: Option<PyObject> = None) -> PyResult<Names> {
+        let namedag = self.namedag(py).borrow();
+        let set = match resolve {
+            None => namedag.revset(expr),
+            Some(pyresolve) => {
+                let resolve = |symbol: &str| -> Result<NameSet> {
+                    let set = pyresolve.call(py, (symbol,), None).into_anyhow_result()?;
+                    Ok(Names::extract(py, &set).into_anyhow_result()?.0)
+                };
+                dag::revset::parse(expr).and_then(|expr| expr.eval(&namedag, &resolve))
+            }
+        };
+        Ok(Names(set.map_pyerr(py)?))
+    }
+
     def debugsegments(&self) -> PyResult<String> {
         let namedag = self.namedag(py).borrow();
         Ok(format!("{:?}", namedag.dag()))
@@ -7,10 +7,11 @@
 
 #![allow(non_camel_case_types)]
 
-use ::metalog::{CommitOptions, Id20, MetaLog, Repair};
+use ::metalog::{resolver, CommitOptions, Id20, KeyChange, MetaLog, Repair, Result};
 use cpython::*;
 use cpython_ext::{Bytes, PyNone, ResultPyErrExt, Str};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::time::SystemTime;
 
@@ -80,20 +81,66 @@ py_class!(class metalog |py| {
         Ok(keys)
     }
 
-    /// Write pending data to disk. Raise if race condition is detected.
+    /// Write pending data to disk. Concurrent changes to bookmarks, remotenames
+    /// and visibleheads are merged. Raise if any other key was changed
+    /// concurrently.
     def commit(&self, message: &str, time: Option<u64> = None, pending: bool = false) -> PyResult<Bytes> {
+        let timestamp = time.unwrap_or_else(now);
+        let key_resolvers = key_resolvers();
         let mut opts = CommitOptions::default();
         opts.detached = pending;
-        opts.timestamp = time.unwrap_or_else(|| {
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .map(|d| d.as_secs()).unwrap_or(0)
-        });
+        opts.resolver = Some(Box::new(
+            move |this: &mut MetaLog, other: &MetaLog, ancestor: &MetaLog| {
+                if changed_without_resolver(other, ancestor, &key_resolvers)? {
+                    resolver::fail(this, other, ancestor)
+                } else {
+                    resolver::merge_keys(this, other, ancestor, &key_resolvers, timestamp)
+                }
+            },
+        ));
+        opts.timestamp = timestamp;
         opts.message = message;
         let id = self.log(py).borrow_mut().commit(opts).map_pyerr(py)?;
         Ok(Bytes::from(id.as_ref().to_vec()))
     }
 
+    /// Keys changed between two roots, as {key: "added" | "removed" | "modified"}.
+    def diff(&self, root_a: Bytes, root_b: Bytes) -> PyResult<PyDict> {
+        let root_a = Id20::from_slice(root_a.as_ref()).map_pyerr(py)?;
+        let root_b = Id20::from_slice(root_b.as_ref()).map_pyerr(py)?;
+        let changes = self.log(py).borrow().diff(root_a, root_b).map_pyerr(py)?;
+        changes_to_dict(py, changes)
+    }
+
+    /// List committed roots, newest first, as (root, message, timestamp, changes).
+    /// `changes` are the keys changed compared to the previous root, in the format
+    /// used by `diff`.
+    def history(&self) -> PyResult<Vec<(PyBytes, Str, u64, PyDict)>> {
+        let log = self.log(py).borrow();
+        let mut result = Vec::new();
+        for entry in log.history().map_pyerr(py)? {
+            let entry = entry.map_pyerr(py)?;
+            result.push((
+                PyBytes::new(py, entry.root_id.as_ref()),
+                Str::from(entry.message),
+                entry.timestamp,
+                changes_to_dict(py, entry.changes)?,
+            ));
+        }
+        Ok(result)
+    }
+
+    /// Commit the content of a previous root as a new root. Raise if there are
+    /// uncommitted changes. Return the new root id.
+    def restore(&self, root: Bytes, message: &str, time: Option<u64> = None) -> PyResult<Bytes> {
+        let root = Id20::from_slice(root.as_ref()).map_pyerr(py)?;
+        let mut opts = CommitOptions::default();
+        opts.timestamp = time.unwrap_or_else(now);
+        opts.message = message;
+        let id = self.log(py).borrow_mut().checkout(root, opts).map_pyerr(py)?;
+        Ok(Bytes::from(id.as_ref().to_vec()))
+    }
+
     /// Export to a git respository
     def exportgit(&self, path: String) -> PyResult<PyNone> {
         let log = self.log(py).borrow();
@@ -140,3 +187,46 @@ py_class!(class metalog |py| {
         py.allow_threads(|| MetaLog::repair(path)).map_pyerr(py).map(Into::into)
     }
 });
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn key_resolvers() -> BTreeMap<String, resolver::KeyResolver> {
+    let mut key_resolvers = BTreeMap::<String, resolver::KeyResolver>::new();
+    key_resolvers.insert("bookmarks".to_string(), Box::new(resolver::bookmarks));
+    key_resolvers.insert("remotenames".to_string(), Box::new(resolver::bookmarks));
+    key_resolvers.insert("visibleheads".to_string(), Box::new(resolver::line_set));
+    key_resolvers
+}
+
+/// Test if `other` changed a key that has no resolver since `ancestor`.
+/// Such changes are conflicts, like they were before keys could be merged.
+fn changed_without_resolver(
+    other: &MetaLog,
+    ancestor: &MetaLog,
+    key_resolvers: &BTreeMap<String, resolver::KeyResolver>,
+) -> Result<bool> {
+    for key in other.keys().into_iter().chain(ancestor.keys()) {
+        if !key_resolvers.contains_key(key) && other.get(key)? != ancestor.get(key)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn changes_to_dict(py: Python, changes: BTreeMap<String, KeyChange>) -> PyResult<PyDict> {
+    let dict = PyDict::new(py);
+    for (key, change) in changes {
+        let change = match change {
+            KeyChange::Added => "added",
+            KeyChange::Removed => "removed",
+            KeyChange::Modified => "modified",
+        };
+        dict.set_item(py, key, change)?;
+    }
+    Ok(dict)
+}
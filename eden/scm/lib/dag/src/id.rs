@@ -16,7 +16,17 @@ use std::ops;
 
 /// An integer [`Id`] representing a node in the graph.
 /// [`Id`]s are topologically sorted.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize
+)]
 pub struct Id(pub u64);
 
 /// Name of a vertex in the graph.
@@ -76,7 +86,18 @@ impl fmt::Debug for VertexName {
 /// for one group to make segments less fragmented.
 ///
 /// `(Group, Id)` are also topologically sorted.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize
+)]
 pub struct Group(pub(crate) usize);
 
 impl Group {
@@ -7,15 +7,16 @@
 
 use crate::id::{Group, Id};
 use crate::iddagstore::{GetLock, IdDagStore, InProcessStore, IndexedLogStore};
-use crate::segment::{Segment, SegmentFlags};
+use crate::segment::{FlatSegment, Segment, SegmentFlags};
 use crate::spanset::Span;
 use crate::spanset::SpanSet;
 use crate::Level;
 use anyhow::{bail, ensure, format_err, Result};
 use indexmap::set::IndexSet;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::collections::{BTreeSet, BinaryHeap};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
 use std::fmt::{self, Debug, Formatter};
 use std::fs::File;
 use std::path::Path;
@@ -36,6 +37,7 @@ use std::path::Path;
 /// [`IdDag`] is often used together with [`IdMap`] to allow customized names
 /// on vertexes. The [`NameDag`] type provides an easy-to-use interface to
 /// keep [`IdDag`] and [`IdMap`] in sync.
+#[derive(Serialize, Deserialize)]
 pub struct IdDag<Store> {
     store: Store,
     max_level: Level,
@@ -1095,7 +1097,7 @@ impl<Store: IdDagStore> IdDag<Store> {
     /// See also [`FirstAncestorConstraint::KnownUniversally`].
     ///
     /// Complexity: `O(flat segments)` for both time and space.
-    fn universal(&self) -> Result<BTreeSet<Id>> {
+    pub fn universal(&self) -> Result<BTreeSet<Id>> {
         let mut result = BTreeSet::new();
         for seg in self.next_segments(Id::MIN, 0)? {
             let parents = seg.parents()?;
@@ -1115,6 +1117,69 @@ impl<Store: IdDagStore> IdDag<Store> {
     }
 }
 
+// Flat segments export and import
+impl<Store: IdDagStore> IdDag<Store> {
+    /// Export flat segments in the given group, sorted by id.
+    ///
+    /// Together with the "universal" names (see [`IdDag::universal`]), this
+    /// is what a client needs to clone the graph without the commit hashes.
+    pub fn flat_segments(&self, group: Group) -> Result<Vec<FlatSegment>> {
+        self.next_segments(group.min_id(), 0)?
+            .into_iter()
+            .map(|seg| {
+                let span = seg.span()?;
+                Ok(FlatSegment {
+                    low: span.low,
+                    high: span.high,
+                    parents: seg.parents()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Build segments from flat segments exported by [`IdDag::flat_segments`].
+    ///
+    /// The flat segments must be in a single group, connected, and start from
+    /// the next free id of that group.
+    ///
+    /// Content inserted by this function *will not* be written to disk.
+    pub fn build_segments_volatile_from_flat_segments(
+        &mut self,
+        segments: &[FlatSegment],
+    ) -> Result<usize> {
+        let high = match segments.last() {
+            None => return Ok(0),
+            Some(seg) => seg.high,
+        };
+        let low = self.next_free_id(0, high.group())?;
+        ensure!(
+            segments[0].low == low,
+            "flat segments start at {} but the next free id is {}",
+            segments[0].low,
+            low
+        );
+        for pair in segments.windows(2) {
+            ensure!(
+                pair[0].high + 1 == pair[1].low,
+                "flat segments {:?} and {:?} are not connected",
+                pair[0],
+                pair[1]
+            );
+        }
+
+        let by_high: BTreeMap<Id, &FlatSegment> =
+            segments.iter().map(|seg| (seg.high, seg)).collect();
+        let get_parents = |id: Id| -> Result<Vec<Id>> {
+            match by_high.range(id..).next() {
+                Some((_, seg)) if seg.low == id => Ok(seg.parents.clone()),
+                Some((_, seg)) if seg.low < id => Ok(vec![id - 1]),
+                _ => bail!("{} is not covered by flat segments", id),
+            }
+        };
+        self.build_segments_volatile(high, &get_parents)
+    }
+}
+
 /// There are many `x~n`s that all resolves to a single commit.
 /// Constraint about `x~n`.
 pub enum FirstAncestorConstraint {
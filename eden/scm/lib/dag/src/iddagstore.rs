@@ -13,6 +13,7 @@ use byteorder::{BigEndian, WriteBytesExt};
 use fs2::FileExt;
 use indexedlog::log;
 use minibytes::Bytes;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{self, File};
 use std::io::Cursor;
@@ -376,12 +377,23 @@ impl IndexedLogStore {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize
+)]
 enum StoreId {
     Master(usize),
     NonMaster(usize),
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct InProcessStore {
     master_segments: Vec<Segment>,
     non_master_segments: Vec<Segment>,
@@ -805,4 +817,32 @@ mod tests {
             .next()
             .is_none());
     }
+
+    #[test]
+    fn test_in_process_store_serialize() {
+        let store = get_in_process_store();
+        let bytes = mincode::serialize(&store).unwrap();
+        let store: InProcessStore = mincode::deserialize(&bytes).unwrap();
+
+        let segment = store
+            .find_segment_by_head_and_level(Id(13), 1 as Level)
+            .unwrap()
+            .unwrap();
+        assert_eq!(&segment, LEVEL1_HEAD13.deref());
+        let segment = store
+            .find_flat_segment_including_id(nid(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&segment, LEVEL0_HEADN2.deref());
+        assert_eq!(
+            store.next_free_id(0 as Level, Group::MASTER).unwrap(),
+            Id(14)
+        );
+        let segments: Vec<Segment> = store
+            .iter_master_flat_segments_with_parent(Id(2))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(segments, segments_to_owned(&[&LEVEL0_HEAD5, &LEVEL0_HEAD9]));
+    }
 }
@@ -18,14 +18,16 @@ pub mod idmap;
 pub mod namedag;
 pub mod nameset;
 pub mod protocol;
+pub mod revset;
 mod segment;
 pub mod spanset;
 
 pub use id::{Group, Id, VertexName};
-pub use iddag::IdDag;
+pub use iddag::{FirstAncestorConstraint, IdDag};
 pub use idmap::IdMap;
 pub use namedag::NameDag;
 pub use nameset::NameSet;
+pub use segment::FlatSegment;
 pub use spanset::SpanSet;
 
 pub type Level = u8;
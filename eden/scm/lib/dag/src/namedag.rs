@@ -19,6 +19,7 @@ use crate::idmap::IdMapLike;
 use crate::idmap::SyncableIdMap;
 use crate::nameset::dag::DagSet;
 use crate::nameset::NameSet;
+use crate::revset;
 use crate::spanset::SpanSet;
 use anyhow::{anyhow, bail, ensure, Result};
 use indexedlog::multi;
@@ -389,6 +390,20 @@ impl NameDag {
         Ok(NameSet::from_spans_idmap(spans, self.snapshot_map.clone()))
     }
 
+    /// Evaluates a [`revset`](crate::revset) expression. Symbols are
+    /// vertex names.
+    pub fn revset(&self, expr: &str) -> Result<NameSet> {
+        revset::parse(expr)?.eval(
+            self,
+            &|name| match self.map.find_id_by_name(name.as_bytes())? {
+                Some(_) => Ok(NameSet::from_static_names(vec![VertexName::copy_from(
+                    name.as_bytes(),
+                )])),
+                None => bail!("unknown revision: {}", name),
+            },
+        )
+    }
+
     /// Converts [`NameSet`] to [`SpanSet`].
     fn to_span_set(&self, set: NameSet) -> Result<SpanSet> {
         // Fast path: extract SpanSet directly.
@@ -20,10 +20,12 @@ use crate::id::VertexName;
 use crate::iddag::{FirstAncestorConstraint, IdDag};
 use crate::iddagstore::IdDagStore;
 use crate::idmap::IdMapLike;
+use crate::segment::FlatSegment;
 use crate::spanset::SpanSet;
 use crate::{Id, IdMap};
 use anyhow::{format_err, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
 // Request and Response structures -------------------------------------------
@@ -88,6 +90,23 @@ impl fmt::Debug for AncestorPath {
     }
 }
 
+/// What a client needs to lazily clone the master group of a graph: the flat
+/// segments and names of the "universal" ids (see [`IdDag::universal`]).
+/// Names of other ids can be resolved later using [`AncestorPath`]s.
+///
+/// `Name` is the commit hash type of the side that produced the data.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CloneData<Name> {
+    #[serde(rename = "h")]
+    pub head_id: Id,
+
+    #[serde(rename = "s")]
+    pub flat_segments: Vec<FlatSegment>,
+
+    #[serde(rename = "m")]
+    pub idmap: BTreeMap<Id, Name>,
+}
+
 // Traits --------------------------------------------------------------------
 
 /// Similar to `From::from(I) -> O`, but with `self` as context.
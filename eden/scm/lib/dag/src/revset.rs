@@ -0,0 +1,523 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! # revset
+//!
+//! A small revset language to compose [`NameDag`] queries.
+//!
+//! Supported syntax, from the lowest to the highest precedence:
+//! - `x | y`, `x + y`, `x or y`: union.
+//! - `x & y`, `x and y`: intersection. `x - y`: difference.
+//!   `x % y`: `only(x, y)`.
+//! - `!x`, `not x`: everything except `x`.
+//! - `x::y`: descendants of `x` that are ancestors of `y`.
+//!   `::x`, `x::`, `::`: ancestors of `x`, descendants of `x`, everything.
+//!
+//! Functions: `all()`, `none()`, `ancestors(x)`, `descendants(x)`,
+//! `parents(x)`, `children(x)`, `heads(x)`, `roots(x)`, `only(x, y)`,
+//! `gca(x, ...)`, `first(x)`, `last(x)`.
+//!
+//! Symbols are words made of alphanumeric characters and `_./@`, or quoted
+//! strings (use quotes for names containing `-`, for example). They are
+//! translated to sets by a caller-provided function.
+//!
+//! Evaluation only composes [`NameDag`] and [`NameSet`] operations, so
+//! the result is as lazy as the underlying sets.
+
+use crate::namedag::NameDag;
+use crate::nameset::NameSet;
+use anyhow::{bail, Result};
+
+/// Parsed revset expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    /// A name to be resolved.
+    Symbol(String),
+    /// `!x`
+    Not(Box<Expr>),
+    /// `x & y`
+    And(Box<Expr>, Box<Expr>),
+    /// `x | y`
+    Or(Box<Expr>, Box<Expr>),
+    /// `x - y`
+    Difference(Box<Expr>, Box<Expr>),
+    /// `x::y`
+    Range(Box<Expr>, Box<Expr>),
+    /// `name(args...)`. Also used for `::x`, `x::`, `::` and `x % y`.
+    Func(String, Vec<Expr>),
+}
+
+/// Parses a revset expression.
+pub fn parse(text: &str) -> Result<Expr> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens, index: 0 };
+    let expr = parser.parse_expr(0)?;
+    parser.expect(Token::End)?;
+    Ok(expr)
+}
+
+impl Expr {
+    /// Evaluates the expression on the given DAG. `resolve` translates
+    /// symbols to sets.
+    pub fn eval(
+        &self,
+        dag: &NameDag,
+        resolve: &dyn Fn(&str) -> Result<NameSet>,
+    ) -> Result<NameSet> {
+        let set = match self {
+            Expr::Symbol(name) => resolve(name)?,
+            Expr::Not(x) => dag.all()?.difference(&x.eval(dag, resolve)?),
+            Expr::And(x, y) => x.eval(dag, resolve)?.intersection(&y.eval(dag, resolve)?),
+            Expr::Or(x, y) => x.eval(dag, resolve)?.union(&y.eval(dag, resolve)?),
+            Expr::Difference(x, y) => x.eval(dag, resolve)?.difference(&y.eval(dag, resolve)?),
+            Expr::Range(x, y) => dag.range(x.eval(dag, resolve)?, y.eval(dag, resolve)?)?,
+            Expr::Func(name, args) => eval_func(dag, name, args, resolve)?,
+        };
+        Ok(set)
+    }
+}
+
+fn eval_func(
+    dag: &NameDag,
+    name: &str,
+    args: &[Expr],
+    resolve: &dyn Fn(&str) -> Result<NameSet>,
+) -> Result<NameSet> {
+    let expect_args = |count: usize| -> Result<()> {
+        if args.len() != count {
+            bail!(
+                "{}() takes {} argument(s), but {} were given",
+                name,
+                count,
+                args.len()
+            );
+        }
+        Ok(())
+    };
+    let arg = |index: usize| args[index].eval(dag, resolve);
+
+    let set = match name {
+        "all" => {
+            expect_args(0)?;
+            dag.all()?
+        }
+        "none" => {
+            expect_args(0)?;
+            NameSet::from_static_names(Vec::new())
+        }
+        "ancestors" => {
+            expect_args(1)?;
+            dag.ancestors(arg(0)?)?
+        }
+        "descendants" => {
+            expect_args(1)?;
+            dag.descendants(arg(0)?)?
+        }
+        "parents" => {
+            expect_args(1)?;
+            dag.parents(arg(0)?)?
+        }
+        "children" => {
+            expect_args(1)?;
+            dag.children(arg(0)?)?
+        }
+        "heads" => {
+            expect_args(1)?;
+            dag.heads(arg(0)?)?
+        }
+        "roots" => {
+            expect_args(1)?;
+            dag.roots(arg(0)?)?
+        }
+        "only" => {
+            expect_args(2)?;
+            let include = dag.ancestors(arg(0)?)?;
+            let exclude = dag.ancestors(arg(1)?)?;
+            include.difference(&exclude)
+        }
+        "gca" => {
+            if args.is_empty() {
+                bail!("gca() takes at least 1 argument");
+            }
+            let mut set = arg(0)?;
+            for index in 1..args.len() {
+                set = set.union(&arg(index)?);
+            }
+            dag.gca_all(set)?
+        }
+        "first" => {
+            expect_args(1)?;
+            NameSet::from_static_names(arg(0)?.first()?)
+        }
+        "last" => {
+            expect_args(1)?;
+            NameSet::from_static_names(arg(0)?.last()?)
+        }
+        _ => bail!("unknown revset function: {}", name),
+    };
+    Ok(set)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Symbol(String),
+    String(String),
+    Op(&'static str),
+    End,
+}
+
+/// Splits text into tokens, with their byte offsets.
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((pos, ch)) = chars.next() {
+        let token = match ch {
+            c if c.is_whitespace() => continue,
+            ':' => match chars.next() {
+                Some((_, ':')) => Token::Op("::"),
+                _ => bail!("revset parse error at {}: expected '::'", pos),
+            },
+            '(' => Token::Op("("),
+            ')' => Token::Op(")"),
+            ',' => Token::Op(","),
+            '%' => Token::Op("%"),
+            '&' => Token::Op("&"),
+            '|' => Token::Op("|"),
+            '+' => Token::Op("|"),
+            '-' => Token::Op("-"),
+            '!' => Token::Op("!"),
+            '\'' | '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, c)) if c == ch => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => value.push(c),
+                            None => bail!("revset parse error at {}: unterminated string", pos),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => bail!("revset parse error at {}: unterminated string", pos),
+                    }
+                }
+                Token::String(value)
+            }
+            c if is_symbol_char(c) => {
+                let mut word = c.to_string();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !is_symbol_char(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "and" => Token::Op("&"),
+                    "or" => Token::Op("|"),
+                    "not" => Token::Op("!"),
+                    _ => Token::Symbol(word),
+                }
+            }
+            c => bail!("revset parse error at {}: unexpected {:?}", pos, c),
+        };
+        tokens.push((pos, token));
+    }
+
+    tokens.push((text.len(), Token::End));
+    Ok(tokens)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_alphanumeric() || "_./@".contains(c)
+}
+
+/// Binding power of prefix operators.
+const NOT_POWER: u8 = 10;
+const RANGE_POWER: u8 = 17;
+
+/// Binding power of infix operators. Higher binds tighter.
+fn infix_power(op: &str) -> Option<u8> {
+    match op {
+        "|" => Some(4),
+        "&" | "-" | "%" => Some(5),
+        "::" => Some(RANGE_POWER),
+        _ => None,
+    }
+}
+
+/// Pratt parser over tokens.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].1
+    }
+
+    fn next(&mut self) -> (usize, Token) {
+        let (pos, token) = self.tokens[self.index].clone();
+        if token != Token::End {
+            self.index += 1;
+        }
+        (pos, token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        let (pos, token) = self.next();
+        if token != expected {
+            bail!(
+                "revset parse error at {}: expected {}, got {}",
+                pos,
+                describe(&expected),
+                describe(&token)
+            );
+        }
+        Ok(())
+    }
+
+    /// Tests whether the next token can start an expression. Used to tell
+    /// `x::y` from `x::` and `::x` from `::`.
+    fn at_expr_start(&self) -> bool {
+        match self.peek() {
+            Token::Symbol(_) | Token::String(_) => true,
+            Token::Op(op) => ["(", "!", "::"].contains(op),
+            Token::End => false,
+        }
+    }
+
+    /// Parses an expression whose infix operators bind tighter than
+    /// `min_power`.
+    fn parse_expr(&mut self, min_power: u8) -> Result<Expr> {
+        let (pos, token) = self.next();
+        let mut lhs = match token {
+            Token::Symbol(name) => {
+                if self.peek() == &Token::Op("(") {
+                    self.next();
+                    Expr::Func(name, self.parse_args()?)
+                } else {
+                    Expr::Symbol(name)
+                }
+            }
+            Token::String(name) => Expr::Symbol(name),
+            Token::Op("(") => {
+                let expr = self.parse_expr(0)?;
+                self.expect(Token::Op(")"))?;
+                expr
+            }
+            Token::Op("!") => Expr::Not(Box::new(self.parse_expr(NOT_POWER)?)),
+            Token::Op("::") => {
+                if self.at_expr_start() {
+                    Expr::Func("ancestors".to_string(), vec![self.parse_expr(RANGE_POWER)?])
+                } else {
+                    Expr::Func("all".to_string(), vec![])
+                }
+            }
+            token => bail!(
+                "revset parse error at {}: unexpected {}",
+                pos,
+                describe(&token)
+            ),
+        };
+
+        while let Token::Op(op) = self.peek() {
+            let op = *op;
+            let power = match infix_power(op) {
+                Some(power) if power > min_power => power,
+                _ => break,
+            };
+            self.next();
+            lhs = match op {
+                "::" if !self.at_expr_start() => Expr::Func("descendants".to_string(), vec![lhs]),
+                "::" => Expr::Range(Box::new(lhs), Box::new(self.parse_expr(power)?)),
+                "%" => Expr::Func("only".to_string(), vec![lhs, self.parse_expr(power)?]),
+                "&" => Expr::And(Box::new(lhs), Box::new(self.parse_expr(power)?)),
+                "-" => Expr::Difference(Box::new(lhs), Box::new(self.parse_expr(power)?)),
+                "|" => Expr::Or(Box::new(lhs), Box::new(self.parse_expr(power)?)),
+                _ => unreachable!("infix_power only accepts the operators above"),
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses function arguments, after the opening parenthesis.
+    fn parse_args(&mut self) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.peek() == &Token::Op(")") {
+            self.next();
+            return Ok(args);
+        }
+        loop {
+            args.push(self.parse_expr(0)?);
+            let (pos, token) = self.next();
+            match token {
+                Token::Op(",") => continue,
+                Token::Op(")") => return Ok(args),
+                token => bail!(
+                    "revset parse error at {}: expected ',' or ')', got {}",
+                    pos,
+                    describe(&token)
+                ),
+            }
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Symbol(name) => format!("symbol {:?}", name),
+        Token::String(value) => format!("string {:?}", value),
+        Token::Op(op) => format!("'{}'", op),
+        Token::End => "end of input".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_segments;
+
+    fn check_parse(text: &str) -> String {
+        match parse(text) {
+            Ok(expr) => format!("{:?}", expr),
+            Err(err) => format!("error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(check_parse("a"), "Symbol(\"a\")");
+        assert_eq!(check_parse("::a"), "Func(\"ancestors\", [Symbol(\"a\")])");
+        assert_eq!(check_parse("a::"), "Func(\"descendants\", [Symbol(\"a\")])");
+        assert_eq!(check_parse("::"), "Func(\"all\", [])");
+        assert_eq!(check_parse("a::b"), "Range(Symbol(\"a\"), Symbol(\"b\"))");
+        assert_eq!(
+            check_parse("a % b"),
+            "Func(\"only\", [Symbol(\"a\"), Symbol(\"b\")])"
+        );
+        assert_eq!(
+            check_parse("'a-b' - \"c\\\"\""),
+            "Difference(Symbol(\"a-b\"), Symbol(\"c\\\"\"))"
+        );
+        assert_eq!(check_parse("heads()"), "Func(\"heads\", [])");
+        assert_eq!(
+            check_parse("gca(a, b::)"),
+            "Func(\"gca\", [Symbol(\"a\"), Func(\"descendants\", [Symbol(\"b\")])])"
+        );
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        assert_eq!(
+            check_parse("a | b & c"),
+            "Or(Symbol(\"a\"), And(Symbol(\"b\"), Symbol(\"c\")))"
+        );
+        assert_eq!(
+            check_parse("a - b - c"),
+            "Difference(Difference(Symbol(\"a\"), Symbol(\"b\")), Symbol(\"c\"))"
+        );
+        assert_eq!(
+            check_parse("not a::b and c"),
+            "And(Not(Range(Symbol(\"a\"), Symbol(\"b\"))), Symbol(\"c\"))"
+        );
+        assert_eq!(
+            check_parse("a:: or ::b"),
+            "Or(Func(\"descendants\", [Symbol(\"a\")]), Func(\"ancestors\", [Symbol(\"b\")]))"
+        );
+        assert_eq!(
+            check_parse("(a + b) & !c"),
+            "And(Or(Symbol(\"a\"), Symbol(\"b\")), Not(Symbol(\"c\")))"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            check_parse(""),
+            "error: revset parse error at 0: unexpected end of input"
+        );
+        assert_eq!(
+            check_parse("a b"),
+            "error: revset parse error at 2: expected end of input, got symbol \"b\""
+        );
+        assert_eq!(
+            check_parse("a:b"),
+            "error: revset parse error at 1: expected '::'"
+        );
+        assert_eq!(
+            check_parse("f(a"),
+            "error: revset parse error at 3: expected ',' or ')', got end of input"
+        );
+        assert_eq!(
+            check_parse("(a"),
+            "error: revset parse error at 2: expected ')', got end of input"
+        );
+        assert_eq!(
+            check_parse("'a"),
+            "error: revset parse error at 0: unterminated string"
+        );
+        assert_eq!(
+            check_parse("a & $"),
+            "error: revset parse error at 4: unexpected '$'"
+        );
+    }
+
+    #[test]
+    fn test_eval() -> Result<()> {
+        let ascii = r#"
+            J K
+           /|\|\
+          G H I H
+          |/|/
+          E F
+         /|/|\
+        A B C D"#;
+        let result = build_segments(ascii, "J K", 2);
+        let dag = &result.name_dag;
+
+        let revset = |text: &str| -> String {
+            match dag.revset(text) {
+                Ok(set) => set
+                    .iter()
+                    .unwrap()
+                    .map(|n| String::from_utf8_lossy(n.unwrap().as_ref()).to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                Err(err) => format!("error: {}", err),
+            }
+        };
+
+        assert_eq!(revset("::"), "K J I H F D C G E B A");
+        assert_eq!(revset("::H"), "H F D C E B A");
+        assert_eq!(revset("F::"), "K J I H F");
+        assert_eq!(revset("A::K"), "K H E A");
+        assert_eq!(revset("J % K"), "J G");
+        assert_eq!(revset("only(K, J)"), "K");
+        assert_eq!(revset("heads(E + H + F + K + I + D)"), "K");
+        assert_eq!(revset("roots(E | G | H | J | I | K | D)"), "I D E");
+        assert_eq!(revset("gca(J, K)"), "I H");
+        assert_eq!(revset("parents(H or I or E)"), "F E B A");
+        assert_eq!(revset("children(E + F + I)"), "K J I H G");
+        assert_eq!(revset("first(::H - ::E)"), "H");
+        assert_eq!(revset("last(::H - ::E)"), "C");
+        assert_eq!(revset("last(none())"), "");
+        assert_eq!(revset("not ::H"), "K J I G");
+        assert_eq!(revset("::J & ::K - ::I"), "H E A");
+        assert_eq!(revset("ancestors(G) and descendants(B)"), "G E B");
+
+        assert_eq!(revset("X"), "error: unknown revision: X");
+        assert_eq!(revset("foo(A)"), "error: unknown revset function: foo");
+        assert_eq!(
+            revset("only(A)"),
+            "error: only() takes 2 argument(s), but 1 were given"
+        );
+
+        Ok(())
+    }
+}
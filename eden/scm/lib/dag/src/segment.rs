@@ -21,6 +21,7 @@ use anyhow::{bail, Result};
 use bitflags::bitflags;
 use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use minibytes::Bytes;
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Debug, Formatter};
 use std::io::Cursor;
 use vlqencoding::{VLQDecode, VLQDecodeAt, VLQEncode};
@@ -28,7 +29,7 @@ use vlqencoding::{VLQDecode, VLQDecodeAt, VLQEncode};
 /// [`Segment`] represents a range of [`Id`]s in an [`IdDag`] graph.
 /// It provides methods to access properties of the segments, including the range itself,
 /// parents, and level information.
-#[derive(Clone, Eq)]
+#[derive(Clone, Eq, Serialize, Deserialize)]
 pub struct Segment(pub(crate) Bytes);
 
 // Serialization format for Segment:
@@ -144,6 +145,18 @@ impl Segment {
     }
 }
 
+/// A flat (level 0) segment in a form that is independent from the storage
+/// format. Used to transfer the graph between a server and a client.
+///
+/// Ids in `low..=high` have their previous id as the only parent, except for
+/// `low`, whose parents are `parents`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FlatSegment {
+    pub low: Id,
+    pub high: Id,
+    pub parents: Vec<Id>,
+}
+
 impl PartialEq for Segment {
     fn eq(&self, other: &Self) -> bool {
         self.0[..] == other.0[..]
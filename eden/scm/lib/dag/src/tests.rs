@@ -8,6 +8,7 @@
 use crate::id::{Group, Id, VertexName};
 use crate::iddag::FirstAncestorConstraint;
 use crate::protocol::{Process, RequestLocationToName, RequestNameToLocation};
+use crate::IdDag;
 use crate::IdMap;
 use crate::NameDag;
 use crate::NameSet;
@@ -211,6 +212,49 @@ Lv3: R0-11[]"#
     );
 }
 
+#[test]
+fn test_flat_segments_export_import() {
+    let built = build_segments(ASCII_DAG1, "A C E L", 3);
+    let dag = &built.name_dag.dag;
+    let flat_segments = dag.flat_segments(Group::MASTER).unwrap();
+    assert_eq!(
+        format!("{:?}", &flat_segments),
+        "[FlatSegment { low: 0, high: 0, parents: [] }, \
+         FlatSegment { low: 1, high: 1, parents: [] }, \
+         FlatSegment { low: 2, high: 2, parents: [0] }, \
+         FlatSegment { low: 3, high: 3, parents: [1] }, \
+         FlatSegment { low: 4, high: 4, parents: [2, 3] }, \
+         FlatSegment { low: 5, high: 7, parents: [4] }, \
+         FlatSegment { low: 8, high: 9, parents: [6] }, \
+         FlatSegment { low: 10, high: 11, parents: [7, 9] }]"
+    );
+
+    let mut imported = IdDag::new_in_process();
+    imported
+        .build_segments_volatile_from_flat_segments(&flat_segments)
+        .unwrap();
+    assert_eq!(
+        format_set(imported.all().unwrap()),
+        format_set(dag.all().unwrap())
+    );
+    assert_eq!(imported.universal().unwrap(), dag.universal().unwrap());
+    for id in Id(0).to(Id(11)) {
+        assert_eq!(
+            imported.parent_ids(id).unwrap(),
+            dag.parent_ids(id).unwrap()
+        );
+        assert_eq!(
+            format_set(imported.ancestors(id).unwrap()),
+            format_set(dag.ancestors(id).unwrap())
+        );
+    }
+
+    // Importing again does not connect to the existing segments.
+    assert!(imported
+        .build_segments_volatile_from_flat_segments(&flat_segments)
+        .is_err());
+}
+
 #[test]
 fn test_segment_examples() {
     assert_eq!(
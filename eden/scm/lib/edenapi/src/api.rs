@@ -7,7 +7,15 @@
 
 use bytes::Bytes;
 
-use types::{HgId, HistoryEntry, Key, RepoPathBuf};
+use dag::protocol::CloneData;
+use types::{
+    api::{
+        AnyId, CommitHashToLocationResponse, CommitLocationToHashRequest,
+        CommitLocationToHashResponse, LookupResponse, PushrebaseResponse, SetBookmarkResponse,
+        UploadHgFilenodeRequest,
+    },
+    DataEntry, HgId, HistoryEntry, Key, RepoPathBuf, Sha256,
+};
 
 use crate::errors::ApiResult;
 use crate::progress::ProgressFn;
@@ -67,6 +75,90 @@ pub trait EdenApi: Send + Sync {
         depth: Option<usize>,
         progress: Option<ProgressFn>,
     ) -> ApiResult<(Box<dyn Iterator<Item = (Key, Bytes)>>, DownloadStats)>;
+
+    /// Fetch the segments of the repo's commit graph, together with the hashes
+    /// of the commits a client needs to know to resolve any other commit
+    /// later on with `commit_location_to_hash` and `commit_hash_to_location`.
+    fn clone_data(&self) -> ApiResult<CloneData<HgId>>;
+
+    /// Resolve commit locations (a known descendant and a number of first
+    /// parent steps from it) into commit hashes. Each request can ask for a
+    /// chain of first ancestors in one go.
+    fn commit_location_to_hash(
+        &self,
+        requests: Vec<CommitLocationToHashRequest>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<(
+        Box<dyn Iterator<Item = CommitLocationToHashResponse>>,
+        DownloadStats,
+    )>;
+
+    /// Find the locations of the given commit hashes relative to
+    /// `master_heads`. Hashes that are not ancestors of `master_heads` are
+    /// missing from the result.
+    fn commit_hash_to_location(
+        &self,
+        master_heads: Vec<HgId>,
+        hgids: Vec<HgId>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<(
+        Box<dyn Iterator<Item = CommitHashToLocationResponse>>,
+        DownloadStats,
+    )>;
+
+    /// Ask the server which of `ids` it already has, so that a push only
+    /// uploads the missing file contents, filenodes, trees and changesets.
+    fn lookup(
+        &self,
+        ids: Vec<AnyId>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<(Box<dyn Iterator<Item = LookupResponse>>, DownloadStats)>;
+
+    /// Upload file contents. Each file is sent as the raw request body of
+    /// its own request and stored under its SHA-256, which the server
+    /// checks. Files are taken from `files` as they are sent, so only those
+    /// currently being uploaded are held in memory.
+    fn upload_files(
+        &self,
+        files: Box<dyn Iterator<Item = (Sha256, Bytes)>>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats>;
+
+    /// Upload Mercurial filenodes whose content has already been uploaded
+    /// with `upload_files`.
+    fn upload_filenodes(
+        &self,
+        filenodes: Vec<UploadHgFilenodeRequest>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats>;
+
+    /// Upload tree manifests, keyed by directory path and manifest node.
+    fn upload_trees(
+        &self,
+        entries: Vec<DataEntry>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats>;
+
+    /// Upload Mercurial changesets in revlog format, parents first. The
+    /// trees and filenodes they refer to must already be on the server.
+    fn upload_changesets(
+        &self,
+        entries: Vec<DataEntry>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats>;
+
+    /// Move a bookmark from `from` to `to`, failing if it does not currently
+    /// point to `from`. `None` stands for a missing bookmark on either side.
+    fn set_bookmark(
+        &self,
+        bookmark: String,
+        from: Option<HgId>,
+        to: Option<HgId>,
+    ) -> ApiResult<SetBookmarkResponse>;
+
+    /// Rebase an uploaded stack of changesets onto a bookmark on the server
+    /// and move the bookmark to the rebased head.
+    fn pushrebase(&self, bookmark: String, changesets: Vec<HgId>) -> ApiResult<PushrebaseResponse>;
 }
 
 // Statically ensure that the EdenApi trait is object safe using
@@ -21,6 +21,7 @@ pub struct Config {
     pub(crate) repo: Option<String>,
     pub(crate) data_batch_size: Option<usize>,
     pub(crate) history_batch_size: Option<usize>,
+    pub(crate) commit_batch_size: Option<usize>,
     pub(crate) validate: bool,
     pub(crate) stream_data: bool,
     pub(crate) stream_history: bool,
@@ -56,6 +57,9 @@ impl Config {
         let history_batch_size = config
             .get_opt("edenapi", "historybatchsize")
             .context(ApiErrorKind::BadConfig("edenapi.historybatchsize".into()))?;
+        let commit_batch_size = config
+            .get_opt("edenapi", "commitbatchsize")
+            .context(ApiErrorKind::BadConfig("edenapi.commitbatchsize".into()))?;
         let validate = config
             .get_or_default("edenapi", "validate")
             .context(ApiErrorKind::BadConfig("edenapi.validate".into()))?;
@@ -75,6 +79,7 @@ impl Config {
             repo,
             data_batch_size,
             history_batch_size,
+            commit_batch_size,
             validate,
             stream_data,
             stream_history,
@@ -128,6 +133,13 @@ impl Config {
         self
     }
 
+    /// Number of commits that should be resolved per commit location or
+    /// commit hash request. Setting this to `None` disables batching.
+    pub fn commit_batch_size(mut self, size: Option<usize>) -> Self {
+        self.commit_batch_size = size;
+        self
+    }
+
     /// Specifies whether the client should attempt to validate the
     /// received data by recomputing and comparing the filenode hash.
     pub fn validate(mut self, validate: bool) -> Self {
@@ -212,6 +224,7 @@ mod tests {
                  url = https://example.com/repo\n\
                  databatchsize = 1234\n\
                  historybatchsize = 5678\n\
+                 commitbatchsize = 90\n\
                  validate = true\n\
                  streamdata = true\n\
                  [auth]\n\
@@ -232,6 +245,7 @@ mod tests {
         assert_eq!(config.creds.as_ref().expect("key missing").key, key);
         assert_eq!(config.data_batch_size, Some(1234));
         assert_eq!(config.history_batch_size, Some(5678));
+        assert_eq!(config.commit_batch_size, Some(90));
         assert_eq!(config.validate, true);
         assert_eq!(config.stream_data, true);
         assert_eq!(config.stream_history, false);
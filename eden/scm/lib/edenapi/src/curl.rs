@@ -20,22 +20,34 @@ use serde::{de::DeserializeOwned, Serialize};
 use serde_cbor::Deserializer;
 use url::Url;
 
+use dag::protocol::CloneData;
 use driver::MultiDriver;
 use handler::Collector;
 use types::{
-    api::{DataRequest, DataResponse, HistoryRequest, HistoryResponse, TreeRequest},
-    DataEntry, HgId, HistoryEntry, Key, RepoPathBuf, Validity, WireHistoryEntry,
+    api::{
+        AnyId, CommitHashToLocationRequestBatch, CommitHashToLocationResponse,
+        CommitLocationToHashRequest, CommitLocationToHashRequestBatch,
+        CommitLocationToHashResponse, DataRequest, DataResponse, HistoryRequest, HistoryResponse,
+        LookupRequestBatch, LookupResponse, PushrebaseRequest, PushrebaseResponse,
+        SetBookmarkRequest, SetBookmarkResponse, TreeRequest, UploadHgChangesetsRequest,
+        UploadHgFilenodeRequest, UploadHgFilenodesRequest, UploadTreesRequest,
+    },
+    DataEntry, HgId, HistoryEntry, Key, RepoPathBuf, Sha256, Validity, WireHistoryEntry,
 };
 
 use crate::api::EdenApi;
 use crate::config::{ClientCreds, Config};
 use crate::errors::{ApiError, ApiErrorContext, ApiErrorKind, ApiResult};
-use crate::progress::{ProgressFn, ProgressReporter};
+use crate::progress::{ProgressFn, ProgressReporter, ProgressUpdater};
 use crate::stats::DownloadStats;
 
 mod driver;
 mod handler;
 
+/// How many uploads are in progress at a time, unless limited by
+/// `edenapi.databatchsize`.
+const DEFAULT_MAX_UPLOADS_IN_FLIGHT: usize = 100;
+
 mod paths {
     pub const HEALTH_CHECK: &str = "/health_check";
     pub const HOSTNAME: &str = "/hostname";
@@ -43,6 +55,16 @@ mod paths {
     pub const HISTORY: &str = "eden/history";
     pub const TREES: &str = "eden/trees";
     pub const PREFETCH_TREES: &str = "eden/trees/prefetch";
+    pub const CLONE_DATA: &str = "commit/clone_data";
+    pub const COMMIT_LOCATION_TO_HASH: &str = "commit/location_to_hash";
+    pub const COMMIT_HASH_TO_LOCATION: &str = "commit/hash_to_location";
+    pub const LOOKUP: &str = "lookup";
+    pub const UPLOAD_FILE_SHA256: &str = "upload/file/sha256/";
+    pub const UPLOAD_FILENODES: &str = "upload/filenodes";
+    pub const UPLOAD_TREES: &str = "upload/trees";
+    pub const UPLOAD_CHANGESETS: &str = "upload/changesets";
+    pub const SET_BOOKMARK: &str = "bookmarks/set";
+    pub const PUSHREBASE: &str = "pushrebase";
 }
 
 /// A thread-safe wrapper around a `curl::Multi` handle.
@@ -85,6 +107,7 @@ pub struct EdenApiCurlClient {
     creds: Option<ClientCreds>,
     data_batch_size: Option<usize>,
     history_batch_size: Option<usize>,
+    commit_batch_size: Option<usize>,
     validate: bool,
     stream_data: bool,
     stream_history: bool,
@@ -111,6 +134,7 @@ impl EdenApiCurlClient {
             creds: config.creds,
             data_batch_size: config.data_batch_size,
             history_batch_size: config.history_batch_size,
+            commit_batch_size: config.commit_batch_size,
             validate: config.validate,
             stream_data: config.stream_data,
             stream_history: config.stream_history,
@@ -319,6 +343,291 @@ impl EdenApi for EdenApiCurlClient {
             .collect::<ApiResult<Vec<(Key, Bytes)>>>()?;
         Ok((Box::new(iter.into_iter()), stats))
     }
+
+    fn clone_data(&self) -> ApiResult<CloneData<HgId>> {
+        let span = tracing::info_span!("api::clone_data");
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::CLONE_DATA)?;
+        let handler = Collector::new(&url);
+        let mut handle = new_easy_handle(self.creds.as_ref(), handler)?;
+        handle.url(url.as_str())?;
+        handle.get(true)?;
+        handle.perform()?;
+
+        let code = handle.response_code()?;
+        let data = handle.get_ref().data();
+
+        if code >= 400 {
+            let msg = String::from_utf8_lossy(data).into_owned();
+            return Err(ApiError::from_http(code, msg));
+        }
+
+        let clone_data: CloneData<HgId> = serde_cbor::from_slice(data)?;
+        log::debug!(
+            "Received {} flat segments and {} commit hashes",
+            clone_data.flat_segments.len(),
+            clone_data.idmap.len()
+        );
+        Ok(clone_data)
+    }
+
+    fn commit_location_to_hash(
+        &self,
+        requests: Vec<CommitLocationToHashRequest>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<(
+        Box<dyn Iterator<Item = CommitLocationToHashResponse>>,
+        DownloadStats,
+    )> {
+        let span = tracing::info_span!("api::commit_location_to_hash", count = requests.len());
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::COMMIT_LOCATION_TO_HASH)?;
+        let batch_size = self
+            .commit_batch_size
+            .unwrap_or_else(|| cmp::max(requests.len(), 1));
+        let chunks = requests.into_iter().chunks(batch_size);
+        let batches = (&chunks)
+            .into_iter()
+            .map(|batch| CommitLocationToHashRequestBatch {
+                requests: batch.collect(),
+            });
+
+        let mut responses = Vec::new();
+        let mut multi = self.multi.lock();
+        let stats = multi_request(
+            &mut multi,
+            &url,
+            self.creds.as_ref(),
+            batches,
+            progress,
+            |response: Vec<CommitLocationToHashResponse>| {
+                responses.extend(response);
+                Ok(())
+            },
+        )?;
+
+        log::debug!("Received {} responses", responses.len());
+        Ok((Box::new(responses.into_iter()), stats))
+    }
+
+    fn commit_hash_to_location(
+        &self,
+        master_heads: Vec<HgId>,
+        hgids: Vec<HgId>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<(
+        Box<dyn Iterator<Item = CommitHashToLocationResponse>>,
+        DownloadStats,
+    )> {
+        let span = tracing::info_span!("api::commit_hash_to_location", count = hgids.len());
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::COMMIT_HASH_TO_LOCATION)?;
+        let batch_size = self
+            .commit_batch_size
+            .unwrap_or_else(|| cmp::max(hgids.len(), 1));
+        let chunks = hgids.into_iter().chunks(batch_size);
+        let batches = (&chunks)
+            .into_iter()
+            .map(|batch| CommitHashToLocationRequestBatch {
+                master_heads: master_heads.clone(),
+                hgids: batch.collect(),
+            });
+
+        let mut responses = Vec::new();
+        let mut multi = self.multi.lock();
+        let stats = multi_request(
+            &mut multi,
+            &url,
+            self.creds.as_ref(),
+            batches,
+            progress,
+            |response: Vec<CommitHashToLocationResponse>| {
+                responses.extend(response);
+                Ok(())
+            },
+        )?;
+
+        log::debug!("Received {} responses", responses.len());
+        Ok((Box::new(responses.into_iter()), stats))
+    }
+
+    fn lookup(
+        &self,
+        ids: Vec<AnyId>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<(Box<dyn Iterator<Item = LookupResponse>>, DownloadStats)> {
+        let span = tracing::info_span!("api::lookup", count = ids.len());
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::LOOKUP)?;
+        let batch_size = self
+            .data_batch_size
+            .unwrap_or_else(|| cmp::max(ids.len(), 1));
+        let chunks = ids.into_iter().chunks(batch_size);
+        let batches = (&chunks).into_iter().map(|batch| LookupRequestBatch {
+            ids: batch.collect(),
+        });
+
+        let mut responses = Vec::new();
+        let mut multi = self.multi.lock();
+        let stats = multi_request(
+            &mut multi,
+            &url,
+            self.creds.as_ref(),
+            batches,
+            progress,
+            |response: Vec<LookupResponse>| {
+                responses.extend(response);
+                Ok(())
+            },
+        )?;
+
+        log::debug!("Received {} responses", responses.len());
+        Ok((Box::new(responses.into_iter()), stats))
+    }
+
+    fn upload_files(
+        &self,
+        files: Box<dyn Iterator<Item = (Sha256, Bytes)>>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        let span = tracing::info_span!("api::upload_files");
+        let _guard = span.enter();
+
+        let base_url = self.repo_base_url()?.join(paths::UPLOAD_FILE_SHA256)?;
+        let max_in_flight = self
+            .data_batch_size
+            .unwrap_or(DEFAULT_MAX_UPLOADS_IN_FLIGHT);
+        let uploads =
+            files.map(|(sha256, content)| Ok((base_url.join(&sha256.to_hex())?, content)));
+
+        let mut multi = self.multi.lock();
+        multi_upload(
+            &mut multi,
+            &base_url,
+            self.creds.as_ref(),
+            uploads,
+            max_in_flight,
+            progress,
+        )
+    }
+
+    fn upload_filenodes(
+        &self,
+        filenodes: Vec<UploadHgFilenodeRequest>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        let span = tracing::info_span!("api::upload_filenodes", count = filenodes.len());
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::UPLOAD_FILENODES)?;
+        let batch_size = self
+            .data_batch_size
+            .unwrap_or_else(|| cmp::max(filenodes.len(), 1));
+        let chunks = filenodes.into_iter().chunks(batch_size);
+        let batches = (&chunks).into_iter().map(|batch| UploadHgFilenodesRequest {
+            filenodes: batch.collect(),
+        });
+
+        let mut multi = self.multi.lock();
+        multi_request(
+            &mut multi,
+            &url,
+            self.creds.as_ref(),
+            batches,
+            progress,
+            |_: Vec<()>| Ok(()),
+        )
+    }
+
+    fn upload_trees(
+        &self,
+        entries: Vec<DataEntry>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        let span = tracing::info_span!("api::upload_trees", count = entries.len());
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::UPLOAD_TREES)?;
+        let batch_size = self
+            .data_batch_size
+            .unwrap_or_else(|| cmp::max(entries.len(), 1));
+        let chunks = entries.into_iter().chunks(batch_size);
+        let batches = (&chunks).into_iter().map(|batch| UploadTreesRequest {
+            entries: batch.collect(),
+        });
+
+        let mut multi = self.multi.lock();
+        multi_request(
+            &mut multi,
+            &url,
+            self.creds.as_ref(),
+            batches,
+            progress,
+            |_: Vec<()>| Ok(()),
+        )
+    }
+
+    fn upload_changesets(
+        &self,
+        entries: Vec<DataEntry>,
+        progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        let span = tracing::info_span!("api::upload_changesets", count = entries.len());
+        let _guard = span.enter();
+
+        // Changesets are sent in a single request, as the server needs a
+        // changeset's parents before it can store the changeset itself.
+        let url = self.repo_base_url()?.join(paths::UPLOAD_CHANGESETS)?;
+        let requests = vec![UploadHgChangesetsRequest { entries }];
+
+        let mut multi = self.multi.lock();
+        multi_request(
+            &mut multi,
+            &url,
+            self.creds.as_ref(),
+            requests,
+            progress,
+            |_: Vec<()>| Ok(()),
+        )
+    }
+
+    fn set_bookmark(
+        &self,
+        bookmark: String,
+        from: Option<HgId>,
+        to: Option<HgId>,
+    ) -> ApiResult<SetBookmarkResponse> {
+        let span = tracing::info_span!("api::set_bookmark");
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::SET_BOOKMARK)?;
+        let request = SetBookmarkRequest { bookmark, from, to };
+        self.single_request(&url, &request)?
+            .pop()
+            .ok_or_else(|| format_err!("Empty set_bookmark response"))
+            .context(ApiErrorKind::BadResponse)
+            .map_err(ApiError::from)
+    }
+
+    fn pushrebase(&self, bookmark: String, changesets: Vec<HgId>) -> ApiResult<PushrebaseResponse> {
+        let span = tracing::info_span!("api::pushrebase", count = changesets.len());
+        let _guard = span.enter();
+
+        let url = self.repo_base_url()?.join(paths::PUSHREBASE)?;
+        let request = PushrebaseRequest {
+            bookmark,
+            changesets,
+        };
+        self.single_request(&url, &request)?
+            .pop()
+            .ok_or_else(|| format_err!("Empty pushrebase response"))
+            .context(ApiErrorKind::BadResponse)
+            .map_err(ApiError::from)
+    }
 }
 
 // Private methods.
@@ -327,6 +636,31 @@ impl EdenApiCurlClient {
         Ok(self.base_url.join(&format!("{}/", &self.repo))?)
     }
 
+    /// Send a single CBOR POST request outside of the multi handle, for
+    /// requests that change the repo and should not be batched.
+    fn single_request<R: Serialize, T: DeserializeOwned>(
+        &self,
+        url: &Url,
+        request: &R,
+    ) -> ApiResult<Vec<T>> {
+        let handler = Collector::new(url);
+        let mut handle = new_easy_handle(self.creds.as_ref(), handler)?;
+        prepare_cbor_post(&mut handle, url, request)?;
+        handle.perform()?;
+
+        let code = handle.response_code()?;
+        let data = handle.get_ref().data();
+
+        if code >= 400 {
+            let msg = String::from_utf8_lossy(data).into_owned();
+            return Err(ApiError::from_http(code, msg));
+        }
+
+        Ok(Deserializer::from_slice(data)
+            .into_iter()
+            .collect::<Result<Vec<T>, serde_cbor::error::Error>>()?)
+    }
+
     fn get_data(
         &self,
         path: &str,
@@ -418,7 +752,7 @@ fn multi_request<'a, R, I, T, F>(
     creds: Option<&ClientCreds>,
     requests: I,
     progress_cb: Option<ProgressFn>,
-    mut response_cb: F,
+    response_cb: F,
 ) -> ApiResult<DownloadStats>
 where
     R: Serialize,
@@ -429,7 +763,7 @@ where
     let requests = requests.into_iter().collect::<Vec<_>>();
     let num_requests = requests.len();
 
-    let mut progress = ProgressReporter::with_capacity(num_requests);
+    let progress = ProgressReporter::with_capacity(num_requests);
     let mut driver = MultiDriver::with_capacity(multi, num_requests);
     driver.fail_early(true);
 
@@ -441,6 +775,75 @@ where
         driver.add(easy)?;
     }
 
+    perform_multi(driver, url, 0, |_| None, progress, progress_cb, response_cb)
+}
+
+/// Upload each of `uploads` as the raw body of a PUT request to the
+/// paired URL, with up to `max_in_flight` requests in progress at a time.
+/// Uploads are only taken from the iterator when they are about to be sent,
+/// so their content doesn't need to be held in memory all at once. `url` is
+/// only used for logging.
+fn multi_upload<I>(
+    multi: &mut Multi,
+    url: &Url,
+    creds: Option<&ClientCreds>,
+    mut uploads: I,
+    max_in_flight: usize,
+    progress_cb: Option<ProgressFn>,
+) -> ApiResult<DownloadStats>
+where
+    I: Iterator<Item = ApiResult<(Url, Bytes)>>,
+{
+    let progress = ProgressReporter::with_capacity(max_in_flight);
+    let mut driver = MultiDriver::with_capacity(multi, max_in_flight);
+    driver.fail_early(true);
+
+    let queue = |updater: Option<ProgressUpdater>| {
+        let (upload_url, content) = match uploads.next()? {
+            Ok(upload) => upload,
+            Err(e) => return Some(Err(e)),
+        };
+        let handler = match updater {
+            Some(updater) => Collector::with_progress(&upload_url, updater),
+            None => Collector::new(&upload_url),
+        };
+        let size = content.len();
+        let mut easy = match new_easy_handle(creds, handler.with_body(content)) {
+            Ok(easy) => easy,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(prepare_raw_put(&mut easy, &upload_url, size).map(|()| easy))
+    };
+
+    perform_multi(
+        driver,
+        url,
+        max_in_flight,
+        queue,
+        progress,
+        progress_cb,
+        |_: Vec<()>| Ok(()),
+    )
+}
+
+/// Run the requests added to `driver`, and those created by `queue` with
+/// up to `max_in_flight` of them in progress (see
+/// `MultiDriver::perform_queued`), passing each deserialized response to
+/// `response_cb`, and collect the transfer stats.
+fn perform_multi<Q, T, F>(
+    mut driver: MultiDriver<'_, Collector>,
+    url: &Url,
+    max_in_flight: usize,
+    queue: Q,
+    mut progress: ProgressReporter,
+    progress_cb: Option<ProgressFn>,
+    mut response_cb: F,
+) -> ApiResult<DownloadStats>
+where
+    Q: FnMut(Option<ProgressUpdater>) -> Option<ApiResult<Easy2<Collector>>>,
+    T: DeserializeOwned,
+    F: FnMut(Vec<T>) -> ApiResult<()>,
+{
     progress.set_callback(progress_cb);
     driver.set_progress_reporter(progress);
 
@@ -449,15 +852,19 @@ where
         url = &AsRef::<str>::as_ref(&url.to_string()),
         downloaded = "",
         uploaded = "",
-        requests = num_requests,
+        requests = "",
         latency = "",
     );
     let _guard = span.enter();
 
-    log::debug!("Performing {} requests", num_requests);
+    if max_in_flight == 0 {
+        log::debug!("Performing {} requests", driver.num_transfers());
+    } else {
+        log::debug!("Performing requests, up to {} at a time", max_in_flight);
+    }
     let start = Instant::now();
 
-    driver.perform(|res| {
+    driver.perform_queued(max_in_flight, queue, |res| {
         let mut easy = res?;
         let code = easy.response_code()?;
         let data = easy.get_ref().data();
@@ -474,6 +881,7 @@ where
     })?;
 
     let elapsed = start.elapsed();
+    let num_requests = driver.num_transfers();
     let progress = driver.progress().unwrap();
     let progstats = progress.stats();
     let latency = progress
@@ -494,6 +902,7 @@ where
     if !span.is_disabled() {
         span.record("downloaded", &dlstats.downloaded);
         span.record("uploaded", &dlstats.uploaded);
+        span.record("requests", &dlstats.requests);
         span.record("latency_ms", &(dlstats.latency.as_millis() as u64));
     }
 
@@ -582,6 +991,20 @@ fn prepare_cbor_post<H, R: Serialize>(
     Ok(())
 }
 
+/// Configure the given Easy2 handle to perform a PUT request whose raw
+/// request body of `size` bytes is read from the handler.
+fn prepare_raw_put<H>(easy: &mut Easy2<H>, url: &Url, size: usize) -> ApiResult<()> {
+    easy.url(url.as_str())?;
+    easy.upload(true)?;
+    easy.in_filesize(size as u64)?;
+
+    let mut headers = List::new();
+    headers.append("Content-Type: application/octet-stream")?;
+    easy.http_headers(headers)?;
+
+    Ok(())
+}
+
 /// Check the integrity of the data in this entry and either return
 /// the data or an integrity check failure depending on the validation
 /// result and the user's configuration.
@@ -612,3 +1035,137 @@ fn check_data(entry: &DataEntry, validate: bool) -> Result<Bytes> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A request received by `TestServer`, with the number of files the
+    /// client had taken from its iterator when the request arrived.
+    struct Received {
+        path: String,
+        body: Vec<u8>,
+        pulled: usize,
+    }
+
+    /// A minimal HTTP/1.1 server that records the requests it receives and
+    /// answers each of them with an empty 200 response.
+    struct TestServer {
+        url: Url,
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl TestServer {
+        fn start(pulled: Arc<AtomicUsize>) -> Result<Self> {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let url = Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+            let received = Arc::new(Mutex::new(Vec::new()));
+            {
+                let received = received.clone();
+                thread::spawn(move || {
+                    for stream in listener.incoming() {
+                        let received = received.clone();
+                        let pulled = pulled.clone();
+                        thread::spawn(move || serve(stream?, &received, &pulled));
+                    }
+                    Ok::<_, std::io::Error>(())
+                });
+            }
+            Ok(Self { url, received })
+        }
+    }
+
+    fn serve(
+        stream: TcpStream,
+        received: &Mutex<Vec<Received>>,
+        pulled: &AtomicUsize,
+    ) -> std::io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line)? == 0 {
+                return Ok(());
+            }
+            let path = request_line
+                .split_whitespace()
+                .nth(1)
+                .unwrap_or_default()
+                .to_string();
+
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header)?;
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                let lower = header.to_ascii_lowercase();
+                if let Some(len) = lower.strip_prefix("content-length:") {
+                    content_length = len.trim().parse().unwrap_or(0);
+                }
+                if lower == "expect: 100-continue" {
+                    writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                }
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            received.lock().push(Received {
+                path,
+                body,
+                pulled: pulled.load(Ordering::SeqCst),
+            });
+            writer.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")?;
+        }
+    }
+
+    #[test]
+    fn test_upload_files_streams_from_iterator() -> Result<()> {
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let server = TestServer::start(pulled.clone())?;
+        let max_in_flight = 2;
+        let client = EdenApiCurlClient::new(
+            Config::new()
+                .base_url(server.url.clone())
+                .repo("repo")
+                .data_batch_size(Some(max_in_flight)),
+        )?;
+
+        let files: Vec<(Sha256, Bytes)> = (0..10u8)
+            .map(|i| (Sha256::from([i; 32]), Bytes::from(vec![i; 100])))
+            .collect();
+        let files_iter = {
+            let pulled = pulled.clone();
+            files.clone().into_iter().inspect(move |_| {
+                pulled.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+
+        let stats = client.upload_files(Box::new(files_iter), None)?;
+        assert_eq!(stats.requests, files.len());
+
+        let received = server.received.lock();
+        assert_eq!(received.len(), files.len());
+        for (i, request) in received.iter().enumerate() {
+            // Files are only taken from the iterator when there is room for
+            // another upload.
+            assert!(request.pulled <= i + max_in_flight);
+        }
+        for (sha256, content) in &files {
+            let path = format!("/repo/upload/file/sha256/{}", sha256.to_hex());
+            let request = received
+                .iter()
+                .find(|request| request.path == path)
+                .expect("file was not uploaded");
+            assert_eq!(request.body, content.to_vec());
+        }
+
+        Ok(())
+    }
+}
@@ -14,7 +14,7 @@ use curl::{
 };
 
 use crate::errors::ApiResult;
-use crate::progress::ProgressReporter;
+use crate::progress::{ProgressReporter, ProgressUpdater};
 
 /// Timeout for a single iteration of waiting for activity
 /// on any active transfer in a curl::Multi session.
@@ -49,6 +49,11 @@ impl<'a, H> MultiDriver<'a, H> {
         self.progress.as_ref()
     }
 
+    /// The number of transfers added to the Multi stack so far.
+    pub fn num_transfers(&self) -> usize {
+        self.num_transfers
+    }
+
     /// Add an Easy2 handle to the Multi stack.
     pub fn add(&mut self, easy: Easy2<H>) -> ApiResult<()> {
         // Assign a token to this Easy2 handle so we can correlate messages
@@ -75,14 +80,43 @@ impl<'a, H> MultiDriver<'a, H> {
     ///
     /// The caller-supplied callback will be called whenever a transfer
     /// completes, successfully or otherwise.
-    pub fn perform<F>(&mut self, mut callback: F) -> ApiResult<()>
+    pub fn perform<F>(&mut self, callback: F) -> ApiResult<()>
+    where
+        F: FnMut(Result<Easy2<H>, curl::Error>) -> ApiResult<()>,
+    {
+        self.perform_queued(0, |_| None, callback)
+    }
+
+    /// Like `perform`, but also add the transfers created by `queue` as
+    /// earlier ones complete, so that at most `max_in_flight` transfers are
+    /// in progress at a time. `queue` is called with the progress updater for
+    /// the transfer it creates, and returns `None` once it has no more.
+    pub fn perform_queued<Q, F>(
+        &mut self,
+        max_in_flight: usize,
+        mut queue: Q,
+        mut callback: F,
+    ) -> ApiResult<()>
     where
+        Q: FnMut(Option<ProgressUpdater>) -> Option<ApiResult<Easy2<H>>>,
         F: FnMut(Result<Easy2<H>, curl::Error>) -> ApiResult<()>,
     {
         let mut in_progress = self.num_transfers;
+        let mut queue_done = max_in_flight == 0;
         let mut i = 0;
 
         loop {
+            while !queue_done && in_progress < max_in_flight {
+                let updater = self.progress.as_ref().map(ProgressReporter::new_updater);
+                match queue(updater) {
+                    Some(easy) => {
+                        self.add(easy?)?;
+                        in_progress += 1;
+                    }
+                    None => queue_done = true,
+                }
+            }
+
             log::trace!(
                 "Iteration {}: {}/{} transfers complete",
                 i,
@@ -144,10 +178,13 @@ impl<'a, H> MultiDriver<'a, H> {
                 }
             }
 
-            if in_progress == 0 {
+            if in_progress == 0 && queue_done {
                 log::debug!("All transfers finished successfully.");
                 break;
             }
+            if in_progress < max_in_flight && !queue_done {
+                continue;
+            }
 
             let timeout = self.multi.get_timeout()?.unwrap_or(DEFAULT_TIMEOUT);
             log::trace!("Waiting for I/O with timeout: {:?}", &timeout);
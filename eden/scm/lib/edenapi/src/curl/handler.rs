@@ -9,7 +9,8 @@ use blackbox::{
     event::{Event, NetworkOp},
     log,
 };
-use curl::easy::{Handler, WriteError};
+use bytes::{Buf, Bytes};
+use curl::easy::{Handler, ReadError, WriteError};
 use lazy_static::lazy_static;
 use regex::Regex;
 use url::Url;
@@ -39,9 +40,11 @@ impl From<&Url> for DraftEvent {
     }
 }
 
-/// Simple Handler that just writes all received data to an internal buffer.
+/// Simple Handler that just writes all received data to an internal buffer,
+/// and sends the request body it was given, if any.
 pub(super) struct Collector {
     data: Vec<u8>,
+    body: Bytes,
     updater: Option<ProgressUpdater>,
     event: DraftEvent,
 }
@@ -50,6 +53,7 @@ impl Collector {
     pub fn new(event: impl Into<DraftEvent>) -> Self {
         Self {
             data: Vec::new(),
+            body: Bytes::new(),
             updater: None,
             event: event.into(),
         }
@@ -61,6 +65,13 @@ impl Collector {
         ret
     }
 
+    /// Send `body` as the request body. The content is read from `body` as
+    /// it is uploaded instead of being copied into the request.
+    pub fn with_body(mut self, body: Bytes) -> Self {
+        self.body = body;
+        self
+    }
+
     pub fn data(&self) -> &[u8] {
         &self.data
     }
@@ -72,6 +83,13 @@ impl Handler for Collector {
         Ok(data.len())
     }
 
+    fn read(&mut self, data: &mut [u8]) -> Result<usize, ReadError> {
+        let len = data.len().min(self.body.len());
+        data[..len].copy_from_slice(&self.body[..len]);
+        self.body.advance(len);
+        Ok(len)
+    }
+
     fn header(&mut self, data: &[u8]) -> bool {
         let line = String::from_utf8_lossy(data);
 
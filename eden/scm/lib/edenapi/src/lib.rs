@@ -18,3 +18,5 @@ pub use crate::curl::EdenApiCurlClient;
 pub use crate::errors::{ApiError, ApiErrorKind, ApiResult};
 pub use crate::progress::{ProgressFn, ProgressStats};
 pub use crate::stats::DownloadStats;
+
+pub use dag::protocol::CloneData;
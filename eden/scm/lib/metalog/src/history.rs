@@ -0,0 +1,219 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::metalog::{load_root, Root};
+use crate::{CommitOptions, Id20, MetaLog, Result};
+use std::collections::BTreeMap;
+
+/// How a key changed between two roots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A root in the history, with the keys changed compared to the root
+/// committed before it.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub root_id: Id20,
+    pub message: String,
+    pub timestamp: u64,
+    pub changes: BTreeMap<String, KeyChange>,
+}
+
+/// Iterator over the roots committed to a [`MetaLog`], newest first.
+/// Created by [`MetaLog::history`].
+pub struct History<'a> {
+    metalog: &'a MetaLog,
+    /// Root ids, oldest first. The first one is the empty root.
+    root_ids: Vec<Id20>,
+    /// Number of roots not yet visited.
+    remaining: usize,
+}
+
+impl MetaLog {
+    /// Calculate keys changed from `root_a` to `root_b`.
+    pub fn diff(&self, root_a: Id20, root_b: Id20) -> Result<BTreeMap<String, KeyChange>> {
+        let root_a = load_root(&self.blobs, root_a)?;
+        let root_b = load_root(&self.blobs, root_b)?;
+        Ok(diff_roots(&root_a, &root_b))
+    }
+
+    /// Iterate through roots committed to the roots log, newest first.
+    ///
+    /// Each entry describes the keys changed compared to the previous root in
+    /// the log. The initial empty root is not included.
+    pub fn history(&self) -> Result<History<'_>> {
+        let root_ids = Self::list_roots(&self.path)?;
+        let remaining = root_ids.len() - 1;
+        Ok(History {
+            metalog: self,
+            root_ids,
+            remaining,
+        })
+    }
+
+    /// Restore the keys and values of a previous root, and commit them as a
+    /// new root. This undoes changes committed after `root_id` without
+    /// rewriting history.
+    ///
+    /// Fails if there are uncommitted changes. Returns the new root id, or
+    /// the current one if `root_id` has the same content.
+    pub fn checkout(&mut self, root_id: Id20, options: CommitOptions) -> Result<Id20> {
+        if self.is_dirty() {
+            return Err(self.error("cannot checkout with uncommitted changes"));
+        }
+        let root = load_root(&self.blobs, root_id)?;
+        self.root.map = root.map;
+        self.commit(options)
+    }
+}
+
+impl<'a> Iterator for History<'a> {
+    type Item = Result<HistoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let index = self.remaining;
+        self.remaining -= 1;
+        let root_id = self.root_ids[index];
+        let entry = (|| -> Result<HistoryEntry> {
+            let root = load_root(&self.metalog.blobs, root_id)?;
+            let parent = load_root(&self.metalog.blobs, self.root_ids[index - 1])?;
+            Ok(HistoryEntry {
+                root_id,
+                changes: diff_roots(&parent, &root),
+                message: root.message,
+                timestamp: root.timestamp,
+            })
+        })();
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+fn diff_roots(old: &Root, new: &Root) -> BTreeMap<String, KeyChange> {
+    let mut changes = BTreeMap::new();
+    for (key, old_id) in old.map.iter() {
+        match new.map.get(key) {
+            None => {
+                changes.insert(key.clone(), KeyChange::Removed);
+            }
+            Some(new_id) if new_id != old_id => {
+                changes.insert(key.clone(), KeyChange::Modified);
+            }
+            Some(_) => {}
+        }
+    }
+    for key in new.map.keys() {
+        if !old.map.contains_key(key) {
+            changes.insert(key.clone(), KeyChange::Added);
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit_opt(message: &str, timestamp: u64) -> CommitOptions<'_> {
+        let mut opts = CommitOptions::default();
+        opts.message = message;
+        opts.timestamp = timestamp;
+        opts
+    }
+
+    fn describe(changes: &BTreeMap<String, KeyChange>) -> String {
+        changes
+            .iter()
+            .map(|(key, change)| format!("{}: {:?}", key, change))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    #[test]
+    fn test_diff_and_history() {
+        let dir = TempDir::new().unwrap();
+        let mut metalog = MetaLog::open(&dir, None).unwrap();
+        metalog.set("a", b"1").unwrap();
+        metalog.set("b", b"1").unwrap();
+        let root1 = metalog.commit(commit_opt("commit 1", 1)).unwrap();
+        metalog.set("a", b"2").unwrap();
+        metalog.remove("b").unwrap();
+        metalog.set("c", b"2").unwrap();
+        let root2 = metalog.commit(commit_opt("commit 2", 2)).unwrap();
+
+        assert_eq!(
+            describe(&metalog.diff(root1, root2).unwrap()),
+            "a: Modified, b: Removed, c: Added"
+        );
+        assert_eq!(
+            describe(&metalog.diff(root2, root1).unwrap()),
+            "a: Modified, b: Added, c: Removed"
+        );
+        assert!(metalog.diff(root2, root2).unwrap().is_empty());
+
+        let history: Vec<HistoryEntry> = metalog.history().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|e| format!("{} {}: {}", e.timestamp, e.message, describe(&e.changes)))
+                .collect::<Vec<_>>(),
+            [
+                "2 commit 2: a: Modified, b: Removed, c: Added",
+                "1 commit 1: a: Added, b: Added",
+            ]
+        );
+        assert_eq!(history[0].root_id, root2);
+        assert_eq!(history[1].root_id, root1);
+    }
+
+    #[test]
+    fn test_checkout() {
+        let dir = TempDir::new().unwrap();
+        let mut metalog = MetaLog::open(&dir, None).unwrap();
+        metalog.set("a", b"1").unwrap();
+        let root1 = metalog.commit(commit_opt("commit 1", 1)).unwrap();
+        metalog.set("a", b"2").unwrap();
+        metalog.set("b", b"2").unwrap();
+        metalog.commit(commit_opt("commit 2", 2)).unwrap();
+
+        metalog.set("c", b"3").unwrap();
+        assert!(metalog
+            .checkout(root1, commit_opt("undo", 3))
+            .unwrap_err()
+            .to_string()
+            .ends_with("cannot checkout with uncommitted changes"));
+        metalog.remove("c").unwrap();
+
+        let root3 = metalog.checkout(root1, commit_opt("undo", 3)).unwrap();
+        assert_ne!(root3, root1);
+        assert_eq!(metalog.get("a").unwrap().unwrap(), b"1");
+        assert_eq!(metalog.get("b").unwrap(), None);
+
+        // The undo is a new root. The old ones are still there.
+        let metalog = MetaLog::open(&dir, None).unwrap();
+        assert_eq!(metalog.root_id(), root3);
+        assert_eq!(metalog.message(), "undo");
+        assert!(metalog.diff(root1, root3).unwrap().is_empty());
+        assert_eq!(MetaLog::list_roots(&dir).unwrap().len(), 4);
+
+        let history = metalog.history().unwrap();
+        assert_eq!(history.size_hint(), (3, Some(3)));
+        let messages: Vec<String> = history.map(|e| e.unwrap().message).collect();
+        assert_eq!(messages, ["undo", "commit 2", "commit 1"]);
+    }
+}
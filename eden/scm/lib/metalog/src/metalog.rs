@@ -52,7 +52,7 @@ pub struct MetaLog {
     pub(crate) orig_root_id: Id20,
 
     /// The current (possibly modified) root.
-    root: Root,
+    pub(crate) root: Root,
 }
 
 /// Options used by the `commit` API.
@@ -74,6 +74,14 @@ pub struct CommitOptions<'a> {
     /// return `Ok(())` if it is able to resolve everything cleanly.
     pub resolver: Option<Box<dyn FnMut(&mut MetaLog, &MetaLog, &MetaLog) -> Result<()>>>,
 
+    /// How to merge values of individual keys, if `resolver` is not set.
+    ///
+    /// If this is not empty, keys changed by only one side are merged
+    /// automatically, and keys changed by both sides are merged by the
+    /// resolver registered for them. See [`resolver::merge_keys`].
+    /// Otherwise, any conflict fails the commit.
+    pub key_resolvers: BTreeMap<String, resolver::KeyResolver>,
+
     /// Prevent constructing via fields.
     _private: (),
 }
@@ -179,8 +187,17 @@ impl MetaLog {
             // since the final root object is not committed yet.
             let ancestor = Self::open(&self.path, Some(self.orig_root_id))?;
             let other = Self::open(&self.path, None)?;
-            let mut resolver = options.resolver.unwrap_or(Box::new(resolver::fail));
-            (resolver)(self, &other, &ancestor)?;
+            match options.resolver {
+                Some(mut resolver) => (resolver)(self, &other, &ancestor)?,
+                None if !options.key_resolvers.is_empty() => resolver::merge_keys(
+                    self,
+                    &other,
+                    &ancestor,
+                    &options.key_resolvers,
+                    options.timestamp,
+                )?,
+                None => resolver::fail(self, &other, &ancestor)?,
+            }
         }
         self.root.message = options.message.to_string();
         self.root.timestamp = options.timestamp;
@@ -362,8 +379,204 @@ pub(crate) struct Root {
 /// Predefined conflict resolutions.
 pub mod resolver {
     use super::MetaLog;
-    use crate::Result;
-    use std::collections::BTreeSet;
+    use crate::{Error, Result};
+    use minibytes::Bytes;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// Merges the values of one key. Returns the merged value, or `None` to
+    /// remove the key.
+    pub type KeyResolver = Box<dyn Fn(&MergeInput) -> Result<Option<Vec<u8>>>>;
+
+    /// Values of a key changed by both sides of a merge.
+    ///
+    /// `this` is the side being committed, `other` is the latest committed
+    /// change, and `ancestor` is what both sides started from. A `None` value
+    /// means the key does not exist on that side.
+    pub struct MergeInput<'a> {
+        pub key: &'a str,
+        pub this: Option<Bytes>,
+        pub other: Option<Bytes>,
+        pub ancestor: Option<Bytes>,
+        pub this_timestamp: u64,
+        pub other_timestamp: u64,
+    }
+
+    /// Merge key by key.
+    ///
+    /// Changes made by only one side are taken as-is. Keys changed by both
+    /// sides to different values are merged by the resolver registered for
+    /// them in `key_resolvers`. The merge fails if there is no resolver for
+    /// such a key.
+    ///
+    /// `timestamp` is the timestamp of the commit being made by `this`.
+    pub fn merge_keys(
+        this: &mut MetaLog,
+        other: &MetaLog,
+        ancestor: &MetaLog,
+        key_resolvers: &BTreeMap<String, KeyResolver>,
+        timestamp: u64,
+    ) -> Result<()> {
+        let keys: BTreeSet<String> = this
+            .keys()
+            .into_iter()
+            .chain(other.keys())
+            .chain(ancestor.keys())
+            .map(ToString::to_string)
+            .collect();
+        let mut conflicts = Vec::new();
+        for key in keys {
+            let ancestor_id = ancestor.root.map.get(&key).cloned();
+            let other_id = other.root.map.get(&key).cloned();
+            let this_id = this.root.map.get(&key).cloned();
+            if other_id == ancestor_id || other_id == this_id {
+                // Nothing to take from other.
+                continue;
+            }
+            if this_id == ancestor_id {
+                // Only other changed.
+                match other_id {
+                    Some(id) => this.root.map.insert(key, id),
+                    None => this.root.map.remove(&key),
+                };
+                continue;
+            }
+            match key_resolvers.get(&key) {
+                Some(resolve) => {
+                    let input = MergeInput {
+                        key: &key,
+                        this: this.get(&key)?,
+                        other: other.get(&key)?,
+                        ancestor: ancestor.get(&key)?,
+                        this_timestamp: timestamp,
+                        other_timestamp: other.timestamp(),
+                    };
+                    match resolve(&input)? {
+                        Some(value) => {
+                            this.set(&key, &value)?;
+                        }
+                        None => this.remove(&key)?,
+                    }
+                }
+                None => conflicts.push(format!("  {}: both changed, diverged", key)),
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(this.error(format!("conflict detected:\n{}", conflicts.join("\n"))))
+        }
+    }
+
+    /// Merge maps stored as "value name" lines, like bookmarks
+    /// ("hex_node name") or remotenames ("hex_node type name").
+    ///
+    /// Names changed by only one side are taken as-is. Fails if a name is
+    /// changed by both sides to different values.
+    pub fn bookmarks(input: &MergeInput) -> Result<Option<Vec<u8>>> {
+        fn parse(value: &Option<Bytes>) -> BTreeMap<&[u8], &[u8]> {
+            let value = value.as_ref().map(|v| v.as_ref()).unwrap_or_default();
+            value
+                .split(|&b| b == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| match line.iter().position(|&b| b == b' ') {
+                    Some(pos) => (&line[pos + 1..], &line[..pos]),
+                    None => (line, &line[line.len()..]),
+                })
+                .collect()
+        }
+
+        let this = parse(&input.this);
+        let other = parse(&input.other);
+        let ancestor = parse(&input.ancestor);
+        let names: BTreeSet<&[u8]> = this
+            .keys()
+            .chain(other.keys())
+            .chain(ancestor.keys())
+            .cloned()
+            .collect();
+
+        let mut merged = Vec::new();
+        let mut conflicts = Vec::new();
+        for name in names {
+            let this_value = this.get(name);
+            let other_value = other.get(name);
+            let ancestor_value = ancestor.get(name);
+            let value = if other_value == ancestor_value || other_value == this_value {
+                this_value
+            } else if this_value == ancestor_value {
+                other_value
+            } else {
+                conflicts.push(String::from_utf8_lossy(name).to_string());
+                continue;
+            };
+            match value {
+                Some(value) if !value.is_empty() => {
+                    merged.extend_from_slice(value);
+                    merged.push(b' ');
+                    merged.extend_from_slice(name);
+                    merged.push(b'\n');
+                }
+                // Preserve lines without a value as-is.
+                Some(_) => {
+                    merged.extend_from_slice(name);
+                    merged.push(b'\n');
+                }
+                None => {}
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(Some(merged))
+        } else {
+            Err(Error(format!(
+                "conflict detected in {}: {} changed by both sides",
+                input.key,
+                conflicts.join(", ")
+            )))
+        }
+    }
+
+    /// Merge sets stored as lines, like visibleheads. Lines added by either
+    /// side are kept. Lines removed by either side are removed.
+    pub fn line_set(input: &MergeInput) -> Result<Option<Vec<u8>>> {
+        fn parse(value: &Option<Bytes>) -> Vec<&[u8]> {
+            let value = value.as_ref().map(|v| v.as_ref()).unwrap_or_default();
+            value
+                .split(|&b| b == b'\n')
+                .filter(|line| !line.is_empty())
+                .collect()
+        }
+
+        let this = parse(&input.this);
+        let other = parse(&input.other);
+        let ancestor: BTreeSet<&[u8]> = parse(&input.ancestor).into_iter().collect();
+        let this_set: BTreeSet<&[u8]> = this.iter().cloned().collect();
+        let other_set: BTreeSet<&[u8]> = other.iter().cloned().collect();
+
+        // Keep the order of lines in this, followed by lines added by other.
+        let mut seen = BTreeSet::new();
+        let mut merged = Vec::new();
+        for line in this.into_iter().chain(other) {
+            let removed =
+                ancestor.contains(line) && (!this_set.contains(line) || !other_set.contains(line));
+            if !removed && seen.insert(line) {
+                merged.extend_from_slice(line);
+                merged.push(b'\n');
+            }
+        }
+        Ok(Some(merged))
+    }
+
+    /// Take the value of the side that committed last. Prefer this side if
+    /// both sides have the same timestamp.
+    pub fn last_writer_wins(input: &MergeInput) -> Result<Option<Vec<u8>>> {
+        let value = if input.other_timestamp > input.this_timestamp {
+            &input.other
+        } else {
+            &input.this
+        };
+        Ok(value.as_ref().map(|v| v.to_vec()))
+    }
 
     /// Fail the merge unconditionally on any kind of conflicts.
     pub fn fail(this: &mut MetaLog, other: &MetaLog, ancestor: &MetaLog) -> Result<()> {
@@ -515,6 +728,115 @@ mod tests {
         assert_eq!(metalog3.get("c").unwrap().unwrap(), b"c");
     }
 
+    #[test]
+    fn test_merge_keys() {
+        let dir = TempDir::new().unwrap();
+        let mut metalog = MetaLog::open(&dir, None).unwrap();
+        metalog.set("a", b"0").unwrap();
+        metalog.set("b", b"0").unwrap();
+        metalog.set("c", b"0").unwrap();
+        metalog.set("d", b"0").unwrap();
+        metalog.commit(commit_opt("commit 0", 0)).unwrap();
+
+        let mut metalog1 = MetaLog::open(&dir, None).unwrap();
+        let mut metalog2 = MetaLog::open(&dir, None).unwrap();
+        metalog1.set("a", b"1").unwrap();
+        metalog1.remove("b").unwrap();
+        metalog1.set("d", b"1").unwrap();
+        metalog1.set("e", b"1").unwrap();
+        metalog2.set("c", b"2").unwrap();
+        metalog2.set("d", b"2").unwrap();
+        metalog1.commit(commit_opt("commit 1", 1)).unwrap();
+
+        // "d" is changed by both sides. Without a resolver for it, this is a conflict.
+        let mut opts = commit_opt("commit 2", 2);
+        opts.key_resolvers
+            .insert("a".to_string(), Box::new(resolver::last_writer_wins));
+        let err = metalog2
+            .commit(opts)
+            .unwrap_err()
+            .to_string()
+            .replace(&format!("{:?}", dir.path()), "<path>");
+        assert_eq!(
+            err,
+            "<path>: conflict detected:\n  d: both changed, diverged"
+        );
+
+        let mut opts = commit_opt("commit 2", 2);
+        opts.key_resolvers
+            .insert("d".to_string(), Box::new(resolver::last_writer_wins));
+        metalog2.commit(opts).unwrap();
+
+        let metalog3 = MetaLog::open(&dir, None).unwrap();
+        assert_eq!(metalog3.message(), "commit 2");
+        assert_eq!(metalog3.get("a").unwrap().unwrap(), b"1");
+        assert_eq!(metalog3.get("b").unwrap(), None);
+        assert_eq!(metalog3.get("c").unwrap().unwrap(), b"2");
+        assert_eq!(metalog3.get("d").unwrap().unwrap(), b"2");
+        assert_eq!(metalog3.get("e").unwrap().unwrap(), b"1");
+    }
+
+    fn merge_input<'a>(
+        this: &[u8],
+        other: &[u8],
+        ancestor: &[u8],
+        this_timestamp: u64,
+        other_timestamp: u64,
+    ) -> resolver::MergeInput<'a> {
+        resolver::MergeInput {
+            key: "key",
+            this: Some(Bytes::from(this.to_vec())),
+            other: Some(Bytes::from(other.to_vec())),
+            ancestor: Some(Bytes::from(ancestor.to_vec())),
+            this_timestamp,
+            other_timestamp,
+        }
+    }
+
+    #[test]
+    fn test_bookmarks_resolver() {
+        let ancestor = b"00 a\n00 b\n00 c\n00 remote/d\n";
+        let this = b"00 a\n11 b\n00 remote/d\n11 e\n";
+        let other = b"22 a\n11 b\n00 c\n22 f\n";
+        let merged = resolver::bookmarks(&merge_input(this, other, ancestor, 1, 2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(merged, b"22 a\n11 b\n11 e\n22 f\n");
+
+        let other = b"22 a\n22 b\n22 e\n";
+        let err = resolver::bookmarks(&merge_input(this, other, ancestor, 1, 2)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "conflict detected in key: b, e changed by both sides"
+        );
+    }
+
+    #[test]
+    fn test_line_set_resolver() {
+        let ancestor = b"v1\na\nb\nc\n";
+        let this = b"v1\na\nc\nd\n";
+        let other = b"v1\nb\nc\ne\nd\n";
+        let merged = resolver::line_set(&merge_input(this, other, ancestor, 1, 2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(merged, b"v1\nc\nd\ne\n");
+    }
+
+    #[test]
+    fn test_last_writer_wins_resolver() {
+        let resolve = |this_timestamp, other_timestamp| {
+            let input = merge_input(b"this", b"other", b"", this_timestamp, other_timestamp);
+            resolver::last_writer_wins(&input).unwrap().unwrap()
+        };
+        assert_eq!(resolve(1, 2), b"other");
+        assert_eq!(resolve(2, 1), b"this");
+        assert_eq!(resolve(1, 1), b"this");
+
+        let mut input = merge_input(b"this", b"other", b"", 1, 2);
+        input.other = None;
+        assert_eq!(resolver::last_writer_wins(&input).unwrap(), None);
+    }
+
     quickcheck! {
         fn test_random_round_trips(map: BTreeMap<String, (Vec<u8>, Vec<u8>)>) -> bool {
             test_round_trips(map);
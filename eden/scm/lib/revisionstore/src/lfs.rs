@@ -6,11 +6,11 @@
  */
 
 use std::{
-    cmp::min,
+    cmp::{max, min},
     collections::HashMap,
     convert::TryInto,
-    fs::File,
-    io::{ErrorKind, Read, Write},
+    fs::{metadata, remove_file, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
     iter,
     ops::Range,
     path::{Path, PathBuf},
@@ -22,11 +22,15 @@ use std::{
 
 use anyhow::{bail, ensure, Result};
 use bytes::{Bytes, BytesMut};
-use futures::stream::{iter, StreamExt};
+use futures::{
+    future::Future,
+    stream::{iter, StreamExt},
+};
 use parking_lot::{Mutex, RwLock};
 use rand::{thread_rng, Rng};
 use reqwest::{Client, Method, RequestBuilder, Url};
 use serde_derive::{Deserialize, Serialize};
+use sha2::Digest;
 use tokio::{runtime::Runtime, task::spawn_blocking};
 
 use configparser::{
@@ -56,6 +60,10 @@ use crate::{
     util::{get_lfs_blobs_path, get_lfs_objects_path, get_lfs_pointers_path, get_str_config},
 };
 
+/// Downloaded blobs are written to the store in pieces of this size as they are received, so that
+/// an interrupted download can be resumed without fetching again what was written already.
+const DOWNLOAD_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
 /// The `LfsPointersStore` holds the mapping between a `HgId` and the content hash (sha256) of the LFS blob.
 struct LfsPointersStore(Store);
 
@@ -223,6 +231,15 @@ struct LfsIndexedLogBlobsEntry {
     data: Bytes,
 }
 
+/// The fields of an `LfsIndexedLogBlobsEntry` that come before its data, to find out which part
+/// of a blob an entry holds without copying the data.
+#[derive(Deserialize)]
+struct LfsIndexedLogBlobsEntryRange {
+    #[allow(dead_code)]
+    sha256: Sha256,
+    range: Range<usize>,
+}
+
 impl LfsIndexedLogBlobsStore {
     fn chunk_size(config: &ConfigSet) -> Result<usize> {
         Ok(config
@@ -253,7 +270,9 @@ impl LfsIndexedLogBlobsStore {
         })
     }
 
-    pub fn get(&self, hash: &Sha256) -> Result<Option<Bytes>> {
+    /// Read the beginning of a blob, up to its first missing chunk. For a fully written blob,
+    /// this is the whole blob.
+    pub fn get_partial(&self, hash: &Sha256) -> Result<Bytes> {
         let store = self.inner.read();
         let chunks_iter = store
             .lookup(0, hash)?
@@ -267,23 +286,18 @@ impl LfsIndexedLogBlobsStore {
             .collect::<Result<Vec<LfsIndexedLogBlobsEntry>>>()?;
         drop(store);
 
-        if chunks.is_empty() {
-            return Ok(None);
-        }
-
-        // Make sure that the ranges are sorted in increasing order.
-        chunks.sort_unstable_by(|a, b| a.range.start.cmp(&b.range.start));
+        // Make sure that the ranges are sorted in increasing order. Entries are looked up newest
+        // first and the sort is stable, so when a blob was written several times (e.g. because
+        // the first download was corrupted), the most recent chunks are used.
+        chunks.sort_by_key(|entry| entry.range.start);
 
-        // unwrap safety: chunks isn't empty.
-        let size = chunks.last().unwrap().range.end;
-
-        let mut res = BytesMut::with_capacity(size);
+        let mut res = BytesMut::new();
 
         let mut next_start = 0;
         for entry in chunks.into_iter() {
             // A chunk is missing.
             if entry.range.start > next_start {
-                return Ok(None);
+                break;
             }
 
             // This chunk is fully contained in the previous ones.
@@ -306,18 +320,47 @@ impl LfsIndexedLogBlobsStore {
             res.extend_from_slice(entry.data.slice(range_in_data).as_ref());
         }
 
-        let data = res.freeze();
-        if &ContentHash::sha256(&data).unwrap_sha256() != hash {
+        Ok(res.freeze())
+    }
+
+    pub fn get(&self, hash: &Sha256) -> Result<Option<Bytes>> {
+        let data = self.get_partial(hash)?;
+        if data.is_empty() || &ContentHash::sha256(&data).unwrap_sha256() != hash {
             Ok(None)
         } else {
             Ok(Some(data))
         }
     }
 
-    /// Test whether a blob is in the store. It returns true if at least one chunk is present, and
-    /// thus it is possible that one of the chunk is missing.
-    pub fn contains(&self, hash: &Sha256) -> Result<bool> {
-        Ok(self.inner.read().lookup(0, hash)?.next().is_some())
+    /// Test whether all of a blob of `size` bytes is in the store: its chunks must cover it
+    /// without gaps. A download that was interrupted leaves only the first chunks of a blob, which
+    /// doesn't count. The data isn't validated.
+    pub fn contains(&self, hash: &Sha256, size: usize) -> Result<bool> {
+        let store = self.inner.read();
+        let mut ranges = store
+            .lookup(0, hash)?
+            .filter_map(|data| {
+                let entry = deserialize::<LfsIndexedLogBlobsEntryRange>(data.ok()?).ok()?;
+                Some(entry.range)
+            })
+            .collect::<Vec<_>>();
+        drop(store);
+
+        if ranges.is_empty() {
+            return Ok(false);
+        }
+
+        ranges.sort_by_key(|range| range.start);
+        let mut next_start = 0;
+        for range in ranges {
+            // A chunk is missing.
+            if range.start > next_start {
+                break;
+            }
+            next_start = max(next_start, range.end);
+        }
+
+        Ok(next_start >= size)
     }
 
     fn chunk(mut data: Bytes, chunk_size: usize) -> impl Iterator<Item = (Range<usize>, Bytes)> {
@@ -334,11 +377,12 @@ impl LfsIndexedLogBlobsStore {
         })
     }
 
-    pub fn add(&self, hash: &Sha256, data: Bytes) -> Result<()> {
+    /// Write part of a blob, starting at `offset`.
+    pub fn add_partial(&self, hash: &Sha256, offset: usize, data: Bytes) -> Result<()> {
         let chunks = LfsIndexedLogBlobsStore::chunk(data, self.chunk_size);
         let chunks = chunks.map(|(range, data)| LfsIndexedLogBlobsEntry {
             sha256: hash.clone(),
-            range,
+            range: offset + range.start..offset + range.end,
             data,
         });
 
@@ -350,6 +394,10 @@ impl LfsIndexedLogBlobsStore {
         Ok(())
     }
 
+    pub fn add(&self, hash: &Sha256, data: Bytes) -> Result<()> {
+        self.add_partial(hash, 0, data)
+    }
+
     pub fn flush(&self) -> Result<()> {
         self.inner.write().flush()
     }
@@ -435,13 +483,54 @@ impl LfsBlobsStore {
         Ok(blob)
     }
 
-    /// Test whether the blob store contains the hash.
-    pub fn contains(&self, hash: &Sha256) -> Result<bool> {
+    /// Read what was written of a blob whose download was interrupted, so that the download can
+    /// be resumed. For a fully written blob, this is the whole blob.
+    ///
+    /// The data isn't validated, as that can only be done once the whole blob is known.
+    pub fn get_partial(&self, hash: &Sha256) -> Result<Bytes> {
+        match self {
+            LfsBlobsStore::Loose(path, _) => {
+                let path = LfsBlobsStore::path(path, hash);
+                let mut file = match File::open(path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        if e.kind() == ErrorKind::NotFound {
+                            return Ok(Bytes::new());
+                        } else {
+                            return Err(e.into());
+                        }
+                    }
+                };
+
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf)?;
+                Ok(Bytes::from(buf))
+            }
+
+            LfsBlobsStore::IndexedLog(log) => log.get_partial(hash),
+
+            LfsBlobsStore::Union(first, _) => first.get_partial(hash),
+        }
+    }
+
+    /// Test whether the blob store contains all of the blob, so that it doesn't need fetching.
+    /// The blob is checked against its `size`, as a blob whose download was interrupted is only
+    /// partly there. If the size isn't known, the blob is read and validated instead.
+    pub fn contains(&self, hash: &Sha256, size: Option<usize>) -> Result<bool> {
+        let size = match size {
+            Some(size) => size,
+            None => return Ok(self.get(hash)?.is_some()),
+        };
+
         match self {
-            LfsBlobsStore::Loose(path, _) => Ok(LfsBlobsStore::path(&path, hash).is_file()),
-            LfsBlobsStore::IndexedLog(log) => log.contains(hash),
+            LfsBlobsStore::Loose(path, _) => match metadata(LfsBlobsStore::path(path, hash)) {
+                Ok(metadata) => Ok(metadata.is_file() && metadata.len() == size as u64),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(e.into()),
+            },
+            LfsBlobsStore::IndexedLog(log) => log.contains(hash, size),
             LfsBlobsStore::Union(first, second) => {
-                Ok(first.contains(hash)? || second.contains(hash)?)
+                Ok(first.contains(hash, Some(size))? || second.contains(hash, Some(size))?)
             }
         }
     }
@@ -475,6 +564,62 @@ impl LfsBlobsStore {
         Ok(())
     }
 
+    /// Write part of a blob, starting at `offset`. Blobs can be written this way as they are
+    /// received, in order: writing at offset 0 starts the blob over.
+    pub fn add_partial(&self, hash: &Sha256, offset: usize, data: Bytes) -> Result<()> {
+        match self {
+            LfsBlobsStore::Loose(path, is_local) => {
+                let path = LfsBlobsStore::path(path, hash);
+                let parent_path = path.parent().unwrap();
+
+                if *is_local {
+                    create_dir(parent_path)?;
+                } else {
+                    create_shared_dir(parent_path)?;
+                }
+
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path)?;
+                file.set_len(offset as u64)?;
+                file.seek(SeekFrom::Start(offset as u64))?;
+                file.write_all(&data)?;
+
+                if *is_local {
+                    file.sync_all()?;
+                }
+            }
+
+            LfsBlobsStore::IndexedLog(log) => log.add_partial(hash, offset, data)?,
+
+            LfsBlobsStore::Union(first, _) => first.add_partial(hash, offset, data)?,
+        }
+
+        Ok(())
+    }
+
+    /// Throw away what was written of a blob whose download can't be resumed, because what was
+    /// written turned out to be corrupted. Chunks can't be removed from an `IndexedLog`: there,
+    /// they are superseded as the blob is written again from the start.
+    pub fn discard_partial(&self, hash: &Sha256) -> Result<()> {
+        match self {
+            LfsBlobsStore::Loose(path, _) => {
+                let path = LfsBlobsStore::path(path, hash);
+                match remove_file(path) {
+                    Ok(()) => Ok(()),
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                    Err(e) => Err(e.into()),
+                }
+            }
+
+            LfsBlobsStore::IndexedLog(_) => Ok(()),
+
+            LfsBlobsStore::Union(first, _) => first.discard_partial(hash),
+        }
+    }
+
     pub fn flush(&self) -> Result<()> {
         match self {
             LfsBlobsStore::IndexedLog(log) => log.flush(),
@@ -510,6 +655,12 @@ impl LfsStore {
         LfsStore::new(pointers, blobs)
     }
 
+    /// The size of a blob, from its pointer.
+    fn size(&self, key: &StoreKey) -> Option<usize> {
+        let pointer = self.pointers.read().entry(key).ok()??;
+        pointer.size.try_into().ok()
+    }
+
     fn blob_impl(&self, key: &StoreKey) -> Result<Option<(LfsPointersEntry, Bytes)>> {
         let pointer = self.pointers.read().entry(key)?;
 
@@ -540,7 +691,8 @@ impl LocalStore for LfsStore {
                             None => None,
                             Some(content_hash) => {
                                 let sha256 = content_hash.clone().unwrap_sha256();
-                                match self.blobs.contains(&sha256) {
+                                let size = entry.size.try_into().ok();
+                                match self.blobs.contains(&sha256, size) {
                                     Ok(true) => None,
                                     Ok(false) | Err(_) => Some(StoreKey::Content(
                                         content_hash.clone(),
@@ -552,7 +704,7 @@ impl LocalStore for LfsStore {
                     }
                 }
                 StoreKey::Content(content_hash, key) => match content_hash {
-                    ContentHash::Sha256(hash) => match self.blobs.contains(&hash) {
+                    ContentHash::Sha256(hash) => match self.blobs.contains(hash, self.size(k)) {
                         Ok(true) => None,
                         Ok(false) | Err(_) => {
                             // WARNING: Hack!
@@ -848,21 +1000,10 @@ impl HgIdMutableDeltaStore for LfsMultiplexer {
 }
 
 impl LfsRemoteInner {
-    fn batch_fetch(
-        &self,
-        objs: &[(Sha256, usize)],
-        write_to_store: impl Fn(Sha256, Bytes) -> Result<()> + Send + Clone + 'static,
-    ) -> Result<()> {
-        let read_from_store = |_sha256| unreachable!();
+    fn batch_fetch(&self, objs: &[(Sha256, usize)], store: Arc<LfsStore>) -> Result<()> {
         match self {
-            LfsRemoteInner::Http(http) => Self::batch_http(
-                http,
-                objs,
-                Operation::Download,
-                read_from_store,
-                write_to_store,
-            ),
-            LfsRemoteInner::File(file) => Self::batch_fetch_file(file, objs, write_to_store),
+            LfsRemoteInner::Http(http) => Self::batch_http_download(http, objs, store),
+            LfsRemoteInner::File(file) => Self::batch_fetch_file(file, objs, &store),
         }
     }
 
@@ -871,19 +1012,37 @@ impl LfsRemoteInner {
         objs: &[(Sha256, usize)],
         read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + Clone + 'static,
     ) -> Result<()> {
-        let write_to_store = |_, _| unreachable!();
         match self {
-            LfsRemoteInner::Http(http) => Self::batch_http(
-                http,
-                objs,
-                Operation::Upload,
-                read_from_store,
-                write_to_store,
-            ),
+            LfsRemoteInner::Http(http) => Self::batch_http_upload(http, objs, read_from_store),
             LfsRemoteInner::File(file) => Self::batch_upload_file(file, objs, read_from_store),
         }
     }
 
+    fn request(
+        client: &Client,
+        method: Method,
+        url: Url,
+        user_agent: &str,
+        add_extra: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> RequestBuilder {
+        let req = client
+            .request(method, url)
+            .header("Accept", "application/vnd.git-lfs+json")
+            .header("Content-Type", "application/vnd.git-lfs+json")
+            .header("User-Agent", user_agent);
+        add_extra(req)
+    }
+
+    async fn backoff(backoff_time: f32) -> Result<()> {
+        spawn_blocking(move || {
+            let mut rng = thread_rng();
+            let sleep_time = Duration::from_secs_f32(rng.gen_range(0.0, backoff_time));
+            sleep(sleep_time)
+        })
+        .await?;
+        Ok(())
+    }
+
     async fn send_with_retry(
         client: Client,
         method: Method,
@@ -895,12 +1054,13 @@ impl LfsRemoteInner {
         let mut backoff = backoff_times.into_iter();
 
         loop {
-            let req = client
-                .request(method.clone(), url.clone())
-                .header("Accept", "application/vnd.git-lfs+json")
-                .header("Content-Type", "application/vnd.git-lfs+json")
-                .header("User-Agent", &user_agent);
-            let req = add_extra(req);
+            let req = Self::request(
+                &client,
+                method.clone(),
+                url.clone(),
+                &user_agent,
+                &add_extra,
+            );
 
             let reply = req.send().await?.error_for_status();
 
@@ -923,12 +1083,7 @@ impl LfsRemoteInner {
                 }
 
                 if let Some(backoff_time) = backoff.next() {
-                    spawn_blocking(move || {
-                        let mut rng = thread_rng();
-                        let sleep_time = Duration::from_secs_f32(rng.gen_range(0.0, backoff_time));
-                        sleep(sleep_time)
-                    })
-                    .await?;
+                    Self::backoff(backoff_time).await?;
                     continue;
                 }
             }
@@ -980,41 +1135,34 @@ impl LfsRemoteInner {
         Ok(Some(serde_json::from_slice(response.as_ref())?))
     }
 
-    async fn process_action(
+    fn add_action_headers(mut builder: RequestBuilder, action: &ObjectAction) -> RequestBuilder {
+        if let Some(header) = action.header.as_ref() {
+            for (key, val) in header {
+                builder = builder.header(key, val)
+            }
+        }
+        builder
+    }
+
+    async fn upload(
         client: Client,
         user_agent: String,
         backoff_times: Vec<f32>,
-        op: Operation,
         action: ObjectAction,
         oid: Sha256,
         read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + 'static,
-        write_to_store: impl Fn(Sha256, Bytes) -> Result<()> + Send + 'static,
     ) -> Result<()> {
-        let body = if op == Operation::Upload {
-            spawn_blocking(move || read_from_store(oid)).await??
-        } else {
-            None
-        };
-
-        let method = match op {
-            Operation::Download => Method::GET,
-            Operation::Upload => Method::PUT,
-        };
+        let body = spawn_blocking(move || read_from_store(oid)).await??;
 
         let url = Url::from_str(&action.href.to_string())?;
-        let data = LfsRemoteInner::send_with_retry(
+        LfsRemoteInner::send_with_retry(
             client,
-            method,
+            Method::PUT,
             url,
             user_agent,
             backoff_times,
-            move |mut builder| {
-                if let Some(header) = action.header.as_ref() {
-                    for (key, val) in header {
-                        builder = builder.header(key, val)
-                    }
-                }
-
+            move |builder| {
+                let builder = LfsRemoteInner::add_action_headers(builder, &action);
                 if let Some(body) = body.clone() {
                     builder.body(body)
                 } else {
@@ -1024,35 +1172,162 @@ impl LfsRemoteInner {
         )
         .await?;
 
-        if op == Operation::Download {
-            if let Some(data) = data {
-                spawn_blocking(move || write_to_store(oid, data)).await??
+        Ok(())
+    }
+
+    /// Write part of a downloaded blob to the store.
+    async fn write_partial(
+        store: &Arc<LfsStore>,
+        oid: Sha256,
+        offset: usize,
+        data: Bytes,
+    ) -> Result<()> {
+        let store = store.clone();
+        spawn_blocking(move || store.blobs.add_partial(&oid, offset, data)).await?
+    }
+
+    /// Stream the response to a download request into the store, starting at `offset`. Returns
+    /// the hash of the whole blob once it has all been received. Data is written in pieces of
+    /// `DOWNLOAD_BUFFER_SIZE` as it comes in: if the download is interrupted, what was received so
+    /// far is kept so that the download can be resumed.
+    async fn download_from(
+        req: RequestBuilder,
+        oid: Sha256,
+        mut offset: usize,
+        mut hasher: sha2::Sha256,
+        store: &Arc<LfsStore>,
+    ) -> Result<Sha256> {
+        let mut response = req.send().await?.error_for_status()?;
+
+        if offset > 0 && response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            // The server is sending us the whole blob rather than what we asked for: start over.
+            offset = 0;
+            hasher = sha2::Sha256::new();
+        }
+
+        let mut buf = BytesMut::new();
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    // Keep what we received so far for when the download is resumed.
+                    LfsRemoteInner::write_partial(store, oid, offset, buf.freeze()).await?;
+                    return Err(e.into());
+                }
+            };
+
+            hasher.input(&chunk);
+            buf.extend_from_slice(&chunk);
+
+            if buf.len() >= DOWNLOAD_BUFFER_SIZE {
+                let data = buf.split().freeze();
+                let len = data.len();
+                LfsRemoteInner::write_partial(store, oid, offset, data).await?;
+                offset += len;
             }
         }
 
-        Ok(())
+        if !buf.is_empty() || offset == 0 {
+            LfsRemoteInner::write_partial(store, oid, offset, buf.freeze()).await?;
+        }
+
+        let bytes: [u8; Sha256::len()] = hasher.result().into();
+        Ok(Sha256::from(bytes))
     }
 
-    /// Fetch and Upload blobs from the LFS server.
-    ///
-    /// When uploading, the `write_to_store` is guaranteed not to be called, similarly when fetching,
-    /// the `read_from_store` will not be called.
+    /// Download a blob into the store, verifying its hash as it is received. If the store has
+    /// the beginning of the blob already, because a previous download was interrupted, only the
+    /// rest of it is requested. If the resumed blob doesn't match its hash, what the store had
+    /// was corrupted: it is discarded and the whole blob downloaded again.
+    async fn download(
+        client: Client,
+        user_agent: String,
+        backoff_times: Vec<f32>,
+        action: ObjectAction,
+        oid: Sha256,
+        size: usize,
+        store: Arc<LfsStore>,
+    ) -> Result<()> {
+        let url = Url::from_str(&action.href.to_string())?;
+        let mut backoff = backoff_times.into_iter();
+        let mut restarted = false;
+
+        loop {
+            let partial = {
+                let store = store.clone();
+                spawn_blocking(move || store.blobs.get_partial(&oid)).await??
+            };
+
+            // If the whole blob is there already, it must be corrupted, or we wouldn't be
+            // downloading it: start over. Once we started over, what the store has might still
+            // include stale chunks, so don't resume from it again.
+            let mut hasher = sha2::Sha256::new();
+            let offset = if !restarted && partial.len() < size {
+                hasher.input(&partial);
+                partial.len()
+            } else {
+                0
+            };
+
+            let req = Self::request(&client, Method::GET, url.clone(), &user_agent, |builder| {
+                let builder = LfsRemoteInner::add_action_headers(builder, &action);
+                if offset > 0 {
+                    builder.header("Range", format!("bytes={}-", offset))
+                } else {
+                    builder
+                }
+            });
+
+            let err = match Self::download_from(req, oid, offset, hasher, &store).await {
+                Ok(hash) if hash == oid => return Ok(()),
+                Ok(_) if offset > 0 && !restarted => {
+                    restarted = true;
+                    let store = store.clone();
+                    spawn_blocking(move || store.blobs.discard_partial(&oid)).await??;
+                    continue;
+                }
+                Ok(hash) => bail!("Downloaded blob for oid {} has sha256 {}", oid, hash),
+                Err(err) => err,
+            };
+
+            // Retry on server and connection errors, resuming from what we received already.
+            let retry = match err.downcast_ref::<reqwest::Error>().map(|e| e.status()) {
+                Some(Some(status)) if status == reqwest::StatusCode::SERVICE_UNAVAILABLE => {
+                    // No need to retry, the server is down.
+                    return Ok(());
+                }
+                Some(Some(status)) => status.is_server_error(),
+                Some(None) => true,
+                None => false,
+            };
+
+            if retry {
+                if let Some(backoff_time) = backoff.next() {
+                    Self::backoff(backoff_time).await?;
+                    continue;
+                }
+            }
+
+            return Err(err);
+        }
+    }
+
+    /// Ask the LFS server what to do with these blobs.
     ///
     /// The protocol is described at: https://github.com/git-lfs/git-lfs/blob/master/docs/api/batch.md
-    fn batch_http(
+    fn batch_http_actions(
         http: &HttpLfsRemote,
         objs: &[(Sha256, usize)],
         operation: Operation,
-        read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + Clone + 'static,
-        write_to_store: impl Fn(Sha256, Bytes) -> Result<()> + Send + Clone + 'static,
-    ) -> Result<()> {
-        let response = LfsRemoteInner::send_batch_request(http, objs, operation)?;
+    ) -> Result<Vec<(Sha256, usize, ObjectAction)>> {
+        let response = LfsRemoteInner::send_batch_request(http, objs, operation.clone())?;
         let response = match response {
-            None => return Ok(()),
+            None => return Ok(vec![]),
             Some(response) => response,
         };
 
-        let mut futures = Vec::new();
+        let mut res = Vec::new();
 
         for object in response.objects {
             let oid = object.object.oid;
@@ -1064,52 +1339,111 @@ impl LfsRemoteInner {
                 ObjectStatus::Err { error: e } => bail!("Couldn't fetch oid {}: {:?}", oid, e),
             };
 
-            for (op, action) in actions.drain() {
-                let client = http.client.clone();
-                let user_agent = http.user_agent.clone();
-                let backoff_times = http.backoff_times.clone();
-
-                let oid = Sha256::from(oid.0);
-                let read_from_store = read_from_store.clone();
-                let write_to_store = write_to_store.clone();
-                let fut = async move {
-                    LfsRemoteInner::process_action(
-                        client,
-                        user_agent,
-                        backoff_times,
-                        op,
-                        action,
-                        oid,
-                        read_from_store,
-                        write_to_store,
-                    )
-                };
-
-                futures.push(fut);
+            if let Some(action) = actions.remove(&operation) {
+                res.push((Sha256::from(oid.0), object.object.size.try_into()?, action));
             }
         }
 
-        // Request a couple of blobs concurrently.
-        let mut stream = iter(futures).buffer_unordered(http.concurrent_fetches);
+        Ok(res)
+    }
+
+    /// Run the transfers, `concurrent_fetches` at a time.
+    fn run_transfers(
+        http: &HttpLfsRemote,
+        transfers: Vec<impl Future<Output = Result<()>>>,
+    ) -> Result<()> {
+        let mut stream = iter(transfers).buffer_unordered(http.concurrent_fetches);
         http.rt.lock().block_on(async {
             while let Some(next) = stream.next().await {
-                next.await?
+                next?
             }
 
             Ok(())
         })
     }
 
-    /// Fetch files from the filesystem.
+    /// Download blobs from the LFS server into the store.
+    fn batch_http_download(
+        http: &HttpLfsRemote,
+        objs: &[(Sha256, usize)],
+        store: Arc<LfsStore>,
+    ) -> Result<()> {
+        let actions = LfsRemoteInner::batch_http_actions(http, objs, Operation::Download)?;
+
+        let downloads = actions
+            .into_iter()
+            .map(|(oid, size, action)| {
+                LfsRemoteInner::download(
+                    http.client.clone(),
+                    http.user_agent.clone(),
+                    http.backoff_times.clone(),
+                    action,
+                    oid,
+                    size,
+                    store.clone(),
+                )
+            })
+            .collect();
+
+        LfsRemoteInner::run_transfers(http, downloads)
+    }
+
+    /// Upload blobs to the LFS server.
+    fn batch_http_upload(
+        http: &HttpLfsRemote,
+        objs: &[(Sha256, usize)],
+        read_from_store: impl Fn(Sha256) -> Result<Option<Bytes>> + Send + Clone + 'static,
+    ) -> Result<()> {
+        let actions = LfsRemoteInner::batch_http_actions(http, objs, Operation::Upload)?;
+
+        let uploads = actions
+            .into_iter()
+            .map(|(oid, _, action)| {
+                LfsRemoteInner::upload(
+                    http.client.clone(),
+                    http.user_agent.clone(),
+                    http.backoff_times.clone(),
+                    action,
+                    oid,
+                    read_from_store.clone(),
+                )
+            })
+            .collect();
+
+        LfsRemoteInner::run_transfers(http, uploads)
+    }
+
+    /// Fetch files from the filesystem. Like downloads, fetches resume from what the store has of
+    /// a blob already, and start over if that turns out to be corrupted.
     fn batch_fetch_file(
         file: &LfsBlobsStore,
         objs: &[(Sha256, usize)],
-        write_to_store: impl Fn(Sha256, Bytes) -> Result<()>,
+        store: &LfsStore,
     ) -> Result<()> {
-        for (hash, _) in objs {
-            if let Some(data) = file.get(hash)? {
-                write_to_store(*hash, data)?;
+        for (hash, size) in objs {
+            let data = match file.get(hash)? {
+                Some(data) => data,
+                None => continue,
+            };
+
+            let partial = store.blobs.get_partial(hash)?;
+            let offset = partial.len();
+            if offset > 0 && offset < *size && offset < data.len() {
+                let mut hasher = sha2::Sha256::new();
+                hasher.input(&partial);
+                hasher.input(&data[offset..]);
+                let bytes: [u8; Sha256::len()] = hasher.result().into();
+                if &Sha256::from(bytes) == hash {
+                    store
+                        .blobs
+                        .add_partial(hash, offset, data.slice(offset..))?;
+                    continue;
+                }
+
+                store.blobs.discard_partial(hash)?;
             }
+
+            store.blobs.add(hash, data)?;
         }
 
         Ok(())
@@ -1161,7 +1495,7 @@ impl LfsRemote {
                 "mercurial/revisionstore".to_string()
             })?;
 
-            let concurrent_fetches = config.get_or("lfs", "concurrentfetches", || 1)?;
+            let concurrent_fetches = config.get_or("lfs", "concurrentfetches", || 4)?;
 
             let backoff_times = config.get_or("lfs", "backofftimes", || vec![1f32, 4f32, 8f32])?;
 
@@ -1182,12 +1516,8 @@ impl LfsRemote {
         }
     }
 
-    fn batch_fetch(
-        &self,
-        objs: &[(Sha256, usize)],
-        write_to_store: impl Fn(Sha256, Bytes) -> Result<()> + Send + Clone + 'static,
-    ) -> Result<()> {
-        self.remote.batch_fetch(objs, write_to_store)
+    fn batch_fetch(&self, objs: &[(Sha256, usize)], store: Arc<LfsStore>) -> Result<()> {
+        self.remote.batch_fetch(objs, store)
     }
 
     fn batch_upload(
@@ -1243,10 +1573,7 @@ impl RemoteDataStore for LfsRemoteStore {
             .filter_map(|res| res.transpose())
             .collect::<Result<Vec<_>>>()?;
 
-        self.remote.batch_fetch(&objs, {
-            let remote = self.remote.clone();
-            move |sha256, data| remote.shared.blobs.add(&sha256, data)
-        })
+        self.remote.batch_fetch(&objs, self.remote.shared.clone())
     }
 
     fn upload(&self, keys: &[StoreKey]) -> Result<Vec<StoreKey>> {
@@ -1373,7 +1700,7 @@ mod tests {
         let indexedlog_blobs = LfsIndexedLogBlobsStore::shared(&dir.path(), &config)?;
         let hash = ContentHash::sha256(&delta.data).unwrap_sha256();
 
-        assert!(indexedlog_blobs.contains(&hash)?);
+        assert!(indexedlog_blobs.contains(&hash, delta.data.len())?);
 
         assert_eq!(Some(delta.data), indexedlog_blobs.get(&hash)?);
 
@@ -1391,7 +1718,7 @@ mod tests {
         let sha256 = ContentHash::sha256(&data).unwrap_sha256();
         loose_store.add(&sha256, data.clone())?;
 
-        assert!(blob_store.contains(&sha256)?);
+        assert!(blob_store.contains(&sha256, Some(data.len()))?);
         assert_eq!(blob_store.get(&sha256)?, Some(data));
 
         Ok(())
@@ -1492,6 +1819,94 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_add_partial() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut config = make_lfs_config(&dir);
+        config.set("lfs", "blobschunksize", Some("2"), &Default::default());
+
+        let store = LfsIndexedLogBlobsStore::shared(dir.path(), &config)?;
+
+        let data = Bytes::from(&[1, 2, 3, 4, 5, 6, 7][..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+
+        store.add_partial(&sha256, 0, data.slice(..3))?;
+        assert_eq!(store.get_partial(&sha256)?, data.slice(..3));
+        assert_eq!(store.get(&sha256)?, None);
+        assert!(!store.contains(&sha256, data.len())?);
+
+        store.add_partial(&sha256, 3, data.slice(3..))?;
+        assert_eq!(store.get_partial(&sha256)?, data);
+        assert!(store.contains(&sha256, data.len())?);
+        assert_eq!(store.get(&sha256)?, Some(data));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_with_gap() -> Result<()> {
+        let dir = TempDir::new()?;
+        let config = make_lfs_config(&dir);
+
+        let store = LfsIndexedLogBlobsStore::shared(dir.path(), &config)?;
+
+        let data = Bytes::from(&[1, 2, 3, 4, 5, 6, 7][..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+
+        store.add_partial(&sha256, 0, data.slice(..2))?;
+        store.add_partial(&sha256, 4, data.slice(4..))?;
+
+        assert_eq!(store.get_partial(&sha256)?, data.slice(..2));
+        assert_eq!(store.get(&sha256)?, None);
+        assert!(!store.contains(&sha256, data.len())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewritten_blob() -> Result<()> {
+        let dir = TempDir::new()?;
+        let config = make_lfs_config(&dir);
+
+        let store = LfsIndexedLogBlobsStore::shared(dir.path(), &config)?;
+
+        let data = Bytes::from(&[1, 2, 3, 4][..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+
+        // A corrupted blob is written over by the next download.
+        store.add(&sha256, Bytes::from(&[4, 3, 2, 1][..]))?;
+        assert_eq!(store.get(&sha256)?, None);
+
+        store.add(&sha256, data.clone())?;
+        assert_eq!(store.get(&sha256)?, Some(data));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loose_add_partial() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = LfsBlobsStore::local(dir.path())?;
+
+        let data = Bytes::from(&[1, 2, 3, 4, 5][..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+
+        assert_eq!(store.get_partial(&sha256)?, Bytes::new());
+
+        store.add_partial(&sha256, 0, Bytes::from(&[1, 2, 9][..]))?;
+        assert_eq!(store.get(&sha256)?, None);
+        assert!(!store.contains(&sha256, Some(data.len()))?);
+        assert!(!store.contains(&sha256, None)?);
+
+        // Resuming overwrites anything past the offset.
+        store.add_partial(&sha256, 2, data.slice(2..))?;
+        assert_eq!(store.get_partial(&sha256)?, data);
+        assert!(store.contains(&sha256, Some(data.len()))?);
+        assert_eq!(store.get(&sha256)?, Some(data));
+
+        Ok(())
+    }
+
     #[test]
     fn test_full_chunked() -> Result<()> {
         let dir = TempDir::new()?;
@@ -1867,7 +2282,7 @@ mod tests {
             let config = make_lfs_config(&cachedir);
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config)?;
+            let remote = LfsRemote::new(lfs.clone(), None, &config)?;
 
             let blob = (
                 Sha256::from_str(
@@ -1877,7 +2292,7 @@ mod tests {
                 Bytes::from(&b"nothing"[..]),
             );
 
-            let resp = remote.batch_fetch(&[(blob.0, blob.1)], |_, _| unreachable!());
+            let resp = remote.batch_fetch(&[(blob.0, blob.1)], lfs);
             let err = resp.err().unwrap();
             assert_eq!(err.to_string(), "Couldn't fetch oid 0000000000000000000000000000000000000000000000000000000000000000: ObjectError { code: 404, message: \"Object does not exist\" }");
 
@@ -1891,7 +2306,7 @@ mod tests {
             let config = make_lfs_config(&cachedir);
 
             let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
-            let remote = LfsRemote::new(lfs, None, &config)?;
+            let remote = LfsRemote::new(lfs.clone(), None, &config)?;
 
             let blob1 = (
                 Sha256::from_str(
@@ -1908,20 +2323,37 @@ mod tests {
                 Bytes::from(&b"1.44.0"[..]),
             );
 
-            let out = Arc::new(Mutex::new(Vec::new()));
-            remote.batch_fetch(&[(blob1.0, blob1.1), (blob2.0, blob2.1)], {
-                let out = out.clone();
-                move |sha256, blob| {
-                    out.lock().push((sha256, blob));
-                    Ok(())
-                }
-            })?;
-            out.lock().sort();
+            remote.batch_fetch(&[(blob1.0, blob1.1), (blob2.0, blob2.1)], lfs.clone())?;
+
+            assert_eq!(lfs.blobs.get(&blob1.0)?, Some(blob1.2));
+            assert_eq!(lfs.blobs.get(&blob2.0)?, Some(blob2.2));
+
+            Ok(())
+        }
+
+        #[test]
+        fn test_lfs_remote_resume() -> Result<()> {
+            let cachedir = TempDir::new()?;
+            let lfsdir = TempDir::new()?;
+            let config = make_lfs_config(&cachedir);
 
-            let mut expected_res = vec![(blob1.0, blob1.2), (blob2.0, blob2.2)];
-            expected_res.sort();
+            let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
+            let remote = LfsRemote::new(lfs.clone(), None, &config)?;
 
-            assert_eq!(*out.lock(), expected_res);
+            let blob = (
+                Sha256::from_str(
+                    "fc613b4dfd6736a7bd268c8a0e74ed0d1c04a959f59dd74ef2874983fd443fc9",
+                )?,
+                6,
+                Bytes::from(&b"master"[..]),
+            );
+
+            // Pretend a previous download was interrupted after a few bytes.
+            lfs.blobs.add_partial(&blob.0, 0, blob.2.slice(..2))?;
+            assert_eq!(lfs.blobs.get(&blob.0)?, None);
+
+            remote.batch_fetch(&[(blob.0, blob.1)], lfs.clone())?;
+            assert_eq!(lfs.blobs.get(&blob.0)?, Some(blob.2));
 
             Ok(())
         }
@@ -2000,22 +2432,132 @@ mod tests {
         let url = Url::from_file_path(&remote).unwrap();
         config.set("lfs", "url", Some(url.as_str()), &Default::default());
 
-        let remote = LfsRemote::new(lfs, None, &config)?;
+        let remote = LfsRemote::new(lfs.clone(), None, &config)?;
 
-        let out = Arc::new(Mutex::new(Vec::new()));
-        remote.batch_fetch(&[(blob1.0, blob1.1), (blob2.0, blob2.1)], {
-            let out = out.clone();
-            move |sha256, blob| {
-                out.lock().push((sha256, blob));
-                Ok(())
-            }
+        remote.batch_fetch(&[(blob1.0, blob1.1), (blob2.0, blob2.1)], lfs.clone())?;
+
+        assert_eq!(lfs.blobs.get(&blob1.0)?, Some(blob1.2));
+        assert_eq!(lfs.blobs.get(&blob2.0)?, Some(blob2.2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lfs_remote_file_resume() -> Result<()> {
+        let cachedir = TempDir::new()?;
+        let mut config = make_lfs_config(&cachedir);
+
+        let lfsdir = TempDir::new()?;
+        let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
+
+        let remote = TempDir::new()?;
+        let remote_lfs_file_store = LfsBlobsStore::Loose(remote.path().to_path_buf(), false);
+
+        let blob1 = (
+            Sha256::from_str("fc613b4dfd6736a7bd268c8a0e74ed0d1c04a959f59dd74ef2874983fd443fc9")?,
+            6,
+            Bytes::from(&b"master"[..]),
+        );
+        let blob2 = (
+            Sha256::from_str("ca3e228a1d8d845064112c4e92781f6b8fc2501f0aa0e415d4a1dcc941485b24")?,
+            6,
+            Bytes::from(&b"1.44.0"[..]),
+        );
+
+        remote_lfs_file_store.add(&blob1.0, blob1.2.clone())?;
+        remote_lfs_file_store.add(&blob2.0, blob2.2.clone())?;
+        remote_lfs_file_store.flush()?;
+
+        // Pretend previous fetches were interrupted after a few bytes, one of them after writing
+        // corrupted data.
+        lfs.blobs.add_partial(&blob1.0, 0, blob1.2.slice(..2))?;
+        lfs.blobs
+            .add_partial(&blob2.0, 0, Bytes::from(&b"2.0"[..]))?;
+        assert_eq!(lfs.blobs.get(&blob1.0)?, None);
+        assert_eq!(lfs.blobs.get(&blob2.0)?, None);
+
+        let url = Url::from_file_path(&remote).unwrap();
+        config.set("lfs", "url", Some(url.as_str()), &Default::default());
+
+        let remote = LfsRemote::new(lfs.clone(), None, &config)?;
+
+        remote.batch_fetch(&[(blob1.0, blob1.1), (blob2.0, blob2.1)], lfs.clone())?;
+
+        assert_eq!(lfs.blobs.get(&blob1.0)?, Some(blob1.2));
+        assert_eq!(lfs.blobs.get(&blob2.0)?, Some(blob2.2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lfs_remote_file_interrupted() -> Result<()> {
+        let cachedir = TempDir::new()?;
+        let mut config = make_lfs_config(&cachedir);
+        config.set("lfs", "blobschunksize", Some("2"), &Default::default());
+
+        let lfsdir = TempDir::new()?;
+        let lfs = Arc::new(LfsStore::shared(&lfsdir, &config)?);
+
+        let remote = TempDir::new()?;
+        let remote_lfs_file_store = LfsBlobsStore::Loose(remote.path().to_path_buf(), false);
+
+        let data = Bytes::from(&b"master"[..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+        remote_lfs_file_store.add(&sha256, data.clone())?;
+
+        let k = key("a", "1");
+        let mut content_hashes = HashMap::new();
+        content_hashes.insert(ContentHashType::Sha256, ContentHash::Sha256(sha256));
+        lfs.pointers.write().add(LfsPointersEntry {
+            hgid: k.hgid.clone(),
+            size: data.len() as u64,
+            is_binary: false,
+            copy_from: None,
+            content_hashes,
         })?;
-        out.lock().sort();
 
-        let mut expected_res = vec![(blob1.0, blob1.2), (blob2.0, blob2.2)];
-        expected_res.sort();
+        // Pretend a previous fetch was interrupted after the first chunks were written.
+        lfs.blobs.add_partial(&sha256, 0, data.slice(..4))?;
+
+        let missing = lfs.get_missing(&[StoreKey::hgid(k.clone())])?;
+        assert_eq!(
+            missing,
+            vec![StoreKey::Content(
+                ContentHash::Sha256(sha256),
+                Some(k.clone())
+            )]
+        );
+        assert_eq!(
+            lfs.get_missing(&[StoreKey::from(ContentHash::Sha256(sha256))])?
+                .len(),
+            1
+        );
+
+        let url = Url::from_file_path(&remote).unwrap();
+        config.set("lfs", "url", Some(url.as_str()), &Default::default());
+        let remote = Arc::new(LfsRemote::new(lfs.clone(), None, &config)?);
+        remote.datastore(lfs.clone()).prefetch(&missing)?;
+
+        assert_eq!(lfs.get_missing(&[StoreKey::hgid(k.clone())])?, vec![]);
+        assert_eq!(lfs.blobs.get(&sha256)?, Some(data));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loose_discard_partial() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = LfsBlobsStore::Loose(dir.path().to_path_buf(), false);
+
+        let data = Bytes::from(&[1, 2, 3, 4][..]);
+        let sha256 = ContentHash::sha256(&data).unwrap_sha256();
+
+        store.add_partial(&sha256, 0, data.slice(..2))?;
+        store.discard_partial(&sha256)?;
+        assert_eq!(store.get_partial(&sha256)?, Bytes::new());
 
-        assert_eq!(*out.lock(), expected_res);
+        // Discarding a blob that isn't there is fine.
+        store.discard_partial(&sha256)?;
 
         Ok(())
     }
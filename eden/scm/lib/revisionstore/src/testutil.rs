@@ -11,8 +11,15 @@ use anyhow::{Error, Result};
 use bytes::Bytes;
 
 use configparser::config::ConfigSet;
-use edenapi::{ApiResult, DownloadStats, EdenApi, ProgressFn};
-use types::{HgId, HistoryEntry, Key, NodeInfo, RepoPathBuf};
+use edenapi::{ApiResult, CloneData, DownloadStats, EdenApi, ProgressFn};
+use types::{
+    api::{
+        AnyId, CommitHashToLocationResponse, CommitLocationToHashRequest,
+        CommitLocationToHashResponse, LookupResponse, PushrebaseResponse, SetBookmarkResponse,
+        UploadHgFilenodeRequest,
+    },
+    DataEntry, HgId, HistoryEntry, Key, NodeInfo, RepoPathBuf, Sha256,
+};
 
 use crate::{
     datastore::{Delta, HgIdDataStore, HgIdMutableDeltaStore, Metadata, RemoteDataStore},
@@ -246,6 +253,90 @@ impl EdenApi for FakeEdenApi {
     ) -> ApiResult<(Box<dyn Iterator<Item = (Key, Bytes)>>, DownloadStats)> {
         unreachable!();
     }
+
+    fn clone_data(&self) -> ApiResult<CloneData<HgId>> {
+        unreachable!();
+    }
+
+    fn commit_location_to_hash(
+        &self,
+        _requests: Vec<CommitLocationToHashRequest>,
+        _progress: Option<ProgressFn>,
+    ) -> ApiResult<(
+        Box<dyn Iterator<Item = CommitLocationToHashResponse>>,
+        DownloadStats,
+    )> {
+        unreachable!();
+    }
+
+    fn commit_hash_to_location(
+        &self,
+        _master_heads: Vec<HgId>,
+        _hgids: Vec<HgId>,
+        _progress: Option<ProgressFn>,
+    ) -> ApiResult<(
+        Box<dyn Iterator<Item = CommitHashToLocationResponse>>,
+        DownloadStats,
+    )> {
+        unreachable!();
+    }
+
+    fn lookup(
+        &self,
+        _ids: Vec<AnyId>,
+        _progress: Option<ProgressFn>,
+    ) -> ApiResult<(Box<dyn Iterator<Item = LookupResponse>>, DownloadStats)> {
+        unreachable!();
+    }
+
+    fn upload_files(
+        &self,
+        _files: Box<dyn Iterator<Item = (Sha256, Bytes)>>,
+        _progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        unreachable!();
+    }
+
+    fn upload_filenodes(
+        &self,
+        _filenodes: Vec<UploadHgFilenodeRequest>,
+        _progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        unreachable!();
+    }
+
+    fn upload_trees(
+        &self,
+        _entries: Vec<DataEntry>,
+        _progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        unreachable!();
+    }
+
+    fn upload_changesets(
+        &self,
+        _entries: Vec<DataEntry>,
+        _progress: Option<ProgressFn>,
+    ) -> ApiResult<DownloadStats> {
+        unreachable!();
+    }
+
+    fn set_bookmark(
+        &self,
+        _bookmark: String,
+        _from: Option<HgId>,
+        _to: Option<HgId>,
+    ) -> ApiResult<SetBookmarkResponse> {
+        unreachable!();
+    }
+
+    fn pushrebase(
+        &self,
+        _bookmark: String,
+        _changesets: Vec<HgId>,
+    ) -> ApiResult<PushrebaseResponse> {
+        unreachable!();
+    }
 }
 
 pub fn fake_edenapi(map: HashMap<Key, Bytes>) -> Arc<dyn EdenApi> {
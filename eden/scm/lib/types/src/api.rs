@@ -14,7 +14,9 @@ use crate::{
     hgid::HgId,
     historyentry::{HistoryEntry, WireHistoryEntry},
     key::Key,
+    parents::Parents,
     path::RepoPathBuf,
+    sha::Sha256,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -111,6 +113,154 @@ impl TreeRequest {
     }
 }
 
+/// A commit given as the `distance`-th first ancestor of `descendant`,
+/// `descendant~distance` in revset syntax.
+///
+/// Used by clients that cloned the commit graph without all the commit
+/// hashes (segmented changelog) to translate between positions in the graph
+/// and hashes.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CommitLocation {
+    pub descendant: HgId,
+    pub distance: u64,
+}
+
+impl CommitLocation {
+    pub fn new(descendant: HgId, distance: u64) -> Self {
+        Self {
+            descendant,
+            distance,
+        }
+    }
+}
+
+/// Request the hashes of the commit at `location` and of its first
+/// ancestors, `count` commits in total.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CommitLocationToHashRequest {
+    pub location: CommitLocation,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommitLocationToHashRequestBatch {
+    pub requests: Vec<CommitLocationToHashRequest>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CommitLocationToHashResponse {
+    pub location: CommitLocation,
+    pub count: u64,
+    pub hgids: Vec<HgId>,
+}
+
+/// Request the locations of `hgids` relative to `master_heads`, which the
+/// client knows the hashes of. Hashes that are not ancestors of
+/// `master_heads` are left out of the response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommitHashToLocationRequestBatch {
+    pub master_heads: Vec<HgId>,
+    pub hgids: Vec<HgId>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CommitHashToLocationResponse {
+    pub hgid: HgId,
+    pub location: CommitLocation,
+}
+
+/// Something a client can upload to the server, named by the hash the
+/// server stores it under.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum AnyId {
+    /// File content, deduplicated across filenodes by its SHA-256.
+    FileContentSha256(Sha256),
+    HgFilenodeId(HgId),
+    HgTreeId(HgId),
+    HgChangesetId(HgId),
+}
+
+/// Ask which of `ids` the server already has, so that a client pushing
+/// commits only uploads what is missing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LookupRequestBatch {
+    pub ids: Vec<AnyId>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LookupResponse {
+    pub id: AnyId,
+    pub present: bool,
+}
+
+/// A Mercurial filenode whose content has already been uploaded by its
+/// SHA-256. `copy_from` is the copy source recorded in the filenode's
+/// metadata header, if any.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UploadHgFilenodeRequest {
+    pub key: Key,
+    pub parents: Parents,
+    pub content_sha256: Sha256,
+    pub copy_from: Option<Key>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadHgFilenodesRequest {
+    pub filenodes: Vec<UploadHgFilenodeRequest>,
+}
+
+/// Tree manifests in Mercurial's format, keyed by directory path and
+/// manifest node.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadTreesRequest {
+    pub entries: Vec<DataEntry>,
+}
+
+/// Mercurial changesets in revlog format, keyed by changeset node with an
+/// empty path. Parents must be either already known to the server or come
+/// earlier in `entries`.
+///
+/// The trees and filenodes the changesets refer to must have been uploaded
+/// beforehand.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadHgChangesetsRequest {
+    pub entries: Vec<DataEntry>,
+}
+
+/// Move `bookmark` from `from` to `to`. A `from` of `None` creates the
+/// bookmark and a `to` of `None` deletes it; the request fails if the
+/// bookmark does not currently point to `from`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SetBookmarkRequest {
+    pub bookmark: String,
+    pub from: Option<HgId>,
+    pub to: Option<HgId>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SetBookmarkResponse {
+    /// Warnings from advisory hooks, which did not block the move.
+    pub hook_warnings: Vec<String>,
+}
+
+/// Rebase the uploaded stack of `changesets` onto `bookmark` and move the
+/// bookmark to the rebased head.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PushrebaseRequest {
+    pub bookmark: String,
+    pub changesets: Vec<HgId>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PushrebaseResponse {
+    /// Where the bookmark points after the pushrebase.
+    pub head: HgId,
+    /// Pairs of (pushed changeset, its rebased counterpart).
+    pub rebased: Vec<(HgId, HgId)>,
+    /// Warnings from advisory hooks, which did not block the push.
+    pub hook_warnings: Vec<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
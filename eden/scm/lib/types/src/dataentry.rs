@@ -73,6 +73,10 @@ impl DataEntry {
         &self.key
     }
 
+    pub fn parents(&self) -> &Parents {
+        &self.parents
+    }
+
     /// Get this entry's data content. This method checks the validity of the
     /// data and return the validation result along with the data iself.
     pub fn data(&self) -> (Bytes, Validity) {